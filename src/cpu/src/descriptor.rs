@@ -0,0 +1,231 @@
+//! Descriptor set layouts, pools and sets.
+//!
+//! Sets hold copies of the views and samplers written to them rather than references, so a set's
+//! contents can be captured whole when it is bound during execution.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+
+use std::sync::Mutex;
+
+use super::{Cpu, Device};
+use memory::{Buffer, BufferView, ImageView};
+use sampler::Sampler;
+
+#[derive(Clone)]
+pub struct LayoutBinding {
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    pub count: u32,
+    pub stages: ShaderStageFlags,
+    immutable_samplers: Vec<Sampler>,
+}
+
+#[derive(Clone)]
+pub struct DescriptorSetLayout {
+    /// Sorted by binding number.
+    bindings: Vec<LayoutBinding>,
+}
+
+impl DescriptorSetLayout {
+    pub fn bindings(&self) -> &[LayoutBinding] { &self.bindings }
+}
+
+impl icd::DescriptorSetLayout<Cpu> for DescriptorSetLayout {
+    fn new(_device: &Device, info: DescriptorSetLayoutCreateInfo<Cpu>) -> Result<Self> {
+        let mut bindings: Vec<LayoutBinding> = info.bindings()
+            .map(|binding| LayoutBinding {
+                binding: binding.binding(),
+                descriptor_type: binding.descriptor_type(),
+                count: binding.descriptor_count(),
+                stages: binding.stage_flags(),
+                immutable_samplers: binding.immutable_samplers().cloned().collect(),
+            })
+            .collect();
+        bindings.sort_by_key(|binding| binding.binding);
+
+        Ok(DescriptorSetLayout { bindings: bindings })
+    }
+}
+
+/// The contents of one array element of a binding.
+#[derive(Clone)]
+pub enum Descriptor {
+    /// Nothing has been written yet.
+    Empty,
+    Sampler(Sampler),
+    /// A sampled, storage or input attachment image, or a combined image sampler.
+    Image {
+        view: ImageView,
+        sampler: Option<Sampler>,
+    },
+    /// A uniform or storage buffer range. Dynamic offsets are already applied to bound sets.
+    Buffer {
+        data: *mut u8,
+        size: u64,
+    },
+    TexelBuffer(BufferView),
+}
+
+unsafe impl Send for Descriptor {}
+unsafe impl Sync for Descriptor {}
+
+#[derive(Clone)]
+pub struct SetBinding {
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    pub descriptors: Vec<Descriptor>,
+}
+
+pub struct DescriptorSet {
+    bindings: Mutex<Vec<SetBinding>>,
+}
+
+impl DescriptorSet {
+    fn new(layout: &DescriptorSetLayout) -> DescriptorSet {
+        let bindings = layout.bindings.iter()
+            .map(|binding| {
+                let descriptors = (0..binding.count as usize)
+                    .map(|i| match binding.immutable_samplers.get(i) {
+                        Some(sampler) if binding.descriptor_type == DescriptorType::Sampler => Descriptor::Sampler(sampler.clone()),
+                        _ => Descriptor::Empty,
+                    })
+                    .collect();
+                SetBinding {
+                    binding: binding.binding,
+                    descriptor_type: binding.descriptor_type,
+                    descriptors: descriptors,
+                }
+            })
+            .collect();
+
+        DescriptorSet { bindings: Mutex::new(bindings) }
+    }
+
+    /// A copy of the set's current contents.
+    pub fn snapshot(&self) -> Vec<SetBinding> {
+        self.bindings.lock().unwrap().clone()
+    }
+
+    /// Stores `descriptors` from `array_element` of `binding` on, continuing into the following
+    /// bindings when they run past the end of one.
+    fn store<It>(&self, binding: u32, array_element: u32, descriptors: It) where
+        It: IntoIterator<Item = Descriptor>
+    {
+        let mut bindings = self.bindings.lock().unwrap();
+        let mut index = match bindings.iter().position(|x| x.binding == binding) {
+            Some(index) => index,
+            None => {
+                error!("descriptor update of binding {}, which the set's layout does not have", binding);
+                return;
+            },
+        };
+        let mut element = array_element as usize;
+
+        for descriptor in descriptors {
+            while index < bindings.len() && element >= bindings[index].descriptors.len() {
+                element -= bindings[index].descriptors.len();
+                index += 1;
+            }
+            let slot = match bindings.get_mut(index) {
+                Some(binding) => &mut binding.descriptors[element],
+                None => {
+                    error!("descriptor update runs past the last binding of the set");
+                    return;
+                },
+            };
+
+            // Combined image samplers with immutable samplers keep them when only a view is written
+            *slot = match (descriptor, &*slot) {
+                (Descriptor::Image { view, sampler: None }, &Descriptor::Image { sampler: Some(ref sampler), .. }) => {
+                    Descriptor::Image { view: view, sampler: Some(sampler.clone()) }
+                },
+                (descriptor, _) => descriptor,
+            };
+            element += 1;
+        }
+    }
+
+    /// Reads `count` descriptors from `array_element` of `binding` on, the same way `store` writes
+    /// them.
+    fn load(&self, binding: u32, array_element: u32, count: u32) -> Vec<Descriptor> {
+        let bindings = self.bindings.lock().unwrap();
+        let mut index = match bindings.iter().position(|x| x.binding == binding) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        let mut element = array_element as usize;
+
+        let mut descriptors = Vec::new();
+        for _ in 0..count {
+            while index < bindings.len() && element >= bindings[index].descriptors.len() {
+                element -= bindings[index].descriptors.len();
+                index += 1;
+            }
+            match bindings.get(index) {
+                Some(binding) => descriptors.push(binding.descriptors[element].clone()),
+                None => break,
+            }
+            element += 1;
+        }
+        descriptors
+    }
+}
+
+impl icd::DescriptorSet<Cpu> for DescriptorSet {
+}
+
+pub(crate) fn update_descriptor_sets(writes: &[DescriptorWrite<Cpu>], copies: &[DescriptorCopy<Cpu>]) {
+    for write in writes {
+        let descriptors: Vec<Descriptor> = match write.descriptors {
+            Descriptors::Images(ref infos) => {
+                infos.iter()
+                    .map(|info| match (info.image_view, info.sampler) {
+                        (Some(view), sampler) => Descriptor::Image {
+                            view: view.clone(),
+                            sampler: sampler.cloned(),
+                        },
+                        (None, Some(sampler)) => Descriptor::Sampler(sampler.clone()),
+                        (None, None) => Descriptor::Empty,
+                    })
+                    .collect()
+            },
+            Descriptors::Buffers(ref infos) => {
+                infos.iter()
+                    .map(|info| buffer_descriptor(info.buffer, info.offset, info.range))
+                    .collect()
+            },
+            Descriptors::TexelBuffers(ref views) => {
+                views.iter().map(|&view| Descriptor::TexelBuffer(view.clone())).collect()
+            },
+        };
+        write.set.store(write.binding, write.array_element, descriptors);
+    }
+
+    for copy in copies {
+        let descriptors = copy.src_set.load(copy.src_binding, copy.src_array_element, copy.descriptor_count);
+        copy.dst_set.store(copy.dst_binding, copy.dst_array_element, descriptors);
+    }
+}
+
+fn buffer_descriptor(buffer: &Buffer, offset: u64, range: u64) -> Descriptor {
+    let size = if range == WHOLE_SIZE { buffer.size().saturating_sub(offset) } else { range };
+    match buffer.range(offset, size) {
+        Some(data) => Descriptor::Buffer { data: data, size: size },
+        None => {
+            error!("buffer descriptor of {} bytes at offset {} runs past the end of a buffer of {} bytes", size, offset, buffer.size());
+            Descriptor::Empty
+        },
+    }
+}
+
+pub struct DescriptorPool {
+}
+
+impl icd::DescriptorPool<Cpu> for DescriptorPool {
+    fn new(_device: &Device, _info: DescriptorPoolCreateInfo<Cpu>) -> Result<Self> { Ok(DescriptorPool {}) }
+
+    fn allocate(&mut self, _device: &Device, layouts: &[&DescriptorSetLayout]) -> Result<Vec<DescriptorSet>> {
+        Ok(layouts.iter().map(|layout| DescriptorSet::new(layout)).collect())
+    }
+}
@@ -0,0 +1,600 @@
+//! Conversion between texels in memory and the four 32-bit components shaders, clears and the
+//! rasterizer work with.
+//!
+//! A `Texel` holds `f32` bits for normalized, scaled and floating-point formats and the integers
+//! themselves for integer formats, the same convention as `VkClearColorValue`. Components a format
+//! lacks read as zero, except alpha which reads as one.
+
+use vk_middle::icd::*;
+
+use std::ptr;
+
+pub type Texel = [u32; 4];
+
+/// The largest texel of a format this module can convert.
+pub const MAX_TEXEL_SIZE: usize = 16;
+
+/// The bytes of one texel, of which only the format's block size are meaningful.
+pub type Packed = [u8; MAX_TEXEL_SIZE];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Numeric {
+    Unorm,
+    Snorm,
+    Uscaled,
+    Sscaled,
+    Uint,
+    Sint,
+    Srgb,
+    Sfloat,
+    Ufloat,
+}
+
+/// Where a color format keeps its components: each `(component, bits)` in order from the least
+/// significant bit of the texel, read as a little-endian number.
+struct Layout {
+    numeric: Numeric,
+    components: &'static [(usize, u32)],
+}
+
+const R8: &'static [(usize, u32)] = &[(0, 8)];
+const RG8: &'static [(usize, u32)] = &[(0, 8), (1, 8)];
+const RGB8: &'static [(usize, u32)] = &[(0, 8), (1, 8), (2, 8)];
+const BGR8: &'static [(usize, u32)] = &[(2, 8), (1, 8), (0, 8)];
+const RGBA8: &'static [(usize, u32)] = &[(0, 8), (1, 8), (2, 8), (3, 8)];
+const BGRA8: &'static [(usize, u32)] = &[(2, 8), (1, 8), (0, 8), (3, 8)];
+const A2R10G10B10: &'static [(usize, u32)] = &[(2, 10), (1, 10), (0, 10), (3, 2)];
+const A2B10G10R10: &'static [(usize, u32)] = &[(0, 10), (1, 10), (2, 10), (3, 2)];
+const R16: &'static [(usize, u32)] = &[(0, 16)];
+const RG16: &'static [(usize, u32)] = &[(0, 16), (1, 16)];
+const RGB16: &'static [(usize, u32)] = &[(0, 16), (1, 16), (2, 16)];
+const RGBA16: &'static [(usize, u32)] = &[(0, 16), (1, 16), (2, 16), (3, 16)];
+const R32: &'static [(usize, u32)] = &[(0, 32)];
+const RG32: &'static [(usize, u32)] = &[(0, 32), (1, 32)];
+const RGB32: &'static [(usize, u32)] = &[(0, 32), (1, 32), (2, 32)];
+const RGBA32: &'static [(usize, u32)] = &[(0, 32), (1, 32), (2, 32), (3, 32)];
+
+fn layout(format: Format) -> Option<Layout> {
+    use self::Numeric::*;
+
+    let (numeric, components): (Numeric, &'static [(usize, u32)]) = match format {
+        Format::R4G4UnormPack8 => (Unorm, &[(1, 4), (0, 4)]),
+        Format::R4G4B4A4UnormPack16 => (Unorm, &[(3, 4), (2, 4), (1, 4), (0, 4)]),
+        Format::B4G4R4A4UnormPack16 => (Unorm, &[(3, 4), (0, 4), (1, 4), (2, 4)]),
+        Format::R5G6B5UnormPack16 => (Unorm, &[(2, 5), (1, 6), (0, 5)]),
+        Format::B5G6R5UnormPack16 => (Unorm, &[(0, 5), (1, 6), (2, 5)]),
+        Format::R5G5B5A1UnormPack16 => (Unorm, &[(3, 1), (2, 5), (1, 5), (0, 5)]),
+        Format::B5G5R5A1UnormPack16 => (Unorm, &[(3, 1), (0, 5), (1, 5), (2, 5)]),
+        Format::A1R5G5B5UnormPack16 => (Unorm, &[(2, 5), (1, 5), (0, 5), (3, 1)]),
+
+        Format::R8Unorm => (Unorm, R8),
+        Format::R8Snorm => (Snorm, R8),
+        Format::R8Uscaled => (Uscaled, R8),
+        Format::R8Sscaled => (Sscaled, R8),
+        Format::R8Uint => (Uint, R8),
+        Format::R8Sint => (Sint, R8),
+        Format::R8Srgb => (Srgb, R8),
+        Format::R8G8Unorm => (Unorm, RG8),
+        Format::R8G8Snorm => (Snorm, RG8),
+        Format::R8G8Uscaled => (Uscaled, RG8),
+        Format::R8G8Sscaled => (Sscaled, RG8),
+        Format::R8G8Uint => (Uint, RG8),
+        Format::R8G8Sint => (Sint, RG8),
+        Format::R8G8Srgb => (Srgb, RG8),
+        Format::R8G8B8Unorm => (Unorm, RGB8),
+        Format::R8G8B8Snorm => (Snorm, RGB8),
+        Format::R8G8B8Uscaled => (Uscaled, RGB8),
+        Format::R8G8B8Sscaled => (Sscaled, RGB8),
+        Format::R8G8B8Uint => (Uint, RGB8),
+        Format::R8G8B8Sint => (Sint, RGB8),
+        Format::R8G8B8Srgb => (Srgb, RGB8),
+        Format::B8G8R8Unorm => (Unorm, BGR8),
+        Format::B8G8R8Snorm => (Snorm, BGR8),
+        Format::B8G8R8Uscaled => (Uscaled, BGR8),
+        Format::B8G8R8Sscaled => (Sscaled, BGR8),
+        Format::B8G8R8Uint => (Uint, BGR8),
+        Format::B8G8R8Sint => (Sint, BGR8),
+        Format::B8G8R8Srgb => (Srgb, BGR8),
+        Format::R8G8B8A8Unorm | Format::A8B8G8R8UnormPack32 => (Unorm, RGBA8),
+        Format::R8G8B8A8Snorm | Format::A8B8G8R8SnormPack32 => (Snorm, RGBA8),
+        Format::R8G8B8A8Uscaled | Format::A8B8G8R8UscaledPack32 => (Uscaled, RGBA8),
+        Format::R8G8B8A8Sscaled | Format::A8B8G8R8SscaledPack32 => (Sscaled, RGBA8),
+        Format::R8G8B8A8Uint | Format::A8B8G8R8UintPack32 => (Uint, RGBA8),
+        Format::R8G8B8A8Sint | Format::A8B8G8R8SintPack32 => (Sint, RGBA8),
+        Format::R8G8B8A8Srgb | Format::A8B8G8R8SrgbPack32 => (Srgb, RGBA8),
+        Format::B8G8R8A8Unorm => (Unorm, BGRA8),
+        Format::B8G8R8A8Snorm => (Snorm, BGRA8),
+        Format::B8G8R8A8Uscaled => (Uscaled, BGRA8),
+        Format::B8G8R8A8Sscaled => (Sscaled, BGRA8),
+        Format::B8G8R8A8Uint => (Uint, BGRA8),
+        Format::B8G8R8A8Sint => (Sint, BGRA8),
+        Format::B8G8R8A8Srgb => (Srgb, BGRA8),
+
+        Format::A2R10G10B10UnormPack32 => (Unorm, A2R10G10B10),
+        Format::A2R10G10B10SnormPack32 => (Snorm, A2R10G10B10),
+        Format::A2R10G10B10UscaledPack32 => (Uscaled, A2R10G10B10),
+        Format::A2R10G10B10SscaledPack32 => (Sscaled, A2R10G10B10),
+        Format::A2R10G10B10UintPack32 => (Uint, A2R10G10B10),
+        Format::A2R10G10B10SintPack32 => (Sint, A2R10G10B10),
+        Format::A2B10G10R10UnormPack32 => (Unorm, A2B10G10R10),
+        Format::A2B10G10R10SnormPack32 => (Snorm, A2B10G10R10),
+        Format::A2B10G10R10UscaledPack32 => (Uscaled, A2B10G10R10),
+        Format::A2B10G10R10SscaledPack32 => (Sscaled, A2B10G10R10),
+        Format::A2B10G10R10UintPack32 => (Uint, A2B10G10R10),
+        Format::A2B10G10R10SintPack32 => (Sint, A2B10G10R10),
+
+        Format::R16Unorm => (Unorm, R16),
+        Format::R16Snorm => (Snorm, R16),
+        Format::R16Uscaled => (Uscaled, R16),
+        Format::R16Sscaled => (Sscaled, R16),
+        Format::R16Uint => (Uint, R16),
+        Format::R16Sint => (Sint, R16),
+        Format::R16Sfloat => (Sfloat, R16),
+        Format::R16G16Unorm => (Unorm, RG16),
+        Format::R16G16Snorm => (Snorm, RG16),
+        Format::R16G16Uscaled => (Uscaled, RG16),
+        Format::R16G16Sscaled => (Sscaled, RG16),
+        Format::R16G16Uint => (Uint, RG16),
+        Format::R16G16Sint => (Sint, RG16),
+        Format::R16G16Sfloat => (Sfloat, RG16),
+        Format::R16G16B16Unorm => (Unorm, RGB16),
+        Format::R16G16B16Snorm => (Snorm, RGB16),
+        Format::R16G16B16Uscaled => (Uscaled, RGB16),
+        Format::R16G16B16Sscaled => (Sscaled, RGB16),
+        Format::R16G16B16Uint => (Uint, RGB16),
+        Format::R16G16B16Sint => (Sint, RGB16),
+        Format::R16G16B16Sfloat => (Sfloat, RGB16),
+        Format::R16G16B16A16Unorm => (Unorm, RGBA16),
+        Format::R16G16B16A16Snorm => (Snorm, RGBA16),
+        Format::R16G16B16A16Uscaled => (Uscaled, RGBA16),
+        Format::R16G16B16A16Sscaled => (Sscaled, RGBA16),
+        Format::R16G16B16A16Uint => (Uint, RGBA16),
+        Format::R16G16B16A16Sint => (Sint, RGBA16),
+        Format::R16G16B16A16Sfloat => (Sfloat, RGBA16),
+
+        Format::R32Uint => (Uint, R32),
+        Format::R32Sint => (Sint, R32),
+        Format::R32Sfloat => (Sfloat, R32),
+        Format::R32G32Uint => (Uint, RG32),
+        Format::R32G32Sint => (Sint, RG32),
+        Format::R32G32Sfloat => (Sfloat, RG32),
+        Format::R32G32B32Uint => (Uint, RGB32),
+        Format::R32G32B32Sint => (Sint, RGB32),
+        Format::R32G32B32Sfloat => (Sfloat, RGB32),
+        Format::R32G32B32A32Uint => (Uint, RGBA32),
+        Format::R32G32B32A32Sint => (Sint, RGBA32),
+        Format::R32G32B32A32Sfloat => (Sfloat, RGBA32),
+
+        Format::B10G11R11UfloatPack32 => (Ufloat, &[(0, 11), (1, 11), (2, 10)]),
+
+        _ => return None,
+    };
+
+    Some(Layout {
+        numeric: numeric,
+        components: components,
+    })
+}
+
+/// Whether `decode` and `encode` understand the format. Depth and stencil formats count, and
+/// convert their depth (or for `S8_UINT`, stencil) in the first component.
+pub fn is_supported(format: Format) -> bool {
+    layout(format).is_some() || format == Format::E5B9G9R9UfloatPack32 || is_depth_stencil(format)
+}
+
+/// Whether texels of the format hold integers rather than floats.
+pub fn is_integer(format: Format) -> bool {
+    match layout(format) {
+        Some(layout) => layout.numeric == Numeric::Uint || layout.numeric == Numeric::Sint,
+        None => format == Format::S8Uint,
+    }
+}
+
+/// Whether the format is normalized, and so clamps what is blended into it.
+pub fn normalized_range(format: Format) -> Option<(f32, f32)> {
+    match layout(format).map(|layout| layout.numeric) {
+        Some(Numeric::Unorm) | Some(Numeric::Srgb) => Some((0.0, 1.0)),
+        Some(Numeric::Snorm) => Some((-1.0, 1.0)),
+        _ => None,
+    }
+}
+
+/// Whether logic ops apply to the format, which they do for everything but floating-point and
+/// sRGB formats.
+pub fn takes_logic_op(format: Format) -> bool {
+    match layout(format).map(|layout| layout.numeric) {
+        Some(Numeric::Sfloat) | Some(Numeric::Ufloat) | Some(Numeric::Srgb) | None => false,
+        Some(_) => true,
+    }
+}
+
+pub fn is_depth_stencil(format: Format) -> bool {
+    has_depth(format) || has_stencil(format)
+}
+
+pub fn has_depth(format: Format) -> bool {
+    match format {
+        Format::D16Unorm | Format::X8_D24UnormPack32 | Format::D32Sfloat |
+        Format::D16Unorm_S8Uint | Format::D24Unorm_S8Uint | Format::D32Sfloat_S8Uint => true,
+        _ => false,
+    }
+}
+
+pub fn has_stencil(format: Format) -> bool {
+    match format {
+        Format::S8Uint | Format::D16Unorm_S8Uint | Format::D24Unorm_S8Uint | Format::D32Sfloat_S8Uint => true,
+        _ => false,
+    }
+}
+
+/// The aspects an image of the format has.
+pub fn aspects(format: Format) -> ImageAspectFlags {
+    let mut aspects = ImageAspectFlags::empty();
+    if has_depth(format) {
+        aspects |= VK_IMAGE_ASPECT_DEPTH_BIT;
+    }
+    if has_stencil(format) {
+        aspects |= VK_IMAGE_ASPECT_STENCIL_BIT;
+    }
+    if aspects.is_empty() {
+        aspects = VK_IMAGE_ASPECT_COLOR_BIT;
+    }
+    aspects
+}
+
+/// The texel `decode` gives components a format lacks.
+pub fn default_texel(integer: bool) -> Texel {
+    if integer { [0, 0, 0, 1] } else { [0, 0, 0, 1.0f32.to_bits()] }
+}
+
+/// Copies the texel at `data` out of memory.
+pub unsafe fn load(format: Format, data: *const u8) -> Packed {
+    let mut packed = [0; MAX_TEXEL_SIZE];
+    let size = format.block_size().unwrap_or(0) as usize;
+    ptr::copy_nonoverlapping(data, packed.as_mut_ptr(), size.min(MAX_TEXEL_SIZE));
+    packed
+}
+
+/// Converts a texel to its components, or `None` if the format is not supported.
+pub fn decode(format: Format, packed: &Packed) -> Option<Texel> {
+    if let Some(layout) = layout(format) {
+        let integer = layout.numeric == Numeric::Uint || layout.numeric == Numeric::Sint;
+        let mut texel = default_texel(integer);
+        let mut offset = 0;
+        for &(component, bits) in layout.components {
+            let raw = read_bits(packed, offset, bits);
+            texel[component] = decode_component(layout.numeric, raw, bits, component == 3);
+            offset += bits;
+        }
+        return Some(texel);
+    }
+
+    if format == Format::E5B9G9R9UfloatPack32 {
+        let raw = read_bits(packed, 0, 32);
+        let scale = 2f32.powi((raw >> 27) as i32 - 15 - 9);
+        return Some([
+            ((raw & 0x1ff) as f32 * scale).to_bits(),
+            (((raw >> 9) & 0x1ff) as f32 * scale).to_bits(),
+            (((raw >> 18) & 0x1ff) as f32 * scale).to_bits(),
+            1.0f32.to_bits(),
+        ]);
+    }
+
+    if has_depth(format) {
+        let mut texel = default_texel(false);
+        texel[0] = decode_depth(format, packed).to_bits();
+        return Some(texel);
+    }
+    if has_stencil(format) {
+        let mut texel = default_texel(true);
+        texel[0] = decode_stencil(format, packed) as u32;
+        return Some(texel);
+    }
+
+    None
+}
+
+/// Converts components to a texel of the format, rounding and clamping to what it can hold, or
+/// `None` if the format is not supported.
+pub fn encode(format: Format, texel: Texel) -> Option<Packed> {
+    let mut packed = [0; MAX_TEXEL_SIZE];
+
+    if let Some(layout) = layout(format) {
+        let mut offset = 0;
+        for &(component, bits) in layout.components {
+            let raw = encode_component(layout.numeric, texel[component], bits, component == 3);
+            write_bits(&mut packed, offset, bits, raw);
+            offset += bits;
+        }
+        return Some(packed);
+    }
+
+    if format == Format::E5B9G9R9UfloatPack32 {
+        write_bits(&mut packed, 0, 32, encode_e5b9g9r9(texel));
+        return Some(packed);
+    }
+
+    if has_depth(format) {
+        encode_depth(format, &mut packed, f32::from_bits(texel[0]));
+        return Some(packed);
+    }
+    if has_stencil(format) {
+        encode_stencil(format, &mut packed, texel[0] as u8);
+        return Some(packed);
+    }
+
+    None
+}
+
+/// Writes `packed` to the texel at `data`, leaving the components `mask` excludes as they were.
+pub unsafe fn store(format: Format, data: *mut u8, packed: &Packed, mask: ColorComponentFlags) {
+    let size = (format.block_size().unwrap_or(0) as usize).min(MAX_TEXEL_SIZE);
+    let full = VK_COLOR_COMPONENT_R_BIT | VK_COLOR_COMPONENT_G_BIT | VK_COLOR_COMPONENT_B_BIT | VK_COLOR_COMPONENT_A_BIT;
+
+    match layout(format) {
+        Some(ref layout) if !mask.contains(full) => {
+            let mut merged = load(format, data);
+            let mut offset = 0;
+            for &(component, bits) in layout.components {
+                if mask.bits() & (1 << component) != 0 {
+                    write_bits(&mut merged, offset, bits, read_bits(packed, offset, bits));
+                }
+                offset += bits;
+            }
+            ptr::copy_nonoverlapping(merged.as_ptr(), data, size);
+        },
+        _ => ptr::copy_nonoverlapping(packed.as_ptr(), data, size),
+    }
+}
+
+/// Reads and decodes the texel at `data`.
+pub unsafe fn read(format: Format, data: *const u8) -> Option<Texel> {
+    decode(format, &load(format, data))
+}
+
+/// Encodes and writes the texel at `data`.
+pub unsafe fn write(format: Format, data: *mut u8, texel: Texel) {
+    if let Some(packed) = encode(format, texel) {
+        let size = (format.block_size().unwrap_or(0) as usize).min(MAX_TEXEL_SIZE);
+        ptr::copy_nonoverlapping(packed.as_ptr(), data, size);
+    }
+}
+
+pub unsafe fn read_depth(format: Format, data: *const u8) -> f32 {
+    decode_depth(format, &load(format, data))
+}
+
+/// Writes the depth of a texel, leaving its stencil alone.
+pub unsafe fn write_depth(format: Format, data: *mut u8, depth: f32) {
+    let mut packed = load(format, data);
+    encode_depth(format, &mut packed, depth);
+    let size = format.block_size().unwrap_or(0) as usize;
+    ptr::copy_nonoverlapping(packed.as_ptr(), data, size);
+}
+
+pub unsafe fn read_stencil(format: Format, data: *const u8) -> u8 {
+    decode_stencil(format, &load(format, data))
+}
+
+/// Writes the stencil of a texel, leaving its depth alone.
+pub unsafe fn write_stencil(format: Format, data: *mut u8, stencil: u8) {
+    let mut packed = load(format, data);
+    encode_stencil(format, &mut packed, stencil);
+    let size = format.block_size().unwrap_or(0) as usize;
+    ptr::copy_nonoverlapping(packed.as_ptr(), data, size);
+}
+
+/// The smallest difference the format's depth can resolve around `depth`, for depth bias.
+pub fn depth_resolution(format: Format, depth: f32) -> f32 {
+    match format {
+        Format::D16Unorm | Format::D16Unorm_S8Uint => 1.0 / 65536.0,
+        Format::X8_D24UnormPack32 | Format::D24Unorm_S8Uint => 1.0 / 16777216.0,
+        _ => {
+            // The bits of the mantissa at the exponent of the depth
+            let exponent = ((depth.abs().to_bits() >> 23) & 0xff) as i32 - 127;
+            2f32.powi(exponent - 23)
+        },
+    }
+}
+
+fn decode_depth(format: Format, packed: &Packed) -> f32 {
+    match format {
+        Format::D16Unorm | Format::D16Unorm_S8Uint => read_bits(packed, 0, 16) as f32 / 65535.0,
+        Format::X8_D24UnormPack32 | Format::D24Unorm_S8Uint => (read_bits(packed, 0, 24) as f64 / 16777215.0) as f32,
+        Format::D32Sfloat | Format::D32Sfloat_S8Uint => f32::from_bits(read_bits(packed, 0, 32)),
+        _ => 0.0,
+    }
+}
+
+fn encode_depth(format: Format, packed: &mut Packed, depth: f32) {
+    match format {
+        Format::D16Unorm | Format::D16Unorm_S8Uint => write_bits(packed, 0, 16, unorm(depth, 16)),
+        Format::X8_D24UnormPack32 | Format::D24Unorm_S8Uint => write_bits(packed, 0, 24, unorm(depth, 24)),
+        Format::D32Sfloat | Format::D32Sfloat_S8Uint => write_bits(packed, 0, 32, depth.to_bits()),
+        _ => {},
+    }
+}
+
+fn stencil_offset(format: Format) -> Option<u32> {
+    match format {
+        Format::S8Uint => Some(0),
+        Format::D16Unorm_S8Uint => Some(16),
+        Format::D24Unorm_S8Uint => Some(24),
+        Format::D32Sfloat_S8Uint => Some(32),
+        _ => None,
+    }
+}
+
+fn decode_stencil(format: Format, packed: &Packed) -> u8 {
+    stencil_offset(format).map_or(0, |offset| read_bits(packed, offset, 8) as u8)
+}
+
+fn encode_stencil(format: Format, packed: &mut Packed, stencil: u8) {
+    if let Some(offset) = stencil_offset(format) {
+        write_bits(packed, offset, 8, stencil as u32);
+    }
+}
+
+fn read_bits(packed: &Packed, offset: u32, bits: u32) -> u32 {
+    let first = (offset / 8) as usize;
+    let mut word = 0u64;
+    for i in 0..(((offset % 8) + bits + 7) / 8) as usize {
+        word |= (packed[first + i] as u64) << (8 * i);
+    }
+    ((word >> (offset % 8)) & mask(bits) as u64) as u32
+}
+
+fn write_bits(packed: &mut Packed, offset: u32, bits: u32, value: u32) {
+    let first = (offset / 8) as usize;
+    let shift = offset % 8;
+    let mut word = 0u64;
+    let bytes = ((shift + bits + 7) / 8) as usize;
+    for i in 0..bytes {
+        word |= (packed[first + i] as u64) << (8 * i);
+    }
+    word &= !((mask(bits) as u64) << shift);
+    word |= ((value & mask(bits)) as u64) << shift;
+    for i in 0..bytes {
+        packed[first + i] = (word >> (8 * i)) as u8;
+    }
+}
+
+fn mask(bits: u32) -> u32 {
+    if bits >= 32 { !0 } else { (1 << bits) - 1 }
+}
+
+fn sign_extend(raw: u32, bits: u32) -> i32 {
+    ((raw << (32 - bits)) as i32) >> (32 - bits)
+}
+
+fn decode_component(numeric: Numeric, raw: u32, bits: u32, alpha: bool) -> u32 {
+    match numeric {
+        Numeric::Unorm => ((raw as f64 / mask(bits) as f64) as f32).to_bits(),
+        Numeric::Srgb => {
+            let value = (raw as f64 / mask(bits) as f64) as f32;
+            if alpha { value.to_bits() } else { srgb_to_linear(value).to_bits() }
+        },
+        Numeric::Snorm => {
+            let max = mask(bits - 1) as f64;
+            ((sign_extend(raw, bits) as f64 / max).max(-1.0) as f32).to_bits()
+        },
+        Numeric::Uscaled => (raw as f32).to_bits(),
+        Numeric::Sscaled => (sign_extend(raw, bits) as f32).to_bits(),
+        Numeric::Uint => raw,
+        Numeric::Sint => sign_extend(raw, bits) as u32,
+        Numeric::Sfloat => if bits == 16 { small_float_to_f32(raw, 10, true).to_bits() } else { raw },
+        Numeric::Ufloat => small_float_to_f32(raw, bits - 5, false).to_bits(),
+    }
+}
+
+fn encode_component(numeric: Numeric, value: u32, bits: u32, alpha: bool) -> u32 {
+    let float = f32::from_bits(value);
+    match numeric {
+        Numeric::Unorm => unorm(float, bits),
+        Numeric::Srgb => unorm(if alpha { float } else { linear_to_srgb(float) }, bits),
+        Numeric::Snorm => {
+            let max = mask(bits - 1) as f32;
+            let clamped = if float.is_nan() { 0.0 } else { float.max(-1.0).min(1.0) };
+            ((clamped * max).round() as i32) as u32 & mask(bits)
+        },
+        Numeric::Uscaled => {
+            let clamped = if float.is_nan() { 0.0 } else { float.max(0.0).min(mask(bits) as f32) };
+            clamped.round() as u32
+        },
+        Numeric::Sscaled => {
+            let max = mask(bits - 1) as f32;
+            let clamped = if float.is_nan() { 0.0 } else { float.max(-max - 1.0).min(max) };
+            (clamped.round() as i32) as u32 & mask(bits)
+        },
+        Numeric::Uint | Numeric::Sint => value & mask(bits),
+        Numeric::Sfloat => if bits == 16 { f32_to_small_float(float, 10, true) } else { value },
+        Numeric::Ufloat => f32_to_small_float(float, bits - 5, false),
+    }
+}
+
+fn unorm(value: f32, bits: u32) -> u32 {
+    let clamped = if value.is_nan() { 0.0 } else { value.max(0.0).min(1.0) };
+    (clamped as f64 * mask(bits) as f64).round() as u32
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Widens a float with a five bit exponent (half floats and the unsigned packed floats).
+fn small_float_to_f32(raw: u32, mantissa_bits: u32, signed: bool) -> f32 {
+    let sign = if signed && raw & (1 << (mantissa_bits + 5)) != 0 { 1u32 << 31 } else { 0 };
+    let exponent = (raw >> mantissa_bits) & 0x1f;
+    let mantissa = raw & mask(mantissa_bits);
+
+    match exponent {
+        0 => {
+            let magnitude = mantissa as f32 * 2f32.powi(-14 - mantissa_bits as i32);
+            f32::from_bits(magnitude.to_bits() | sign)
+        },
+        0x1f => f32::from_bits(sign | (0xff << 23) | (mantissa << (23 - mantissa_bits))),
+        _ => f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << (23 - mantissa_bits))),
+    }
+}
+
+/// Narrows to a float with a five bit exponent, rounding to nearest even. Unsigned formats clamp
+/// negative values to zero.
+fn f32_to_small_float(value: f32, mantissa_bits: u32, signed: bool) -> u32 {
+    let bits = value.to_bits();
+    let sign = if signed && bits >> 31 != 0 { 1 << (mantissa_bits + 5) } else { 0 };
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7fffff;
+
+    if exponent == 0xff {
+        if mantissa != 0 {
+            return (0x1f << mantissa_bits) | (1 << (mantissa_bits - 1));
+        }
+        return if !signed && bits >> 31 != 0 { 0 } else { sign | (0x1f << mantissa_bits) };
+    }
+    if !signed && bits >> 31 != 0 {
+        return 0;
+    }
+
+    let rebiased = exponent - 127 + 15;
+    let (significand, shift) = if rebiased <= 0 {
+        // Subnormal: the implicit one becomes explicit and the exponent stays at zero
+        ((mantissa | 0x800000) as u64, (23 - mantissa_bits) as i32 + 1 - rebiased)
+    } else {
+        ((((rebiased as u64) << 23) | mantissa as u64), (23 - mantissa_bits) as i32)
+    };
+    if shift > 40 {
+        return sign;
+    }
+
+    let shift = shift as u32;
+    let truncated = significand >> shift;
+    let remainder = significand & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let rounded = if remainder > halfway || (remainder == halfway && truncated & 1 != 0) { truncated + 1 } else { truncated };
+
+    // Rounding can carry into the exponent, up to infinity
+    sign | (rounded as u32).min(0x1f << mantissa_bits)
+}
+
+fn encode_e5b9g9r9(texel: Texel) -> u32 {
+    let max_value = (511.0 / 512.0) * 2f32.powi(16);
+    let clamp = |value: u32| {
+        let value = f32::from_bits(value);
+        if value.is_nan() { 0.0 } else { value.max(0.0).min(max_value) }
+    };
+    let (red, green, blue) = (clamp(texel[0]), clamp(texel[1]), clamp(texel[2]));
+    let largest = red.max(green).max(blue);
+
+    let mut exponent = if largest > 0.0 { largest.log2().floor().max(-16.0) as i32 + 16 } else { 0 };
+    if (largest / 2f32.powi(exponent - 24) + 0.5).floor() >= 512.0 {
+        exponent += 1;
+    }
+
+    let scale = 2f32.powi(exponent - 24);
+    let quantize = |value: f32| ((value / scale + 0.5).floor() as u32).min(511);
+    ((exponent as u32) << 27) | (quantize(blue) << 18) | (quantize(green) << 9) | quantize(red)
+}
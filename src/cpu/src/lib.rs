@@ -0,0 +1,534 @@
+//! A software backend that runs everything on the host, as a reference to compare hardware
+//! results against and for rendering in environments without a GPU.
+//!
+//! Work is executed in order during `vkQueueSubmit`, one invocation at a time, so results are the
+//! same from run to run and machine to machine. Correctness comes before speed throughout: there
+//! is one sample per pixel, no tessellation or geometry shaders, and no sparse resources, but what
+//! the device does report it implements in full. The library can be loaded as an ICD: pointing
+//! `VK_ICD_FILENAMES` at `vk_middle_cpu_icd.json` makes the loader use the debug build from the
+//! workspace's target directory.
+//!
+//! Setting `VK_MIDDLE_CPU_VALIDATE=1` makes queues refuse submissions that use an image in a
+//! layout other than the one it is in with `VK_ERROR_VALIDATION_FAILED_EXT`, and setting
+//! `VK_MIDDLE_CPU_VALIDATE_SYNC=1` makes them refuse submissions with read-after-write,
+//! write-after-read or write-after-write hazards the same way. Only semaphores and barriers order
+//! accesses across submissions; waiting for a fence in between does not.
+
+#[macro_use] extern crate vk_middle;
+
+#[macro_use] extern crate log;
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+
+use std::env;
+use std::sync::{Arc, Mutex};
+
+mod format;
+mod memory;
+pub use memory::{DeviceMemory, Buffer, BufferView, Image, ImageView};
+mod sampler;
+pub use sampler::Sampler;
+mod descriptor;
+pub use descriptor::{DescriptorSetLayout, DescriptorPool, DescriptorSet};
+mod shader;
+mod pipeline;
+pub use pipeline::{PipelineLayout, ShaderModule, Pipeline, PipelineCache};
+mod render_pass;
+pub use render_pass::{RenderPass, Framebuffer};
+mod raster;
+mod transfer;
+mod queue;
+pub use queue::Queue;
+use queue::Validation;
+
+vulkan_icd_dispatch!(Cpu);
+
+pub enum Cpu {
+}
+
+impl icd::Impl for Cpu {
+    type Instance = Instance;
+    type PhysicalDevice = PhysicalDevice;
+    type Device = Device;
+    type Queue = Queue;
+    type CommandPool = RecordedCommandPool<Cpu>;
+    type CommandBuffer = RecordedCommandBuffer<Cpu>;
+
+    type DeviceMemory = DeviceMemory;
+    type Buffer = Buffer;
+    type BufferView = BufferView;
+    type Image = Image;
+    type ImageView = ImageView;
+
+    type Fence = CpuFence;
+    type Semaphore = Semaphore;
+    type Event = HostEvent;
+    type QueryPool = HostQueryPool;
+
+    type RenderPass = RenderPass;
+    type Framebuffer = Framebuffer;
+
+    type Sampler = Sampler;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorPool = DescriptorPool;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
+
+    type ShaderModule = ShaderModule;
+    type Pipeline = Pipeline;
+    type PipelineCache = PipelineCache;
+
+    /// Every command but those of sparse resources and extensions the backend does not expose.
+    fn supports(command: Command) -> bool {
+        match command {
+            Command::CreateInstance |
+            Command::DestroyInstance |
+            Command::EnumeratePhysicalDevices |
+            Command::GetPhysicalDeviceFeatures |
+            Command::GetPhysicalDeviceFormatProperties |
+            Command::GetPhysicalDeviceProperties |
+            Command::GetPhysicalDeviceQueueFamilyProperties |
+            Command::GetPhysicalDeviceMemoryProperties |
+            Command::GetDeviceProcAddr |
+            Command::CreateDevice |
+            Command::DestroyDevice |
+            Command::GetDeviceQueue |
+            Command::QueueSubmit |
+            Command::QueueWaitIdle |
+            Command::DeviceWaitIdle |
+            Command::AllocateMemory |
+            Command::FreeMemory |
+            Command::MapMemory |
+            Command::UnmapMemory |
+            Command::FlushMappedMemoryRanges |
+            Command::InvalidateMappedMemoryRanges |
+            Command::GetDeviceMemoryCommitment |
+            Command::BindBufferMemory |
+            Command::BindImageMemory |
+            Command::GetBufferMemoryRequirements |
+            Command::GetImageMemoryRequirements |
+            Command::CreateBuffer |
+            Command::DestroyBuffer |
+            Command::CreateBufferView |
+            Command::DestroyBufferView |
+            Command::CreateImage |
+            Command::DestroyImage |
+            Command::GetImageSubresourceLayout |
+            Command::CreateImageView |
+            Command::DestroyImageView |
+            Command::CreateFence |
+            Command::DestroyFence |
+            Command::ResetFences |
+            Command::GetFenceStatus |
+            Command::WaitForFences |
+            Command::CreateSemaphore |
+            Command::DestroySemaphore |
+            Command::CreateEvent |
+            Command::DestroyEvent |
+            Command::GetEventStatus |
+            Command::SetEvent |
+            Command::ResetEvent |
+            Command::CreateShaderModule |
+            Command::DestroyShaderModule |
+            Command::CreatePipelineCache |
+            Command::DestroyPipelineCache |
+            Command::GetPipelineCacheData |
+            Command::MergePipelineCaches |
+            Command::CreateGraphicsPipelines |
+            Command::CreateComputePipelines |
+            Command::DestroyPipeline |
+            Command::CreateQueryPool |
+            Command::DestroyQueryPool |
+            Command::GetQueryPoolResults |
+            Command::CreatePipelineLayout |
+            Command::DestroyPipelineLayout |
+            Command::CreateSampler |
+            Command::DestroySampler |
+            Command::CreateDescriptorSetLayout |
+            Command::DestroyDescriptorSetLayout |
+            Command::CreateDescriptorPool |
+            Command::DestroyDescriptorPool |
+            Command::ResetDescriptorPool |
+            Command::AllocateDescriptorSets |
+            Command::FreeDescriptorSets |
+            Command::UpdateDescriptorSets |
+            Command::CreateFramebuffer |
+            Command::DestroyFramebuffer |
+            Command::CreateRenderPass |
+            Command::DestroyRenderPass |
+            Command::GetRenderAreaGranularity |
+            Command::CreateCommandPool |
+            Command::DestroyCommandPool |
+            Command::ResetCommandPool |
+            Command::AllocateCommandBuffers |
+            Command::FreeCommandBuffers |
+            Command::BeginCommandBuffer |
+            Command::EndCommandBuffer |
+            Command::ResetCommandBuffer |
+            Command::CmdBindPipeline |
+            Command::CmdSetViewport |
+            Command::CmdSetScissor |
+            Command::CmdSetLineWidth |
+            Command::CmdSetDepthBias |
+            Command::CmdSetBlendConstants |
+            Command::CmdSetDepthBounds |
+            Command::CmdSetStencilCompareMask |
+            Command::CmdSetStencilWriteMask |
+            Command::CmdSetStencilReference |
+            Command::CmdBindDescriptorSets |
+            Command::CmdBindIndexBuffer |
+            Command::CmdBindVertexBuffers |
+            Command::CmdDraw |
+            Command::CmdDrawIndexed |
+            Command::CmdDrawIndirect |
+            Command::CmdDrawIndexedIndirect |
+            Command::CmdDispatch |
+            Command::CmdDispatchIndirect |
+            Command::CmdCopyBuffer |
+            Command::CmdCopyImage |
+            Command::CmdBlitImage |
+            Command::CmdCopyBufferToImage |
+            Command::CmdCopyImageToBuffer |
+            Command::CmdUpdateBuffer |
+            Command::CmdFillBuffer |
+            Command::CmdClearColorImage |
+            Command::CmdClearDepthStencilImage |
+            Command::CmdClearAttachments |
+            Command::CmdResolveImage |
+            Command::CmdSetEvent |
+            Command::CmdResetEvent |
+            Command::CmdWaitEvents |
+            Command::CmdPipelineBarrier |
+            Command::CmdBeginQuery |
+            Command::CmdEndQuery |
+            Command::CmdResetQueryPool |
+            Command::CmdWriteTimestamp |
+            Command::CmdCopyQueryPoolResults |
+            Command::CmdPushConstants |
+            Command::CmdBeginRenderPass |
+            Command::CmdNextSubpass |
+            Command::CmdEndRenderPass |
+            Command::CmdExecuteCommands |
+            Command::EnumerateInstanceExtensionProperties |
+            Command::EnumerateDeviceExtensionProperties => true,
+            _ => false,
+        }
+    }
+}
+
+/// The size of the device's only memory heap.
+const HEAP_SIZE: u64 = 1 << 30;
+
+pub struct Instance {
+}
+
+impl icd::Instance<Cpu> for Instance {
+    fn create(_info: InstanceCreateInfo<Cpu>) -> Result<Self> { Ok(Instance {}) }
+
+    fn physical_device_count(&self) -> Result<u32> { Ok(1) }
+
+    fn enumerate_physical_devices(&self, capacity: u32) -> Result<Vec<PhysicalDevice>> {
+        if capacity == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(vec![PhysicalDevice {}])
+    }
+}
+
+pub struct PhysicalDevice {
+}
+
+impl icd::PhysicalDevice<Cpu> for PhysicalDevice {
+    fn get_features(&self) -> PhysicalDeviceFeatures { supported_features() }
+
+    fn get_format_properties(&self, format: Format) -> FormatProperties {
+        if format.block_size().is_none() || !format::is_supported(format) {
+            return FormatProperties::default();
+        }
+
+        let mut image_features = VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT |
+            VK_FORMAT_FEATURE_BLIT_SRC_BIT |
+            VK_FORMAT_FEATURE_BLIT_DST_BIT;
+        let mut buffer_features = FormatFeatureFlags::empty();
+
+        if format::is_depth_stencil(format) {
+            image_features |= VK_FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT;
+        } else {
+            image_features |= VK_FORMAT_FEATURE_STORAGE_IMAGE_BIT | VK_FORMAT_FEATURE_COLOR_ATTACHMENT_BIT;
+            buffer_features |= VK_FORMAT_FEATURE_VERTEX_BUFFER_BIT |
+                VK_FORMAT_FEATURE_UNIFORM_TEXEL_BUFFER_BIT |
+                VK_FORMAT_FEATURE_STORAGE_TEXEL_BUFFER_BIT;
+
+            if !format::is_integer(format) {
+                image_features |= VK_FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT | VK_FORMAT_FEATURE_COLOR_ATTACHMENT_BLEND_BIT;
+            }
+            if format == Format::R32Uint || format == Format::R32Sint {
+                image_features |= VK_FORMAT_FEATURE_STORAGE_IMAGE_ATOMIC_BIT;
+                buffer_features |= VK_FORMAT_FEATURE_STORAGE_TEXEL_BUFFER_ATOMIC_BIT;
+            }
+        }
+
+        FormatProperties {
+            linear_tiling_features: image_features,
+            optimal_tiling_features: image_features,
+            buffer_features: buffer_features,
+        }
+    }
+
+    fn get_properties(&self) -> PhysicalDeviceProperties {
+        PhysicalDeviceProperties {
+            api_version: API_VERSION_1_0,
+            driver_version: 1,
+            vendor_id: 0,
+            device_id: 0,
+            device_type: PhysicalDeviceType::Cpu,
+            device_name: "vk-middle CPU device".to_owned(),
+            pipeline_cache_uuid: [0; 16],
+            limits: limits(),
+            sparse_properties: PhysicalDeviceSparseProperties::default(),
+        }
+    }
+
+    fn get_queue_family_properties(&self) -> Vec<QueueFamilyProperties> {
+        vec![QueueFamilyProperties {
+            queue_flags: VK_QUEUE_GRAPHICS_BIT | VK_QUEUE_COMPUTE_BIT | VK_QUEUE_TRANSFER_BIT,
+            queue_count: 1,
+            timestamp_valid_bits: 64,
+            min_image_transfer_granularity: Extent3D { width: 1, height: 1, depth: 1 },
+        }]
+    }
+
+    fn get_memory_properties(&self) -> PhysicalDeviceMemoryProperties {
+        PhysicalDeviceMemoryProperties {
+            memory_types: vec![MemoryType {
+                property_flags: VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT |
+                    VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT |
+                    VK_MEMORY_PROPERTY_HOST_COHERENT_BIT |
+                    VK_MEMORY_PROPERTY_HOST_CACHED_BIT,
+                heap_index: 0,
+            }],
+            memory_heaps: vec![MemoryHeap {
+                size: HEAP_SIZE,
+                flags: VK_MEMORY_HEAP_DEVICE_LOCAL_BIT,
+            }],
+        }
+    }
+
+    fn create_device(&self, info: DeviceCreateInfo<Cpu>) -> Result<Device> {
+        for queue in info.queues() {
+            if queue.family_index() != 0 || queue.count() > 1 {
+                error!("vkCreateDevice called for {} queues of family {}, which the device does not have", queue.count(), queue.family_index());
+                return Err(Error::InitializationFailed);
+            }
+        }
+        if !supported_features().contains(&info.enabled_features()) {
+            return Err(Error::FeatureNotPresent);
+        }
+
+        let validate = env::var("VK_MIDDLE_CPU_VALIDATE").map(|x| x == "1").unwrap_or(false);
+        let validate_synchronization = env::var("VK_MIDDLE_CPU_VALIDATE_SYNC").map(|x| x == "1").unwrap_or(false);
+        Ok(Device {
+            allocated: Mutex::new(0),
+            validation: if validate || validate_synchronization {
+                Some(Arc::new(Mutex::new(Validation::new(validate, validate_synchronization))))
+            } else {
+                None
+            },
+        })
+    }
+}
+
+pub struct Device {
+    allocated: Mutex<u64>,
+    validation: Option<Arc<Mutex<Validation>>>,
+}
+
+impl Device {
+    fn release_memory(&self, size: u64) {
+        *self.allocated.lock().unwrap() -= size;
+    }
+}
+
+impl icd::Device<Cpu> for Device {
+    fn get_queue(&self, _family_index: u32, _queue_index: u32) -> Result<Queue> {
+        Ok(Queue::new(self.validation.clone()))
+    }
+
+    fn wait_idle(&self) -> Result<()> { Ok(()) }
+
+    fn allocate_memory(&self, info: MemoryAllocateInfo<Cpu>) -> Result<DeviceMemory> {
+        if info.memory_type_index() != 0 {
+            error!("vkAllocateMemory called with memory type {}, which the device does not have", info.memory_type_index());
+            return Err(Error::OutOfDeviceMemory);
+        }
+
+        let size = info.allocation_size();
+        let mut allocated = self.allocated.lock().unwrap();
+        if HEAP_SIZE - *allocated < size {
+            return Err(Error::OutOfDeviceMemory);
+        }
+        let memory = try!(DeviceMemory::new(size));
+        *allocated += size;
+        Ok(memory)
+    }
+
+    fn flush_mapped_memory_ranges(&self, _ranges: MappedMemoryRanges<Cpu>) -> Result<()> { Ok(()) }
+    fn invalidate_mapped_memory_ranges(&self, _ranges: MappedMemoryRanges<Cpu>) -> Result<()> { Ok(()) }
+
+    fn update_descriptor_sets(&self, writes: &[DescriptorWrite<Cpu>], copies: &[DescriptorCopy<Cpu>]) {
+        descriptor::update_descriptor_sets(writes, copies);
+    }
+}
+
+/// Work finishes before `vkQueueSubmit` returns, so there is never anything to wait for.
+pub struct Semaphore {
+}
+
+impl icd::Semaphore<Cpu> for Semaphore {
+    fn new(_device: &Device, _info: SemaphoreCreateInfo<Cpu>) -> Result<Self> { Ok(Semaphore {}) }
+}
+
+/// Everything the rasterizer and shader execution implement. Sample counts above one, wide
+/// lines, multiple viewports, tessellation, geometry shaders, anisotropy, compressed formats and
+/// sparse resources are left out.
+fn supported_features() -> PhysicalDeviceFeatures {
+    PhysicalDeviceFeatures {
+        robust_buffer_access: true,
+        full_draw_index_uint32: true,
+        image_cube_array: true,
+        independent_blend: true,
+        login_op: true,
+        multi_draw_indirect: true,
+        draw_indirect_first_instance: true,
+        depth_clamp: true,
+        depth_bias_clamp: true,
+        fill_mode_non_solid: true,
+        depth_bounds: true,
+        large_points: true,
+        alpha_to_one: true,
+        occlusion_query_precise: true,
+        pipeline_statistics_query: true,
+        vertex_pipeline_stores_and_atomics: true,
+        fragment_stores_and_atomics: true,
+        shader_uniform_buffer_array_dynamic_indexing: true,
+        shader_sampled_image_array_dynamic_indexing: true,
+        shader_storage_buffer_array_dynamic_indexing: true,
+        shader_storage_image_array_dynamic_indexing: true,
+        ..PhysicalDeviceFeatures::default()
+    }
+}
+
+/// Limits in line with desktop GPUs, narrowed to what the rasterizer supports.
+fn limits() -> PhysicalDeviceLimits {
+    let sample_counts = VK_SAMPLE_COUNT_1_BIT;
+
+    PhysicalDeviceLimits {
+        max_image_dimension_1d: 16384,
+        max_image_dimension_2d: 16384,
+        max_image_dimension_3d: 2048,
+        max_image_dimension_cube: 16384,
+        max_image_array_layers: 2048,
+        max_texel_buffer_elements: 1 << 27,
+        max_uniform_buffer_range: 65536,
+        max_storage_buffer_range: 1 << 30,
+        max_push_constants_size: 256,
+        max_memory_allocation_count: 4096,
+        max_sampler_allocation_count: 4000,
+        buffer_image_granularity: 1,
+        sparse_address_space_size: 0,
+        max_bound_descriptor_sets: 8,
+        max_per_stage_descriptor_samplers: 4096,
+        max_per_stage_descriptor_uniform_buffers: 64,
+        max_per_stage_descriptor_storage_buffers: 64,
+        max_per_stage_descriptor_sampled_images: 4096,
+        max_per_stage_descriptor_storage_images: 64,
+        max_per_stage_descriptor_input_attachments: 8,
+        max_per_stage_resources: 8192,
+        max_descriptor_set_samplers: 4096,
+        max_descriptor_set_uniform_buffers: 384,
+        max_descriptor_set_uniform_buffers_dynamic: 16,
+        max_descriptor_set_storage_buffers: 384,
+        max_descriptor_set_storage_buffers_dynamic: 16,
+        max_descriptor_set_sampled_images: 4096,
+        max_descriptor_set_storage_images: 384,
+        max_descriptor_set_input_attachments: 8,
+        max_vertex_input_attributes: 32,
+        max_vertex_input_bindings: 32,
+        max_vertex_input_attribute_offset: 2047,
+        max_vertex_input_binding_stride: 2048,
+        max_vertex_output_components: 128,
+        max_tessellation_generation_level: 0,
+        max_tessellation_patch_size: 0,
+        max_tessellation_control_per_vertex_input_components: 0,
+        max_tessellation_control_per_vertex_output_components: 0,
+        max_tessellation_control_per_patch_output_components: 0,
+        max_tessellation_control_total_output_components: 0,
+        max_tessellation_evaluation_input_components: 0,
+        max_tessellation_evaluation_output_components: 0,
+        max_geometry_shader_invocations: 0,
+        max_geometry_input_components: 0,
+        max_geometry_output_components: 0,
+        max_geometry_output_vertices: 0,
+        max_geometry_total_output_components: 0,
+        max_fragment_input_components: 128,
+        max_fragment_output_attachments: 8,
+        max_fragment_dual_src_attachments: 0,
+        max_fragment_combined_output_resources: 72,
+        max_compute_shared_memory_size: 32768,
+        max_compute_work_group_count: [65535, 65535, 65535],
+        max_compute_work_group_invocations: 1024,
+        max_compute_work_group_size: [1024, 1024, 64],
+        sub_pixel_precision_bits: 8,
+        sub_texel_precision_bits: 8,
+        mipmap_precision_bits: 8,
+        max_draw_indexed_index_value: !0,
+        max_draw_indirect_count: !0,
+        max_sampler_lod_bias: 16.0,
+        max_sampler_anisotropy: 1.0,
+        max_viewports: 1,
+        max_viewport_dimensions: [16384, 16384],
+        viewport_bounds_range: [-32768.0, 32767.0],
+        viewport_sub_pixel_bits: 8,
+        min_memory_map_alignment: 64,
+        min_texel_buffer_offset_alignment: 16,
+        min_uniform_buffer_offset_alignment: 256,
+        min_storage_buffer_offset_alignment: 16,
+        min_texel_offset: -8,
+        max_texel_offset: 7,
+        min_texel_gather_offset: 0,
+        max_texel_gather_offset: 0,
+        min_interpolation_offset: 0.0,
+        max_interpolation_offset: 0.0,
+        sub_pixel_interpolation_offset_bits: 0,
+        max_framebuffer_width: 16384,
+        max_framebuffer_height: 16384,
+        max_framebuffer_layers: 2048,
+        framebuffer_color_sample_counts: sample_counts,
+        framebuffer_depth_sample_counts: sample_counts,
+        framebuffer_stencil_sample_counts: sample_counts,
+        framebuffer_no_attachments_sample_counts: sample_counts,
+        max_color_attachments: 8,
+        sampled_image_color_sample_counts: sample_counts,
+        sampled_image_integer_sample_counts: sample_counts,
+        sampled_image_depth_sample_counts: sample_counts,
+        sampled_image_stencil_sample_counts: sample_counts,
+        storage_image_sample_counts: sample_counts,
+        max_sample_mask_words: 1,
+        timestamp_compute_and_graphics: true,
+        timestamp_period: 1.0,
+        max_clip_distances: 0,
+        max_cull_distances: 0,
+        max_combined_clip_and_cull_distances: 0,
+        discrete_queue_priorities: 2,
+        point_size_range: [1.0, 64.0],
+        line_width_range: [1.0, 1.0],
+        point_size_granularity: 0.125,
+        line_width_granularity: 0.0,
+        strict_lines: false,
+        standard_sample_locations: true,
+        optimal_buffer_copy_offset_alignment: 1,
+        optimal_buffer_copy_row_pitch_alignment: 1,
+        non_coherent_atom_size: 64,
+    }
+}
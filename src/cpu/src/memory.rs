@@ -0,0 +1,463 @@
+//! Device memory backed by host allocations, and the buffers, images and views bound to it.
+//!
+//! Images use `HostImageLayout` whatever their tiling. Views copy what they need from their image
+//! when they are created, which the spec allows since an image must be bound to memory before a
+//! view of it is made.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+use vk_middle::icd::recorded::ObjectRef;
+
+use std::{cmp, ptr};
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use super::{Cpu, Device};
+use format::{self, Texel};
+
+/// The alignment required of buffers and images within an allocation. Covers every minimum
+/// offset alignment in the limits.
+const RESOURCE_ALIGNMENT: u64 = 256;
+
+pub struct DeviceMemory {
+    memory: HostMemory,
+}
+
+impl DeviceMemory {
+    pub(crate) fn new(size: u64) -> Result<DeviceMemory> {
+        Ok(DeviceMemory { memory: try!(HostMemory::new(size)) })
+    }
+}
+
+impl icd::DeviceMemory<Cpu> for DeviceMemory {
+    fn map(&mut self, _device: &Device, offset: u64, _size: u64, _flags: MemoryMapFlags) -> Result<*mut u8> {
+        Ok(self.memory.ptr(offset))
+    }
+
+    fn get_memory_commitment(&self, _device: &Device) -> u64 { self.memory.size() }
+
+    fn destroy(self, device: &Device) {
+        device.release_memory(self.memory.size());
+    }
+}
+
+fn bind(memory: &DeviceMemory, offset: u64, size: u64) -> Result<*mut u8> {
+    match memory.memory.bind(offset, size, RESOURCE_ALIGNMENT) {
+        Some(data) => Ok(data),
+        None => {
+            error!("resource of {} bytes bound at offset {} of an allocation of {} bytes", size, offset, memory.memory.size());
+            Err(Error::OutOfDeviceMemory)
+        },
+    }
+}
+
+/// Numbers buffers and images in the order they are created.
+static NEXT_SERIAL: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub struct Buffer {
+    size: u64,
+    data: *mut u8,
+    serial: u64,
+}
+
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    pub fn size(&self) -> u64 { self.size }
+
+    /// The address of the byte at `offset`. Memory must be bound.
+    pub fn ptr(&self, offset: u64) -> *mut u8 {
+        unsafe { self.data.offset(offset as isize) }
+    }
+
+    /// The address of `size` bytes at `offset`, or `None` if they run past the end of the buffer.
+    pub fn range(&self, offset: u64, size: u64) -> Option<*mut u8> {
+        if self.data.is_null() || offset.checked_add(size).map_or(true, |end| end > self.size) {
+            return None;
+        }
+        Some(self.ptr(offset))
+    }
+
+    /// Distinguishes the buffer from every other buffer or image created by the process.
+    pub fn serial(&self) -> u64 { self.serial }
+}
+
+impl icd::Buffer<Cpu> for Buffer {
+    fn new(_device: &Device, info: BufferCreateInfo<Cpu>) -> Result<Self> {
+        Ok(Buffer {
+            size: info.size(),
+            data: ptr::null_mut(),
+            serial: NEXT_SERIAL.fetch_add(1, Ordering::Relaxed) as u64,
+        })
+    }
+
+    fn get_memory_requirements(&self, _device: &Device) -> MemoryRequirements {
+        MemoryRequirements {
+            size: self.size,
+            alignment: RESOURCE_ALIGNMENT,
+            memory_types: 1,
+        }
+    }
+
+    fn bind_memory(&mut self, _device: &Device, memory: &DeviceMemory, offset: u64) -> Result<()> {
+        self.data = try!(bind(memory, offset, self.size));
+        Ok(())
+    }
+}
+
+/// A formatted view of a buffer, for texel buffer descriptors.
+#[derive(Clone)]
+pub struct BufferView {
+    data: *mut u8,
+    format: Format,
+    texel_size: u64,
+    texels: u64,
+}
+
+unsafe impl Send for BufferView {}
+unsafe impl Sync for BufferView {}
+
+impl BufferView {
+    pub fn format(&self) -> Format { self.format }
+
+    /// The number of texels in the view.
+    pub fn len(&self) -> u64 { self.texels }
+
+    /// Reads a texel, or zero past the end of the view.
+    pub fn read(&self, index: u64) -> Texel {
+        if index >= self.texels {
+            return [0; 4];
+        }
+        unsafe { format::read(self.format, self.data.offset((index * self.texel_size) as isize)).unwrap_or([0; 4]) }
+    }
+
+    /// Writes a texel. Writes past the end of the view are discarded.
+    pub fn write(&self, index: u64, texel: Texel) {
+        if index < self.texels {
+            unsafe { format::write(self.format, self.data.offset((index * self.texel_size) as isize), texel) };
+        }
+    }
+
+    /// The address of a texel, for atomics.
+    pub fn texel_ptr(&self, index: u64) -> Option<*mut u8> {
+        if index >= self.texels {
+            return None;
+        }
+        Some(unsafe { self.data.offset((index * self.texel_size) as isize) })
+    }
+}
+
+impl icd::BufferView<Cpu> for BufferView {
+    fn new(_device: &Device, info: BufferViewCreateInfo<Cpu>) -> Result<Self> {
+        let texel_size = match info.format().block_size() {
+            Some(size) if format::is_supported(info.format()) => size as u64,
+            _ => return Err(Error::FormatNotSupported),
+        };
+
+        let buffer = info.buffer();
+        let range = if info.range() == WHOLE_SIZE { buffer.size() - info.offset() } else { info.range() };
+        Ok(BufferView {
+            data: buffer.ptr(info.offset()),
+            format: info.format(),
+            texel_size: texel_size,
+            texels: range / texel_size,
+        })
+    }
+}
+
+pub struct Image {
+    image_type: ImageType,
+    layout: HostImageLayout,
+    data: *mut u8,
+    serial: u64,
+}
+
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Image {
+    pub fn image_type(&self) -> ImageType { self.image_type }
+    pub fn layout(&self) -> &HostImageLayout { &self.layout }
+    pub fn format(&self) -> Format { self.layout.format }
+
+    /// The address of the first byte of the image. Memory must be bound.
+    pub fn ptr(&self) -> *mut u8 { self.data }
+
+    /// Distinguishes the image from every other buffer or image created by the process.
+    pub fn serial(&self) -> u64 { self.serial }
+
+    /// A view of every mip level and array layer of the image in its own format, for transfer
+    /// commands.
+    pub fn view(&self, aspect: ImageAspectFlags) -> ImageView {
+        let view_type = match self.image_type {
+            ImageType::D1 => ImageViewType::D1Array,
+            ImageType::D3 => ImageViewType::D3,
+            _ => ImageViewType::D2Array,
+        };
+
+        ImageView {
+            image: ObjectRef::new(self),
+            data: self.data,
+            layout: self.layout,
+            view_type: view_type,
+            format: self.layout.format,
+            aspect: aspect,
+            components: identity_components(),
+            base_mip_level: 0,
+            mip_levels: self.layout.mip_levels,
+            base_array_layer: 0,
+            array_layers: self.layout.array_layers,
+        }
+    }
+}
+
+impl icd::Image<Cpu> for Image {
+    fn new(_device: &Device, info: ImageCreateInfo<Cpu>) -> Result<Self> {
+        if info.samples() != VK_SAMPLE_COUNT_1_BIT {
+            error!("vkCreateImage called with {:?} samples, but the device only supports one", info.samples());
+            return Err(Error::FormatNotSupported);
+        }
+
+        let layout = match HostImageLayout::new(info.format(), info.extent(), info.mip_levels(), info.array_layers()) {
+            Some(layout) => layout,
+            None => return Err(Error::FormatNotSupported),
+        };
+
+        Ok(Image {
+            image_type: info.image_type(),
+            layout: layout,
+            data: ptr::null_mut(),
+            serial: NEXT_SERIAL.fetch_add(1, Ordering::Relaxed) as u64,
+        })
+    }
+
+    fn get_memory_requirements(&self, _device: &Device) -> MemoryRequirements {
+        MemoryRequirements {
+            size: self.layout.size(),
+            alignment: RESOURCE_ALIGNMENT,
+            memory_types: 1,
+        }
+    }
+
+    fn bind_memory(&mut self, _device: &Device, memory: &DeviceMemory, offset: u64) -> Result<()> {
+        self.data = try!(bind(memory, offset, self.layout.size()));
+        Ok(())
+    }
+
+    fn get_subresource_layout(&self, _device: &Device, subresource: ImageSubresource<Cpu>) -> SubresourceLayout {
+        self.layout.subresource_layout(subresource.mip_level(), subresource.array_layer())
+    }
+}
+
+fn identity_components() -> ComponentMapping {
+    ComponentMapping {
+        r: ComponentSwizzle::Identity,
+        g: ComponentSwizzle::Identity,
+        b: ComponentSwizzle::Identity,
+        a: ComponentSwizzle::Identity,
+    }
+}
+
+/// A range of an image's subresources seen through a format and aspect.
+///
+/// Mip levels and array layers are numbered from the start of the view. The texels of 3D views
+/// are addressed by depth rather than layer.
+#[derive(Clone)]
+pub struct ImageView {
+    image: ObjectRef<Image>,
+    data: *mut u8,
+    layout: HostImageLayout,
+    view_type: ImageViewType,
+    format: Format,
+    aspect: ImageAspectFlags,
+    components: ComponentMapping,
+    base_mip_level: u32,
+    mip_levels: u32,
+    base_array_layer: u32,
+    array_layers: u32,
+}
+
+unsafe impl Send for ImageView {}
+unsafe impl Sync for ImageView {}
+
+impl ImageView {
+    pub fn view_type(&self) -> ImageViewType { self.view_type }
+    pub fn format(&self) -> Format { self.format }
+    pub fn aspect(&self) -> ImageAspectFlags { self.aspect }
+    pub fn components(&self) -> ComponentMapping { self.components }
+    pub fn mip_levels(&self) -> u32 { self.mip_levels }
+    pub fn array_layers(&self) -> u32 { self.array_layers }
+
+    /// The image viewed and the subresources of it in the view.
+    pub fn subresources(&self) -> (ObjectRef<Image>, ImageSubresourceRange) {
+        let range = ImageSubresourceRange {
+            aspect_mask: self.aspect,
+            base_mip_level: self.base_mip_level,
+            level_count: self.mip_levels,
+            base_array_layer: self.base_array_layer,
+            layer_count: self.array_layers,
+        };
+        (self.image, range)
+    }
+
+    /// Whether reads give integers rather than floats, which is also the case for stencil views of
+    /// depth/stencil images.
+    pub fn is_integer(&self) -> bool {
+        format::is_integer(self.format) || self.aspect == VK_IMAGE_ASPECT_STENCIL_BIT
+    }
+
+    pub fn extent(&self, mip_level: u32) -> Extent3D {
+        self.layout.mip_extent(self.base_mip_level + mip_level)
+    }
+
+    /// The view restricted to a range of its own subresources.
+    pub fn subview(&self, aspect: ImageAspectFlags, mip_levels: (u32, u32), array_layers: (u32, u32)) -> ImageView {
+        ImageView {
+            aspect: aspect,
+            base_mip_level: self.base_mip_level + mip_levels.0,
+            mip_levels: mip_levels.1,
+            base_array_layer: self.base_array_layer + array_layers.0,
+            array_layers: array_layers.1,
+            ..self.clone()
+        }
+    }
+
+    /// The address of a texel, or `None` if it is outside the view.
+    pub fn texel_ptr(&self, mip_level: u32, array_layer: u32, x: i32, y: i32, z: i32) -> Option<*mut u8> {
+        if mip_level >= self.mip_levels || array_layer >= self.array_layers || self.data.is_null() {
+            return None;
+        }
+        let extent = self.extent(mip_level);
+        if x < 0 || y < 0 || z < 0 || x as u32 >= extent.width || y as u32 >= extent.height || z as u32 >= extent.depth {
+            return None;
+        }
+
+        let offset = self.layout.offset(self.base_mip_level + mip_level, self.base_array_layer + array_layer, Offset3D { x: x, y: y, z: z });
+        Some(unsafe { self.data.offset(offset as isize) })
+    }
+
+    /// Reads a texel of the view's aspect, without swizzling. Texels outside the view read as zero.
+    pub fn read(&self, mip_level: u32, array_layer: u32, x: i32, y: i32, z: i32) -> Texel {
+        let data = match self.texel_ptr(mip_level, array_layer, x, y, z) {
+            Some(data) => data,
+            None => return [0; 4],
+        };
+
+        unsafe {
+            if self.aspect == VK_IMAGE_ASPECT_STENCIL_BIT {
+                let mut texel = format::default_texel(true);
+                texel[0] = format::read_stencil(self.format, data) as u32;
+                texel
+            } else {
+                format::read(self.format, data).unwrap_or([0; 4])
+            }
+        }
+    }
+
+    /// Writes every component of a texel of the view's aspect. Texels outside the view are left
+    /// alone.
+    pub fn write(&self, mip_level: u32, array_layer: u32, x: i32, y: i32, z: i32, texel: Texel) {
+        if let Some(data) = self.texel_ptr(mip_level, array_layer, x, y, z) {
+            unsafe {
+                if self.aspect == VK_IMAGE_ASPECT_STENCIL_BIT {
+                    format::write_stencil(self.format, data, texel[0] as u8);
+                } else if self.aspect == VK_IMAGE_ASPECT_DEPTH_BIT && format::has_stencil(self.format) {
+                    format::write_depth(self.format, data, f32::from_bits(texel[0]));
+                } else {
+                    format::write(self.format, data, texel);
+                }
+            }
+        }
+    }
+
+    /// Reads a texel with the view's component swizzle applied, as shaders see it.
+    pub fn fetch(&self, mip_level: u32, array_layer: u32, x: i32, y: i32, z: i32) -> Texel {
+        self.swizzle(self.read(mip_level, array_layer, x, y, z))
+    }
+
+    /// Applies the view's component swizzle to a texel read from it.
+    pub fn swizzle(&self, texel: Texel) -> Texel {
+        let one = if self.is_integer() { 1 } else { 1.0f32.to_bits() };
+        let select = |swizzle: ComponentSwizzle, identity: usize| match swizzle {
+            ComponentSwizzle::Zero => 0,
+            ComponentSwizzle::One => one,
+            ComponentSwizzle::R => texel[0],
+            ComponentSwizzle::G => texel[1],
+            ComponentSwizzle::B => texel[2],
+            ComponentSwizzle::A => texel[3],
+            _ => texel[identity],
+        };
+        [
+            select(self.components.r, 0),
+            select(self.components.g, 1),
+            select(self.components.b, 2),
+            select(self.components.a, 3),
+        ]
+    }
+
+    /// The depth and stencil at a texel of a depth/stencil attachment view, as far as the format
+    /// has them.
+    pub fn read_depth_stencil(&self, array_layer: u32, x: i32, y: i32) -> (Option<f32>, Option<u8>) {
+        match self.texel_ptr(0, array_layer, x, y, 0) {
+            Some(data) => unsafe {
+                (
+                    if format::has_depth(self.format) { Some(format::read_depth(self.format, data)) } else { None },
+                    if format::has_stencil(self.format) { Some(format::read_stencil(self.format, data)) } else { None },
+                )
+            },
+            None => (None, None),
+        }
+    }
+
+    pub fn write_depth(&self, array_layer: u32, x: i32, y: i32, depth: f32) {
+        if let Some(data) = self.texel_ptr(0, array_layer, x, y, 0) {
+            if format::has_depth(self.format) {
+                unsafe { format::write_depth(self.format, data, depth) };
+            }
+        }
+    }
+
+    pub fn write_stencil(&self, array_layer: u32, x: i32, y: i32, stencil: u8) {
+        if let Some(data) = self.texel_ptr(0, array_layer, x, y, 0) {
+            if format::has_stencil(self.format) {
+                unsafe { format::write_stencil(self.format, data, stencil) };
+            }
+        }
+    }
+}
+
+impl icd::ImageView<Cpu> for ImageView {
+    fn new(_device: &Device, info: ImageViewCreateInfo<Cpu>) -> Result<Self> {
+        let image = info.image();
+        let range = info.subresource_range();
+
+        let mip_levels = if range.level_count == REMAINING_MIP_LEVELS {
+            image.layout.mip_levels - range.base_mip_level
+        } else {
+            range.level_count
+        };
+        let array_layers = if range.layer_count == REMAINING_ARRAY_LAYERS {
+            image.layout.array_layers - range.base_array_layer
+        } else {
+            range.layer_count
+        };
+
+        if !format::is_supported(info.format()) {
+            warn!("image view of format {:?} can only be used in transfers", info.format());
+        }
+
+        Ok(ImageView {
+            image: ObjectRef::new(image),
+            data: image.data,
+            layout: image.layout,
+            view_type: info.view_type(),
+            format: info.format(),
+            aspect: range.aspect_mask,
+            components: info.components(),
+            base_mip_level: range.base_mip_level,
+            mip_levels: cmp::max(mip_levels, 1),
+            base_array_layer: range.base_array_layer,
+            array_layers: cmp::max(array_layers, 1),
+        })
+    }
+}
@@ -0,0 +1,344 @@
+//! Pipeline layouts, shader modules and pipelines.
+//!
+//! Graphics pipelines keep their fixed-function state in plain structs the rasterizer reads
+//! directly. State the pipeline makes dynamic is resolved at each draw against what the command
+//! buffer has set.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+use vk_middle::spirv::{self, SpirvModule, LayoutBinding};
+use vk_middle::spirv::interpret::Program;
+
+use std::sync::Arc;
+
+use super::{Cpu, Device};
+use descriptor::DescriptorSetLayout;
+use shader::{self, Shader};
+
+pub struct PipelineLayout {
+    set_layouts: Vec<DescriptorSetLayout>,
+    push_constant_ranges: Vec<PushConstantRange>,
+    /// Every binding of every set, for checking shaders against.
+    bindings: Vec<LayoutBinding>,
+}
+
+impl PipelineLayout {
+    pub fn set_layouts(&self) -> &[DescriptorSetLayout] { &self.set_layouts }
+
+    /// The number of bytes of push constants the layout's ranges cover.
+    pub fn push_constant_size(&self) -> u32 {
+        self.push_constant_ranges.iter().map(|range| range.offset + range.size).max().unwrap_or(0)
+    }
+}
+
+impl icd::PipelineLayout<Cpu> for PipelineLayout {
+    fn new(_device: &Device, info: PipelineLayoutCreateInfo<Cpu>) -> Result<Self> {
+        let set_layouts: Vec<DescriptorSetLayout> = info.set_layouts().cloned().collect();
+        let mut bindings = Vec::new();
+        for (set, layout) in set_layouts.iter().enumerate() {
+            for binding in layout.bindings() {
+                bindings.push(LayoutBinding {
+                    set: set as u32,
+                    binding: binding.binding,
+                    descriptor_type: binding.descriptor_type,
+                    count: binding.count,
+                    stages: binding.stages,
+                });
+            }
+        }
+
+        Ok(PipelineLayout {
+            set_layouts: set_layouts,
+            push_constant_ranges: info.push_constant_ranges().collect(),
+            bindings: bindings,
+        })
+    }
+}
+
+/// Shader modules are parsed into a program for the interpreter up front, so that pipelines
+/// made from the same module share it unless they specialize it.
+pub struct ShaderModule {
+    spirv: SpirvModule,
+    program: Arc<Program>,
+}
+
+impl ShaderModule {
+    pub fn spirv(&self) -> &SpirvModule { &self.spirv }
+    pub fn program(&self) -> &Arc<Program> { &self.program }
+}
+
+impl icd::ShaderModule<Cpu> for ShaderModule {
+    fn new(_device: &Device, info: ShaderModuleCreateInfo<Cpu>) -> Result<Self> {
+        Ok(ShaderModule {
+            spirv: info.module().clone(),
+            program: Arc::new(try!(Program::new(info.code()))),
+        })
+    }
+}
+
+/// Pipelines are compiled from nothing but their create info, so there is nothing to cache, but
+/// cache data still has to carry the device's header.
+pub struct PipelineCache {
+    data: PipelineCacheData,
+}
+
+impl icd::PipelineCache<Cpu> for PipelineCache {
+    fn new(_device: &Device, info: PipelineCacheCreateInfo<Cpu>) -> Result<Self> {
+        Ok(PipelineCache { data: PipelineCacheData::new(&info) })
+    }
+
+    fn data(&self) -> Option<&PipelineCacheData> { Some(&self.data) }
+}
+
+/// The state a graphics pipeline may leave to `vkCmdSet*` commands.
+#[derive(Clone, Copy, Debug)]
+pub struct DynamicValues {
+    pub viewport: Viewport,
+    pub scissor: Rect2D,
+    pub line_width: f32,
+    /// The constant factor, clamp and slope factor.
+    pub depth_bias: (f32, f32, f32),
+    pub blend_constants: [f32; 4],
+    pub depth_bounds: (f32, f32),
+    /// The front face's value, then the back face's.
+    pub stencil_compare_mask: [u32; 2],
+    pub stencil_write_mask: [u32; 2],
+    pub stencil_reference: [u32; 2],
+}
+
+impl Default for DynamicValues {
+    fn default() -> DynamicValues {
+        DynamicValues {
+            viewport: Viewport { x: 0.0, y: 0.0, width: 0.0, height: 0.0, min_depth: 0.0, max_depth: 1.0 },
+            scissor: Rect2D { offset: Offset2D { x: 0, y: 0 }, extent: Extent2D { width: 0, height: 0 } },
+            line_width: 1.0,
+            depth_bias: (0.0, 0.0, 0.0),
+            blend_constants: [0.0; 4],
+            depth_bounds: (0.0, 1.0),
+            stencil_compare_mask: [!0; 2],
+            stencil_write_mask: [!0; 2],
+            stencil_reference: [0; 2],
+        }
+    }
+}
+
+pub struct GraphicsPipeline {
+    pub vertex_shader: Box<Shader>,
+    pub fragment_shader: Option<Box<Shader>>,
+
+    pub bindings: Vec<VertexInputBindingDescription>,
+    pub attributes: Vec<VertexInputAttributeDescription>,
+    pub topology: PrimitiveTopology,
+    pub primitive_restart: bool,
+
+    pub rasterizer_discard: bool,
+    pub depth_clamp: bool,
+    pub polygon_mode: PolygonMode,
+    pub cull_mode: CullModeFlags,
+    pub front_face: FrontFace,
+    pub depth_bias: bool,
+
+    pub sample_mask: u32,
+    pub alpha_to_coverage: bool,
+    pub alpha_to_one: bool,
+
+    pub depth_compare_op: Option<CompareOp>,
+    pub depth_write: bool,
+    pub depth_bounds_test: bool,
+    /// The front face's state, then the back face's, with masks and references in `DynamicValues`.
+    pub stencil: Option<[StencilOpState; 2]>,
+
+    pub logic_op: Option<LogicOp>,
+    pub blend_attachments: Vec<PipelineColorBlendAttachmentState>,
+
+    pub subpass: u32,
+
+    static_values: DynamicValues,
+    dynamic_states: Vec<DynamicState>,
+}
+
+impl GraphicsPipeline {
+    /// The pipeline's static state, with its dynamic state taken from `dynamic` instead.
+    pub fn values(&self, dynamic: &DynamicValues) -> DynamicValues {
+        let mut values = self.static_values;
+        for &state in &self.dynamic_states {
+            match state {
+                DynamicState::Viewport => values.viewport = dynamic.viewport,
+                DynamicState::Scissor => values.scissor = dynamic.scissor,
+                DynamicState::LineWidth => values.line_width = dynamic.line_width,
+                DynamicState::DepthBias => values.depth_bias = dynamic.depth_bias,
+                DynamicState::BlendConstants => values.blend_constants = dynamic.blend_constants,
+                DynamicState::DepthBounds => values.depth_bounds = dynamic.depth_bounds,
+                DynamicState::StencilCompareMask => values.stencil_compare_mask = dynamic.stencil_compare_mask,
+                DynamicState::StencilWriteMask => values.stencil_write_mask = dynamic.stencil_write_mask,
+                DynamicState::StencilReference => values.stencil_reference = dynamic.stencil_reference,
+                DynamicState::Other(_) => {},
+            }
+        }
+        values
+    }
+}
+
+pub struct ComputePipeline {
+    pub shader: Box<Shader>,
+}
+
+pub enum Pipeline {
+    Graphics(GraphicsPipeline),
+    Compute(ComputePipeline),
+}
+
+impl icd::Pipeline<Cpu> for Pipeline {
+    fn create_graphics(
+        _device: &Device,
+        _pipeline_cache: Option<&PipelineCache>,
+        create_infos: &[GraphicsPipelineCreateInfo<Cpu>],
+    ) -> Vec<Result<Self>> {
+        create_infos.iter().map(|info| create_graphics(info).map(Pipeline::Graphics)).collect()
+    }
+
+    fn create_compute(
+        _device: &Device,
+        _pipeline_cache: Option<&PipelineCache>,
+        create_infos: &[ComputePipelineCreateInfo<Cpu>],
+    ) -> Vec<Result<Self>> {
+        create_infos.iter()
+            .map(|info| {
+                let stage = info.stage();
+                let shader = try!(shader::compile(&stage));
+                try!(check_layout(&stage, info.layout()));
+                Ok(Pipeline::Compute(ComputePipeline { shader: shader }))
+            })
+            .collect()
+    }
+}
+
+/// Checks that `layout` has every descriptor and push constant the entry point of `stage` uses,
+/// once `shader::compile` has found the entry point.
+fn check_layout(stage: &PipelineShaderStageCreateInfo<Cpu>, layout: &PipelineLayout) -> Result<()> {
+    let name = stage.entry_point().to_string_lossy();
+    let entry_point = spirv::execution_model(stage.stage()).and_then(|model| stage.module().spirv().entry_point(&name, model));
+    match entry_point {
+        Some(entry_point) => entry_point.check_layout(&layout.bindings, &layout.push_constant_ranges),
+        None => Ok(()),
+    }
+}
+
+fn create_graphics(info: &GraphicsPipelineCreateInfo<Cpu>) -> Result<GraphicsPipeline> {
+    let mut vertex_shader = None;
+    let mut fragment_shader = None;
+    for stage in info.stages() {
+        let shader = try!(shader::compile(&stage));
+        try!(check_layout(&stage, info.layout()));
+        if stage.stage() == VK_SHADER_STAGE_VERTEX_BIT {
+            vertex_shader = Some(shader);
+        } else if stage.stage() == VK_SHADER_STAGE_FRAGMENT_BIT {
+            fragment_shader = Some(shader);
+        } else {
+            error!("pipeline has a {:?} stage, which the device does not support", stage.stage());
+            return Err(Error::FeatureNotPresent);
+        }
+    }
+    let vertex_shader = match vertex_shader {
+        Some(shader) => shader,
+        None => {
+            error!("graphics pipeline has no vertex shader");
+            return Err(Error::InitializationFailed);
+        },
+    };
+
+    let vertex_input = info.vertex_input_state();
+    let input_assembly = info.input_assembly_state();
+    let rasterization = info.rasterization_state();
+
+    let mut static_values = DynamicValues::default();
+    static_values.line_width = rasterization.line_width();
+    static_values.depth_bias = (
+        rasterization.depth_bias_constant_factor(),
+        rasterization.depth_bias_clamp(),
+        rasterization.depth_bias_slope_factor(),
+    );
+
+    if let Some(viewport_state) = info.viewport_state() {
+        if let Some(viewport) = viewport_state.viewports().next() {
+            static_values.viewport = viewport;
+        }
+        if let Some(scissor) = viewport_state.scissors().next() {
+            static_values.scissor = scissor;
+        }
+    }
+
+    let mut pipeline = GraphicsPipeline {
+        vertex_shader: vertex_shader,
+        fragment_shader: fragment_shader,
+
+        bindings: vertex_input.bindings().collect(),
+        attributes: vertex_input.attributes().collect(),
+        topology: input_assembly.topology(),
+        primitive_restart: input_assembly.primitive_restart_enable(),
+
+        rasterizer_discard: rasterization.rasterizer_discard_enable(),
+        depth_clamp: rasterization.depth_clamp_enable(),
+        polygon_mode: rasterization.polygon_mode(),
+        cull_mode: rasterization.cull_mode(),
+        front_face: rasterization.front_face(),
+        depth_bias: rasterization.depth_bias_enable(),
+
+        sample_mask: !0,
+        alpha_to_coverage: false,
+        alpha_to_one: false,
+
+        depth_compare_op: None,
+        depth_write: false,
+        depth_bounds_test: false,
+        stencil: None,
+
+        logic_op: None,
+        blend_attachments: Vec::new(),
+
+        subpass: info.subpass(),
+
+        static_values: static_values,
+        dynamic_states: info.dynamic_states().collect(),
+    };
+
+    match pipeline.topology {
+        PrimitiveTopology::PatchList | PrimitiveTopology::Other(_) => {
+            error!("pipeline has topology {:?}, which the device does not support", pipeline.topology);
+            return Err(Error::FeatureNotPresent);
+        },
+        _ => {},
+    }
+
+    if let Some(multisample) = info.multisample_state() {
+        if let Some(mask) = multisample.sample_mask() {
+            pipeline.sample_mask = mask[0];
+        }
+        pipeline.alpha_to_coverage = multisample.alpha_to_coverage_enable();
+        pipeline.alpha_to_one = multisample.alpha_to_one_enable();
+    }
+
+    if let Some(depth_stencil) = info.depth_stencil_state() {
+        pipeline.depth_compare_op = depth_stencil.depth_compare_op();
+        pipeline.depth_write = depth_stencil.depth_write_enable();
+        if let Some(bounds) = depth_stencil.depth_bounds() {
+            pipeline.depth_bounds_test = true;
+            pipeline.static_values.depth_bounds = bounds;
+        }
+        if depth_stencil.stencil_test_enable() {
+            let (front, back) = (depth_stencil.front(), depth_stencil.back());
+            pipeline.stencil = Some([front, back]);
+            pipeline.static_values.stencil_compare_mask = [front.compare_mask, back.compare_mask];
+            pipeline.static_values.stencil_write_mask = [front.write_mask, back.write_mask];
+            pipeline.static_values.stencil_reference = [front.reference, back.reference];
+        }
+    }
+
+    if let Some(color_blend) = info.color_blend_state() {
+        pipeline.logic_op = color_blend.logic_op();
+        pipeline.blend_attachments = color_blend.attachments().collect();
+        pipeline.static_values.blend_constants = color_blend.blend_constants();
+    }
+
+    Ok(pipeline)
+}
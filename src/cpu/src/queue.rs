@@ -0,0 +1,625 @@
+//! Executes recorded command buffers as they are submitted.
+//!
+//! Each submission replays its command buffers in order against an `Executor`, which holds the
+//! state commands bind and hands draws to the rasterizer and dispatches to the compute shader.
+//! Everything finishes before `submit` returns, so barriers and semaphores have nothing to wait
+//! for and fences are signaled right away.
+//!
+//! When the device validates submissions, the image layouts of each submission are checked
+//! against those left by earlier submissions before anything runs, and so are its memory
+//! accesses when it validates synchronization.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+use vk_middle::icd::recorded::{Command, CommandStream, ObjectRef};
+use vk_middle::icd::query::write_query_results;
+use vk_middle::icd::host::{self, copy_buffer_image, copy_image};
+use vk_middle::icd::layout::{LayoutSource, LayoutTracker};
+use vk_middle::icd::hazard::{HazardSource, HazardTracker};
+
+use std::{ptr, slice};
+use std::sync::{Arc, Mutex};
+
+use super::Cpu;
+use descriptor::Descriptor;
+use memory::{Buffer, Image};
+use pipeline::{Pipeline, DynamicValues};
+use raster::{Counters, Draw};
+use render_pass::{RenderPass, Framebuffer};
+use shader::Resources;
+use transfer;
+
+pub struct Queue {
+    validation: Option<Arc<Mutex<Validation>>>,
+}
+
+impl Queue {
+    pub(crate) fn new(validation: Option<Arc<Mutex<Validation>>>) -> Queue {
+        Queue { validation: validation }
+    }
+}
+
+impl icd::Queue<Cpu> for Queue {
+    fn submit(&mut self, submits: SubmitInfos<Cpu>, fence: Option<&mut CpuFence>) -> Result<()> {
+        if let Some(ref validation) = self.validation {
+            try!(validation.lock().unwrap().check(&submits));
+        }
+
+        for submit in submits.iter() {
+            for command_buffer in submit.command_buffers() {
+                let mut executor = Executor::new();
+                unsafe { executor.execute(command_buffer.stream()) };
+            }
+        }
+
+        if let Some(fence) = fence {
+            fence.signal();
+        }
+        Ok(())
+    }
+
+    fn wait_idle(&self) -> Result<()> { Ok(()) }
+    fn is_idle(&self) -> bool { true }
+}
+
+/// What submissions are checked against.
+pub(crate) struct Validation {
+    layouts: Option<LayoutTracker<Cpu>>,
+    hazards: Option<HazardTracker<Cpu>>,
+}
+
+impl Validation {
+    pub(crate) fn new(layouts: bool, hazards: bool) -> Validation {
+        Validation {
+            layouts: if layouts { Some(LayoutTracker::new(true)) } else { None },
+            hazards: if hazards { Some(HazardTracker::new()) } else { None },
+        }
+    }
+
+    /// Follows the submissions, which are refused if they use images in the wrong layouts or
+    /// access memory without the synchronization they need. A refused submission leaves the
+    /// tracked state as it was.
+    fn check(&mut self, submits: &SubmitInfos<Cpu>) -> Result<()> {
+        let mut layouts = self.layouts.clone();
+        let mut hazards = self.hazards.clone();
+        for submit in submits.iter() {
+            if let Some(ref mut layouts) = layouts {
+                for command_buffer in submit.command_buffers() {
+                    layouts.execute(&Objects, command_buffer.stream());
+                }
+            }
+            if let Some(ref mut hazards) = hazards {
+                hazards.submit(&Objects, &submit);
+            }
+        }
+
+        if layouts.as_mut().map_or(false, |x| !x.take_mismatches().is_empty()) {
+            error!("vkQueueSubmit refused, since it uses images in the wrong layouts");
+            return Err(Error::ValidationFailed);
+        }
+        if hazards.as_mut().map_or(false, |x| !x.take_hazards().is_empty()) {
+            error!("vkQueueSubmit refused, since it accesses memory without the synchronization it needs");
+            return Err(Error::ValidationFailed);
+        }
+        self.layouts = layouts;
+        self.hazards = hazards;
+        Ok(())
+    }
+}
+
+/// Answers for the objects themselves, which keep what the trackers need.
+struct Objects;
+
+impl LayoutSource<Cpu> for Objects {
+    fn image_subresource_counts(&self, image: &Image) -> (u32, u32) {
+        (image.layout().mip_levels, image.layout().array_layers)
+    }
+
+    fn render_pass_attachment_usage(&self, render_pass: &RenderPass) -> Vec<Option<AttachmentUsage>> {
+        render_pass.attachment_usage().to_vec()
+    }
+
+    fn framebuffer_attachments(&self, framebuffer: &Framebuffer) -> Vec<(ObjectRef<Image>, ImageSubresourceRange)> {
+        framebuffer.attachments().iter().map(|x| x.subresources()).collect()
+    }
+
+    fn image_serial(&self, image: &Image) -> u64 { image.serial() }
+}
+
+impl HazardSource<Cpu> for Objects {
+    fn render_pass_dependencies(&self, render_pass: &RenderPass) -> Vec<SubpassDependency> {
+        render_pass.dependencies().to_vec()
+    }
+
+    fn buffer_serial(&self, buffer: &Buffer) -> u64 { buffer.serial() }
+}
+
+/// What is bound to one pipeline bind point.
+struct Bound {
+    pipeline: Option<ObjectRef<Pipeline>>,
+    resources: Resources,
+}
+
+impl Bound {
+    fn new() -> Bound {
+        Bound {
+            pipeline: None,
+            resources: Resources { sets: Vec::new(), push_constants: Vec::new() },
+        }
+    }
+}
+
+struct RenderPassState {
+    render_pass: ObjectRef<RenderPass>,
+    framebuffer: ObjectRef<Framebuffer>,
+    render_area: Rect2D,
+    clear_values: Vec<ClearValue>,
+    subpass: u32,
+}
+
+/// A query between `vkCmdBeginQuery` and `vkCmdEndQuery`, with what it has counted so far.
+struct ActiveQuery {
+    pool: ObjectRef<HostQueryPool>,
+    query: u32,
+    counters: Counters,
+}
+
+struct Executor {
+    graphics: Bound,
+    compute: Bound,
+    index_buffer: Option<(ObjectRef<Buffer>, u64, IndexType)>,
+    /// The address and remaining size of the buffer bound to each vertex input binding.
+    vertex_buffers: Vec<Option<(*const u8, u64)>>,
+    dynamic: DynamicValues,
+    render_pass: Option<RenderPassState>,
+    queries: Vec<ActiveQuery>,
+}
+
+impl Executor {
+    fn new() -> Executor {
+        Executor {
+            graphics: Bound::new(),
+            compute: Bound::new(),
+            index_buffer: None,
+            vertex_buffers: Vec::new(),
+            dynamic: DynamicValues::default(),
+            render_pass: None,
+            queries: Vec::new(),
+        }
+    }
+
+    unsafe fn execute(&mut self, stream: &CommandStream<Cpu>) {
+        for command in stream.commands() {
+            match *command {
+                Command::BindPipeline { bind_point, pipeline } => {
+                    self.bound(bind_point).pipeline = Some(pipeline);
+                },
+                Command::BindDescriptorSets { bind_point, first_set, sets, dynamic_offsets, .. } => {
+                    let mut dynamic_offsets = stream.get(dynamic_offsets).iter();
+                    let resources = &mut self.bound(bind_point).resources;
+                    for (i, set) in stream.get(sets).iter().enumerate() {
+                        let mut bindings = set.get().snapshot();
+                        for binding in &mut bindings {
+                            match binding.descriptor_type {
+                                DescriptorType::UniformBufferDynamic | DescriptorType::StorageBufferDynamic => {},
+                                _ => continue,
+                            }
+                            for descriptor in &mut binding.descriptors {
+                                let offset = dynamic_offsets.next().cloned().unwrap_or(0);
+                                if let Descriptor::Buffer { ref mut data, .. } = *descriptor {
+                                    *data = data.offset(offset as isize);
+                                }
+                            }
+                        }
+
+                        let index = first_set as usize + i;
+                        while resources.sets.len() <= index {
+                            resources.sets.push(None);
+                        }
+                        resources.sets[index] = Some(bindings);
+                    }
+                },
+                Command::BindIndexBuffer { buffer, offset, index_type } => {
+                    self.index_buffer = Some((buffer, offset, index_type));
+                },
+                Command::BindVertexBuffers { first_binding, buffers, offsets } => {
+                    for (i, (buffer, &offset)) in stream.get(buffers).iter().zip(stream.get(offsets)).enumerate() {
+                        let index = first_binding as usize + i;
+                        while self.vertex_buffers.len() <= index {
+                            self.vertex_buffers.push(None);
+                        }
+                        let buffer = buffer.get();
+                        self.vertex_buffers[index] = Some((buffer.ptr(offset) as *const u8, buffer.size().saturating_sub(offset)));
+                    }
+                },
+                Command::PushConstants { offset, data, .. } => {
+                    let data = stream.get(data);
+                    for bound in &mut [&mut self.graphics, &mut self.compute] {
+                        let push_constants = &mut bound.resources.push_constants;
+                        let end = offset as usize + data.len();
+                        if push_constants.len() < end {
+                            push_constants.resize(end, 0);
+                        }
+                        push_constants[offset as usize..end].copy_from_slice(data);
+                    }
+                },
+
+                // Only the first viewport and scissor are used, as the device has one of each
+                Command::SetViewport { first_viewport, viewports } => {
+                    if let (0, Some(&viewport)) = (first_viewport, stream.get(viewports).first()) {
+                        self.dynamic.viewport = viewport;
+                    }
+                },
+                Command::SetScissor { first_scissor, scissors } => {
+                    if let (0, Some(&scissor)) = (first_scissor, stream.get(scissors).first()) {
+                        self.dynamic.scissor = scissor;
+                    }
+                },
+                Command::SetLineWidth(width) => self.dynamic.line_width = width,
+                Command::SetDepthBias { constant_factor, clamp, slope_factor } => {
+                    self.dynamic.depth_bias = (constant_factor, clamp, slope_factor);
+                },
+                Command::SetBlendConstants(constants) => self.dynamic.blend_constants = constants,
+                Command::SetDepthBounds { min_depth_bounds, max_depth_bounds } => {
+                    self.dynamic.depth_bounds = (min_depth_bounds, max_depth_bounds);
+                },
+                Command::SetStencilCompareMask { faces, compare_mask } => {
+                    set_faces(&mut self.dynamic.stencil_compare_mask, faces, compare_mask);
+                },
+                Command::SetStencilWriteMask { faces, write_mask } => {
+                    set_faces(&mut self.dynamic.stencil_write_mask, faces, write_mask);
+                },
+                Command::SetStencilReference { faces, reference } => {
+                    set_faces(&mut self.dynamic.stencil_reference, faces, reference);
+                },
+
+                Command::Draw { vertex_count, instance_count, first_vertex, first_instance } => {
+                    self.draw(vertex_count, instance_count, first_vertex, first_instance);
+                },
+                Command::DrawIndexed { index_count, instance_count, first_index, vertex_offset, first_instance } => {
+                    self.draw_indexed(index_count, instance_count, first_index, vertex_offset, first_instance);
+                },
+                Command::DrawIndirect { buffer, offset, draw_count, stride } => {
+                    for i in 0..draw_count {
+                        if let Some(words) = read_words(buffer.get(), offset + i as u64 * stride as u64, 4) {
+                            self.draw(words[0], words[1], words[2], words[3]);
+                        }
+                    }
+                },
+                Command::DrawIndexedIndirect { buffer, offset, draw_count, stride } => {
+                    for i in 0..draw_count {
+                        if let Some(words) = read_words(buffer.get(), offset + i as u64 * stride as u64, 5) {
+                            self.draw_indexed(words[0], words[1], words[2], words[3] as i32, words[4]);
+                        }
+                    }
+                },
+                Command::Dispatch { group_count_x, group_count_y, group_count_z } => {
+                    self.dispatch([group_count_x, group_count_y, group_count_z]);
+                },
+                Command::DispatchIndirect { buffer, offset } => {
+                    if let Some(words) = read_words(buffer.get(), offset, 3) {
+                        self.dispatch([words[0], words[1], words[2]]);
+                    }
+                },
+
+                Command::CopyBuffer { src, dst, regions } => {
+                    for region in stream.get(regions) {
+                        ptr::copy(src.get().ptr(region.src_offset), dst.get().ptr(region.dst_offset), region.size as usize);
+                    }
+                },
+                Command::CopyImage { src, dst, regions, .. } => {
+                    let (src, dst) = (src.get(), dst.get());
+                    for region in stream.get(regions) {
+                        copy_image(src.ptr(), src.layout(), dst.ptr(), dst.layout(), region);
+                    }
+                },
+                Command::BlitImage { src, dst, regions, filter, .. } => {
+                    for region in stream.get(regions) {
+                        transfer::blit_image(src.get(), dst.get(), region, filter);
+                    }
+                },
+                Command::CopyBufferToImage { src, dst, regions, .. } => {
+                    let (src, dst) = (src.get(), dst.get());
+                    for region in stream.get(regions) {
+                        copy_buffer_image(src.ptr(0), dst.ptr(), dst.layout(), region, true);
+                    }
+                },
+                Command::CopyImageToBuffer { src, dst, regions, .. } => {
+                    let (src, dst) = (src.get(), dst.get());
+                    for region in stream.get(regions) {
+                        copy_buffer_image(dst.ptr(0), src.ptr(), src.layout(), region, false);
+                    }
+                },
+                Command::UpdateBuffer { dst, offset, data } => {
+                    let data = stream.get(data);
+                    ptr::copy_nonoverlapping(data.as_ptr(), dst.get().ptr(offset), data.len());
+                },
+                Command::FillBuffer { dst, offset, size, data } => {
+                    let dst = dst.get();
+                    let size = if size == WHOLE_SIZE { (dst.size() - offset) & !3 } else { size };
+                    let words = dst.ptr(offset) as *mut u32;
+                    for i in 0..(size / 4) {
+                        *words.offset(i as isize) = data;
+                    }
+                },
+                Command::ClearColorImage { image, color, ranges, .. } => {
+                    for range in stream.get(ranges) {
+                        transfer::clear_color_image(image.get(), &color, range);
+                    }
+                },
+                Command::ClearDepthStencilImage { image, depth_stencil, ranges, .. } => {
+                    for range in stream.get(ranges) {
+                        transfer::clear_depth_stencil_image(image.get(), &depth_stencil, range);
+                    }
+                },
+                Command::ClearAttachments { attachments, rects } => {
+                    let state = match self.render_pass {
+                        Some(ref state) => state,
+                        None => continue,
+                    };
+                    let target = state.render_pass.get().target(state.subpass, state.framebuffer.get(), state.render_area);
+                    for attachment in stream.get(attachments) {
+                        let view = if attachment.aspect_mask.contains(VK_IMAGE_ASPECT_COLOR_BIT) {
+                            target.colors.get(attachment.color_attachment as usize).and_then(|&view| view)
+                        } else {
+                            target.depth_stencil
+                        };
+                        if let Some(view) = view {
+                            for rect in stream.get(rects) {
+                                let layers = (rect.base_array_layer, rect.layer_count);
+                                transfer::clear_attachment(view, attachment.aspect_mask, &attachment.clear_value, rect.rect, layers);
+                            }
+                        }
+                    }
+                },
+                Command::ResolveImage { src, dst, regions, .. } => {
+                    // Images have one sample, so resolving is copying
+                    let (src, dst) = (src.get(), dst.get());
+                    for region in stream.get(regions) {
+                        let copy = ImageCopy {
+                            src_subresource: region.src_subresource,
+                            src_offset: region.src_offset,
+                            dst_subresource: region.dst_subresource,
+                            dst_offset: region.dst_offset,
+                            extent: region.extent,
+                        };
+                        copy_image(src.ptr(), src.layout(), dst.ptr(), dst.layout(), &copy);
+                    }
+                },
+
+                Command::SetEvent { event, .. } => event.get().set_signaled(true),
+                Command::ResetEvent { event, .. } => event.get().set_signaled(false),
+
+                Command::BeginQuery { pool, query, .. } => {
+                    self.queries.push(ActiveQuery { pool: pool, query: query, counters: Counters::default() });
+                },
+                Command::EndQuery { pool, query } => {
+                    if let Some(index) = self.queries.iter().position(|x| x.pool == pool && x.query == query) {
+                        let active = self.queries.remove(index);
+                        let pool = pool.get();
+                        pool.write(query, Some(query_values(pool, &active.counters)));
+                    }
+                },
+                Command::ResetQueryPool { pool, first_query, query_count } => {
+                    for query in first_query..(first_query + query_count) {
+                        pool.get().write(query, None);
+                    }
+                },
+                Command::WriteTimestamp { pool, query, .. } => {
+                    let mut values = [0; MAX_QUERY_VALUES];
+                    values[0] = host::timestamp();
+                    pool.get().write(query, Some(values));
+                },
+                Command::CopyQueryPoolResults { pool, first_query, query_count, dst, offset, stride, flags } => {
+                    let pool = pool.get();
+                    let dst = dst.get();
+                    let data = slice::from_raw_parts_mut(dst.ptr(offset), (dst.size() - offset) as usize);
+                    if write_query_results(&pool.read(first_query..(first_query + query_count)), pool.value_count(), data, stride as usize, flags).is_err() {
+                        error!("vkCmdCopyQueryPoolResults writes past the end of its buffer");
+                    }
+                },
+
+                Command::BeginRenderPass { render_pass, framebuffer, render_area, clear_values, .. } => {
+                    let state = RenderPassState {
+                        render_pass: render_pass,
+                        framebuffer: framebuffer,
+                        render_area: render_area,
+                        clear_values: stream.get(clear_values).to_vec(),
+                        subpass: 0,
+                    };
+                    render_pass.get().begin_subpass(0, framebuffer.get(), render_area, &state.clear_values);
+                    self.render_pass = Some(state);
+                },
+                Command::NextSubpass(_) => {
+                    if let Some(ref mut state) = self.render_pass {
+                        let (render_pass, framebuffer) = (state.render_pass.get(), state.framebuffer.get());
+                        render_pass.end_subpass(state.subpass, framebuffer, state.render_area);
+                        state.subpass += 1;
+                        render_pass.begin_subpass(state.subpass, framebuffer, state.render_area, &state.clear_values);
+                    }
+                },
+                Command::EndRenderPass => {
+                    if let Some(state) = self.render_pass.take() {
+                        state.render_pass.get().end_subpass(state.subpass, state.framebuffer.get(), state.render_area);
+                    }
+                },
+
+                // Secondary command buffers run with the executing buffer's state, so those that
+                // continue a render pass draw into it
+                Command::ExecuteCommands(command_buffers) => {
+                    for command_buffer in stream.get(command_buffers) {
+                        self.execute(command_buffer.get().stream());
+                    }
+                },
+
+                Command::WaitEvents { .. } | Command::PipelineBarrier { .. } => {},
+            }
+        }
+    }
+
+    fn bound(&mut self, bind_point: PipelineBindPoint) -> &mut Bound {
+        match bind_point {
+            PipelineBindPoint::Compute => &mut self.compute,
+            _ => &mut self.graphics,
+        }
+    }
+
+    unsafe fn draw(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) {
+        let vertices: Vec<_> = (first_vertex..(first_vertex + vertex_count)).map(Some).collect();
+        self.run_draw(&vertices, first_instance, instance_count);
+    }
+
+    unsafe fn draw_indexed(&mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32) {
+        let (buffer, offset, index_type) = match self.index_buffer {
+            Some((buffer, offset, index_type)) => (buffer.get(), offset, index_type),
+            None => {
+                error!("indexed draw without an index buffer bound");
+                return;
+            },
+        };
+        let primitive_restart = match self.graphics.pipeline.map(|x| x.get()) {
+            Some(&Pipeline::Graphics(ref pipeline)) => pipeline.primitive_restart,
+            _ => false,
+        };
+
+        let (size, restart) = match index_type {
+            IndexType::Uint16 => (2, 0xFFFF),
+            _ => (4, 0xFFFFFFFF),
+        };
+        let data = match buffer.range(offset + first_index as u64 * size, index_count as u64 * size) {
+            Some(data) => data as *const u8,
+            None => {
+                error!("indexed draw reads past the end of the index buffer");
+                return;
+            },
+        };
+
+        let vertices: Vec<_> = (0..index_count as isize)
+            .map(|i| {
+                let index = if size == 2 {
+                    ptr::read_unaligned(data.offset(i * 2) as *const u16) as u32
+                } else {
+                    ptr::read_unaligned(data.offset(i * 4) as *const u32)
+                };
+                if primitive_restart && index == restart {
+                    None
+                } else {
+                    Some((index as i32).wrapping_add(vertex_offset) as u32)
+                }
+            })
+            .collect();
+        self.run_draw(&vertices, first_instance, instance_count);
+    }
+
+    unsafe fn run_draw(&mut self, vertices: &[Option<u32>], first_instance: u32, instance_count: u32) {
+        let pipeline = match self.graphics.pipeline.map(|x| x.get()) {
+            Some(&Pipeline::Graphics(ref pipeline)) => pipeline,
+            _ => {
+                error!("draw without a graphics pipeline bound");
+                return;
+            },
+        };
+        let mut counters = Counters::default();
+        {
+            let state = match self.render_pass {
+                Some(ref state) => state,
+                None => {
+                    error!("draw outside of a render pass");
+                    return;
+                },
+            };
+
+            let target = state.render_pass.get().target(state.subpass, state.framebuffer.get(), state.render_area);
+            let draw = Draw {
+                pipeline: pipeline,
+                values: pipeline.values(&self.dynamic),
+                resources: &self.graphics.resources,
+                target: &target,
+                vertex_buffers: &self.vertex_buffers,
+            };
+            draw.run(vertices, first_instance, instance_count, &mut counters);
+        }
+        self.count(&counters);
+    }
+
+    unsafe fn dispatch(&mut self, group_count: [u32; 3]) {
+        let shader = match self.compute.pipeline.map(|x| x.get()) {
+            Some(&Pipeline::Compute(ref pipeline)) => &pipeline.shader,
+            _ => {
+                error!("dispatch without a compute pipeline bound");
+                return;
+            },
+        };
+
+        for z in 0..group_count[2] {
+            for y in 0..group_count[1] {
+                for x in 0..group_count[0] {
+                    shader.dispatch(&self.compute.resources, [x, y, z], group_count);
+                }
+            }
+        }
+
+        let size = shader.workgroup_size();
+        let mut counters = Counters::default();
+        counters.compute_invocations = group_count.iter().chain(&size).fold(1, |total, &x| total * x as u64);
+        self.count(&counters);
+    }
+
+    /// Adds what a draw or dispatch counted to every active query.
+    fn count(&mut self, counters: &Counters) {
+        for query in &mut self.queries {
+            query.counters.add(counters);
+        }
+    }
+}
+
+/// Sets the values of the faces in `faces`, front first.
+fn set_faces(values: &mut [u32; 2], faces: StencilFaceFlags, value: u32) {
+    if faces.contains(VK_STENCIL_FACE_FRONT_BIT) {
+        values[0] = value;
+    }
+    if faces.contains(VK_STENCIL_FACE_BACK_BIT) {
+        values[1] = value;
+    }
+}
+
+/// Reads the parameters of an indirect command.
+unsafe fn read_words(buffer: &Buffer, offset: u64, count: u64) -> Option<Vec<u32>> {
+    match buffer.range(offset, count * 4) {
+        Some(data) => Some((0..count as isize).map(|i| ptr::read_unaligned((data as *const u32).offset(i))).collect()),
+        None => {
+            error!("indirect command reads past the end of its buffer");
+            None
+        },
+    }
+}
+
+/// The results of a finished occlusion or pipeline statistics query.
+fn query_values(pool: &HostQueryPool, counters: &Counters) -> [u64; MAX_QUERY_VALUES] {
+    let mut values = [0; MAX_QUERY_VALUES];
+    match pool.query_type() {
+        QueryType::Occlusion => values[0] = counters.samples_passed,
+        QueryType::PipelineStatistics => {
+            // In bit order; geometry and tessellation shaders never run
+            let statistics = [
+                (VK_QUERY_PIPELINE_STATISTIC_INPUT_ASSEMBLY_VERTICES_BIT, counters.input_vertices),
+                (VK_QUERY_PIPELINE_STATISTIC_INPUT_ASSEMBLY_PRIMITIVES_BIT, counters.input_primitives),
+                (VK_QUERY_PIPELINE_STATISTIC_VERTEX_SHADER_INVOCATIONS_BIT, counters.vertex_invocations),
+                (VK_QUERY_PIPELINE_STATISTIC_GEOMETRY_SHADER_INVOCATIONS_BIT, 0),
+                (VK_QUERY_PIPELINE_STATISTIC_GEOMETRY_SHADER_PRIMITIVES_BIT, 0),
+                (VK_QUERY_PIPELINE_STATISTIC_CLIPPING_INVOCATIONS_BIT, counters.clipping_invocations),
+                (VK_QUERY_PIPELINE_STATISTIC_CLIPPING_PRIMITIVES_BIT, counters.clipping_primitives),
+                (VK_QUERY_PIPELINE_STATISTIC_FRAGMENT_SHADER_INVOCATIONS_BIT, counters.fragment_invocations),
+                (VK_QUERY_PIPELINE_STATISTIC_TESSELLATION_CONTROL_SHADER_PATCHES_BIT, 0),
+                (VK_QUERY_PIPELINE_STATISTIC_TESSELLATION_EVALUATION_SHADER_INVOCATIONS_BIT, 0),
+                (VK_QUERY_PIPELINE_STATISTIC_COMPUTE_SHADER_INVOCATIONS_BIT, counters.compute_invocations),
+            ];
+            let enabled = statistics.iter().filter(|&&(flag, _)| pool.pipeline_statistics().contains(flag));
+            for (value, &(_, count)) in values.iter_mut().zip(enabled) {
+                *value = count;
+            }
+        },
+        _ => {},
+    }
+    values
+}
@@ -0,0 +1,1039 @@
+//! Draws: vertex fetch and shading, primitive assembly, clipping, rasterization and the
+//! per-fragment operations that write to the framebuffer.
+//!
+//! Everything happens one primitive at a time, in primitive order, and within a primitive one 2×2
+//! quad of fragments at a time so the fragment shader can take derivatives across it. Blending and
+//! depth testing give the same result on every run. Each pixel has a single sample at its
+//! center. Triangles are rasterized with edge functions on a fixed-point grid of
+//! `SUBPIXEL_BITS`, with a fill rule that covers pixels on an edge shared by two triangles exactly
+//! once. Lines are one pixel wide and stepped along their major axis; points are squares.
+
+use vk_middle::icd::*;
+
+use std::collections::HashMap;
+
+use format::{self, Packed, Texel};
+use memory::ImageView;
+use pipeline::{GraphicsPipeline, DynamicValues};
+use sampler::compare;
+use shader::{Resources, Shader, VertexInput, VertexOutput, FragmentInput, FragmentOutput, Interpolation};
+
+/// The bits of subpixel precision triangle vertices are snapped to, as reported in the limits.
+const SUBPIXEL_BITS: u32 = 8;
+
+/// How far outside the viewport, in multiples of its size, primitives are clipped. Beyond this
+/// primitives are clipped against the sides of the clip volume too, which keeps framebuffer
+/// coordinates small enough to convert to fixed point.
+const GUARD_BAND: f32 = 8.0;
+
+/// The smallest clip-space `w` a vertex may have after clipping.
+const W_EPSILON: f32 = 1.0e-6;
+
+/// What a draw or dispatch counts, for occlusion and pipeline statistics queries.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Counters {
+    pub input_vertices: u64,
+    pub input_primitives: u64,
+    pub vertex_invocations: u64,
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_invocations: u64,
+    pub compute_invocations: u64,
+    pub samples_passed: u64,
+}
+
+impl Counters {
+    pub fn add(&mut self, other: &Counters) {
+        self.input_vertices += other.input_vertices;
+        self.input_primitives += other.input_primitives;
+        self.vertex_invocations += other.vertex_invocations;
+        self.clipping_invocations += other.clipping_invocations;
+        self.clipping_primitives += other.clipping_primitives;
+        self.fragment_invocations += other.fragment_invocations;
+        self.compute_invocations += other.compute_invocations;
+        self.samples_passed += other.samples_passed;
+    }
+}
+
+/// The attachments of the current subpass.
+pub struct Target<'a> {
+    /// Indexed by fragment shader output location. Unused attachments are `None`.
+    pub colors: Vec<Option<&'a ImageView>>,
+    pub depth_stencil: Option<&'a ImageView>,
+    /// Where fragments may be written: the render area, within the framebuffer's extent.
+    pub area: Rect2D,
+}
+
+/// Everything a draw reads besides its vertices.
+pub struct Draw<'a> {
+    pub pipeline: &'a GraphicsPipeline,
+    /// The pipeline's static state with the command buffer's dynamic state applied.
+    pub values: DynamicValues,
+    pub resources: &'a Resources,
+    pub target: &'a Target<'a>,
+    /// The address and size of the vertex buffer bound to each binding, from its bound offset.
+    pub vertex_buffers: &'a [Option<(*const u8, u64)>],
+}
+
+#[derive(Clone, Copy)]
+enum Primitive {
+    /// Vertices are indices into the shaded vertices, with the provoking vertex first.
+    Point(usize),
+    Line(usize, usize),
+    Triangle(usize, usize, usize),
+}
+
+/// A vertex in framebuffer coordinates.
+#[derive(Clone)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    point_size: f32,
+    varyings: Vec<[u32; 4]>,
+}
+
+/// Where a pixel center falls in a primitive. Pixels outside it get the same, extrapolated.
+struct Coverage {
+    depth: f32,
+    /// The screen-space weight of each of the primitive's vertices.
+    weights: [f32; 3],
+    point_coord: [f32; 2],
+}
+
+/// What is the same for every fragment of a primitive.
+struct PrimitiveInfo<'a> {
+    id: u32,
+    front_facing: bool,
+    /// The constant depth offset from depth bias.
+    depth_offset: f32,
+    /// The outputs of the provoking vertex, for flat interpolation.
+    flat: &'a [[u32; 4]],
+}
+
+impl<'a> Draw<'a> {
+    /// Draws `instance_count` instances of a sequence of vertex indices, in which `None` marks a
+    /// primitive restart.
+    pub fn run(&self, vertices: &[Option<u32>], first_instance: u32, instance_count: u32, counters: &mut Counters) {
+        for instance in 0..instance_count {
+            let instance_index = first_instance + instance;
+
+            // Each distinct index is shaded once per instance
+            let mut cache: HashMap<u32, usize> = HashMap::new();
+            let mut outputs: Vec<VertexOutput> = Vec::new();
+            let mut primitives = Vec::new();
+            let mut strip = Vec::new();
+            for &vertex in vertices {
+                let index = match vertex {
+                    Some(index) => index,
+                    None => {
+                        assemble(self.pipeline.topology, &strip, &mut primitives);
+                        strip.clear();
+                        continue;
+                    },
+                };
+                counters.input_vertices += 1;
+
+                let cached = cache.get(&index).cloned();
+                let slot = match cached {
+                    Some(slot) => slot,
+                    None => {
+                        let input = self.fetch(index, instance_index);
+                        outputs.push(self.pipeline.vertex_shader.vertex(self.resources, &input));
+                        counters.vertex_invocations += 1;
+                        cache.insert(index, outputs.len() - 1);
+                        outputs.len() - 1
+                    },
+                };
+                strip.push(slot);
+            }
+            assemble(self.pipeline.topology, &strip, &mut primitives);
+            counters.input_primitives += primitives.len() as u64;
+
+            if self.pipeline.rasterizer_discard {
+                continue;
+            }
+            for (id, &primitive) in primitives.iter().enumerate() {
+                self.primitive(&outputs, primitive, id as u32, counters);
+            }
+        }
+    }
+
+    fn fetch(&self, vertex_index: u32, instance_index: u32) -> VertexInput {
+        let mut attributes = Vec::new();
+        for attribute in &self.pipeline.attributes {
+            let location = attribute.location as usize;
+            if attributes.len() <= location {
+                attributes.resize(location + 1, None);
+            }
+            attributes[location] = Some(self.fetch_attribute(attribute, vertex_index, instance_index));
+        }
+
+        VertexInput {
+            vertex_index: vertex_index,
+            instance_index: instance_index,
+            attributes: attributes,
+        }
+    }
+
+    /// Reads an attribute, giving `(0, 0, 0, 1)` for reads outside the bound buffer as robust
+    /// buffer access allows.
+    fn fetch_attribute(&self, attribute: &VertexInputAttributeDescription, vertex_index: u32, instance_index: u32) -> Texel {
+        let default = format::default_texel(format::is_integer(attribute.format));
+
+        let binding = match self.pipeline.bindings.iter().find(|x| x.binding == attribute.binding) {
+            Some(binding) => binding,
+            None => return default,
+        };
+        let (data, size) = match self.vertex_buffers.get(binding.binding as usize) {
+            Some(&Some(buffer)) => buffer,
+            _ => return default,
+        };
+
+        let index = if binding.input_rate == VertexInputRate::Instance { instance_index } else { vertex_index };
+        let offset = index as u64 * binding.stride as u64 + attribute.offset as u64;
+        let texel_size = attribute.format.block_size().unwrap_or(0) as u64;
+        if texel_size == 0 || offset + texel_size > size {
+            return default;
+        }
+
+        unsafe { format::read(attribute.format, data.offset(offset as isize)) }.unwrap_or(default)
+    }
+
+    fn primitive(&self, outputs: &[VertexOutput], primitive: Primitive, id: u32, counters: &mut Counters) {
+        counters.clipping_invocations += 1;
+
+        match primitive {
+            Primitive::Point(a) => {
+                let vertex = &outputs[a];
+                if !self.clip_planes().iter().all(|plane| plane_distance(*plane, &vertex.position) >= 0.0) {
+                    return;
+                }
+                counters.clipping_primitives += 1;
+
+                let info = PrimitiveInfo { id: id, front_facing: true, depth_offset: 0.0, flat: &vertex.varyings };
+                self.rasterize_point(&self.to_screen(vertex), &info, counters);
+            },
+            Primitive::Line(first, second) => {
+                let (a, b) = match self.clip_line(&outputs[first], &outputs[second]) {
+                    Some(line) => line,
+                    None => return,
+                };
+                counters.clipping_primitives += 1;
+
+                let info = PrimitiveInfo { id: id, front_facing: true, depth_offset: 0.0, flat: &outputs[first].varyings };
+                self.rasterize_line(&self.to_screen(&a), &self.to_screen(&b), &info, counters);
+            },
+            Primitive::Triangle(a, b, c) => {
+                let polygon = self.clip_polygon(vec![outputs[a].clone(), outputs[b].clone(), outputs[c].clone()]);
+                if polygon.len() < 3 {
+                    return;
+                }
+                counters.clipping_primitives += polygon.len() as u64 - 2;
+
+                let polygon: Vec<ScreenVertex> = polygon.iter().map(|vertex| self.to_screen(vertex)).collect();
+                self.polygon(&polygon, id, &outputs[a].varyings, counters);
+            },
+        }
+    }
+
+    /// Culls and rasterizes a clipped triangle, which clipping may have made a convex polygon.
+    fn polygon(&self, polygon: &[ScreenVertex], id: u32, flat: &[[u32; 4]], counters: &mut Counters) {
+        let mut area = 0.0;
+        for i in 0..polygon.len() {
+            let (a, b) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+            area += a.x * b.y - b.x * a.y;
+        }
+        let area = -0.5 * area;
+        if area == 0.0 || area.is_nan() {
+            return;
+        }
+
+        let front_facing = match self.pipeline.front_face {
+            FrontFace::Clockwise => area < 0.0,
+            _ => area > 0.0,
+        };
+        if front_facing && self.pipeline.cull_mode.contains(VK_CULL_MODE_FRONT_BIT) {
+            return;
+        }
+        if !front_facing && self.pipeline.cull_mode.contains(VK_CULL_MODE_BACK_BIT) {
+            return;
+        }
+
+        let info = PrimitiveInfo {
+            id: id,
+            front_facing: front_facing,
+            depth_offset: self.depth_offset(polygon),
+            flat: flat,
+        };
+
+        match self.pipeline.polygon_mode {
+            PolygonMode::Line => {
+                for i in 0..polygon.len() {
+                    self.rasterize_line(&polygon[i], &polygon[(i + 1) % polygon.len()], &info, counters);
+                }
+            },
+            PolygonMode::Point => {
+                for vertex in polygon {
+                    self.rasterize_point(vertex, &info, counters);
+                }
+            },
+            _ => {
+                for i in 1..(polygon.len() - 1) {
+                    self.rasterize_triangle([&polygon[0], &polygon[i], &polygon[i + 1]], &info, counters);
+                }
+            },
+        }
+    }
+
+    /// The planes primitives are clipped against. With depth clamping the near and far planes are
+    /// left out.
+    fn clip_planes(&self) -> Vec<Plane> {
+        let mut planes = vec![Plane::W, Plane::Left, Plane::Right, Plane::Top, Plane::Bottom];
+        if !self.pipeline.depth_clamp {
+            planes.push(Plane::Near);
+            planes.push(Plane::Far);
+        }
+        planes
+    }
+
+    fn clip_line(&self, a: &VertexOutput, b: &VertexOutput) -> Option<(VertexOutput, VertexOutput)> {
+        let (mut start, mut end) = (0.0, 1.0);
+        for plane in self.clip_planes() {
+            let (da, db) = (plane_distance(plane, &a.position), plane_distance(plane, &b.position));
+            if da < 0.0 && db < 0.0 {
+                return None;
+            }
+            if da < 0.0 {
+                start = f32::max(start, da / (da - db));
+            } else if db < 0.0 {
+                end = f32::min(end, da / (da - db));
+            }
+        }
+        if start > end {
+            return None;
+        }
+
+        Some((lerp_vertex(a, b, start), lerp_vertex(a, b, end)))
+    }
+
+    fn clip_polygon(&self, polygon: Vec<VertexOutput>) -> Vec<VertexOutput> {
+        let mut polygon = polygon;
+        for plane in self.clip_planes() {
+            if polygon.is_empty() {
+                break;
+            }
+
+            let mut clipped = Vec::new();
+            for i in 0..polygon.len() {
+                let (a, b) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+                let (da, db) = (plane_distance(plane, &a.position), plane_distance(plane, &b.position));
+                if da >= 0.0 {
+                    clipped.push(a.clone());
+                }
+                if (da >= 0.0) != (db >= 0.0) {
+                    clipped.push(lerp_vertex(a, b, da / (da - db)));
+                }
+            }
+            polygon = clipped;
+        }
+        polygon
+    }
+
+    /// Applies the perspective divide and the viewport transform.
+    fn to_screen(&self, vertex: &VertexOutput) -> ScreenVertex {
+        let viewport = &self.values.viewport;
+        let p = vertex.position;
+        let inv_w = 1.0 / p[3];
+
+        ScreenVertex {
+            x: viewport.x + viewport.width * 0.5 * (p[0] * inv_w + 1.0),
+            y: viewport.y + viewport.height * 0.5 * (p[1] * inv_w + 1.0),
+            z: viewport.min_depth + (viewport.max_depth - viewport.min_depth) * p[2] * inv_w,
+            inv_w: inv_w,
+            point_size: vertex.point_size,
+            varyings: vertex.varyings.clone(),
+        }
+    }
+
+    /// The depth bias of a polygon, from its maximum depth slope and the depth attachment's
+    /// resolution.
+    fn depth_offset(&self, polygon: &[ScreenVertex]) -> f32 {
+        if !self.pipeline.depth_bias {
+            return 0.0;
+        }
+        let (constant_factor, clamp, slope_factor) = self.values.depth_bias;
+
+        let (a, b, c) = (&polygon[0], &polygon[1], &polygon[2]);
+        let det = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+        let slope = if det == 0.0 {
+            0.0
+        } else {
+            let dzdx = ((b.z - a.z) * (c.y - a.y) - (c.z - a.z) * (b.y - a.y)) / det;
+            let dzdy = ((b.x - a.x) * (c.z - a.z) - (c.x - a.x) * (b.z - a.z)) / det;
+            f32::max(dzdx.abs(), dzdy.abs())
+        };
+
+        let max_depth = polygon.iter().fold(0f32, |max, vertex| max.max(vertex.z.abs()));
+        let resolution = match self.target.depth_stencil {
+            Some(view) => format::depth_resolution(view.format(), max_depth),
+            None => 0.0,
+        };
+
+        let offset = slope * slope_factor + resolution * constant_factor;
+        if clamp > 0.0 {
+            offset.min(clamp)
+        } else if clamp < 0.0 {
+            offset.max(clamp)
+        } else {
+            offset
+        }
+    }
+
+    /// The pixels fragments may be generated for, as `(x0, y0, x1, y1)` with the ends exclusive.
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        let (area, scissor) = (&self.target.area, &self.values.scissor);
+        (
+            i32::max(area.offset.x, scissor.offset.x),
+            i32::max(area.offset.y, scissor.offset.y),
+            i32::min(area.offset.x + area.extent.width as i32, scissor.offset.x + scissor.extent.width as i32),
+            i32::min(area.offset.y + area.extent.height as i32, scissor.offset.y + scissor.extent.height as i32),
+        )
+    }
+
+    fn rasterize_triangle(&self, vertices: [&ScreenVertex; 3], info: &PrimitiveInfo, counters: &mut Counters) {
+        let scale = (1 << SUBPIXEL_BITS) as f32;
+        let fixed = |vertex: &ScreenVertex| ((vertex.x * scale).round() as i64, (vertex.y * scale).round() as i64);
+
+        // Order the vertices so the interior is on the positive side of every edge
+        let mut v = vertices;
+        let mut p = [fixed(v[0]), fixed(v[1]), fixed(v[2])];
+        let mut area = edge(p[0], p[1], p[2]);
+        if area == 0 {
+            return;
+        }
+        if area < 0 {
+            v.swap(1, 2);
+            p.swap(1, 2);
+            area = -area;
+        }
+
+        let (x0, y0, x1, y1) = self.bounds();
+        let min_x = i32::max(x0, (p.iter().map(|p| p.0).min().unwrap() >> SUBPIXEL_BITS) as i32);
+        let min_y = i32::max(y0, (p.iter().map(|p| p.1).min().unwrap() >> SUBPIXEL_BITS) as i32);
+        let max_x = i32::min(x1 - 1, (p.iter().map(|p| p.0).max().unwrap() >> SUBPIXEL_BITS) as i32);
+        let max_y = i32::min(y1 - 1, (p.iter().map(|p| p.1).max().unwrap() >> SUBPIXEL_BITS) as i32);
+
+        let half = 1i64 << (SUBPIXEL_BITS - 1);
+        let edges = |x: i32, y: i32| {
+            let sample = (((x as i64) << SUBPIXEL_BITS) + half, ((y as i64) << SUBPIXEL_BITS) + half);
+            [edge(p[1], p[2], sample), edge(p[2], p[0], sample), edge(p[0], p[1], sample)]
+        };
+
+        let mut pixels = Vec::new();
+        for y in min_y..(max_y + 1) {
+            for x in min_x..(max_x + 1) {
+                let w = edges(x, y);
+                if covers(w[0], p[1], p[2]) && covers(w[1], p[2], p[0]) && covers(w[2], p[0], p[1]) {
+                    pixels.push((x, y));
+                }
+            }
+        }
+
+        let at = |x: i32, y: i32| {
+            let w = edges(x, y);
+            let weights = [w[0] as f32 / area as f32, w[1] as f32 / area as f32, w[2] as f32 / area as f32];
+            Coverage {
+                depth: weights[0] * v[0].z + weights[1] * v[1].z + weights[2] * v[2].z,
+                weights: weights,
+                point_coord: [0.5, 0.5],
+            }
+        };
+        self.shade(&pixels, &[v[0], v[1], v[2]], &at, info, counters);
+    }
+
+    fn rasterize_line(&self, a: &ScreenVertex, b: &ScreenVertex, info: &PrimitiveInfo, counters: &mut Counters) {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        let x_major = dx.abs() >= dy.abs();
+        let (x0, y0, x1, y1) = self.bounds();
+
+        // Every pixel whose center along the major axis is in [start, end) gets one fragment
+        let (start, end, low, high) = if x_major { (a.x, b.x, x0, x1) } else { (a.y, b.y, y0, y1) };
+        let first = i32::max(low, (f32::min(start, end) - 0.5).ceil() as i32);
+        let last = i32::min(high - 1, (f32::max(start, end) - 0.5).ceil() as i32 - 1);
+        let t = |major: i32| (major as f32 + 0.5 - start) / (end - start);
+
+        let mut pixels = Vec::new();
+        for major in first..(last + 1) {
+            let (x, y) = if x_major {
+                (major, (a.y + t(major) * dy).floor() as i32)
+            } else {
+                ((a.x + t(major) * dx).floor() as i32, major)
+            };
+            if x >= x0 && x < x1 && y >= y0 && y < y1 {
+                pixels.push((x, y));
+            }
+        }
+
+        let at = |x: i32, y: i32| {
+            let t = t(if x_major { x } else { y });
+            Coverage {
+                depth: (1.0 - t) * a.z + t * b.z,
+                weights: [1.0 - t, t, 0.0],
+                point_coord: [0.5, 0.5],
+            }
+        };
+        self.shade(&pixels, &[a, b], &at, info, counters);
+    }
+
+    fn rasterize_point(&self, vertex: &ScreenVertex, info: &PrimitiveInfo, counters: &mut Counters) {
+        let size = if vertex.point_size >= 1.0 { vertex.point_size.min(64.0) } else { 1.0 };
+        let half = size * 0.5;
+        let (x0, y0, x1, y1) = self.bounds();
+
+        let first_x = i32::max(x0, (vertex.x - half - 0.5).ceil() as i32);
+        let last_x = i32::min(x1 - 1, (vertex.x + half - 0.5).ceil() as i32 - 1);
+        let first_y = i32::max(y0, (vertex.y - half - 0.5).ceil() as i32);
+        let last_y = i32::min(y1 - 1, (vertex.y + half - 0.5).ceil() as i32 - 1);
+
+        let mut pixels = Vec::new();
+        for y in first_y..(last_y + 1) {
+            for x in first_x..(last_x + 1) {
+                pixels.push((x, y));
+            }
+        }
+
+        let at = |x: i32, y: i32| Coverage {
+            depth: vertex.z,
+            weights: [1.0, 0.0, 0.0],
+            point_coord: [
+                0.5 + (x as f32 + 0.5 - vertex.x) / size,
+                0.5 + (y as f32 + 0.5 - vertex.y) / size,
+            ],
+        };
+        self.shade(&pixels, &[vertex], &at, info, counters);
+    }
+
+    /// Shades the covered pixels of a primitive a 2×2 quad at a time, in the order each quad's
+    /// first pixel is covered. `at` locates any pixel in the primitive, covered or not, since the
+    /// uncovered pixels of a quad still run the fragment shader as helpers for derivatives.
+    fn shade(
+        &self,
+        pixels: &[(i32, i32)],
+        vertices: &[&ScreenVertex],
+        at: &Fn(i32, i32) -> Coverage,
+        info: &PrimitiveInfo,
+        counters: &mut Counters,
+    ) {
+        let mut quads: Vec<((i32, i32), [bool; 4])> = Vec::new();
+        let mut indices: HashMap<(i32, i32), usize> = HashMap::new();
+        for &(x, y) in pixels {
+            let origin = (x & !1, y & !1);
+            let index = *indices.entry(origin).or_insert_with(|| {
+                quads.push((origin, [false; 4]));
+                quads.len() - 1
+            });
+            quads[index].1[((y & 1) * 2 + (x & 1)) as usize] = true;
+        }
+
+        for &(origin, covered) in &quads {
+            self.quad(origin, covered, vertices, at, info, counters);
+        }
+    }
+
+    /// Runs the fragment shader for a quad, with the pixels that are not covered or fail early
+    /// tests as helpers, then writes the fragments that survive it.
+    fn quad(
+        &self,
+        origin: (i32, i32),
+        covered: [bool; 4],
+        vertices: &[&ScreenVertex],
+        at: &Fn(i32, i32) -> Coverage,
+        info: &PrimitiveInfo,
+        counters: &mut Counters,
+    ) {
+        let pixel = |i: usize| (origin.0 + (i & 1) as i32, origin.1 + (i >> 1) as i32);
+        let covered = |i: usize| covered[i] && self.pipeline.sample_mask & 1 != 0;
+
+        let shader = match self.pipeline.fragment_shader {
+            Some(ref shader) => shader,
+            None => {
+                for i in (0..4).filter(|&i| covered(i)) {
+                    let (x, y) = pixel(i);
+                    let depth = self.clamp_depth(at(x, y).depth + info.depth_offset);
+                    if self.depth_stencil_tests(x, y, depth, info.front_facing) {
+                        counters.samples_passed += 1;
+                    }
+                }
+                return;
+            },
+        };
+
+        let early_tests = shader.early_fragment_tests();
+        let mut inputs = Vec::with_capacity(4);
+        for i in 0..4 {
+            let (x, y) = pixel(i);
+            let coverage = at(x, y);
+            let depth = self.clamp_depth(coverage.depth + info.depth_offset);
+            let live = covered(i) && (!early_tests || self.depth_stencil_tests(x, y, depth, info.front_facing));
+            if live {
+                counters.fragment_invocations += 1;
+            }
+            inputs.push(self.fragment_input(x, y, depth, vertices, &coverage, &**shader, info, !live));
+        }
+        if inputs.iter().all(|input| input.helper) {
+            return;
+        }
+
+        let outputs = shader.fragment(self.resources, &inputs);
+        for (i, (input, output)) in inputs.iter().zip(outputs).enumerate() {
+            if let (false, Some(output)) = (input.helper, output) {
+                let (x, y) = pixel(i);
+                self.output(x, y, input.frag_coord[2], output, early_tests, info, counters);
+            }
+        }
+    }
+
+    /// Interpolates a fragment's inputs from the vertices of its primitive.
+    fn fragment_input(
+        &self,
+        x: i32,
+        y: i32,
+        depth: f32,
+        vertices: &[&ScreenVertex],
+        coverage: &Coverage,
+        shader: &Shader,
+        info: &PrimitiveInfo,
+        helper: bool,
+    ) -> FragmentInput {
+        let weights = &coverage.weights[..vertices.len()];
+        let inv_w: f32 = vertices.iter().zip(weights).map(|(vertex, weight)| vertex.inv_w * weight).sum();
+
+        let locations = vertices.iter().map(|vertex| vertex.varyings.len()).max().unwrap_or(0);
+        let mut varyings = Vec::with_capacity(locations);
+        for location in 0..locations {
+            varyings.push(match shader.interpolation(location as u32) {
+                Interpolation::Flat => info.flat.get(location).cloned().unwrap_or([0; 4]),
+                Interpolation::NoPerspective => interpolate(vertices, weights, location),
+                Interpolation::Smooth => {
+                    let perspective: Vec<f32> = vertices.iter().zip(weights).map(|(vertex, weight)| weight * vertex.inv_w / inv_w).collect();
+                    interpolate(vertices, &perspective, location)
+                },
+            });
+        }
+
+        FragmentInput {
+            frag_coord: [x as f32 + 0.5, y as f32 + 0.5, depth, inv_w],
+            front_facing: info.front_facing,
+            point_coord: coverage.point_coord,
+            primitive_id: info.id,
+            varyings: varyings,
+            helper: helper,
+        }
+    }
+
+    /// Applies the per-fragment operations after the fragment shader to what it output, and
+    /// writes the fragment if it survives them.
+    fn output(
+        &self,
+        x: i32,
+        y: i32,
+        depth: f32,
+        mut output: FragmentOutput,
+        early_tests: bool,
+        info: &PrimitiveInfo,
+        counters: &mut Counters,
+    ) {
+        let mut depth = depth;
+        if !early_tests {
+            if let Some(shader_depth) = output.depth {
+                depth = self.clamp_depth(shader_depth);
+            }
+        }
+
+        if self.pipeline.alpha_to_coverage {
+            let alpha = match output.colors.get(0) {
+                Some(&Some(color)) => f32::from_bits(color[3]),
+                _ => 1.0,
+            };
+            if !(alpha >= 0.5) {
+                return;
+            }
+        }
+        if self.pipeline.alpha_to_one {
+            for (color, view) in output.colors.iter_mut().zip(&self.target.colors) {
+                if let (&mut Some(ref mut color), &Some(view)) = (color, view) {
+                    if !format::is_integer(view.format()) {
+                        color[3] = 1.0f32.to_bits();
+                    }
+                }
+            }
+        }
+
+        if !early_tests && !self.depth_stencil_tests(x, y, depth, info.front_facing) {
+            return;
+        }
+        counters.samples_passed += 1;
+
+        self.write_colors(x, y, &output.colors);
+    }
+
+    /// Clamps depth to the viewport's depth range when depth clamping is enabled.
+    fn clamp_depth(&self, depth: f32) -> f32 {
+        if !self.pipeline.depth_clamp {
+            return depth;
+        }
+        let viewport = &self.values.viewport;
+        let (min, max) = (viewport.min_depth.min(viewport.max_depth), viewport.min_depth.max(viewport.max_depth));
+        depth.max(min).min(max)
+    }
+
+    /// Runs the depth bounds, stencil and depth tests at a pixel, updating stencil and depth as
+    /// they direct, and returns whether the fragment passed.
+    fn depth_stencil_tests(&self, x: i32, y: i32, depth: f32, front_facing: bool) -> bool {
+        let view = match self.target.depth_stencil {
+            Some(view) => view,
+            None => return true,
+        };
+        let (stored_depth, stored_stencil) = view.read_depth_stencil(0, x, y);
+
+        if let (true, Some(stored)) = (self.pipeline.depth_bounds_test, stored_depth) {
+            let (min, max) = self.values.depth_bounds;
+            if stored < min || stored > max {
+                return false;
+            }
+        }
+
+        let face = if front_facing { 0 } else { 1 };
+        if let (Some(states), Some(stored)) = (self.pipeline.stencil, stored_stencil) {
+            let mask = self.values.stencil_compare_mask[face];
+            let reference = self.values.stencil_reference[face];
+            if !compare(states[face].compare_op, (reference & mask) as f32, (stored as u32 & mask) as f32) {
+                self.update_stencil(view, x, y, face, states[face].fail_op, stored);
+                return false;
+            }
+        }
+
+        // The fragment's depth is compared at the attachment's precision
+        let depth = quantize_depth(view.format(), depth);
+        if let (Some(op), Some(stored)) = (self.pipeline.depth_compare_op, stored_depth) {
+            if !compare(op, depth, stored) {
+                if let (Some(states), Some(stored)) = (self.pipeline.stencil, stored_stencil) {
+                    self.update_stencil(view, x, y, face, states[face].depth_fail_op, stored);
+                }
+                return false;
+            }
+            if self.pipeline.depth_write {
+                view.write_depth(0, x, y, depth);
+            }
+        }
+
+        if let (Some(states), Some(stored)) = (self.pipeline.stencil, stored_stencil) {
+            self.update_stencil(view, x, y, face, states[face].pass_op, stored);
+        }
+        true
+    }
+
+    fn update_stencil(&self, view: &ImageView, x: i32, y: i32, face: usize, op: StencilOp, stored: u8) {
+        let value = match op {
+            StencilOp::Zero => 0,
+            StencilOp::Replace => self.values.stencil_reference[face] as u8,
+            StencilOp::IncrementAndClamp => stored.saturating_add(1),
+            StencilOp::DecrementAndClamp => stored.saturating_sub(1),
+            StencilOp::Invert => !stored,
+            StencilOp::IncrementAndWrap => stored.wrapping_add(1),
+            StencilOp::DecrementAndWrap => stored.wrapping_sub(1),
+            StencilOp::Keep | StencilOp::Other(_) => return,
+        };
+        let mask = self.values.stencil_write_mask[face] as u8;
+        view.write_stencil(0, x, y, (stored & !mask) | (value & mask));
+    }
+
+    fn write_colors(&self, x: i32, y: i32, colors: &[Option<[u32; 4]>]) {
+        let all = VK_COLOR_COMPONENT_R_BIT | VK_COLOR_COMPONENT_G_BIT | VK_COLOR_COMPONENT_B_BIT | VK_COLOR_COMPONENT_A_BIT;
+
+        for (location, view) in self.target.colors.iter().enumerate() {
+            let view = match *view {
+                Some(view) => view,
+                None => continue,
+            };
+            let color = match colors.get(location) {
+                Some(&Some(color)) => color,
+                _ => continue,
+            };
+            let state = self.pipeline.blend_attachments.get(location).cloned();
+            let mask = state.map_or(all, |state| state.color_write_mask);
+            let data = match view.texel_ptr(0, 0, x, y, 0) {
+                Some(data) => data,
+                None => continue,
+            };
+            let format = view.format();
+
+            unsafe {
+                let packed = match self.pipeline.logic_op {
+                    Some(op) if format::takes_logic_op(format) => {
+                        match format::encode(format, color) {
+                            Some(source) => logic_op(op, &source, &format::load(format, data)),
+                            None => continue,
+                        }
+                    },
+                    _ => {
+                        let color = match state {
+                            Some(ref state) if state.blend_enable && !format::is_integer(format) => {
+                                let destination = format::read(format, data).unwrap_or(format::default_texel(false));
+                                self.blend(state, color, destination, format)
+                            },
+                            _ => color,
+                        };
+                        match format::encode(format, color) {
+                            Some(packed) => packed,
+                            None => continue,
+                        }
+                    },
+                };
+                format::store(format, data, &packed, mask);
+            }
+        }
+    }
+
+    fn blend(&self, state: &PipelineColorBlendAttachmentState, source: Texel, destination: Texel, format: Format) -> Texel {
+        let mut s = [0f32; 4];
+        let mut d = [0f32; 4];
+        let mut c = self.values.blend_constants;
+        for i in 0..4 {
+            s[i] = f32::from_bits(source[i]);
+            d[i] = f32::from_bits(destination[i]);
+        }
+
+        // Fixed-point attachments clamp what is blended into them
+        if let Some((min, max)) = format::normalized_range(format) {
+            for i in 0..4 {
+                s[i] = s[i].max(min).min(max);
+                c[i] = c[i].max(min).min(max);
+            }
+        }
+
+        let mut result = [0u32; 4];
+        for i in 0..4 {
+            let (src_factor, dst_factor, op) = if i < 3 {
+                (state.src_color_blend_factor, state.dst_color_blend_factor, state.color_blend_op)
+            } else {
+                (state.src_alpha_blend_factor, state.dst_alpha_blend_factor, state.alpha_blend_op)
+            };
+            let source = s[i] * blend_factor(src_factor, &s, &d, &c, i);
+            let destination = d[i] * blend_factor(dst_factor, &s, &d, &c, i);
+            result[i] = match op {
+                BlendOp::Subtract => source - destination,
+                BlendOp::ReverseSubtract => destination - source,
+                BlendOp::Min => s[i].min(d[i]),
+                BlendOp::Max => s[i].max(d[i]),
+                _ => source + destination,
+            }.to_bits();
+        }
+        result
+    }
+}
+
+/// Splits a strip of shaded vertices (with no restarts in it) into primitives, in the order and
+/// with the provoking vertices the spec gives for each topology.
+fn assemble(topology: PrimitiveTopology, v: &[usize], primitives: &mut Vec<Primitive>) {
+    let n = v.len();
+    match topology {
+        PrimitiveTopology::PointList => {
+            primitives.extend(v.iter().map(|&a| Primitive::Point(a)));
+        },
+        PrimitiveTopology::LineList => {
+            for i in 0..(n / 2) {
+                primitives.push(Primitive::Line(v[2 * i], v[2 * i + 1]));
+            }
+        },
+        PrimitiveTopology::LineStrip => {
+            for i in 1..n {
+                primitives.push(Primitive::Line(v[i - 1], v[i]));
+            }
+        },
+        PrimitiveTopology::TriangleList => {
+            for i in 0..(n / 3) {
+                primitives.push(Primitive::Triangle(v[3 * i], v[3 * i + 1], v[3 * i + 2]));
+            }
+        },
+        PrimitiveTopology::TriangleStrip => {
+            for i in 0..n.saturating_sub(2) {
+                primitives.push(if i % 2 == 0 {
+                    Primitive::Triangle(v[i], v[i + 1], v[i + 2])
+                } else {
+                    Primitive::Triangle(v[i], v[i + 2], v[i + 1])
+                });
+            }
+        },
+        PrimitiveTopology::TriangleFan => {
+            for i in 0..n.saturating_sub(2) {
+                primitives.push(Primitive::Triangle(v[i + 1], v[i + 2], v[0]));
+            }
+        },
+        PrimitiveTopology::LineListWithAdjacency => {
+            for i in 0..(n / 4) {
+                primitives.push(Primitive::Line(v[4 * i + 1], v[4 * i + 2]));
+            }
+        },
+        PrimitiveTopology::LineStripWithAdjacency => {
+            for i in 0..n.saturating_sub(3) {
+                primitives.push(Primitive::Line(v[i + 1], v[i + 2]));
+            }
+        },
+        PrimitiveTopology::TriangleListWithAdjacency => {
+            for i in 0..(n / 6) {
+                primitives.push(Primitive::Triangle(v[6 * i], v[6 * i + 2], v[6 * i + 4]));
+            }
+        },
+        PrimitiveTopology::TriangleStripWithAdjacency => {
+            for i in 0..(n.saturating_sub(4) / 2) {
+                primitives.push(if i % 2 == 0 {
+                    Primitive::Triangle(v[2 * i], v[2 * i + 2], v[2 * i + 4])
+                } else {
+                    Primitive::Triangle(v[2 * i], v[2 * i + 4], v[2 * i + 2])
+                });
+            }
+        },
+        PrimitiveTopology::PatchList | PrimitiveTopology::Other(_) => {},
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Plane {
+    W,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Near,
+    Far,
+}
+
+/// The signed distance of a clip-space position from a plane, which is negative outside.
+fn plane_distance(plane: Plane, p: &[f32; 4]) -> f32 {
+    match plane {
+        Plane::W => p[3] - W_EPSILON,
+        Plane::Left => GUARD_BAND * p[3] + p[0],
+        Plane::Right => GUARD_BAND * p[3] - p[0],
+        Plane::Top => GUARD_BAND * p[3] + p[1],
+        Plane::Bottom => GUARD_BAND * p[3] - p[1],
+        Plane::Near => p[2],
+        Plane::Far => p[3] - p[2],
+    }
+}
+
+/// Interpolates linearly between two clip-space vertices. Flat outputs are interpolated too,
+/// but are only ever read from the unclipped provoking vertex.
+fn lerp_vertex(a: &VertexOutput, b: &VertexOutput, t: f32) -> VertexOutput {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+    let mut position = [0.0; 4];
+    for i in 0..4 {
+        position[i] = lerp(a.position[i], b.position[i]);
+    }
+    let varyings = a.varyings.iter().zip(&b.varyings)
+        .map(|(a, b)| {
+            let mut varying = [0; 4];
+            for i in 0..4 {
+                varying[i] = lerp(f32::from_bits(a[i]), f32::from_bits(b[i])).to_bits();
+            }
+            varying
+        })
+        .collect();
+
+    VertexOutput {
+        position: position,
+        point_size: lerp(a.point_size, b.point_size),
+        varyings: varyings,
+    }
+}
+
+fn interpolate(vertices: &[&ScreenVertex], weights: &[f32], location: usize) -> [u32; 4] {
+    let mut value = [0f32; 4];
+    for (vertex, &weight) in vertices.iter().zip(weights) {
+        if let Some(varying) = vertex.varyings.get(location) {
+            for i in 0..4 {
+                value[i] += f32::from_bits(varying[i]) * weight;
+            }
+        }
+    }
+    [value[0].to_bits(), value[1].to_bits(), value[2].to_bits(), value[3].to_bits()]
+}
+
+/// The edge function of `a` to `b` at `p`, in fixed point: twice the signed area of the triangle
+/// they form.
+fn edge(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+/// Whether a sample with edge function `w` for the edge from `a` to `b` is covered. Samples
+/// exactly on an edge belong to only one of the two triangles sharing it, since the edge runs the
+/// opposite way in the other.
+fn covers(w: i64, a: (i64, i64), b: (i64, i64)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    w > 0 || (w == 0 && ((dy == 0 && dx > 0) || dy < 0))
+}
+
+/// Rounds a depth to what the depth attachment's format can hold.
+fn quantize_depth(format: Format, depth: f32) -> f32 {
+    let mut texel = format::default_texel(false);
+    texel[0] = depth.to_bits();
+    match format::encode(format, texel).and_then(|packed| format::decode(format, &packed)) {
+        Some(texel) => f32::from_bits(texel[0]),
+        None => depth,
+    }
+}
+
+fn blend_factor(factor: BlendFactor, s: &[f32; 4], d: &[f32; 4], c: &[f32; 4], i: usize) -> f32 {
+    match factor {
+        BlendFactor::Zero => 0.0,
+        BlendFactor::One => 1.0,
+        BlendFactor::SrcColor => s[i],
+        BlendFactor::OneMinusSrcColor => 1.0 - s[i],
+        BlendFactor::DstColor => d[i],
+        BlendFactor::OneMinusDstColor => 1.0 - d[i],
+        BlendFactor::SrcAlpha => s[3],
+        BlendFactor::OneMinusSrcAlpha => 1.0 - s[3],
+        BlendFactor::DstAlpha => d[3],
+        BlendFactor::OneMinusDstAlpha => 1.0 - d[3],
+        BlendFactor::ConstantColor => c[i],
+        BlendFactor::OneMinusConstantColor => 1.0 - c[i],
+        BlendFactor::ConstantAlpha => c[3],
+        BlendFactor::OneMinusConstantAlpha => 1.0 - c[3],
+        BlendFactor::SrcAlphaSaturate => if i < 3 { s[3].min(1.0 - d[3]) } else { 1.0 },
+        // Dual-source blending is not supported, so there is no second source to read
+        _ => 0.0,
+    }
+}
+
+/// Applies a logic op to the bits of two texels.
+fn logic_op(op: LogicOp, source: &Packed, destination: &Packed) -> Packed {
+    let mut result = [0; format::MAX_TEXEL_SIZE];
+    for i in 0..result.len() {
+        let (s, d) = (source[i], destination[i]);
+        result[i] = match op {
+            LogicOp::Clear => 0,
+            LogicOp::And => s & d,
+            LogicOp::AndReverse => s & !d,
+            LogicOp::AndInverted => !s & d,
+            LogicOp::NoOp => d,
+            LogicOp::Xor => s ^ d,
+            LogicOp::Or => s | d,
+            LogicOp::Nor => !(s | d),
+            LogicOp::Equivalent => !(s ^ d),
+            LogicOp::Invert => !d,
+            LogicOp::OrReverse => s | !d,
+            LogicOp::CopyInverted => !s,
+            LogicOp::OrInverted => !s | d,
+            LogicOp::Nand => !(s & d),
+            LogicOp::Set => 0xff,
+            LogicOp::Copy | LogicOp::Other(_) => s,
+        };
+    }
+    result
+}
@@ -0,0 +1,165 @@
+//! Render passes and framebuffers.
+//!
+//! Attachments are loaded at the start of the first subpass that uses them, which is the only
+//! load op that does any work here: clears. Store ops need nothing, since attachments are written
+//! in place. Resolves happen at the end of each subpass with resolve attachments, and since every
+//! image has one sample they are plain copies of the render area.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+
+use super::{Cpu, Device};
+use format;
+use memory::ImageView;
+use raster::Target;
+use transfer;
+
+struct Attachment {
+    format: Format,
+    load_op: AttachmentLoadOp,
+    stencil_load_op: AttachmentLoadOp,
+    /// The first subpass to use the attachment, if any does.
+    first_subpass: Option<u32>,
+}
+
+/// The attachments a subpass uses, as indices into the render pass's attachments.
+/// `ATTACHMENT_UNUSED` entries are kept so color attachments keep their locations.
+pub struct Subpass {
+    pub input_attachments: Vec<u32>,
+    pub color_attachments: Vec<u32>,
+    pub resolve_attachments: Vec<u32>,
+    pub depth_stencil_attachment: Option<u32>,
+}
+
+pub struct RenderPass {
+    attachments: Vec<Attachment>,
+    subpasses: Vec<Subpass>,
+    usage: Vec<Option<AttachmentUsage>>,
+    dependencies: Vec<SubpassDependency>,
+}
+
+impl RenderPass {
+    pub fn subpasses(&self) -> &[Subpass] { &self.subpasses }
+
+    /// `RenderPassCreateInfo::attachment_usage` of the info the render pass was created from.
+    pub fn attachment_usage(&self) -> &[Option<AttachmentUsage>] { &self.usage }
+
+    /// `RenderPassCreateInfo::dependencies_with_implicit` of the info the render pass was created
+    /// from.
+    pub fn dependencies(&self) -> &[SubpassDependency] { &self.dependencies }
+
+    /// Clears the attachments `subpass` is the first to use whose load ops ask for it.
+    pub fn begin_subpass(&self, subpass: u32, framebuffer: &Framebuffer, render_area: Rect2D, clear_values: &[ClearValue]) {
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            if attachment.first_subpass != Some(subpass) {
+                continue;
+            }
+            let (view, value) = match (framebuffer.attachments.get(index), clear_values.get(index)) {
+                (Some(view), Some(value)) => (view, value),
+                _ => continue,
+            };
+
+            let mut aspects = format::aspects(attachment.format);
+            if attachment.load_op != AttachmentLoadOp::Clear {
+                aspects &= !(VK_IMAGE_ASPECT_COLOR_BIT | VK_IMAGE_ASPECT_DEPTH_BIT);
+            }
+            if attachment.stencil_load_op != AttachmentLoadOp::Clear {
+                aspects &= !VK_IMAGE_ASPECT_STENCIL_BIT;
+            }
+
+            if !aspects.is_empty() {
+                transfer::clear_attachment(view, aspects, value, render_area, (0, framebuffer.layers));
+            }
+        }
+    }
+
+    /// Resolves the color attachments of `subpass` that have resolve attachments.
+    pub fn end_subpass(&self, subpass: u32, framebuffer: &Framebuffer, render_area: Rect2D) {
+        let subpass = &self.subpasses[subpass as usize];
+        for (&color, &resolve) in subpass.color_attachments.iter().zip(&subpass.resolve_attachments) {
+            if let (Some(src), Some(dst)) = (framebuffer.attachment(color), framebuffer.attachment(resolve)) {
+                transfer::resolve_attachment(src, dst, render_area, (0, framebuffer.layers));
+            }
+        }
+    }
+
+    /// The attachments draws in `subpass` render to.
+    pub fn target<'a>(&self, subpass: u32, framebuffer: &'a Framebuffer, render_area: Rect2D) -> Target<'a> {
+        let subpass = &self.subpasses[subpass as usize];
+        let area = Rect2D {
+            offset: render_area.offset,
+            extent: Extent2D {
+                width: ::std::cmp::min(render_area.extent.width, framebuffer.width.saturating_sub(render_area.offset.x as u32)),
+                height: ::std::cmp::min(render_area.extent.height, framebuffer.height.saturating_sub(render_area.offset.y as u32)),
+            },
+        };
+
+        Target {
+            colors: subpass.color_attachments.iter().map(|&index| framebuffer.attachment(index)).collect(),
+            depth_stencil: subpass.depth_stencil_attachment.and_then(|index| framebuffer.attachment(index)),
+            area: area,
+        }
+    }
+}
+
+impl icd::RenderPass<Cpu> for RenderPass {
+    fn new(_device: &Device, info: RenderPassCreateInfo<Cpu>) -> Result<Self> {
+        let usage = info.attachment_usage();
+        let attachments = info.attachments()
+            .zip(&usage)
+            .map(|(attachment, usage)| Attachment {
+                format: attachment.format(),
+                load_op: attachment.load_op(),
+                stencil_load_op: attachment.stencil_load_op(),
+                first_subpass: usage.as_ref().map(|usage| usage.first_subpass),
+            })
+            .collect();
+
+        let subpasses = info.subpasses()
+            .map(|subpass| Subpass {
+                input_attachments: subpass.input_attachments().map(|x| x.attachment).collect(),
+                color_attachments: subpass.color_attachments().map(|x| x.attachment).collect(),
+                resolve_attachments: subpass.resolve_attachments().map(|x| x.attachment).collect(),
+                depth_stencil_attachment: subpass.depth_stencil_attachment()
+                    .map(|x| x.attachment)
+                    .and_then(|x| if x == ATTACHMENT_UNUSED { None } else { Some(x) }),
+            })
+            .collect();
+
+        Ok(RenderPass {
+            attachments: attachments,
+            subpasses: subpasses,
+            usage: usage,
+            dependencies: info.dependencies_with_implicit(),
+        })
+    }
+}
+
+pub struct Framebuffer {
+    attachments: Vec<ImageView>,
+    width: u32,
+    height: u32,
+    layers: u32,
+}
+
+impl Framebuffer {
+    /// The view of an attachment, or `None` for `ATTACHMENT_UNUSED`.
+    pub fn attachment(&self, index: u32) -> Option<&ImageView> {
+        self.attachments.get(index as usize)
+    }
+
+    pub fn layers(&self) -> u32 { self.layers }
+
+    pub fn attachments(&self) -> &[ImageView] { &self.attachments }
+}
+
+impl icd::Framebuffer<Cpu> for Framebuffer {
+    fn new(_device: &Device, info: FramebufferCreateInfo<Cpu>) -> Result<Self> {
+        Ok(Framebuffer {
+            attachments: info.attachments().cloned().collect(),
+            width: info.width(),
+            height: info.height(),
+            layers: info.layers(),
+        })
+    }
+}
@@ -0,0 +1,315 @@
+//! Texture filtering for sampled image reads.
+//!
+//! Level of detail is whatever the caller passes in; the shader works it out from explicit
+//! operands, or from derivatives across a fragment quad. Anisotropic filtering is not supported,
+//! and cube maps are filtered within each face, clamping at the face's edges.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+
+use super::{Cpu, Device};
+use format::Texel;
+use memory::ImageView;
+
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    mag_filter: Filter,
+    min_filter: Filter,
+    mipmap_mode: SamplerMipmapMode,
+    address_modes: [SamplerAddressMode; 3],
+    mip_lod_bias: f32,
+    min_lod: f32,
+    max_lod: f32,
+    compare_op: Option<CompareOp>,
+    border_color: BorderColor,
+    unnormalized_coordinates: bool,
+}
+
+impl icd::Sampler<Cpu> for Sampler {
+    fn new(_device: &Device, info: SamplerCreateInfo<Cpu>) -> Result<Self> {
+        Ok(Sampler {
+            mag_filter: info.mag_filter(),
+            min_filter: info.min_filter(),
+            mipmap_mode: info.mipmap_mode(),
+            address_modes: [info.address_mode_u(), info.address_mode_v(), info.address_mode_w()],
+            mip_lod_bias: info.mip_lod_bias(),
+            min_lod: info.min_lod(),
+            max_lod: info.max_lod(),
+            compare_op: info.compare_op(),
+            border_color: info.border_color(),
+            unnormalized_coordinates: info.unnormalized_coordinates(),
+        })
+    }
+}
+
+/// Where one filter tap lands: a texel, or the border.
+enum Tap {
+    Texel(i32),
+    Border,
+}
+
+impl Sampler {
+    /// Samples `view` at `coordinates`, which are `(u, v, w, layer)` for 1D, 2D and 3D views with
+    /// unused components ignored, and `(x, y, z, layer)` directions for cube views. `offset` is
+    /// added to the texel coordinates of every tap, as for `Offset` image operands.
+    ///
+    /// With `reference` set the sampler's compare op is applied to each tap, as for `Dref`
+    /// instructions, and the result is in the first component.
+    pub fn sample(
+        &self,
+        view: &ImageView,
+        coordinates: [f32; 4],
+        lod: f32,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> Texel {
+        let (dimensions, coordinates, layer) = locate(view, coordinates);
+
+        let lod = (lod + self.mip_lod_bias).max(self.min_lod).min(self.max_lod);
+        let filter = if lod <= 0.0 { self.mag_filter } else { self.min_filter };
+        let filter = if view.is_integer() && reference.is_none() { Filter::Nearest } else { filter };
+
+        let last_level = view.mip_levels() - 1;
+        let levels = if self.unnormalized_coordinates || lod <= 0.0 {
+            vec![(0, 1.0)]
+        } else {
+            match self.mipmap_mode {
+                SamplerMipmapMode::Linear => {
+                    let lower = lod.floor();
+                    let fraction = lod - lower;
+                    let lower = (lower as u32).min(last_level);
+                    if fraction > 0.0 && lower < last_level {
+                        vec![(lower, 1.0 - fraction), (lower + 1, fraction)]
+                    } else {
+                        vec![(lower, 1.0)]
+                    }
+                },
+                _ => {
+                    let level = if lod <= 0.5 { 0 } else { ((lod + 0.5).ceil() - 1.0) as u32 };
+                    vec![(level.min(last_level), 1.0)]
+                },
+            }
+        };
+
+        let mut result = [0f32; 4];
+        let mut integer_result = [0; 4];
+        for &(level, weight) in &levels {
+            let extent = view.extent(level);
+            let sizes = [extent.width as f32, extent.height as f32, extent.depth as f32];
+            let mut scaled = coordinates;
+            if !self.unnormalized_coordinates {
+                for i in 0..dimensions {
+                    scaled[i] *= sizes[i];
+                }
+            }
+
+            let texel = self.filter(view, level, layer, dimensions, &scaled, filter, reference, offset);
+
+            if view.is_integer() && reference.is_none() {
+                integer_result = texel;
+            } else {
+                for i in 0..4 {
+                    result[i] += f32::from_bits(texel[i]) * weight;
+                }
+            }
+        }
+
+        if view.is_integer() && reference.is_none() {
+            integer_result
+        } else {
+            [result[0].to_bits(), result[1].to_bits(), result[2].to_bits(), result[3].to_bits()]
+        }
+    }
+
+    /// Filters one mip level at texel-space `coordinates`.
+    fn filter(
+        &self,
+        view: &ImageView,
+        level: u32,
+        layer: u32,
+        dimensions: usize,
+        coordinates: &[f32; 3],
+        filter: Filter,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> Texel {
+        let extent = view.extent(level);
+        let sizes = [extent.width as i32, extent.height as i32, extent.depth as i32];
+
+        // Each dimension contributes one tap (nearest) or two weighted taps (linear)
+        let mut taps: Vec<Vec<(Tap, f32)>> = Vec::new();
+        for i in 0..3 {
+            if i >= dimensions {
+                taps.push(vec![(Tap::Texel(0), 1.0)]);
+                continue;
+            }
+            let mode = self.address_modes[i];
+            taps.push(match filter {
+                Filter::Linear => {
+                    let position = coordinates[i] - 0.5;
+                    let lower = position.floor();
+                    let fraction = position - lower;
+                    vec![
+                        (wrap(lower as i32 + offset[i], sizes[i], mode), 1.0 - fraction),
+                        (wrap(lower as i32 + offset[i] + 1, sizes[i], mode), fraction),
+                    ]
+                },
+                _ => vec![(wrap(coordinates[i].floor() as i32 + offset[i], sizes[i], mode), 1.0)],
+            });
+        }
+
+        let integer = view.is_integer() && reference.is_none();
+        let mut result = [0f32; 4];
+        for &(ref x, x_weight) in &taps[0] {
+            for &(ref y, y_weight) in &taps[1] {
+                for &(ref z, z_weight) in &taps[2] {
+                    let texel = match (x, y, z) {
+                        (&Tap::Texel(x), &Tap::Texel(y), &Tap::Texel(z)) => view.fetch(level, layer, x, y, z),
+                        _ => self.border(view.is_integer()),
+                    };
+                    if integer {
+                        // Integer formats are only ever filtered with a single tap
+                        return texel;
+                    }
+
+                    let texel = match (reference, self.compare_op) {
+                        (Some(reference), Some(op)) => {
+                            let passed = compare(op, reference, f32::from_bits(texel[0]));
+                            [(if passed { 1.0f32 } else { 0.0 }).to_bits(), 0, 0, 1.0f32.to_bits()]
+                        },
+                        _ => texel,
+                    };
+
+                    let weight = x_weight * y_weight * z_weight;
+                    for i in 0..4 {
+                        result[i] += f32::from_bits(texel[i]) * weight;
+                    }
+                }
+            }
+        }
+
+        [result[0].to_bits(), result[1].to_bits(), result[2].to_bits(), result[3].to_bits()]
+    }
+
+    /// Gathers one component of the four texels linear filtering of level 0 would take, in the
+    /// order `OpImageGather` returns them: (i0, j1), (i1, j1), (i1, j0), (i0, j0). With
+    /// `reference` set, each is instead the result of the compare op against that texel's depth.
+    pub fn gather(
+        &self,
+        view: &ImageView,
+        coordinates: [f32; 4],
+        component: u32,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> Texel {
+        let (_, coordinates, layer) = locate(view, coordinates);
+        let extent = view.extent(0);
+        let sizes = [extent.width as i32, extent.height as i32];
+
+        let mut taps = Vec::with_capacity(2);
+        for i in 0..2 {
+            let position = if self.unnormalized_coordinates { coordinates[i] } else { coordinates[i] * sizes[i] as f32 };
+            let lower = (position - 0.5).floor() as i32 + offset[i];
+            let mode = self.address_modes[i];
+            taps.push([wrap(lower, sizes[i], mode), wrap(lower + 1, sizes[i], mode)]);
+        }
+
+        let mut result = [0; 4];
+        for (k, &(i, j)) in [(0, 1), (1, 1), (1, 0), (0, 0)].iter().enumerate() {
+            let texel = match (&taps[0][i], &taps[1][j]) {
+                (&Tap::Texel(x), &Tap::Texel(y)) => view.fetch(0, layer, x, y, 0),
+                _ => self.border(view.is_integer()),
+            };
+            result[k] = match (reference, self.compare_op) {
+                (Some(reference), Some(op)) => {
+                    (if compare(op, reference, f32::from_bits(texel[0])) { 1.0f32 } else { 0.0 }).to_bits()
+                },
+                _ => texel[component.min(3) as usize],
+            };
+        }
+        result
+    }
+
+    fn border(&self, integer: bool) -> Texel {
+        let (rgb, alpha) = match self.border_color {
+            BorderColor::FloatOpaqueBlack | BorderColor::IntOpaqueBlack => (0, 1),
+            BorderColor::FloatOpaqueWhite | BorderColor::IntOpaqueWhite => (1, 1),
+            _ => (0, 0),
+        };
+        if integer {
+            [rgb, rgb, rgb, alpha]
+        } else {
+            let (rgb, alpha) = ((rgb as f32).to_bits(), (alpha as f32).to_bits());
+            [rgb, rgb, rgb, alpha]
+        }
+    }
+}
+
+/// Splits sampling coordinates into the number of dimensions filtered, the coordinates within the
+/// image (or cube face) and the array layer.
+fn locate(view: &ImageView, coordinates: [f32; 4]) -> (usize, [f32; 3], u32) {
+    let (dimensions, coordinates, layer) = match view.view_type() {
+        ImageViewType::D1 => (1, [coordinates[0], 0.0, 0.0], 0.0),
+        ImageViewType::D1Array => (1, [coordinates[0], 0.0, 0.0], coordinates[1]),
+        ImageViewType::D2 => (2, [coordinates[0], coordinates[1], 0.0], 0.0),
+        ImageViewType::D2Array => (2, [coordinates[0], coordinates[1], 0.0], coordinates[2]),
+        ImageViewType::D3 => (3, [coordinates[0], coordinates[1], coordinates[2]], 0.0),
+        ImageViewType::Cube | ImageViewType::CubeArray => {
+            let (face, s, t) = cube_face(coordinates[0], coordinates[1], coordinates[2]);
+            let base = if view.view_type() == ImageViewType::CubeArray { coordinates[3].round().max(0.0) * 6.0 } else { 0.0 };
+            (2, [s, t, 0.0], base + face as f32)
+        },
+        _ => (2, [coordinates[0], coordinates[1], 0.0], 0.0),
+    };
+    (dimensions, coordinates, (layer.round().max(0.0) as u32).min(view.array_layers() - 1))
+}
+
+/// Applies an address mode to a texel coordinate along a dimension of `size` texels.
+fn wrap(coordinate: i32, size: i32, mode: SamplerAddressMode) -> Tap {
+    match mode {
+        SamplerAddressMode::Repeat => Tap::Texel(((coordinate % size) + size) % size),
+        SamplerAddressMode::MirroredRepeat => {
+            let period = ((coordinate % (2 * size)) + 2 * size) % (2 * size);
+            Tap::Texel(if period >= size { 2 * size - 1 - period } else { period })
+        },
+        SamplerAddressMode::ClampToBorder => {
+            if coordinate < 0 || coordinate >= size { Tap::Border } else { Tap::Texel(coordinate) }
+        },
+        SamplerAddressMode::MirrorClampToEdge => {
+            let mirrored = if coordinate < 0 { -1 - coordinate } else { coordinate };
+            Tap::Texel(mirrored.min(size - 1))
+        },
+        _ => Tap::Texel(coordinate.max(0).min(size - 1)),
+    }
+}
+
+/// Selects the cube face a direction points at, and the normalized coordinates within it.
+fn cube_face(x: f32, y: f32, z: f32) -> (u32, f32, f32) {
+    let (face, s, t, major) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+        if x >= 0.0 { (0, -z, -y, x) } else { (1, z, -y, x) }
+    } else if y.abs() >= z.abs() {
+        if y >= 0.0 { (2, x, z, y) } else { (3, x, -z, y) }
+    } else {
+        if z >= 0.0 { (4, x, -y, z) } else { (5, -x, -y, z) }
+    };
+
+    let major = major.abs();
+    if major == 0.0 {
+        return (face, 0.5, 0.5);
+    }
+    (face, 0.5 * (s / major + 1.0), 0.5 * (t / major + 1.0))
+}
+
+/// Whether `reference op value` holds, as for depth tests and depth comparison samplers.
+pub fn compare(op: CompareOp, reference: f32, value: f32) -> bool {
+    match op {
+        CompareOp::Never => false,
+        CompareOp::Less => reference < value,
+        CompareOp::Equal => reference == value,
+        CompareOp::LessOrEqual => reference <= value,
+        CompareOp::Greater => reference > value,
+        CompareOp::NotEqual => reference != value,
+        CompareOp::GreaterOrEqual => reference >= value,
+        CompareOp::Always | CompareOp::Other(_) => true,
+    }
+}
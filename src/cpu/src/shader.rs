@@ -0,0 +1,430 @@
+//! The interface between pipelines and whatever executes their shaders.
+//!
+//! The rasterizer and the compute dispatcher only see the `Shader` trait: they pass in the bound
+//! resources and the built-in inputs of one invocation (or, for fragments, one 2×2 quad, and for
+//! compute, one workgroup) and get back its outputs. Values cross the interface as four 32-bit
+//! components per location, in the same convention as `format::Texel`.
+//!
+//! Shaders run on vk-middle's SPIR-V interpreter; `Resources` is what it sees of the bound
+//! descriptors.
+
+use vk_middle::icd::*;
+use vk_middle::spirv::{self, BuiltIn, ExecutionModel};
+use vk_middle::spirv::transform;
+use vk_middle::spirv::interpret::{self, Bindings, DescriptorRef, EntryPoint, Invocation, Program, Slot, Status};
+
+use std::sync::Arc;
+
+use descriptor::{Descriptor, SetBinding};
+use super::Cpu;
+
+/// What a draw or dispatch has bound for its shaders to use.
+pub struct Resources {
+    /// The contents of each bound descriptor set, indexed by set number, with dynamic offsets
+    /// already applied.
+    pub sets: Vec<Option<Vec<SetBinding>>>,
+    pub push_constants: Vec<u8>,
+}
+
+impl Resources {
+    pub fn descriptor(&self, set: u32, binding: u32, array_element: u32) -> Option<&Descriptor> {
+        let bindings = match self.sets.get(set as usize) {
+            Some(&Some(ref bindings)) => bindings,
+            _ => return None,
+        };
+        bindings.iter()
+            .find(|x| x.binding == binding)
+            .and_then(|x| x.descriptors.get(array_element as usize))
+    }
+}
+
+pub struct VertexInput {
+    pub vertex_index: u32,
+    pub instance_index: u32,
+    /// The fetched attributes, indexed by location. Locations without an attribute are `None`.
+    pub attributes: Vec<Option<[u32; 4]>>,
+}
+
+#[derive(Clone)]
+pub struct VertexOutput {
+    /// The clip-space position.
+    pub position: [f32; 4],
+    pub point_size: f32,
+    /// The outputs to the fragment shader, indexed by location.
+    pub varyings: Vec<[u32; 4]>,
+}
+
+pub struct FragmentInput {
+    pub frag_coord: [f32; 4],
+    pub front_facing: bool,
+    pub point_coord: [f32; 2],
+    pub primitive_id: u32,
+    /// The interpolated vertex outputs, indexed by location.
+    pub varyings: Vec<[u32; 4]>,
+    /// Whether the fragment only runs to give its quad derivatives. Its outputs are ignored.
+    pub helper: bool,
+}
+
+pub struct FragmentOutput {
+    /// The color written to each location. Locations the shader does not write are `None`.
+    pub colors: Vec<Option<[u32; 4]>>,
+    /// The depth the shader wrote, if it writes one.
+    pub depth: Option<f32>,
+}
+
+/// How a fragment shader input is interpolated across a primitive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interpolation {
+    Smooth,
+    NoPerspective,
+    /// Taken from the provoking vertex.
+    Flat,
+}
+
+/// One entry point of a shader module, ready to execute.
+pub trait Shader: Send + Sync {
+    /// The local size of a compute shader.
+    fn workgroup_size(&self) -> [u32; 3];
+
+    /// Runs every invocation of one workgroup of a compute shader.
+    fn dispatch(&self, resources: &Resources, workgroup_id: [u32; 3], workgroup_count: [u32; 3]);
+
+    fn vertex(&self, resources: &Resources, input: &VertexInput) -> VertexOutput;
+
+    /// Runs a fragment shader for a 2×2 quad of fragments, ordered (0, 0), (1, 0), (0, 1), (1, 1),
+    /// giving `None` for each fragment that was discarded.
+    fn fragment(&self, resources: &Resources, inputs: &[FragmentInput]) -> Vec<Option<FragmentOutput>>;
+
+    /// How the fragment shader input at `location` is interpolated.
+    fn interpolation(&self, location: u32) -> Interpolation;
+
+    /// Whether the fragment shader asks for depth and stencil tests before it runs.
+    fn early_fragment_tests(&self) -> bool;
+}
+
+/// Prepares the entry point of a pipeline stage to run, with its specialization applied.
+pub fn compile(stage: &PipelineShaderStageCreateInfo<Cpu>) -> Result<Box<Shader>> {
+    let model = match spirv::execution_model(stage.stage()) {
+        Some(ExecutionModel::Vertex) => ExecutionModel::Vertex,
+        Some(ExecutionModel::Fragment) => ExecutionModel::Fragment,
+        Some(ExecutionModel::GLCompute) => ExecutionModel::GLCompute,
+        _ => {
+            error!("cannot run shaders as {:?}, which the device does not support", stage.stage());
+            return Err(Error::FeatureNotPresent);
+        },
+    };
+
+    let name = stage.entry_point().to_string_lossy();
+    let program = match stage.specialization_info() {
+        // Specialization changes constants the program has already evaluated, so a specialized
+        // stage gets a program of its own.
+        Some(info) => {
+            let map_entries: Vec<SpecializationMapEntry> = info.map_entries().collect();
+            let code = try!(transform::specialize(stage.module().spirv().code(), &map_entries, info.data()));
+            Arc::new(try!(Program::new(&code)))
+        },
+        None => stage.module().program().clone(),
+    };
+    let index = match program.entry_points().iter().position(|x| x.name == name && x.model == model) {
+        Some(index) => index,
+        None => {
+            error!("shader module has no {:?} entry point named {:?}", model, name);
+            return Err(Error::InitializationFailed);
+        },
+    };
+
+    let mut interpolation = Vec::new();
+    for input in program.entry_points()[index].inputs() {
+        if let Slot::Location(location) = input.slot {
+            let location = location as usize;
+            if interpolation.len() <= location {
+                interpolation.resize(location + 1, Interpolation::Smooth);
+            }
+            interpolation[location] = if input.flat {
+                Interpolation::Flat
+            } else if input.no_perspective {
+                Interpolation::NoPerspective
+            } else {
+                Interpolation::Smooth
+            };
+        }
+    }
+
+    Ok(Box::new(Entry {
+        program: program,
+        entry_point: index,
+        interpolation: interpolation,
+    }))
+}
+
+/// An entry point of a program, run by the interpreter.
+struct Entry {
+    program: Arc<Program>,
+    entry_point: usize,
+    /// How each fragment shader input is interpolated, indexed by location.
+    interpolation: Vec<Interpolation>,
+}
+
+impl Entry {
+    fn entry_point(&self) -> &EntryPoint { &self.program.entry_points()[self.entry_point] }
+
+    /// Collects the outputs an invocation wrote to each location.
+    fn locations(&self, invocation: &Invocation) -> Vec<Option<[u32; 4]>> {
+        let mut locations = Vec::new();
+        for output in self.entry_point().outputs() {
+            if let Slot::Location(location) = output.slot {
+                let location = location as usize;
+                if locations.len() <= location {
+                    locations.resize(location + 1, None);
+                }
+                locations[location] = invocation.output(output.slot).map(|x| texel(&x));
+            }
+        }
+        locations
+    }
+}
+
+impl Shader for Entry {
+    fn workgroup_size(&self) -> [u32; 3] { self.entry_point().local_size }
+
+    fn dispatch(&self, resources: &Resources, workgroup_id: [u32; 3], workgroup_count: [u32; 3]) {
+        let entry_point = self.entry_point();
+        let size = entry_point.local_size;
+        let mut invocations = Vec::with_capacity((size[0] * size[1] * size[2]) as usize);
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    let global = [
+                        workgroup_id[0] * size[0] + x,
+                        workgroup_id[1] * size[1] + y,
+                        workgroup_id[2] * size[2] + z,
+                    ];
+                    let mut invocation = Invocation::new(&self.program, entry_point, resources);
+                    invocation.set_input(Slot::BuiltIn(BuiltIn::LocalInvocationId), &[x, y, z]);
+                    invocation.set_input(Slot::BuiltIn(BuiltIn::GlobalInvocationId), &global);
+                    invocation.set_input(Slot::BuiltIn(BuiltIn::WorkgroupId), &workgroup_id);
+                    invocation.set_input(Slot::BuiltIn(BuiltIn::NumWorkgroups), &workgroup_count);
+                    invocation.set_input(Slot::BuiltIn(BuiltIn::LocalInvocationIndex), &[(z * size[1] + y) * size[0] + x]);
+                    invocation.set_input(Slot::BuiltIn(BuiltIn::WorkgroupSize), &size);
+                    invocations.push(invocation);
+                }
+            }
+        }
+
+        let mut shared = self.program.workgroup_memory();
+        interpret::run_workgroup(&mut invocations, &mut shared);
+    }
+
+    fn vertex(&self, resources: &Resources, input: &VertexInput) -> VertexOutput {
+        let mut invocation = Invocation::new(&self.program, self.entry_point(), resources);
+        for (location, attribute) in input.attributes.iter().enumerate() {
+            if let Some(ref attribute) = *attribute {
+                invocation.set_input(Slot::Location(location as u32), attribute);
+            }
+        }
+        invocation.set_input(Slot::BuiltIn(BuiltIn::VertexIndex), &[input.vertex_index]);
+        invocation.set_input(Slot::BuiltIn(BuiltIn::InstanceIndex), &[input.instance_index]);
+        invocation.run(&mut []);
+
+        let position = match invocation.output(Slot::BuiltIn(BuiltIn::Position)) {
+            Some(position) => {
+                let position = texel(&position);
+                [f32::from_bits(position[0]), f32::from_bits(position[1]), f32::from_bits(position[2]), f32::from_bits(position[3])]
+            },
+            None => [0.0, 0.0, 0.0, 1.0],
+        };
+        let point_size = match invocation.output(Slot::BuiltIn(BuiltIn::PointSize)) {
+            Some(ref size) if !size.is_empty() => f32::from_bits(size[0]),
+            _ => 1.0,
+        };
+        VertexOutput {
+            position: position,
+            point_size: point_size,
+            varyings: self.locations(&invocation).into_iter().map(|x| x.unwrap_or([0; 4])).collect(),
+        }
+    }
+
+    fn fragment(&self, resources: &Resources, inputs: &[FragmentInput]) -> Vec<Option<FragmentOutput>> {
+        let entry_point = self.entry_point();
+        let mut invocations: Vec<Invocation> = inputs.iter()
+            .map(|input| {
+                let mut invocation = Invocation::new(&self.program, entry_point, resources);
+                invocation.set_fragment(input.frag_coord[0].floor() as i32, input.frag_coord[1].floor() as i32, input.helper);
+                for (location, varying) in input.varyings.iter().enumerate() {
+                    invocation.set_input(Slot::Location(location as u32), varying);
+                }
+                let frag_coord = [
+                    input.frag_coord[0].to_bits(),
+                    input.frag_coord[1].to_bits(),
+                    input.frag_coord[2].to_bits(),
+                    input.frag_coord[3].to_bits(),
+                ];
+                invocation.set_input(Slot::BuiltIn(BuiltIn::FragCoord), &frag_coord);
+                invocation.set_input(Slot::BuiltIn(BuiltIn::FrontFacing), &[input.front_facing as u32]);
+                invocation.set_input(Slot::BuiltIn(BuiltIn::PointCoord), &[input.point_coord[0].to_bits(), input.point_coord[1].to_bits()]);
+                invocation.set_input(Slot::BuiltIn(BuiltIn::PrimitiveId), &[input.primitive_id]);
+                invocation.set_input(Slot::BuiltIn(BuiltIn::HelperInvocation), &[input.helper as u32]);
+                invocation
+            })
+            .collect();
+
+        let statuses = interpret::run_quad(&mut invocations);
+        invocations.iter()
+            .zip(statuses)
+            .map(|(invocation, status)| {
+                if let Status::Killed = status {
+                    return None;
+                }
+                let depth = if entry_point.depth_replacing {
+                    match invocation.output(Slot::BuiltIn(BuiltIn::FragDepth)) {
+                        Some(ref depth) if !depth.is_empty() => Some(f32::from_bits(depth[0])),
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+                Some(FragmentOutput {
+                    colors: self.locations(invocation),
+                    depth: depth,
+                })
+            })
+            .collect()
+    }
+
+    fn interpolation(&self, location: u32) -> Interpolation {
+        self.interpolation.get(location as usize).cloned().unwrap_or(Interpolation::Smooth)
+    }
+
+    fn early_fragment_tests(&self) -> bool { self.entry_point().early_fragment_tests }
+}
+
+/// Pads an output to the four components that cross the interface.
+fn texel(scalars: &[u32]) -> [u32; 4] {
+    let mut texel = [0; 4];
+    for (component, &scalar) in texel.iter_mut().zip(scalars) {
+        *component = scalar;
+    }
+    texel
+}
+
+impl Resources {
+    fn image(&self, image: DescriptorRef) -> Option<&Descriptor> {
+        self.descriptor(image.set, image.binding, image.element)
+    }
+}
+
+impl Bindings for Resources {
+    fn buffer(&self, descriptor: DescriptorRef) -> Option<(*mut u8, u64)> {
+        match self.descriptor(descriptor.set, descriptor.binding, descriptor.element) {
+            Some(&Descriptor::Buffer { data, size }) => Some((data, size)),
+            _ => None,
+        }
+    }
+
+    fn push_constants(&self) -> &[u8] { &self.push_constants }
+
+    fn sample(
+        &self,
+        image: DescriptorRef,
+        sampler: DescriptorRef,
+        coordinates: [f32; 4],
+        lod: f32,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> [u32; 4] {
+        match (self.image(image), self.image(sampler)) {
+            (Some(&Descriptor::Image { ref view, .. }), Some(&Descriptor::Sampler(ref sampler))) |
+            (Some(&Descriptor::Image { ref view, .. }), Some(&Descriptor::Image { sampler: Some(ref sampler), .. })) => {
+                sampler.sample(view, coordinates, lod, reference, offset)
+            },
+            _ => [0; 4],
+        }
+    }
+
+    fn gather(
+        &self,
+        image: DescriptorRef,
+        sampler: DescriptorRef,
+        coordinates: [f32; 4],
+        component: u32,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> [u32; 4] {
+        match (self.image(image), self.image(sampler)) {
+            (Some(&Descriptor::Image { ref view, .. }), Some(&Descriptor::Sampler(ref sampler))) |
+            (Some(&Descriptor::Image { ref view, .. }), Some(&Descriptor::Image { sampler: Some(ref sampler), .. })) => {
+                sampler.gather(view, coordinates, component, reference, offset)
+            },
+            _ => [0; 4],
+        }
+    }
+
+    fn read(&self, image: DescriptorRef, coordinates: [i32; 4], lod: u32) -> [u32; 4] {
+        match self.image(image) {
+            Some(&Descriptor::Image { ref view, .. }) => {
+                match view_layer(view.view_type(), coordinates) {
+                    Some((layer, z)) => view.fetch(lod, layer, coordinates[0], coordinates[1], z),
+                    None => [0; 4],
+                }
+            },
+            Some(&Descriptor::TexelBuffer(ref view)) if coordinates[0] >= 0 => view.read(coordinates[0] as u64),
+            _ => [0; 4],
+        }
+    }
+
+    fn write(&self, image: DescriptorRef, coordinates: [i32; 4], texel: [u32; 4]) {
+        match self.image(image) {
+            Some(&Descriptor::Image { ref view, .. }) => {
+                if let Some((layer, z)) = view_layer(view.view_type(), coordinates) {
+                    view.write(0, layer, coordinates[0], coordinates[1], z, texel);
+                }
+            },
+            Some(&Descriptor::TexelBuffer(ref view)) if coordinates[0] >= 0 => view.write(coordinates[0] as u64, texel),
+            _ => {},
+        }
+    }
+
+    fn texel_pointer(&self, image: DescriptorRef, coordinates: [i32; 4]) -> Option<*mut u32> {
+        match self.image(image) {
+            Some(&Descriptor::Image { ref view, .. }) => {
+                view_layer(view.view_type(), coordinates)
+                    .and_then(|(layer, z)| view.texel_ptr(0, layer, coordinates[0], coordinates[1], z))
+                    .map(|x| x as *mut u32)
+            },
+            Some(&Descriptor::TexelBuffer(ref view)) if coordinates[0] >= 0 => {
+                view.texel_ptr(coordinates[0] as u64).map(|x| x as *mut u32)
+            },
+            _ => None,
+        }
+    }
+
+    fn size(&self, image: DescriptorRef, lod: u32) -> [u32; 4] {
+        match self.image(image) {
+            Some(&Descriptor::Image { ref view, .. }) => {
+                let extent = view.extent(lod);
+                [extent.width, extent.height, extent.depth, view.array_layers()]
+            },
+            Some(&Descriptor::TexelBuffer(ref view)) => [view.len() as u32, 1, 1, 1],
+            _ => [0; 4],
+        }
+    }
+
+    fn levels(&self, image: DescriptorRef) -> u32 {
+        match self.image(image) {
+            Some(&Descriptor::Image { ref view, .. }) => view.mip_levels(),
+            _ => 0,
+        }
+    }
+}
+
+/// The layer and depth of `(x, y, z, layer)` image coordinates in a view, or `None` if the layer
+/// is out of range. Only 3D views have depth.
+fn view_layer(view_type: ImageViewType, coordinates: [i32; 4]) -> Option<(u32, i32)> {
+    if view_type == ImageViewType::D3 {
+        Some((0, coordinates[2]))
+    } else if coordinates[3] >= 0 {
+        Some((coordinates[3] as u32, 0))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,191 @@
+//! Transfer work that converts texels: image clears, blits, and the clears and resolves render
+//! passes do. Plain copies go through the `host` helpers byte for byte instead.
+
+use vk_middle::icd::*;
+
+use std::cmp;
+
+use format::Texel;
+use memory::{Image, ImageView};
+
+/// Writes `texel` to a rectangle of the given layers of a mip level of a view, through every
+/// depth slice of 3D views.
+pub fn fill(view: &ImageView, mip_level: u32, layers: (u32, u32), rect: Rect2D, texel: Texel) {
+    let extent = view.extent(mip_level);
+    let (x0, y0) = (cmp::max(rect.offset.x, 0), cmp::max(rect.offset.y, 0));
+    let x1 = cmp::min(rect.offset.x + rect.extent.width as i32, extent.width as i32);
+    let y1 = cmp::min(rect.offset.y + rect.extent.height as i32, extent.height as i32);
+
+    for layer in layers.0..(layers.0 + layers.1) {
+        for z in 0..(extent.depth as i32) {
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    view.write(mip_level, layer, x, y, z, texel);
+                }
+            }
+        }
+    }
+}
+
+/// The rectangle covering a whole mip level.
+fn whole(extent: Extent3D) -> Rect2D {
+    Rect2D {
+        offset: Offset2D { x: 0, y: 0 },
+        extent: Extent2D { width: extent.width, height: extent.height },
+    }
+}
+
+/// A view of one aspect of a range of an image's subresources.
+fn range_view(image: &Image, aspect: ImageAspectFlags, range: &ImageSubresourceRange) -> ImageView {
+    let full = image.view(aspect);
+    let levels = if range.level_count == REMAINING_MIP_LEVELS {
+        full.mip_levels() - range.base_mip_level
+    } else {
+        range.level_count
+    };
+    let layers = if range.layer_count == REMAINING_ARRAY_LAYERS {
+        full.array_layers() - range.base_array_layer
+    } else {
+        range.layer_count
+    };
+    full.subview(aspect, (range.base_mip_level, levels), (range.base_array_layer, layers))
+}
+
+pub fn clear_color_image(image: &Image, color: &ClearColorValue, range: &ImageSubresourceRange) {
+    let view = range_view(image, VK_IMAGE_ASPECT_COLOR_BIT, range);
+    for level in 0..view.mip_levels() {
+        fill(&view, level, (0, view.array_layers()), whole(view.extent(level)), color.0);
+    }
+}
+
+pub fn clear_depth_stencil_image(image: &Image, value: &ClearDepthStencilValue, range: &ImageSubresourceRange) {
+    for &(aspect, texel) in &depth_stencil_texels(value) {
+        if range.aspect_mask.contains(aspect) {
+            let view = range_view(image, aspect, range);
+            for level in 0..view.mip_levels() {
+                fill(&view, level, (0, view.array_layers()), whole(view.extent(level)), texel);
+            }
+        }
+    }
+}
+
+/// Clears the given aspects of a rectangle of a framebuffer attachment.
+pub fn clear_attachment(view: &ImageView, aspects: ImageAspectFlags, value: &ClearValue, rect: Rect2D, layers: (u32, u32)) {
+    if aspects.contains(VK_IMAGE_ASPECT_COLOR_BIT) {
+        fill(view, 0, layers, rect, value.color().0);
+    }
+    for &(aspect, texel) in &depth_stencil_texels(&value.depth_stencil()) {
+        if aspects.contains(aspect) && view.aspect().contains(aspect) {
+            let aspect_view = view.subview(aspect, (0, view.mip_levels()), (0, view.array_layers()));
+            fill(&aspect_view, 0, layers, rect, texel);
+        }
+    }
+}
+
+fn depth_stencil_texels(value: &ClearDepthStencilValue) -> [(ImageAspectFlags, Texel); 2] {
+    [
+        (VK_IMAGE_ASPECT_DEPTH_BIT, [value.depth.to_bits(), 0, 0, 0]),
+        (VK_IMAGE_ASPECT_STENCIL_BIT, [value.stencil, 0, 0, 0]),
+    ]
+}
+
+/// Copies a rectangle of a single-sampled attachment to its resolve attachment.
+pub fn resolve_attachment(src: &ImageView, dst: &ImageView, rect: Rect2D, layers: (u32, u32)) {
+    let (x0, y0) = (rect.offset.x, rect.offset.y);
+    let (x1, y1) = (x0 + rect.extent.width as i32, y0 + rect.extent.height as i32);
+    for layer in layers.0..(layers.0 + layers.1) {
+        for y in y0..y1 {
+            for x in x0..x1 {
+                dst.write(0, layer, x, y, 0, src.read(0, layer, x, y, 0));
+            }
+        }
+    }
+}
+
+/// Scales a region of one image into another, converting between their formats. Each destination
+/// texel takes the source at its center, clamped to the edge of the source subresource.
+pub fn blit_image(src: &Image, dst: &Image, region: &ImageBlit, filter: Filter) {
+    let aspects = [VK_IMAGE_ASPECT_COLOR_BIT, VK_IMAGE_ASPECT_DEPTH_BIT, VK_IMAGE_ASPECT_STENCIL_BIT];
+    for &aspect in aspects.iter().filter(|&&aspect| region.src_subresource.aspect_mask.contains(aspect)) {
+        let (src_subresource, dst_subresource) = (&region.src_subresource, &region.dst_subresource);
+        let src_view = src.view(aspect).subview(aspect, (src_subresource.mip_level, 1), (src_subresource.base_array_layer, src_subresource.layer_count));
+        let dst_view = dst.view(aspect).subview(aspect, (dst_subresource.mip_level, 1), (dst_subresource.base_array_layer, dst_subresource.layer_count));
+        let filter = if aspect == VK_IMAGE_ASPECT_COLOR_BIT && !src_view.is_integer() { filter } else { Filter::Nearest };
+
+        let (s0, s1) = (region.src_offsets[0], region.src_offsets[1]);
+        let (d0, d1) = (region.dst_offsets[0], region.dst_offsets[1]);
+        let scale = |d: i32, d0: i32, d1: i32, s0: i32, s1: i32| {
+            s0 as f32 + (d as f32 + 0.5 - d0 as f32) * (s1 - s0) as f32 / (d1 - d0) as f32
+        };
+
+        for layer in 0..cmp::min(src_view.array_layers(), dst_view.array_layers()) {
+            for z in cmp::min(d0.z, d1.z)..cmp::max(d0.z, d1.z) {
+                for y in cmp::min(d0.y, d1.y)..cmp::max(d0.y, d1.y) {
+                    for x in cmp::min(d0.x, d1.x)..cmp::max(d0.x, d1.x) {
+                        let coordinates = [
+                            scale(x, d0.x, d1.x, s0.x, s1.x),
+                            scale(y, d0.y, d1.y, s0.y, s1.y),
+                            scale(z, d0.z, d1.z, s0.z, s1.z),
+                        ];
+                        let texel = match filter {
+                            Filter::Linear => sample_linear(&src_view, layer, coordinates),
+                            _ => sample_nearest(&src_view, layer, coordinates),
+                        };
+                        dst_view.write(0, layer, x, y, z, texel);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn clamp_to_edge(coordinate: i32, size: u32) -> i32 {
+    cmp::max(0, cmp::min(coordinate, size as i32 - 1))
+}
+
+fn sample_nearest(view: &ImageView, layer: u32, coordinates: [f32; 3]) -> Texel {
+    let extent = view.extent(0);
+    view.read(
+        0,
+        layer,
+        clamp_to_edge(coordinates[0].floor() as i32, extent.width),
+        clamp_to_edge(coordinates[1].floor() as i32, extent.height),
+        clamp_to_edge(coordinates[2].floor() as i32, extent.depth),
+    )
+}
+
+fn sample_linear(view: &ImageView, layer: u32, coordinates: [f32; 3]) -> Texel {
+    let extent = view.extent(0);
+    let sizes = [extent.width, extent.height, extent.depth];
+
+    // Two weighted taps along each dimension the image has
+    let mut taps = [[(0, 1.0), (0, 0.0)]; 3];
+    for i in 0..3 {
+        if sizes[i] > 1 {
+            let position = coordinates[i] - 0.5;
+            let lower = position.floor();
+            let fraction = position - lower;
+            taps[i] = [
+                (clamp_to_edge(lower as i32, sizes[i]), 1.0 - fraction),
+                (clamp_to_edge(lower as i32 + 1, sizes[i]), fraction),
+            ];
+        }
+    }
+
+    let mut result = [0f32; 4];
+    for &(x, x_weight) in &taps[0] {
+        for &(y, y_weight) in &taps[1] {
+            for &(z, z_weight) in &taps[2] {
+                let weight: f32 = x_weight * y_weight * z_weight;
+                if weight == 0.0 {
+                    continue;
+                }
+                let texel = view.read(0, layer, x, y, z);
+                for i in 0..4 {
+                    result[i] += f32::from_bits(texel[i]) * weight;
+                }
+            }
+        }
+    }
+    [result[0].to_bits(), result[1].to_bits(), result[2].to_bits(), result[3].to_bits()]
+}
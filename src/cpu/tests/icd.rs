@@ -0,0 +1,1019 @@
+//! Drives the CPU backend the way the Vulkan loader does, through `vk_icdGetInstanceProcAddr`, and
+//! checks what transfers, dispatches and draws leave in memory.
+
+extern crate vk_middle_cpu;
+extern crate vk_middle_sys as sys;
+
+use sys::*;
+
+use std::ffi::CString;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+unsafe fn proc_addr<T>(instance: VkInstance, name: &str) -> T {
+    let name = CString::new(name).unwrap();
+    let addr = vk_middle_cpu::vk_icdGetInstanceProcAddr(instance as *mut _, name.as_ptr());
+    assert!(addr.is_some(), "{:?} is not advertised", name);
+    mem::transmute_copy(&addr)
+}
+
+/// An instance and a device with its only queue, and a command pool for it.
+struct Context {
+    instance: VkInstance,
+    device: VkDevice,
+    queue: VkQueue,
+    pool: VkCommandPool,
+}
+
+impl Context {
+    unsafe fn new() -> Context {
+        let create_instance: PFN_vkCreateInstance = proc_addr(ptr::null_mut(), "vkCreateInstance");
+        let instance_info = VkInstanceCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_INSTANCE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            pApplicationInfo: ptr::null(),
+            enabledLayerCount: 0,
+            ppEnabledLayerNames: ptr::null(),
+            enabledExtensionCount: 0,
+            ppEnabledExtensionNames: ptr::null(),
+        };
+        let mut instance = ptr::null_mut();
+        assert_eq!(create_instance.unwrap()(&instance_info, ptr::null(), &mut instance), VkResult::VK_SUCCESS);
+
+        let enumerate: PFN_vkEnumeratePhysicalDevices = proc_addr(instance, "vkEnumeratePhysicalDevices");
+        let mut count = 1;
+        let mut physical_device = ptr::null_mut();
+        assert_eq!(enumerate.unwrap()(instance, &mut count, &mut physical_device), VkResult::VK_SUCCESS);
+        assert_eq!(count, 1);
+
+        let priority = 1.0f32;
+        let queue_info = VkDeviceQueueCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_DEVICE_QUEUE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queueFamilyIndex: 0,
+            queueCount: 1,
+            pQueuePriorities: &priority,
+        };
+        let mut features: VkPhysicalDeviceFeatures = mem::zeroed();
+        features.pipelineStatisticsQuery = VK_TRUE;
+        let device_info = VkDeviceCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_DEVICE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queueCreateInfoCount: 1,
+            pQueueCreateInfos: &queue_info,
+            enabledLayerCount: 0,
+            ppEnabledLayerNames: ptr::null(),
+            enabledExtensionCount: 0,
+            ppEnabledExtensionNames: ptr::null(),
+            pEnabledFeatures: &features,
+        };
+        let create_device: PFN_vkCreateDevice = proc_addr(instance, "vkCreateDevice");
+        let mut device = ptr::null_mut();
+        assert_eq!(create_device.unwrap()(physical_device, &device_info, ptr::null(), &mut device), VkResult::VK_SUCCESS);
+
+        let get_queue: PFN_vkGetDeviceQueue = proc_addr(instance, "vkGetDeviceQueue");
+        let mut queue = ptr::null_mut();
+        get_queue.unwrap()(device, 0, 0, &mut queue);
+        assert!(!queue.is_null());
+
+        let create_command_pool: PFN_vkCreateCommandPool = proc_addr(instance, "vkCreateCommandPool");
+        let pool_info = VkCommandPoolCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queueFamilyIndex: 0,
+        };
+        let mut pool = mem::zeroed();
+        assert_eq!(create_command_pool.unwrap()(device, &pool_info, ptr::null(), &mut pool), VkResult::VK_SUCCESS);
+
+        Context { instance: instance, device: device, queue: queue, pool: pool }
+    }
+
+    unsafe fn proc_addr<T>(&self, name: &str) -> T {
+        proc_addr(self.instance, name)
+    }
+
+    /// Allocates memory for a resource with the given requirements, which stays mapped.
+    unsafe fn allocate(&self, requirements: &VkMemoryRequirements) -> (VkDeviceMemory, *mut u8) {
+        assert!(requirements.memoryTypeBits & 1 != 0);
+        let allocate_memory: PFN_vkAllocateMemory = self.proc_addr("vkAllocateMemory");
+        let info = VkMemoryAllocateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+            pNext: ptr::null(),
+            allocationSize: requirements.size,
+            memoryTypeIndex: 0,
+        };
+        let mut memory = mem::zeroed();
+        assert_eq!(allocate_memory.unwrap()(self.device, &info, ptr::null(), &mut memory), VkResult::VK_SUCCESS);
+
+        let map_memory: PFN_vkMapMemory = self.proc_addr("vkMapMemory");
+        let mut mapped = ptr::null_mut();
+        assert_eq!(map_memory.unwrap()(self.device, memory, 0, requirements.size, 0, &mut mapped), VkResult::VK_SUCCESS);
+        (memory, mapped as *mut u8)
+    }
+
+    /// A buffer of `size` bytes in memory of its own, and that memory's mapping.
+    unsafe fn create_buffer(&self, size: u64, usage: VkBufferUsageFlags) -> (VkBuffer, VkDeviceMemory, *mut u8) {
+        let create_buffer: PFN_vkCreateBuffer = self.proc_addr("vkCreateBuffer");
+        let info = VkBufferCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            size: size,
+            usage: usage,
+            sharingMode: VkSharingMode::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: ptr::null(),
+        };
+        let mut buffer = mem::zeroed();
+        assert_eq!(create_buffer.unwrap()(self.device, &info, ptr::null(), &mut buffer), VkResult::VK_SUCCESS);
+
+        let get_requirements: PFN_vkGetBufferMemoryRequirements = self.proc_addr("vkGetBufferMemoryRequirements");
+        let mut requirements: VkMemoryRequirements = mem::zeroed();
+        get_requirements.unwrap()(self.device, buffer, &mut requirements);
+        let (memory, mapped) = self.allocate(&requirements);
+        let bind_buffer_memory: PFN_vkBindBufferMemory = self.proc_addr("vkBindBufferMemory");
+        assert_eq!(bind_buffer_memory.unwrap()(self.device, buffer, memory, 0), VkResult::VK_SUCCESS);
+        (buffer, memory, mapped)
+    }
+
+    /// A 4×4 RGBA8 image in memory of its own.
+    unsafe fn create_image(&self, usage: VkImageUsageFlags) -> (VkImage, VkDeviceMemory) {
+        let create_image: PFN_vkCreateImage = self.proc_addr("vkCreateImage");
+        let info = VkImageCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            imageType: VkImageType::VK_IMAGE_TYPE_2D,
+            format: VkFormat::VK_FORMAT_R8G8B8A8_UNORM,
+            extent: VkExtent3D { width: 4, height: 4, depth: 1 },
+            mipLevels: 1,
+            arrayLayers: 1,
+            samples: VkSampleCountFlagBits::VK_SAMPLE_COUNT_1_BIT,
+            tiling: VkImageTiling::VK_IMAGE_TILING_OPTIMAL,
+            usage: usage,
+            sharingMode: VkSharingMode::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: ptr::null(),
+            initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
+        };
+        let mut image = mem::zeroed();
+        assert_eq!(create_image.unwrap()(self.device, &info, ptr::null(), &mut image), VkResult::VK_SUCCESS);
+
+        let get_requirements: PFN_vkGetImageMemoryRequirements = self.proc_addr("vkGetImageMemoryRequirements");
+        let mut requirements: VkMemoryRequirements = mem::zeroed();
+        get_requirements.unwrap()(self.device, image, &mut requirements);
+        let (memory, _) = self.allocate(&requirements);
+        let bind_image_memory: PFN_vkBindImageMemory = self.proc_addr("vkBindImageMemory");
+        assert_eq!(bind_image_memory.unwrap()(self.device, image, memory, 0), VkResult::VK_SUCCESS);
+        (image, memory)
+    }
+
+    unsafe fn create_shader_module(&self, code: &[u32]) -> VkShaderModule {
+        let create_shader_module: PFN_vkCreateShaderModule = self.proc_addr("vkCreateShaderModule");
+        let info = VkShaderModuleCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_SHADER_MODULE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            codeSize: code.len() * 4,
+            pCode: code.as_ptr(),
+        };
+        let mut module = mem::zeroed();
+        assert_eq!(create_shader_module.unwrap()(self.device, &info, ptr::null(), &mut module), VkResult::VK_SUCCESS);
+        module
+    }
+
+    unsafe fn create_pipeline_layout(&self, set_layouts: &[VkDescriptorSetLayout]) -> VkPipelineLayout {
+        let create_pipeline_layout: PFN_vkCreatePipelineLayout = self.proc_addr("vkCreatePipelineLayout");
+        let info = VkPipelineLayoutCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_LAYOUT_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            setLayoutCount: set_layouts.len() as u32,
+            pSetLayouts: set_layouts.as_ptr(),
+            pushConstantRangeCount: 0,
+            pPushConstantRanges: ptr::null(),
+        };
+        let mut layout = mem::zeroed();
+        assert_eq!(create_pipeline_layout.unwrap()(self.device, &info, ptr::null(), &mut layout), VkResult::VK_SUCCESS);
+        layout
+    }
+
+    unsafe fn create_query_pool(&self, query_type: VkQueryType, pipeline_statistics: VkQueryPipelineStatisticFlags) -> VkQueryPool {
+        let create_query_pool: PFN_vkCreateQueryPool = self.proc_addr("vkCreateQueryPool");
+        let info = VkQueryPoolCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_QUERY_POOL_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queryType: query_type,
+            queryCount: 1,
+            pipelineStatistics: pipeline_statistics,
+        };
+        let mut pool = mem::zeroed();
+        assert_eq!(create_query_pool.unwrap()(self.device, &info, ptr::null(), &mut pool), VkResult::VK_SUCCESS);
+        pool
+    }
+
+    /// The 64-bit results of the only query of `pool`, waiting for them.
+    unsafe fn query_results(&self, pool: VkQueryPool, count: usize) -> Vec<u64> {
+        let get_query_pool_results: PFN_vkGetQueryPoolResults = self.proc_addr("vkGetQueryPoolResults");
+        let mut results = vec![0u64; count];
+        let flags = VkQueryResultFlagBits::VK_QUERY_RESULT_64_BIT as u32 | VkQueryResultFlagBits::VK_QUERY_RESULT_WAIT_BIT as u32;
+        let result = get_query_pool_results.unwrap()(self.device, pool, 0, 1, count * 8, results.as_mut_ptr() as *mut _, count as u64 * 8, flags);
+        assert_eq!(result, VkResult::VK_SUCCESS);
+        results
+    }
+
+    /// Records a primary command buffer with `commands`, submits it and waits for it to finish.
+    unsafe fn execute(&self, commands: &Fn(VkCommandBuffer)) {
+        let allocate_command_buffers: PFN_vkAllocateCommandBuffers = self.proc_addr("vkAllocateCommandBuffers");
+        let allocate_info = VkCommandBufferAllocateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO,
+            pNext: ptr::null(),
+            commandPool: self.pool,
+            level: VkCommandBufferLevel::VK_COMMAND_BUFFER_LEVEL_PRIMARY,
+            commandBufferCount: 1,
+        };
+        let mut command_buffer = ptr::null_mut();
+        assert_eq!(allocate_command_buffers.unwrap()(self.device, &allocate_info, &mut command_buffer), VkResult::VK_SUCCESS);
+
+        let begin: PFN_vkBeginCommandBuffer = self.proc_addr("vkBeginCommandBuffer");
+        let end: PFN_vkEndCommandBuffer = self.proc_addr("vkEndCommandBuffer");
+        let begin_info = VkCommandBufferBeginInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+            pNext: ptr::null(),
+            flags: VkCommandBufferUsageFlagBits::VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT as u32,
+            pInheritanceInfo: ptr::null(),
+        };
+        assert_eq!(begin.unwrap()(command_buffer, &begin_info), VkResult::VK_SUCCESS);
+        commands(command_buffer);
+        assert_eq!(end.unwrap()(command_buffer), VkResult::VK_SUCCESS);
+
+        let submit: PFN_vkQueueSubmit = self.proc_addr("vkQueueSubmit");
+        let submit_info = VkSubmitInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_SUBMIT_INFO,
+            pNext: ptr::null(),
+            waitSemaphoreCount: 0,
+            pWaitSemaphores: ptr::null(),
+            pWaitDstStageMask: ptr::null(),
+            commandBufferCount: 1,
+            pCommandBuffers: &command_buffer,
+            signalSemaphoreCount: 0,
+            pSignalSemaphores: ptr::null(),
+        };
+        assert_eq!(submit.unwrap()(self.queue, 1, &submit_info, mem::zeroed()), VkResult::VK_SUCCESS);
+        let queue_wait_idle: PFN_vkQueueWaitIdle = self.proc_addr("vkQueueWaitIdle");
+        assert_eq!(queue_wait_idle.unwrap()(self.queue), VkResult::VK_SUCCESS);
+
+        let free_command_buffers: PFN_vkFreeCommandBuffers = self.proc_addr("vkFreeCommandBuffers");
+        free_command_buffers.unwrap()(self.device, self.pool, 1, &command_buffer);
+    }
+
+    /// Makes writes by `src_stage` and `src_access` visible to `dst_stage` and `dst_access`.
+    unsafe fn barrier(&self, command_buffer: VkCommandBuffer, src: (VkPipelineStageFlagBits, VkAccessFlagBits), dst: (VkPipelineStageFlagBits, VkAccessFlagBits)) {
+        let pipeline_barrier: PFN_vkCmdPipelineBarrier = self.proc_addr("vkCmdPipelineBarrier");
+        let barrier = VkMemoryBarrier {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: src.1 as u32,
+            dstAccessMask: dst.1 as u32,
+        };
+        pipeline_barrier.unwrap()(command_buffer, src.0 as u32, dst.0 as u32, 0, 1, &barrier, 0, ptr::null(), 0, ptr::null());
+    }
+
+    /// Moves an image written by transfers between layouts.
+    unsafe fn transition(&self, command_buffer: VkCommandBuffer, image: VkImage, old_layout: VkImageLayout, new_layout: VkImageLayout) {
+        let pipeline_barrier: PFN_vkCmdPipelineBarrier = self.proc_addr("vkCmdPipelineBarrier");
+        let barrier = VkImageMemoryBarrier {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: VkAccessFlagBits::VK_ACCESS_TRANSFER_WRITE_BIT as u32,
+            dstAccessMask: VkAccessFlagBits::VK_ACCESS_TRANSFER_READ_BIT as u32 | VkAccessFlagBits::VK_ACCESS_TRANSFER_WRITE_BIT as u32,
+            oldLayout: old_layout,
+            newLayout: new_layout,
+            srcQueueFamilyIndex: !0,
+            dstQueueFamilyIndex: !0,
+            image: image,
+            subresourceRange: VkImageSubresourceRange {
+                aspectMask: VkImageAspectFlagBits::VK_IMAGE_ASPECT_COLOR_BIT as u32,
+                baseMipLevel: 0,
+                levelCount: 1,
+                baseArrayLayer: 0,
+                layerCount: 1,
+            },
+        };
+        let transfer = VkPipelineStageFlagBits::VK_PIPELINE_STAGE_TRANSFER_BIT as u32;
+        pipeline_barrier.unwrap()(command_buffer, transfer, transfer, 0, 0, ptr::null(), 0, ptr::null(), 1, &barrier);
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe {
+            let destroy_command_pool: PFN_vkDestroyCommandPool = self.proc_addr("vkDestroyCommandPool");
+            let destroy_device: PFN_vkDestroyDevice = self.proc_addr("vkDestroyDevice");
+            let destroy_instance: PFN_vkDestroyInstance = self.proc_addr("vkDestroyInstance");
+            destroy_command_pool.unwrap()(self.device, self.pool, ptr::null());
+            destroy_device.unwrap()(self.device, ptr::null());
+            destroy_instance.unwrap()(self.instance, ptr::null());
+        }
+    }
+}
+
+/// The whole of a 4×4 image, as copy regions see it.
+fn whole_image(buffer_offset: u64) -> VkBufferImageCopy {
+    VkBufferImageCopy {
+        bufferOffset: buffer_offset,
+        bufferRowLength: 0,
+        bufferImageHeight: 0,
+        imageSubresource: color_layers(),
+        imageOffset: VkOffset3D { x: 0, y: 0, z: 0 },
+        imageExtent: VkExtent3D { width: 4, height: 4, depth: 1 },
+    }
+}
+
+fn color_layers() -> VkImageSubresourceLayers {
+    VkImageSubresourceLayers {
+        aspectMask: VkImageAspectFlagBits::VK_IMAGE_ASPECT_COLOR_BIT as u32,
+        mipLevel: 0,
+        baseArrayLayer: 0,
+        layerCount: 1,
+    }
+}
+
+/// Builds SPIR-V modules one instruction at a time. Opcodes and operands are the numbers the
+/// SPIR-V specification gives them.
+struct Module {
+    words: Vec<u32>,
+}
+
+impl Module {
+    /// Starts a shader module with a single entry point named `main`, whose ID is 1.
+    fn new(model: u32, interface: &[u32]) -> Module {
+        let mut module = Module { words: vec![0x07230203, 0x00010000, 0, 100, 0] };
+        module.op(17, &[1]); // OpCapability Shader
+        module.op(14, &[0, 1]); // OpMemoryModel Logical GLSL450
+        let mut operands = vec![model, 1, u32::from_le(0x6e69616d), 0]; // "main"
+        operands.extend_from_slice(interface);
+        module.op(15, &operands); // OpEntryPoint
+        module
+    }
+
+    fn op(&mut self, opcode: u32, operands: &[u32]) -> &mut Module {
+        self.words.push((operands.len() as u32 + 1) << 16 | opcode);
+        self.words.extend_from_slice(operands);
+        self
+    }
+}
+
+// IDs the shaders share.
+const MAIN: u32 = 1;
+const VOID: u32 = 2;
+const VOID_FN: u32 = 3;
+const UINT: u32 = 4;
+const FLOAT: u32 = 5;
+const VEC4: u32 = 6;
+
+fn declare_types(module: &mut Module) {
+    module.op(19, &[VOID]); // OpTypeVoid
+    module.op(33, &[VOID_FN, VOID]); // OpTypeFunction
+    module.op(21, &[UINT, 32, 0]); // OpTypeInt
+    module.op(22, &[FLOAT, 32]); // OpTypeFloat
+    module.op(23, &[VEC4, FLOAT, 4]); // OpTypeVector
+}
+
+fn begin_main(module: &mut Module) {
+    module.op(54, &[VOID, MAIN, 0, VOID_FN]); // OpFunction
+    module.op(248, &[10]); // OpLabel
+}
+
+fn end_main(module: &mut Module) {
+    module.op(253, &[]); // OpReturn
+    module.op(56, &[]); // OpFunctionEnd
+}
+
+/// A compute shader with 4 invocations per workgroup, which writes `index + 1` to element `index`
+/// of the storage buffer at binding 0 of set 0, where `index` is its global invocation ID.
+fn compute_shader() -> Vec<u32> {
+    const UVEC3: u32 = 20;
+    const INPUT_UVEC3: u32 = 21;
+    const GLOBAL_ID: u32 = 22;
+    const ARRAY: u32 = 23;
+    const BLOCK: u32 = 24;
+    const BLOCK_POINTER: u32 = 25;
+    const ELEMENT_POINTER: u32 = 26;
+    const BUFFER: u32 = 27;
+    const ZERO: u32 = 28;
+    const ONE: u32 = 29;
+
+    let mut module = Module::new(5, &[GLOBAL_ID]); // GLCompute
+    module.op(16, &[MAIN, 17, 4, 1, 1]); // OpExecutionMode LocalSize
+    module.op(71, &[GLOBAL_ID, 11, 28]); // OpDecorate BuiltIn GlobalInvocationId
+    module.op(71, &[ARRAY, 6, 4]); // OpDecorate ArrayStride
+    module.op(72, &[BLOCK, 0, 35, 0]); // OpMemberDecorate Offset
+    module.op(71, &[BLOCK, 3]); // OpDecorate BufferBlock
+    module.op(71, &[BUFFER, 34, 0]); // OpDecorate DescriptorSet
+    module.op(71, &[BUFFER, 33, 0]); // OpDecorate Binding
+    declare_types(&mut module);
+    module.op(23, &[UVEC3, UINT, 3]); // OpTypeVector
+    module.op(32, &[INPUT_UVEC3, 1, UVEC3]); // OpTypePointer Input
+    module.op(29, &[ARRAY, UINT]); // OpTypeRuntimeArray
+    module.op(30, &[BLOCK, ARRAY]); // OpTypeStruct
+    module.op(32, &[BLOCK_POINTER, 2, BLOCK]); // OpTypePointer Uniform
+    module.op(32, &[ELEMENT_POINTER, 2, UINT]); // OpTypePointer Uniform
+    module.op(43, &[UINT, ZERO, 0]); // OpConstant
+    module.op(43, &[UINT, ONE, 1]); // OpConstant
+    module.op(59, &[INPUT_UVEC3, GLOBAL_ID, 1]); // OpVariable Input
+    module.op(59, &[BLOCK_POINTER, BUFFER, 2]); // OpVariable Uniform
+    begin_main(&mut module);
+    module.op(61, &[UVEC3, 40, GLOBAL_ID]); // OpLoad
+    module.op(81, &[UINT, 41, 40, 0]); // OpCompositeExtract
+    module.op(128, &[UINT, 42, 41, ONE]); // OpIAdd
+    module.op(65, &[ELEMENT_POINTER, 43, BUFFER, ZERO, 41]); // OpAccessChain
+    module.op(62, &[43, 42]); // OpStore
+    end_main(&mut module);
+    module.words
+}
+
+/// A vertex shader that passes the attribute at location 0 through as the position.
+fn vertex_shader() -> Vec<u32> {
+    const INPUT_VEC4: u32 = 20;
+    const OUTPUT_VEC4: u32 = 21;
+    const ATTRIBUTE: u32 = 22;
+    const POSITION: u32 = 23;
+
+    let mut module = Module::new(0, &[ATTRIBUTE, POSITION]); // Vertex
+    module.op(71, &[ATTRIBUTE, 30, 0]); // OpDecorate Location
+    module.op(71, &[POSITION, 11, 0]); // OpDecorate BuiltIn Position
+    declare_types(&mut module);
+    module.op(32, &[INPUT_VEC4, 1, VEC4]); // OpTypePointer Input
+    module.op(32, &[OUTPUT_VEC4, 3, VEC4]); // OpTypePointer Output
+    module.op(59, &[INPUT_VEC4, ATTRIBUTE, 1]); // OpVariable Input
+    module.op(59, &[OUTPUT_VEC4, POSITION, 3]); // OpVariable Output
+    begin_main(&mut module);
+    module.op(61, &[VEC4, 40, ATTRIBUTE]); // OpLoad
+    module.op(62, &[POSITION, 40]); // OpStore
+    end_main(&mut module);
+    module.words
+}
+
+/// A fragment shader that writes opaque red to location 0.
+fn fragment_shader() -> Vec<u32> {
+    const OUTPUT_VEC4: u32 = 20;
+    const COLOR: u32 = 21;
+    const ZERO: u32 = 22;
+    const ONE: u32 = 23;
+    const RED: u32 = 24;
+
+    let mut module = Module::new(4, &[COLOR]); // Fragment
+    module.op(16, &[MAIN, 7]); // OpExecutionMode OriginUpperLeft
+    module.op(71, &[COLOR, 30, 0]); // OpDecorate Location
+    declare_types(&mut module);
+    module.op(32, &[OUTPUT_VEC4, 3, VEC4]); // OpTypePointer Output
+    module.op(43, &[FLOAT, ZERO, 0.0f32.to_bits()]); // OpConstant
+    module.op(43, &[FLOAT, ONE, 1.0f32.to_bits()]); // OpConstant
+    module.op(44, &[VEC4, RED, ONE, ZERO, ZERO, ONE]); // OpConstantComposite
+    module.op(59, &[OUTPUT_VEC4, COLOR, 3]); // OpVariable Output
+    begin_main(&mut module);
+    module.op(62, &[COLOR, RED]); // OpStore
+    end_main(&mut module);
+    module.words
+}
+
+#[test]
+fn copies_between_buffers_and_images() {
+    use VkImageLayout::*;
+
+    unsafe {
+        let context = Context::new();
+        let transfer = VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_SRC_BIT as u32 |
+            VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_DST_BIT as u32;
+        let (upload, upload_memory, upload_data) = context.create_buffer(64, transfer);
+        let (staging, staging_memory, _) = context.create_buffer(64, transfer);
+        let (readback, readback_memory, readback_data) = context.create_buffer(128, transfer);
+        let image_usage = VkImageUsageFlagBits::VK_IMAGE_USAGE_TRANSFER_SRC_BIT as u32 |
+            VkImageUsageFlagBits::VK_IMAGE_USAGE_TRANSFER_DST_BIT as u32;
+        let (first, first_memory) = context.create_image(image_usage);
+        let (second, second_memory) = context.create_image(image_usage);
+
+        let upload_data = slice::from_raw_parts_mut(upload_data, 64);
+        for (i, byte) in upload_data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let readback_data = slice::from_raw_parts(readback_data, 128);
+
+        let copy_buffer: PFN_vkCmdCopyBuffer = context.proc_addr("vkCmdCopyBuffer");
+        let fill_buffer: PFN_vkCmdFillBuffer = context.proc_addr("vkCmdFillBuffer");
+        let copy_buffer_to_image: PFN_vkCmdCopyBufferToImage = context.proc_addr("vkCmdCopyBufferToImage");
+        let copy_image: PFN_vkCmdCopyImage = context.proc_addr("vkCmdCopyImage");
+        let copy_image_to_buffer: PFN_vkCmdCopyImageToBuffer = context.proc_addr("vkCmdCopyImageToBuffer");
+        let transfer_write = (VkPipelineStageFlagBits::VK_PIPELINE_STAGE_TRANSFER_BIT, VkAccessFlagBits::VK_ACCESS_TRANSFER_WRITE_BIT);
+        let transfer_read = (VkPipelineStageFlagBits::VK_PIPELINE_STAGE_TRANSFER_BIT, VkAccessFlagBits::VK_ACCESS_TRANSFER_READ_BIT);
+        context.execute(&|x| {
+            copy_buffer.unwrap()(x, upload, staging, 1, &VkBufferCopy { srcOffset: 0, dstOffset: 0, size: 64 });
+            fill_buffer.unwrap()(x, readback, 0, !0, 0xffffffff);
+            context.transition(x, first, VK_IMAGE_LAYOUT_UNDEFINED, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+            context.transition(x, second, VK_IMAGE_LAYOUT_UNDEFINED, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+            context.barrier(x, transfer_write, transfer_read);
+            copy_buffer_to_image.unwrap()(x, staging, first, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, 1, &whole_image(0));
+            context.transition(x, first, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL);
+            // Swaps the top and bottom halves.
+            let regions = [
+                VkImageCopy {
+                    srcSubresource: color_layers(),
+                    srcOffset: VkOffset3D { x: 0, y: 0, z: 0 },
+                    dstSubresource: color_layers(),
+                    dstOffset: VkOffset3D { x: 0, y: 2, z: 0 },
+                    extent: VkExtent3D { width: 4, height: 2, depth: 1 },
+                },
+                VkImageCopy {
+                    srcSubresource: color_layers(),
+                    srcOffset: VkOffset3D { x: 0, y: 2, z: 0 },
+                    dstSubresource: color_layers(),
+                    dstOffset: VkOffset3D { x: 0, y: 0, z: 0 },
+                    extent: VkExtent3D { width: 4, height: 2, depth: 1 },
+                },
+            ];
+            copy_image.unwrap()(x, first, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, second, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, 2, regions.as_ptr());
+            context.transition(x, second, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL);
+            copy_image_to_buffer.unwrap()(x, second, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, readback, 1, &whole_image(32));
+        });
+
+        // Rows are 16 bytes, so the halves are 32.
+        assert!(readback_data[..32].iter().all(|&x| x == 0xff));
+        assert_eq!(&readback_data[32..64], &upload_data[32..]);
+        assert_eq!(&readback_data[64..96], &upload_data[..32]);
+        assert!(readback_data[96..].iter().all(|&x| x == 0xff));
+
+        let destroy_buffer: PFN_vkDestroyBuffer = context.proc_addr("vkDestroyBuffer");
+        let destroy_image: PFN_vkDestroyImage = context.proc_addr("vkDestroyImage");
+        let free_memory: PFN_vkFreeMemory = context.proc_addr("vkFreeMemory");
+        for &(buffer, memory) in &[(upload, upload_memory), (staging, staging_memory), (readback, readback_memory)] {
+            destroy_buffer.unwrap()(context.device, buffer, ptr::null());
+            free_memory.unwrap()(context.device, memory, ptr::null());
+        }
+        for &(image, memory) in &[(first, first_memory), (second, second_memory)] {
+            destroy_image.unwrap()(context.device, image, ptr::null());
+            free_memory.unwrap()(context.device, memory, ptr::null());
+        }
+    }
+}
+
+#[test]
+fn dispatches_write_storage_buffers() {
+    unsafe {
+        let context = Context::new();
+        let usage = VkBufferUsageFlagBits::VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as u32;
+        let (buffer, memory, data) = context.create_buffer(64, usage);
+        let data = slice::from_raw_parts_mut(data as *mut u32, 16);
+        for value in data.iter_mut() {
+            *value = 0;
+        }
+
+        let create_set_layout: PFN_vkCreateDescriptorSetLayout = context.proc_addr("vkCreateDescriptorSetLayout");
+        let binding = VkDescriptorSetLayoutBinding {
+            binding: 0,
+            descriptorType: VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            descriptorCount: 1,
+            stageFlags: VkShaderStageFlagBits::VK_SHADER_STAGE_COMPUTE_BIT as u32,
+            pImmutableSamplers: ptr::null(),
+        };
+        let set_layout_info = VkDescriptorSetLayoutCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            bindingCount: 1,
+            pBindings: &binding,
+        };
+        let mut set_layout = mem::zeroed();
+        assert_eq!(create_set_layout.unwrap()(context.device, &set_layout_info, ptr::null(), &mut set_layout), VkResult::VK_SUCCESS);
+
+        let create_descriptor_pool: PFN_vkCreateDescriptorPool = context.proc_addr("vkCreateDescriptorPool");
+        let pool_size = VkDescriptorPoolSize {
+            type_: VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            descriptorCount: 1,
+        };
+        let pool_info = VkDescriptorPoolCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            maxSets: 1,
+            poolSizeCount: 1,
+            pPoolSizes: &pool_size,
+        };
+        let mut descriptor_pool = mem::zeroed();
+        assert_eq!(create_descriptor_pool.unwrap()(context.device, &pool_info, ptr::null(), &mut descriptor_pool), VkResult::VK_SUCCESS);
+
+        let allocate_descriptor_sets: PFN_vkAllocateDescriptorSets = context.proc_addr("vkAllocateDescriptorSets");
+        let allocate_info = VkDescriptorSetAllocateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_ALLOCATE_INFO,
+            pNext: ptr::null(),
+            descriptorPool: descriptor_pool,
+            descriptorSetCount: 1,
+            pSetLayouts: &set_layout,
+        };
+        let mut set = mem::zeroed();
+        assert_eq!(allocate_descriptor_sets.unwrap()(context.device, &allocate_info, &mut set), VkResult::VK_SUCCESS);
+
+        let update_descriptor_sets: PFN_vkUpdateDescriptorSets = context.proc_addr("vkUpdateDescriptorSets");
+        let buffer_info = VkDescriptorBufferInfo { buffer: buffer, offset: 0, range: !0 };
+        let write = VkWriteDescriptorSet {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_WRITE_DESCRIPTOR_SET,
+            pNext: ptr::null(),
+            dstSet: set,
+            dstBinding: 0,
+            dstArrayElement: 0,
+            descriptorCount: 1,
+            descriptorType: VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            pImageInfo: ptr::null(),
+            pBufferInfo: &buffer_info,
+            pTexelBufferView: ptr::null(),
+        };
+        update_descriptor_sets.unwrap()(context.device, 1, &write, 0, ptr::null());
+
+        let module = context.create_shader_module(&compute_shader());
+        let layout = context.create_pipeline_layout(&[set_layout]);
+        let name = CString::new("main").unwrap();
+        let pipeline_info = VkComputePipelineCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMPUTE_PIPELINE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            stage: VkPipelineShaderStageCreateInfo {
+                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_COMPUTE_BIT,
+                module: module,
+                pName: name.as_ptr(),
+                pSpecializationInfo: ptr::null(),
+            },
+            layout: layout,
+            basePipelineHandle: mem::zeroed(),
+            basePipelineIndex: -1,
+        };
+        let create_compute_pipelines: PFN_vkCreateComputePipelines = context.proc_addr("vkCreateComputePipelines");
+        let mut pipeline = mem::zeroed();
+        assert_eq!(create_compute_pipelines.unwrap()(context.device, mem::zeroed(), 1, &pipeline_info, ptr::null(), &mut pipeline), VkResult::VK_SUCCESS);
+
+        let statistics = context.create_query_pool(
+            VkQueryType::VK_QUERY_TYPE_PIPELINE_STATISTICS,
+            VkQueryPipelineStatisticFlagBits::VK_QUERY_PIPELINE_STATISTIC_COMPUTE_SHADER_INVOCATIONS_BIT as u32,
+        );
+
+        let bind_pipeline: PFN_vkCmdBindPipeline = context.proc_addr("vkCmdBindPipeline");
+        let bind_descriptor_sets: PFN_vkCmdBindDescriptorSets = context.proc_addr("vkCmdBindDescriptorSets");
+        let dispatch: PFN_vkCmdDispatch = context.proc_addr("vkCmdDispatch");
+        let reset_query_pool: PFN_vkCmdResetQueryPool = context.proc_addr("vkCmdResetQueryPool");
+        let begin_query: PFN_vkCmdBeginQuery = context.proc_addr("vkCmdBeginQuery");
+        let end_query: PFN_vkCmdEndQuery = context.proc_addr("vkCmdEndQuery");
+        let compute = VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_COMPUTE;
+        context.execute(&|x| {
+            reset_query_pool.unwrap()(x, statistics, 0, 1);
+            bind_pipeline.unwrap()(x, compute, pipeline);
+            bind_descriptor_sets.unwrap()(x, compute, layout, 0, 1, &set, 0, ptr::null());
+            begin_query.unwrap()(x, statistics, 0, 0);
+            dispatch.unwrap()(x, 3, 1, 1);
+            end_query.unwrap()(x, statistics, 0);
+        });
+
+        let expected: Vec<u32> = (1..13).chain(vec![0; 4]).collect();
+        assert_eq!(data.to_vec(), expected);
+        assert_eq!(context.query_results(statistics, 1), [12]);
+
+        let destroy_query_pool: PFN_vkDestroyQueryPool = context.proc_addr("vkDestroyQueryPool");
+        let destroy_pipeline: PFN_vkDestroyPipeline = context.proc_addr("vkDestroyPipeline");
+        let destroy_pipeline_layout: PFN_vkDestroyPipelineLayout = context.proc_addr("vkDestroyPipelineLayout");
+        let destroy_shader_module: PFN_vkDestroyShaderModule = context.proc_addr("vkDestroyShaderModule");
+        let destroy_descriptor_pool: PFN_vkDestroyDescriptorPool = context.proc_addr("vkDestroyDescriptorPool");
+        let destroy_set_layout: PFN_vkDestroyDescriptorSetLayout = context.proc_addr("vkDestroyDescriptorSetLayout");
+        let destroy_buffer: PFN_vkDestroyBuffer = context.proc_addr("vkDestroyBuffer");
+        let free_memory: PFN_vkFreeMemory = context.proc_addr("vkFreeMemory");
+        destroy_query_pool.unwrap()(context.device, statistics, ptr::null());
+        destroy_pipeline.unwrap()(context.device, pipeline, ptr::null());
+        destroy_pipeline_layout.unwrap()(context.device, layout, ptr::null());
+        destroy_shader_module.unwrap()(context.device, module, ptr::null());
+        destroy_descriptor_pool.unwrap()(context.device, descriptor_pool, ptr::null());
+        destroy_set_layout.unwrap()(context.device, set_layout, ptr::null());
+        destroy_buffer.unwrap()(context.device, buffer, ptr::null());
+        free_memory.unwrap()(context.device, memory, ptr::null());
+    }
+}
+
+#[test]
+fn draws_rasterize_into_color_attachments() {
+    use VkImageLayout::*;
+
+    unsafe {
+        let context = Context::new();
+        let (image, image_memory) = context.create_image(
+            VkImageUsageFlagBits::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT as u32 |
+            VkImageUsageFlagBits::VK_IMAGE_USAGE_TRANSFER_SRC_BIT as u32,
+        );
+        let (readback, readback_memory, readback_data) = context.create_buffer(64, VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_DST_BIT as u32);
+        let readback_data = slice::from_raw_parts(readback_data, 64);
+
+        // A triangle over the whole left half of the image, whose right edge is the vertical center line.
+        let (vertices, vertex_memory, vertex_data) = context.create_buffer(48, VkBufferUsageFlagBits::VK_BUFFER_USAGE_VERTEX_BUFFER_BIT as u32);
+        let positions: [[f32; 4]; 3] = [[-3.0, -1.0, 0.0, 1.0], [0.0, -1.0, 0.0, 1.0], [0.0, 3.0, 0.0, 1.0]];
+        ptr::copy_nonoverlapping(positions.as_ptr() as *const u8, vertex_data, 48);
+
+        let create_image_view: PFN_vkCreateImageView = context.proc_addr("vkCreateImageView");
+        let view_info = VkImageViewCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            image: image,
+            viewType: VkImageViewType::VK_IMAGE_VIEW_TYPE_2D,
+            format: VkFormat::VK_FORMAT_R8G8B8A8_UNORM,
+            components: VkComponentMapping {
+                r: VkComponentSwizzle::VK_COMPONENT_SWIZZLE_IDENTITY,
+                g: VkComponentSwizzle::VK_COMPONENT_SWIZZLE_IDENTITY,
+                b: VkComponentSwizzle::VK_COMPONENT_SWIZZLE_IDENTITY,
+                a: VkComponentSwizzle::VK_COMPONENT_SWIZZLE_IDENTITY,
+            },
+            subresourceRange: VkImageSubresourceRange {
+                aspectMask: VkImageAspectFlagBits::VK_IMAGE_ASPECT_COLOR_BIT as u32,
+                baseMipLevel: 0,
+                levelCount: 1,
+                baseArrayLayer: 0,
+                layerCount: 1,
+            },
+        };
+        let mut view = mem::zeroed();
+        assert_eq!(create_image_view.unwrap()(context.device, &view_info, ptr::null(), &mut view), VkResult::VK_SUCCESS);
+
+        let attachment = VkAttachmentDescription {
+            flags: 0,
+            format: VkFormat::VK_FORMAT_R8G8B8A8_UNORM,
+            samples: VkSampleCountFlagBits::VK_SAMPLE_COUNT_1_BIT,
+            loadOp: VkAttachmentLoadOp::VK_ATTACHMENT_LOAD_OP_CLEAR,
+            storeOp: VkAttachmentStoreOp::VK_ATTACHMENT_STORE_OP_STORE,
+            stencilLoadOp: VkAttachmentLoadOp::VK_ATTACHMENT_LOAD_OP_DONT_CARE,
+            stencilStoreOp: VkAttachmentStoreOp::VK_ATTACHMENT_STORE_OP_DONT_CARE,
+            initialLayout: VK_IMAGE_LAYOUT_UNDEFINED,
+            finalLayout: VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL,
+        };
+        let color_reference = VkAttachmentReference { attachment: 0, layout: VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL };
+        let subpass = VkSubpassDescription {
+            flags: 0,
+            pipelineBindPoint: VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_GRAPHICS,
+            inputAttachmentCount: 0,
+            pInputAttachments: ptr::null(),
+            colorAttachmentCount: 1,
+            pColorAttachments: &color_reference,
+            pResolveAttachments: ptr::null(),
+            pDepthStencilAttachment: ptr::null(),
+            preserveAttachmentCount: 0,
+            pPreserveAttachments: ptr::null(),
+        };
+        let render_pass_info = VkRenderPassCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_RENDER_PASS_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            attachmentCount: 1,
+            pAttachments: &attachment,
+            subpassCount: 1,
+            pSubpasses: &subpass,
+            dependencyCount: 0,
+            pDependencies: ptr::null(),
+        };
+        let create_render_pass: PFN_vkCreateRenderPass = context.proc_addr("vkCreateRenderPass");
+        let mut render_pass = mem::zeroed();
+        assert_eq!(create_render_pass.unwrap()(context.device, &render_pass_info, ptr::null(), &mut render_pass), VkResult::VK_SUCCESS);
+
+        let framebuffer_info = VkFramebufferCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_FRAMEBUFFER_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            renderPass: render_pass,
+            attachmentCount: 1,
+            pAttachments: &view,
+            width: 4,
+            height: 4,
+            layers: 1,
+        };
+        let create_framebuffer: PFN_vkCreateFramebuffer = context.proc_addr("vkCreateFramebuffer");
+        let mut framebuffer = mem::zeroed();
+        assert_eq!(create_framebuffer.unwrap()(context.device, &framebuffer_info, ptr::null(), &mut framebuffer), VkResult::VK_SUCCESS);
+
+        let vertex_module = context.create_shader_module(&vertex_shader());
+        let fragment_module = context.create_shader_module(&fragment_shader());
+        let layout = context.create_pipeline_layout(&[]);
+        let name = CString::new("main").unwrap();
+        let stages = [
+            VkPipelineShaderStageCreateInfo {
+                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_VERTEX_BIT,
+                module: vertex_module,
+                pName: name.as_ptr(),
+                pSpecializationInfo: ptr::null(),
+            },
+            VkPipelineShaderStageCreateInfo {
+                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_FRAGMENT_BIT,
+                module: fragment_module,
+                pName: name.as_ptr(),
+                pSpecializationInfo: ptr::null(),
+            },
+        ];
+        let vertex_binding = VkVertexInputBindingDescription {
+            binding: 0,
+            stride: 16,
+            inputRate: VkVertexInputRate::VK_VERTEX_INPUT_RATE_VERTEX,
+        };
+        let vertex_attribute = VkVertexInputAttributeDescription {
+            location: 0,
+            binding: 0,
+            format: VkFormat::VK_FORMAT_R32G32B32A32_SFLOAT,
+            offset: 0,
+        };
+        let vertex_input = VkPipelineVertexInputStateCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            vertexBindingDescriptionCount: 1,
+            pVertexBindingDescriptions: &vertex_binding,
+            vertexAttributeDescriptionCount: 1,
+            pVertexAttributeDescriptions: &vertex_attribute,
+        };
+        let input_assembly = VkPipelineInputAssemblyStateCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            topology: VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST,
+            primitiveRestartEnable: VK_FALSE,
+        };
+        let viewport = VkViewport { x: 0.0, y: 0.0, width: 4.0, height: 4.0, minDepth: 0.0, maxDepth: 1.0 };
+        let scissor = VkRect2D { offset: VkOffset2D { x: 0, y: 0 }, extent: VkExtent2D { width: 4, height: 4 } };
+        let viewport_state = VkPipelineViewportStateCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            viewportCount: 1,
+            pViewports: &viewport,
+            scissorCount: 1,
+            pScissors: &scissor,
+        };
+        let rasterization = VkPipelineRasterizationStateCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            depthClampEnable: VK_FALSE,
+            rasterizerDiscardEnable: VK_FALSE,
+            polygonMode: VkPolygonMode::VK_POLYGON_MODE_FILL,
+            cullMode: VkCullModeFlagBits::VK_CULL_MODE_NONE as u32,
+            frontFace: VkFrontFace::VK_FRONT_FACE_COUNTER_CLOCKWISE,
+            depthBiasEnable: VK_FALSE,
+            depthBiasConstantFactor: 0.0,
+            depthBiasClamp: 0.0,
+            depthBiasSlopeFactor: 0.0,
+            lineWidth: 1.0,
+        };
+        let multisample = VkPipelineMultisampleStateCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            rasterizationSamples: VkSampleCountFlagBits::VK_SAMPLE_COUNT_1_BIT,
+            sampleShadingEnable: VK_FALSE,
+            minSampleShading: 0.0,
+            pSampleMask: ptr::null(),
+            alphaToCoverageEnable: VK_FALSE,
+            alphaToOneEnable: VK_FALSE,
+        };
+        let blend_attachment = VkPipelineColorBlendAttachmentState {
+            blendEnable: VK_FALSE,
+            srcColorBlendFactor: VkBlendFactor::VK_BLEND_FACTOR_ONE,
+            dstColorBlendFactor: VkBlendFactor::VK_BLEND_FACTOR_ZERO,
+            colorBlendOp: VkBlendOp::VK_BLEND_OP_ADD,
+            srcAlphaBlendFactor: VkBlendFactor::VK_BLEND_FACTOR_ONE,
+            dstAlphaBlendFactor: VkBlendFactor::VK_BLEND_FACTOR_ZERO,
+            alphaBlendOp: VkBlendOp::VK_BLEND_OP_ADD,
+            colorWriteMask: 0xf,
+        };
+        let color_blend = VkPipelineColorBlendStateCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            logicOpEnable: VK_FALSE,
+            logicOp: VkLogicOp::VK_LOGIC_OP_COPY,
+            attachmentCount: 1,
+            pAttachments: &blend_attachment,
+            blendConstants: [0.0; 4],
+        };
+        let pipeline_info = VkGraphicsPipelineCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_GRAPHICS_PIPELINE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            stageCount: 2,
+            pStages: stages.as_ptr(),
+            pVertexInputState: &vertex_input,
+            pInputAssemblyState: &input_assembly,
+            pTessellationState: ptr::null(),
+            pViewportState: &viewport_state,
+            pRasterizationState: &rasterization,
+            pMultisampleState: &multisample,
+            pDepthStencilState: ptr::null(),
+            pColorBlendState: &color_blend,
+            pDynamicState: ptr::null(),
+            layout: layout,
+            renderPass: render_pass,
+            subpass: 0,
+            basePipelineHandle: mem::zeroed(),
+            basePipelineIndex: -1,
+        };
+        let create_graphics_pipelines: PFN_vkCreateGraphicsPipelines = context.proc_addr("vkCreateGraphicsPipelines");
+        let mut pipeline = mem::zeroed();
+        assert_eq!(create_graphics_pipelines.unwrap()(context.device, mem::zeroed(), 1, &pipeline_info, ptr::null(), &mut pipeline), VkResult::VK_SUCCESS);
+
+        let occlusion = context.create_query_pool(VkQueryType::VK_QUERY_TYPE_OCCLUSION, 0);
+        let statistics = context.create_query_pool(
+            VkQueryType::VK_QUERY_TYPE_PIPELINE_STATISTICS,
+            VkQueryPipelineStatisticFlagBits::VK_QUERY_PIPELINE_STATISTIC_INPUT_ASSEMBLY_VERTICES_BIT as u32 |
+                VkQueryPipelineStatisticFlagBits::VK_QUERY_PIPELINE_STATISTIC_VERTEX_SHADER_INVOCATIONS_BIT as u32,
+        );
+
+        // Opaque green, which the draw replaces with red where it covers the image.
+        let mut clear_value: VkClearValue = mem::zeroed();
+        *(&mut clear_value as *mut VkClearValue as *mut [f32; 4]) = [0.0, 1.0, 0.0, 1.0];
+        let begin_info = VkRenderPassBeginInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_RENDER_PASS_BEGIN_INFO,
+            pNext: ptr::null(),
+            renderPass: render_pass,
+            framebuffer: framebuffer,
+            renderArea: scissor,
+            clearValueCount: 1,
+            pClearValues: &clear_value,
+        };
+
+        let reset_query_pool: PFN_vkCmdResetQueryPool = context.proc_addr("vkCmdResetQueryPool");
+        let begin_query: PFN_vkCmdBeginQuery = context.proc_addr("vkCmdBeginQuery");
+        let end_query: PFN_vkCmdEndQuery = context.proc_addr("vkCmdEndQuery");
+        let begin_render_pass: PFN_vkCmdBeginRenderPass = context.proc_addr("vkCmdBeginRenderPass");
+        let end_render_pass: PFN_vkCmdEndRenderPass = context.proc_addr("vkCmdEndRenderPass");
+        let bind_pipeline: PFN_vkCmdBindPipeline = context.proc_addr("vkCmdBindPipeline");
+        let bind_vertex_buffers: PFN_vkCmdBindVertexBuffers = context.proc_addr("vkCmdBindVertexBuffers");
+        let draw: PFN_vkCmdDraw = context.proc_addr("vkCmdDraw");
+        let copy_image_to_buffer: PFN_vkCmdCopyImageToBuffer = context.proc_addr("vkCmdCopyImageToBuffer");
+        context.execute(&|x| {
+            reset_query_pool.unwrap()(x, occlusion, 0, 1);
+            reset_query_pool.unwrap()(x, statistics, 0, 1);
+            begin_render_pass.unwrap()(x, &begin_info, VkSubpassContents::VK_SUBPASS_CONTENTS_INLINE);
+            bind_pipeline.unwrap()(x, VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_GRAPHICS, pipeline);
+            bind_vertex_buffers.unwrap()(x, 0, 1, &vertices, &0);
+            begin_query.unwrap()(x, occlusion, 0, 0);
+            begin_query.unwrap()(x, statistics, 0, 0);
+            draw.unwrap()(x, 3, 1, 0, 0);
+            end_query.unwrap()(x, statistics, 0);
+            end_query.unwrap()(x, occlusion, 0);
+            end_render_pass.unwrap()(x);
+            context.barrier(
+                x,
+                (VkPipelineStageFlagBits::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT, VkAccessFlagBits::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT),
+                (VkPipelineStageFlagBits::VK_PIPELINE_STAGE_TRANSFER_BIT, VkAccessFlagBits::VK_ACCESS_TRANSFER_READ_BIT),
+            );
+            copy_image_to_buffer.unwrap()(x, image, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, readback, 1, &whole_image(0));
+        });
+
+        // The triangle covers the centers of the left two pixels of each row.
+        for (i, pixel) in readback_data.chunks(4).enumerate() {
+            let expected: &[u8] = if i % 4 < 2 { &[255, 0, 0, 255] } else { &[0, 255, 0, 255] };
+            assert_eq!(pixel, expected, "pixel ({}, {})", i % 4, i / 4);
+        }
+        assert_eq!(context.query_results(occlusion, 1), [8]);
+        assert_eq!(context.query_results(statistics, 2), [3, 3]);
+
+        let destroy_query_pool: PFN_vkDestroyQueryPool = context.proc_addr("vkDestroyQueryPool");
+        let destroy_pipeline: PFN_vkDestroyPipeline = context.proc_addr("vkDestroyPipeline");
+        let destroy_pipeline_layout: PFN_vkDestroyPipelineLayout = context.proc_addr("vkDestroyPipelineLayout");
+        let destroy_shader_module: PFN_vkDestroyShaderModule = context.proc_addr("vkDestroyShaderModule");
+        let destroy_framebuffer: PFN_vkDestroyFramebuffer = context.proc_addr("vkDestroyFramebuffer");
+        let destroy_render_pass: PFN_vkDestroyRenderPass = context.proc_addr("vkDestroyRenderPass");
+        let destroy_image_view: PFN_vkDestroyImageView = context.proc_addr("vkDestroyImageView");
+        let destroy_image: PFN_vkDestroyImage = context.proc_addr("vkDestroyImage");
+        let destroy_buffer: PFN_vkDestroyBuffer = context.proc_addr("vkDestroyBuffer");
+        let free_memory: PFN_vkFreeMemory = context.proc_addr("vkFreeMemory");
+        destroy_query_pool.unwrap()(context.device, occlusion, ptr::null());
+        destroy_query_pool.unwrap()(context.device, statistics, ptr::null());
+        destroy_pipeline.unwrap()(context.device, pipeline, ptr::null());
+        destroy_pipeline_layout.unwrap()(context.device, layout, ptr::null());
+        destroy_shader_module.unwrap()(context.device, vertex_module, ptr::null());
+        destroy_shader_module.unwrap()(context.device, fragment_module, ptr::null());
+        destroy_framebuffer.unwrap()(context.device, framebuffer, ptr::null());
+        destroy_render_pass.unwrap()(context.device, render_pass, ptr::null());
+        destroy_image_view.unwrap()(context.device, view, ptr::null());
+        destroy_image.unwrap()(context.device, image, ptr::null());
+        free_memory.unwrap()(context.device, image_memory, ptr::null());
+        for &(buffer, memory) in &[(readback, readback_memory), (vertices, vertex_memory)] {
+            destroy_buffer.unwrap()(context.device, buffer, ptr::null());
+            free_memory.unwrap()(context.device, memory, ptr::null());
+        }
+    }
+}
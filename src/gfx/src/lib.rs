@@ -5,9 +5,6 @@ extern crate gfx_corell;
 #[cfg(feature = "metal")]
 extern crate gfx_device_metalll as backend;
 
-use std::borrow::{Borrow, BorrowMut};
-use std::mem;
-
 use vk_middle::icd;
 use vk_middle::icd::*;
 
@@ -24,15 +21,46 @@ impl icd::Impl for I {
     type PhysicalDevice = PhysicalDevice;
     type Device = Device;
     type Queue = Queue;
+    type CommandPool = CommandPool;
     type CommandBuffer = CommandBuffer;
 
     type DeviceMemory = DeviceMemory;
     type Buffer = Buffer;
+    type BufferView = BufferView;
     type Image = Image;
+    type ImageView = ImageView;
 
     type Fence = Fence;
+    type Semaphore = Semaphore;
+    type Event = Event;
+    type QueryPool = QueryPool;
     
+    type RenderPass = RenderPass;
+    type Framebuffer = Framebuffer;
+
+    type Sampler = Sampler;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorPool = DescriptorPool;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
+
+    type ShaderModule = ShaderModule;
+    type Pipeline = Pipeline;
     type PipelineCache = PipelineCache;
+
+    fn supports(command: Command) -> bool {
+        match command {
+            Command::EnumerateInstanceExtensionProperties |
+            Command::CreateInstance |
+            Command::DestroyInstance |
+            Command::EnumeratePhysicalDevices |
+            Command::EnumerateDeviceExtensionProperties |
+            Command::CreateDevice |
+            Command::DestroyDevice |
+            Command::GetDeviceQueue => true,
+            _ => false,
+        }
+    }
 }
 
 struct Instance {
@@ -72,14 +100,6 @@ unsafe impl Sync for PhysicalDevice {
 }
 
 impl icd::PhysicalDevice<I> for PhysicalDevice {
-    fn get_features(&self) -> PhysicalDeviceFeatures {
-        unimplemented!()
-    }
-
-    fn get_format_properties(&self, format: Format) -> FormatProperties {
-        unimplemented!()
-    }
-
     fn create_device(&self, create_info: DeviceCreateInfo<I>) -> Result<Device> {
         let queues = create_info.queues().map(|queue_info| {
             let family = self.inner.get_queue_families().nth(queue_info.family_index() as usize).expect("invalid queue index");
@@ -102,18 +122,11 @@ unsafe impl Sync for Device {
 }
 
 impl icd::Device<I> for Device {
-    fn wait_idle(&self) -> Result<()> {
-        unimplemented!()
-    }
-
-    fn allocate_memory(&self, info: MemoryAllocateInfo<I>) -> Result<DeviceMemory> {
-        unimplemented!()
-    }
-    fn flush_mapped_memory_ranges(&self, ranges: MappedMemoryRanges<I>) -> Result<()> {
-        unimplemented!()
-    }
-    fn invalidate_mapped_memory_ranges(&self, ranges: MappedMemoryRanges<I>) -> Result<()> {
-        unimplemented!()
+    /// vk-middle asks for every queue when the device is created, so this must succeed for
+    /// `vkCreateDevice` to. The queues were opened along with the device, and submission is not
+    /// implemented yet, so there is nothing for the handle to hold.
+    fn get_queue(&self, _family_index: u32, _queue_index: u32) -> Result<Queue> {
+        Ok(Queue {})
     }
 }
 
@@ -121,16 +134,12 @@ struct Queue {
 }
 
 impl icd::Queue<I> for Queue {
-    fn submit(&mut self, submits: SubmitInfos<I>, fence: Option<&mut Fence>) {
-        unimplemented!()
-    }
-    fn wait_idle(&self) -> Result<()> {
-        unimplemented!()
-    }
+}
 
-    fn bind_sparse(&mut self, infos: BindSparseInfos<I>, fence: Option<&mut Fence>) {
-        unimplemented!()
-    }
+struct CommandPool {
+}
+
+impl icd::CommandPool<I> for CommandPool {
 }
 
 struct CommandBuffer {
@@ -143,101 +152,118 @@ struct DeviceMemory {
 }
 
 impl icd::DeviceMemory<I> for DeviceMemory {
-    fn map(&mut self, device: &Device, offset: u64, size: u64, flags: MemoryMapFlags) -> Result<*mut u8> {
-        unimplemented!()
-    }
-    fn unmap(&mut self, device: &Device) {
-        unimplemented!()
-    }
-
-    fn get_memory_commitment(&self, device: &Device) {
-        unimplemented!()
-    }
-
-    fn destroy(self, _device: &Device) { mem::drop(self) }
 }
 
 struct Buffer {
 }
 
 impl icd::Buffer<I> for Buffer {
-    fn new(device: &Device, info: BufferCreateInfo<I>) -> Result<Self> {
-        unimplemented!()
-    }
-    fn destroy(self, _device: &Device) { mem::drop(self) }
+}
 
-    fn get_memory_requirements(&self, device: &Device) -> MemoryRequirements {
-        unimplemented!()
-    }
-    fn bind_memory(&mut self, device: &Device, memory: &DeviceMemory, offset: u64) -> Result<()> {
-        unimplemented!()
-    }
+struct BufferView {
+}
+
+impl icd::BufferView<I> for BufferView {
 }
 
 struct Image {
 }
 
 impl icd::Image<I> for Image {
-    fn new(device: &Device, info: ImageCreateInfo<I>) -> Result<Self> {
-        unimplemented!()
-    }
-    fn destroy(self, _device: &Device) { mem::drop(self) }
+}
 
-    fn get_memory_requirements(&self, device: &Device) -> MemoryRequirements {
-        unimplemented!()
-    }
-    fn get_sparse_memory_requirements(&self, device: &Device, capacity: u32) -> Vec<SparseImageMemoryRequirements> {
-        unimplemented!()
-    }
-    fn bind_memory(&mut self, device: &Device, memory: &DeviceMemory, offset: u64) -> Result<()> {
-        unimplemented!()
-    }
+struct ImageView {
+}
 
-    fn get_subresource_layout(&self, subresource: ImageSubresource<I>) -> Result<SubresourceLayout> {
-        unimplemented!()
-    }
+impl icd::ImageView<I> for ImageView {
 }
 
 struct Fence {
 }
 
 impl icd::Fence<I> for Fence {
-    fn new(device: &Device, info: FenceCreateInfo<I>) -> Result<Self> {
-        unimplemented!()
-    }
-    fn destroy(self, _device: &Device) { mem::drop(self) }
+}
 
-    fn reset<F>(device: &Device, fences: &mut [F]) where
-        F: BorrowMut<Self> {
-        unimplemented!()
-    }
-    fn wait<F>(device: &Device, fences: &[F]) where
-        F: Borrow<Self> {
-        unimplemented!()
-    }
-    fn get_status(&self, device: &Device) -> Result<()> {
-        unimplemented!()
-    }
+struct Semaphore {
 }
 
-struct PipelineCache {
+impl icd::Semaphore<I> for Semaphore {
 }
 
-impl icd::PipelineCache<I> for PipelineCache {
-    fn new(device: &Device, info: PipelineCacheCreateInfo<I>) -> Result<Self> {
-        unimplemented!()
-    }
-    fn destroy(self, _device: &Device) { mem::drop(self) }
+struct Event {
+}
 
-    fn get_data_size(&self, device: &Device) -> Result<usize> {
-        unimplemented!()
-    }
-    fn get_data(&self, device: &Device, data: &mut [u8]) -> Result<usize> {
-        unimplemented!()
-    }
+impl icd::Event<I> for Event {
+}
+
+struct QueryPool {
+}
+
+impl icd::QueryPool<I> for QueryPool {
+}
+
+struct RenderPass {
+}
+
+impl icd::RenderPass<I> for RenderPass {
+}
+
+struct Framebuffer {
+}
+
+impl icd::Framebuffer<I> for Framebuffer {
+}
+
+struct Sampler {
+}
+
+impl icd::Sampler<I> for Sampler {
+}
+
+struct DescriptorSetLayout {
+}
+
+impl icd::DescriptorSetLayout<I> for DescriptorSetLayout {
+}
+
+struct DescriptorPool {
+}
 
-    fn merge<C>(&self, device: &Device, sources: &[C]) -> Result<()> where
-        C: Borrow<Self> {
-        unimplemented!()
+impl icd::DescriptorPool<I> for DescriptorPool {
+}
+
+struct DescriptorSet {
+}
+
+impl icd::DescriptorSet<I> for DescriptorSet {
+}
+
+struct PipelineLayout {
+}
+
+impl icd::PipelineLayout<I> for PipelineLayout {
+}
+
+/// Holds nothing until pipelines can be created, since `vk_middle` has already validated the
+/// code and Metal cannot take SPIR-V anyway. Shader module commands are not advertised until then.
+struct ShaderModule {
+}
+
+impl icd::ShaderModule<I> for ShaderModule {
+    fn new(_device: &Device, _info: ShaderModuleCreateInfo<I>) -> Result<Self> {
+        Ok(ShaderModule {
+        })
     }
 }
+
+struct Pipeline {
+}
+
+impl icd::Pipeline<I> for Pipeline {
+}
+
+struct PipelineCache {
+}
+
+impl icd::PipelineCache<I> for PipelineCache {
+}
@@ -0,0 +1,55 @@
+#![feature(test)]
+
+extern crate test;
+extern crate vk_middle;
+
+use std::ffi::{CStr, CString};
+
+use test::Bencher;
+use vk_middle::icd::Command;
+
+/// Names loaders and layers ask for that are not in the table.
+const MISSES: &'static [&'static str] = &[
+    "vkCmdDrawIndexedIndirectCountAMD",
+    "vkCreateSwapchainKHR",
+    "vkGetPhysicalDeviceSurfaceSupportKHR",
+    "vkCreateDebugReportCallbackEXT",
+    "vkCreateInstanceKHX",
+    "vkTrimCommandPool",
+    "vkZ",
+    "",
+];
+
+/// Every name in the table, followed by the misses.
+fn names() -> Vec<CString> {
+    Command::all().iter().map(|command| command.name())
+        .chain(MISSES.iter().cloned())
+        .map(|name| CString::new(name).unwrap())
+        .collect()
+}
+
+/// A scan of every name, standing in for the string match lookups used to be.
+fn linear_lookup(name: &CStr) -> Option<Command> {
+    let name = name.to_bytes();
+    Command::all().iter().cloned().find(|command| command.name().as_bytes() == name)
+}
+
+#[bench]
+fn sorted_table(b: &mut Bencher) {
+    let names = names();
+    b.iter(|| {
+        for name in &names {
+            test::black_box(Command::from_name(name));
+        }
+    });
+}
+
+#[bench]
+fn linear_scan(b: &mut Bencher) {
+    let names = names();
+    b.iter(|| {
+        for name in &names {
+            test::black_box(linear_lookup(name));
+        }
+    });
+}
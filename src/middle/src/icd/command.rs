@@ -0,0 +1,1473 @@
+//! Trampolines for command pools, command buffers and the `vkCmd*` recording commands.
+
+use super::*;
+use super::handle::NonDispatchableHandle;
+
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+/// The states the spec defines for a command buffer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum CommandBufferState {
+    Initial,
+    Recording,
+    Executable,
+    Pending,
+    Invalid,
+}
+
+/// What vk-middle tracks about a command buffer to enforce its lifecycle. It lives behind a mutex
+/// because fence waits on other threads move command buffers out of the pending state.
+pub(crate) struct Lifecycle {
+    pub state: CommandBufferState,
+    pub usage: CommandBufferUsageFlags,
+    /// How many submissions of the command buffer have not yet been seen to complete.
+    pub pending: u32,
+    /// Raw handles of the objects the recorded commands use, including secondary command buffers.
+    pub references: HashSet<u64>,
+    pub secondaries: Vec<sys::VkCommandBuffer>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Lifecycle {
+            state: CommandBufferState::Initial,
+            usage: CommandBufferUsageFlags::empty(),
+            pending: 0,
+            references: HashSet::new(),
+            secondaries: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self, state: CommandBufferState) {
+        self.state = state;
+        self.references.clear();
+        self.secondaries.clear();
+    }
+}
+
+// Dispatchable handles are plain pointers, so they share the raw handle space with the rest.
+fn command_buffer_raw(command_buffer: sys::VkCommandBuffer) -> u64 {
+    command_buffer as usize as u64
+}
+
+unsafe fn lifecycle<'a, I: Impl>(command_buffer: sys::VkCommandBuffer) -> MutexGuard<'a, Lifecycle> {
+    vulkan_dispatch_cast!(command_buffer => raw CommandBuffer).lifecycle.lock().unwrap()
+}
+
+/// Moves every command buffer of `device` that uses the object with the given raw handle to the
+/// invalid state.
+pub(crate) unsafe fn invalidate_references<I: Impl>(device: sys::VkDevice, object: u64) {
+    if object == 0 {
+        return;
+    }
+
+    let device = vulkan_dispatch_cast!(device => raw Device);
+    for &command_buffer in device.command_buffers.lock().unwrap().iter() {
+        let mut lifecycle = lifecycle::<I>(command_buffer);
+        if !lifecycle.references.contains(&object) {
+            continue;
+        }
+        match lifecycle.state {
+            CommandBufferState::Pending => {
+                error!("object {:#x} used by a pending command buffer was destroyed or modified", object);
+            },
+            CommandBufferState::Recording | CommandBufferState::Executable => {},
+            CommandBufferState::Initial | CommandBufferState::Invalid => continue,
+        }
+        lifecycle.state = CommandBufferState::Invalid;
+    }
+}
+
+/// Checks that every command buffer of a submission may be submitted and marks them and the
+/// secondaries they execute pending. Returns the command buffers to retire once the submission
+/// completes, or `None` if the submission must be refused.
+pub(crate) unsafe fn begin_submission<I: Impl>(command_buffers: &[sys::VkCommandBuffer]) -> Option<Vec<sys::VkCommandBuffer>> {
+    for (i, &command_buffer) in command_buffers.iter().enumerate() {
+        if vulkan_dispatch_cast!(command_buffer => raw CommandBuffer).level != CommandBufferLevel::Primary {
+            error!("vkQueueSubmit called with a secondary command buffer");
+            return None;
+        }
+        let lifecycle = lifecycle::<I>(command_buffer);
+        let simultaneous = lifecycle.usage.contains(VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT);
+        if !simultaneous && command_buffers[..i].contains(&command_buffer) {
+            error!("vkQueueSubmit called with the same command buffer more than once without VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT");
+            return None;
+        }
+        match lifecycle.state {
+            CommandBufferState::Executable => {},
+            CommandBufferState::Pending if simultaneous => {},
+            CommandBufferState::Pending => {
+                error!("vkQueueSubmit called with a pending command buffer not recorded with VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT");
+                return None;
+            },
+            state => {
+                error!("vkQueueSubmit called with a command buffer in the {:?} state", state);
+                return None;
+            },
+        }
+    }
+
+    let mut submitted = Vec::new();
+    for &command_buffer in command_buffers {
+        let secondaries = {
+            let mut lifecycle = lifecycle::<I>(command_buffer);
+            lifecycle.state = CommandBufferState::Pending;
+            lifecycle.pending += 1;
+            lifecycle.secondaries.clone()
+        };
+        submitted.push(command_buffer);
+        for secondary in secondaries {
+            let mut lifecycle = lifecycle::<I>(secondary);
+            lifecycle.state = CommandBufferState::Pending;
+            lifecycle.pending += 1;
+            submitted.push(secondary);
+        }
+    }
+
+    Some(submitted)
+}
+
+/// Retires a completed submission of the given command buffers. Command buffers that have since
+/// been freed are skipped.
+pub(crate) unsafe fn end_submission<I: Impl>(device: sys::VkDevice, command_buffers: &[sys::VkCommandBuffer]) {
+    let device = vulkan_dispatch_cast!(device => raw Device);
+    let live = device.command_buffers.lock().unwrap();
+    for command_buffer in command_buffers {
+        if !live.contains(command_buffer) {
+            continue;
+        }
+        let mut lifecycle = lifecycle::<I>(*command_buffer);
+        lifecycle.pending = lifecycle.pending.saturating_sub(1);
+        if lifecycle.pending == 0 && lifecycle.state == CommandBufferState::Pending {
+            lifecycle.state = if lifecycle.usage.contains(VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT) {
+                CommandBufferState::Invalid
+            } else {
+                CommandBufferState::Executable
+            };
+        }
+    }
+}
+
+/// Undoes `begin_submission` for a submission the backend refused, so its command buffers are
+/// left as they were.
+pub(crate) unsafe fn cancel_submission<I: Impl>(command_buffers: &[sys::VkCommandBuffer]) {
+    for &command_buffer in command_buffers {
+        let mut lifecycle = lifecycle::<I>(command_buffer);
+        lifecycle.pending = lifecycle.pending.saturating_sub(1);
+        if lifecycle.pending == 0 && lifecycle.state == CommandBufferState::Pending {
+            lifecycle.state = CommandBufferState::Executable;
+        }
+    }
+}
+
+/// Gets the backend command buffer for a `vkCmd*` command, provided the command buffer is
+/// recording, and notes the objects the command uses.
+unsafe fn recording<'a, I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    command: &str,
+    references: &[u64],
+) -> Option<&'a mut I::CommandBuffer> {
+    let command_buffer = vulkan_dispatch_cast!(command_buffer => raw mut CommandBuffer);
+    {
+        let mut lifecycle = command_buffer.lifecycle.lock().unwrap();
+        if lifecycle.state != CommandBufferState::Recording {
+            error!("{} called on a command buffer in the {:?} state", command, lifecycle.state);
+            return None;
+        }
+        lifecycle.references.extend(references.iter().cloned());
+    }
+    Some(&mut command_buffer.imp)
+}
+
+macro_rules! vulkan_recording {
+    ($x:expr => $command:ident, $references:expr) => {
+        match recording::<I>($x, stringify!($command), $references) {
+            Some(command_buffer) => command_buffer,
+            None => return,
+        }
+    };
+    ($x:expr => $command:ident) => {
+        vulkan_recording!($x => $command, &[])
+    };
+}
+
+/// What a command pool handle refers to. vk-middle keeps track of the command buffers allocated
+/// from each pool so it can reset and release them along with the pool.
+pub(crate) struct CommandPoolObject<I: Impl> {
+    pub imp: I::CommandPool,
+    pub flags: CommandPoolCreateFlags,
+    pub command_buffers: HashSet<sys::VkCommandBuffer>,
+}
+
+impl<I: Impl> CommandPoolObject<I> {
+    /// Takes back every command buffer allocated from the pool.
+    unsafe fn take_command_buffers(&mut self) -> Vec<I::CommandBuffer> {
+        self.command_buffers.drain()
+            .map(|command_buffer| Box::from_raw(command_buffer as *mut dispatch::CommandBuffer<I>).imp)
+            .collect()
+    }
+}
+
+unsafe fn query_pool<'a, I: Impl>(pool: sys::VkQueryPool) -> &'a I::QueryPool {
+    &handle::cast::<query::QueryPoolObject<I>, _>(pool).imp
+}
+
+/// Drops command buffers that are about to be freed from the device's registry, and invalidates
+/// the primaries that execute them.
+unsafe fn forget_command_buffers<I: Impl>(device: sys::VkDevice, command_buffers: &[sys::VkCommandBuffer]) {
+    {
+        let mut live = vulkan_dispatch_cast!(device => raw Device).command_buffers.lock().unwrap();
+        for command_buffer in command_buffers {
+            live.remove(command_buffer);
+        }
+    }
+    for &command_buffer in command_buffers {
+        invalidate_references::<I>(device, command_buffer_raw(command_buffer));
+    }
+}
+
+pub unsafe extern "C" fn vkCreateCommandPool<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkCommandPoolCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    command_pool: *mut sys::VkCommandPool,
+) -> sys::VkResult {
+    let info = CommandPoolCreateInfo(&*create_info, PhantomData);
+    let flags = info.flags();
+
+    let icd_pool = vulkan_try!(I::CommandPool::new(
+        vulkan_dispatch_cast!(device => Device),
+        info,
+    ));
+
+    // TODO: alloc
+    mem::drop(alloc);
+
+    *command_pool = handle::form(CommandPoolObject::<I> {
+        imp: icd_pool,
+        flags: flags,
+        command_buffers: HashSet::new(),
+    });
+
+    OK
+}
+
+pub unsafe extern "C" fn vkDestroyCommandPool<I: Impl>(
+    device: sys::VkDevice,
+    command_pool: sys::VkCommandPool,
+    alloc: *const sys::VkAllocationCallbacks,
+) {
+    // TODO: alloc
+    mem::drop(alloc);
+
+    if let Some(mut pool) = handle::take::<CommandPoolObject<I>, _>(command_pool) {
+        let raw_command_buffers: Vec<sys::VkCommandBuffer> = pool.command_buffers.iter().cloned().collect();
+        if raw_command_buffers.iter().any(|&x| lifecycle::<I>(x).state == CommandBufferState::Pending) {
+            error!("vkDestroyCommandPool called while command buffers allocated from the pool are pending");
+        }
+        forget_command_buffers::<I>(device, &raw_command_buffers);
+
+        let device = vulkan_dispatch_cast!(device => Device);
+        let command_buffers = pool.take_command_buffers();
+        if !command_buffers.is_empty() {
+            pool.imp.free(device, command_buffers);
+        }
+        pool.imp.destroy(device);
+    }
+}
+
+pub unsafe extern "C" fn vkResetCommandPool<I: Impl>(
+    device: sys::VkDevice,
+    command_pool: sys::VkCommandPool,
+    flags: sys::VkCommandPoolResetFlags,
+) -> sys::VkResult {
+    let pool = handle::cast_mut::<CommandPoolObject<I>, _>(command_pool);
+
+    if pool.command_buffers.iter().any(|&x| lifecycle::<I>(x).state == CommandBufferState::Pending) {
+        error!("vkResetCommandPool called while command buffers allocated from the pool are pending");
+        return INVALID_USAGE;
+    }
+
+    {
+        let mut command_buffers: Vec<(&mut I::CommandBuffer, CommandBufferLevel)> = pool.command_buffers.iter()
+            .map(|&command_buffer| {
+                let level = vulkan_dispatch_cast!(command_buffer => raw CommandBuffer).level;
+                (vulkan_dispatch_cast!(command_buffer => mut CommandBuffer), level)
+            })
+            .collect();
+        vulkan_try!(pool.imp.reset(
+            vulkan_dispatch_cast!(device => Device),
+            &mut command_buffers,
+            CommandPoolResetFlags::from_bits_truncate(flags),
+        ));
+    }
+
+    for &command_buffer in &pool.command_buffers {
+        lifecycle::<I>(command_buffer).reset(CommandBufferState::Initial);
+        invalidate_references::<I>(device, command_buffer_raw(command_buffer));
+    }
+
+    OK
+}
+
+pub unsafe extern "C" fn vkTrimCommandPoolKHR<I: Impl>(
+    device: sys::VkDevice,
+    command_pool: sys::VkCommandPool,
+    _flags: sys::VkCommandPoolTrimFlagsKHR,
+) {
+    handle::cast_mut::<CommandPoolObject<I>, _>(command_pool).imp
+        .trim(vulkan_dispatch_cast!(device => Device));
+}
+
+pub unsafe extern "C" fn vkAllocateCommandBuffers<I: Impl>(
+    device: sys::VkDevice,
+    allocate_info: *const sys::VkCommandBufferAllocateInfo,
+    command_buffers: *mut sys::VkCommandBuffer,
+) -> sys::VkResult {
+    let allocate_info = &*allocate_info;
+    let count = allocate_info.commandBufferCount;
+    let level: CommandBufferLevel = allocate_info.level.into();
+    let pool = handle::cast_mut::<CommandPoolObject<I>, _>(allocate_info.commandPool);
+
+    let result = pool.imp.allocate(vulkan_dispatch_cast!(device => Device), level, count);
+    let icd_command_buffers = match result {
+        Ok(ref icd_command_buffers) if icd_command_buffers.len() != count as usize => {
+            error!("backend allocated {} command buffers instead of {}", icd_command_buffers.len(), count);
+            Err(Error::InitializationFailed)
+        },
+        other => other,
+    };
+
+    match icd_command_buffers {
+        Ok(icd_command_buffers) => {
+            let mut live = vulkan_dispatch_cast!(device => raw Device).command_buffers.lock().unwrap();
+            for (i, icd_command_buffer) in icd_command_buffers.into_iter().enumerate() {
+                let command_buffer: sys::VkCommandBuffer = vulkan_dispatch_form!(icd_command_buffer => CommandBuffer {
+                    device: device,
+                    pool_flags: pool.flags,
+                    level: level,
+                    lifecycle: Mutex::new(Lifecycle::new()),
+                });
+                pool.command_buffers.insert(command_buffer);
+                live.insert(command_buffer);
+                *command_buffers.offset(i as isize) = command_buffer;
+            }
+            OK
+        },
+        Err(err) => {
+            for i in 0..count {
+                *command_buffers.offset(i as isize) = ptr::null_mut();
+            }
+            err.into()
+        },
+    }
+}
+
+pub unsafe extern "C" fn vkFreeCommandBuffers<I: Impl>(
+    device: sys::VkDevice,
+    command_pool: sys::VkCommandPool,
+    command_buffer_count: u32,
+    command_buffers: *const sys::VkCommandBuffer,
+) {
+    let pool = handle::cast_mut::<CommandPoolObject<I>, _>(command_pool);
+
+    let mut freed = Vec::new();
+    for &command_buffer in raw_slice(command_buffers, command_buffer_count) {
+        if command_buffer.is_null() {
+            continue;
+        }
+        if !pool.command_buffers.contains(&command_buffer) {
+            error!("vkFreeCommandBuffers called with a command buffer not allocated from the given pool");
+            continue;
+        }
+        if lifecycle::<I>(command_buffer).state == CommandBufferState::Pending {
+            error!("vkFreeCommandBuffers called with a pending command buffer");
+            continue;
+        }
+        pool.command_buffers.remove(&command_buffer);
+        freed.push(command_buffer);
+    }
+    forget_command_buffers::<I>(device, &freed);
+
+    let icd_command_buffers = freed.into_iter()
+        .map(|command_buffer| Box::from_raw(command_buffer as *mut dispatch::CommandBuffer<I>).imp)
+        .collect();
+    pool.imp.free(vulkan_dispatch_cast!(device => Device), icd_command_buffers);
+}
+
+pub unsafe extern "C" fn vkBeginCommandBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    begin_info: *const sys::VkCommandBufferBeginInfo,
+) -> sys::VkResult {
+    let raw_command_buffer = command_buffer;
+    let command_buffer = vulkan_dispatch_cast!(command_buffer => raw mut CommandBuffer);
+    let state = command_buffer.lifecycle.lock().unwrap().state;
+
+    match state {
+        CommandBufferState::Initial => {},
+        CommandBufferState::Recording | CommandBufferState::Pending => {
+            error!("vkBeginCommandBuffer called on a command buffer in the {:?} state", state);
+            return INVALID_USAGE;
+        },
+        CommandBufferState::Executable | CommandBufferState::Invalid => {
+            if !command_buffer.pool_flags.contains(VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT) {
+                error!("vkBeginCommandBuffer called on a recorded command buffer whose pool was created without VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT");
+                return INVALID_USAGE;
+            }
+            vulkan_try!(command_buffer.imp.reset(CommandBufferResetFlags::empty()));
+            command_buffer.lifecycle.lock().unwrap().reset(CommandBufferState::Initial);
+            invalidate_references::<I>(command_buffer.device, command_buffer_raw(raw_command_buffer));
+        },
+    }
+
+    // Inheritance info is ignored for primaries, so don't let backends see whatever was passed.
+    let mut begin_info = *begin_info;
+    if command_buffer.level == CommandBufferLevel::Primary {
+        begin_info.pInheritanceInfo = ptr::null();
+    }
+    let info = CommandBufferBeginInfo(&begin_info, PhantomData);
+    let usage = info.flags();
+
+    let mut inherited = Vec::new();
+    if command_buffer.level == CommandBufferLevel::Secondary {
+        match info.inheritance_info() {
+            Some(inheritance) => {
+                let inheritance = inheritance.0;
+                if usage.contains(VK_COMMAND_BUFFER_USAGE_RENDER_PASS_CONTINUE_BIT) {
+                    if inheritance.renderPass.is_null() {
+                        error!("vkBeginCommandBuffer called with VK_COMMAND_BUFFER_USAGE_RENDER_PASS_CONTINUE_BIT but no render pass to inherit");
+                        return INVALID_USAGE;
+                    }
+                    inherited.push(inheritance.renderPass.as_raw());
+                    if !inheritance.framebuffer.is_null() {
+                        inherited.push(inheritance.framebuffer.as_raw());
+                    }
+                }
+            },
+            None => {
+                error!("vkBeginCommandBuffer called on a secondary command buffer without inheritance info");
+                return INVALID_USAGE;
+            },
+        }
+    }
+
+    vulkan_try!(command_buffer.imp.begin(info));
+
+    let mut lifecycle = command_buffer.lifecycle.lock().unwrap();
+    lifecycle.reset(CommandBufferState::Recording);
+    lifecycle.usage = usage;
+    lifecycle.references.extend(inherited);
+
+    OK
+}
+
+pub unsafe extern "C" fn vkEndCommandBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+) -> sys::VkResult {
+    let command_buffer = vulkan_dispatch_cast!(command_buffer => raw mut CommandBuffer);
+    let state = command_buffer.lifecycle.lock().unwrap().state;
+
+    if state != CommandBufferState::Recording {
+        error!("vkEndCommandBuffer called on a command buffer in the {:?} state", state);
+        return INVALID_USAGE;
+    }
+
+    let result = command_buffer.imp.end();
+    command_buffer.lifecycle.lock().unwrap().state = match result {
+        Ok(()) => CommandBufferState::Executable,
+        Err(_) => CommandBufferState::Invalid,
+    };
+    vulkan_try!(result);
+
+    OK
+}
+
+pub unsafe extern "C" fn vkResetCommandBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    flags: sys::VkCommandBufferResetFlags,
+) -> sys::VkResult {
+    let raw_command_buffer = command_buffer;
+    let command_buffer = vulkan_dispatch_cast!(command_buffer => raw mut CommandBuffer);
+
+    if !command_buffer.pool_flags.contains(VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT) {
+        error!("vkResetCommandBuffer called on a command buffer whose pool was created without VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT");
+        return INVALID_USAGE;
+    }
+    if command_buffer.lifecycle.lock().unwrap().state == CommandBufferState::Pending {
+        error!("vkResetCommandBuffer called on a pending command buffer");
+        return INVALID_USAGE;
+    }
+
+    vulkan_try!(command_buffer.imp.reset(CommandBufferResetFlags::from_bits_truncate(flags)));
+    command_buffer.lifecycle.lock().unwrap().reset(CommandBufferState::Initial);
+    invalidate_references::<I>(command_buffer.device, command_buffer_raw(raw_command_buffer));
+
+    OK
+}
+
+pub unsafe extern "C" fn vkCmdBindPipeline<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    bind_point: sys::VkPipelineBindPoint,
+    pipeline: sys::VkPipeline,
+) {
+    vulkan_recording!(command_buffer => vkCmdBindPipeline, &[pipeline.as_raw()])
+        .bind_pipeline(bind_point.into(), vulkan_handle_cast!(pipeline => Pipeline));
+}
+
+pub unsafe extern "C" fn vkCmdBindDescriptorSets<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    bind_point: sys::VkPipelineBindPoint,
+    layout: sys::VkPipelineLayout,
+    first_set: u32,
+    descriptor_set_count: u32,
+    descriptor_sets: *const sys::VkDescriptorSet,
+    dynamic_offset_count: u32,
+    dynamic_offsets: *const u32,
+) {
+    let sets: Vec<&I::DescriptorSet> = raw_slice(descriptor_sets, descriptor_set_count).iter()
+        .map(|&set| vulkan_handle_cast!(set => DescriptorSet))
+        .collect();
+    let references: Vec<u64> = raw_slice(descriptor_sets, descriptor_set_count).iter()
+        .map(|set| set.as_raw())
+        .chain(Some(layout.as_raw()))
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdBindDescriptorSets, &references).bind_descriptor_sets(
+        bind_point.into(),
+        vulkan_handle_cast!(layout => PipelineLayout),
+        first_set,
+        &sets,
+        raw_slice(dynamic_offsets, dynamic_offset_count),
+    );
+}
+
+pub unsafe extern "C" fn vkCmdBindIndexBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    buffer: sys::VkBuffer,
+    offset: sys::VkDeviceSize,
+    index_type: sys::VkIndexType,
+) {
+    vulkan_recording!(command_buffer => vkCmdBindIndexBuffer, &[buffer.as_raw()])
+        .bind_index_buffer(vulkan_handle_cast!(buffer => Buffer), offset, index_type.into());
+}
+
+pub unsafe extern "C" fn vkCmdBindVertexBuffers<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    first_binding: u32,
+    binding_count: u32,
+    buffers: *const sys::VkBuffer,
+    offsets: *const sys::VkDeviceSize,
+) {
+    let icd_buffers: Vec<&I::Buffer> = raw_slice(buffers, binding_count).iter()
+        .map(|&buffer| vulkan_handle_cast!(buffer => Buffer))
+        .collect();
+    let references: Vec<u64> = raw_slice(buffers, binding_count).iter()
+        .map(|buffer| buffer.as_raw())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdBindVertexBuffers, &references)
+        .bind_vertex_buffers(first_binding, &icd_buffers, raw_slice(offsets, binding_count));
+}
+
+pub unsafe extern "C" fn vkCmdPushConstants<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    layout: sys::VkPipelineLayout,
+    stage_flags: sys::VkShaderStageFlags,
+    offset: u32,
+    size: u32,
+    values: *const c_void,
+) {
+    vulkan_recording!(command_buffer => vkCmdPushConstants, &[layout.as_raw()]).push_constants(
+        vulkan_handle_cast!(layout => PipelineLayout),
+        ShaderStageFlags::from_bits_truncate(stage_flags),
+        offset,
+        raw_slice(values as *const u8, size),
+    );
+}
+
+pub unsafe extern "C" fn vkCmdSetViewport<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    first_viewport: u32,
+    viewport_count: u32,
+    viewports: *const sys::VkViewport,
+) {
+    let viewports: Vec<Viewport> = raw_slice(viewports, viewport_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdSetViewport).set_viewport(first_viewport, &viewports);
+}
+
+pub unsafe extern "C" fn vkCmdSetScissor<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    first_scissor: u32,
+    scissor_count: u32,
+    scissors: *const sys::VkRect2D,
+) {
+    let scissors: Vec<Rect2D> = raw_slice(scissors, scissor_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdSetScissor).set_scissor(first_scissor, &scissors);
+}
+
+pub unsafe extern "C" fn vkCmdSetLineWidth<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    line_width: f32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetLineWidth).set_line_width(line_width);
+}
+
+pub unsafe extern "C" fn vkCmdSetDepthBias<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    constant_factor: f32,
+    clamp: f32,
+    slope_factor: f32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetDepthBias).set_depth_bias(constant_factor, clamp, slope_factor);
+}
+
+pub unsafe extern "C" fn vkCmdSetBlendConstants<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    blend_constants: *const f32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetBlendConstants)
+        .set_blend_constants(*(blend_constants as *const [f32; 4]));
+}
+
+pub unsafe extern "C" fn vkCmdSetDepthBounds<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    min_depth_bounds: f32,
+    max_depth_bounds: f32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetDepthBounds).set_depth_bounds(min_depth_bounds, max_depth_bounds);
+}
+
+pub unsafe extern "C" fn vkCmdSetStencilCompareMask<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    face_mask: sys::VkStencilFaceFlags,
+    compare_mask: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetStencilCompareMask)
+        .set_stencil_compare_mask(StencilFaceFlags::from_bits_truncate(face_mask), compare_mask);
+}
+
+pub unsafe extern "C" fn vkCmdSetStencilWriteMask<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    face_mask: sys::VkStencilFaceFlags,
+    write_mask: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetStencilWriteMask)
+        .set_stencil_write_mask(StencilFaceFlags::from_bits_truncate(face_mask), write_mask);
+}
+
+pub unsafe extern "C" fn vkCmdSetStencilReference<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    face_mask: sys::VkStencilFaceFlags,
+    reference: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetStencilReference)
+        .set_stencil_reference(StencilFaceFlags::from_bits_truncate(face_mask), reference);
+}
+
+pub unsafe extern "C" fn vkCmdDraw<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdDraw)
+        .draw(vertex_count, instance_count, first_vertex, first_instance);
+}
+
+pub unsafe extern "C" fn vkCmdDrawIndexed<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    vertex_offset: i32,
+    first_instance: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdDrawIndexed)
+        .draw_indexed(index_count, instance_count, first_index, vertex_offset, first_instance);
+}
+
+pub unsafe extern "C" fn vkCmdDrawIndirect<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    buffer: sys::VkBuffer,
+    offset: sys::VkDeviceSize,
+    draw_count: u32,
+    stride: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdDrawIndirect, &[buffer.as_raw()])
+        .draw_indirect(vulkan_handle_cast!(buffer => Buffer), offset, draw_count, stride);
+}
+
+pub unsafe extern "C" fn vkCmdDrawIndexedIndirect<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    buffer: sys::VkBuffer,
+    offset: sys::VkDeviceSize,
+    draw_count: u32,
+    stride: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdDrawIndexedIndirect, &[buffer.as_raw()])
+        .draw_indexed_indirect(vulkan_handle_cast!(buffer => Buffer), offset, draw_count, stride);
+}
+
+pub unsafe extern "C" fn vkCmdDispatch<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    group_count_x: u32,
+    group_count_y: u32,
+    group_count_z: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdDispatch)
+        .dispatch(group_count_x, group_count_y, group_count_z);
+}
+
+pub unsafe extern "C" fn vkCmdDispatchIndirect<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    buffer: sys::VkBuffer,
+    offset: sys::VkDeviceSize,
+) {
+    vulkan_recording!(command_buffer => vkCmdDispatchIndirect, &[buffer.as_raw()])
+        .dispatch_indirect(vulkan_handle_cast!(buffer => Buffer), offset);
+}
+
+pub unsafe extern "C" fn vkCmdCopyBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_buffer: sys::VkBuffer,
+    dst_buffer: sys::VkBuffer,
+    region_count: u32,
+    regions: *const sys::VkBufferCopy,
+) {
+    let regions: Vec<BufferCopy> = raw_slice(regions, region_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdCopyBuffer, &[src_buffer.as_raw(), dst_buffer.as_raw()]).copy_buffer(
+        vulkan_handle_cast!(src_buffer => Buffer),
+        vulkan_handle_cast!(dst_buffer => Buffer),
+        &regions,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdCopyImage<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_image: sys::VkImage,
+    src_image_layout: sys::VkImageLayout,
+    dst_image: sys::VkImage,
+    dst_image_layout: sys::VkImageLayout,
+    region_count: u32,
+    regions: *const sys::VkImageCopy,
+) {
+    let regions: Vec<ImageCopy> = raw_slice(regions, region_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdCopyImage, &[src_image.as_raw(), dst_image.as_raw()]).copy_image(
+        vulkan_handle_cast!(src_image => Image),
+        src_image_layout.into(),
+        vulkan_handle_cast!(dst_image => Image),
+        dst_image_layout.into(),
+        &regions,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdBlitImage<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_image: sys::VkImage,
+    src_image_layout: sys::VkImageLayout,
+    dst_image: sys::VkImage,
+    dst_image_layout: sys::VkImageLayout,
+    region_count: u32,
+    regions: *const sys::VkImageBlit,
+    filter: sys::VkFilter,
+) {
+    let regions: Vec<ImageBlit> = raw_slice(regions, region_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdBlitImage, &[src_image.as_raw(), dst_image.as_raw()]).blit_image(
+        vulkan_handle_cast!(src_image => Image),
+        src_image_layout.into(),
+        vulkan_handle_cast!(dst_image => Image),
+        dst_image_layout.into(),
+        &regions,
+        filter.into(),
+    );
+}
+
+pub unsafe extern "C" fn vkCmdCopyBufferToImage<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_buffer: sys::VkBuffer,
+    dst_image: sys::VkImage,
+    dst_image_layout: sys::VkImageLayout,
+    region_count: u32,
+    regions: *const sys::VkBufferImageCopy,
+) {
+    let regions: Vec<BufferImageCopy> = raw_slice(regions, region_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdCopyBufferToImage, &[src_buffer.as_raw(), dst_image.as_raw()]).copy_buffer_to_image(
+        vulkan_handle_cast!(src_buffer => Buffer),
+        vulkan_handle_cast!(dst_image => Image),
+        dst_image_layout.into(),
+        &regions,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdCopyImageToBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_image: sys::VkImage,
+    src_image_layout: sys::VkImageLayout,
+    dst_buffer: sys::VkBuffer,
+    region_count: u32,
+    regions: *const sys::VkBufferImageCopy,
+) {
+    let regions: Vec<BufferImageCopy> = raw_slice(regions, region_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdCopyImageToBuffer, &[src_image.as_raw(), dst_buffer.as_raw()]).copy_image_to_buffer(
+        vulkan_handle_cast!(src_image => Image),
+        src_image_layout.into(),
+        vulkan_handle_cast!(dst_buffer => Buffer),
+        &regions,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdUpdateBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    dst_buffer: sys::VkBuffer,
+    dst_offset: sys::VkDeviceSize,
+    data_size: sys::VkDeviceSize,
+    data: *const c_void,
+) {
+    vulkan_recording!(command_buffer => vkCmdUpdateBuffer, &[dst_buffer.as_raw()]).update_buffer(
+        vulkan_handle_cast!(dst_buffer => Buffer),
+        dst_offset,
+        raw_slice(data as *const u8, data_size as u32),
+    );
+}
+
+pub unsafe extern "C" fn vkCmdFillBuffer<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    dst_buffer: sys::VkBuffer,
+    dst_offset: sys::VkDeviceSize,
+    size: sys::VkDeviceSize,
+    data: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdFillBuffer, &[dst_buffer.as_raw()])
+        .fill_buffer(vulkan_handle_cast!(dst_buffer => Buffer), dst_offset, size, data);
+}
+
+pub unsafe extern "C" fn vkCmdClearColorImage<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    image: sys::VkImage,
+    image_layout: sys::VkImageLayout,
+    color: *const sys::VkClearColorValue,
+    range_count: u32,
+    ranges: *const sys::VkImageSubresourceRange,
+) {
+    let ranges: Vec<ImageSubresourceRange> = raw_slice(ranges, range_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdClearColorImage, &[image.as_raw()]).clear_color_image(
+        vulkan_handle_cast!(image => Image),
+        image_layout.into(),
+        ClearColorValue::from_raw(&*color),
+        &ranges,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdClearDepthStencilImage<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    image: sys::VkImage,
+    image_layout: sys::VkImageLayout,
+    depth_stencil: *const sys::VkClearDepthStencilValue,
+    range_count: u32,
+    ranges: *const sys::VkImageSubresourceRange,
+) {
+    let ranges: Vec<ImageSubresourceRange> = raw_slice(ranges, range_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdClearDepthStencilImage, &[image.as_raw()]).clear_depth_stencil_image(
+        vulkan_handle_cast!(image => Image),
+        image_layout.into(),
+        (*depth_stencil).into(),
+        &ranges,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdClearAttachments<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    attachment_count: u32,
+    attachments: *const sys::VkClearAttachment,
+    rect_count: u32,
+    rects: *const sys::VkClearRect,
+) {
+    let attachments: Vec<ClearAttachment> = raw_slice(attachments, attachment_count).iter()
+        .map(|&x| x.into())
+        .collect();
+    let rects: Vec<ClearRect> = raw_slice(rects, rect_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdClearAttachments).clear_attachments(&attachments, &rects);
+}
+
+pub unsafe extern "C" fn vkCmdResolveImage<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_image: sys::VkImage,
+    src_image_layout: sys::VkImageLayout,
+    dst_image: sys::VkImage,
+    dst_image_layout: sys::VkImageLayout,
+    region_count: u32,
+    regions: *const sys::VkImageResolve,
+) {
+    let regions: Vec<ImageResolve> = raw_slice(regions, region_count).iter()
+        .map(|&x| x.into())
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdResolveImage, &[src_image.as_raw(), dst_image.as_raw()]).resolve_image(
+        vulkan_handle_cast!(src_image => Image),
+        src_image_layout.into(),
+        vulkan_handle_cast!(dst_image => Image),
+        dst_image_layout.into(),
+        &regions,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdSetEvent<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    event: sys::VkEvent,
+    stage_mask: sys::VkPipelineStageFlags,
+) {
+    vulkan_recording!(command_buffer => vkCmdSetEvent, &[event.as_raw()])
+        .set_event(vulkan_handle_cast!(event => Event), PipelineStageFlags::from_bits_truncate(stage_mask));
+}
+
+pub unsafe extern "C" fn vkCmdResetEvent<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    event: sys::VkEvent,
+    stage_mask: sys::VkPipelineStageFlags,
+) {
+    vulkan_recording!(command_buffer => vkCmdResetEvent, &[event.as_raw()])
+        .reset_event(vulkan_handle_cast!(event => Event), PipelineStageFlags::from_bits_truncate(stage_mask));
+}
+
+/// Raw handles of the buffers and images barriers apply to.
+unsafe fn barrier_references(
+    buffer_memory_barrier_count: u32,
+    buffer_memory_barriers: *const sys::VkBufferMemoryBarrier,
+    image_memory_barrier_count: u32,
+    image_memory_barriers: *const sys::VkImageMemoryBarrier,
+) -> Vec<u64> {
+    raw_slice(buffer_memory_barriers, buffer_memory_barrier_count).iter()
+        .map(|x| x.buffer.as_raw())
+        .chain(raw_slice(image_memory_barriers, image_memory_barrier_count).iter().map(|x| x.image.as_raw()))
+        .collect()
+}
+
+unsafe fn barriers<'a, I: Impl>(
+    memory_barrier_count: u32,
+    memory_barriers: *const sys::VkMemoryBarrier,
+    buffer_memory_barrier_count: u32,
+    buffer_memory_barriers: *const sys::VkBufferMemoryBarrier,
+    image_memory_barrier_count: u32,
+    image_memory_barriers: *const sys::VkImageMemoryBarrier,
+) -> (Vec<MemoryBarrier<'a, I>>, Vec<BufferMemoryBarrier<'a, I>>, Vec<ImageMemoryBarrier<'a, I>>) {
+    (
+        raw_slice(memory_barriers, memory_barrier_count).iter()
+            .map(|x| MemoryBarrier(x, PhantomData))
+            .collect(),
+        raw_slice(buffer_memory_barriers, buffer_memory_barrier_count).iter()
+            .map(|x| BufferMemoryBarrier(x, PhantomData))
+            .collect(),
+        raw_slice(image_memory_barriers, image_memory_barrier_count).iter()
+            .map(|x| ImageMemoryBarrier(x, PhantomData))
+            .collect(),
+    )
+}
+
+pub unsafe extern "C" fn vkCmdWaitEvents<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    event_count: u32,
+    events: *const sys::VkEvent,
+    src_stage_mask: sys::VkPipelineStageFlags,
+    dst_stage_mask: sys::VkPipelineStageFlags,
+    memory_barrier_count: u32,
+    memory_barriers: *const sys::VkMemoryBarrier,
+    buffer_memory_barrier_count: u32,
+    buffer_memory_barriers: *const sys::VkBufferMemoryBarrier,
+    image_memory_barrier_count: u32,
+    image_memory_barriers: *const sys::VkImageMemoryBarrier,
+) {
+    let icd_events: Vec<&I::Event> = raw_slice(events, event_count).iter()
+        .map(|&event| vulkan_handle_cast!(event => Event))
+        .collect();
+    let mut references = barrier_references(
+        buffer_memory_barrier_count, buffer_memory_barriers,
+        image_memory_barrier_count, image_memory_barriers,
+    );
+    references.extend(raw_slice(events, event_count).iter().map(|event| event.as_raw()));
+    let (memory_barriers, buffer_barriers, image_barriers) = barriers::<I>(
+        memory_barrier_count, memory_barriers,
+        buffer_memory_barrier_count, buffer_memory_barriers,
+        image_memory_barrier_count, image_memory_barriers,
+    );
+
+    vulkan_recording!(command_buffer => vkCmdWaitEvents, &references).wait_events(
+        &icd_events,
+        PipelineStageFlags::from_bits_truncate(src_stage_mask),
+        PipelineStageFlags::from_bits_truncate(dst_stage_mask),
+        &memory_barriers,
+        &buffer_barriers,
+        &image_barriers,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdPipelineBarrier<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    src_stage_mask: sys::VkPipelineStageFlags,
+    dst_stage_mask: sys::VkPipelineStageFlags,
+    dependency_flags: sys::VkDependencyFlags,
+    memory_barrier_count: u32,
+    memory_barriers: *const sys::VkMemoryBarrier,
+    buffer_memory_barrier_count: u32,
+    buffer_memory_barriers: *const sys::VkBufferMemoryBarrier,
+    image_memory_barrier_count: u32,
+    image_memory_barriers: *const sys::VkImageMemoryBarrier,
+) {
+    let references = barrier_references(
+        buffer_memory_barrier_count, buffer_memory_barriers,
+        image_memory_barrier_count, image_memory_barriers,
+    );
+    let (memory_barriers, buffer_barriers, image_barriers) = barriers::<I>(
+        memory_barrier_count, memory_barriers,
+        buffer_memory_barrier_count, buffer_memory_barriers,
+        image_memory_barrier_count, image_memory_barriers,
+    );
+
+    vulkan_recording!(command_buffer => vkCmdPipelineBarrier, &references).pipeline_barrier(
+        PipelineStageFlags::from_bits_truncate(src_stage_mask),
+        PipelineStageFlags::from_bits_truncate(dst_stage_mask),
+        DependencyFlags::from_bits_truncate(dependency_flags),
+        &memory_barriers,
+        &buffer_barriers,
+        &image_barriers,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdBeginQuery<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    pool: sys::VkQueryPool,
+    query: u32,
+    flags: sys::VkQueryControlFlags,
+) {
+    vulkan_recording!(command_buffer => vkCmdBeginQuery, &[pool.as_raw()])
+        .begin_query(query_pool::<I>(pool), query, QueryControlFlags::from_bits_truncate(flags));
+}
+
+pub unsafe extern "C" fn vkCmdEndQuery<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    pool: sys::VkQueryPool,
+    query: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdEndQuery, &[pool.as_raw()]).end_query(query_pool::<I>(pool), query);
+}
+
+pub unsafe extern "C" fn vkCmdResetQueryPool<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    pool: sys::VkQueryPool,
+    first_query: u32,
+    query_count: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdResetQueryPool, &[pool.as_raw()])
+        .reset_query_pool(query_pool::<I>(pool), first_query..(first_query + query_count));
+}
+
+pub unsafe extern "C" fn vkCmdWriteTimestamp<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    pipeline_stage: sys::VkPipelineStageFlagBits,
+    pool: sys::VkQueryPool,
+    query: u32,
+) {
+    vulkan_recording!(command_buffer => vkCmdWriteTimestamp, &[pool.as_raw()]).write_timestamp(
+        PipelineStageFlags::from_bits_truncate(pipeline_stage as u32),
+        query_pool::<I>(pool),
+        query,
+    );
+}
+
+pub unsafe extern "C" fn vkCmdCopyQueryPoolResults<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    pool: sys::VkQueryPool,
+    first_query: u32,
+    query_count: u32,
+    dst_buffer: sys::VkBuffer,
+    dst_offset: sys::VkDeviceSize,
+    stride: sys::VkDeviceSize,
+    flags: sys::VkQueryResultFlags,
+) {
+    vulkan_recording!(command_buffer => vkCmdCopyQueryPoolResults, &[pool.as_raw(), dst_buffer.as_raw()]).copy_query_pool_results(
+        query_pool::<I>(pool),
+        first_query..(first_query + query_count),
+        vulkan_handle_cast!(dst_buffer => Buffer),
+        dst_offset,
+        stride,
+        QueryResultFlags::from_bits_truncate(flags),
+    );
+}
+
+pub unsafe extern "C" fn vkCmdBeginRenderPass<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    render_pass_begin: *const sys::VkRenderPassBeginInfo,
+    contents: sys::VkSubpassContents,
+) {
+    vulkan_recording!(command_buffer => vkCmdBeginRenderPass, &[(*render_pass_begin).renderPass.as_raw(), (*render_pass_begin).framebuffer.as_raw()])
+        .begin_render_pass(RenderPassBeginInfo(&*render_pass_begin, PhantomData), contents.into());
+}
+
+pub unsafe extern "C" fn vkCmdNextSubpass<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    contents: sys::VkSubpassContents,
+) {
+    vulkan_recording!(command_buffer => vkCmdNextSubpass).next_subpass(contents.into());
+}
+
+pub unsafe extern "C" fn vkCmdEndRenderPass<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+) {
+    vulkan_recording!(command_buffer => vkCmdEndRenderPass).end_render_pass();
+}
+
+pub unsafe extern "C" fn vkCmdExecuteCommands<I: Impl>(
+    command_buffer: sys::VkCommandBuffer,
+    command_buffer_count: u32,
+    command_buffers: *const sys::VkCommandBuffer,
+) {
+    if vulkan_dispatch_cast!(command_buffer => raw CommandBuffer).level != CommandBufferLevel::Primary {
+        error!("vkCmdExecuteCommands called on a secondary command buffer");
+        return;
+    }
+
+    let raw_secondaries = raw_slice(command_buffers, command_buffer_count);
+    for &secondary in raw_secondaries {
+        if vulkan_dispatch_cast!(secondary => raw CommandBuffer).level != CommandBufferLevel::Secondary {
+            error!("vkCmdExecuteCommands called with a primary command buffer");
+            return;
+        }
+        let (state, usage) = {
+            let lifecycle = lifecycle::<I>(secondary);
+            (lifecycle.state, lifecycle.usage)
+        };
+        match state {
+            CommandBufferState::Executable => {},
+            CommandBufferState::Pending if usage.contains(VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT) => {},
+            _ => {
+                error!("vkCmdExecuteCommands called with a secondary command buffer in the {:?} state", state);
+                return;
+            },
+        }
+    }
+    let references: Vec<u64> = raw_secondaries.iter()
+        .map(|&secondary| command_buffer_raw(secondary))
+        .collect();
+    let secondaries: Vec<&I::CommandBuffer> = raw_secondaries.iter()
+        .map(|&secondary| vulkan_dispatch_cast!(secondary => CommandBuffer))
+        .collect();
+
+    vulkan_recording!(command_buffer => vkCmdExecuteCommands, &references).execute_commands(&secondaries);
+    lifecycle::<I>(command_buffer).secondaries.extend(raw_secondaries.iter().cloned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_impl::*;
+
+    type I = TestImpl;
+
+    /// A device with one queue and a command pool, driven through the trampolines.
+    struct Fixture {
+        device: sys::VkDevice,
+        queue: sys::VkQueue,
+        pool: sys::VkCommandPool,
+    }
+
+    impl Fixture {
+        unsafe fn new(pool_flags: CommandPoolCreateFlags) -> Fixture {
+            let physical_device: sys::VkPhysicalDevice = vulkan_dispatch_form!(TestPhysicalDevice(0) => PhysicalDevice);
+            let priority = 1.0f32;
+            let queue_info = sys::VkDeviceQueueCreateInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_DEVICE_QUEUE_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                queueFamilyIndex: 0,
+                queueCount: 1,
+                pQueuePriorities: &priority,
+            };
+            let device_info = sys::VkDeviceCreateInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_DEVICE_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                queueCreateInfoCount: 1,
+                pQueueCreateInfos: &queue_info,
+                enabledLayerCount: 0,
+                ppEnabledLayerNames: ptr::null(),
+                enabledExtensionCount: 0,
+                ppEnabledExtensionNames: ptr::null(),
+                pEnabledFeatures: ptr::null(),
+            };
+            let mut device = ptr::null_mut();
+            assert_eq!(vkCreateDevice::<I>(physical_device, &device_info, ptr::null(), &mut device), OK);
+            let mut queue = ptr::null_mut();
+            vkGetDeviceQueue::<I>(device, 0, 0, &mut queue);
+
+            let pool_info = sys::VkCommandPoolCreateInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: pool_flags.bits(),
+                queueFamilyIndex: 0,
+            };
+            let mut pool = mem::zeroed();
+            assert_eq!(vkCreateCommandPool::<I>(device, &pool_info, ptr::null(), &mut pool), OK);
+
+            Fixture { device: device, queue: queue, pool: pool }
+        }
+
+        unsafe fn allocate(&self, level: CommandBufferLevel) -> sys::VkCommandBuffer {
+            let info = sys::VkCommandBufferAllocateInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO,
+                pNext: ptr::null(),
+                commandPool: self.pool,
+                level: level.into(),
+                commandBufferCount: 1,
+            };
+            let mut command_buffer = ptr::null_mut();
+            assert_eq!(vkAllocateCommandBuffers::<I>(self.device, &info, &mut command_buffer), OK);
+            command_buffer
+        }
+
+        /// Records a fill of `buffer` into a new primary command buffer.
+        unsafe fn record(&self, usage: CommandBufferUsageFlags, buffer: sys::VkBuffer) -> sys::VkCommandBuffer {
+            let command_buffer = self.allocate(CommandBufferLevel::Primary);
+            assert_eq!(begin(command_buffer, usage), OK);
+            vkCmdFillBuffer::<I>(command_buffer, buffer, 0, 16, 0);
+            assert_eq!(vkEndCommandBuffer::<I>(command_buffer), OK);
+            command_buffer
+        }
+
+        unsafe fn submit(&self, command_buffers: &[sys::VkCommandBuffer], fence: sys::VkFence) -> sys::VkResult {
+            let info = sys::VkSubmitInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_SUBMIT_INFO,
+                pNext: ptr::null(),
+                waitSemaphoreCount: 0,
+                pWaitSemaphores: ptr::null(),
+                pWaitDstStageMask: ptr::null(),
+                commandBufferCount: command_buffers.len() as u32,
+                pCommandBuffers: command_buffers.as_ptr(),
+                signalSemaphoreCount: 0,
+                pSignalSemaphores: ptr::null(),
+            };
+            vkQueueSubmit::<I>(self.queue, 1, &info, fence)
+        }
+
+        unsafe fn create_fence(&self) -> sys::VkFence {
+            let info = sys::VkFenceCreateInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_FENCE_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+            };
+            let mut fence = mem::zeroed();
+            assert_eq!(vkCreateFence::<I>(self.device, &info, ptr::null(), &mut fence), OK);
+            fence
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            unsafe {
+                vkDestroyCommandPool::<I>(self.device, self.pool, ptr::null());
+                vkDestroyDevice::<I>(self.device, ptr::null());
+            }
+        }
+    }
+
+    unsafe fn begin(command_buffer: sys::VkCommandBuffer, usage: CommandBufferUsageFlags) -> sys::VkResult {
+        let info = sys::VkCommandBufferBeginInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+            pNext: ptr::null(),
+            flags: usage.bits(),
+            pInheritanceInfo: ptr::null(),
+        };
+        vkBeginCommandBuffer::<I>(command_buffer, &info)
+    }
+
+    unsafe fn state(command_buffer: sys::VkCommandBuffer) -> CommandBufferState {
+        lifecycle::<I>(command_buffer).state
+    }
+
+    fn buffer() -> sys::VkBuffer {
+        handle::form::<TestBuffer, _>(TestBuffer(0))
+    }
+
+    #[test]
+    fn command_buffers_move_through_their_states() {
+        unsafe {
+            let fixture = Fixture::new(VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT);
+            let buffer = buffer();
+            let command_buffer = fixture.allocate(CommandBufferLevel::Primary);
+            assert_eq!(state(command_buffer), CommandBufferState::Initial);
+            assert_eq!(fixture.submit(&[command_buffer], mem::zeroed()), INVALID_USAGE);
+
+            assert_eq!(begin(command_buffer, CommandBufferUsageFlags::empty()), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Recording);
+            assert_eq!(begin(command_buffer, CommandBufferUsageFlags::empty()), INVALID_USAGE);
+            vkCmdFillBuffer::<I>(command_buffer, buffer, 0, 16, 0);
+            assert_eq!(vkEndCommandBuffer::<I>(command_buffer), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Executable);
+
+            assert_eq!(fixture.submit(&[command_buffer], mem::zeroed()), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Pending);
+            // Without VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT, a pending command buffer can be
+            // neither submitted again nor reset.
+            assert_eq!(fixture.submit(&[command_buffer], mem::zeroed()), INVALID_USAGE);
+            assert_eq!(vkResetCommandBuffer::<I>(command_buffer, 0), INVALID_USAGE);
+            assert_eq!(begin(command_buffer, CommandBufferUsageFlags::empty()), INVALID_USAGE);
+            assert_eq!(vkQueueWaitIdle::<I>(fixture.queue), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Executable);
+
+            // Fenced submissions complete once the fence is seen signaled, along with everything
+            // submitted before them.
+            let fence = fixture.create_fence();
+            let other = fixture.record(CommandBufferUsageFlags::empty(), buffer);
+            assert_eq!(fixture.submit(&[command_buffer], mem::zeroed()), OK);
+            assert_eq!(fixture.submit(&[other], fence), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Pending);
+            assert_eq!(vkWaitForFences::<I>(fixture.device, 1, &fence, sys::VK_TRUE, !0), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Executable);
+            assert_eq!(state(other), CommandBufferState::Executable);
+            // Naming a fence twice is allowed.
+            assert_eq!(vkResetFences::<I>(fixture.device, 2, [fence, fence].as_ptr()), OK);
+            assert_eq!(vkGetFenceStatus::<I>(fixture.device, fence), sys::VkResult::VK_NOT_READY);
+
+            assert_eq!(vkResetCommandBuffer::<I>(command_buffer, 0), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Initial);
+
+            vkDestroyFence::<I>(fixture.device, fence, ptr::null());
+            vkDestroyBuffer::<I>(fixture.device, buffer, ptr::null());
+        }
+    }
+
+    #[test]
+    fn one_time_submissions_invalidate_the_command_buffer() {
+        unsafe {
+            let fixture = Fixture::new(CommandPoolCreateFlags::empty());
+            let buffer = buffer();
+            let command_buffer = fixture.record(VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT, buffer);
+            let fence = fixture.create_fence();
+
+            assert_eq!(fixture.submit(&[command_buffer], fence), OK);
+            assert_eq!(vkGetFenceStatus::<I>(fixture.device, fence), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Invalid);
+            assert_eq!(fixture.submit(&[command_buffer], mem::zeroed()), INVALID_USAGE);
+            // Without VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT, only resetting the pool
+            // makes the command buffer usable again.
+            assert_eq!(begin(command_buffer, CommandBufferUsageFlags::empty()), INVALID_USAGE);
+            assert_eq!(vkResetCommandPool::<I>(fixture.device, fixture.pool, 0), OK);
+            assert_eq!(state(command_buffer), CommandBufferState::Initial);
+            assert_eq!(begin(command_buffer, CommandBufferUsageFlags::empty()), OK);
+
+            vkDestroyFence::<I>(fixture.device, fence, ptr::null());
+            vkDestroyBuffer::<I>(fixture.device, buffer, ptr::null());
+        }
+    }
+
+    #[test]
+    fn changing_a_secondary_invalidates_the_primaries_executing_it() {
+        unsafe {
+            let fixture = Fixture::new(VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT);
+            let buffer = buffer();
+            let secondary = fixture.allocate(CommandBufferLevel::Secondary);
+            let inheritance = sys::VkCommandBufferInheritanceInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_INHERITANCE_INFO,
+                pNext: ptr::null(),
+                renderPass: mem::zeroed(),
+                subpass: 0,
+                framebuffer: mem::zeroed(),
+                occlusionQueryEnable: sys::VK_FALSE,
+                queryFlags: 0,
+                pipelineStatistics: 0,
+            };
+            let begin_info = sys::VkCommandBufferBeginInfo {
+                sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                pInheritanceInfo: &inheritance,
+            };
+            let record_secondary = || {
+                assert_eq!(vkBeginCommandBuffer::<I>(secondary, &begin_info), OK);
+                vkCmdFillBuffer::<I>(secondary, buffer, 0, 16, 0);
+                assert_eq!(vkEndCommandBuffer::<I>(secondary), OK);
+            };
+            let record_primary = || {
+                let primary = fixture.allocate(CommandBufferLevel::Primary);
+                assert_eq!(begin(primary, CommandBufferUsageFlags::empty()), OK);
+                vkCmdExecuteCommands::<I>(primary, 1, &secondary);
+                assert_eq!(vkEndCommandBuffer::<I>(primary), OK);
+                primary
+            };
+
+            // Recording the secondary again.
+            record_secondary();
+            let primary = record_primary();
+            record_secondary();
+            assert_eq!(state(primary), CommandBufferState::Invalid);
+            assert_eq!(fixture.submit(&[primary], mem::zeroed()), INVALID_USAGE);
+
+            // Resetting it.
+            let primary = record_primary();
+            assert_eq!(fixture.submit(&[primary], mem::zeroed()), OK);
+            assert_eq!(vkQueueWaitIdle::<I>(fixture.queue), OK);
+            assert_eq!(vkResetCommandBuffer::<I>(secondary, 0), OK);
+            assert_eq!(state(primary), CommandBufferState::Invalid);
+
+            // Freeing it.
+            record_secondary();
+            let primary = record_primary();
+            vkFreeCommandBuffers::<I>(fixture.device, fixture.pool, 1, &secondary);
+            assert_eq!(state(primary), CommandBufferState::Invalid);
+
+            vkDestroyBuffer::<I>(fixture.device, buffer, ptr::null());
+        }
+    }
+
+    #[test]
+    fn destroying_a_referenced_object_invalidates_the_command_buffer() {
+        unsafe {
+            let fixture = Fixture::new(CommandPoolCreateFlags::empty());
+            let (buffer, unused) = (buffer(), buffer());
+            let recording = fixture.allocate(CommandBufferLevel::Primary);
+            assert_eq!(begin(recording, CommandBufferUsageFlags::empty()), OK);
+            vkCmdFillBuffer::<I>(recording, buffer, 0, 16, 0);
+            let executable = fixture.record(CommandBufferUsageFlags::empty(), buffer);
+
+            vkDestroyBuffer::<I>(fixture.device, unused, ptr::null());
+            assert_eq!(state(recording), CommandBufferState::Recording);
+            assert_eq!(state(executable), CommandBufferState::Executable);
+
+            vkDestroyBuffer::<I>(fixture.device, buffer, ptr::null());
+            assert_eq!(state(recording), CommandBufferState::Invalid);
+            assert_eq!(state(executable), CommandBufferState::Invalid);
+            assert_eq!(vkEndCommandBuffer::<I>(recording), INVALID_USAGE);
+            assert_eq!(fixture.submit(&[executable], mem::zeroed()), INVALID_USAGE);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! A fence signaled from the host, for software and emulation backends.
+//!
+//! All `CpuFence`s share one condition variable. That makes waiting on any of several fences as
+//! simple as waiting on all of them, at the cost of spurious wakeups that CPU backends can afford.
+
+use super::*;
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+lazy_static! {
+    static ref SIGNAL_LOCK: Mutex<()> = Mutex::new(());
+    static ref SIGNALED: Condvar = Condvar::new();
+}
+
+pub struct CpuFence {
+    signaled: Arc<AtomicBool>,
+}
+
+/// Signals a `CpuFence` from another thread, such as a backend's queue worker.
+#[derive(Clone)]
+pub struct CpuFenceSignaler {
+    signaled: Arc<AtomicBool>,
+}
+
+impl CpuFence {
+    pub fn new(signaled: bool) -> CpuFence {
+        CpuFence {
+            signaled: Arc::new(AtomicBool::new(signaled)),
+        }
+    }
+
+    pub fn signaler(&self) -> CpuFenceSignaler {
+        CpuFenceSignaler { signaled: self.signaled.clone() }
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        self.signaled.load(Ordering::Acquire)
+    }
+
+    pub fn signal(&self) {
+        signal(&self.signaled)
+    }
+
+    pub fn reset(&self) {
+        self.signaled.store(false, Ordering::Release);
+    }
+
+    /// Blocks until every fence (or with `wait_all` unset, any fence) is signaled, or until
+    /// `timeout` nanoseconds have passed.
+    pub fn wait_many<F>(fences: &[F], wait_all: bool, timeout: u64) -> WaitStatus where
+        F: Borrow<CpuFence>
+    {
+        let done = || if wait_all {
+            fences.iter().all(|x| x.borrow().is_signaled())
+        } else {
+            fences.iter().any(|x| x.borrow().is_signaled())
+        };
+
+        let timeout = Duration::new(timeout / 1_000_000_000, (timeout % 1_000_000_000) as u32);
+        let start = Instant::now();
+
+        let mut guard = SIGNAL_LOCK.lock().unwrap();
+        loop {
+            if done() {
+                return WaitStatus::Signaled;
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return WaitStatus::Timeout;
+            }
+
+            guard = SIGNALED.wait_timeout(guard, timeout - elapsed).unwrap().0;
+        }
+    }
+}
+
+impl CpuFenceSignaler {
+    pub fn signal(&self) {
+        signal(&self.signaled)
+    }
+}
+
+fn signal(signaled: &AtomicBool) {
+    // Taking the lock ensures a waiter can't miss the notification between checking and sleeping
+    let _guard = SIGNAL_LOCK.lock().unwrap();
+    signaled.store(true, Ordering::Release);
+    SIGNALED.notify_all();
+}
+
+impl<I: Impl> Fence<I> for CpuFence {
+    fn new(_device: &I::Device, info: FenceCreateInfo<I>) -> Result<Self> {
+        Ok(CpuFence::new(info.flags().contains(VK_FENCE_CREATE_SIGNALED_BIT)))
+    }
+
+    fn reset<F>(_device: &I::Device, fences: &mut [F]) -> Result<()> where
+        F: BorrowMut<Self>
+    {
+        for fence in fences {
+            fence.borrow_mut().reset();
+        }
+        Ok(())
+    }
+
+    fn wait<F>(_device: &I::Device, fences: &[F], wait_all: bool, timeout: u64) -> Result<WaitStatus> where
+        F: Borrow<Self>
+    {
+        Ok(CpuFence::wait_many(fences, wait_all, timeout))
+    }
+
+    fn get_status(&self, _device: &I::Device) -> Result<()> {
+        if self.is_signaled() {
+            Ok(())
+        } else {
+            Err(Error::NotReady)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn waiting_for_all_needs_every_fence() {
+        let fences = [CpuFence::new(true), CpuFence::new(false)];
+        assert_eq!(CpuFence::wait_many(&fences, true, 0), WaitStatus::Timeout);
+
+        fences[1].signal();
+        assert_eq!(CpuFence::wait_many(&fences, true, 0), WaitStatus::Signaled);
+    }
+
+    #[test]
+    fn waiting_for_any_needs_one_fence() {
+        let fences = [CpuFence::new(false), CpuFence::new(false)];
+        assert_eq!(CpuFence::wait_many(&fences, false, 0), WaitStatus::Timeout);
+
+        fences[1].signal();
+        assert_eq!(CpuFence::wait_many(&fences, false, 0), WaitStatus::Signaled);
+
+        fences[1].reset();
+        assert_eq!(CpuFence::wait_many(&fences, false, 0), WaitStatus::Timeout);
+    }
+
+    #[test]
+    fn timeouts_are_respected() {
+        let fences = [CpuFence::new(false)];
+        let start = Instant::now();
+        assert_eq!(CpuFence::wait_many(&fences, true, 10_000_000), WaitStatus::Timeout);
+        assert!(start.elapsed() >= Duration::new(0, 10_000_000));
+    }
+
+    #[test]
+    fn signals_from_other_threads_wake_waiters() {
+        let fences = [CpuFence::new(false), CpuFence::new(false)];
+        let signaler = fences[1].signaler();
+
+        let thread = thread::spawn(move || {
+            thread::sleep(Duration::new(0, 10_000_000));
+            signaler.signal();
+        });
+
+        assert_eq!(CpuFence::wait_many(&fences, false, !0), WaitStatus::Signaled);
+        assert!(fences[1].is_signaled());
+        thread.join().unwrap();
+    }
+}
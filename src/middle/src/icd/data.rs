@@ -1,5 +1,6 @@
 use ::sys;
 use super::*;
+use spirv::SpirvModule;
 
 use std::slice;
 
@@ -21,11 +22,41 @@ pub struct ImageCreateInfo<'a, I:Impl>(pub(crate) &'a sys::VkImageCreateInfo, pu
 pub struct ImageViewCreateInfo<'a, I:Impl>(pub(crate) &'a sys::VkImageViewCreateInfo, pub(crate) PhantomData<&'static I>);
 pub struct ImageSubresource<'a, I:Impl>(pub(crate) &'a sys::VkImageSubresource, pub(crate) PhantomData<&'static I>);
 
-pub struct ShaderModuleCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkShaderModuleCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct SamplerCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkSamplerCreateInfo, pub(crate) PhantomData<&'static I>);
+
+pub struct DescriptorSetLayoutCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkDescriptorSetLayoutCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct DescriptorSetLayoutBinding<'a, I: Impl>(pub(crate) &'a sys::VkDescriptorSetLayoutBinding, pub(crate) PhantomData<&'static I>);
+pub struct DescriptorPoolCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkDescriptorPoolCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineLayoutCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineLayoutCreateInfo, pub(crate) PhantomData<&'static I>);
+
+pub struct CommandPoolCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkCommandPoolCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct CommandBufferBeginInfo<'a, I: Impl>(pub(crate) &'a sys::VkCommandBufferBeginInfo, pub(crate) PhantomData<&'static I>);
+pub struct CommandBufferInheritanceInfo<'a, I: Impl>(pub(crate) &'a sys::VkCommandBufferInheritanceInfo, pub(crate) PhantomData<&'static I>);
+pub struct RenderPassBeginInfo<'a, I: Impl>(pub(crate) &'a sys::VkRenderPassBeginInfo, pub(crate) PhantomData<&'static I>);
+pub struct MemoryBarrier<'a, I: Impl>(pub(crate) &'a sys::VkMemoryBarrier, pub(crate) PhantomData<&'static I>);
+pub struct BufferMemoryBarrier<'a, I: Impl>(pub(crate) &'a sys::VkBufferMemoryBarrier, pub(crate) PhantomData<&'static I>);
+pub struct ImageMemoryBarrier<'a, I: Impl>(pub(crate) &'a sys::VkImageMemoryBarrier, pub(crate) PhantomData<&'static I>);
+
+pub struct RenderPassCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkRenderPassCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct AttachmentDescription<'a, I: Impl>(pub(crate) &'a sys::VkAttachmentDescription, pub(crate) PhantomData<&'static I>);
+pub struct SubpassDescription<'a, I: Impl>(pub(crate) &'a sys::VkSubpassDescription, pub(crate) PhantomData<&'static I>);
+pub struct FramebufferCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkFramebufferCreateInfo, pub(crate) PhantomData<&'static I>);
+
+pub struct ShaderModuleCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkShaderModuleCreateInfo, pub(crate) &'a SpirvModule, pub(crate) PhantomData<&'static I>);
 
 pub struct GraphicsPipelineCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkGraphicsPipelineCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct ComputePipelineCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkComputePipelineCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineShaderStageCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineShaderStageCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct SpecializationInfo<'a, I: Impl>(pub(crate) &'a sys::VkSpecializationInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineVertexInputStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineVertexInputStateCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineInputAssemblyStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineInputAssemblyStateCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineViewportStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineViewportStateCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineRasterizationStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineRasterizationStateCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineMultisampleStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineMultisampleStateCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineDepthStencilStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineDepthStencilStateCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineColorBlendStateCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineColorBlendStateCreateInfo, pub(crate) PhantomData<&'static I>);
 
-pub struct PipelineCacheCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineCacheCreateInfo, pub(crate) PhantomData<&'static I>);
+pub struct PipelineCacheCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkPipelineCacheCreateInfo, pub(crate) CacheHeader, pub(crate) PhantomData<&'static I>);
 
 pub struct FenceCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkFenceCreateInfo, pub(crate) PhantomData<&'static I>);
 
@@ -35,11 +66,30 @@ pub struct EventCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkEventCreateInfo, p
 
 pub struct QueryPoolCreateInfo<'a, I: Impl>(pub(crate) &'a sys::VkQueryPoolCreateInfo, pub(crate) PhantomData<&'static I>);
 
+impl<'a, I: Impl> InstanceCreateInfo<'a, I> {
+    pub fn enabled_extensions(&self) -> impl ExactSizeIterator<Item = &'a CStr> {
+        let slice = unsafe { raw_slice(self.0.ppEnabledExtensionNames, self.0.enabledExtensionCount) };
+        slice.iter().map(|&x| unsafe { CStr::from_ptr(x) })
+    }
+}
+
 impl<'a, I: Impl> DeviceCreateInfo<'a, I> {
+    pub fn enabled_extensions(&self) -> impl ExactSizeIterator<Item = &'a CStr> {
+        let slice = unsafe { raw_slice(self.0.ppEnabledExtensionNames, self.0.enabledExtensionCount) };
+        slice.iter().map(|&x| unsafe { CStr::from_ptr(x) })
+    }
     pub fn queues(&self) -> impl ExactSizeIterator<Item = DeviceQueueCreateInfo<'a, I>> {
         let slice = unsafe { slice::from_raw_parts(self.0.pQueueCreateInfos, self.0.queueCreateInfoCount as usize) };
         slice.iter().map(|x| DeviceQueueCreateInfo(x, PhantomData))
     }
+    /// All features are disabled if the application did not pass any.
+    pub fn enabled_features(&self) -> PhysicalDeviceFeatures {
+        if self.0.pEnabledFeatures.is_null() {
+            PhysicalDeviceFeatures::default()
+        } else {
+            unsafe { *self.0.pEnabledFeatures }.into()
+        }
+    }
 }
 
 impl<'a, I: Impl> DeviceQueueCreateInfo<'a, I> {
@@ -47,6 +97,554 @@ impl<'a, I: Impl> DeviceQueueCreateInfo<'a, I> {
     pub fn count(&self) -> u32 { self.0.queueCount }
 }
 
+impl<'a, I: Impl> MemoryAllocateInfo<'a, I> {
+    pub fn allocation_size(&self) -> u64 { self.0.allocationSize }
+    pub fn memory_type_index(&self) -> u32 { self.0.memoryTypeIndex }
+}
+
+pub struct SubmitInfo<'a, I: Impl>(pub(crate) &'a sys::VkSubmitInfo, pub(crate) PhantomData<&'static I>);
+
+impl<'a, I: Impl> SubmitInfos<'a, I> {
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = SubmitInfo<'a, I>> {
+        self.0.iter().map(|x| SubmitInfo(x, PhantomData))
+    }
+}
+
+impl<'a, I: Impl> SubmitInfo<'a, I> {
+    /// Each semaphore is paired with the stages that wait on it.
+    pub fn wait_semaphores(&self) -> impl ExactSizeIterator<Item = (&'a I::Semaphore, PipelineStageFlags)> {
+        let semaphores = unsafe { raw_slice(self.0.pWaitSemaphores, self.0.waitSemaphoreCount) };
+        let stages = unsafe { raw_slice(self.0.pWaitDstStageMask, self.0.waitSemaphoreCount) };
+        semaphores.iter().zip(stages.iter())
+            .map(|(&semaphore, &stages)| (unsafe { handle::cast(semaphore) }, PipelineStageFlags::from_bits_truncate(stages)))
+    }
+    pub fn command_buffers(&self) -> impl ExactSizeIterator<Item = &'a I::CommandBuffer> {
+        let slice = unsafe { raw_slice(self.0.pCommandBuffers, self.0.commandBufferCount) };
+        slice.iter().map(|&x| unsafe { &(*(x as *const dispatch::CommandBuffer<I>)).imp })
+    }
+    pub fn signal_semaphores(&self) -> impl ExactSizeIterator<Item = &'a I::Semaphore> {
+        let slice = unsafe { raw_slice(self.0.pSignalSemaphores, self.0.signalSemaphoreCount) };
+        slice.iter().map(|&x| unsafe { handle::cast(x) })
+    }
+}
+
+pub struct MappedMemoryRange<'a, I: Impl>(pub(crate) &'a sys::VkMappedMemoryRange, pub(crate) PhantomData<&'static I>);
+
+impl<'a, I: Impl> MappedMemoryRanges<'a, I> {
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = MappedMemoryRange<'a, I>> {
+        self.0.iter().map(|x| MappedMemoryRange(x, PhantomData))
+    }
+}
+
+impl<'a, I: Impl> MappedMemoryRange<'a, I> {
+    pub fn memory(&self) -> &'a I::DeviceMemory { unsafe { handle::cast(self.0.memory) } }
+    pub fn offset(&self) -> u64 { self.0.offset }
+    /// May be `WHOLE_SIZE`, meaning the range extends to the end of the current mapping.
+    pub fn size(&self) -> u64 { self.0.size }
+}
+
+impl<'a, I: Impl> BufferCreateInfo<'a, I> {
+    pub fn flags(&self) -> BufferCreateFlags { BufferCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn size(&self) -> u64 { self.0.size }
+    pub fn usage(&self) -> BufferUsageFlags { BufferUsageFlags::from_bits_truncate(self.0.usage) }
+    pub fn sharing_mode(&self) -> SharingMode { self.0.sharingMode.into() }
+    pub fn queue_family_indices(&self) -> &'a [u32] {
+        unsafe { raw_slice(self.0.pQueueFamilyIndices, self.0.queueFamilyIndexCount) }
+    }
+}
+
+impl<'a, I: Impl> BufferViewCreateInfo<'a, I> {
+    pub fn buffer(&self) -> &'a I::Buffer { unsafe { handle::cast(self.0.buffer) } }
+    pub fn format(&self) -> Format { self.0.format.into() }
+    pub fn offset(&self) -> u64 { self.0.offset }
+    /// May be `WHOLE_SIZE`, meaning the view extends to the end of the buffer.
+    pub fn range(&self) -> u64 { self.0.range }
+}
+
+impl<'a, I: Impl> ImageCreateInfo<'a, I> {
+    pub fn flags(&self) -> ImageCreateFlags { ImageCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn image_type(&self) -> ImageType { self.0.imageType.into() }
+    pub fn format(&self) -> Format { self.0.format.into() }
+    pub fn extent(&self) -> Extent3D { self.0.extent.into() }
+    pub fn mip_levels(&self) -> u32 { self.0.mipLevels }
+    pub fn array_layers(&self) -> u32 { self.0.arrayLayers }
+    pub fn samples(&self) -> SampleCountFlags { SampleCountFlags::from_bits_truncate(self.0.samples as u32) }
+    pub fn tiling(&self) -> ImageTiling { self.0.tiling.into() }
+    pub fn usage(&self) -> ImageUsageFlags { ImageUsageFlags::from_bits_truncate(self.0.usage) }
+    pub fn sharing_mode(&self) -> SharingMode { self.0.sharingMode.into() }
+    pub fn queue_family_indices(&self) -> &'a [u32] {
+        unsafe { raw_slice(self.0.pQueueFamilyIndices, self.0.queueFamilyIndexCount) }
+    }
+    pub fn initial_layout(&self) -> ImageLayout { self.0.initialLayout.into() }
+}
+
+impl<'a, I: Impl> ImageViewCreateInfo<'a, I> {
+    pub fn image(&self) -> &'a I::Image { unsafe { handle::cast(self.0.image) } }
+    pub fn view_type(&self) -> ImageViewType { self.0.viewType.into() }
+    pub fn format(&self) -> Format { self.0.format.into() }
+    pub fn components(&self) -> ComponentMapping { self.0.components.into() }
+    pub fn subresource_range(&self) -> ImageSubresourceRange { self.0.subresourceRange.into() }
+}
+
+impl<'a, I: Impl> FenceCreateInfo<'a, I> {
+    pub fn flags(&self) -> FenceCreateFlags { FenceCreateFlags::from_bits_truncate(self.0.flags) }
+}
+
+impl<'a, I: Impl> SamplerCreateInfo<'a, I> {
+    pub fn mag_filter(&self) -> Filter { self.0.magFilter.into() }
+    pub fn min_filter(&self) -> Filter { self.0.minFilter.into() }
+    pub fn mipmap_mode(&self) -> SamplerMipmapMode { self.0.mipmapMode.into() }
+    pub fn address_mode_u(&self) -> SamplerAddressMode { self.0.addressModeU.into() }
+    pub fn address_mode_v(&self) -> SamplerAddressMode { self.0.addressModeV.into() }
+    pub fn address_mode_w(&self) -> SamplerAddressMode { self.0.addressModeW.into() }
+    pub fn mip_lod_bias(&self) -> f32 { self.0.mipLodBias }
+    /// `None` if anisotropic filtering is disabled.
+    pub fn max_anisotropy(&self) -> Option<f32> {
+        if self.0.anisotropyEnable == sys::VK_TRUE { Some(self.0.maxAnisotropy) } else { None }
+    }
+    /// `None` if depth comparison is disabled.
+    pub fn compare_op(&self) -> Option<CompareOp> {
+        if self.0.compareEnable == sys::VK_TRUE { Some(self.0.compareOp.into()) } else { None }
+    }
+    pub fn min_lod(&self) -> f32 { self.0.minLod }
+    pub fn max_lod(&self) -> f32 { self.0.maxLod }
+    pub fn border_color(&self) -> BorderColor { self.0.borderColor.into() }
+    pub fn unnormalized_coordinates(&self) -> bool { self.0.unnormalizedCoordinates == sys::VK_TRUE }
+}
+
+impl<'a, I: Impl> DescriptorSetLayoutCreateInfo<'a, I> {
+    pub fn flags(&self) -> DescriptorSetLayoutCreateFlags { DescriptorSetLayoutCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn bindings(&self) -> impl ExactSizeIterator<Item = DescriptorSetLayoutBinding<'a, I>> {
+        let slice = unsafe { raw_slice(self.0.pBindings, self.0.bindingCount) };
+        slice.iter().map(|x| DescriptorSetLayoutBinding(x, PhantomData))
+    }
+}
+
+impl<'a, I: Impl> DescriptorSetLayoutBinding<'a, I> {
+    pub fn binding(&self) -> u32 { self.0.binding }
+    pub fn descriptor_type(&self) -> DescriptorType { self.0.descriptorType.into() }
+    pub fn descriptor_count(&self) -> u32 { self.0.descriptorCount }
+    pub fn stage_flags(&self) -> ShaderStageFlags { ShaderStageFlags::from_bits_truncate(self.0.stageFlags) }
+    /// Empty unless the binding is a sampler or combined image sampler binding with immutable
+    /// samplers, in which case there is one per descriptor.
+    pub fn immutable_samplers(&self) -> impl ExactSizeIterator<Item = &'a I::Sampler> {
+        let count = match self.descriptor_type() {
+            DescriptorType::Sampler | DescriptorType::CombinedImageSampler if !self.0.pImmutableSamplers.is_null() => self.0.descriptorCount,
+            _ => 0,
+        };
+        let slice = unsafe { raw_slice(self.0.pImmutableSamplers, count) };
+        slice.iter().map(|&x| unsafe { handle::cast(x) })
+    }
+}
+
+impl<'a, I: Impl> DescriptorPoolCreateInfo<'a, I> {
+    pub fn flags(&self) -> DescriptorPoolCreateFlags { DescriptorPoolCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn max_sets(&self) -> u32 { self.0.maxSets }
+    pub fn pool_sizes(&self) -> impl ExactSizeIterator<Item = DescriptorPoolSize> {
+        let slice = unsafe { raw_slice(self.0.pPoolSizes, self.0.poolSizeCount) };
+        slice.iter().map(|&x| x.into())
+    }
+}
+
+impl<'a, I: Impl> PipelineLayoutCreateInfo<'a, I> {
+    pub fn set_layouts(&self) -> impl ExactSizeIterator<Item = &'a I::DescriptorSetLayout> {
+        let slice = unsafe { raw_slice(self.0.pSetLayouts, self.0.setLayoutCount) };
+        slice.iter().map(|&x| unsafe { handle::cast(x) })
+    }
+    pub fn push_constant_ranges(&self) -> impl ExactSizeIterator<Item = PushConstantRange> {
+        let slice = unsafe { raw_slice(self.0.pPushConstantRanges, self.0.pushConstantRangeCount) };
+        slice.iter().map(|&x| x.into())
+    }
+}
+
+impl<'a, I: Impl> CommandPoolCreateInfo<'a, I> {
+    pub fn flags(&self) -> CommandPoolCreateFlags { CommandPoolCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn queue_family_index(&self) -> u32 { self.0.queueFamilyIndex }
+}
+
+impl<'a, I: Impl> CommandBufferBeginInfo<'a, I> {
+    pub fn flags(&self) -> CommandBufferUsageFlags { CommandBufferUsageFlags::from_bits_truncate(self.0.flags) }
+    /// Always `None` when beginning a primary command buffer.
+    pub fn inheritance_info(&self) -> Option<CommandBufferInheritanceInfo<'a, I>> {
+        if self.0.pInheritanceInfo.is_null() {
+            None
+        } else {
+            Some(CommandBufferInheritanceInfo(unsafe { &*self.0.pInheritanceInfo }, PhantomData))
+        }
+    }
+}
+
+impl<'a, I: Impl> CommandBufferInheritanceInfo<'a, I> {
+    /// The render pass the command buffer will execute within. Only set when the command buffer
+    /// is begun with `VK_COMMAND_BUFFER_USAGE_RENDER_PASS_CONTINUE_BIT`.
+    pub fn render_pass(&self) -> Option<&'a I::RenderPass> { unsafe { handle::cast_opt(self.0.renderPass) } }
+    pub fn subpass(&self) -> u32 { self.0.subpass }
+    /// The framebuffer may be left unspecified even when executing within a render pass.
+    pub fn framebuffer(&self) -> Option<&'a I::Framebuffer> { unsafe { handle::cast_opt(self.0.framebuffer) } }
+    pub fn occlusion_query_enable(&self) -> bool { self.0.occlusionQueryEnable == sys::VK_TRUE }
+    pub fn query_flags(&self) -> QueryControlFlags { QueryControlFlags::from_bits_truncate(self.0.queryFlags) }
+    pub fn pipeline_statistics(&self) -> QueryPipelineStatisticFlags {
+        QueryPipelineStatisticFlags::from_bits_truncate(self.0.pipelineStatistics)
+    }
+}
+
+impl<'a, I: Impl> RenderPassBeginInfo<'a, I> {
+    pub fn render_pass(&self) -> &'a I::RenderPass { unsafe { handle::cast(self.0.renderPass) } }
+    pub fn framebuffer(&self) -> &'a I::Framebuffer { unsafe { handle::cast(self.0.framebuffer) } }
+    pub fn render_area(&self) -> Rect2D { self.0.renderArea.into() }
+    /// Indexed by attachment. Only entries for attachments that are cleared are meaningful.
+    pub fn clear_values(&self) -> impl ExactSizeIterator<Item = ClearValue> {
+        let slice = unsafe { raw_slice(self.0.pClearValues, self.0.clearValueCount) };
+        slice.iter().map(|x| unsafe { ClearValue::from_raw(x) })
+    }
+}
+
+impl<'a, I: Impl> MemoryBarrier<'a, I> {
+    pub fn src_access_mask(&self) -> AccessFlags { AccessFlags::from_bits_truncate(self.0.srcAccessMask) }
+    pub fn dst_access_mask(&self) -> AccessFlags { AccessFlags::from_bits_truncate(self.0.dstAccessMask) }
+}
+
+impl<'a, I: Impl> BufferMemoryBarrier<'a, I> {
+    pub fn src_access_mask(&self) -> AccessFlags { AccessFlags::from_bits_truncate(self.0.srcAccessMask) }
+    pub fn dst_access_mask(&self) -> AccessFlags { AccessFlags::from_bits_truncate(self.0.dstAccessMask) }
+    pub fn src_queue_family_index(&self) -> u32 { self.0.srcQueueFamilyIndex }
+    pub fn dst_queue_family_index(&self) -> u32 { self.0.dstQueueFamilyIndex }
+    pub fn buffer(&self) -> &'a I::Buffer { unsafe { handle::cast(self.0.buffer) } }
+    pub fn offset(&self) -> u64 { self.0.offset }
+    /// May be `WHOLE_SIZE`, meaning the rest of the buffer after `offset`.
+    pub fn size(&self) -> u64 { self.0.size }
+}
+
+impl<'a, I: Impl> ImageMemoryBarrier<'a, I> {
+    pub fn src_access_mask(&self) -> AccessFlags { AccessFlags::from_bits_truncate(self.0.srcAccessMask) }
+    pub fn dst_access_mask(&self) -> AccessFlags { AccessFlags::from_bits_truncate(self.0.dstAccessMask) }
+    pub fn old_layout(&self) -> ImageLayout { self.0.oldLayout.into() }
+    pub fn new_layout(&self) -> ImageLayout { self.0.newLayout.into() }
+    pub fn src_queue_family_index(&self) -> u32 { self.0.srcQueueFamilyIndex }
+    pub fn dst_queue_family_index(&self) -> u32 { self.0.dstQueueFamilyIndex }
+    pub fn image(&self) -> &'a I::Image { unsafe { handle::cast(self.0.image) } }
+    pub fn subresource_range(&self) -> ImageSubresourceRange { self.0.subresourceRange.into() }
+}
+
+impl<'a, I: Impl> RenderPassCreateInfo<'a, I> {
+    pub fn attachments(&self) -> impl ExactSizeIterator<Item = AttachmentDescription<'a, I>> {
+        let slice = unsafe { raw_slice(self.0.pAttachments, self.0.attachmentCount) };
+        slice.iter().map(|x| AttachmentDescription(x, PhantomData))
+    }
+    pub fn subpasses(&self) -> impl ExactSizeIterator<Item = SubpassDescription<'a, I>> {
+        let slice = unsafe { raw_slice(self.0.pSubpasses, self.0.subpassCount) };
+        slice.iter().map(|x| SubpassDescription(x, PhantomData))
+    }
+    pub fn dependencies(&self) -> impl ExactSizeIterator<Item = SubpassDependency> {
+        let slice = unsafe { raw_slice(self.0.pDependencies, self.0.dependencyCount) };
+        slice.iter().map(|&x| x.into())
+    }
+}
+
+impl<'a, I: Impl> AttachmentDescription<'a, I> {
+    pub fn flags(&self) -> AttachmentDescriptionFlags { AttachmentDescriptionFlags::from_bits_truncate(self.0.flags) }
+    pub fn format(&self) -> Format { self.0.format.into() }
+    pub fn samples(&self) -> SampleCountFlags { SampleCountFlags::from_bits_truncate(self.0.samples as u32) }
+    pub fn load_op(&self) -> AttachmentLoadOp { self.0.loadOp.into() }
+    pub fn store_op(&self) -> AttachmentStoreOp { self.0.storeOp.into() }
+    pub fn stencil_load_op(&self) -> AttachmentLoadOp { self.0.stencilLoadOp.into() }
+    pub fn stencil_store_op(&self) -> AttachmentStoreOp { self.0.stencilStoreOp.into() }
+    pub fn initial_layout(&self) -> ImageLayout { self.0.initialLayout.into() }
+    pub fn final_layout(&self) -> ImageLayout { self.0.finalLayout.into() }
+}
+
+impl<'a, I: Impl> SubpassDescription<'a, I> {
+    pub fn flags(&self) -> SubpassDescriptionFlags { SubpassDescriptionFlags::from_bits_truncate(self.0.flags) }
+    pub fn pipeline_bind_point(&self) -> PipelineBindPoint { self.0.pipelineBindPoint.into() }
+    pub fn input_attachments(&self) -> impl ExactSizeIterator<Item = AttachmentReference> {
+        let slice = unsafe { raw_slice(self.0.pInputAttachments, self.0.inputAttachmentCount) };
+        slice.iter().map(|&x| x.into())
+    }
+    pub fn color_attachments(&self) -> impl ExactSizeIterator<Item = AttachmentReference> {
+        let slice = unsafe { raw_slice(self.0.pColorAttachments, self.0.colorAttachmentCount) };
+        slice.iter().map(|&x| x.into())
+    }
+    /// Either empty, or parallel to `color_attachments()`.
+    pub fn resolve_attachments(&self) -> impl ExactSizeIterator<Item = AttachmentReference> {
+        let count = if self.0.pResolveAttachments.is_null() { 0 } else { self.0.colorAttachmentCount };
+        let slice = unsafe { raw_slice(self.0.pResolveAttachments, count) };
+        slice.iter().map(|&x| x.into())
+    }
+    pub fn depth_stencil_attachment(&self) -> Option<AttachmentReference> {
+        if self.0.pDepthStencilAttachment.is_null() {
+            None
+        } else {
+            Some(unsafe { *self.0.pDepthStencilAttachment }.into())
+        }
+    }
+    pub fn preserve_attachments(&self) -> &'a [u32] {
+        unsafe { raw_slice(self.0.pPreserveAttachments, self.0.preserveAttachmentCount) }
+    }
+}
+
+impl<'a, I: Impl> FramebufferCreateInfo<'a, I> {
+    pub fn render_pass(&self) -> &'a I::RenderPass { unsafe { handle::cast(self.0.renderPass) } }
+    pub fn attachments(&self) -> impl ExactSizeIterator<Item = &'a I::ImageView> {
+        let slice = unsafe { raw_slice(self.0.pAttachments, self.0.attachmentCount) };
+        slice.iter().map(|&x| unsafe { handle::cast(x) })
+    }
+    pub fn width(&self) -> u32 { self.0.width }
+    pub fn height(&self) -> u32 { self.0.height }
+    pub fn layers(&self) -> u32 { self.0.layers }
+}
+
+impl<'a, I: Impl> ShaderModuleCreateInfo<'a, I> {
+    pub fn code(&self) -> &'a [u32] {
+        unsafe { raw_slice(self.0.pCode, (self.0.codeSize / 4) as u32) }
+    }
+    /// The code parsed and validated, which it always is before a module is created.
+    pub fn module(&self) -> &'a SpirvModule { self.1 }
+}
+
+/// The pipeline a derivative pipeline is based on.
+pub enum BasePipeline<'a, I: Impl> {
+    Handle(&'a I::Pipeline),
+    /// An earlier element of the same creation batch.
+    Index(usize),
+}
+
+unsafe fn base_pipeline<'a, I: Impl>(flags: sys::VkPipelineCreateFlags, handle: sys::VkPipeline, index: i32) -> Option<BasePipeline<'a, I>> {
+    if !PipelineCreateFlags::from_bits_truncate(flags).contains(VK_PIPELINE_CREATE_DERIVATIVE_BIT) {
+        return None;
+    }
+
+    if let Some(pipeline) = handle::cast_opt(handle) {
+        Some(BasePipeline::Handle(pipeline))
+    } else if index >= 0 {
+        Some(BasePipeline::Index(index as usize))
+    } else {
+        None
+    }
+}
+
+impl<'a, I: Impl> GraphicsPipelineCreateInfo<'a, I> {
+    pub fn flags(&self) -> PipelineCreateFlags { PipelineCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn stages(&self) -> impl ExactSizeIterator<Item = PipelineShaderStageCreateInfo<'a, I>> {
+        let slice = unsafe { raw_slice(self.0.pStages, self.0.stageCount) };
+        slice.iter().map(|x| PipelineShaderStageCreateInfo(x, PhantomData))
+    }
+    pub fn base_pipeline(&self) -> Option<BasePipeline<'a, I>> {
+        unsafe { base_pipeline(self.0.flags, self.0.basePipelineHandle, self.0.basePipelineIndex) }
+    }
+    pub fn vertex_input_state(&self) -> PipelineVertexInputStateCreateInfo<'a, I> {
+        PipelineVertexInputStateCreateInfo(unsafe { &*self.0.pVertexInputState }, PhantomData)
+    }
+    pub fn input_assembly_state(&self) -> PipelineInputAssemblyStateCreateInfo<'a, I> {
+        PipelineInputAssemblyStateCreateInfo(unsafe { &*self.0.pInputAssemblyState }, PhantomData)
+    }
+    /// `None` when rasterization is disabled, in which case the application may omit it.
+    pub fn viewport_state(&self) -> Option<PipelineViewportStateCreateInfo<'a, I>> {
+        if self.rasterization_state().rasterizer_discard_enable() || self.0.pViewportState.is_null() {
+            None
+        } else {
+            Some(PipelineViewportStateCreateInfo(unsafe { &*self.0.pViewportState }, PhantomData))
+        }
+    }
+    pub fn rasterization_state(&self) -> PipelineRasterizationStateCreateInfo<'a, I> {
+        PipelineRasterizationStateCreateInfo(unsafe { &*self.0.pRasterizationState }, PhantomData)
+    }
+    /// `None` when rasterization is disabled, in which case the application may omit it.
+    pub fn multisample_state(&self) -> Option<PipelineMultisampleStateCreateInfo<'a, I>> {
+        if self.rasterization_state().rasterizer_discard_enable() || self.0.pMultisampleState.is_null() {
+            None
+        } else {
+            Some(PipelineMultisampleStateCreateInfo(unsafe { &*self.0.pMultisampleState }, PhantomData))
+        }
+    }
+    /// `None` when rasterization is disabled or the subpass has no depth/stencil attachment, in
+    /// which cases the application may omit it.
+    pub fn depth_stencil_state(&self) -> Option<PipelineDepthStencilStateCreateInfo<'a, I>> {
+        if self.rasterization_state().rasterizer_discard_enable() || self.0.pDepthStencilState.is_null() {
+            None
+        } else {
+            Some(PipelineDepthStencilStateCreateInfo(unsafe { &*self.0.pDepthStencilState }, PhantomData))
+        }
+    }
+    /// `None` when rasterization is disabled or the subpass has no color attachments, in which
+    /// cases the application may omit it.
+    pub fn color_blend_state(&self) -> Option<PipelineColorBlendStateCreateInfo<'a, I>> {
+        if self.rasterization_state().rasterizer_discard_enable() || self.0.pColorBlendState.is_null() {
+            None
+        } else {
+            Some(PipelineColorBlendStateCreateInfo(unsafe { &*self.0.pColorBlendState }, PhantomData))
+        }
+    }
+    pub fn dynamic_states(&self) -> impl ExactSizeIterator<Item = DynamicState> {
+        let slice = if self.0.pDynamicState.is_null() {
+            &[]
+        } else {
+            unsafe { raw_slice((*self.0.pDynamicState).pDynamicStates, (*self.0.pDynamicState).dynamicStateCount) }
+        };
+        slice.iter().map(|&x| x.into())
+    }
+    pub fn layout(&self) -> &'a I::PipelineLayout { unsafe { handle::cast(self.0.layout) } }
+    pub fn render_pass(&self) -> &'a I::RenderPass { unsafe { handle::cast(self.0.renderPass) } }
+    pub fn subpass(&self) -> u32 { self.0.subpass }
+}
+
+impl<'a, I: Impl> PipelineVertexInputStateCreateInfo<'a, I> {
+    pub fn bindings(&self) -> impl ExactSizeIterator<Item = VertexInputBindingDescription> {
+        let slice = unsafe { raw_slice(self.0.pVertexBindingDescriptions, self.0.vertexBindingDescriptionCount) };
+        slice.iter().map(|&x| x.into())
+    }
+    pub fn attributes(&self) -> impl ExactSizeIterator<Item = VertexInputAttributeDescription> {
+        let slice = unsafe { raw_slice(self.0.pVertexAttributeDescriptions, self.0.vertexAttributeDescriptionCount) };
+        slice.iter().map(|&x| x.into())
+    }
+}
+
+impl<'a, I: Impl> PipelineInputAssemblyStateCreateInfo<'a, I> {
+    pub fn topology(&self) -> PrimitiveTopology { self.0.topology.into() }
+    pub fn primitive_restart_enable(&self) -> bool { self.0.primitiveRestartEnable == sys::VK_TRUE }
+}
+
+impl<'a, I: Impl> PipelineViewportStateCreateInfo<'a, I> {
+    /// Empty if the viewports are dynamic state.
+    pub fn viewports(&self) -> impl ExactSizeIterator<Item = Viewport> {
+        let count = if self.0.pViewports.is_null() { 0 } else { self.0.viewportCount };
+        unsafe { raw_slice(self.0.pViewports, count) }.iter().map(|&x| x.into())
+    }
+    /// Empty if the scissors are dynamic state.
+    pub fn scissors(&self) -> impl ExactSizeIterator<Item = Rect2D> {
+        let count = if self.0.pScissors.is_null() { 0 } else { self.0.scissorCount };
+        unsafe { raw_slice(self.0.pScissors, count) }.iter().map(|&x| x.into())
+    }
+    pub fn viewport_count(&self) -> u32 { self.0.viewportCount }
+}
+
+impl<'a, I: Impl> PipelineRasterizationStateCreateInfo<'a, I> {
+    pub fn depth_clamp_enable(&self) -> bool { self.0.depthClampEnable == sys::VK_TRUE }
+    pub fn rasterizer_discard_enable(&self) -> bool { self.0.rasterizerDiscardEnable == sys::VK_TRUE }
+    pub fn polygon_mode(&self) -> PolygonMode { self.0.polygonMode.into() }
+    pub fn cull_mode(&self) -> CullModeFlags { CullModeFlags::from_bits_truncate(self.0.cullMode) }
+    pub fn front_face(&self) -> FrontFace { self.0.frontFace.into() }
+    pub fn depth_bias_enable(&self) -> bool { self.0.depthBiasEnable == sys::VK_TRUE }
+    pub fn depth_bias_constant_factor(&self) -> f32 { self.0.depthBiasConstantFactor }
+    pub fn depth_bias_clamp(&self) -> f32 { self.0.depthBiasClamp }
+    pub fn depth_bias_slope_factor(&self) -> f32 { self.0.depthBiasSlopeFactor }
+    pub fn line_width(&self) -> f32 { self.0.lineWidth }
+}
+
+impl<'a, I: Impl> PipelineMultisampleStateCreateInfo<'a, I> {
+    pub fn rasterization_samples(&self) -> SampleCountFlags { SampleCountFlags::from_bits_truncate(self.0.rasterizationSamples as u32) }
+    /// `None` if sample shading is disabled.
+    pub fn min_sample_shading(&self) -> Option<f32> {
+        if self.0.sampleShadingEnable == sys::VK_TRUE { Some(self.0.minSampleShading) } else { None }
+    }
+    /// One bit per sample, or `None` if every sample is enabled.
+    pub fn sample_mask(&self) -> Option<&'a [u32]> {
+        if self.0.pSampleMask.is_null() {
+            None
+        } else {
+            let words = (self.0.rasterizationSamples as u32 + 31) / 32;
+            Some(unsafe { raw_slice(self.0.pSampleMask, words) })
+        }
+    }
+    pub fn alpha_to_coverage_enable(&self) -> bool { self.0.alphaToCoverageEnable == sys::VK_TRUE }
+    pub fn alpha_to_one_enable(&self) -> bool { self.0.alphaToOneEnable == sys::VK_TRUE }
+}
+
+impl<'a, I: Impl> PipelineDepthStencilStateCreateInfo<'a, I> {
+    /// `None` if the depth test is disabled.
+    pub fn depth_compare_op(&self) -> Option<CompareOp> {
+        if self.0.depthTestEnable == sys::VK_TRUE { Some(self.0.depthCompareOp.into()) } else { None }
+    }
+    pub fn depth_write_enable(&self) -> bool { self.0.depthWriteEnable == sys::VK_TRUE }
+    /// `None` if the depth bounds test is disabled.
+    pub fn depth_bounds(&self) -> Option<(f32, f32)> {
+        if self.0.depthBoundsTestEnable == sys::VK_TRUE { Some((self.0.minDepthBounds, self.0.maxDepthBounds)) } else { None }
+    }
+    pub fn stencil_test_enable(&self) -> bool { self.0.stencilTestEnable == sys::VK_TRUE }
+    pub fn front(&self) -> StencilOpState { self.0.front.into() }
+    pub fn back(&self) -> StencilOpState { self.0.back.into() }
+}
+
+impl<'a, I: Impl> PipelineColorBlendStateCreateInfo<'a, I> {
+    /// `None` if logic ops are disabled.
+    pub fn logic_op(&self) -> Option<LogicOp> {
+        if self.0.logicOpEnable == sys::VK_TRUE { Some(self.0.logicOp.into()) } else { None }
+    }
+    pub fn attachments(&self) -> impl ExactSizeIterator<Item = PipelineColorBlendAttachmentState> {
+        let slice = unsafe { raw_slice(self.0.pAttachments, self.0.attachmentCount) };
+        slice.iter().map(|&x| x.into())
+    }
+    pub fn blend_constants(&self) -> [f32; 4] { self.0.blendConstants }
+}
+
+impl<'a, I: Impl> ComputePipelineCreateInfo<'a, I> {
+    pub fn flags(&self) -> PipelineCreateFlags { PipelineCreateFlags::from_bits_truncate(self.0.flags) }
+    pub fn stage(&self) -> PipelineShaderStageCreateInfo<'a, I> { PipelineShaderStageCreateInfo(&self.0.stage, PhantomData) }
+    pub fn layout(&self) -> &'a I::PipelineLayout { unsafe { handle::cast(self.0.layout) } }
+    pub fn base_pipeline(&self) -> Option<BasePipeline<'a, I>> {
+        unsafe { base_pipeline(self.0.flags, self.0.basePipelineHandle, self.0.basePipelineIndex) }
+    }
+}
+
+impl<'a, I: Impl> PipelineShaderStageCreateInfo<'a, I> {
+    pub fn stage(&self) -> ShaderStageFlags { ShaderStageFlags::from_bits_truncate(self.0.stage as u32) }
+    pub fn module(&self) -> &'a I::ShaderModule { unsafe { handle::cast(self.0.module) } }
+    pub fn entry_point(&self) -> &'a CStr { unsafe { CStr::from_ptr(self.0.pName) } }
+    pub fn specialization_info(&self) -> Option<SpecializationInfo<'a, I>> {
+        unsafe { self.0.pSpecializationInfo.as_ref() }.map(|x| SpecializationInfo(x, PhantomData))
+    }
+}
+
+impl<'a, I: Impl> SpecializationInfo<'a, I> {
+    pub fn map_entries(&self) -> impl ExactSizeIterator<Item = SpecializationMapEntry> {
+        let slice = unsafe { raw_slice(self.0.pMapEntries, self.0.mapEntryCount) };
+        slice.iter().map(|&x| x.into())
+    }
+    pub fn data(&self) -> &'a [u8] {
+        if self.0.dataSize == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.0.pData as *const u8, self.0.dataSize) }
+        }
+    }
+}
+
+impl<'a, I: Impl> PipelineCacheCreateInfo<'a, I> {
+    /// The header of cache data for the device.
+    pub fn header(&self) -> CacheHeader { self.1 }
+    /// Empty unless it starts with the device's header, which `vkCreatePipelineCache` checks.
+    pub fn initial_data(&self) -> &'a [u8] {
+        if self.0.initialDataSize == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.0.pInitialData as *const u8, self.0.initialDataSize) }
+        }
+    }
+}
+
+impl<'a, I: Impl> QueryPoolCreateInfo<'a, I> {
+    pub fn query_type(&self) -> QueryType { self.0.queryType.into() }
+    pub fn query_count(&self) -> u32 { self.0.queryCount }
+    pub fn pipeline_statistics(&self) -> QueryPipelineStatisticFlags {
+        QueryPipelineStatisticFlags::from_bits_truncate(self.0.pipelineStatistics)
+    }
+}
+
+impl<'a, I: Impl> ImageSubresource<'a, I> {
+    pub fn aspect_mask(&self) -> ImageAspectFlags { ImageAspectFlags::from_bits_truncate(self.0.aspectMask) }
+    pub fn mip_level(&self) -> u32 { self.0.mipLevel }
+    pub fn array_layer(&self) -> u32 { self.0.arrayLayer }
+}
+
+/// Builds a slice from a pointer and count taken from a Vulkan structure, which may leave the
+/// pointer dangling or null when the count is zero.
+pub(crate) unsafe fn raw_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if count == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, count as usize)
+    }
+}
+
 macro_rules! vulkan_map_type {
     (VkBool32) => { bool };
     (VkFormatFeatureFlags) => {FormatFeatureFlags};
@@ -56,6 +654,35 @@ macro_rules! vulkan_map_type {
     (VkImageAspectFlags) => {ImageAspectFlags};
     (VkExtent3D) => {Extent3D};
     (VkSparseImageFormatFlags) => {SparseImageFormatFlags};
+    (VkComponentSwizzle) => {ComponentSwizzle};
+    (VkImageLayout) => {ImageLayout};
+    (VkDescriptorType) => {DescriptorType};
+    (VkShaderStageFlags) => {ShaderStageFlags};
+    (f32) => { f32 };
+    (i32) => { i32 };
+    (VkExtent2D) => {Extent2D};
+    (VkOffset2D) => {Offset2D};
+    (VkOffset3D) => {Offset3D};
+    (VkRect2D) => {Rect2D};
+    (VkImageSubresourceLayers) => {ImageSubresourceLayers};
+    (VkPipelineStageFlags) => {PipelineStageFlags};
+    (VkAccessFlags) => {AccessFlags};
+    (VkDependencyFlags) => {DependencyFlags};
+    (VkSampleCountFlags) => {SampleCountFlags};
+    (VkQueueFlags) => {QueueFlags};
+    (VkMemoryPropertyFlags) => {MemoryPropertyFlags};
+    (VkMemoryHeapFlags) => {MemoryHeapFlags};
+    (usize) => { usize };
+    (u32x2) => { [u32; 2] };
+    (u32x3) => { [u32; 3] };
+    (f32x2) => { [f32; 2] };
+    (VkFormat) => {Format};
+    (VkVertexInputRate) => {VertexInputRate};
+    (VkStencilOp) => {StencilOp};
+    (VkCompareOp) => {CompareOp};
+    (VkBlendFactor) => {BlendFactor};
+    (VkBlendOp) => {BlendOp};
+    (VkColorComponentFlags) => {ColorComponentFlags};
 }
 
 macro_rules! vulkan_map_value_with_type {
@@ -75,15 +702,74 @@ macro_rules! vulkan_map_value_with_type {
     (VkExtent3D <= $x:expr) => { $x.into() };
     (VkSparseImageFormatFlags => $x:expr) => { SparseImageFormatFlags::from_bits_truncate($x) };
     (VkSparseImageFormatFlags <= $x:expr) => { $x.bits() };
+    (VkComponentSwizzle => $x:expr) => { $x.into() };
+    (VkComponentSwizzle <= $x:expr) => { $x.into() };
+    (VkImageLayout => $x:expr) => { $x.into() };
+    (VkImageLayout <= $x:expr) => { $x.into() };
+    (VkDescriptorType => $x:expr) => { $x.into() };
+    (VkDescriptorType <= $x:expr) => { $x.into() };
+    (VkShaderStageFlags => $x:expr) => { ShaderStageFlags::from_bits_truncate($x) };
+    (VkShaderStageFlags <= $x:expr) => { $x.bits() };
+    (f32 => $x:expr) => { $x };
+    (f32 <= $x:expr) => { $x };
+    (i32 => $x:expr) => { $x };
+    (i32 <= $x:expr) => { $x };
+    (VkExtent2D => $x:expr) => { $x.into() };
+    (VkExtent2D <= $x:expr) => { $x.into() };
+    (VkOffset2D => $x:expr) => { $x.into() };
+    (VkOffset2D <= $x:expr) => { $x.into() };
+    (VkOffset3D => $x:expr) => { $x.into() };
+    (VkOffset3D <= $x:expr) => { $x.into() };
+    (VkRect2D => $x:expr) => { $x.into() };
+    (VkRect2D <= $x:expr) => { $x.into() };
+    (VkImageSubresourceLayers => $x:expr) => { $x.into() };
+    (VkImageSubresourceLayers <= $x:expr) => { $x.into() };
+    (VkPipelineStageFlags => $x:expr) => { PipelineStageFlags::from_bits_truncate($x) };
+    (VkPipelineStageFlags <= $x:expr) => { $x.bits() };
+    (VkAccessFlags => $x:expr) => { AccessFlags::from_bits_truncate($x) };
+    (VkAccessFlags <= $x:expr) => { $x.bits() };
+    (VkDependencyFlags => $x:expr) => { DependencyFlags::from_bits_truncate($x) };
+    (VkDependencyFlags <= $x:expr) => { $x.bits() };
+    (VkSampleCountFlags => $x:expr) => { SampleCountFlags::from_bits_truncate($x) };
+    (VkSampleCountFlags <= $x:expr) => { $x.bits() };
+    (VkQueueFlags => $x:expr) => { QueueFlags::from_bits_truncate($x) };
+    (VkQueueFlags <= $x:expr) => { $x.bits() };
+    (VkMemoryPropertyFlags => $x:expr) => { MemoryPropertyFlags::from_bits_truncate($x) };
+    (VkMemoryPropertyFlags <= $x:expr) => { $x.bits() };
+    (VkMemoryHeapFlags => $x:expr) => { MemoryHeapFlags::from_bits_truncate($x) };
+    (VkMemoryHeapFlags <= $x:expr) => { $x.bits() };
+    (usize => $x:expr) => { $x };
+    (usize <= $x:expr) => { $x };
+    (u32x2 => $x:expr) => { $x };
+    (u32x2 <= $x:expr) => { $x };
+    (u32x3 => $x:expr) => { $x };
+    (u32x3 <= $x:expr) => { $x };
+    (f32x2 => $x:expr) => { $x };
+    (f32x2 <= $x:expr) => { $x };
+    (VkFormat => $x:expr) => { $x.into() };
+    (VkFormat <= $x:expr) => { $x.into() };
+    (VkVertexInputRate => $x:expr) => { $x.into() };
+    (VkVertexInputRate <= $x:expr) => { $x.into() };
+    (VkStencilOp => $x:expr) => { $x.into() };
+    (VkStencilOp <= $x:expr) => { $x.into() };
+    (VkCompareOp => $x:expr) => { $x.into() };
+    (VkCompareOp <= $x:expr) => { $x.into() };
+    (VkBlendFactor => $x:expr) => { $x.into() };
+    (VkBlendFactor <= $x:expr) => { $x.into() };
+    (VkBlendOp => $x:expr) => { $x.into() };
+    (VkBlendOp <= $x:expr) => { $x.into() };
+    (VkColorComponentFlags => $x:expr) => { ColorComponentFlags::from_bits_truncate($x) };
+    (VkColorComponentFlags <= $x:expr) => { $x.bits() };
 }
 
 macro_rules! vulkan_struct_correspondence {
     (struct $name:ident => $vk_name:ident {
         $($member_name:ident => $vk_member_name:ident : $vk_type:ident ,)*
     }) => {
+        #[derive(Clone, Copy, Debug)]
         pub struct $name {
             $(
-                $member_name: vulkan_map_type!($vk_type),
+                pub $member_name: vulkan_map_type!($vk_type),
             )*
         }
 
@@ -106,6 +792,12 @@ macro_rules! vulkan_struct_correspondence {
                 }
             }
         }
+
+        impl Default for $name {
+            fn default() -> $name {
+                unsafe { mem::zeroed::<sys::$vk_name>() }.into()
+            }
+        }
     }
 }
 
@@ -114,6 +806,7 @@ macro_rules! vulkan_enum_correspondence {
         $($case:ident => $vk_case_name:ident,)*
     }) => {
         #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
         pub enum $name {
             $($case,)*
             Other(i32),
@@ -208,6 +901,284 @@ vulkan_struct_correspondence! {
     }
 }
 
+impl PhysicalDeviceFeatures {
+    /// Every feature enabled.
+    pub fn all() -> PhysicalDeviceFeatures {
+        let mut raw: sys::VkPhysicalDeviceFeatures = unsafe { mem::zeroed() };
+        for feature in features_as_slice_mut(&mut raw) {
+            *feature = sys::VK_TRUE;
+        }
+        raw.into()
+    }
+
+    /// Whether every feature enabled in `other` is also enabled in `self`.
+    pub fn contains(&self, other: &PhysicalDeviceFeatures) -> bool {
+        let mut raw: sys::VkPhysicalDeviceFeatures = (*self).into();
+        let mut other: sys::VkPhysicalDeviceFeatures = (*other).into();
+        features_as_slice_mut(&mut raw).iter().zip(features_as_slice_mut(&mut other).iter())
+            .all(|(&supported, &requested)| supported == sys::VK_TRUE || requested != sys::VK_TRUE)
+    }
+}
+
+// VkPhysicalDeviceFeatures is nothing but VkBool32s.
+fn features_as_slice_mut(raw: &mut sys::VkPhysicalDeviceFeatures) -> &mut [sys::VkBool32] {
+    unsafe {
+        slice::from_raw_parts_mut(
+            raw as *mut _ as *mut sys::VkBool32,
+            mem::size_of::<sys::VkPhysicalDeviceFeatures>() / mem::size_of::<sys::VkBool32>(),
+        )
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum PhysicalDeviceType => VkPhysicalDeviceType {
+        Unknown => VK_PHYSICAL_DEVICE_TYPE_OTHER,
+        IntegratedGpu => VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU,
+        DiscreteGpu => VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU,
+        VirtualGpu => VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU,
+        Cpu => VK_PHYSICAL_DEVICE_TYPE_CPU,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct PhysicalDeviceLimits => VkPhysicalDeviceLimits {
+        max_image_dimension_1d => maxImageDimension1D: u32,
+        max_image_dimension_2d => maxImageDimension2D: u32,
+        max_image_dimension_3d => maxImageDimension3D: u32,
+        max_image_dimension_cube => maxImageDimensionCube: u32,
+        max_image_array_layers => maxImageArrayLayers: u32,
+        max_texel_buffer_elements => maxTexelBufferElements: u32,
+        max_uniform_buffer_range => maxUniformBufferRange: u32,
+        max_storage_buffer_range => maxStorageBufferRange: u32,
+        max_push_constants_size => maxPushConstantsSize: u32,
+        max_memory_allocation_count => maxMemoryAllocationCount: u32,
+        max_sampler_allocation_count => maxSamplerAllocationCount: u32,
+        buffer_image_granularity => bufferImageGranularity: VkDeviceSize,
+        sparse_address_space_size => sparseAddressSpaceSize: VkDeviceSize,
+        max_bound_descriptor_sets => maxBoundDescriptorSets: u32,
+        max_per_stage_descriptor_samplers => maxPerStageDescriptorSamplers: u32,
+        max_per_stage_descriptor_uniform_buffers => maxPerStageDescriptorUniformBuffers: u32,
+        max_per_stage_descriptor_storage_buffers => maxPerStageDescriptorStorageBuffers: u32,
+        max_per_stage_descriptor_sampled_images => maxPerStageDescriptorSampledImages: u32,
+        max_per_stage_descriptor_storage_images => maxPerStageDescriptorStorageImages: u32,
+        max_per_stage_descriptor_input_attachments => maxPerStageDescriptorInputAttachments: u32,
+        max_per_stage_resources => maxPerStageResources: u32,
+        max_descriptor_set_samplers => maxDescriptorSetSamplers: u32,
+        max_descriptor_set_uniform_buffers => maxDescriptorSetUniformBuffers: u32,
+        max_descriptor_set_uniform_buffers_dynamic => maxDescriptorSetUniformBuffersDynamic: u32,
+        max_descriptor_set_storage_buffers => maxDescriptorSetStorageBuffers: u32,
+        max_descriptor_set_storage_buffers_dynamic => maxDescriptorSetStorageBuffersDynamic: u32,
+        max_descriptor_set_sampled_images => maxDescriptorSetSampledImages: u32,
+        max_descriptor_set_storage_images => maxDescriptorSetStorageImages: u32,
+        max_descriptor_set_input_attachments => maxDescriptorSetInputAttachments: u32,
+        max_vertex_input_attributes => maxVertexInputAttributes: u32,
+        max_vertex_input_bindings => maxVertexInputBindings: u32,
+        max_vertex_input_attribute_offset => maxVertexInputAttributeOffset: u32,
+        max_vertex_input_binding_stride => maxVertexInputBindingStride: u32,
+        max_vertex_output_components => maxVertexOutputComponents: u32,
+        max_tessellation_generation_level => maxTessellationGenerationLevel: u32,
+        max_tessellation_patch_size => maxTessellationPatchSize: u32,
+        max_tessellation_control_per_vertex_input_components => maxTessellationControlPerVertexInputComponents: u32,
+        max_tessellation_control_per_vertex_output_components => maxTessellationControlPerVertexOutputComponents: u32,
+        max_tessellation_control_per_patch_output_components => maxTessellationControlPerPatchOutputComponents: u32,
+        max_tessellation_control_total_output_components => maxTessellationControlTotalOutputComponents: u32,
+        max_tessellation_evaluation_input_components => maxTessellationEvaluationInputComponents: u32,
+        max_tessellation_evaluation_output_components => maxTessellationEvaluationOutputComponents: u32,
+        max_geometry_shader_invocations => maxGeometryShaderInvocations: u32,
+        max_geometry_input_components => maxGeometryInputComponents: u32,
+        max_geometry_output_components => maxGeometryOutputComponents: u32,
+        max_geometry_output_vertices => maxGeometryOutputVertices: u32,
+        max_geometry_total_output_components => maxGeometryTotalOutputComponents: u32,
+        max_fragment_input_components => maxFragmentInputComponents: u32,
+        max_fragment_output_attachments => maxFragmentOutputAttachments: u32,
+        max_fragment_dual_src_attachments => maxFragmentDualSrcAttachments: u32,
+        max_fragment_combined_output_resources => maxFragmentCombinedOutputResources: u32,
+        max_compute_shared_memory_size => maxComputeSharedMemorySize: u32,
+        max_compute_work_group_count => maxComputeWorkGroupCount: u32x3,
+        max_compute_work_group_invocations => maxComputeWorkGroupInvocations: u32,
+        max_compute_work_group_size => maxComputeWorkGroupSize: u32x3,
+        sub_pixel_precision_bits => subPixelPrecisionBits: u32,
+        sub_texel_precision_bits => subTexelPrecisionBits: u32,
+        mipmap_precision_bits => mipmapPrecisionBits: u32,
+        max_draw_indexed_index_value => maxDrawIndexedIndexValue: u32,
+        max_draw_indirect_count => maxDrawIndirectCount: u32,
+        max_sampler_lod_bias => maxSamplerLodBias: f32,
+        max_sampler_anisotropy => maxSamplerAnisotropy: f32,
+        max_viewports => maxViewports: u32,
+        max_viewport_dimensions => maxViewportDimensions: u32x2,
+        viewport_bounds_range => viewportBoundsRange: f32x2,
+        viewport_sub_pixel_bits => viewportSubPixelBits: u32,
+        min_memory_map_alignment => minMemoryMapAlignment: usize,
+        min_texel_buffer_offset_alignment => minTexelBufferOffsetAlignment: VkDeviceSize,
+        min_uniform_buffer_offset_alignment => minUniformBufferOffsetAlignment: VkDeviceSize,
+        min_storage_buffer_offset_alignment => minStorageBufferOffsetAlignment: VkDeviceSize,
+        min_texel_offset => minTexelOffset: i32,
+        max_texel_offset => maxTexelOffset: u32,
+        min_texel_gather_offset => minTexelGatherOffset: i32,
+        max_texel_gather_offset => maxTexelGatherOffset: u32,
+        min_interpolation_offset => minInterpolationOffset: f32,
+        max_interpolation_offset => maxInterpolationOffset: f32,
+        sub_pixel_interpolation_offset_bits => subPixelInterpolationOffsetBits: u32,
+        max_framebuffer_width => maxFramebufferWidth: u32,
+        max_framebuffer_height => maxFramebufferHeight: u32,
+        max_framebuffer_layers => maxFramebufferLayers: u32,
+        framebuffer_color_sample_counts => framebufferColorSampleCounts: VkSampleCountFlags,
+        framebuffer_depth_sample_counts => framebufferDepthSampleCounts: VkSampleCountFlags,
+        framebuffer_stencil_sample_counts => framebufferStencilSampleCounts: VkSampleCountFlags,
+        framebuffer_no_attachments_sample_counts => framebufferNoAttachmentsSampleCounts: VkSampleCountFlags,
+        max_color_attachments => maxColorAttachments: u32,
+        sampled_image_color_sample_counts => sampledImageColorSampleCounts: VkSampleCountFlags,
+        sampled_image_integer_sample_counts => sampledImageIntegerSampleCounts: VkSampleCountFlags,
+        sampled_image_depth_sample_counts => sampledImageDepthSampleCounts: VkSampleCountFlags,
+        sampled_image_stencil_sample_counts => sampledImageStencilSampleCounts: VkSampleCountFlags,
+        storage_image_sample_counts => storageImageSampleCounts: VkSampleCountFlags,
+        max_sample_mask_words => maxSampleMaskWords: u32,
+        timestamp_compute_and_graphics => timestampComputeAndGraphics: VkBool32,
+        timestamp_period => timestampPeriod: f32,
+        max_clip_distances => maxClipDistances: u32,
+        max_cull_distances => maxCullDistances: u32,
+        max_combined_clip_and_cull_distances => maxCombinedClipAndCullDistances: u32,
+        discrete_queue_priorities => discreteQueuePriorities: u32,
+        point_size_range => pointSizeRange: f32x2,
+        line_width_range => lineWidthRange: f32x2,
+        point_size_granularity => pointSizeGranularity: f32,
+        line_width_granularity => lineWidthGranularity: f32,
+        strict_lines => strictLines: VkBool32,
+        standard_sample_locations => standardSampleLocations: VkBool32,
+        optimal_buffer_copy_offset_alignment => optimalBufferCopyOffsetAlignment: VkDeviceSize,
+        optimal_buffer_copy_row_pitch_alignment => optimalBufferCopyRowPitchAlignment: VkDeviceSize,
+        non_coherent_atom_size => nonCoherentAtomSize: VkDeviceSize,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct PhysicalDeviceSparseProperties => VkPhysicalDeviceSparseProperties {
+        residency_standard_2d_block_shape => residencyStandard2DBlockShape: VkBool32,
+        residency_standard_2d_multisample_block_shape => residencyStandard2DMultisampleBlockShape: VkBool32,
+        residency_standard_3d_block_shape => residencyStandard3DBlockShape: VkBool32,
+        residency_aligned_mip_size => residencyAlignedMipSize: VkBool32,
+        residency_non_resident_strict => residencyNonResidentStrict: VkBool32,
+    }
+}
+
+/// Version 1.0.0 of the API, as encoded by `VK_MAKE_VERSION`.
+pub const API_VERSION_1_0: u32 = 1 << 22;
+
+#[derive(Clone, Debug)]
+pub struct PhysicalDeviceProperties {
+    pub api_version: u32,
+    pub driver_version: u32,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub device_type: PhysicalDeviceType,
+    /// Truncated to fit `VK_MAX_PHYSICAL_DEVICE_NAME_SIZE` when reported.
+    pub device_name: String,
+    pub pipeline_cache_uuid: [u8; 16],
+    pub limits: PhysicalDeviceLimits,
+    pub sparse_properties: PhysicalDeviceSparseProperties,
+}
+
+impl Default for PhysicalDeviceProperties {
+    fn default() -> PhysicalDeviceProperties {
+        PhysicalDeviceProperties {
+            api_version: API_VERSION_1_0,
+            driver_version: 0,
+            vendor_id: 0,
+            device_id: 0,
+            device_type: PhysicalDeviceType::Unknown,
+            device_name: String::new(),
+            pipeline_cache_uuid: [0; 16],
+            limits: PhysicalDeviceLimits::default(),
+            sparse_properties: PhysicalDeviceSparseProperties::default(),
+        }
+    }
+}
+
+impl<'a> From<&'a PhysicalDeviceProperties> for sys::VkPhysicalDeviceProperties {
+    fn from(properties: &'a PhysicalDeviceProperties) -> sys::VkPhysicalDeviceProperties {
+        let mut raw: sys::VkPhysicalDeviceProperties = unsafe { mem::zeroed() };
+        raw.apiVersion = properties.api_version;
+        raw.driverVersion = properties.driver_version;
+        raw.vendorID = properties.vendor_id;
+        raw.deviceID = properties.device_id;
+        raw.deviceType = properties.device_type.into();
+        // Leave room for the terminating NUL, which the zeroed array already provides.
+        let name = properties.device_name.as_bytes();
+        for (dst, &src) in raw.deviceName.iter_mut().zip(&name[..cmp::min(name.len(), sys::VK_MAX_PHYSICAL_DEVICE_NAME_SIZE as usize - 1)]) {
+            *dst = src as c_char;
+        }
+        raw.pipelineCacheUUID = properties.pipeline_cache_uuid;
+        raw.limits = properties.limits.into();
+        raw.sparseProperties = properties.sparse_properties.into();
+        raw
+    }
+}
+
+/// An extension the backend implements, and the revision of its specification it follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtensionProperties {
+    /// Truncated to fit `VK_MAX_EXTENSION_NAME_SIZE` when reported.
+    pub name: &'static str,
+    pub spec_version: u32,
+}
+
+impl<'a> From<&'a ExtensionProperties> for sys::VkExtensionProperties {
+    fn from(properties: &'a ExtensionProperties) -> sys::VkExtensionProperties {
+        let mut raw: sys::VkExtensionProperties = unsafe { mem::zeroed() };
+        let name = properties.name.as_bytes();
+        for (dst, &src) in raw.extensionName.iter_mut().zip(&name[..cmp::min(name.len(), sys::VK_MAX_EXTENSION_NAME_SIZE as usize - 1)]) {
+            *dst = src as c_char;
+        }
+        raw.specVersion = properties.spec_version;
+        raw
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct QueueFamilyProperties => VkQueueFamilyProperties {
+        queue_flags => queueFlags: VkQueueFlags,
+        queue_count => queueCount: u32,
+        timestamp_valid_bits => timestampValidBits: u32,
+        min_image_transfer_granularity => minImageTransferGranularity: VkExtent3D,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct MemoryType => VkMemoryType {
+        property_flags => propertyFlags: VkMemoryPropertyFlags,
+        heap_index => heapIndex: u32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct MemoryHeap => VkMemoryHeap {
+        size => size: VkDeviceSize,
+        flags => flags: VkMemoryHeapFlags,
+    }
+}
+
+/// Memory types past `VK_MAX_MEMORY_TYPES` and heaps past `VK_MAX_MEMORY_HEAPS` are not reported.
+#[derive(Clone, Debug, Default)]
+pub struct PhysicalDeviceMemoryProperties {
+    pub memory_types: Vec<MemoryType>,
+    pub memory_heaps: Vec<MemoryHeap>,
+}
+
+impl<'a> From<&'a PhysicalDeviceMemoryProperties> for sys::VkPhysicalDeviceMemoryProperties {
+    fn from(properties: &'a PhysicalDeviceMemoryProperties) -> sys::VkPhysicalDeviceMemoryProperties {
+        let mut raw: sys::VkPhysicalDeviceMemoryProperties = unsafe { mem::zeroed() };
+        raw.memoryTypeCount = cmp::min(properties.memory_types.len(), sys::VK_MAX_MEMORY_TYPES as usize) as u32;
+        for (dst, &src) in raw.memoryTypes.iter_mut().zip(&properties.memory_types) {
+            *dst = src.into();
+        }
+        raw.memoryHeapCount = cmp::min(properties.memory_heaps.len(), sys::VK_MAX_MEMORY_HEAPS as usize) as u32;
+        for (dst, &src) in raw.memoryHeaps.iter_mut().zip(&properties.memory_heaps) {
+            *dst = src.into();
+        }
+        raw
+    }
+}
+
 vulkan_enum_correspondence! {
     enum Format => VkFormat {
         Unknown => VK_FORMAT_UNDEFINED,
@@ -398,6 +1369,263 @@ vulkan_enum_correspondence! {
     }
 }
 
+impl Format {
+    /// The size in bytes of one texel, or of one compressed block for block-compressed formats.
+    ///
+    /// Combined depth/stencil formats report the size of their usual packed storage, which the spec
+    /// leaves up to the implementation.
+    pub fn block_size(&self) -> Option<u32> {
+        match *self {
+            Format::R4G4UnormPack8 |
+            Format::R8Unorm |
+            Format::R8Snorm |
+            Format::R8Uscaled |
+            Format::R8Sscaled |
+            Format::R8Uint |
+            Format::R8Sint |
+            Format::R8Srgb |
+            Format::S8Uint => Some(1),
+            Format::R32G32B32Uint |
+            Format::R32G32B32Sint |
+            Format::R32G32B32Sfloat => Some(12),
+            Format::R32G32B32A32Uint |
+            Format::R32G32B32A32Sint |
+            Format::R32G32B32A32Sfloat |
+            Format::R64G64Uint |
+            Format::R64G64Sint |
+            Format::R64G64Sfloat |
+            Format::BC2UnormBlock |
+            Format::BC2SrgbBlock |
+            Format::BC3UnormBlock |
+            Format::BC3SrgbBlock |
+            Format::BC5UnormBlock |
+            Format::BC5SnormBlock |
+            Format::BC6HUfloatBlock |
+            Format::BC6HSfloatBlock |
+            Format::BC7UnormBlock |
+            Format::BC7SrgbBlock |
+            Format::ETC2_R8G8B8A8UnormBlock |
+            Format::ETC2_R8G8B8A8SrgbBlock |
+            Format::EAC_R11G11UnormBlock |
+            Format::EAC_R11G11SnormBlock |
+            Format::ASTC_4x4UnormBlock |
+            Format::ASTC_4x4SrgbBlock |
+            Format::ASTC_5x4UnormBlock |
+            Format::ASTC_5x4SrgbBlock |
+            Format::ASTC_5x5UnormBlock |
+            Format::ASTC_5x5SrgbBlock |
+            Format::ASTC_6x5UnormBlock |
+            Format::ASTC_6x5SrgbBlock |
+            Format::ASTC_6x6UnormBlock |
+            Format::ASTC_6x6SrgbBlock |
+            Format::ASTC_8x5UnormBlock |
+            Format::ASTC_8x5SrgbBlock |
+            Format::ASTC_8x6UnormBlock |
+            Format::ASTC_8x6SrgbBlock |
+            Format::ASTC_8x8UnormBlock |
+            Format::ASTC_8x8SrgbBlock |
+            Format::ASTC_10x5UnormBlock |
+            Format::ASTC_10x5SrgbBlock |
+            Format::ASTC_10x6UnormBlock |
+            Format::ASTC_10x6SrgbBlock |
+            Format::ASTC_10x8UnormBlock |
+            Format::ASTC_10x8SrgbBlock |
+            Format::ASTC_10x10UnormBlock |
+            Format::ASTC_10x10SrgbBlock |
+            Format::ASTC_12x10UnormBlock |
+            Format::ASTC_12x10SrgbBlock |
+            Format::ASTC_12x12UnormBlock |
+            Format::ASTC_12x12SrgbBlock => Some(16),
+            Format::R4G4B4A4UnormPack16 |
+            Format::B4G4R4A4UnormPack16 |
+            Format::R5G6B5UnormPack16 |
+            Format::B5G6R5UnormPack16 |
+            Format::R5G5B5A1UnormPack16 |
+            Format::B5G5R5A1UnormPack16 |
+            Format::A1R5G5B5UnormPack16 |
+            Format::R8G8Unorm |
+            Format::R8G8Snorm |
+            Format::R8G8Uscaled |
+            Format::R8G8Sscaled |
+            Format::R8G8Uint |
+            Format::R8G8Sint |
+            Format::R8G8Srgb |
+            Format::R16Unorm |
+            Format::R16Snorm |
+            Format::R16Uscaled |
+            Format::R16Sscaled |
+            Format::R16Uint |
+            Format::R16Sint |
+            Format::R16Sfloat |
+            Format::D16Unorm => Some(2),
+            Format::R64G64B64Uint |
+            Format::R64G64B64Sint |
+            Format::R64G64B64Sfloat => Some(24),
+            Format::R8G8B8Unorm |
+            Format::R8G8B8Snorm |
+            Format::R8G8B8Uscaled |
+            Format::R8G8B8Sscaled |
+            Format::R8G8B8Uint |
+            Format::R8G8B8Sint |
+            Format::R8G8B8Srgb |
+            Format::B8G8R8Unorm |
+            Format::B8G8R8Snorm |
+            Format::B8G8R8Uscaled |
+            Format::B8G8R8Sscaled |
+            Format::B8G8R8Uint |
+            Format::B8G8R8Sint |
+            Format::B8G8R8Srgb => Some(3),
+            Format::R64G64B64A64Uint |
+            Format::R64G64B64A64Sint |
+            Format::R64G64B64A64Sfloat => Some(32),
+            Format::R8G8B8A8Unorm |
+            Format::R8G8B8A8Snorm |
+            Format::R8G8B8A8Uscaled |
+            Format::R8G8B8A8Sscaled |
+            Format::R8G8B8A8Uint |
+            Format::R8G8B8A8Sint |
+            Format::R8G8B8A8Srgb |
+            Format::B8G8R8A8Unorm |
+            Format::B8G8R8A8Snorm |
+            Format::B8G8R8A8Uscaled |
+            Format::B8G8R8A8Sscaled |
+            Format::B8G8R8A8Uint |
+            Format::B8G8R8A8Sint |
+            Format::B8G8R8A8Srgb |
+            Format::A8B8G8R8UnormPack32 |
+            Format::A8B8G8R8SnormPack32 |
+            Format::A8B8G8R8UscaledPack32 |
+            Format::A8B8G8R8SscaledPack32 |
+            Format::A8B8G8R8UintPack32 |
+            Format::A8B8G8R8SintPack32 |
+            Format::A8B8G8R8SrgbPack32 |
+            Format::A2R10G10B10UnormPack32 |
+            Format::A2R10G10B10SnormPack32 |
+            Format::A2R10G10B10UscaledPack32 |
+            Format::A2R10G10B10SscaledPack32 |
+            Format::A2R10G10B10UintPack32 |
+            Format::A2R10G10B10SintPack32 |
+            Format::A2B10G10R10UnormPack32 |
+            Format::A2B10G10R10SnormPack32 |
+            Format::A2B10G10R10UscaledPack32 |
+            Format::A2B10G10R10SscaledPack32 |
+            Format::A2B10G10R10UintPack32 |
+            Format::A2B10G10R10SintPack32 |
+            Format::R16G16Unorm |
+            Format::R16G16Snorm |
+            Format::R16G16Uscaled |
+            Format::R16G16Sscaled |
+            Format::R16G16Uint |
+            Format::R16G16Sint |
+            Format::R16G16Sfloat |
+            Format::R32Uint |
+            Format::R32Sint |
+            Format::R32Sfloat |
+            Format::B10G11R11UfloatPack32 |
+            Format::E5B9G9R9UfloatPack32 |
+            Format::X8_D24UnormPack32 |
+            Format::D32Sfloat |
+            Format::D16Unorm_S8Uint |
+            Format::D24Unorm_S8Uint => Some(4),
+            Format::R16G16B16Unorm |
+            Format::R16G16B16Snorm |
+            Format::R16G16B16Uscaled |
+            Format::R16G16B16Sscaled |
+            Format::R16G16B16Uint |
+            Format::R16G16B16Sint |
+            Format::R16G16B16Sfloat => Some(6),
+            Format::R16G16B16A16Unorm |
+            Format::R16G16B16A16Snorm |
+            Format::R16G16B16A16Uscaled |
+            Format::R16G16B16A16Sscaled |
+            Format::R16G16B16A16Uint |
+            Format::R16G16B16A16Sint |
+            Format::R16G16B16A16Sfloat |
+            Format::R32G32Uint |
+            Format::R32G32Sint |
+            Format::R32G32Sfloat |
+            Format::R64Uint |
+            Format::R64Sint |
+            Format::R64Sfloat |
+            Format::D32Sfloat_S8Uint |
+            Format::BC1_RGBUnormBlock |
+            Format::BC1_RGBSrgbBlock |
+            Format::BC1_RGBAUnormBlock |
+            Format::BC1_RGBASrgbBlock |
+            Format::BC4UnormBlock |
+            Format::BC4SnormBlock |
+            Format::ETC2_R8G8B8UnormBlock |
+            Format::ETC2_R8G8B8SrgbBlock |
+            Format::ETC2_R8G8B8A1UnormBlock |
+            Format::ETC2_R8G8B8A1SrgbBlock |
+            Format::EAC_R11UnormBlock |
+            Format::EAC_R11SnormBlock => Some(8),
+            Format::Unknown | Format::Other(_) => None,
+        }
+    }
+
+    /// The width and height in texels of one compressed block, or `(1, 1)` for uncompressed formats.
+    pub fn block_extent(&self) -> (u32, u32) {
+        match *self {
+            Format::ASTC_10x10UnormBlock |
+            Format::ASTC_10x10SrgbBlock => (10, 10),
+            Format::ASTC_10x5UnormBlock |
+            Format::ASTC_10x5SrgbBlock => (10, 5),
+            Format::ASTC_10x6UnormBlock |
+            Format::ASTC_10x6SrgbBlock => (10, 6),
+            Format::ASTC_10x8UnormBlock |
+            Format::ASTC_10x8SrgbBlock => (10, 8),
+            Format::ASTC_12x10UnormBlock |
+            Format::ASTC_12x10SrgbBlock => (12, 10),
+            Format::ASTC_12x12UnormBlock |
+            Format::ASTC_12x12SrgbBlock => (12, 12),
+            Format::BC1_RGBUnormBlock |
+            Format::BC1_RGBSrgbBlock |
+            Format::BC1_RGBAUnormBlock |
+            Format::BC1_RGBASrgbBlock |
+            Format::BC2UnormBlock |
+            Format::BC2SrgbBlock |
+            Format::BC3UnormBlock |
+            Format::BC3SrgbBlock |
+            Format::BC4UnormBlock |
+            Format::BC4SnormBlock |
+            Format::BC5UnormBlock |
+            Format::BC5SnormBlock |
+            Format::BC6HUfloatBlock |
+            Format::BC6HSfloatBlock |
+            Format::BC7UnormBlock |
+            Format::BC7SrgbBlock |
+            Format::ETC2_R8G8B8UnormBlock |
+            Format::ETC2_R8G8B8SrgbBlock |
+            Format::ETC2_R8G8B8A1UnormBlock |
+            Format::ETC2_R8G8B8A1SrgbBlock |
+            Format::ETC2_R8G8B8A8UnormBlock |
+            Format::ETC2_R8G8B8A8SrgbBlock |
+            Format::EAC_R11UnormBlock |
+            Format::EAC_R11SnormBlock |
+            Format::EAC_R11G11UnormBlock |
+            Format::EAC_R11G11SnormBlock |
+            Format::ASTC_4x4UnormBlock |
+            Format::ASTC_4x4SrgbBlock => (4, 4),
+            Format::ASTC_5x4UnormBlock |
+            Format::ASTC_5x4SrgbBlock => (5, 4),
+            Format::ASTC_5x5UnormBlock |
+            Format::ASTC_5x5SrgbBlock => (5, 5),
+            Format::ASTC_6x5UnormBlock |
+            Format::ASTC_6x5SrgbBlock => (6, 5),
+            Format::ASTC_6x6UnormBlock |
+            Format::ASTC_6x6SrgbBlock => (6, 6),
+            Format::ASTC_8x5UnormBlock |
+            Format::ASTC_8x5SrgbBlock => (8, 5),
+            Format::ASTC_8x6UnormBlock |
+            Format::ASTC_8x6SrgbBlock => (8, 6),
+            Format::ASTC_8x8UnormBlock |
+            Format::ASTC_8x8SrgbBlock => (8, 8),
+            _ => (1, 1),
+        }
+    }
+}
+
 vulkan_struct_correspondence! {
     struct FormatProperties => VkFormatProperties {
         linear_tiling_features => linearTilingFeatures: VkFormatFeatureFlags,
@@ -437,6 +1665,35 @@ vulkan_struct_correspondence! {
     }
 }
 
+vulkan_struct_correspondence! {
+    struct Extent2D => VkExtent2D {
+        width => width: u32,
+        height => height: u32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct Offset2D => VkOffset2D {
+        x => x: i32,
+        y => y: i32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct Offset3D => VkOffset3D {
+        x => x: i32,
+        y => y: i32,
+        z => z: i32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct Rect2D => VkRect2D {
+        offset => offset: VkOffset2D,
+        extent => extent: VkExtent2D,
+    }
+}
+
 bitflags! {
     pub struct MemoryMapFlags: u32 {
         const VK_MEMORY_MAP_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
@@ -490,20 +1747,889 @@ vulkan_struct_correspondence! {
     }
 }
 
+pub const WHOLE_SIZE: u64 = !0;
+pub const REMAINING_MIP_LEVELS: u32 = !0;
+pub const REMAINING_ARRAY_LAYERS: u32 = !0;
+pub const ATTACHMENT_UNUSED: u32 = !0;
+pub const SUBPASS_EXTERNAL: u32 = !0;
+
 bitflags! {
-    pub struct QueryResultFlags: u32 {
-        const VK_QUERY_RESULT_64_BIT = 0x00000001;
-        const VK_QUERY_RESULT_WAIT_BIT = 0x00000002;
+    pub struct BufferCreateFlags: u32 {
+        const VK_BUFFER_CREATE_SPARSE_BINDING_BIT = 0x00000001;
+        const VK_BUFFER_CREATE_SPARSE_RESIDENCY_BIT = 0x00000002;
+        const VK_BUFFER_CREATE_SPARSE_ALIASED_BIT = 0x00000004;
+        const VK_BUFFER_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct BufferUsageFlags: u32 {
+        const VK_BUFFER_USAGE_TRANSFER_SRC_BIT = 0x00000001;
+        const VK_BUFFER_USAGE_TRANSFER_DST_BIT = 0x00000002;
+        const VK_BUFFER_USAGE_UNIFORM_TEXEL_BUFFER_BIT = 0x00000004;
+        const VK_BUFFER_USAGE_STORAGE_TEXEL_BUFFER_BIT = 0x00000008;
+        const VK_BUFFER_USAGE_UNIFORM_BUFFER_BIT = 0x00000010;
+        const VK_BUFFER_USAGE_STORAGE_BUFFER_BIT = 0x00000020;
+        const VK_BUFFER_USAGE_INDEX_BUFFER_BIT = 0x00000040;
+        const VK_BUFFER_USAGE_VERTEX_BUFFER_BIT = 0x00000080;
+        const VK_BUFFER_USAGE_INDIRECT_BUFFER_BIT = 0x00000100;
+        const VK_BUFFER_USAGE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct ImageCreateFlags: u32 {
+        const VK_IMAGE_CREATE_SPARSE_BINDING_BIT = 0x00000001;
+        const VK_IMAGE_CREATE_SPARSE_RESIDENCY_BIT = 0x00000002;
+        const VK_IMAGE_CREATE_SPARSE_ALIASED_BIT = 0x00000004;
+        const VK_IMAGE_CREATE_MUTABLE_FORMAT_BIT = 0x00000008;
+        const VK_IMAGE_CREATE_CUBE_COMPATIBLE_BIT = 0x00000010;
+        const VK_IMAGE_CREATE_2D_ARRAY_COMPATIBLE_BIT_KHR = 0x00000020;
+        const VK_IMAGE_CREATE_BIND_SFR_BIT_KHX = 0x00000040;
+        const VK_IMAGE_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct ImageUsageFlags: u32 {
+        const VK_IMAGE_USAGE_TRANSFER_SRC_BIT = 0x00000001;
+        const VK_IMAGE_USAGE_TRANSFER_DST_BIT = 0x00000002;
+        const VK_IMAGE_USAGE_SAMPLED_BIT = 0x00000004;
+        const VK_IMAGE_USAGE_STORAGE_BIT = 0x00000008;
+        const VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT = 0x00000010;
+        const VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT = 0x00000020;
+        const VK_IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT = 0x00000040;
+        const VK_IMAGE_USAGE_INPUT_ATTACHMENT_BIT = 0x00000080;
+        const VK_IMAGE_USAGE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct SampleCountFlags: u32 {
+        const VK_SAMPLE_COUNT_1_BIT = 0x00000001;
+        const VK_SAMPLE_COUNT_2_BIT = 0x00000002;
+        const VK_SAMPLE_COUNT_4_BIT = 0x00000004;
+        const VK_SAMPLE_COUNT_8_BIT = 0x00000008;
+        const VK_SAMPLE_COUNT_16_BIT = 0x00000010;
+        const VK_SAMPLE_COUNT_32_BIT = 0x00000020;
+        const VK_SAMPLE_COUNT_64_BIT = 0x00000040;
+        const VK_SAMPLE_COUNT_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct QueueFlags: u32 {
+        const VK_QUEUE_GRAPHICS_BIT = 0x00000001;
+        const VK_QUEUE_COMPUTE_BIT = 0x00000002;
+        const VK_QUEUE_TRANSFER_BIT = 0x00000004;
+        const VK_QUEUE_SPARSE_BINDING_BIT = 0x00000008;
+        const VK_QUEUE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct MemoryPropertyFlags: u32 {
+        const VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT = 0x00000001;
+        const VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT = 0x00000002;
+        const VK_MEMORY_PROPERTY_HOST_COHERENT_BIT = 0x00000004;
+        const VK_MEMORY_PROPERTY_HOST_CACHED_BIT = 0x00000008;
+        const VK_MEMORY_PROPERTY_LAZILY_ALLOCATED_BIT = 0x00000010;
+        const VK_MEMORY_PROPERTY_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct MemoryHeapFlags: u32 {
+        const VK_MEMORY_HEAP_DEVICE_LOCAL_BIT = 0x00000001;
+        const VK_MEMORY_HEAP_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct FenceCreateFlags: u32 {
+        const VK_FENCE_CREATE_SIGNALED_BIT = 0x00000001;
+        const VK_FENCE_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct PipelineCreateFlags: u32 {
+        const VK_PIPELINE_CREATE_DISABLE_OPTIMIZATION_BIT = 0x00000001;
+        const VK_PIPELINE_CREATE_ALLOW_DERIVATIVES_BIT = 0x00000002;
+        const VK_PIPELINE_CREATE_DERIVATIVE_BIT = 0x00000004;
+        const VK_PIPELINE_CREATE_VIEW_INDEX_FROM_DEVICE_INDEX_BIT_KHX = 0x00000008;
+        const VK_PIPELINE_CREATE_DISPATCH_BASE_KHX = 0x00000010;
+        const VK_PIPELINE_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct ShaderStageFlags: u32 {
+        const VK_SHADER_STAGE_VERTEX_BIT = 0x00000001;
+        const VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT = 0x00000002;
+        const VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT = 0x00000004;
+        const VK_SHADER_STAGE_GEOMETRY_BIT = 0x00000008;
+        const VK_SHADER_STAGE_FRAGMENT_BIT = 0x00000010;
+        const VK_SHADER_STAGE_COMPUTE_BIT = 0x00000020;
+        const VK_SHADER_STAGE_ALL_GRAPHICS = 0x0000001F;
+        const VK_SHADER_STAGE_ALL = 0x7FFFFFFF;
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum SharingMode => VkSharingMode {
+        Exclusive => VK_SHARING_MODE_EXCLUSIVE,
+        Concurrent => VK_SHARING_MODE_CONCURRENT,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum ImageType => VkImageType {
+        D1 => VK_IMAGE_TYPE_1D,
+        D2 => VK_IMAGE_TYPE_2D,
+        D3 => VK_IMAGE_TYPE_3D,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum ImageTiling => VkImageTiling {
+        Optimal => VK_IMAGE_TILING_OPTIMAL,
+        Linear => VK_IMAGE_TILING_LINEAR,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum ImageLayout => VkImageLayout {
+        Undefined => VK_IMAGE_LAYOUT_UNDEFINED,
+        General => VK_IMAGE_LAYOUT_GENERAL,
+        ColorAttachmentOptimal => VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL,
+        DepthStencilAttachmentOptimal => VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        DepthStencilReadOnlyOptimal => VK_IMAGE_LAYOUT_DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        ShaderReadOnlyOptimal => VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+        TransferSrcOptimal => VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL,
+        TransferDstOptimal => VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+        Preinitialized => VK_IMAGE_LAYOUT_PREINITIALIZED,
+        PresentSrcKhr => VK_IMAGE_LAYOUT_PRESENT_SRC_KHR,
+        SharedPresentKhr => VK_IMAGE_LAYOUT_SHARED_PRESENT_KHR,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum ImageViewType => VkImageViewType {
+        D1 => VK_IMAGE_VIEW_TYPE_1D,
+        D2 => VK_IMAGE_VIEW_TYPE_2D,
+        D3 => VK_IMAGE_VIEW_TYPE_3D,
+        Cube => VK_IMAGE_VIEW_TYPE_CUBE,
+        D1Array => VK_IMAGE_VIEW_TYPE_1D_ARRAY,
+        D2Array => VK_IMAGE_VIEW_TYPE_2D_ARRAY,
+        CubeArray => VK_IMAGE_VIEW_TYPE_CUBE_ARRAY,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum ComponentSwizzle => VkComponentSwizzle {
+        Identity => VK_COMPONENT_SWIZZLE_IDENTITY,
+        Zero => VK_COMPONENT_SWIZZLE_ZERO,
+        One => VK_COMPONENT_SWIZZLE_ONE,
+        R => VK_COMPONENT_SWIZZLE_R,
+        G => VK_COMPONENT_SWIZZLE_G,
+        B => VK_COMPONENT_SWIZZLE_B,
+        A => VK_COMPONENT_SWIZZLE_A,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct ComponentMapping => VkComponentMapping {
+        r => r: VkComponentSwizzle,
+        g => g: VkComponentSwizzle,
+        b => b: VkComponentSwizzle,
+        a => a: VkComponentSwizzle,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct ImageSubresourceRange => VkImageSubresourceRange {
+        aspect_mask => aspectMask: VkImageAspectFlags,
+        base_mip_level => baseMipLevel: u32,
+        level_count => levelCount: u32,
+        base_array_layer => baseArrayLayer: u32,
+        layer_count => layerCount: u32,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum Filter => VkFilter {
+        Nearest => VK_FILTER_NEAREST,
+        Linear => VK_FILTER_LINEAR,
+        CubicImg => VK_FILTER_CUBIC_IMG,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum SamplerMipmapMode => VkSamplerMipmapMode {
+        Nearest => VK_SAMPLER_MIPMAP_MODE_NEAREST,
+        Linear => VK_SAMPLER_MIPMAP_MODE_LINEAR,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum SamplerAddressMode => VkSamplerAddressMode {
+        Repeat => VK_SAMPLER_ADDRESS_MODE_REPEAT,
+        MirroredRepeat => VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT,
+        ClampToEdge => VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE,
+        ClampToBorder => VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER,
+        MirrorClampToEdge => VK_SAMPLER_ADDRESS_MODE_MIRROR_CLAMP_TO_EDGE,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum BorderColor => VkBorderColor {
+        FloatTransparentBlack => VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK,
+        IntTransparentBlack => VK_BORDER_COLOR_INT_TRANSPARENT_BLACK,
+        FloatOpaqueBlack => VK_BORDER_COLOR_FLOAT_OPAQUE_BLACK,
+        IntOpaqueBlack => VK_BORDER_COLOR_INT_OPAQUE_BLACK,
+        FloatOpaqueWhite => VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE,
+        IntOpaqueWhite => VK_BORDER_COLOR_INT_OPAQUE_WHITE,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum CompareOp => VkCompareOp {
+        Never => VK_COMPARE_OP_NEVER,
+        Less => VK_COMPARE_OP_LESS,
+        Equal => VK_COMPARE_OP_EQUAL,
+        LessOrEqual => VK_COMPARE_OP_LESS_OR_EQUAL,
+        Greater => VK_COMPARE_OP_GREATER,
+        NotEqual => VK_COMPARE_OP_NOT_EQUAL,
+        GreaterOrEqual => VK_COMPARE_OP_GREATER_OR_EQUAL,
+        Always => VK_COMPARE_OP_ALWAYS,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum DescriptorType => VkDescriptorType {
+        Sampler => VK_DESCRIPTOR_TYPE_SAMPLER,
+        CombinedImageSampler => VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+        SampledImage => VK_DESCRIPTOR_TYPE_SAMPLED_IMAGE,
+        StorageImage => VK_DESCRIPTOR_TYPE_STORAGE_IMAGE,
+        UniformTexelBuffer => VK_DESCRIPTOR_TYPE_UNIFORM_TEXEL_BUFFER,
+        StorageTexelBuffer => VK_DESCRIPTOR_TYPE_STORAGE_TEXEL_BUFFER,
+        UniformBuffer => VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+        StorageBuffer => VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+        UniformBufferDynamic => VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC,
+        StorageBufferDynamic => VK_DESCRIPTOR_TYPE_STORAGE_BUFFER_DYNAMIC,
+        InputAttachment => VK_DESCRIPTOR_TYPE_INPUT_ATTACHMENT,
+    }
+}
+
+bitflags! {
+    pub struct DescriptorSetLayoutCreateFlags: u32 {
+        const VK_DESCRIPTOR_SET_LAYOUT_CREATE_PUSH_DESCRIPTOR_BIT_KHR = 0x00000001;
+        const VK_DESCRIPTOR_SET_LAYOUT_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct DescriptorPoolCreateFlags: u32 {
+        const VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT = 0x00000001;
+        const VK_DESCRIPTOR_POOL_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct DescriptorPoolSize => VkDescriptorPoolSize {
+        ty => type_: VkDescriptorType,
+        descriptor_count => descriptorCount: u32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct PushConstantRange => VkPushConstantRange {
+        stage_flags => stageFlags: VkShaderStageFlags,
+        offset => offset: u32,
+        size => size: u32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct SpecializationMapEntry => VkSpecializationMapEntry {
+        constant_id => constantID: u32,
+        offset => offset: u32,
+        size => size: usize,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum VertexInputRate => VkVertexInputRate {
+        Vertex => VK_VERTEX_INPUT_RATE_VERTEX,
+        Instance => VK_VERTEX_INPUT_RATE_INSTANCE,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct VertexInputBindingDescription => VkVertexInputBindingDescription {
+        binding => binding: u32,
+        stride => stride: u32,
+        input_rate => inputRate: VkVertexInputRate,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct VertexInputAttributeDescription => VkVertexInputAttributeDescription {
+        location => location: u32,
+        binding => binding: u32,
+        format => format: VkFormat,
+        offset => offset: u32,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum PrimitiveTopology => VkPrimitiveTopology {
+        PointList => VK_PRIMITIVE_TOPOLOGY_POINT_LIST,
+        LineList => VK_PRIMITIVE_TOPOLOGY_LINE_LIST,
+        LineStrip => VK_PRIMITIVE_TOPOLOGY_LINE_STRIP,
+        TriangleList => VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST,
+        TriangleStrip => VK_PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP,
+        TriangleFan => VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN,
+        LineListWithAdjacency => VK_PRIMITIVE_TOPOLOGY_LINE_LIST_WITH_ADJACENCY,
+        LineStripWithAdjacency => VK_PRIMITIVE_TOPOLOGY_LINE_STRIP_WITH_ADJACENCY,
+        TriangleListWithAdjacency => VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST_WITH_ADJACENCY,
+        TriangleStripWithAdjacency => VK_PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP_WITH_ADJACENCY,
+        PatchList => VK_PRIMITIVE_TOPOLOGY_PATCH_LIST,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum PolygonMode => VkPolygonMode {
+        Fill => VK_POLYGON_MODE_FILL,
+        Line => VK_POLYGON_MODE_LINE,
+        Point => VK_POLYGON_MODE_POINT,
+    }
+}
+
+bitflags! {
+    pub struct CullModeFlags: u32 {
+        const VK_CULL_MODE_NONE = 0;
+        const VK_CULL_MODE_FRONT_BIT = 0x00000001;
+        const VK_CULL_MODE_BACK_BIT = 0x00000002;
+        const VK_CULL_MODE_FRONT_AND_BACK = 0x00000003;
+        const VK_CULL_MODE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum FrontFace => VkFrontFace {
+        CounterClockwise => VK_FRONT_FACE_COUNTER_CLOCKWISE,
+        Clockwise => VK_FRONT_FACE_CLOCKWISE,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum StencilOp => VkStencilOp {
+        Keep => VK_STENCIL_OP_KEEP,
+        Zero => VK_STENCIL_OP_ZERO,
+        Replace => VK_STENCIL_OP_REPLACE,
+        IncrementAndClamp => VK_STENCIL_OP_INCREMENT_AND_CLAMP,
+        DecrementAndClamp => VK_STENCIL_OP_DECREMENT_AND_CLAMP,
+        Invert => VK_STENCIL_OP_INVERT,
+        IncrementAndWrap => VK_STENCIL_OP_INCREMENT_AND_WRAP,
+        DecrementAndWrap => VK_STENCIL_OP_DECREMENT_AND_WRAP,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct StencilOpState => VkStencilOpState {
+        fail_op => failOp: VkStencilOp,
+        pass_op => passOp: VkStencilOp,
+        depth_fail_op => depthFailOp: VkStencilOp,
+        compare_op => compareOp: VkCompareOp,
+        compare_mask => compareMask: u32,
+        write_mask => writeMask: u32,
+        reference => reference: u32,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum BlendFactor => VkBlendFactor {
+        Zero => VK_BLEND_FACTOR_ZERO,
+        One => VK_BLEND_FACTOR_ONE,
+        SrcColor => VK_BLEND_FACTOR_SRC_COLOR,
+        OneMinusSrcColor => VK_BLEND_FACTOR_ONE_MINUS_SRC_COLOR,
+        DstColor => VK_BLEND_FACTOR_DST_COLOR,
+        OneMinusDstColor => VK_BLEND_FACTOR_ONE_MINUS_DST_COLOR,
+        SrcAlpha => VK_BLEND_FACTOR_SRC_ALPHA,
+        OneMinusSrcAlpha => VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
+        DstAlpha => VK_BLEND_FACTOR_DST_ALPHA,
+        OneMinusDstAlpha => VK_BLEND_FACTOR_ONE_MINUS_DST_ALPHA,
+        ConstantColor => VK_BLEND_FACTOR_CONSTANT_COLOR,
+        OneMinusConstantColor => VK_BLEND_FACTOR_ONE_MINUS_CONSTANT_COLOR,
+        ConstantAlpha => VK_BLEND_FACTOR_CONSTANT_ALPHA,
+        OneMinusConstantAlpha => VK_BLEND_FACTOR_ONE_MINUS_CONSTANT_ALPHA,
+        SrcAlphaSaturate => VK_BLEND_FACTOR_SRC_ALPHA_SATURATE,
+        Src1Color => VK_BLEND_FACTOR_SRC1_COLOR,
+        OneMinusSrc1Color => VK_BLEND_FACTOR_ONE_MINUS_SRC1_COLOR,
+        Src1Alpha => VK_BLEND_FACTOR_SRC1_ALPHA,
+        OneMinusSrc1Alpha => VK_BLEND_FACTOR_ONE_MINUS_SRC1_ALPHA,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum BlendOp => VkBlendOp {
+        Add => VK_BLEND_OP_ADD,
+        Subtract => VK_BLEND_OP_SUBTRACT,
+        ReverseSubtract => VK_BLEND_OP_REVERSE_SUBTRACT,
+        Min => VK_BLEND_OP_MIN,
+        Max => VK_BLEND_OP_MAX,
+    }
+}
+
+bitflags! {
+    pub struct ColorComponentFlags: u32 {
+        const VK_COLOR_COMPONENT_R_BIT = 0x00000001;
+        const VK_COLOR_COMPONENT_G_BIT = 0x00000002;
+        const VK_COLOR_COMPONENT_B_BIT = 0x00000004;
+        const VK_COLOR_COMPONENT_A_BIT = 0x00000008;
+        const VK_COLOR_COMPONENT_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct PipelineColorBlendAttachmentState => VkPipelineColorBlendAttachmentState {
+        blend_enable => blendEnable: VkBool32,
+        src_color_blend_factor => srcColorBlendFactor: VkBlendFactor,
+        dst_color_blend_factor => dstColorBlendFactor: VkBlendFactor,
+        color_blend_op => colorBlendOp: VkBlendOp,
+        src_alpha_blend_factor => srcAlphaBlendFactor: VkBlendFactor,
+        dst_alpha_blend_factor => dstAlphaBlendFactor: VkBlendFactor,
+        alpha_blend_op => alphaBlendOp: VkBlendOp,
+        color_write_mask => colorWriteMask: VkColorComponentFlags,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum LogicOp => VkLogicOp {
+        Clear => VK_LOGIC_OP_CLEAR,
+        And => VK_LOGIC_OP_AND,
+        AndReverse => VK_LOGIC_OP_AND_REVERSE,
+        Copy => VK_LOGIC_OP_COPY,
+        AndInverted => VK_LOGIC_OP_AND_INVERTED,
+        NoOp => VK_LOGIC_OP_NO_OP,
+        Xor => VK_LOGIC_OP_XOR,
+        Or => VK_LOGIC_OP_OR,
+        Nor => VK_LOGIC_OP_NOR,
+        Equivalent => VK_LOGIC_OP_EQUIVALENT,
+        Invert => VK_LOGIC_OP_INVERT,
+        OrReverse => VK_LOGIC_OP_OR_REVERSE,
+        CopyInverted => VK_LOGIC_OP_COPY_INVERTED,
+        OrInverted => VK_LOGIC_OP_OR_INVERTED,
+        Nand => VK_LOGIC_OP_NAND,
+        Set => VK_LOGIC_OP_SET,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum DynamicState => VkDynamicState {
+        Viewport => VK_DYNAMIC_STATE_VIEWPORT,
+        Scissor => VK_DYNAMIC_STATE_SCISSOR,
+        LineWidth => VK_DYNAMIC_STATE_LINE_WIDTH,
+        DepthBias => VK_DYNAMIC_STATE_DEPTH_BIAS,
+        BlendConstants => VK_DYNAMIC_STATE_BLEND_CONSTANTS,
+        DepthBounds => VK_DYNAMIC_STATE_DEPTH_BOUNDS,
+        StencilCompareMask => VK_DYNAMIC_STATE_STENCIL_COMPARE_MASK,
+        StencilWriteMask => VK_DYNAMIC_STATE_STENCIL_WRITE_MASK,
+        StencilReference => VK_DYNAMIC_STATE_STENCIL_REFERENCE,
+    }
+}
+
+bitflags! {
+    pub struct AttachmentDescriptionFlags: u32 {
+        const VK_ATTACHMENT_DESCRIPTION_MAY_ALIAS_BIT = 0x00000001;
+        const VK_ATTACHMENT_DESCRIPTION_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct SubpassDescriptionFlags: u32 {
+        const VK_SUBPASS_DESCRIPTION_PER_VIEW_ATTRIBUTES_BIT_NVX = 0x00000001;
+        const VK_SUBPASS_DESCRIPTION_PER_VIEW_POSITION_X_ONLY_BIT_NVX = 0x00000002;
+        const VK_SUBPASS_DESCRIPTION_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum AttachmentLoadOp => VkAttachmentLoadOp {
+        Load => VK_ATTACHMENT_LOAD_OP_LOAD,
+        Clear => VK_ATTACHMENT_LOAD_OP_CLEAR,
+        DontCare => VK_ATTACHMENT_LOAD_OP_DONT_CARE,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum AttachmentStoreOp => VkAttachmentStoreOp {
+        Store => VK_ATTACHMENT_STORE_OP_STORE,
+        DontCare => VK_ATTACHMENT_STORE_OP_DONT_CARE,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum PipelineBindPoint => VkPipelineBindPoint {
+        Graphics => VK_PIPELINE_BIND_POINT_GRAPHICS,
+        Compute => VK_PIPELINE_BIND_POINT_COMPUTE,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct AttachmentReference => VkAttachmentReference {
+        attachment => attachment: u32,
+        layout => layout: VkImageLayout,
+    }
+}
+
+bitflags! {
+    pub struct PipelineStageFlags: u32 {
+        const VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT = 0x00000001;
+        const VK_PIPELINE_STAGE_DRAW_INDIRECT_BIT = 0x00000002;
+        const VK_PIPELINE_STAGE_VERTEX_INPUT_BIT = 0x00000004;
+        const VK_PIPELINE_STAGE_VERTEX_SHADER_BIT = 0x00000008;
+        const VK_PIPELINE_STAGE_TESSELLATION_CONTROL_SHADER_BIT = 0x00000010;
+        const VK_PIPELINE_STAGE_TESSELLATION_EVALUATION_SHADER_BIT = 0x00000020;
+        const VK_PIPELINE_STAGE_GEOMETRY_SHADER_BIT = 0x00000040;
+        const VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT = 0x00000080;
+        const VK_PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT = 0x00000100;
+        const VK_PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT = 0x00000200;
+        const VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT = 0x00000400;
+        const VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT = 0x00000800;
+        const VK_PIPELINE_STAGE_TRANSFER_BIT = 0x00001000;
+        const VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT = 0x00002000;
+        const VK_PIPELINE_STAGE_HOST_BIT = 0x00004000;
+        const VK_PIPELINE_STAGE_ALL_GRAPHICS_BIT = 0x00008000;
+        const VK_PIPELINE_STAGE_ALL_COMMANDS_BIT = 0x00010000;
+        const VK_PIPELINE_STAGE_COMMAND_PROCESS_BIT_NVX = 0x00020000;
+        const VK_PIPELINE_STAGE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct AccessFlags: u32 {
+        const VK_ACCESS_INDIRECT_COMMAND_READ_BIT = 0x00000001;
+        const VK_ACCESS_INDEX_READ_BIT = 0x00000002;
+        const VK_ACCESS_VERTEX_ATTRIBUTE_READ_BIT = 0x00000004;
+        const VK_ACCESS_UNIFORM_READ_BIT = 0x00000008;
+        const VK_ACCESS_INPUT_ATTACHMENT_READ_BIT = 0x00000010;
+        const VK_ACCESS_SHADER_READ_BIT = 0x00000020;
+        const VK_ACCESS_SHADER_WRITE_BIT = 0x00000040;
+        const VK_ACCESS_COLOR_ATTACHMENT_READ_BIT = 0x00000080;
+        const VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT = 0x00000100;
+        const VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_READ_BIT = 0x00000200;
+        const VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT = 0x00000400;
+        const VK_ACCESS_TRANSFER_READ_BIT = 0x00000800;
+        const VK_ACCESS_TRANSFER_WRITE_BIT = 0x00001000;
+        const VK_ACCESS_HOST_READ_BIT = 0x00002000;
+        const VK_ACCESS_HOST_WRITE_BIT = 0x00004000;
+        const VK_ACCESS_MEMORY_READ_BIT = 0x00008000;
+        const VK_ACCESS_MEMORY_WRITE_BIT = 0x00010000;
+        const VK_ACCESS_COMMAND_PROCESS_READ_BIT_NVX = 0x00020000;
+        const VK_ACCESS_COMMAND_PROCESS_WRITE_BIT_NVX = 0x00040000;
+        const VK_ACCESS_COLOR_ATTACHMENT_READ_NONCOHERENT_BIT_EXT = 0x00080000;
+        const VK_ACCESS_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct DependencyFlags: u32 {
+        const VK_DEPENDENCY_BY_REGION_BIT = 0x00000001;
+        const VK_DEPENDENCY_VIEW_LOCAL_BIT_KHX = 0x00000002;
+        const VK_DEPENDENCY_DEVICE_GROUP_BIT_KHX = 0x00000004;
+        const VK_DEPENDENCY_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct SubpassDependency => VkSubpassDependency {
+        src_subpass => srcSubpass: u32,
+        dst_subpass => dstSubpass: u32,
+        src_stage_mask => srcStageMask: VkPipelineStageFlags,
+        dst_stage_mask => dstStageMask: VkPipelineStageFlags,
+        src_access_mask => srcAccessMask: VkAccessFlags,
+        dst_access_mask => dstAccessMask: VkAccessFlags,
+        dependency_flags => dependencyFlags: VkDependencyFlags,
+    }
+}
+
+bitflags! {
+    pub struct CommandPoolCreateFlags: u32 {
+        const VK_COMMAND_POOL_CREATE_TRANSIENT_BIT = 0x00000001;
+        const VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT = 0x00000002;
+        const VK_COMMAND_POOL_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct CommandPoolResetFlags: u32 {
+        const VK_COMMAND_POOL_RESET_RELEASE_RESOURCES_BIT = 0x00000001;
+        const VK_COMMAND_POOL_RESET_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct CommandBufferResetFlags: u32 {
+        const VK_COMMAND_BUFFER_RESET_RELEASE_RESOURCES_BIT = 0x00000001;
+        const VK_COMMAND_BUFFER_RESET_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct CommandBufferUsageFlags: u32 {
+        const VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT = 0x00000001;
+        const VK_COMMAND_BUFFER_USAGE_RENDER_PASS_CONTINUE_BIT = 0x00000002;
+        const VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT = 0x00000004;
+        const VK_COMMAND_BUFFER_USAGE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum CommandBufferLevel => VkCommandBufferLevel {
+        Primary => VK_COMMAND_BUFFER_LEVEL_PRIMARY,
+        Secondary => VK_COMMAND_BUFFER_LEVEL_SECONDARY,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum SubpassContents => VkSubpassContents {
+        Inline => VK_SUBPASS_CONTENTS_INLINE,
+        SecondaryCommandBuffers => VK_SUBPASS_CONTENTS_SECONDARY_COMMAND_BUFFERS,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum IndexType => VkIndexType {
+        Uint16 => VK_INDEX_TYPE_UINT16,
+        Uint32 => VK_INDEX_TYPE_UINT32,
+    }
+}
+
+bitflags! {
+    pub struct StencilFaceFlags: u32 {
+        const VK_STENCIL_FACE_FRONT_BIT = 0x00000001;
+        const VK_STENCIL_FACE_BACK_BIT = 0x00000002;
+        const VK_STENCIL_FRONT_AND_BACK = 0x00000003;
+        const VK_STENCIL_FACE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct Viewport => VkViewport {
+        x => x: f32,
+        y => y: f32,
+        width => width: f32,
+        height => height: f32,
+        min_depth => minDepth: f32,
+        max_depth => maxDepth: f32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct ImageSubresourceLayers => VkImageSubresourceLayers {
+        aspect_mask => aspectMask: VkImageAspectFlags,
+        mip_level => mipLevel: u32,
+        base_array_layer => baseArrayLayer: u32,
+        layer_count => layerCount: u32,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct BufferCopy => VkBufferCopy {
+        src_offset => srcOffset: VkDeviceSize,
+        dst_offset => dstOffset: VkDeviceSize,
+        size => size: VkDeviceSize,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct ImageCopy => VkImageCopy {
+        src_subresource => srcSubresource: VkImageSubresourceLayers,
+        src_offset => srcOffset: VkOffset3D,
+        dst_subresource => dstSubresource: VkImageSubresourceLayers,
+        dst_offset => dstOffset: VkOffset3D,
+        extent => extent: VkExtent3D,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct ImageResolve => VkImageResolve {
+        src_subresource => srcSubresource: VkImageSubresourceLayers,
+        src_offset => srcOffset: VkOffset3D,
+        dst_subresource => dstSubresource: VkImageSubresourceLayers,
+        dst_offset => dstOffset: VkOffset3D,
+        extent => extent: VkExtent3D,
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct BufferImageCopy => VkBufferImageCopy {
+        buffer_offset => bufferOffset: VkDeviceSize,
+        buffer_row_length => bufferRowLength: u32,
+        buffer_image_height => bufferImageHeight: u32,
+        image_subresource => imageSubresource: VkImageSubresourceLayers,
+        image_offset => imageOffset: VkOffset3D,
+        image_extent => imageExtent: VkExtent3D,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ImageBlit {
+    pub src_subresource: ImageSubresourceLayers,
+    pub src_offsets: [Offset3D; 2],
+    pub dst_subresource: ImageSubresourceLayers,
+    pub dst_offsets: [Offset3D; 2],
+}
+
+impl From<sys::VkImageBlit> for ImageBlit {
+    fn from(raw: sys::VkImageBlit) -> ImageBlit {
+        ImageBlit {
+            src_subresource: raw.srcSubresource.into(),
+            src_offsets: [raw.srcOffsets[0].into(), raw.srcOffsets[1].into()],
+            dst_subresource: raw.dstSubresource.into(),
+            dst_offsets: [raw.dstOffsets[0].into(), raw.dstOffsets[1].into()],
+        }
+    }
+}
+
+/// The raw contents of a `VkClearColorValue`. Whether they are floats, signed or unsigned integers
+/// depends on the format of the image being cleared.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClearColorValue(pub [u32; 4]);
+
+impl ClearColorValue {
+    pub(crate) unsafe fn from_raw(raw: &sys::VkClearColorValue) -> ClearColorValue {
+        ClearColorValue(*(raw as *const _ as *const [u32; 4]))
+    }
+
+    pub fn float32(&self) -> [f32; 4] { unsafe { mem::transmute(self.0) } }
+    pub fn int32(&self) -> [i32; 4] { unsafe { mem::transmute(self.0) } }
+    pub fn uint32(&self) -> [u32; 4] { self.0 }
+}
+
+vulkan_struct_correspondence! {
+    struct ClearDepthStencilValue => VkClearDepthStencilValue {
+        depth => depth: f32,
+        stencil => stencil: u32,
+    }
+}
+
+/// The raw contents of a `VkClearValue`, which holds a color or a depth/stencil value depending on
+/// the attachment it applies to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClearValue(pub [u32; 4]);
+
+impl ClearValue {
+    pub(crate) unsafe fn from_raw(raw: &sys::VkClearValue) -> ClearValue {
+        ClearValue(*(raw as *const _ as *const [u32; 4]))
+    }
+
+    pub fn color(&self) -> ClearColorValue { ClearColorValue(self.0) }
+    pub fn depth_stencil(&self) -> ClearDepthStencilValue {
+        ClearDepthStencilValue {
+            depth: unsafe { mem::transmute(self.0[0]) },
+            stencil: self.0[1],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClearAttachment {
+    pub aspect_mask: ImageAspectFlags,
+    /// Only meaningful when `aspect_mask` includes `VK_IMAGE_ASPECT_COLOR_BIT`.
+    pub color_attachment: u32,
+    pub clear_value: ClearValue,
+}
+
+impl From<sys::VkClearAttachment> for ClearAttachment {
+    fn from(raw: sys::VkClearAttachment) -> ClearAttachment {
+        ClearAttachment {
+            aspect_mask: ImageAspectFlags::from_bits_truncate(raw.aspectMask),
+            color_attachment: raw.colorAttachment,
+            clear_value: unsafe { ClearValue::from_raw(&raw.clearValue) },
+        }
+    }
+}
+
+vulkan_struct_correspondence! {
+    struct ClearRect => VkClearRect {
+        rect => rect: VkRect2D,
+        base_array_layer => baseArrayLayer: u32,
+        layer_count => layerCount: u32,
+    }
+}
+
+vulkan_enum_correspondence! {
+    enum QueryType => VkQueryType {
+        Occlusion => VK_QUERY_TYPE_OCCLUSION,
+        PipelineStatistics => VK_QUERY_TYPE_PIPELINE_STATISTICS,
+        Timestamp => VK_QUERY_TYPE_TIMESTAMP,
+    }
+}
+
+bitflags! {
+    pub struct QueryPipelineStatisticFlags: u32 {
+        const VK_QUERY_PIPELINE_STATISTIC_INPUT_ASSEMBLY_VERTICES_BIT = 0x00000001;
+        const VK_QUERY_PIPELINE_STATISTIC_INPUT_ASSEMBLY_PRIMITIVES_BIT = 0x00000002;
+        const VK_QUERY_PIPELINE_STATISTIC_VERTEX_SHADER_INVOCATIONS_BIT = 0x00000004;
+        const VK_QUERY_PIPELINE_STATISTIC_GEOMETRY_SHADER_INVOCATIONS_BIT = 0x00000008;
+        const VK_QUERY_PIPELINE_STATISTIC_GEOMETRY_SHADER_PRIMITIVES_BIT = 0x00000010;
+        const VK_QUERY_PIPELINE_STATISTIC_CLIPPING_INVOCATIONS_BIT = 0x00000020;
+        const VK_QUERY_PIPELINE_STATISTIC_CLIPPING_PRIMITIVES_BIT = 0x00000040;
+        const VK_QUERY_PIPELINE_STATISTIC_FRAGMENT_SHADER_INVOCATIONS_BIT = 0x00000080;
+        const VK_QUERY_PIPELINE_STATISTIC_TESSELLATION_CONTROL_SHADER_PATCHES_BIT = 0x00000100;
+        const VK_QUERY_PIPELINE_STATISTIC_TESSELLATION_EVALUATION_SHADER_INVOCATIONS_BIT = 0x00000200;
+        const VK_QUERY_PIPELINE_STATISTIC_COMPUTE_SHADER_INVOCATIONS_BIT = 0x00000400;
+    }
+}
+
+bitflags! {
+    pub struct QueryControlFlags: u32 {
+        const VK_QUERY_CONTROL_PRECISE_BIT = 0x00000001;
+        const VK_QUERY_CONTROL_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
+    }
+}
+
+bitflags! {
+    pub struct QueryResultFlags: u32 {
+        const VK_QUERY_RESULT_64_BIT = 0x00000001;
+        const VK_QUERY_RESULT_WAIT_BIT = 0x00000002;
         const VK_QUERY_RESULT_WITH_AVAILABILITY_BIT = 0x00000004;
         const VK_QUERY_RESULT_PARTIAL_BIT = 0x00000008;
         const VK_QUERY_RESULT_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WaitStatus {
+    Signaled,
+    Timeout,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub enum Error {
     NotReady = 1,
+    OutOfHostMemory = -1,
+    OutOfDeviceMemory = -2,
+    InitializationFailed = -3,
+    DeviceLost = -4,
+    MemoryMapFailed = -5,
+    LayerNotPresent = -6,
+    ExtensionNotPresent = -7,
+    FeatureNotPresent = -8,
+    IncompatibleDriver = -9,
+    TooManyObjects = -10,
+    FormatNotSupported = -11,
+    FragmentedPool = -12,
+    /// `VK_ERROR_INVALID_SHADER_NV`: a shader module is not valid SPIR-V.
+    InvalidShader = -1000012000,
+    /// `VK_ERROR_VALIDATION_FAILED_EXT`: the call violates the spec, and vk-middle refused it
+    /// instead of passing it on.
+    ValidationFailed = -1000011001,
 }
 
 impl From<Error> for sys::VkResult {
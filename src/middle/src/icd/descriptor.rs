@@ -0,0 +1,132 @@
+//! Descriptor set updates, resolved from the raw `vkUpdateDescriptorSets` arguments into the
+//! backend's own objects.
+
+use super::*;
+
+use std::collections::HashSet;
+
+/// An element written to an image, sampler or input attachment descriptor.
+///
+/// Which fields are present follows the descriptor type: `sampler` is only set for sampler and
+/// combined image sampler descriptors (and not when the binding uses immutable samplers), and
+/// `image_view` is set for every type except plain samplers.
+pub struct DescriptorImageInfo<'a, I: Impl> {
+    pub sampler: Option<&'a I::Sampler>,
+    pub image_view: Option<&'a I::ImageView>,
+    pub image_layout: ImageLayout,
+}
+
+/// An element written to a uniform or storage buffer descriptor.
+pub struct DescriptorBufferInfo<'a, I: Impl> {
+    pub buffer: &'a I::Buffer,
+    pub offset: u64,
+    /// May be `WHOLE_SIZE`, meaning the rest of the buffer after `offset`.
+    pub range: u64,
+}
+
+/// The elements of a single write, one per consecutive array element starting at the write's
+/// `array_element`.
+pub enum Descriptors<'a, I: Impl> {
+    Images(Vec<DescriptorImageInfo<'a, I>>),
+    Buffers(Vec<DescriptorBufferInfo<'a, I>>),
+    TexelBuffers(Vec<&'a I::BufferView>),
+}
+
+pub struct DescriptorWrite<'a, I: Impl> {
+    pub set: &'a I::DescriptorSet,
+    pub binding: u32,
+    /// Writes that run past the end of `binding` continue into the following bindings.
+    pub array_element: u32,
+    pub descriptor_type: DescriptorType,
+    pub descriptors: Descriptors<'a, I>,
+}
+
+pub struct DescriptorCopy<'a, I: Impl> {
+    pub src_set: &'a I::DescriptorSet,
+    pub src_binding: u32,
+    pub src_array_element: u32,
+    pub dst_set: &'a I::DescriptorSet,
+    pub dst_binding: u32,
+    pub dst_array_element: u32,
+    pub descriptor_count: u32,
+}
+
+impl<'a, I: Impl> DescriptorWrite<'a, I> {
+    pub(crate) unsafe fn from_raw(raw: &'a sys::VkWriteDescriptorSet) -> DescriptorWrite<'a, I> {
+        let descriptor_type: DescriptorType = raw.descriptorType.into();
+
+        let descriptors = match descriptor_type {
+            DescriptorType::Sampler |
+            DescriptorType::CombinedImageSampler |
+            DescriptorType::SampledImage |
+            DescriptorType::StorageImage |
+            DescriptorType::InputAttachment => {
+                let with_sampler = descriptor_type == DescriptorType::Sampler ||
+                    descriptor_type == DescriptorType::CombinedImageSampler;
+                let with_view = descriptor_type != DescriptorType::Sampler;
+
+                Descriptors::Images(raw_slice(raw.pImageInfo, raw.descriptorCount).iter()
+                    .map(|info| DescriptorImageInfo {
+                        sampler: if with_sampler { handle::cast_opt(info.sampler) } else { None },
+                        image_view: if with_view { handle::cast_opt(info.imageView) } else { None },
+                        image_layout: info.imageLayout.into(),
+                    })
+                    .collect())
+            },
+            DescriptorType::UniformTexelBuffer |
+            DescriptorType::StorageTexelBuffer => {
+                Descriptors::TexelBuffers(raw_slice(raw.pTexelBufferView, raw.descriptorCount).iter()
+                    .map(|&view| handle::cast(view))
+                    .collect())
+            },
+            _ => {
+                Descriptors::Buffers(raw_slice(raw.pBufferInfo, raw.descriptorCount).iter()
+                    .map(|info| DescriptorBufferInfo {
+                        buffer: handle::cast(info.buffer),
+                        offset: info.offset,
+                        range: info.range,
+                    })
+                    .collect())
+            },
+        };
+
+        DescriptorWrite {
+            set: handle::cast(raw.dstSet),
+            binding: raw.dstBinding,
+            array_element: raw.dstArrayElement,
+            descriptor_type: descriptor_type,
+            descriptors: descriptors,
+        }
+    }
+}
+
+impl<'a, I: Impl> DescriptorCopy<'a, I> {
+    pub(crate) unsafe fn from_raw(raw: &'a sys::VkCopyDescriptorSet) -> DescriptorCopy<'a, I> {
+        DescriptorCopy {
+            src_set: handle::cast(raw.srcSet),
+            src_binding: raw.srcBinding,
+            src_array_element: raw.srcArrayElement,
+            dst_set: handle::cast(raw.dstSet),
+            dst_binding: raw.dstBinding,
+            dst_array_element: raw.dstArrayElement,
+            descriptor_count: raw.descriptorCount,
+        }
+    }
+}
+
+/// What a descriptor pool handle refers to. vk-middle keeps track of the sets allocated from each
+/// pool so it can release them on reset and destruction.
+pub(crate) struct DescriptorPoolObject<I: Impl> {
+    pub imp: I::DescriptorPool,
+    pub flags: DescriptorPoolCreateFlags,
+    pub sets: HashSet<sys::VkDescriptorSet>,
+}
+
+impl<I: Impl> DescriptorPoolObject<I> {
+    /// Takes back every set allocated from the pool.
+    pub unsafe fn take_sets(&mut self) -> Vec<I::DescriptorSet> {
+        self.sets.drain()
+            .filter_map(|set| handle::take::<I::DescriptorSet, _>(set))
+            .collect()
+    }
+}
@@ -0,0 +1,361 @@
+//! Mapping between backend objects and non-dispatchable handles.
+//!
+//! By default a handle is simply the address of a boxed backend object. With the `checked-handles`
+//! feature every object instead lives in a generation-checked slot, so that a stale or mistyped
+//! handle is reported instead of dereferenced.
+
+use ::sys;
+
+use std::{fmt, process};
+
+pub trait NonDispatchableHandle: Copy {
+    fn from_raw(raw: u64) -> Self;
+    fn as_raw(self) -> u64;
+
+    fn null() -> Self { Self::from_raw(0) }
+    fn is_null(self) -> bool { self.as_raw() == 0 }
+}
+
+macro_rules! vulkan_non_dispatchable_handles {
+    ($($name:ident)*) => {
+        $(
+            impl NonDispatchableHandle for sys::$name {
+                #[cfg(target_pointer_width = "64")]
+                fn from_raw(raw: u64) -> Self { raw as usize as _ }
+                #[cfg(target_pointer_width = "64")]
+                fn as_raw(self) -> u64 { self as usize as u64 }
+
+                #[cfg(not(target_pointer_width = "64"))]
+                fn from_raw(raw: u64) -> Self { raw }
+                #[cfg(not(target_pointer_width = "64"))]
+                fn as_raw(self) -> u64 { self }
+            }
+        )*
+    }
+}
+
+vulkan_non_dispatchable_handles! {
+    VkSemaphore
+    VkFence
+    VkDeviceMemory
+    VkBuffer
+    VkImage
+    VkEvent
+    VkQueryPool
+    VkBufferView
+    VkImageView
+    VkShaderModule
+    VkPipelineCache
+    VkPipelineLayout
+    VkRenderPass
+    VkPipeline
+    VkDescriptorSetLayout
+    VkSampler
+    VkDescriptorPool
+    VkDescriptorSet
+    VkFramebuffer
+    VkCommandPool
+}
+
+/// Why a handle failed to resolve to a live object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    /// The handle was never handed out.
+    Invalid,
+    /// The object behind the handle has been destroyed.
+    Stale,
+    /// The handle refers to an object of another type.
+    WrongType,
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            HandleError::Invalid => "invalid handle",
+            HandleError::Stale => "stale handle used after its object was destroyed",
+            HandleError::WrongType => "handle refers to an object of a different type",
+        })
+    }
+}
+
+/// Moves `value` to the heap and returns a handle referring to it.
+pub fn form<T: 'static, H: NonDispatchableHandle>(value: T) -> H {
+    H::from_raw(imp::insert(Box::new(value)))
+}
+
+/// Resolves a handle that must not be null to its object.
+///
+/// Without `checked-handles` every handle resolves.
+pub unsafe fn lookup<T: 'static, H: NonDispatchableHandle>(handle: H) -> Result<*mut T, HandleError> {
+    imp::lookup::<T>(handle.as_raw())
+}
+
+/// Takes back ownership of the object behind a handle that must not be null, invalidating it.
+pub unsafe fn remove<T: 'static, H: NonDispatchableHandle>(handle: H) -> Result<Box<T>, HandleError> {
+    imp::remove::<T>(handle.as_raw())
+}
+
+// Commands have no way to report a bad handle, and going on would be undefined behaviour.
+fn or_abort<T, H: NonDispatchableHandle>(result: Result<T, HandleError>, handle: H) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            error!("{} {:#x}", err, handle.as_raw());
+            process::abort()
+        },
+    }
+}
+
+/// Borrows the object behind a handle that must not be null.
+///
+/// Aborts the process if the handle does not resolve.
+pub unsafe fn cast<'a, T: 'static, H: NonDispatchableHandle>(handle: H) -> &'a T {
+    &*or_abort(lookup::<T, _>(handle), handle)
+}
+
+/// Mutably borrows the object behind a handle that must not be null.
+///
+/// Aborts the process if the handle does not resolve.
+pub unsafe fn cast_mut<'a, T: 'static, H: NonDispatchableHandle>(handle: H) -> &'a mut T {
+    &mut *or_abort(lookup::<T, _>(handle), handle)
+}
+
+/// Borrows the object behind a handle, treating `VK_NULL_HANDLE` as `None`.
+pub unsafe fn cast_opt<'a, T: 'static, H: NonDispatchableHandle>(handle: H) -> Option<&'a T> {
+    if handle.is_null() {
+        None
+    } else {
+        Some(cast(handle))
+    }
+}
+
+/// Mutably borrows the object behind a handle, treating `VK_NULL_HANDLE` as `None`.
+pub unsafe fn cast_opt_mut<'a, T: 'static, H: NonDispatchableHandle>(handle: H) -> Option<&'a mut T> {
+    if handle.is_null() {
+        None
+    } else {
+        Some(cast_mut(handle))
+    }
+}
+
+/// Takes back ownership of the object behind a handle, invalidating it.
+///
+/// Destroying `VK_NULL_HANDLE` is legal in Vulkan, so a null handle yields `None`. Aborts the
+/// process if any other handle does not resolve.
+pub unsafe fn take<T: 'static, H: NonDispatchableHandle>(handle: H) -> Option<T> {
+    if handle.is_null() {
+        None
+    } else {
+        Some(*or_abort(remove::<T, _>(handle), handle))
+    }
+}
+
+#[cfg(not(feature = "checked-handles"))]
+mod imp {
+    use super::HandleError;
+
+    use std::mem;
+
+    // Boxes of zero-sized objects all share one dangling address, so such objects are given a byte
+    // after them to make their handles distinct.
+    #[repr(C)]
+    struct Padded<T>(T, u8);
+
+    pub fn insert<T: 'static>(value: Box<T>) -> u64 {
+        if mem::size_of::<T>() == 0 {
+            Box::into_raw(Box::new(Padded(*value, 0))) as usize as u64
+        } else {
+            Box::into_raw(value) as usize as u64
+        }
+    }
+
+    pub unsafe fn lookup<T: 'static>(raw: u64) -> Result<*mut T, HandleError> {
+        Ok(raw as usize as *mut T)
+    }
+
+    pub unsafe fn remove<T: 'static>(raw: u64) -> Result<Box<T>, HandleError> {
+        if mem::size_of::<T>() == 0 {
+            Ok(Box::new(Box::from_raw(raw as usize as *mut Padded<T>).0))
+        } else {
+            Ok(Box::from_raw(raw as usize as *mut T))
+        }
+    }
+}
+
+#[cfg(feature = "checked-handles")]
+mod imp {
+    use super::HandleError;
+
+    use std::any::TypeId;
+    use std::sync::Mutex;
+
+    struct Slot {
+        generation: u32,
+        value: Option<(usize, TypeId)>,
+    }
+
+    struct Slots {
+        slots: Vec<Slot>,
+        free: Vec<u32>,
+    }
+
+    lazy_static! {
+        static ref SLOTS: Mutex<Slots> = Mutex::new(Slots {
+            slots: Vec::new(),
+            free: Vec::new(),
+        });
+    }
+
+    // Index zero is never handed out so that no live object ever maps to VK_NULL_HANDLE.
+    fn split(raw: u64) -> (usize, u32) {
+        (((raw & 0xffffffff) as usize).wrapping_sub(1), (raw >> 32) as u32)
+    }
+
+    fn join(index: usize, generation: u32) -> u64 {
+        ((generation as u64) << 32) | (index as u64 + 1)
+    }
+
+    fn check<T: 'static>(slots: &Slots, raw: u64) -> Result<usize, HandleError> {
+        let (index, generation) = split(raw);
+        match slots.slots.get(index) {
+            Some(&Slot { generation: slot_generation, value: Some((ptr, type_id)) }) if slot_generation == generation => {
+                if type_id == TypeId::of::<T>() {
+                    Ok(ptr)
+                } else {
+                    Err(HandleError::WrongType)
+                }
+            },
+            // A generation from the future was never handed out
+            Some(&Slot { generation: slot_generation, .. }) if generation != 0 && generation < slot_generation => Err(HandleError::Stale),
+            _ => Err(HandleError::Invalid),
+        }
+    }
+
+    pub fn insert<T: 'static>(value: Box<T>) -> u64 {
+        let mut slots = SLOTS.lock().unwrap();
+        let entry = Some((Box::into_raw(value) as usize, TypeId::of::<T>()));
+
+        if let Some(index) = slots.free.pop() {
+            let slot = &mut slots.slots[index as usize];
+            slot.value = entry;
+            join(index as usize, slot.generation)
+        } else {
+            slots.slots.push(Slot { generation: 1, value: entry });
+            join(slots.slots.len() - 1, 1)
+        }
+    }
+
+    pub unsafe fn lookup<T: 'static>(raw: u64) -> Result<*mut T, HandleError> {
+        let slots = SLOTS.lock().unwrap();
+        check::<T>(&slots, raw).map(|ptr| ptr as *mut T)
+    }
+
+    pub unsafe fn remove<T: 'static>(raw: u64) -> Result<Box<T>, HandleError> {
+        let mut slots = SLOTS.lock().unwrap();
+        let ptr = try!(check::<T>(&slots, raw));
+        let (index, _) = split(raw);
+
+        {
+            let slot = &mut slots.slots[index];
+            slot.value = None;
+            slot.generation = slot.generation.wrapping_add(1);
+        }
+        slots.free.push(index as u32);
+
+        Ok(Box::from_raw(ptr as *mut T))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+    struct Empty;
+
+    impl Drop for Empty {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    static DROPS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    #[test]
+    fn zero_sized_objects_get_handles_of_their_own() {
+        let first: sys::VkDescriptorSet = form(Empty);
+        let second: sys::VkDescriptorSet = form(Empty);
+        assert!(first.as_raw() != second.as_raw());
+
+        unsafe {
+            take::<Empty, _>(first);
+            take::<Empty, _>(second);
+        }
+        assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "checked-handles")]
+    #[test]
+    fn live_handles_resolve() {
+        let buffer: sys::VkBuffer = form(7u32);
+        unsafe {
+            assert_eq!(*lookup::<u32, _>(buffer).unwrap(), 7);
+            assert_eq!(*remove::<u32, _>(buffer).unwrap(), 7);
+        }
+    }
+
+    #[cfg(feature = "checked-handles")]
+    #[test]
+    fn stale_handles_are_reported() {
+        let buffer: sys::VkBuffer = form(7u32);
+        unsafe {
+            remove::<u32, _>(buffer).unwrap();
+            assert_eq!(lookup::<u32, _>(buffer), Err(HandleError::Stale));
+            assert_eq!(remove::<u32, _>(buffer).err(), Some(HandleError::Stale));
+        }
+    }
+
+    #[cfg(feature = "checked-handles")]
+    #[test]
+    fn handles_of_another_type_are_reported() {
+        let buffer: sys::VkBuffer = form(7u32);
+        unsafe {
+            assert_eq!(lookup::<u64, _>(buffer), Err(HandleError::WrongType));
+            assert_eq!(remove::<u64, _>(buffer).err(), Some(HandleError::WrongType));
+            // A failed removal leaves the object in place
+            assert_eq!(*remove::<u32, _>(buffer).unwrap(), 7);
+        }
+    }
+
+    #[cfg(feature = "checked-handles")]
+    #[test]
+    fn handles_never_handed_out_are_reported() {
+        let unknown = sys::VkBuffer::from_raw(0x7fffffff);
+        let future = sys::VkBuffer::from_raw(form::<u32, sys::VkBuffer>(7).as_raw() + (1 << 40));
+        unsafe {
+            assert_eq!(lookup::<u32, _>(unknown), Err(HandleError::Invalid));
+            assert_eq!(lookup::<u32, _>(future), Err(HandleError::Invalid));
+        }
+    }
+
+    #[cfg(feature = "checked-handles")]
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let handles = (0..16).map(|i| form::<u32, sys::VkBuffer>(i)).collect::<Vec<_>>();
+        for &handle in &handles {
+            unsafe { remove::<u32, _>(handle).unwrap(); }
+        }
+
+        let reused = (16..32).map(|i| form::<u32, sys::VkBuffer>(i)).collect::<Vec<_>>();
+        unsafe {
+            for &handle in &handles {
+                assert!(!reused.contains(&handle));
+                assert_eq!(lookup::<u32, _>(handle), Err(HandleError::Stale));
+            }
+            for (i, &handle) in reused.iter().enumerate() {
+                assert_eq!(*remove::<u32, _>(handle).unwrap(), 16 + i as u32);
+            }
+        }
+    }
+}
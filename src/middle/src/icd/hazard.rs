@@ -0,0 +1,1023 @@
+//! Detection of missing synchronization in recorded command streams.
+//!
+//! `HazardTracker` follows the accesses each command makes to buffer ranges and image
+//! subresources, and the pipeline barriers, events, subpass dependencies and semaphore waits that
+//! order them. Read-after-write, write-after-read and write-after-write pairs that nothing orders
+//! are reported, naming both commands. The analysis only looks at recorded streams and the
+//! information a `HazardSource` provides, so it does not depend on what the backend does with the
+//! commands.
+//!
+//! Accesses made by shaders through descriptors are not tracked, since they depend on the shader
+//! code. Buffer ranges whose size depends on an image format (the buffer side of buffer/image
+//! copies) and vertex buffer ranges are assumed to extend to the end of the buffer.
+
+use super::*;
+use super::recorded::{Command, CommandStream, ObjectRef};
+use super::layout::{LayoutSource, subresource_indices, layers_range};
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::u64;
+
+/// What the tracker needs to know about backend objects beyond what `LayoutSource` provides.
+pub trait HazardSource<I: Impl>: LayoutSource<I> {
+    /// `RenderPassCreateInfo::dependencies_with_implicit` for the info `render_pass` was created
+    /// from.
+    fn render_pass_dependencies(&self, render_pass: &I::RenderPass) -> Vec<SubpassDependency>;
+
+    /// Tells `buffer` apart from destroyed buffers that had the same address, as
+    /// `LayoutSource::image_serial` does for images.
+    fn buffer_serial(&self, _buffer: &I::Buffer) -> u64 { 0 }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum HazardKind {
+    ReadAfterWrite,
+    WriteAfterRead,
+    WriteAfterWrite,
+}
+
+/// Identifies a command by where it was submitted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HazardCommand {
+    pub name: &'static str,
+    /// Counts the streams passed to the tracker, starting from zero.
+    pub submission: usize,
+    /// Index of the command in its stream. Commands recorded in secondary command buffers use the
+    /// index of the `vkCmdExecuteCommands` that ran them.
+    pub command: usize,
+}
+
+pub enum Resource<I: Impl> {
+    Buffer(ObjectRef<I::Buffer>),
+    Image(ObjectRef<I::Image>),
+}
+
+impl<I: Impl> Clone for Resource<I> {
+    fn clone(&self) -> Resource<I> { *self }
+}
+
+impl<I: Impl> Copy for Resource<I> {}
+
+impl<I: Impl> PartialEq for Resource<I> {
+    fn eq(&self, other: &Resource<I>) -> bool {
+        match (*self, *other) {
+            (Resource::Buffer(a), Resource::Buffer(b)) => a == b,
+            (Resource::Image(a), Resource::Image(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<I: Impl> Eq for Resource<I> {}
+
+impl<I: Impl> Hash for Resource<I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            Resource::Buffer(buffer) => { 0u8.hash(state); buffer.hash(state) },
+            Resource::Image(image) => { 1u8.hash(state); image.hash(state) },
+        }
+    }
+}
+
+/// Two commands accessing overlapping parts of a resource without synchronization between them.
+pub struct Hazard<I: Impl> {
+    pub kind: HazardKind,
+    pub resource: Resource<I>,
+    pub first: HazardCommand,
+    pub second: HazardCommand,
+}
+
+impl<I: Impl> Clone for Hazard<I> {
+    fn clone(&self) -> Hazard<I> {
+        Hazard {
+            kind: self.kind,
+            resource: self.resource,
+            first: self.first,
+            second: self.second,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Access {
+    command: HazardCommand,
+    /// Position in the order the tracker saw accesses in, used to decide which accesses precede
+    /// a `vkCmdSetEvent`.
+    sequence: u64,
+    stages: PipelineStageFlags,
+    access: AccessFlags,
+}
+
+#[derive(Clone, Copy)]
+struct WriteState {
+    write: Access,
+    /// Whether a barrier made the write available.
+    available: bool,
+    /// Stages that wait on the write, through barriers possibly chained together.
+    visible_stages: PipelineStageFlags,
+    /// Accesses the write has been made visible to.
+    visible_access: AccessFlags,
+}
+
+#[derive(Clone, Copy)]
+struct ReadState {
+    read: Access,
+    /// Stages that wait on the read.
+    barrier_stages: PipelineStageFlags,
+}
+
+/// The accesses to one range of a resource since it was last written. Image subresources are
+/// numbered as by `layout::subresource_indices`. The regions of a resource never overlap.
+#[derive(Clone)]
+struct Region {
+    range: Range<u64>,
+    write: Option<WriteState>,
+    reads: Vec<ReadState>,
+}
+
+/// A barrier in the form all synchronization commands are reduced to.
+struct Barrier {
+    src_stages: PipelineStageFlags,
+    dst_stages: PipelineStageFlags,
+    src_access: AccessFlags,
+    dst_access: AccessFlags,
+    /// Only accesses before this sequence number are ordered by the barrier.
+    before: u64,
+}
+
+fn graphics_stages() -> PipelineStageFlags {
+    VK_PIPELINE_STAGE_DRAW_INDIRECT_BIT | VK_PIPELINE_STAGE_VERTEX_INPUT_BIT | VK_PIPELINE_STAGE_VERTEX_SHADER_BIT |
+        VK_PIPELINE_STAGE_TESSELLATION_CONTROL_SHADER_BIT | VK_PIPELINE_STAGE_TESSELLATION_EVALUATION_SHADER_BIT |
+        VK_PIPELINE_STAGE_GEOMETRY_SHADER_BIT | VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT |
+        VK_PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT | VK_PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT |
+        VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+}
+
+/// Expands the shorthand stages. `everything` is the stage that means "all stages" on this side
+/// of a dependency (`BOTTOM_OF_PIPE` for the source, `TOP_OF_PIPE` for the destination).
+fn expand_stages(stages: PipelineStageFlags, everything: PipelineStageFlags) -> PipelineStageFlags {
+    if stages.intersects(VK_PIPELINE_STAGE_ALL_COMMANDS_BIT | everything) {
+        PipelineStageFlags::all()
+    } else if stages.contains(VK_PIPELINE_STAGE_ALL_GRAPHICS_BIT) {
+        stages | graphics_stages()
+    } else {
+        stages
+    }
+}
+
+fn covers_access(mask: AccessFlags, access: AccessFlags, write: bool) -> bool {
+    let all = if write { VK_ACCESS_MEMORY_WRITE_BIT } else { VK_ACCESS_MEMORY_READ_BIT };
+    mask.intersects(all) || mask.intersects(access)
+}
+
+impl Barrier {
+    fn new(src_stages: PipelineStageFlags, dst_stages: PipelineStageFlags, src_access: AccessFlags, dst_access: AccessFlags, before: u64) -> Barrier {
+        Barrier {
+            src_stages: expand_stages(src_stages, VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT),
+            dst_stages: expand_stages(dst_stages, VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT),
+            src_access: src_access,
+            dst_access: dst_access,
+            before: before,
+        }
+    }
+
+    fn apply(&self, region: &mut Region) {
+        if let Some(ref mut write) = region.write {
+            if write.write.sequence < self.before &&
+                self.src_stages.intersects(write.write.stages | write.visible_stages)
+            {
+                write.visible_stages |= self.dst_stages;
+                if covers_access(self.src_access, write.write.access, true) {
+                    write.available = true;
+                }
+                if write.available {
+                    write.visible_access |= self.dst_access;
+                }
+            }
+        }
+        for read in &mut region.reads {
+            if read.read.sequence < self.before &&
+                self.src_stages.intersects(read.read.stages | read.barrier_stages)
+            {
+                read.barrier_stages |= self.dst_stages;
+            }
+        }
+    }
+}
+
+/// Binding state of a stream, which secondary command buffers do not inherit.
+struct StreamState<I: Impl> {
+    index_buffer: Option<(ObjectRef<I::Buffer>, u64, IndexType)>,
+    vertex_buffers: Vec<Option<(ObjectRef<I::Buffer>, u64)>>,
+}
+
+/// Follows resource accesses through command streams in queue order.
+///
+/// Like `LayoutTracker`, the tracker can be cloned to go back to an earlier state.
+pub struct HazardTracker<I: Impl> {
+    /// The regions of each resource, with the serial number of the resource they belong to.
+    regions: HashMap<Resource<I>, (u64, Vec<Region>)>,
+    events: HashMap<ObjectRef<I::Event>, u64>,
+    /// The dependencies of the render pass instance being recorded, if any.
+    render_pass_dependencies: Option<Vec<SubpassDependency>>,
+    sequence: u64,
+    submission: usize,
+    hazards: Vec<Hazard<I>>,
+}
+
+impl<I: Impl> Clone for HazardTracker<I> {
+    fn clone(&self) -> HazardTracker<I> {
+        HazardTracker {
+            regions: self.regions.clone(),
+            events: self.events.clone(),
+            render_pass_dependencies: self.render_pass_dependencies.clone(),
+            sequence: self.sequence,
+            submission: self.submission,
+            hazards: self.hazards.clone(),
+        }
+    }
+}
+
+impl<I: Impl<CommandBuffer = RecordedCommandBuffer<I>>> HazardTracker<I> {
+    pub fn new() -> HazardTracker<I> {
+        HazardTracker {
+            regions: HashMap::new(),
+            events: HashMap::new(),
+            render_pass_dependencies: None,
+            sequence: 0,
+            submission: 0,
+            hazards: Vec::new(),
+        }
+    }
+
+    /// The hazards found since the last call.
+    pub fn take_hazards(&mut self) -> Vec<Hazard<I>> {
+        mem::replace(&mut self.hazards, Vec::new())
+    }
+
+    /// Drops what the tracker knows about a resource. Call this when the resource is destroyed,
+    /// since a new one may later be created at the same address.
+    pub fn forget(&mut self, resource: Resource<I>) {
+        self.regions.remove(&resource);
+    }
+
+    /// Follows the command buffers of a submission, after waiting on its semaphores.
+    pub fn submit<S: HazardSource<I>>(&mut self, source: &S, submit: &SubmitInfo<I>) {
+        for (_, stages) in submit.wait_semaphores() {
+            let barrier = Barrier::new(
+                VK_PIPELINE_STAGE_ALL_COMMANDS_BIT,
+                stages,
+                VK_ACCESS_MEMORY_WRITE_BIT,
+                VK_ACCESS_MEMORY_READ_BIT | VK_ACCESS_MEMORY_WRITE_BIT,
+                u64::MAX,
+            );
+            self.global_barrier(&barrier);
+        }
+        for command_buffer in submit.command_buffers() {
+            self.execute(source, command_buffer.stream());
+        }
+    }
+
+    /// Follows `stream` as executed after every stream previously passed to the tracker.
+    pub fn execute<S: HazardSource<I>>(&mut self, source: &S, stream: &CommandStream<I>) {
+        self.execute_stream(source, stream, None);
+        self.submission += 1;
+    }
+
+    fn execute_stream<S: HazardSource<I>>(&mut self, source: &S, stream: &CommandStream<I>, parent_command: Option<usize>) {
+        let mut state = StreamState::<I> {
+            index_buffer: None,
+            vertex_buffers: Vec::new(),
+        };
+
+        for (index, command) in stream.commands().iter().enumerate() {
+            let command_id = HazardCommand {
+                name: command.name(),
+                submission: self.submission,
+                command: parent_command.unwrap_or(index),
+            };
+
+            match *command {
+                Command::BindIndexBuffer { buffer, offset, index_type } => {
+                    state.index_buffer = Some((buffer, offset, index_type));
+                },
+                Command::BindVertexBuffers { first_binding, buffers, offsets } => {
+                    let first_binding = first_binding as usize;
+                    let buffers = stream.get(buffers);
+                    if state.vertex_buffers.len() < first_binding + buffers.len() {
+                        state.vertex_buffers.resize(first_binding + buffers.len(), None);
+                    }
+                    for (i, (&buffer, &offset)) in buffers.iter().zip(stream.get(offsets).iter()).enumerate() {
+                        state.vertex_buffers[first_binding + i] = Some((buffer, offset));
+                    }
+                },
+                Command::Draw { .. } => {
+                    self.vertex_reads(source, &state, command_id);
+                },
+                Command::DrawIndexed { index_count, first_index, .. } => {
+                    self.vertex_reads(source, &state, command_id);
+                    if let Some((buffer, offset, index_type)) = state.index_buffer {
+                        let size = if index_type == IndexType::Uint16 { 2 } else { 4 };
+                        let start = offset + first_index as u64 * size;
+                        self.buffer_access(source, command_id, buffer, start, index_count as u64 * size,
+                            VK_PIPELINE_STAGE_VERTEX_INPUT_BIT, VK_ACCESS_INDEX_READ_BIT, false);
+                    }
+                },
+                Command::DrawIndirect { buffer, offset, draw_count, stride } |
+                Command::DrawIndexedIndirect { buffer, offset, draw_count, stride } => {
+                    self.vertex_reads(source, &state, command_id);
+                    if let Command::DrawIndexedIndirect { .. } = *command {
+                        if let Some((index_buffer, index_offset, _)) = state.index_buffer {
+                            self.buffer_access(source, command_id, index_buffer, index_offset, WHOLE_SIZE,
+                                VK_PIPELINE_STAGE_VERTEX_INPUT_BIT, VK_ACCESS_INDEX_READ_BIT, false);
+                        }
+                    }
+                    let size = if draw_count == 0 { 0 } else { (draw_count as u64 - 1) * stride as u64 + 20 };
+                    self.buffer_access(source, command_id, buffer, offset, size,
+                        VK_PIPELINE_STAGE_DRAW_INDIRECT_BIT, VK_ACCESS_INDIRECT_COMMAND_READ_BIT, false);
+                },
+                Command::DispatchIndirect { buffer, offset } => {
+                    self.buffer_access(source, command_id, buffer, offset, 12,
+                        VK_PIPELINE_STAGE_DRAW_INDIRECT_BIT, VK_ACCESS_INDIRECT_COMMAND_READ_BIT, false);
+                },
+
+                Command::CopyBuffer { src, dst, regions } => {
+                    for region in stream.get(regions) {
+                        self.transfer_read_buffer(source, command_id, src, region.src_offset, region.size);
+                        self.transfer_write_buffer(source, command_id, dst, region.dst_offset, region.size);
+                    }
+                },
+                Command::CopyImage { src, dst, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.transfer_image(source, command_id, src, layers_range(&region.src_subresource), false);
+                        self.transfer_image(source, command_id, dst, layers_range(&region.dst_subresource), true);
+                    }
+                },
+                Command::BlitImage { src, dst, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.transfer_image(source, command_id, src, layers_range(&region.src_subresource), false);
+                        self.transfer_image(source, command_id, dst, layers_range(&region.dst_subresource), true);
+                    }
+                },
+                Command::ResolveImage { src, dst, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.transfer_image(source, command_id, src, layers_range(&region.src_subresource), false);
+                        self.transfer_image(source, command_id, dst, layers_range(&region.dst_subresource), true);
+                    }
+                },
+                Command::CopyBufferToImage { src, dst, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.transfer_read_buffer(source, command_id, src, region.buffer_offset, WHOLE_SIZE);
+                        self.transfer_image(source, command_id, dst, layers_range(&region.image_subresource), true);
+                    }
+                },
+                Command::CopyImageToBuffer { src, dst, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.transfer_image(source, command_id, src, layers_range(&region.image_subresource), false);
+                        self.transfer_write_buffer(source, command_id, dst, region.buffer_offset, WHOLE_SIZE);
+                    }
+                },
+                Command::UpdateBuffer { dst, offset, data } => {
+                    self.transfer_write_buffer(source, command_id, dst, offset, data.len() as u64);
+                },
+                Command::FillBuffer { dst, offset, size, .. } => {
+                    self.transfer_write_buffer(source, command_id, dst, offset, size);
+                },
+                Command::ClearColorImage { image, ranges, .. } |
+                Command::ClearDepthStencilImage { image, ranges, .. } => {
+                    for &range in stream.get(ranges) {
+                        self.transfer_image(source, command_id, image, range, true);
+                    }
+                },
+                Command::CopyQueryPoolResults { query_count, dst, offset, stride, .. } => {
+                    let size = if query_count == 0 { 0 } else { (query_count as u64 - 1) * stride + 8 };
+                    self.transfer_write_buffer(source, command_id, dst, offset, size);
+                },
+
+                Command::SetEvent { event, .. } => {
+                    self.events.insert(event, self.sequence);
+                },
+                Command::WaitEvents { events, src_stages, dst_stages, memory_barriers, buffer_barriers, image_barriers } => {
+                    // Only accesses before the latest vkCmdSetEvent of any of the events are
+                    // ordered. Events set elsewhere (by the host, say) order everything.
+                    let before = stream.get(events).iter()
+                        .map(|event| self.events.get(event).cloned().unwrap_or(u64::MAX))
+                        .max()
+                        .unwrap_or(0);
+                    self.barriers(source, stream, src_stages, dst_stages, before, memory_barriers, buffer_barriers, image_barriers);
+                },
+                Command::PipelineBarrier { src_stages, dst_stages, memory_barriers, buffer_barriers, image_barriers, .. } => {
+                    self.barriers(source, stream, src_stages, dst_stages, u64::MAX, memory_barriers, buffer_barriers, image_barriers);
+                },
+
+                Command::BeginRenderPass { render_pass, framebuffer, .. } => {
+                    let (dependencies, usage, attachments) = unsafe {
+                        (
+                            source.render_pass_dependencies(render_pass.get()),
+                            source.render_pass_attachment_usage(render_pass.get()),
+                            source.framebuffer_attachments(framebuffer.get()),
+                        )
+                    };
+                    for dependency in dependencies.iter().filter(|x| x.src_subpass == SUBPASS_EXTERNAL) {
+                        self.global_barrier(&Barrier::new(dependency.src_stage_mask, dependency.dst_stage_mask,
+                            dependency.src_access_mask, dependency.dst_access_mask, u64::MAX));
+                    }
+                    // Attachment accesses within the render pass are ordered by the render pass
+                    // itself, so each attachment is treated as written once when it begins.
+                    for (usage, &(image, range)) in usage.iter().zip(attachments.iter()) {
+                        if usage.is_some() {
+                            self.image_access(source, command_id, image, range,
+                                VK_PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT | VK_PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT |
+                                    VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                                VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT | VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+                                true);
+                        }
+                    }
+                    self.render_pass_dependencies = Some(dependencies);
+                },
+                Command::EndRenderPass => {
+                    if let Some(dependencies) = self.render_pass_dependencies.take() {
+                        for dependency in dependencies.iter().filter(|x| x.dst_subpass == SUBPASS_EXTERNAL) {
+                            self.global_barrier(&Barrier::new(dependency.src_stage_mask, dependency.dst_stage_mask,
+                                dependency.src_access_mask, dependency.dst_access_mask, u64::MAX));
+                        }
+                    }
+                },
+
+                Command::ExecuteCommands(command_buffers) => {
+                    for &command_buffer in stream.get(command_buffers) {
+                        let secondary = unsafe { command_buffer.get() }.stream();
+                        self.execute_stream(source, secondary, Some(index));
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn vertex_reads<S: HazardSource<I>>(&mut self, source: &S, state: &StreamState<I>, command: HazardCommand) {
+        for &(buffer, offset) in state.vertex_buffers.iter().filter_map(|x| x.as_ref()) {
+            self.buffer_access(source, command, buffer, offset, WHOLE_SIZE,
+                VK_PIPELINE_STAGE_VERTEX_INPUT_BIT, VK_ACCESS_VERTEX_ATTRIBUTE_READ_BIT, false);
+        }
+    }
+
+    fn transfer_read_buffer<S: HazardSource<I>>(&mut self, source: &S, command: HazardCommand, buffer: ObjectRef<I::Buffer>, offset: u64, size: u64) {
+        self.buffer_access(source, command, buffer, offset, size, VK_PIPELINE_STAGE_TRANSFER_BIT, VK_ACCESS_TRANSFER_READ_BIT, false);
+    }
+
+    fn transfer_write_buffer<S: HazardSource<I>>(&mut self, source: &S, command: HazardCommand, buffer: ObjectRef<I::Buffer>, offset: u64, size: u64) {
+        self.buffer_access(source, command, buffer, offset, size, VK_PIPELINE_STAGE_TRANSFER_BIT, VK_ACCESS_TRANSFER_WRITE_BIT, true);
+    }
+
+    fn transfer_image<S: HazardSource<I>>(&mut self, source: &S, command: HazardCommand, image: ObjectRef<I::Image>, range: ImageSubresourceRange, write: bool) {
+        let access = if write { VK_ACCESS_TRANSFER_WRITE_BIT } else { VK_ACCESS_TRANSFER_READ_BIT };
+        self.image_access(source, command, image, range, VK_PIPELINE_STAGE_TRANSFER_BIT, access, write);
+    }
+
+    fn buffer_access<S: HazardSource<I>>(
+        &mut self,
+        source: &S,
+        command: HazardCommand,
+        buffer: ObjectRef<I::Buffer>,
+        offset: u64,
+        size: u64,
+        stages: PipelineStageFlags,
+        access: AccessFlags,
+        write: bool,
+    ) {
+        let end = if size == WHOLE_SIZE { u64::MAX } else { offset.saturating_add(size) };
+        self.access(source, command, Resource::Buffer(buffer), offset..end, stages, access, write);
+    }
+
+    fn image_access<S: HazardSource<I>>(
+        &mut self,
+        source: &S,
+        command: HazardCommand,
+        image: ObjectRef<I::Image>,
+        range: ImageSubresourceRange,
+        stages: PipelineStageFlags,
+        access: AccessFlags,
+        write: bool,
+    ) {
+        for range in image_ranges(source, image, &range) {
+            self.access(source, command, Resource::Image(image), range, stages, access, write);
+        }
+    }
+
+    fn access<S: HazardSource<I>>(
+        &mut self,
+        source: &S,
+        command: HazardCommand,
+        resource: Resource<I>,
+        range: Range<u64>,
+        stages: PipelineStageFlags,
+        access: AccessFlags,
+        write: bool,
+    ) {
+        if range.start >= range.end {
+            return;
+        }
+
+        let current = Access {
+            command: command,
+            sequence: self.sequence,
+            stages: stages,
+            access: access,
+        };
+        self.sequence += 1;
+
+        let mut hazards = Vec::new();
+        {
+            let regions = self.resource_regions(source, resource);
+            split_regions(regions, &range);
+
+            for region in regions.iter_mut().filter(|x| contains(&range, &x.range)) {
+                if let Some(ref state) = region.write {
+                    let ordered = state.available &&
+                        state.visible_stages.intersects(stages) &&
+                        (write || covers_access(state.visible_access, access, false));
+                    if !ordered && state.write.command != command {
+                        let kind = if write { HazardKind::WriteAfterWrite } else { HazardKind::ReadAfterWrite };
+                        hazards.push((kind, state.write.command));
+                    }
+                }
+                if write {
+                    for read in &region.reads {
+                        if !read.barrier_stages.intersects(stages) && read.read.command != command {
+                            hazards.push((HazardKind::WriteAfterRead, read.read.command));
+                        }
+                    }
+                } else {
+                    // Unordered reads of the same kind are interchangeable for later checks, so
+                    // only the latest is kept, which keeps repeated draws from piling up reads.
+                    let read = ReadState { read: current, barrier_stages: PipelineStageFlags::empty() };
+                    let position = region.reads.iter().position(|x| {
+                        x.barrier_stages.is_empty() && x.read.stages == stages && x.read.access == access
+                    });
+                    match position {
+                        Some(i) => region.reads[i] = read,
+                        None => region.reads.push(read),
+                    }
+                }
+            }
+
+            if write {
+                // What the write replaces is no longer of interest.
+                regions.retain(|x| !contains(&range, &x.range));
+                regions.push(Region {
+                    range: range,
+                    write: Some(WriteState {
+                        write: current,
+                        available: false,
+                        visible_stages: PipelineStageFlags::empty(),
+                        visible_access: AccessFlags::empty(),
+                    }),
+                    reads: Vec::new(),
+                });
+            } else {
+                // Parts of the range nothing has accessed yet get regions of their own.
+                let mut covered: Vec<Range<u64>> = regions.iter()
+                    .filter(|x| contains(&range, &x.range))
+                    .map(|x| x.range.clone())
+                    .collect();
+                covered.sort_by_key(|x| x.start);
+                let mut start = range.start;
+                let mut gaps = Vec::new();
+                for covered in covered {
+                    if start < covered.start {
+                        gaps.push(start..covered.start);
+                    }
+                    start = cmp::max(start, covered.end);
+                }
+                if start < range.end {
+                    gaps.push(start..range.end);
+                }
+                for gap in gaps {
+                    regions.push(Region {
+                        range: gap,
+                        write: None,
+                        reads: vec![ReadState { read: current, barrier_stages: PipelineStageFlags::empty() }],
+                    });
+                }
+            }
+        }
+
+        for (kind, first) in hazards {
+            let duplicate = self.hazards.iter().any(|x| {
+                x.kind == kind && x.first == first && x.second == command && x.resource == resource
+            });
+            if duplicate {
+                continue;
+            }
+            warn!("{:?} hazard between {} (submission {}, command {}) and {} (submission {}, command {})",
+                kind, first.name, first.submission, first.command, command.name, command.submission, command.command);
+            self.hazards.push(Hazard {
+                kind: kind,
+                resource: resource,
+                first: first,
+                second: command,
+            });
+        }
+    }
+
+    fn barriers<S: HazardSource<I>>(
+        &mut self,
+        source: &S,
+        stream: &CommandStream<I>,
+        src_stages: PipelineStageFlags,
+        dst_stages: PipelineStageFlags,
+        before: u64,
+        memory_barriers: recorded::Slice<recorded::RecordedMemoryBarrier>,
+        buffer_barriers: recorded::Slice<recorded::RecordedBufferBarrier<I>>,
+        image_barriers: recorded::Slice<recorded::RecordedImageBarrier<I>>,
+    ) {
+        // A barrier without memory barriers is still an execution dependency, which is all a
+        // write-after-read needs.
+        self.global_barrier(&Barrier::new(src_stages, dst_stages, AccessFlags::empty(), AccessFlags::empty(), before));
+
+        for barrier in stream.get(memory_barriers) {
+            self.global_barrier(&Barrier::new(src_stages, dst_stages, barrier.src_access_mask, barrier.dst_access_mask, before));
+        }
+        for barrier in stream.get(buffer_barriers) {
+            let end = if barrier.size == WHOLE_SIZE { u64::MAX } else { barrier.offset.saturating_add(barrier.size) };
+            let applied = Barrier::new(src_stages, dst_stages, barrier.src_access_mask, barrier.dst_access_mask, before);
+            self.range_barrier(source, Resource::Buffer(barrier.buffer), barrier.offset..end, &applied);
+        }
+        for barrier in stream.get(image_barriers) {
+            let applied = Barrier::new(src_stages, dst_stages, barrier.src_access_mask, barrier.dst_access_mask, before);
+            for range in image_ranges(source, barrier.image, &barrier.subresource_range) {
+                self.range_barrier(source, Resource::Image(barrier.image), range, &applied);
+            }
+        }
+    }
+
+    fn global_barrier(&mut self, barrier: &Barrier) {
+        for &mut (_, ref mut regions) in self.regions.values_mut() {
+            for region in regions.iter_mut() {
+                barrier.apply(region);
+            }
+        }
+    }
+
+    fn range_barrier<S: HazardSource<I>>(&mut self, source: &S, resource: Resource<I>, range: Range<u64>, barrier: &Barrier) {
+        // Only the part of a region inside the range is ordered.
+        let regions = self.resource_regions(source, resource);
+        split_regions(regions, &range);
+        for region in regions.iter_mut().filter(|x| contains(&range, &x.range)) {
+            barrier.apply(region);
+        }
+    }
+
+    /// The regions of `resource`, which are dropped if they belonged to a resource destroyed since.
+    fn resource_regions<S: HazardSource<I>>(&mut self, source: &S, resource: Resource<I>) -> &mut Vec<Region> {
+        let serial = unsafe {
+            match resource {
+                Resource::Buffer(buffer) => source.buffer_serial(buffer.get()),
+                Resource::Image(image) => source.image_serial(image.get()),
+            }
+        };
+        let entry = self.regions.entry(resource).or_insert_with(|| (serial, Vec::new()));
+        if entry.0 != serial {
+            *entry = (serial, Vec::new());
+        }
+        &mut entry.1
+    }
+}
+
+fn contains(outer: &Range<u64>, inner: &Range<u64>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Splits the regions that straddle either end of `range`, so that each region is either inside
+/// `range` or outside of it.
+fn split_regions(regions: &mut Vec<Region>, range: &Range<u64>) {
+    if !regions.iter().any(|x| x.range.start < range.start && range.start < x.range.end ||
+        x.range.start < range.end && range.end < x.range.end)
+    {
+        return;
+    }
+
+    let mut split = Vec::with_capacity(regions.len() + 2);
+    for region in regions.drain(..) {
+        let mut bounds = vec![region.range.start];
+        for &at in &[range.start, range.end] {
+            if region.range.start < at && at < region.range.end {
+                bounds.push(at);
+            }
+        }
+        bounds.push(region.range.end);
+        for ends in bounds.windows(2) {
+            split.push(Region {
+                range: ends[0]..ends[1],
+                write: region.write,
+                reads: region.reads.clone(),
+            });
+        }
+    }
+    *regions = split;
+}
+
+/// The subresources of `range` as runs of consecutive subresource numbers.
+fn image_ranges<I: Impl, S: HazardSource<I>>(source: &S, image: ObjectRef<I::Image>, range: &ImageSubresourceRange) -> Vec<Range<u64>> {
+    let (mip_levels, array_layers) = source.image_subresource_counts(unsafe { image.get() });
+    let mut ranges: Vec<Range<u64>> = Vec::new();
+    for index in subresource_indices(mip_levels, array_layers, range) {
+        let index = index as u64;
+        let extends = match ranges.last() {
+            Some(last) => last.end == index,
+            None => false,
+        };
+        if extends {
+            ranges.last_mut().unwrap().end += 1;
+        } else {
+            ranges.push(index..(index + 1));
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_impl::*;
+
+    use std::ptr;
+
+    /// Images have one mip level and one array layer.
+    struct Source {
+        usage: Vec<Option<AttachmentUsage>>,
+        attachments: Vec<(ObjectRef<TestImage>, ImageSubresourceRange)>,
+        dependencies: Vec<SubpassDependency>,
+    }
+
+    impl LayoutSource<TestImpl> for Source {
+        fn image_subresource_counts(&self, _image: &TestImage) -> (u32, u32) { (1, 1) }
+        fn render_pass_attachment_usage(&self, _render_pass: &TestRenderPass) -> Vec<Option<AttachmentUsage>> { self.usage.clone() }
+        fn framebuffer_attachments(&self, _framebuffer: &TestFramebuffer) -> Vec<(ObjectRef<TestImage>, ImageSubresourceRange)> { self.attachments.clone() }
+    }
+
+    impl HazardSource<TestImpl> for Source {
+        fn render_pass_dependencies(&self, _render_pass: &TestRenderPass) -> Vec<SubpassDependency> { self.dependencies.clone() }
+    }
+
+    fn source() -> Source {
+        Source { usage: Vec::new(), attachments: Vec::new(), dependencies: Vec::new() }
+    }
+
+    /// Barriers and render pass begin infos name objects by handle, so the objects are given
+    /// handles, which live as long as the test process.
+    fn object<T: 'static, H: handle::NonDispatchableHandle>(value: T) -> (H, &'static T) {
+        let raw: H = handle::form(value);
+        (raw, unsafe { handle::cast(raw) })
+    }
+
+    fn allocate() -> RecordedCommandBuffer<TestImpl> {
+        let info = sys::VkCommandPoolCreateInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queueFamilyIndex: 0,
+        };
+        let mut pool = RecordedCommandPool::<TestImpl>::new(&TestDevice(0), CommandPoolCreateInfo(&info, PhantomData)).unwrap();
+        pool.allocate(&TestDevice(0), CommandBufferLevel::Primary, 1).unwrap().pop().unwrap()
+    }
+
+    fn copy(command_buffer: &mut RecordedCommandBuffer<TestImpl>, src: &TestBuffer, dst: &TestBuffer, offset: u64, size: u64) {
+        command_buffer.copy_buffer(src, dst, &[BufferCopy { src_offset: offset, dst_offset: offset, size: size }]);
+    }
+
+    fn memory_barrier(src_access: AccessFlags, dst_access: AccessFlags) -> sys::VkMemoryBarrier {
+        sys::VkMemoryBarrier {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: src_access.bits(),
+            dstAccessMask: dst_access.bits(),
+        }
+    }
+
+    /// A barrier between transfers, with the given memory barriers.
+    fn transfer_barrier(command_buffer: &mut RecordedCommandBuffer<TestImpl>, memory_barriers: &[sys::VkMemoryBarrier], buffer_barriers: &[sys::VkBufferMemoryBarrier]) {
+        let memory_barriers: Vec<_> = memory_barriers.iter().map(|x| MemoryBarrier(x, PhantomData)).collect();
+        let buffer_barriers: Vec<_> = buffer_barriers.iter().map(|x| BufferMemoryBarrier(x, PhantomData)).collect();
+        command_buffer.pipeline_barrier(VK_PIPELINE_STAGE_TRANSFER_BIT, VK_PIPELINE_STAGE_TRANSFER_BIT, DependencyFlags::empty(),
+            &memory_barriers, &buffer_barriers, &[]);
+    }
+
+    fn hazards(tracker: &mut HazardTracker<TestImpl>) -> Vec<(HazardKind, usize, usize)> {
+        let mut hazards: Vec<_> = tracker.take_hazards().iter().map(|x| (x.kind, x.first.command, x.second.command)).collect();
+        hazards.sort_by_key(|x| (x.2, x.1));
+        hazards
+    }
+
+    #[test]
+    fn unordered_transfers_are_hazards() {
+        let (a, b, c) = (TestBuffer(0), TestBuffer(1), TestBuffer(2));
+        let mut command_buffer = allocate();
+        copy(&mut command_buffer, &a, &b, 0, 16);
+        command_buffer.fill_buffer(&b, 0, 16, 0);
+        copy(&mut command_buffer, &b, &c, 0, 16);
+        command_buffer.update_buffer(&a, 12, &[0; 8]);
+        // Disjoint from everything before.
+        command_buffer.fill_buffer(&b, 16, WHOLE_SIZE, 0);
+
+        let mut tracker = HazardTracker::new();
+        tracker.execute(&source(), command_buffer.stream());
+        assert_eq!(hazards(&mut tracker), [
+            (HazardKind::WriteAfterWrite, 0, 1),
+            (HazardKind::ReadAfterWrite, 1, 2),
+            (HazardKind::WriteAfterRead, 0, 3),
+        ]);
+
+        // Streams are followed in order, and each is numbered.
+        let mut next = allocate();
+        copy(&mut next, &c, &a, 0, 4);
+        tracker.execute(&source(), next.stream());
+        let found = tracker.take_hazards();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().any(|x| x.kind == HazardKind::ReadAfterWrite && x.first.command == 2 && x.first.submission == 0 && x.second.submission == 1));
+        assert!(found.iter().any(|x| x.kind == HazardKind::WriteAfterRead && x.first.command == 0 && x.resource == Resource::Buffer(ObjectRef::new(&a))));
+    }
+
+    #[test]
+    fn pipeline_barriers_order_accesses() {
+        let (raw_b, b) = object::<_, sys::VkBuffer>(TestBuffer(1));
+        let c = TestBuffer(2);
+        let write_to_read = memory_barrier(VK_ACCESS_TRANSFER_WRITE_BIT, VK_ACCESS_TRANSFER_READ_BIT);
+        let write_to_shader = memory_barrier(VK_ACCESS_TRANSFER_WRITE_BIT, VK_ACCESS_SHADER_READ_BIT);
+
+        let mut command_buffer = allocate();
+        command_buffer.fill_buffer(b, 0, 16, 0);
+        transfer_barrier(&mut command_buffer, &[write_to_read], &[]);
+        copy(&mut command_buffer, b, &c, 0, 16);
+        // Write-after-read only needs execution ordering.
+        transfer_barrier(&mut command_buffer, &[], &[]);
+        command_buffer.fill_buffer(b, 0, 16, 0);
+        // Made visible to the wrong kind of access.
+        transfer_barrier(&mut command_buffer, &[write_to_shader], &[]);
+        copy(&mut command_buffer, b, &c, 0, 16);
+
+        let mut tracker = HazardTracker::new();
+        tracker.execute(&source(), command_buffer.stream());
+        assert_eq!(hazards(&mut tracker), [(HazardKind::ReadAfterWrite, 4, 6)]);
+
+        // A buffer barrier only orders the range it names.
+        let buffer_barrier = sys::VkBufferMemoryBarrier {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_BUFFER_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: VK_ACCESS_TRANSFER_WRITE_BIT.bits(),
+            dstAccessMask: VK_ACCESS_TRANSFER_READ_BIT.bits(),
+            srcQueueFamilyIndex: !0,
+            dstQueueFamilyIndex: !0,
+            buffer: raw_b,
+            offset: 0,
+            size: 8,
+        };
+        let mut command_buffer = allocate();
+        command_buffer.fill_buffer(b, 0, 16, 0);
+        transfer_barrier(&mut command_buffer, &[], &[buffer_barrier]);
+        copy(&mut command_buffer, b, &c, 0, 8);
+        copy(&mut command_buffer, b, &c, 8, 8);
+
+        let mut tracker = HazardTracker::new();
+        tracker.execute(&source(), command_buffer.stream());
+        assert_eq!(hazards(&mut tracker), [(HazardKind::ReadAfterWrite, 0, 3)]);
+    }
+
+    #[test]
+    fn partly_overwritten_regions_keep_only_the_rest() {
+        let (b, c) = (TestBuffer(1), TestBuffer(2));
+        let mut command_buffer = allocate();
+        command_buffer.fill_buffer(&b, 0, 16, 0);
+        command_buffer.fill_buffer(&b, 8, 16, 0);
+        copy(&mut command_buffer, &b, &c, 8, 8);
+        copy(&mut command_buffer, &b, &c, 0, 8);
+
+        let mut tracker = HazardTracker::new();
+        tracker.execute(&source(), command_buffer.stream());
+        let found = hazards(&mut tracker);
+        // The second fill alone wrote what the first copy reads. The second copy reads what only
+        // the first fill wrote, and writes where the first copy wrote.
+        assert_eq!(found, [
+            (HazardKind::WriteAfterWrite, 0, 1),
+            (HazardKind::ReadAfterWrite, 1, 2),
+            (HazardKind::ReadAfterWrite, 0, 3),
+        ]);
+    }
+
+    #[test]
+    fn events_order_what_precedes_the_set() {
+        let (b, c, d) = (TestBuffer(1), TestBuffer(2), TestBuffer(3));
+        let event = TestEvent(0);
+        let write_to_read = memory_barrier(VK_ACCESS_TRANSFER_WRITE_BIT, VK_ACCESS_TRANSFER_READ_BIT);
+
+        let mut command_buffer = allocate();
+        command_buffer.fill_buffer(&b, 0, 16, 0);
+        command_buffer.set_event(&event, VK_PIPELINE_STAGE_TRANSFER_BIT);
+        command_buffer.fill_buffer(&c, 0, 16, 0);
+        command_buffer.wait_events(&[&event], VK_PIPELINE_STAGE_TRANSFER_BIT, VK_PIPELINE_STAGE_TRANSFER_BIT,
+            &[MemoryBarrier(&write_to_read, PhantomData)], &[], &[]);
+        copy(&mut command_buffer, &b, &d, 0, 8);
+        copy(&mut command_buffer, &c, &d, 8, 8);
+
+        let mut tracker = HazardTracker::new();
+        tracker.execute(&source(), command_buffer.stream());
+        assert_eq!(hazards(&mut tracker), [(HazardKind::ReadAfterWrite, 2, 5)]);
+
+        // An event set outside the tracked streams, by the host say, orders everything.
+        let other = TestEvent(1);
+        let mut command_buffer = allocate();
+        command_buffer.fill_buffer(&b, 0, 16, 0);
+        command_buffer.wait_events(&[&other], VK_PIPELINE_STAGE_TRANSFER_BIT, VK_PIPELINE_STAGE_TRANSFER_BIT,
+            &[MemoryBarrier(&write_to_read, PhantomData)], &[], &[]);
+        copy(&mut command_buffer, &b, &d, 0, 16);
+        let mut tracker = HazardTracker::new();
+        tracker.execute(&source(), command_buffer.stream());
+        assert!(tracker.take_hazards().is_empty());
+    }
+
+    #[test]
+    fn subpass_dependencies_order_attachment_accesses() {
+        let (raw_render_pass, _) = object::<_, sys::VkRenderPass>(TestRenderPass(0));
+        let (raw_framebuffer, _) = object::<_, sys::VkFramebuffer>(TestFramebuffer(0));
+        let image = TestImage(0);
+        let buffer = TestBuffer(0);
+        let range = ImageSubresourceRange {
+            aspect_mask: VK_IMAGE_ASPECT_COLOR_BIT,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let usage = AttachmentUsage {
+            first_subpass: 0,
+            last_subpass: 0,
+            transitions: Vec::new(),
+            load_ops: (AttachmentLoadOp::Load, AttachmentLoadOp::DontCare),
+            store_ops: (AttachmentStoreOp::Store, AttachmentStoreOp::DontCare),
+        };
+        let into = SubpassDependency {
+            src_subpass: SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: VK_PIPELINE_STAGE_TRANSFER_BIT,
+            dst_stage_mask: VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+            src_access_mask: VK_ACCESS_TRANSFER_WRITE_BIT,
+            dst_access_mask: VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+            dependency_flags: DependencyFlags::empty(),
+        };
+        let out_of = SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: SUBPASS_EXTERNAL,
+            src_stage_mask: VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+            dst_stage_mask: VK_PIPELINE_STAGE_TRANSFER_BIT,
+            src_access_mask: VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+            dst_access_mask: VK_ACCESS_TRANSFER_READ_BIT,
+            dependency_flags: DependencyFlags::empty(),
+        };
+
+        let begin_info = sys::VkRenderPassBeginInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_RENDER_PASS_BEGIN_INFO,
+            pNext: ptr::null(),
+            renderPass: raw_render_pass,
+            framebuffer: raw_framebuffer,
+            renderArea: unsafe { mem::zeroed() },
+            clearValueCount: 0,
+            pClearValues: ptr::null(),
+        };
+        let mut command_buffer = allocate();
+        command_buffer.clear_color_image(&image, ImageLayout::General, ClearColorValue([0; 4]), &[range]);
+        command_buffer.begin_render_pass(RenderPassBeginInfo(&begin_info, PhantomData), SubpassContents::Inline);
+        command_buffer.end_render_pass();
+        command_buffer.copy_image_to_buffer(&image, ImageLayout::General, &buffer, &[BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: ImageSubresourceLayers {
+                aspect_mask: VK_IMAGE_ASPECT_COLOR_BIT,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: Extent3D { width: 1, height: 1, depth: 1 },
+        }]);
+
+        let results: Vec<_> = [vec![into, out_of], vec![into], vec![out_of], Vec::new()].iter().map(|dependencies| {
+            let source = Source {
+                usage: vec![Some(usage.clone())],
+                attachments: vec![(ObjectRef::new(&image), range)],
+                dependencies: dependencies.clone(),
+            };
+            let mut tracker = HazardTracker::new();
+            tracker.execute(&source, command_buffer.stream());
+            hazards(&mut tracker)
+        }).collect();
+        assert_eq!(results, [
+            vec![],
+            vec![(HazardKind::ReadAfterWrite, 1, 3)],
+            vec![(HazardKind::WriteAfterWrite, 0, 1)],
+            vec![(HazardKind::WriteAfterWrite, 0, 1), (HazardKind::ReadAfterWrite, 1, 3)],
+        ]);
+    }
+}
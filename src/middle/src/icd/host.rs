@@ -0,0 +1,310 @@
+//! Building blocks for backends whose resources live in host memory, such as software renderers
+//! and mocks.
+//!
+//! `HostImageLayout` stores images linearly whatever their tiling: array layers one after another,
+//! each holding its mip levels in order, each level tightly packed rows of texel blocks. The copy
+//! helpers move whole rows of blocks between such images and buffers, so they work for every format
+//! with a known block size without interpreting texels.
+
+use super::*;
+
+use std::{cmp, ptr};
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use self::query::value_count;
+
+/// The alignment of every `HostMemory` allocation.
+pub const HOST_MEMORY_ALIGNMENT: u64 = 64;
+
+/// A zeroed host allocation with `HOST_MEMORY_ALIGNMENT`, for backing `vkAllocateMemory`.
+pub struct HostMemory {
+    // Over-allocated so `base` can be aligned
+    _storage: Vec<u8>,
+    base: *mut u8,
+    size: u64,
+}
+
+unsafe impl Send for HostMemory {}
+unsafe impl Sync for HostMemory {}
+
+impl HostMemory {
+    pub fn new(size: u64) -> Result<HostMemory> {
+        if size > (usize::max_value() as u64) - HOST_MEMORY_ALIGNMENT {
+            return Err(Error::OutOfHostMemory);
+        }
+
+        let mut storage = vec![0u8; (size + HOST_MEMORY_ALIGNMENT) as usize];
+        let base = unsafe {
+            let unaligned = storage.as_mut_ptr();
+            unaligned.offset(((HOST_MEMORY_ALIGNMENT - (unaligned as u64 % HOST_MEMORY_ALIGNMENT)) % HOST_MEMORY_ALIGNMENT) as isize)
+        };
+
+        Ok(HostMemory {
+            _storage: storage,
+            base: base,
+            size: size,
+        })
+    }
+
+    pub fn size(&self) -> u64 { self.size }
+
+    pub fn ptr(&self, offset: u64) -> *mut u8 {
+        unsafe { self.base.offset(offset as isize) }
+    }
+
+    /// The address `size` bytes bound at `offset` start at, or `None` if they don't fit in the
+    /// allocation or `offset` isn't a multiple of `alignment`.
+    pub fn bind(&self, offset: u64, size: u64, alignment: u64) -> Option<*mut u8> {
+        if offset % alignment != 0 || offset.checked_add(size).map_or(true, |end| end > self.size) {
+            return None;
+        }
+        Some(self.ptr(offset))
+    }
+}
+
+/// Where each texel block of an image is in a linear host allocation.
+#[derive(Clone, Copy, Debug)]
+pub struct HostImageLayout {
+    pub format: Format,
+    pub extent: Extent3D,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub block_size: u32,
+    pub block_extent: (u32, u32),
+    layer_size: u64,
+}
+
+impl HostImageLayout {
+    /// `None` if the format's block size is unknown.
+    pub fn new(format: Format, extent: Extent3D, mip_levels: u32, array_layers: u32) -> Option<HostImageLayout> {
+        let mut layout = HostImageLayout {
+            format: format,
+            extent: extent,
+            mip_levels: mip_levels,
+            array_layers: array_layers,
+            block_size: match format.block_size() {
+                Some(block_size) => block_size,
+                None => return None,
+            },
+            block_extent: format.block_extent(),
+            layer_size: 0,
+        };
+        let layer_size = (0..mip_levels).map(|level| layout.mip_size(level)).sum();
+        layout.layer_size = layer_size;
+        Some(layout)
+    }
+
+    /// The size of the whole image in bytes.
+    pub fn size(&self) -> u64 { self.layer_size * self.array_layers as u64 }
+
+    /// The extent of a mip level in texels.
+    pub fn mip_extent(&self, mip_level: u32) -> Extent3D {
+        Extent3D {
+            width: cmp::max(self.extent.width >> mip_level, 1),
+            height: cmp::max(self.extent.height >> mip_level, 1),
+            depth: cmp::max(self.extent.depth >> mip_level, 1),
+        }
+    }
+
+    /// The layout of one subresource, as `vkGetImageSubresourceLayout` reports it.
+    pub fn subresource_layout(&self, mip_level: u32, array_layer: u32) -> SubresourceLayout {
+        let mut offset = array_layer as u64 * self.layer_size;
+        for level in 0..mip_level {
+            offset += self.mip_size(level);
+        }
+
+        let extent = self.mip_extent(mip_level);
+        let row_pitch = blocks(extent.width, self.block_extent.0) as u64 * self.block_size as u64;
+        let depth_pitch = row_pitch * blocks(extent.height, self.block_extent.1) as u64;
+        SubresourceLayout {
+            offset: offset,
+            size: depth_pitch * extent.depth as u64,
+            row_pitch: row_pitch,
+            array_pitch: self.layer_size,
+            depth_pitch: depth_pitch,
+        }
+    }
+
+    /// The byte offset of the block holding the texel at `texel`.
+    pub fn offset(&self, mip_level: u32, array_layer: u32, texel: Offset3D) -> u64 {
+        let layout = self.subresource_layout(mip_level, array_layer);
+        layout.offset +
+            texel.z as u64 * layout.depth_pitch +
+            (texel.y as u32 / self.block_extent.1) as u64 * layout.row_pitch +
+            (texel.x as u32 / self.block_extent.0) as u64 * self.block_size as u64
+    }
+
+    fn mip_size(&self, mip_level: u32) -> u64 {
+        let extent = self.mip_extent(mip_level);
+        blocks(extent.width, self.block_extent.0) as u64 *
+            blocks(extent.height, self.block_extent.1) as u64 *
+            extent.depth as u64 *
+            self.block_size as u64
+    }
+}
+
+/// The number of blocks of `block` texels needed to cover `texels`.
+pub fn blocks(texels: u32, block: u32) -> u32 {
+    (texels + block - 1) / block
+}
+
+/// Copies one `vkCmdCopyBufferToImage` (with `to_image` set) or `vkCmdCopyImageToBuffer` region.
+///
+/// `buffer` and `image` point at the start of the buffer and image. Combined depth/stencil formats
+/// are copied as whole texels whatever the aspect.
+pub unsafe fn copy_buffer_image(buffer: *mut u8, image: *mut u8, layout: &HostImageLayout, region: &BufferImageCopy, to_image: bool) {
+    let (block_width, block_height) = layout.block_extent;
+    let block_size = layout.block_size as u64;
+
+    let row_length = if region.buffer_row_length == 0 { region.image_extent.width } else { region.buffer_row_length };
+    let image_height = if region.buffer_image_height == 0 { region.image_extent.height } else { region.buffer_image_height };
+    let row_pitch = blocks(row_length, block_width) as u64 * block_size;
+    let slice_pitch = blocks(image_height, block_height) as u64 * row_pitch;
+    let row_size = blocks(region.image_extent.width, block_width) as usize * block_size as usize;
+    let rows = blocks(region.image_extent.height, block_height);
+
+    let subresource = region.image_subresource;
+    let mut buffer_slice = 0;
+    for layer in 0..subresource.layer_count {
+        for z in 0..region.image_extent.depth {
+            for row in 0..rows {
+                let buffer_ptr = buffer.offset((region.buffer_offset + buffer_slice * slice_pitch + row as u64 * row_pitch) as isize);
+                let image_ptr = image.offset(layout.offset(subresource.mip_level, subresource.base_array_layer + layer, Offset3D {
+                    x: region.image_offset.x,
+                    y: region.image_offset.y + (row * block_height) as i32,
+                    z: region.image_offset.z + z as i32,
+                }) as isize);
+                if to_image {
+                    ptr::copy_nonoverlapping(buffer_ptr, image_ptr, row_size);
+                } else {
+                    ptr::copy_nonoverlapping(image_ptr, buffer_ptr, row_size);
+                }
+            }
+            buffer_slice += 1;
+        }
+    }
+}
+
+/// Copies one `vkCmdCopyImage` region. The formats of the two images have the same block size, so
+/// the extent is measured in the source's blocks.
+pub unsafe fn copy_image(src: *const u8, src_layout: &HostImageLayout, dst: *mut u8, dst_layout: &HostImageLayout, region: &ImageCopy) {
+    let (block_width, block_height) = src_layout.block_extent;
+    let row_size = blocks(region.extent.width, block_width) as usize * src_layout.block_size as usize;
+    let rows = blocks(region.extent.height, block_height);
+
+    let (src_subresource, dst_subresource) = (region.src_subresource, region.dst_subresource);
+    for layer in 0..src_subresource.layer_count {
+        for z in 0..region.extent.depth {
+            for row in 0..rows {
+                let y = (row * block_height) as i32;
+                let src_offset = src_layout.offset(src_subresource.mip_level, src_subresource.base_array_layer + layer, Offset3D {
+                    x: region.src_offset.x,
+                    y: region.src_offset.y + y,
+                    z: region.src_offset.z + z as i32,
+                });
+                let dst_offset = dst_layout.offset(dst_subresource.mip_level, dst_subresource.base_array_layer + layer, Offset3D {
+                    x: region.dst_offset.x,
+                    y: region.dst_offset.y + y,
+                    z: region.dst_offset.z + z as i32,
+                });
+                ptr::copy(src.offset(src_offset as isize), dst.offset(dst_offset as isize), row_size);
+            }
+        }
+    }
+}
+
+/// An event that lives on the host, set and reset by the backend as it executes commands.
+pub struct HostEvent {
+    signaled: AtomicBool,
+}
+
+impl HostEvent {
+    pub fn is_signaled(&self) -> bool { self.signaled.load(Ordering::Acquire) }
+
+    pub fn set_signaled(&self, signaled: bool) {
+        self.signaled.store(signaled, Ordering::Release);
+    }
+}
+
+impl<I: Impl> Event<I> for HostEvent {
+    fn new(_device: &I::Device, _info: EventCreateInfo<I>) -> Result<Self> {
+        Ok(HostEvent { signaled: AtomicBool::new(false) })
+    }
+
+    fn get_status(&self, _device: &I::Device) -> Result<bool> { Ok(self.is_signaled()) }
+
+    fn set(&mut self, _device: &I::Device) -> Result<()> {
+        self.set_signaled(true);
+        Ok(())
+    }
+
+    fn reset(&mut self, _device: &I::Device) -> Result<()> {
+        self.set_signaled(false);
+        Ok(())
+    }
+}
+
+/// A query pool whose results the backend writes as it executes commands.
+///
+/// Backends that finish their work within `vkQueueSubmit` never leave `get_results` anything to
+/// wait for, so it only reports what has been written.
+pub struct HostQueryPool {
+    query_type: QueryType,
+    pipeline_statistics: QueryPipelineStatisticFlags,
+    value_count: usize,
+    results: Mutex<Vec<Option<QueryValues>>>,
+}
+
+impl HostQueryPool {
+    pub fn query_type(&self) -> QueryType { self.query_type }
+
+    /// The statistics each query counts, in the order of their values. Empty unless this is a
+    /// pipeline statistics pool.
+    pub fn pipeline_statistics(&self) -> QueryPipelineStatisticFlags { self.pipeline_statistics }
+
+    /// The number of values each query produces.
+    pub fn value_count(&self) -> usize { self.value_count }
+
+    pub fn read(&self, queries: Range<u32>) -> Vec<Option<QueryValues>> {
+        self.results.lock().unwrap()[(queries.start as usize)..(queries.end as usize)].to_vec()
+    }
+
+    /// Makes a query available with `values`, or unavailable with `None`.
+    pub fn write(&self, query: u32, values: Option<QueryValues>) {
+        self.results.lock().unwrap()[query as usize] = values;
+    }
+}
+
+impl<I: Impl> QueryPool<I> for HostQueryPool {
+    fn new(_device: &I::Device, info: QueryPoolCreateInfo<I>) -> Result<Self> {
+        let pipeline_statistics = match info.query_type() {
+            QueryType::PipelineStatistics => info.pipeline_statistics(),
+            _ => QueryPipelineStatisticFlags::empty(),
+        };
+
+        Ok(HostQueryPool {
+            query_type: info.query_type(),
+            pipeline_statistics: pipeline_statistics,
+            value_count: value_count(info.query_type(), pipeline_statistics),
+            results: Mutex::new(vec![None; info.query_count() as usize]),
+        })
+    }
+
+    fn get_results(&self, _device: &I::Device, queries: Range<u32>, _wait: bool) -> Result<Vec<Option<QueryValues>>> {
+        Ok(self.read(queries))
+    }
+}
+
+lazy_static! {
+    static ref EPOCH: Instant = Instant::now();
+}
+
+/// Nanoseconds since the first timestamp was taken, for `vkCmdWriteTimestamp` with a
+/// `timestampPeriod` of one.
+pub fn timestamp() -> u64 {
+    let elapsed = EPOCH.elapsed();
+    elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64
+}
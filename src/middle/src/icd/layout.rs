@@ -0,0 +1,650 @@
+//! Tracking of image layouts through recorded command streams, for backends that have no notion
+//! of layouts but still have to act on the transitions an application asks for.
+//!
+//! A backend keeps one `LayoutTracker` per queue and passes it each command buffer in the order the
+//! command buffers are submitted. The tracker follows pipeline barriers, event waits and render
+//! pass transitions subresource by subresource, reports every transition in order, and optionally
+//! checks that images are used in the layouts they are actually in.
+
+use super::*;
+use super::recorded::{Command, CommandStream, ObjectRef};
+
+use std::collections::HashMap;
+
+/// What the tracker needs to know about backend objects it only sees as references in a stream.
+/// Backends usually keep this around from when the objects were created.
+pub trait LayoutSource<I: Impl> {
+    /// The number of mip levels and array layers `image` was created with.
+    fn image_subresource_counts(&self, image: &I::Image) -> (u32, u32);
+    /// `RenderPassCreateInfo::attachment_usage` for the info `render_pass` was created from.
+    fn render_pass_attachment_usage(&self, render_pass: &I::RenderPass) -> Vec<Option<AttachmentUsage>>;
+    /// The image and subresource range viewed by each attachment of `framebuffer`, in order.
+    fn framebuffer_attachments(&self, framebuffer: &I::Framebuffer) -> Vec<(ObjectRef<I::Image>, ImageSubresourceRange)>;
+
+    /// Tells `image` apart from destroyed images that had the same address. Backends that cannot
+    /// call `LayoutTracker::forget` as images are destroyed number their images instead, and the
+    /// tracker starts over on an image whose number changed.
+    fn image_serial(&self, _image: &I::Image) -> u64 { 0 }
+}
+
+/// A layout change of an image subresource range, and the command that caused it.
+pub struct ImageTransition<I: Impl> {
+    /// Index of the command in the stream passed to `LayoutTracker::execute`. Transitions recorded
+    /// in secondary command buffers report the index of the `vkCmdExecuteCommands` that ran them.
+    pub command: usize,
+    pub image: ObjectRef<I::Image>,
+    pub range: ImageSubresourceRange,
+    pub old_layout: ImageLayout,
+    pub new_layout: ImageLayout,
+}
+
+/// A command that declared an image to be in a layout other than the one it is in.
+pub struct LayoutMismatch<I: Impl> {
+    pub command: usize,
+    pub command_name: &'static str,
+    pub image: ObjectRef<I::Image>,
+    pub range: ImageSubresourceRange,
+    /// The layout the command declared.
+    pub expected: ImageLayout,
+    /// The layout the tracker had for the first mismatched subresource in `range`.
+    pub actual: ImageLayout,
+}
+
+impl<I: Impl> Clone for LayoutMismatch<I> {
+    fn clone(&self) -> LayoutMismatch<I> {
+        LayoutMismatch {
+            command: self.command,
+            command_name: self.command_name,
+            image: self.image,
+            range: self.range,
+            expected: self.expected,
+            actual: self.actual,
+        }
+    }
+}
+
+const ASPECTS: [ImageAspectFlags; 4] = [
+    VK_IMAGE_ASPECT_COLOR_BIT,
+    VK_IMAGE_ASPECT_DEPTH_BIT,
+    VK_IMAGE_ASPECT_STENCIL_BIT,
+    VK_IMAGE_ASPECT_METADATA_BIT,
+];
+
+/// The layout of every subresource of one image. `None` means the tracker has not seen the
+/// subresource used yet, in which case the first use determines its layout.
+#[derive(Clone)]
+struct ImageLayouts {
+    serial: u64,
+    mip_levels: u32,
+    array_layers: u32,
+    layouts: Vec<Option<ImageLayout>>,
+}
+
+impl ImageLayouts {
+    fn new(serial: u64, mip_levels: u32, array_layers: u32) -> ImageLayouts {
+        ImageLayouts {
+            serial: serial,
+            mip_levels: mip_levels,
+            array_layers: array_layers,
+            layouts: vec![None; ASPECTS.len() * mip_levels as usize * array_layers as usize],
+        }
+    }
+
+    fn indices(&self, range: &ImageSubresourceRange) -> Vec<usize> {
+        subresource_indices(self.mip_levels, self.array_layers, range)
+    }
+}
+
+/// Numbers the subresources of an image with the given mip level and array layer counts, and
+/// returns the numbers of those in `range` in ascending order. The `REMAINING_*` counts are
+/// resolved and anything outside the image is ignored.
+pub(crate) fn subresource_indices(mip_levels: u32, array_layers: u32, range: &ImageSubresourceRange) -> Vec<usize> {
+    let level_end = if range.level_count == REMAINING_MIP_LEVELS {
+        mip_levels
+    } else {
+        cmp::min(range.base_mip_level.saturating_add(range.level_count), mip_levels)
+    };
+    let layer_end = if range.layer_count == REMAINING_ARRAY_LAYERS {
+        array_layers
+    } else {
+        cmp::min(range.base_array_layer.saturating_add(range.layer_count), array_layers)
+    };
+
+    let mut indices = Vec::new();
+    for (aspect_index, &aspect) in ASPECTS.iter().enumerate() {
+        if !range.aspect_mask.contains(aspect) {
+            continue;
+        }
+        for level in range.base_mip_level..level_end {
+            for layer in range.base_array_layer..layer_end {
+                indices.push(((aspect_index * mip_levels as usize) + level as usize) * array_layers as usize + layer as usize);
+            }
+        }
+    }
+    indices
+}
+
+pub(crate) fn layers_range(layers: &ImageSubresourceLayers) -> ImageSubresourceRange {
+    ImageSubresourceRange {
+        aspect_mask: layers.aspect_mask,
+        base_mip_level: layers.mip_level,
+        level_count: 1,
+        base_array_layer: layers.base_array_layer,
+        layer_count: layers.layer_count,
+    }
+}
+
+/// The render pass instance a stream is inside of.
+struct ActiveRenderPass<I: Impl> {
+    usage: Vec<Option<AttachmentUsage>>,
+    attachments: Vec<(ObjectRef<I::Image>, ImageSubresourceRange)>,
+    subpass: u32,
+}
+
+/// Follows image layouts through command streams in queue order.
+///
+/// Cloning the tracker lets a backend follow a submission and go back to the earlier state if it
+/// ends up refusing the submission.
+pub struct LayoutTracker<I: Impl> {
+    images: HashMap<ObjectRef<I::Image>, ImageLayouts>,
+    check: bool,
+    mismatches: Vec<LayoutMismatch<I>>,
+}
+
+impl<I: Impl> Clone for LayoutTracker<I> {
+    fn clone(&self) -> LayoutTracker<I> {
+        LayoutTracker {
+            images: self.images.clone(),
+            check: self.check,
+            mismatches: self.mismatches.clone(),
+        }
+    }
+}
+
+impl<I: Impl<CommandBuffer = RecordedCommandBuffer<I>>> LayoutTracker<I> {
+    /// With `check` set, every declared layout is compared against the tracked one and mismatches
+    /// are logged and kept for `take_mismatches`.
+    pub fn new(check: bool) -> LayoutTracker<I> {
+        LayoutTracker {
+            images: HashMap::new(),
+            check: check,
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// The tracked layout of a single subresource, if the tracker has seen it used.
+    pub fn layout(&self, image: ObjectRef<I::Image>, aspect: ImageAspectFlags, mip_level: u32, array_layer: u32) -> Option<ImageLayout> {
+        let layouts = match self.images.get(&image) {
+            Some(layouts) => layouts,
+            None => return None,
+        };
+        let range = ImageSubresourceRange {
+            aspect_mask: aspect,
+            base_mip_level: mip_level,
+            level_count: 1,
+            base_array_layer: array_layer,
+            layer_count: 1,
+        };
+        layouts.indices(&range).first().and_then(|&index| layouts.layouts[index])
+    }
+
+    /// Drops what the tracker knows about `image`. Call this when the image is destroyed, since a
+    /// new image may later be created at the same address.
+    pub fn forget(&mut self, image: ObjectRef<I::Image>) {
+        self.images.remove(&image);
+    }
+
+    /// The mismatches found since the last call.
+    pub fn take_mismatches(&mut self) -> Vec<LayoutMismatch<I>> {
+        mem::replace(&mut self.mismatches, Vec::new())
+    }
+
+    /// Follows `stream` as executed after every stream previously passed to the tracker, returning
+    /// the layout transitions it performs in order.
+    pub fn execute<S: LayoutSource<I>>(&mut self, source: &S, stream: &CommandStream<I>) -> Vec<ImageTransition<I>> {
+        let mut transitions = Vec::new();
+        let mut render_pass = None;
+        self.execute_stream(source, stream, None, &mut render_pass, &mut transitions);
+        transitions
+    }
+
+    fn execute_stream<S: LayoutSource<I>>(
+        &mut self,
+        source: &S,
+        stream: &CommandStream<I>,
+        parent_command: Option<usize>,
+        render_pass: &mut Option<ActiveRenderPass<I>>,
+        transitions: &mut Vec<ImageTransition<I>>,
+    ) {
+        for (index, command) in stream.commands().iter().enumerate() {
+            let index = parent_command.unwrap_or(index);
+            let name = command.name();
+
+            match *command {
+                Command::PipelineBarrier { image_barriers, .. } | Command::WaitEvents { image_barriers, .. } => {
+                    for barrier in stream.get(image_barriers) {
+                        self.transition(source, index, name, barrier.image, barrier.subresource_range, barrier.old_layout, barrier.new_layout, transitions);
+                    }
+                },
+                Command::CopyImage { src, src_layout, dst, dst_layout, regions } => {
+                    for region in stream.get(regions) {
+                        self.expect(source, index, name, src, layers_range(&region.src_subresource), src_layout);
+                        self.expect(source, index, name, dst, layers_range(&region.dst_subresource), dst_layout);
+                    }
+                },
+                Command::BlitImage { src, src_layout, dst, dst_layout, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.expect(source, index, name, src, layers_range(&region.src_subresource), src_layout);
+                        self.expect(source, index, name, dst, layers_range(&region.dst_subresource), dst_layout);
+                    }
+                },
+                Command::ResolveImage { src, src_layout, dst, dst_layout, regions } => {
+                    for region in stream.get(regions) {
+                        self.expect(source, index, name, src, layers_range(&region.src_subresource), src_layout);
+                        self.expect(source, index, name, dst, layers_range(&region.dst_subresource), dst_layout);
+                    }
+                },
+                Command::CopyBufferToImage { dst, dst_layout, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.expect(source, index, name, dst, layers_range(&region.image_subresource), dst_layout);
+                    }
+                },
+                Command::CopyImageToBuffer { src, src_layout, regions, .. } => {
+                    for region in stream.get(regions) {
+                        self.expect(source, index, name, src, layers_range(&region.image_subresource), src_layout);
+                    }
+                },
+                Command::ClearColorImage { image, layout, ranges, .. } |
+                Command::ClearDepthStencilImage { image, layout, ranges, .. } => {
+                    for &range in stream.get(ranges) {
+                        self.expect(source, index, name, image, range, layout);
+                    }
+                },
+                Command::BeginRenderPass { render_pass: pass, framebuffer, .. } => {
+                    let (usage, attachments) = unsafe {
+                        (source.render_pass_attachment_usage(pass.get()), source.framebuffer_attachments(framebuffer.get()))
+                    };
+                    let active = ActiveRenderPass { usage: usage, attachments: attachments, subpass: 0 };
+                    self.render_pass_transitions(source, index, name, &active, TransitionPoint::BeginSubpass(0), transitions);
+                    *render_pass = Some(active);
+                },
+                Command::NextSubpass(..) => {
+                    if let Some(ref mut active) = *render_pass {
+                        active.subpass += 1;
+                    }
+                    if let Some(ref active) = *render_pass {
+                        self.render_pass_transitions(source, index, name, active, TransitionPoint::BeginSubpass(active.subpass), transitions);
+                    }
+                },
+                Command::EndRenderPass => {
+                    if let Some(active) = render_pass.take() {
+                        self.render_pass_transitions(source, index, name, &active, TransitionPoint::EndRenderPass, transitions);
+                    }
+                },
+                Command::ExecuteCommands(command_buffers) => {
+                    for &command_buffer in stream.get(command_buffers) {
+                        let secondary = unsafe { command_buffer.get() }.stream();
+                        self.execute_stream(source, secondary, Some(index), render_pass, transitions);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn render_pass_transitions<S: LayoutSource<I>>(
+        &mut self,
+        source: &S,
+        command: usize,
+        command_name: &'static str,
+        active: &ActiveRenderPass<I>,
+        point: TransitionPoint,
+        transitions: &mut Vec<ImageTransition<I>>,
+    ) {
+        for (usage, &(image, range)) in active.usage.iter().zip(active.attachments.iter()) {
+            let usage = match *usage {
+                Some(ref usage) => usage,
+                None => continue,
+            };
+            for transition in usage.transitions.iter().filter(|x| x.point == point) {
+                self.transition(source, command, command_name, image, range, transition.old_layout, transition.new_layout, transitions);
+            }
+        }
+    }
+
+    fn image_layouts<S: LayoutSource<I>>(&mut self, source: &S, image: ObjectRef<I::Image>) -> &mut ImageLayouts {
+        let serial = source.image_serial(unsafe { image.get() });
+        if self.images.get(&image).map_or(false, |x| x.serial != serial) {
+            self.images.remove(&image);
+        }
+        self.images.entry(image).or_insert_with(|| {
+            let (mip_levels, array_layers) = source.image_subresource_counts(unsafe { image.get() });
+            ImageLayouts::new(serial, mip_levels, array_layers)
+        })
+    }
+
+    /// Records that `command` uses `range` in `layout` without changing it.
+    fn expect<S: LayoutSource<I>>(
+        &mut self,
+        source: &S,
+        command: usize,
+        command_name: &'static str,
+        image: ObjectRef<I::Image>,
+        range: ImageSubresourceRange,
+        layout: ImageLayout,
+    ) {
+        self.transition(source, command, command_name, image, range, layout, layout, &mut Vec::new());
+    }
+
+    /// Moves `range` from `old_layout` to `new_layout`. An `old_layout` of `Undefined` matches any
+    /// current layout, since the contents are being discarded.
+    fn transition<S: LayoutSource<I>>(
+        &mut self,
+        source: &S,
+        command: usize,
+        command_name: &'static str,
+        image: ObjectRef<I::Image>,
+        range: ImageSubresourceRange,
+        old_layout: ImageLayout,
+        new_layout: ImageLayout,
+        transitions: &mut Vec<ImageTransition<I>>,
+    ) {
+        let check = self.check && old_layout != ImageLayout::Undefined;
+        let mut mismatch = None;
+        {
+            let layouts = self.image_layouts(source, image);
+            for index in layouts.indices(&range) {
+                if let Some(actual) = layouts.layouts[index] {
+                    if check && actual != old_layout && mismatch.is_none() {
+                        mismatch = Some(actual);
+                    }
+                }
+                layouts.layouts[index] = Some(new_layout);
+            }
+        }
+
+        if let Some(actual) = mismatch {
+            error!("{} (command {}) expects an image in layout {:?}, but it is in layout {:?}", command_name, command, old_layout, actual);
+            self.mismatches.push(LayoutMismatch {
+                command: command,
+                command_name: command_name,
+                image: image,
+                range: range,
+                expected: old_layout,
+                actual: actual,
+            });
+        }
+
+        if old_layout != new_layout {
+            transitions.push(ImageTransition {
+                command: command,
+                image: image,
+                range: range,
+                old_layout: old_layout,
+                new_layout: new_layout,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::render_pass::{LayoutTransition, TransitionPoint};
+    use super::super::test_impl::*;
+
+    use std::ptr;
+
+    use self::ImageLayout::*;
+
+    /// Every image has two mip levels and two array layers.
+    struct Source {
+        serial: u64,
+        render_passes: HashMap<ObjectRef<TestRenderPass>, Vec<Option<AttachmentUsage>>>,
+        framebuffers: HashMap<ObjectRef<TestFramebuffer>, Vec<(ObjectRef<TestImage>, ImageSubresourceRange)>>,
+    }
+
+    impl LayoutSource<TestImpl> for Source {
+        fn image_subresource_counts(&self, _image: &TestImage) -> (u32, u32) { (2, 2) }
+
+        fn render_pass_attachment_usage(&self, render_pass: &TestRenderPass) -> Vec<Option<AttachmentUsage>> {
+            self.render_passes[&ObjectRef::new(render_pass)].clone()
+        }
+
+        fn framebuffer_attachments(&self, framebuffer: &TestFramebuffer) -> Vec<(ObjectRef<TestImage>, ImageSubresourceRange)> {
+            self.framebuffers[&ObjectRef::new(framebuffer)].clone()
+        }
+
+        fn image_serial(&self, _image: &TestImage) -> u64 { self.serial }
+    }
+
+    fn source() -> Source {
+        Source { serial: 0, render_passes: HashMap::new(), framebuffers: HashMap::new() }
+    }
+
+    /// Barriers and render pass begin infos name objects by handle, so the objects are given
+    /// handles, which live as long as the test process.
+    fn object<T: 'static, H: handle::NonDispatchableHandle>(value: T) -> (H, &'static T) {
+        let raw: H = handle::form(value);
+        (raw, unsafe { handle::cast(raw) })
+    }
+
+    fn allocate(level: CommandBufferLevel) -> RecordedCommandBuffer<TestImpl> {
+        let info = sys::VkCommandPoolCreateInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queueFamilyIndex: 0,
+        };
+        let mut pool = RecordedCommandPool::<TestImpl>::new(&TestDevice(0), CommandPoolCreateInfo(&info, PhantomData)).unwrap();
+        pool.allocate(&TestDevice(0), level, 1).unwrap().pop().unwrap()
+    }
+
+    fn range(base_mip_level: u32, level_count: u32, base_array_layer: u32, layer_count: u32) -> ImageSubresourceRange {
+        ImageSubresourceRange {
+            aspect_mask: VK_IMAGE_ASPECT_COLOR_BIT,
+            base_mip_level: base_mip_level,
+            level_count: level_count,
+            base_array_layer: base_array_layer,
+            layer_count: layer_count,
+        }
+    }
+
+    fn whole() -> ImageSubresourceRange {
+        range(0, REMAINING_MIP_LEVELS, 0, REMAINING_ARRAY_LAYERS)
+    }
+
+    fn barrier(image: sys::VkImage, range: ImageSubresourceRange, old_layout: ImageLayout, new_layout: ImageLayout) -> sys::VkImageMemoryBarrier {
+        sys::VkImageMemoryBarrier {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: 0,
+            dstAccessMask: 0,
+            oldLayout: old_layout.into(),
+            newLayout: new_layout.into(),
+            srcQueueFamilyIndex: !0,
+            dstQueueFamilyIndex: !0,
+            image: image,
+            subresourceRange: range.into(),
+        }
+    }
+
+    fn pipeline_barrier(command_buffer: &mut RecordedCommandBuffer<TestImpl>, barriers: &[sys::VkImageMemoryBarrier]) {
+        let barriers: Vec<_> = barriers.iter().map(|x| ImageMemoryBarrier(x, PhantomData)).collect();
+        command_buffer.pipeline_barrier(VK_PIPELINE_STAGE_TRANSFER_BIT, VK_PIPELINE_STAGE_TRANSFER_BIT, DependencyFlags::empty(), &[], &[], &barriers);
+    }
+
+    fn clear(command_buffer: &mut RecordedCommandBuffer<TestImpl>, image: &TestImage, layout: ImageLayout) {
+        command_buffer.clear_color_image(image, layout, ClearColorValue([0; 4]), &[whole()]);
+    }
+
+    fn summary(transitions: &[ImageTransition<TestImpl>]) -> Vec<(usize, ImageLayout, ImageLayout)> {
+        transitions.iter().map(|x| (x.command, x.old_layout, x.new_layout)).collect()
+    }
+
+    #[test]
+    fn barriers_move_the_subresources_they_name() {
+        let (raw_image, image) = object::<_, sys::VkImage>(TestImage(0));
+        let mut command_buffer = allocate(CommandBufferLevel::Primary);
+        pipeline_barrier(&mut command_buffer, &[barrier(raw_image, range(0, 1, 0, 2), Undefined, TransferDstOptimal)]);
+        pipeline_barrier(&mut command_buffer, &[
+            barrier(raw_image, range(0, 1, 1, 1), TransferDstOptimal, ShaderReadOnlyOptimal),
+            barrier(raw_image, range(1, 1, 0, REMAINING_ARRAY_LAYERS), Undefined, General),
+        ]);
+
+        let mut tracker = LayoutTracker::new(true);
+        let transitions = tracker.execute(&source(), command_buffer.stream());
+        assert_eq!(summary(&transitions), [
+            (0, Undefined, TransferDstOptimal),
+            (1, TransferDstOptimal, ShaderReadOnlyOptimal),
+            (1, Undefined, General),
+        ]);
+        assert!(transitions.iter().all(|x| x.image == ObjectRef::new(image)));
+        assert_eq!(transitions[1].range.base_array_layer, 1);
+
+        let image = ObjectRef::new(image);
+        assert_eq!(tracker.layout(image, VK_IMAGE_ASPECT_COLOR_BIT, 0, 0), Some(TransferDstOptimal));
+        assert_eq!(tracker.layout(image, VK_IMAGE_ASPECT_COLOR_BIT, 0, 1), Some(ShaderReadOnlyOptimal));
+        assert_eq!(tracker.layout(image, VK_IMAGE_ASPECT_COLOR_BIT, 1, 0), Some(General));
+        assert_eq!(tracker.layout(image, VK_IMAGE_ASPECT_COLOR_BIT, 1, 1), Some(General));
+        assert_eq!(tracker.layout(image, VK_IMAGE_ASPECT_DEPTH_BIT, 0, 0), None);
+        assert!(tracker.take_mismatches().is_empty());
+
+        // Layouts carry over to the next stream.
+        let mut next = allocate(CommandBufferLevel::Primary);
+        pipeline_barrier(&mut next, &[barrier(raw_image, range(0, 1, 0, 1), TransferDstOptimal, TransferSrcOptimal)]);
+        tracker.execute(&source(), next.stream());
+        assert_eq!(tracker.layout(image, VK_IMAGE_ASPECT_COLOR_BIT, 0, 0), Some(TransferSrcOptimal));
+        assert!(tracker.take_mismatches().is_empty());
+    }
+
+    #[test]
+    fn render_passes_move_attachments_through_their_subpass_and_final_layouts() {
+        let (raw_render_pass, render_pass) = object::<_, sys::VkRenderPass>(TestRenderPass(0));
+        let (raw_framebuffer, framebuffer) = object::<_, sys::VkFramebuffer>(TestFramebuffer(0));
+        let (color, input) = (Box::new(TestImage(0)), Box::new(TestImage(1)));
+
+        let usage = |transitions: Vec<(TransitionPoint, ImageLayout, ImageLayout)>| Some(AttachmentUsage {
+            first_subpass: 0,
+            last_subpass: 1,
+            transitions: transitions.into_iter().map(|(point, old_layout, new_layout)| LayoutTransition {
+                point: point,
+                old_layout: old_layout,
+                new_layout: new_layout,
+            }).collect(),
+            load_ops: (AttachmentLoadOp::Load, AttachmentLoadOp::DontCare),
+            store_ops: (AttachmentStoreOp::Store, AttachmentStoreOp::DontCare),
+        });
+        let mut source = source();
+        source.render_passes.insert(ObjectRef::new(render_pass), vec![
+            // Rendered to in the first subpass and read as an input attachment in the second.
+            usage(vec![
+                (TransitionPoint::BeginSubpass(0), Undefined, ColorAttachmentOptimal),
+                (TransitionPoint::BeginSubpass(1), ColorAttachmentOptimal, ShaderReadOnlyOptimal),
+                (TransitionPoint::EndRenderPass, ShaderReadOnlyOptimal, TransferSrcOptimal),
+            ]),
+            None,
+            // Used in its initial layout throughout.
+            usage(Vec::new()),
+        ]);
+        source.framebuffers.insert(ObjectRef::new(framebuffer), vec![
+            (ObjectRef::new(&*color), range(0, 1, 0, 1)),
+            (ObjectRef::new(&*input), range(0, 1, 0, 1)),
+            (ObjectRef::new(&*input), range(1, 1, 0, 1)),
+        ]);
+
+        let begin_info = sys::VkRenderPassBeginInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_RENDER_PASS_BEGIN_INFO,
+            pNext: ptr::null(),
+            renderPass: raw_render_pass,
+            framebuffer: raw_framebuffer,
+            renderArea: unsafe { mem::zeroed() },
+            clearValueCount: 0,
+            pClearValues: ptr::null(),
+        };
+        let mut command_buffer = allocate(CommandBufferLevel::Primary);
+        command_buffer.begin_render_pass(RenderPassBeginInfo(&begin_info, PhantomData), SubpassContents::Inline);
+        command_buffer.next_subpass(SubpassContents::Inline);
+        command_buffer.end_render_pass();
+
+        let mut tracker = LayoutTracker::new(true);
+        let transitions = tracker.execute(&source, command_buffer.stream());
+        assert_eq!(summary(&transitions), [
+            (0, Undefined, ColorAttachmentOptimal),
+            (1, ColorAttachmentOptimal, ShaderReadOnlyOptimal),
+            (2, ShaderReadOnlyOptimal, TransferSrcOptimal),
+        ]);
+        assert!(transitions.iter().all(|x| x.image == ObjectRef::new(&*color)));
+        assert_eq!(tracker.layout(ObjectRef::new(&*color), VK_IMAGE_ASPECT_COLOR_BIT, 0, 0), Some(TransferSrcOptimal));
+        assert_eq!(tracker.layout(ObjectRef::new(&*color), VK_IMAGE_ASPECT_COLOR_BIT, 1, 0), None);
+        assert_eq!(tracker.layout(ObjectRef::new(&*input), VK_IMAGE_ASPECT_COLOR_BIT, 1, 0), None);
+    }
+
+    #[test]
+    fn secondary_command_buffers_report_the_command_that_executed_them() {
+        let (raw_image, image) = object::<_, sys::VkImage>(TestImage(0));
+        let mut secondary = allocate(CommandBufferLevel::Secondary);
+        clear(&mut secondary, image, TransferDstOptimal);
+        pipeline_barrier(&mut secondary, &[barrier(raw_image, whole(), TransferDstOptimal, ShaderReadOnlyOptimal)]);
+
+        let mut primary = allocate(CommandBufferLevel::Primary);
+        pipeline_barrier(&mut primary, &[barrier(raw_image, whole(), Undefined, TransferDstOptimal)]);
+        primary.execute_commands(&[&secondary]);
+        clear(&mut primary, image, ShaderReadOnlyOptimal);
+
+        let mut tracker = LayoutTracker::new(true);
+        let transitions = tracker.execute(&source(), primary.stream());
+        assert_eq!(summary(&transitions), [
+            (0, Undefined, TransferDstOptimal),
+            (1, TransferDstOptimal, ShaderReadOnlyOptimal),
+        ]);
+        assert!(tracker.take_mismatches().is_empty());
+    }
+
+    #[test]
+    fn uses_in_the_wrong_layout_are_reported() {
+        let (raw_image, image) = object::<_, sys::VkImage>(TestImage(0));
+        let mut command_buffer = allocate(CommandBufferLevel::Primary);
+        pipeline_barrier(&mut command_buffer, &[barrier(raw_image, whole(), Undefined, TransferDstOptimal)]);
+        clear(&mut command_buffer, image, TransferDstOptimal);
+        command_buffer.copy_image_to_buffer(image, TransferSrcOptimal, &TestBuffer(0), &[BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: ImageSubresourceLayers {
+                aspect_mask: VK_IMAGE_ASPECT_COLOR_BIT,
+                mip_level: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: Extent3D { width: 1, height: 1, depth: 1 },
+        }]);
+        // Discarding the contents is allowed whatever the layout.
+        pipeline_barrier(&mut command_buffer, &[barrier(raw_image, whole(), Undefined, General)]);
+
+        let mut tracker = LayoutTracker::new(true);
+        tracker.execute(&source(), command_buffer.stream());
+        let mismatches = tracker.take_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!((mismatches[0].command, mismatches[0].command_name), (2, "vkCmdCopyImageToBuffer"));
+        assert_eq!(mismatches[0].image, ObjectRef::new(image));
+        assert_eq!(mismatches[0].range.base_mip_level, 1);
+        assert_eq!((mismatches[0].expected, mismatches[0].actual), (TransferSrcOptimal, TransferDstOptimal));
+        assert!(tracker.take_mismatches().is_empty());
+
+        // Without checking, the same stream is only followed.
+        let mut unchecked = LayoutTracker::new(false);
+        unchecked.execute(&source(), command_buffer.stream());
+        assert!(unchecked.take_mismatches().is_empty());
+        assert_eq!(unchecked.layout(ObjectRef::new(image), VK_IMAGE_ASPECT_COLOR_BIT, 1, 0), Some(General));
+
+        // An image with a new serial is a new image, whatever its address.
+        let mut later = allocate(CommandBufferLevel::Primary);
+        clear(&mut later, image, TransferSrcOptimal);
+        tracker.execute(&source(), later.stream());
+        assert_eq!(tracker.take_mismatches().len(), 1);
+        tracker.execute(&Source { serial: 1, ..source() }, later.stream());
+        assert!(tracker.take_mismatches().is_empty());
+    }
+}
@@ -1,18 +1,58 @@
 #![allow(non_snake_case)]
 
 use super::sys;
+use spirv::SpirvModule;
 
 use std::borrow::{Borrow, BorrowMut};
 use std::ops::Range;
 use std::cmp;
 use std::marker::PhantomData;
 use std::mem;
+use std::collections::HashSet;
+use std::slice;
+use std::ptr;
 use std::os::raw::{c_char, c_void};
 use std::ffi::CStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::Mutex;
 
 mod data;
 pub use self::data::*;
 
+mod cpu_fence;
+pub use self::cpu_fence::{CpuFence, CpuFenceSignaler};
+
+pub mod query;
+pub use self::query::{QueryValues, MAX_QUERY_VALUES};
+
+pub mod host;
+pub use self::host::{HostMemory, HostImageLayout, HostEvent, HostQueryPool};
+
+mod pipeline_cache;
+pub use self::pipeline_cache::{CacheHeader, PipelineCacheData};
+
+mod pipeline_sharing;
+use self::pipeline_sharing::{PipelineSharing, PipelineKey, RawPipelineCreateInfo};
+
+mod sampler;
+
+mod descriptor;
+pub use self::descriptor::{DescriptorImageInfo, DescriptorBufferInfo, Descriptors, DescriptorWrite, DescriptorCopy};
+
+mod render_pass;
+pub use self::render_pass::{AttachmentUsage, LayoutTransition, TransitionPoint};
+
+pub mod recorded;
+pub use self::recorded::{RecordedCommandPool, RecordedCommandBuffer, RecordedInheritance};
+pub mod layout;
+pub mod hazard;
+
+pub mod handle;
+
+#[cfg(test)]
+pub(crate) mod test_impl;
+
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 pub trait Impl: Sized + Sync + 'static {
@@ -20,147 +60,478 @@ pub trait Impl: Sized + Sync + 'static {
     type PhysicalDevice: PhysicalDevice<Self>;
     type Device: Device<Self>;
     type Queue: Queue<Self>;
+    type CommandPool: CommandPool<Self>;
     type CommandBuffer: CommandBuffer<Self>;
 
     type DeviceMemory: DeviceMemory<Self>;
     type Buffer: Buffer<Self>;
+    type BufferView: BufferView<Self>;
     type Image: Image<Self>;
-
-    type Fence;
-    
-    type PipelineCache;
+    type ImageView: ImageView<Self>;
+
+    type Fence: Fence<Self>;
+    type Semaphore: Semaphore<Self>;
+    type Event: Event<Self>;
+    type QueryPool: QueryPool<Self>;
+
+    type RenderPass: RenderPass<Self>;
+    type Framebuffer: Framebuffer<Self>;
+
+    type Sampler: Sampler<Self>;
+    type DescriptorSetLayout: DescriptorSetLayout<Self>;
+    type DescriptorPool: DescriptorPool<Self>;
+    type DescriptorSet: DescriptorSet<Self>;
+    type PipelineLayout: PipelineLayout<Self>;
+
+    type ShaderModule: ShaderModule<Self>;
+    type Pipeline: Pipeline<Self>;
+    type PipelineCache: PipelineCache<Self>;
+
+    /// Declares which commands the backend actually implements.
+    ///
+    /// Commands for which this returns `false` are reported as NULL by `vk_icdGetInstanceProcAddr`
+    /// instead of resolving to a trampoline that can only fail with `VK_ERROR_FEATURE_NOT_PRESENT`.
+    /// Nothing is advertised unless the backend says so.
+    fn supports(_command: Command) -> bool { false }
+
+    /// Whether `RecordedCommandBuffer` copies the commands of secondary command buffers into the
+    /// primary when `vkCmdExecuteCommands` is recorded, instead of recording a reference to them.
+    /// Backends that can't nest encoders then never see `recorded::Command::ExecuteCommands`.
+    fn inline_secondary_command_buffers() -> bool { false }
 }
 
 pub trait Instance<I: Impl>: Sized + Send + Sync + 'static {
-    fn create(info: InstanceCreateInfo<I>) -> Result<Self>;
+    /// The instance extensions the backend implements. Asked before any instance exists, and
+    /// instances are only created with extensions from this list.
+    fn extension_properties() -> Vec<ExtensionProperties> { Vec::new() }
 
-    fn physical_device_count(&self) -> Result<u32>;
-    fn enumerate_physical_devices(&self, capacity: u32) -> Result<Vec<I::PhysicalDevice>>;
+    fn create(_info: InstanceCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+
+    fn physical_device_count(&self) -> Result<u32> { Err(Error::FeatureNotPresent) }
+    fn enumerate_physical_devices(&self, _capacity: u32) -> Result<Vec<I::PhysicalDevice>> { Err(Error::FeatureNotPresent) }
 }
 
 pub trait PhysicalDevice<I: Impl>: Sized + Send + Sync + 'static {
-    fn get_features(&self) -> PhysicalDeviceFeatures;
-    fn get_format_properties(&self, format: Format) -> FormatProperties;
-
-    fn create_device(&self, create_info: DeviceCreateInfo<I>) -> Result<I::Device>;
+    fn get_features(&self) -> PhysicalDeviceFeatures { PhysicalDeviceFeatures::default() }
+    fn get_format_properties(&self, _format: Format) -> FormatProperties { FormatProperties::default() }
+    fn get_properties(&self) -> PhysicalDeviceProperties { PhysicalDeviceProperties::default() }
+    fn get_queue_family_properties(&self) -> Vec<QueueFamilyProperties> { Vec::new() }
+    fn get_memory_properties(&self) -> PhysicalDeviceMemoryProperties { PhysicalDeviceMemoryProperties::default() }
+    /// Devices are only created with extensions from this list.
+    fn get_extension_properties(&self) -> Vec<ExtensionProperties> { Vec::new() }
+
+    fn create_device(&self, _create_info: DeviceCreateInfo<I>) -> Result<I::Device> { Err(Error::FeatureNotPresent) }
 }
 
 pub trait Device<I: Impl>: Sized + Send + Sync + 'static {
-    fn wait_idle(&self) -> Result<()>;
+    /// Called at device creation for each queue the application requested.
+    fn get_queue(&self, _family_index: u32, _queue_index: u32) -> Result<I::Queue> { Err(Error::FeatureNotPresent) }
+
+    fn wait_idle(&self) -> Result<()> { Err(Error::FeatureNotPresent) }
+
+    fn allocate_memory(&self, _info: MemoryAllocateInfo<I>) -> Result<I::DeviceMemory> { Err(Error::FeatureNotPresent) }
+    fn flush_mapped_memory_ranges(&self, _ranges: MappedMemoryRanges<I>) -> Result<()> { Err(Error::FeatureNotPresent) }
+    fn invalidate_mapped_memory_ranges(&self, _ranges: MappedMemoryRanges<I>) -> Result<()> { Err(Error::FeatureNotPresent) }
 
-    fn allocate_memory(&self, info: MemoryAllocateInfo<I>) -> Result<I::DeviceMemory>;
-    fn flush_mapped_memory_ranges(&self, ranges: MappedMemoryRanges<I>) -> Result<()>;
-    fn invalidate_mapped_memory_ranges(&self, ranges: MappedMemoryRanges<I>) -> Result<()>;
+    /// Applies `writes` and then `copies`, each in order. Descriptor sets are only ever passed by
+    /// shared reference, so backends need interior mutability to store their contents.
+    fn update_descriptor_sets(&self, _writes: &[DescriptorWrite<I>], _copies: &[DescriptorCopy<I>]) {}
 }
 
 pub trait Queue<I: Impl>: Sized + Send + Sync + 'static {
-    fn submit(&mut self, submits: SubmitInfos<I>, fence: Option<&mut I::Fence>);
-    fn wait_idle(&self) -> Result<()>;
+    /// Errors mean nothing was submitted, and the fence is not to be signaled.
+    fn submit(&mut self, _submits: SubmitInfos<I>, _fence: Option<&mut I::Fence>) -> Result<()> { Err(Error::FeatureNotPresent) }
+    fn wait_idle(&self) -> Result<()> { Err(Error::FeatureNotPresent) }
+    /// Whether every submission made so far has completed. vk-middle asks after each submission,
+    /// so that submissions without a fence do not pile up in queues that are never waited on.
+    /// Backends that finish their work within `submit` return `true`.
+    fn is_idle(&self) -> bool { false }
+
+    fn bind_sparse(&mut self, _infos: BindSparseInfos<I>, _fence: Option<&mut I::Fence>) -> Result<()> { Err(Error::FeatureNotPresent) }
+}
+
+pub trait CommandPool<I: Impl>: Sized + Send + Sync + 'static {
+    fn new(_device: &I::Device, _info: CommandPoolCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+
+    /// Command buffers still allocated from the pool have already been handed to `free` by the
+    /// time this is called.
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+
+    /// Allocates `count` command buffers. Either every allocation succeeds or none do.
+    fn allocate(&mut self, _device: &I::Device, _level: CommandBufferLevel, _count: u32) -> Result<Vec<I::CommandBuffer>> {
+        Err(Error::FeatureNotPresent)
+    }
+
+    /// Returns command buffers to the pool, either because the application freed them or because
+    /// the pool is being destroyed.
+    fn free(&mut self, _device: &I::Device, command_buffers: Vec<I::CommandBuffer>) { mem::drop(command_buffers) }
+
+    /// Resets every command buffer allocated from the pool, each given with its level. The default
+    /// implementation allocates new command buffers and frees the old ones in their place, since
+    /// `CommandBuffer::reset` is only called when the pool was created with
+    /// `VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT`. With
+    /// `VK_COMMAND_POOL_RESET_RELEASE_RESOURCES_BIT` it also trims the pool.
+    fn reset(&mut self, device: &I::Device, command_buffers: &mut [(&mut I::CommandBuffer, CommandBufferLevel)], flags: CommandPoolResetFlags) -> Result<()> {
+        // Allocate everything up front, so that a failure leaves the command buffers as they were.
+        let mut replacements = Vec::with_capacity(command_buffers.len());
+        for &(_, level) in command_buffers.iter() {
+            match self.allocate(device, level, 1) {
+                Ok(allocated) => replacements.extend(allocated),
+                Err(err) => {
+                    self.free(device, replacements);
+                    return Err(err);
+                },
+            }
+        }
 
-    fn bind_sparse(&mut self, infos: BindSparseInfos<I>, fence: Option<&mut I::Fence>);
+        let old = command_buffers.iter_mut()
+            .zip(replacements)
+            .map(|(&mut (ref mut command_buffer, _), replacement)| mem::replace(&mut **command_buffer, replacement))
+            .collect();
+        self.free(device, old);
+
+        if flags.contains(VK_COMMAND_POOL_RESET_RELEASE_RESOURCES_BIT) {
+            self.trim(device);
+        }
+        Ok(())
+    }
+
+    /// Gives memory the pool holds on to but does not currently use back to the system.
+    fn trim(&mut self, _device: &I::Device) {}
 }
 
+/// A command buffer being recorded.
+///
+/// vk-middle takes care of the `VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT` rules: `reset` is
+/// only called for command buffers whose pool allows it, either explicitly or when a command buffer
+/// that was already recorded is begun again. The recording methods do nothing by default.
 pub trait CommandBuffer<I: Impl>: Sized + Send + 'static {
+    fn begin(&mut self, _info: CommandBufferBeginInfo<I>) -> Result<()> { Err(Error::FeatureNotPresent) }
+    fn end(&mut self) -> Result<()> { Err(Error::FeatureNotPresent) }
+    fn reset(&mut self, _flags: CommandBufferResetFlags) -> Result<()> { Err(Error::FeatureNotPresent) }
+
+    fn bind_pipeline(&mut self, _bind_point: PipelineBindPoint, _pipeline: &I::Pipeline) {}
+    fn bind_descriptor_sets(
+        &mut self,
+        _bind_point: PipelineBindPoint,
+        _layout: &I::PipelineLayout,
+        _first_set: u32,
+        _sets: &[&I::DescriptorSet],
+        _dynamic_offsets: &[u32],
+    ) {}
+    fn bind_index_buffer(&mut self, _buffer: &I::Buffer, _offset: u64, _index_type: IndexType) {}
+    fn bind_vertex_buffers(&mut self, _first_binding: u32, _buffers: &[&I::Buffer], _offsets: &[u64]) {}
+    fn push_constants(&mut self, _layout: &I::PipelineLayout, _stages: ShaderStageFlags, _offset: u32, _data: &[u8]) {}
+
+    fn set_viewport(&mut self, _first_viewport: u32, _viewports: &[Viewport]) {}
+    fn set_scissor(&mut self, _first_scissor: u32, _scissors: &[Rect2D]) {}
+    fn set_line_width(&mut self, _line_width: f32) {}
+    fn set_depth_bias(&mut self, _constant_factor: f32, _clamp: f32, _slope_factor: f32) {}
+    fn set_blend_constants(&mut self, _blend_constants: [f32; 4]) {}
+    fn set_depth_bounds(&mut self, _min_depth_bounds: f32, _max_depth_bounds: f32) {}
+    fn set_stencil_compare_mask(&mut self, _faces: StencilFaceFlags, _compare_mask: u32) {}
+    fn set_stencil_write_mask(&mut self, _faces: StencilFaceFlags, _write_mask: u32) {}
+    fn set_stencil_reference(&mut self, _faces: StencilFaceFlags, _reference: u32) {}
+
+    fn draw(&mut self, _vertex_count: u32, _instance_count: u32, _first_vertex: u32, _first_instance: u32) {}
+    fn draw_indexed(&mut self, _index_count: u32, _instance_count: u32, _first_index: u32, _vertex_offset: i32, _first_instance: u32) {}
+    fn draw_indirect(&mut self, _buffer: &I::Buffer, _offset: u64, _draw_count: u32, _stride: u32) {}
+    fn draw_indexed_indirect(&mut self, _buffer: &I::Buffer, _offset: u64, _draw_count: u32, _stride: u32) {}
+    fn dispatch(&mut self, _group_count_x: u32, _group_count_y: u32, _group_count_z: u32) {}
+    fn dispatch_indirect(&mut self, _buffer: &I::Buffer, _offset: u64) {}
+
+    fn copy_buffer(&mut self, _src: &I::Buffer, _dst: &I::Buffer, _regions: &[BufferCopy]) {}
+    fn copy_image(&mut self, _src: &I::Image, _src_layout: ImageLayout, _dst: &I::Image, _dst_layout: ImageLayout, _regions: &[ImageCopy]) {}
+    fn blit_image(
+        &mut self,
+        _src: &I::Image,
+        _src_layout: ImageLayout,
+        _dst: &I::Image,
+        _dst_layout: ImageLayout,
+        _regions: &[ImageBlit],
+        _filter: Filter,
+    ) {}
+    fn copy_buffer_to_image(&mut self, _src: &I::Buffer, _dst: &I::Image, _dst_layout: ImageLayout, _regions: &[BufferImageCopy]) {}
+    fn copy_image_to_buffer(&mut self, _src: &I::Image, _src_layout: ImageLayout, _dst: &I::Buffer, _regions: &[BufferImageCopy]) {}
+    fn update_buffer(&mut self, _dst: &I::Buffer, _offset: u64, _data: &[u8]) {}
+    /// `size` may be `WHOLE_SIZE`, meaning the rest of the buffer after `offset`.
+    fn fill_buffer(&mut self, _dst: &I::Buffer, _offset: u64, _size: u64, _data: u32) {}
+    fn clear_color_image(&mut self, _image: &I::Image, _layout: ImageLayout, _color: ClearColorValue, _ranges: &[ImageSubresourceRange]) {}
+    fn clear_depth_stencil_image(
+        &mut self,
+        _image: &I::Image,
+        _layout: ImageLayout,
+        _depth_stencil: ClearDepthStencilValue,
+        _ranges: &[ImageSubresourceRange],
+    ) {}
+    fn clear_attachments(&mut self, _attachments: &[ClearAttachment], _rects: &[ClearRect]) {}
+    fn resolve_image(&mut self, _src: &I::Image, _src_layout: ImageLayout, _dst: &I::Image, _dst_layout: ImageLayout, _regions: &[ImageResolve]) {}
+
+    fn set_event(&mut self, _event: &I::Event, _stages: PipelineStageFlags) {}
+    fn reset_event(&mut self, _event: &I::Event, _stages: PipelineStageFlags) {}
+    fn wait_events(
+        &mut self,
+        _events: &[&I::Event],
+        _src_stages: PipelineStageFlags,
+        _dst_stages: PipelineStageFlags,
+        _memory_barriers: &[MemoryBarrier<I>],
+        _buffer_barriers: &[BufferMemoryBarrier<I>],
+        _image_barriers: &[ImageMemoryBarrier<I>],
+    ) {}
+    fn pipeline_barrier(
+        &mut self,
+        _src_stages: PipelineStageFlags,
+        _dst_stages: PipelineStageFlags,
+        _dependency_flags: DependencyFlags,
+        _memory_barriers: &[MemoryBarrier<I>],
+        _buffer_barriers: &[BufferMemoryBarrier<I>],
+        _image_barriers: &[ImageMemoryBarrier<I>],
+    ) {}
+
+    fn begin_query(&mut self, _pool: &I::QueryPool, _query: u32, _flags: QueryControlFlags) {}
+    fn end_query(&mut self, _pool: &I::QueryPool, _query: u32) {}
+    fn reset_query_pool(&mut self, _pool: &I::QueryPool, _queries: Range<u32>) {}
+    fn write_timestamp(&mut self, _stage: PipelineStageFlags, _pool: &I::QueryPool, _query: u32) {}
+    /// Results are laid out in `dst` as `query::write_query_results` would lay them out.
+    fn copy_query_pool_results(
+        &mut self,
+        _pool: &I::QueryPool,
+        _queries: Range<u32>,
+        _dst: &I::Buffer,
+        _offset: u64,
+        _stride: u64,
+        _flags: QueryResultFlags,
+    ) {}
+
+    fn begin_render_pass(&mut self, _info: RenderPassBeginInfo<I>, _contents: SubpassContents) {}
+    fn next_subpass(&mut self, _contents: SubpassContents) {}
+    fn end_render_pass(&mut self) {}
+
+    fn execute_commands(&mut self, _command_buffers: &[&I::CommandBuffer]) {}
 }
 
 pub trait DeviceMemory<I: Impl>: Sized + Send + Sync + 'static {
-    fn map(&mut self, device: &I::Device, offset: u64, size: u64, flags: MemoryMapFlags) -> Result<*mut u8>;
-    fn unmap(&mut self, device: &I::Device);
+    fn map(&mut self, _device: &I::Device, _offset: u64, _size: u64, _flags: MemoryMapFlags) -> Result<*mut u8> { Err(Error::MemoryMapFailed) }
+    fn unmap(&mut self, _device: &I::Device) {}
 
-    fn get_memory_commitment(&self, device: &I::Device);
+    fn get_memory_commitment(&self, _device: &I::Device) -> u64 { 0 }
 
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 }
 
 pub trait Buffer<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: BufferCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: BufferCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 
-    fn get_memory_requirements(&self, device: &I::Device) -> MemoryRequirements;
-    fn bind_memory(&mut self, device: &I::Device, memory: &I::DeviceMemory, offset: u64) -> Result<()>;
+    fn get_memory_requirements(&self, _device: &I::Device) -> MemoryRequirements { MemoryRequirements::default() }
+    fn bind_memory(&mut self, _device: &I::Device, _memory: &I::DeviceMemory, _offset: u64) -> Result<()> { Err(Error::FeatureNotPresent) }
 }
 
 pub trait BufferView<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: BufferViewCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: BufferViewCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 }
 
 pub trait Image<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: ImageCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: ImageCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 
-    fn get_memory_requirements(&self, device: &I::Device) -> MemoryRequirements;
-    fn get_sparse_memory_requirements(&self, device: &I::Device, capacity: u32) -> Vec<SparseImageMemoryRequirements>;
-    fn bind_memory(&mut self, device: &I::Device, memory: &I::DeviceMemory, offset: u64) -> Result<()>;
+    fn get_memory_requirements(&self, _device: &I::Device) -> MemoryRequirements { MemoryRequirements::default() }
+    fn get_sparse_memory_requirements(&self, _device: &I::Device, _capacity: u32) -> Vec<SparseImageMemoryRequirements> { Vec::new() }
+    fn bind_memory(&mut self, _device: &I::Device, _memory: &I::DeviceMemory, _offset: u64) -> Result<()> { Err(Error::FeatureNotPresent) }
 
-    fn get_subresource_layout(&self, subresource: ImageSubresource<I>) -> Result<SubresourceLayout>;
+    fn get_subresource_layout(&self, _device: &I::Device, _subresource: ImageSubresource<I>) -> SubresourceLayout { SubresourceLayout::default() }
 }
 
 pub trait ImageView<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: ImageViewCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: ImageViewCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+}
+
+pub trait RenderPass<I: Impl>: Sized + Send + Sync + 'static {
+    fn new(_device: &I::Device, _info: RenderPassCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+
+    fn get_render_area_granularity(&self, _device: &I::Device) -> Extent2D { Extent2D { width: 1, height: 1 } }
+}
+
+pub trait Framebuffer<I: Impl>: Sized + Send + Sync + 'static {
+    fn new(_device: &I::Device, _info: FramebufferCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+}
+
+pub trait Sampler<I: Impl>: Sized + Send + Sync + 'static {
+    /// The create info has already been checked against the features enabled on the device.
+    fn new(_device: &I::Device, _info: SamplerCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+}
+
+pub trait DescriptorSetLayout<I: Impl>: Sized + Send + Sync + 'static {
+    fn new(_device: &I::Device, _info: DescriptorSetLayoutCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+}
+
+pub trait DescriptorPool<I: Impl>: Sized + Send + Sync + 'static {
+    fn new(_device: &I::Device, _info: DescriptorPoolCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
+
+    /// Sets still allocated from the pool have already been handed to `free` by the time this is
+    /// called.
+    fn destroy(self, _device: &I::Device) { mem::drop(self) }
+
+    /// Allocates one set per layout. Either every allocation succeeds or none do.
+    fn allocate(&mut self, _device: &I::Device, _layouts: &[&I::DescriptorSetLayout]) -> Result<Vec<I::DescriptorSet>> {
+        Err(Error::FeatureNotPresent)
+    }
+
+    /// Returns sets to the pool, either because the application freed them (which it may only do
+    /// for pools created with `VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT`) or because the
+    /// pool is being destroyed.
+    fn free(&mut self, _device: &I::Device, sets: Vec<I::DescriptorSet>) { mem::drop(sets) }
+
+    /// Returns every set allocated from the pool at once.
+    fn reset(&mut self, device: &I::Device, sets: Vec<I::DescriptorSet>) { self.free(device, sets) }
+}
+
+pub trait DescriptorSet<I: Impl>: Sized + Send + Sync + 'static {
+}
+
+pub trait PipelineLayout<I: Impl>: Sized + Send + Sync + 'static {
+    fn new(_device: &I::Device, _info: PipelineLayoutCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 }
 
 pub trait ShaderModule<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: ShaderModuleCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: ShaderModuleCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 }
 
 pub trait Pipeline<I: Impl>: Sized + Send + Sync + 'static {
+    /// Creates one pipeline per create info, in order.
+    ///
+    /// A derivative pipeline may name its base by index into the same batch; that base is always
+    /// at an earlier index.
+    ///
+    /// Create infos that match a live pipeline in everything but dynamic and unused state are not
+    /// passed on; the application is given that pipeline again instead.
     fn create_graphics(
-        device: &I::Device, 
-        pipeline_cache: Option<&I::PipelineCache>, 
-        create_info: &[GraphicsPipelineCreateInfo<I>],
-    ) -> Vec<Self>;
+        _device: &I::Device,
+        _pipeline_cache: Option<&I::PipelineCache>,
+        create_infos: &[GraphicsPipelineCreateInfo<I>],
+    ) -> Vec<Result<Self>> {
+        create_infos.iter().map(|_| Err(Error::FeatureNotPresent)).collect()
+    }
     fn create_compute(
-        device: &I::Device, 
-        pipeline_cache: Option<&I::PipelineCache>, 
-        create_info: &[GraphicsPipelineCreateInfo<I>],
-    ) -> Vec<Self>;
+        _device: &I::Device,
+        _pipeline_cache: Option<&I::PipelineCache>,
+        create_infos: &[ComputePipelineCreateInfo<I>],
+    ) -> Vec<Result<Self>> {
+        create_infos.iter().map(|_| Err(Error::FeatureNotPresent)).collect()
+    }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 }
 
 pub trait PipelineCache<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: PipelineCacheCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: PipelineCacheCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 
-    fn get_data_size(&self, device: &I::Device) -> Result<usize>;
-    fn get_data(&self, device: &I::Device, data: &mut [u8]) -> Result<usize>;
+    /// The entries of a backend that keeps them in a `PipelineCacheData`, which the default
+    /// `get_data_size`, `get_data` and `merge` work with. Without one, the cache is always empty.
+    fn data(&self) -> Option<&PipelineCacheData> { None }
 
-    fn merge<C>(&self, device: &I::Device, sources: &[C]) -> Result<()> where
-        C: Borrow<Self>;
+    fn get_data_size(&self, _device: &I::Device) -> Result<usize> {
+        Ok(self.data().map(|data| data.size()).unwrap_or(0))
+    }
+    fn get_data(&self, _device: &I::Device, data: &mut [u8]) -> Result<usize> {
+        Ok(self.data().map(|own| own.write(data)).unwrap_or(0))
+    }
+
+    fn merge<C>(&mut self, _device: &I::Device, sources: &[C]) -> Result<()> where
+        C: Borrow<Self>
+    {
+        if let Some(data) = self.data() {
+            for source in sources {
+                if let Some(source) = source.borrow().data() {
+                    data.merge(source);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub trait Fence<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: FenceCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: FenceCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 
-    fn reset<F>(device: &I::Device, fences: &mut [F]) where
-        F: BorrowMut<Self>;
-    fn wait<F>(device: &I::Device, fences: &[F]) where
-        F: Borrow<Self>;
-    fn get_status(&self, device: &I::Device) -> Result<()>;
+    fn reset<F>(_device: &I::Device, _fences: &mut [F]) -> Result<()> where
+        F: BorrowMut<Self> { Err(Error::FeatureNotPresent) }
+
+    /// Waits until every fence (or with `wait_all` unset, any fence) is signaled, giving up after
+    /// `timeout` nanoseconds.
+    ///
+    /// A timeout of zero only polls, and `!0` waits forever. The default implementation polls
+    /// `get_status`, sleeping twice as long after each miss up to a millisecond; backends that can
+    /// block on their fences should override it.
+    fn wait<F>(device: &I::Device, fences: &[F], wait_all: bool, timeout: u64) -> Result<WaitStatus> where
+        F: Borrow<Self>
+    {
+        let timeout = Duration::new(timeout / 1_000_000_000, (timeout % 1_000_000_000) as u32);
+        let start = Instant::now();
+        let mut backoff = Duration::new(0, 1_000);
+        loop {
+            let mut signaled = 0;
+            for fence in fences {
+                match fence.borrow().get_status(device) {
+                    Ok(()) => signaled += 1,
+                    Err(Error::NotReady) => {},
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if (wait_all && signaled == fences.len()) || (!wait_all && signaled > 0) {
+                return Ok(WaitStatus::Signaled);
+            }
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Ok(WaitStatus::Timeout);
+            }
+
+            thread::sleep(cmp::min(backoff, timeout - elapsed));
+            backoff = cmp::min(backoff * 2, Duration::new(0, 1_000_000));
+        }
+    }
+
+    /// Returns `Err(Error::NotReady)` while the fence is unsignaled.
+    fn get_status(&self, _device: &I::Device) -> Result<()> { Err(Error::FeatureNotPresent) }
 }
 
 pub trait Semaphore<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: SemaphoreCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: SemaphoreCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 }
 
 pub trait Event<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: EventCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: EventCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 
-    fn get_status(&self, device: &I::Device) -> Result<()>;
-    fn set(&mut self, device: &I::Device) -> Result<()>;
-    fn reset(&mut self, _device: &I::Device) -> Result<()>;
+    /// Returns whether the event is signaled.
+    fn get_status(&self, _device: &I::Device) -> Result<bool> { Err(Error::FeatureNotPresent) }
+    fn set(&mut self, _device: &I::Device) -> Result<()> { Err(Error::FeatureNotPresent) }
+    fn reset(&mut self, _device: &I::Device) -> Result<()> { Err(Error::FeatureNotPresent) }
 }
 
 pub trait QueryPool<I: Impl>: Sized + Send + Sync + 'static {
-    fn new(device: &I::Device, info: QueryPoolCreateInfo<I>) -> Result<Self>;
+    fn new(_device: &I::Device, _info: QueryPoolCreateInfo<I>) -> Result<Self> { Err(Error::FeatureNotPresent) }
     fn destroy(self, _device: &I::Device) { mem::drop(self) }
 
-    fn get_results(&self, device: &I::Device, queries: Range<u32>, data: &mut [u8], stride: u64, flags: QueryResultFlags) -> Result<()>;
+    /// Returns the values of each query in `queries`, or `None` for those whose results are not
+    /// available yet. With `wait` set, blocks until every query in the range is available.
+    ///
+    /// Occlusion and timestamp queries produce one value. Pipeline statistics queries produce one
+    /// per enabled statistic, in bit order.
+    fn get_results(&self, _device: &I::Device, _queries: Range<u32>, _wait: bool) -> Result<Vec<Option<QueryValues>>> {
+        Err(Error::FeatureNotPresent)
+    }
 }
 
 macro_rules! vulkan_try {
@@ -172,66 +543,229 @@ macro_rules! vulkan_try {
     }
 }
 
-macro_rules! vk_get_proc {
+macro_rules! vulkan_commands {
     {
-        match $name:tt {
-            functions { $($func_name:ident,)* }
-            _ => $default:expr,
-        }
+        $($func_name:ident => $command:ident,)*
     } => {
-        use sys::*;
-        match $name {
-            $(
-                stringify!($func_name) => mem::transmute::<concat_idents!(PFN_, $func_name), _>(Some(*&$func_name::<I>)),
-            )*
-            _ => $default,
+        /// A command that can be resolved through `vk_icdGetInstanceProcAddr`.
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum Command {
+            $($command,)*
+        }
+
+        /// Commands by name, which the invocation below lists in byte order so this can be searched.
+        static COMMANDS_BY_NAME: &'static [(&'static str, Command)] = &[$((stringify!($func_name), Command::$command),)*];
+
+        impl Command {
+            /// Looks up a command by the raw bytes of its entry point name.
+            pub fn from_name(name: &CStr) -> Option<Command> {
+                let name = name.to_bytes();
+                COMMANDS_BY_NAME.binary_search_by(|&(x, _)| x.as_bytes().cmp(name))
+                    .ok()
+                    .map(|i| COMMANDS_BY_NAME[i].1)
+            }
+
+            /// Every command, in order of name.
+            pub fn all() -> &'static [Command] {
+                const ALL: &'static [Command] = &[$(Command::$command,)*];
+                ALL
+            }
+
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $(Command::$command => stringify!($func_name),)*
+                }
+            }
+
+            unsafe fn proc_addr<I: Impl>(&self) -> Option<unsafe extern "C" fn()> {
+                use sys::*;
+
+                match *self {
+                    $(
+                        Command::$command => mem::transmute::<concat_idents!(PFN_, $func_name), _>(Some(*&$func_name::<I>)),
+                    )*
+                }
+            }
         }
     }
 }
 
+vulkan_commands! {
+    vkAllocateCommandBuffers => AllocateCommandBuffers,
+    vkAllocateDescriptorSets => AllocateDescriptorSets,
+    vkAllocateMemory => AllocateMemory,
+    vkBeginCommandBuffer => BeginCommandBuffer,
+    vkBindBufferMemory => BindBufferMemory,
+    vkBindImageMemory => BindImageMemory,
+    vkCmdBeginQuery => CmdBeginQuery,
+    vkCmdBeginRenderPass => CmdBeginRenderPass,
+    vkCmdBindDescriptorSets => CmdBindDescriptorSets,
+    vkCmdBindIndexBuffer => CmdBindIndexBuffer,
+    vkCmdBindPipeline => CmdBindPipeline,
+    vkCmdBindVertexBuffers => CmdBindVertexBuffers,
+    vkCmdBlitImage => CmdBlitImage,
+    vkCmdClearAttachments => CmdClearAttachments,
+    vkCmdClearColorImage => CmdClearColorImage,
+    vkCmdClearDepthStencilImage => CmdClearDepthStencilImage,
+    vkCmdCopyBuffer => CmdCopyBuffer,
+    vkCmdCopyBufferToImage => CmdCopyBufferToImage,
+    vkCmdCopyImage => CmdCopyImage,
+    vkCmdCopyImageToBuffer => CmdCopyImageToBuffer,
+    vkCmdCopyQueryPoolResults => CmdCopyQueryPoolResults,
+    vkCmdDispatch => CmdDispatch,
+    vkCmdDispatchIndirect => CmdDispatchIndirect,
+    vkCmdDraw => CmdDraw,
+    vkCmdDrawIndexed => CmdDrawIndexed,
+    vkCmdDrawIndexedIndirect => CmdDrawIndexedIndirect,
+    vkCmdDrawIndirect => CmdDrawIndirect,
+    vkCmdEndQuery => CmdEndQuery,
+    vkCmdEndRenderPass => CmdEndRenderPass,
+    vkCmdExecuteCommands => CmdExecuteCommands,
+    vkCmdFillBuffer => CmdFillBuffer,
+    vkCmdNextSubpass => CmdNextSubpass,
+    vkCmdPipelineBarrier => CmdPipelineBarrier,
+    vkCmdPushConstants => CmdPushConstants,
+    vkCmdResetEvent => CmdResetEvent,
+    vkCmdResetQueryPool => CmdResetQueryPool,
+    vkCmdResolveImage => CmdResolveImage,
+    vkCmdSetBlendConstants => CmdSetBlendConstants,
+    vkCmdSetDepthBias => CmdSetDepthBias,
+    vkCmdSetDepthBounds => CmdSetDepthBounds,
+    vkCmdSetEvent => CmdSetEvent,
+    vkCmdSetLineWidth => CmdSetLineWidth,
+    vkCmdSetScissor => CmdSetScissor,
+    vkCmdSetStencilCompareMask => CmdSetStencilCompareMask,
+    vkCmdSetStencilReference => CmdSetStencilReference,
+    vkCmdSetStencilWriteMask => CmdSetStencilWriteMask,
+    vkCmdSetViewport => CmdSetViewport,
+    vkCmdUpdateBuffer => CmdUpdateBuffer,
+    vkCmdWaitEvents => CmdWaitEvents,
+    vkCmdWriteTimestamp => CmdWriteTimestamp,
+    vkCreateBuffer => CreateBuffer,
+    vkCreateBufferView => CreateBufferView,
+    vkCreateCommandPool => CreateCommandPool,
+    vkCreateComputePipelines => CreateComputePipelines,
+    vkCreateDescriptorPool => CreateDescriptorPool,
+    vkCreateDescriptorSetLayout => CreateDescriptorSetLayout,
+    vkCreateDevice => CreateDevice,
+    vkCreateEvent => CreateEvent,
+    vkCreateFence => CreateFence,
+    vkCreateFramebuffer => CreateFramebuffer,
+    vkCreateGraphicsPipelines => CreateGraphicsPipelines,
+    vkCreateImage => CreateImage,
+    vkCreateImageView => CreateImageView,
+    vkCreateInstance => CreateInstance,
+    vkCreatePipelineCache => CreatePipelineCache,
+    vkCreatePipelineLayout => CreatePipelineLayout,
+    vkCreateQueryPool => CreateQueryPool,
+    vkCreateRenderPass => CreateRenderPass,
+    vkCreateSampler => CreateSampler,
+    vkCreateSemaphore => CreateSemaphore,
+    vkCreateShaderModule => CreateShaderModule,
+    vkDestroyBuffer => DestroyBuffer,
+    vkDestroyBufferView => DestroyBufferView,
+    vkDestroyCommandPool => DestroyCommandPool,
+    vkDestroyDescriptorPool => DestroyDescriptorPool,
+    vkDestroyDescriptorSetLayout => DestroyDescriptorSetLayout,
+    vkDestroyDevice => DestroyDevice,
+    vkDestroyEvent => DestroyEvent,
+    vkDestroyFence => DestroyFence,
+    vkDestroyFramebuffer => DestroyFramebuffer,
+    vkDestroyImage => DestroyImage,
+    vkDestroyImageView => DestroyImageView,
+    vkDestroyInstance => DestroyInstance,
+    vkDestroyPipeline => DestroyPipeline,
+    vkDestroyPipelineCache => DestroyPipelineCache,
+    vkDestroyPipelineLayout => DestroyPipelineLayout,
+    vkDestroyQueryPool => DestroyQueryPool,
+    vkDestroyRenderPass => DestroyRenderPass,
+    vkDestroySampler => DestroySampler,
+    vkDestroySemaphore => DestroySemaphore,
+    vkDestroyShaderModule => DestroyShaderModule,
+    vkDeviceWaitIdle => DeviceWaitIdle,
+    vkEndCommandBuffer => EndCommandBuffer,
+    vkEnumerateDeviceExtensionProperties => EnumerateDeviceExtensionProperties,
+    //vkEnumerateDeviceLayerProperties => EnumerateDeviceLayerProperties,
+    vkEnumerateInstanceExtensionProperties => EnumerateInstanceExtensionProperties,
+    //vkEnumerateInstanceLayerProperties => EnumerateInstanceLayerProperties,
+    vkEnumeratePhysicalDevices => EnumeratePhysicalDevices,
+    vkFlushMappedMemoryRanges => FlushMappedMemoryRanges,
+    vkFreeCommandBuffers => FreeCommandBuffers,
+    vkFreeDescriptorSets => FreeDescriptorSets,
+    vkFreeMemory => FreeMemory,
+    vkGetBufferMemoryRequirements => GetBufferMemoryRequirements,
+    vkGetDeviceMemoryCommitment => GetDeviceMemoryCommitment,
+    vkGetDeviceProcAddr => GetDeviceProcAddr,
+    vkGetDeviceQueue => GetDeviceQueue,
+    vkGetEventStatus => GetEventStatus,
+    vkGetFenceStatus => GetFenceStatus,
+    vkGetImageMemoryRequirements => GetImageMemoryRequirements,
+    vkGetImageSparseMemoryRequirements => GetImageSparseMemoryRequirements,
+    vkGetImageSubresourceLayout => GetImageSubresourceLayout,
+    //vkGetInstanceProcAddr => GetInstanceProcAddr,
+    vkGetPhysicalDeviceFeatures => GetPhysicalDeviceFeatures,
+    vkGetPhysicalDeviceFormatProperties => GetPhysicalDeviceFormatProperties,
+    //vkGetPhysicalDeviceImageFormatProperties => GetPhysicalDeviceImageFormatProperties,
+    vkGetPhysicalDeviceMemoryProperties => GetPhysicalDeviceMemoryProperties,
+    vkGetPhysicalDeviceProperties => GetPhysicalDeviceProperties,
+    vkGetPhysicalDeviceQueueFamilyProperties => GetPhysicalDeviceQueueFamilyProperties,
+    //vkGetPhysicalDeviceSparseImageFormatProperties => GetPhysicalDeviceSparseImageFormatProperties,
+    vkGetPipelineCacheData => GetPipelineCacheData,
+    vkGetQueryPoolResults => GetQueryPoolResults,
+    vkGetRenderAreaGranularity => GetRenderAreaGranularity,
+    vkInvalidateMappedMemoryRanges => InvalidateMappedMemoryRanges,
+    vkMapMemory => MapMemory,
+    vkMergePipelineCaches => MergePipelineCaches,
+    vkQueueSubmit => QueueSubmit,
+    vkQueueWaitIdle => QueueWaitIdle,
+    vkResetCommandBuffer => ResetCommandBuffer,
+    vkResetCommandPool => ResetCommandPool,
+    vkResetDescriptorPool => ResetDescriptorPool,
+    vkResetEvent => ResetEvent,
+    vkResetFences => ResetFences,
+    vkSetEvent => SetEvent,
+    vkTrimCommandPoolKHR => TrimCommandPoolKHR,
+    vkUnmapMemory => UnmapMemory,
+    vkUpdateDescriptorSets => UpdateDescriptorSets,
+    vkWaitForFences => WaitForFences,
+}
+
 #[doc(hidden)]
 #[inline]
 pub unsafe fn get_instance_proc_addr<I: Impl>(_instance: *mut c_void, name: *const c_char) -> Option<unsafe extern "C" fn()> {
     let name = CStr::from_ptr(name);
 
-    let name = if let Ok(name) = name.to_str() {
-        name
+    let command = if let Some(command) = Command::from_name(name) {
+        command
     } else {
-        warn!("invalid UTF8 in name passed to vk_icdGetInstanceProcAddr: {:?}", name);
+        warn!("unknown name passed to vk_icdGetInstanceProcAddr: {:?}", name);
         return None;
     };
 
-    vk_get_proc! { match name {
-        functions {
-            vkCreateInstance,
-            vkDestroyInstance,
-            vkEnumeratePhysicalDevices,
-            vkGetPhysicalDeviceFeatures,
-            vkGetPhysicalDeviceFormatProperties,
-            //vkGetPhysicalDeviceImageFormatProperties,
-            //vkGetPhysicalDeviceProperties,
-            //vkGetPhysicalDeviceQueueFamilyProperties,
-            //vkGetPhysicalDeviceMemoryProperties,
-            //vkGetInstanceProcAddr,
-            //vkGetDeviceProcAddr,
-            vkCreateDevice,
-            //vkEnumerateInstanceExtensionProperties,
-            //vkEnumerateDeviceExtensionProperties,
-            //vkEnumerateInstanceLayerProperties,
-            //vkEnumerateDeviceLayerProperties,
-            //vkGetPhysicalDeviceSparseImageFormatProperties,
-        }
-        _ => {
-            warn!("unknown name passed to vk_icdGetInstanceProcAddr: {:?}", name);
-            None
-        },
-    } }
+    if !I::supports(command) {
+        debug!("backend does not implement {}", command.name());
+        return None;
+    }
+
+    command.proc_addr::<I>()
 }
 
 macro_rules! vulkan_dispatch_cast {
     ($x:expr => $name:ident) => {
         &(*($x as *const dispatch::$name<I>)).imp
-    }
+    };
+    ($x:expr => $name:ident . $field:ident) => {
+        &(*($x as *const dispatch::$name<I>)).$field
+    };
+    ($x:expr => mut $name:ident) => {
+        &mut (*($x as *mut dispatch::$name<I>)).imp
+    };
+    ($x:expr => raw mut $name:ident) => {
+        &mut *($x as *mut dispatch::$name<I>)
+    };
+    ($x:expr => raw $name:ident) => {
+        &*($x as *const dispatch::$name<I>)
+    };
 }
 
 macro_rules! vulkan_dispatch_form {
@@ -242,10 +776,21 @@ macro_rules! vulkan_dispatch_form {
         vulkan_dispatch_form!($x => $name)
         }
     };
+    ($x:expr => $name:ident { $($field:ident: $value:expr,)* } with $alloc:expr) => {
+        // TODO: alloc
+        {
+        mem::drop($alloc); // To prevent unused variable warning
+        vulkan_dispatch_form!($x => $name { $($field: $value,)* })
+        }
+    };
     ($x:expr => $name:ident) => {
+        vulkan_dispatch_form!($x => $name {})
+    };
+    ($x:expr => $name:ident { $($field:ident: $value:expr,)* }) => {
         Box::into_raw(Box::new(dispatch::$name::<I> {
             loader: sys::ICD_LOADER_MAGIC as usize,
             imp: $x,
+            $($field: $value,)*
         })) as *mut _
     };
 }
@@ -272,16 +817,135 @@ macro_rules! vulkan_simple_destroy_impl {
     }
 }
 
+macro_rules! vulkan_handle_cast {
+    ($x:expr => mut $name:ident) => {
+        handle::cast_mut::<I::$name, _>($x)
+    };
+    ($x:expr => opt mut $name:ident) => {
+        handle::cast_opt_mut::<I::$name, _>($x)
+    };
+    ($x:expr => opt $name:ident) => {
+        handle::cast_opt::<I::$name, _>($x)
+    };
+    ($x:expr => $name:ident) => {
+        handle::cast::<I::$name, _>($x)
+    };
+}
+
+macro_rules! vulkan_handle_form {
+    ($x:expr => $name:ident with $alloc:expr) => {
+        // TODO: alloc
+        {
+        mem::drop($alloc); // To prevent unused variable warning
+        handle::form::<I::$name, _>($x)
+        }
+    };
+}
+
+macro_rules! vulkan_handle_destroy_impl {
+    ($raw_name:ident as $name:ident => $destructor_name:ident with alloc) => {
+        unsafe extern "C" fn $destructor_name<I: Impl>(
+            device: sys::VkDevice,
+            object: sys::$raw_name,
+            alloc: *const sys::VkAllocationCallbacks,
+        ) {
+            // TODO: alloc
+            mem::drop(alloc);
+
+            invalidate_references::<I>(device, handle::NonDispatchableHandle::as_raw(object));
+            vulkan_dispatch_cast!(device => Device.pipelines).forget(handle::NonDispatchableHandle::as_raw(object));
+
+            if let Some(object) = handle::take::<I::$name, _>(object) {
+                object.destroy(vulkan_dispatch_cast!(device => Device));
+            }
+        }
+    }
+}
+
 const OK: sys::VkResult = sys::VkResult::VK_SUCCESS;
+/// Returned when vk-middle refuses a call that violates the spec instead of passing it on.
+const INVALID_USAGE: sys::VkResult = sys::VkResult::VK_ERROR_VALIDATION_FAILED_EXT;
+
+mod command;
+use self::command::*;
+
+mod queue;
+use self::queue::*;
+
+/// Writes `properties` out in the two-call idiom shared by every enumeration command.
+unsafe fn write_extension_properties(
+    properties: Vec<ExtensionProperties>,
+    property_count: *mut u32,
+    raw_properties: *mut sys::VkExtensionProperties,
+) -> sys::VkResult {
+    if raw_properties.is_null() {
+        *property_count = properties.len() as u32;
+        return OK;
+    }
+
+    let buffer_size = *property_count;
+    *property_count = cmp::min(properties.len() as u32, buffer_size);
+    for (i, extension) in properties.iter().take(buffer_size as usize).enumerate() {
+        *raw_properties.offset(i as isize) = extension.into();
+    }
+
+    if buffer_size as usize >= properties.len() {
+        OK
+    } else {
+        sys::VkResult::VK_INCOMPLETE
+    }
+}
+
+/// Checks that every extension in `enabled` is among `available`.
+fn extensions_available<'a, E: Iterator<Item = &'a CStr>>(enabled: E, available: &[ExtensionProperties]) -> bool {
+    let mut all = true;
+    for name in enabled {
+        if !available.iter().any(|x| x.name.as_bytes() == name.to_bytes()) {
+            warn!("extension {:?} is not implemented by this backend", name);
+            all = false;
+        }
+    }
+    all
+}
+
+unsafe extern "C" fn vkEnumerateInstanceExtensionProperties<I: Impl>(
+    layer_name: *const c_char,
+    property_count: *mut u32,
+    properties: *mut sys::VkExtensionProperties,
+) -> sys::VkResult {
+    // A driver provides no layers, so extensions of a named layer are never ours to report.
+    if !layer_name.is_null() {
+        return Error::LayerNotPresent.into();
+    }
+
+    write_extension_properties(I::Instance::extension_properties(), property_count, properties)
+}
+
+unsafe extern "C" fn vkEnumerateDeviceExtensionProperties<I: Impl>(
+    physical_device: sys::VkPhysicalDevice,
+    layer_name: *const c_char,
+    property_count: *mut u32,
+    properties: *mut sys::VkExtensionProperties,
+) -> sys::VkResult {
+    if !layer_name.is_null() {
+        return Error::LayerNotPresent.into();
+    }
+
+    let extensions = vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_extension_properties();
+    write_extension_properties(extensions, property_count, properties)
+}
 
 unsafe extern "C" fn vkCreateInstance<I: Impl>(
     create_info: *const sys::VkInstanceCreateInfo,
     alloc: *const sys::VkAllocationCallbacks,
     instance: *mut sys::VkInstance,
 ) -> sys::VkResult {
-    let icd_inst = vulkan_try!(I::Instance::create(
-        InstanceCreateInfo(&*create_info, PhantomData),
-    ));
+    let info = InstanceCreateInfo(&*create_info, PhantomData);
+    if !extensions_available(info.enabled_extensions(), &I::Instance::extension_properties()) {
+        return Error::ExtensionNotPresent.into();
+    }
+
+    let icd_inst = vulkan_try!(I::Instance::create(info));
 
     *instance = vulkan_dispatch_form!(icd_inst => Instance with alloc);
 
@@ -340,55 +1004,1195 @@ unsafe extern "C" fn vkGetPhysicalDeviceFormatProperties<I: Impl>(
         .into();
 }
 
+unsafe extern "C" fn vkGetPhysicalDeviceProperties<I: Impl>(
+    physical_device: sys::VkPhysicalDevice,
+    properties: *mut sys::VkPhysicalDeviceProperties,
+) {
+    *properties = (&vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_properties()).into();
+}
+
+unsafe extern "C" fn vkGetPhysicalDeviceQueueFamilyProperties<I: Impl>(
+    physical_device: sys::VkPhysicalDevice,
+    property_count: *mut u32,
+    properties: *mut sys::VkQueueFamilyProperties,
+) {
+    let families = vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_queue_family_properties();
+
+    if properties.is_null() {
+        *property_count = families.len() as u32;
+        return;
+    }
+
+    *property_count = cmp::min(families.len() as u32, *property_count);
+    for (i, family) in families.into_iter().take(*property_count as usize).enumerate() {
+        *properties.offset(i as isize) = family.into();
+    }
+}
+
+unsafe extern "C" fn vkGetPhysicalDeviceMemoryProperties<I: Impl>(
+    physical_device: sys::VkPhysicalDevice,
+    properties: *mut sys::VkPhysicalDeviceMemoryProperties,
+) {
+    *properties = (&vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_memory_properties()).into();
+}
+
+unsafe extern "C" fn vkGetDeviceProcAddr<I: Impl>(
+    _device: sys::VkDevice,
+    name: *const c_char,
+) -> sys::PFN_vkVoidFunction {
+    get_instance_proc_addr::<I>(ptr::null_mut(), name)
+}
+
 unsafe extern "C" fn vkCreateDevice<I: Impl>(
     physical_device: sys::VkPhysicalDevice,
     create_info: *const sys::VkDeviceCreateInfo,
     alloc: *const sys::VkAllocationCallbacks,
     device: *mut sys::VkDevice,
 ) -> sys::VkResult {
+    let info = DeviceCreateInfo(&*create_info, PhantomData);
+    let extensions = vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_extension_properties();
+    if !extensions_available(info.enabled_extensions(), &extensions) {
+        return Error::ExtensionNotPresent.into();
+    }
+    let enabled_features = info.enabled_features();
+    if !vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_features().contains(&enabled_features) {
+        warn!("vkCreateDevice called with features the physical device does not support");
+        return Error::FeatureNotPresent.into();
+    }
+    let requested_queues: Vec<(u32, u32)> = info.queues()
+        .flat_map(|queue_info| {
+            let family_index = queue_info.family_index();
+            (0..queue_info.count()).map(move |queue_index| (family_index, queue_index))
+        })
+        .collect();
+
+    let properties = vulkan_dispatch_cast!(physical_device => PhysicalDevice).get_properties();
+    let pipeline_cache_header = CacheHeader::new(&properties);
+
     let icd_dev = vulkan_try!(I::PhysicalDevice::create_device(
         vulkan_dispatch_cast!(physical_device => PhysicalDevice),
-        DeviceCreateInfo(&*create_info, PhantomData),
+        info,
     ));
 
-    *device = vulkan_dispatch_form!(icd_dev => Device with alloc);
+    let mut icd_queues = Vec::new();
+    for &(family_index, queue_index) in &requested_queues {
+        icd_queues.push(vulkan_try!(icd_dev.get_queue(family_index, queue_index)));
+    }
+
+    *device = vulkan_dispatch_form!(icd_dev => Device {
+        enabled_features: enabled_features,
+        limits: properties.limits,
+        pipeline_cache_header: pipeline_cache_header,
+        pipelines: PipelineSharing::new(),
+        queues: Vec::new(),
+        command_buffers: Mutex::new(HashSet::new()),
+    } with alloc);
+
+    let queues = requested_queues.into_iter().zip(icd_queues)
+        .map(|((family_index, queue_index), icd_queue)| {
+            (family_index, queue_index, vulkan_dispatch_form!(icd_queue => Queue {
+                device: *device,
+                submissions: Mutex::new(Vec::new()),
+            }))
+        })
+        .collect();
+    vulkan_dispatch_cast!(*device => raw mut Device).queues = queues;
 
     OK
 }
 
-macro_rules! vulkan_dispatch_impls {
-    ($($n:ident)*) => {
-        #[allow(unused_imports)]
-        mod dispatch {
-            use super::{Impl, sys};
+unsafe extern "C" fn vkDestroyDevice<I: Impl>(
+    device: sys::VkDevice,
+    alloc: *const sys::VkAllocationCallbacks,
+) {
+    if device.is_null() {
+        return;
+    }
 
-            $(
-                #[repr(C)]
-                pub struct $n<I: Impl> {
-                    pub loader: usize,
-                    pub imp: I::$n,
-                }
-            )*
-        }
+    let pipelines = vulkan_dispatch_cast!(device => Device.pipelines);
+    debug!("pipeline creations: {} shared, {} passed to the backend", pipelines.hits(), pipelines.misses());
+
+    for &(_, _, queue) in &vulkan_dispatch_cast!(device => raw Device).queues {
+        vulkan_dispatch_drop!(queue => Queue);
     }
+    vulkan_dispatch_drop!(device => Device with alloc);
 }
 
-vulkan_dispatch_impls! {
-    Instance
-    PhysicalDevice
-    Device
+unsafe extern "C" fn vkAllocateMemory<I: Impl>(
+    device: sys::VkDevice,
+    allocate_info: *const sys::VkMemoryAllocateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    memory: *mut sys::VkDeviceMemory,
+) -> sys::VkResult {
+    let icd_memory = vulkan_try!(vulkan_dispatch_cast!(device => Device)
+        .allocate_memory(MemoryAllocateInfo(&*allocate_info, PhantomData)));
+
+    *memory = vulkan_handle_form!(icd_memory => DeviceMemory with alloc);
+
+    OK
 }
 
-#[macro_export]
-macro_rules! vulkan_icd_dispatch {
-    ($target:ty) => {
-        #[allow(non_snake_case)]
-        #[no_mangle]
-        pub unsafe extern "C" fn vk_icdGetInstanceProcAddr(instance: *mut ::std::os::raw::c_void, name: *const ::std::os::raw::c_char) 
-            -> Option<unsafe extern "C" fn()>
-        {
-            $crate::icd::get_instance_proc_addr::<$target>(instance, name)
+vulkan_handle_destroy_impl!(VkDeviceMemory as DeviceMemory => vkFreeMemory with alloc);
+
+unsafe extern "C" fn vkMapMemory<I: Impl>(
+    device: sys::VkDevice,
+    memory: sys::VkDeviceMemory,
+    offset: sys::VkDeviceSize,
+    size: sys::VkDeviceSize,
+    flags: sys::VkMemoryMapFlags,
+    data: *mut *mut c_void,
+) -> sys::VkResult {
+    let ptr = vulkan_try!(vulkan_handle_cast!(memory => mut DeviceMemory).map(
+        vulkan_dispatch_cast!(device => Device),
+        offset,
+        size,
+        MemoryMapFlags::from_bits_truncate(flags),
+    ));
+
+    *data = ptr as *mut c_void;
+
+    OK
+}
+
+unsafe extern "C" fn vkUnmapMemory<I: Impl>(
+    device: sys::VkDevice,
+    memory: sys::VkDeviceMemory,
+) {
+    vulkan_handle_cast!(memory => mut DeviceMemory)
+        .unmap(vulkan_dispatch_cast!(device => Device));
+}
+
+unsafe extern "C" fn vkFlushMappedMemoryRanges<I: Impl>(
+    device: sys::VkDevice,
+    range_count: u32,
+    ranges: *const sys::VkMappedMemoryRange,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_dispatch_cast!(device => Device)
+        .flush_mapped_memory_ranges(MappedMemoryRanges(raw_slice(ranges, range_count), PhantomData)));
+
+    OK
+}
+
+unsafe extern "C" fn vkInvalidateMappedMemoryRanges<I: Impl>(
+    device: sys::VkDevice,
+    range_count: u32,
+    ranges: *const sys::VkMappedMemoryRange,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_dispatch_cast!(device => Device)
+        .invalidate_mapped_memory_ranges(MappedMemoryRanges(raw_slice(ranges, range_count), PhantomData)));
+
+    OK
+}
+
+unsafe extern "C" fn vkGetDeviceMemoryCommitment<I: Impl>(
+    device: sys::VkDevice,
+    memory: sys::VkDeviceMemory,
+    committed_bytes: *mut sys::VkDeviceSize,
+) {
+    *committed_bytes = vulkan_handle_cast!(memory => DeviceMemory)
+        .get_memory_commitment(vulkan_dispatch_cast!(device => Device));
+}
+
+unsafe extern "C" fn vkBindBufferMemory<I: Impl>(
+    device: sys::VkDevice,
+    buffer: sys::VkBuffer,
+    memory: sys::VkDeviceMemory,
+    offset: sys::VkDeviceSize,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_handle_cast!(buffer => mut Buffer).bind_memory(
+        vulkan_dispatch_cast!(device => Device),
+        vulkan_handle_cast!(memory => DeviceMemory),
+        offset,
+    ));
+
+    OK
+}
+
+unsafe extern "C" fn vkBindImageMemory<I: Impl>(
+    device: sys::VkDevice,
+    image: sys::VkImage,
+    memory: sys::VkDeviceMemory,
+    offset: sys::VkDeviceSize,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_handle_cast!(image => mut Image).bind_memory(
+        vulkan_dispatch_cast!(device => Device),
+        vulkan_handle_cast!(memory => DeviceMemory),
+        offset,
+    ));
+
+    OK
+}
+
+unsafe extern "C" fn vkGetBufferMemoryRequirements<I: Impl>(
+    device: sys::VkDevice,
+    buffer: sys::VkBuffer,
+    requirements: *mut sys::VkMemoryRequirements,
+) {
+    *requirements = vulkan_handle_cast!(buffer => Buffer)
+        .get_memory_requirements(vulkan_dispatch_cast!(device => Device))
+        .into();
+}
+
+unsafe extern "C" fn vkGetImageMemoryRequirements<I: Impl>(
+    device: sys::VkDevice,
+    image: sys::VkImage,
+    requirements: *mut sys::VkMemoryRequirements,
+) {
+    *requirements = vulkan_handle_cast!(image => Image)
+        .get_memory_requirements(vulkan_dispatch_cast!(device => Device))
+        .into();
+}
+
+unsafe extern "C" fn vkGetImageSparseMemoryRequirements<I: Impl>(
+    device: sys::VkDevice,
+    image: sys::VkImage,
+    requirement_count: *mut u32,
+    requirements: *mut sys::VkSparseImageMemoryRequirements,
+) {
+    let capacity = if requirements.is_null() { !0 } else { *requirement_count };
+
+    let icd_requirements = vulkan_handle_cast!(image => Image)
+        .get_sparse_memory_requirements(vulkan_dispatch_cast!(device => Device), capacity);
+
+    *requirement_count = cmp::min(icd_requirements.len() as u32, capacity);
+
+    if !requirements.is_null() {
+        for (i, requirement) in icd_requirements.into_iter().take(capacity as usize).enumerate() {
+            *requirements.offset(i as isize) = requirement.into();
         }
     }
 }
 
+unsafe extern "C" fn vkCreateBuffer<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkBufferCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    buffer: *mut sys::VkBuffer,
+) -> sys::VkResult {
+    let icd_buffer = vulkan_try!(I::Buffer::new(
+        vulkan_dispatch_cast!(device => Device),
+        BufferCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *buffer = vulkan_handle_form!(icd_buffer => Buffer with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkBuffer as Buffer => vkDestroyBuffer with alloc);
+
+unsafe extern "C" fn vkCreateBufferView<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkBufferViewCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    view: *mut sys::VkBufferView,
+) -> sys::VkResult {
+    let icd_view = vulkan_try!(I::BufferView::new(
+        vulkan_dispatch_cast!(device => Device),
+        BufferViewCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *view = vulkan_handle_form!(icd_view => BufferView with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkBufferView as BufferView => vkDestroyBufferView with alloc);
+
+unsafe extern "C" fn vkCreateImage<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkImageCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    image: *mut sys::VkImage,
+) -> sys::VkResult {
+    let icd_image = vulkan_try!(I::Image::new(
+        vulkan_dispatch_cast!(device => Device),
+        ImageCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *image = vulkan_handle_form!(icd_image => Image with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkImage as Image => vkDestroyImage with alloc);
+
+unsafe extern "C" fn vkGetImageSubresourceLayout<I: Impl>(
+    device: sys::VkDevice,
+    image: sys::VkImage,
+    subresource: *const sys::VkImageSubresource,
+    layout: *mut sys::VkSubresourceLayout,
+) {
+    *layout = vulkan_handle_cast!(image => Image)
+        .get_subresource_layout(
+            vulkan_dispatch_cast!(device => Device),
+            ImageSubresource(&*subresource, PhantomData),
+        )
+        .into();
+}
+
+unsafe extern "C" fn vkCreateImageView<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkImageViewCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    view: *mut sys::VkImageView,
+) -> sys::VkResult {
+    let icd_view = vulkan_try!(I::ImageView::new(
+        vulkan_dispatch_cast!(device => Device),
+        ImageViewCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *view = vulkan_handle_form!(icd_view => ImageView with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkImageView as ImageView => vkDestroyImageView with alloc);
+
+unsafe extern "C" fn vkCreateFence<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkFenceCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    fence: *mut sys::VkFence,
+) -> sys::VkResult {
+    let icd_fence = vulkan_try!(I::Fence::new(
+        vulkan_dispatch_cast!(device => Device),
+        FenceCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *fence = vulkan_handle_form!(icd_fence => Fence with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkFence as Fence => vkDestroyFence with alloc);
+
+unsafe extern "C" fn vkResetFences<I: Impl>(
+    device: sys::VkDevice,
+    fence_count: u32,
+    fences: *const sys::VkFence,
+) -> sys::VkResult {
+    // A fence named more than once is still only borrowed once.
+    let mut raw_fences: Vec<sys::VkFence> = Vec::with_capacity(fence_count as usize);
+    for &fence in raw_slice(fences, fence_count) {
+        if !raw_fences.iter().any(|x| handle::NonDispatchableHandle::as_raw(*x) == handle::NonDispatchableHandle::as_raw(fence)) {
+            raw_fences.push(fence);
+        }
+    }
+    let mut icd_fences: Vec<&mut I::Fence> = raw_fences.into_iter()
+        .map(|fence| vulkan_handle_cast!(fence => mut Fence))
+        .collect();
+
+    vulkan_try!(I::Fence::reset(vulkan_dispatch_cast!(device => Device), &mut icd_fences));
+
+    OK
+}
+
+unsafe extern "C" fn vkGetFenceStatus<I: Impl>(
+    device: sys::VkDevice,
+    fence: sys::VkFence,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_handle_cast!(fence => Fence).get_status(vulkan_dispatch_cast!(device => Device)));
+    retire_fence::<I>(device, fence);
+
+    OK
+}
+
+unsafe extern "C" fn vkWaitForFences<I: Impl>(
+    device: sys::VkDevice,
+    fence_count: u32,
+    fences: *const sys::VkFence,
+    wait_all: sys::VkBool32,
+    timeout: u64,
+) -> sys::VkResult {
+    let icd_fences: Vec<&I::Fence> = raw_slice(fences, fence_count).iter()
+        .map(|&fence| vulkan_handle_cast!(fence => Fence))
+        .collect();
+
+    match vulkan_try!(I::Fence::wait(
+        vulkan_dispatch_cast!(device => Device),
+        &icd_fences,
+        wait_all == sys::VK_TRUE,
+        timeout,
+    )) {
+        WaitStatus::Signaled => {},
+        WaitStatus::Timeout => return sys::VkResult::VK_TIMEOUT,
+    }
+
+    for (&fence, icd_fence) in raw_slice(fences, fence_count).iter().zip(icd_fences) {
+        if wait_all == sys::VK_TRUE || icd_fence.get_status(vulkan_dispatch_cast!(device => Device)).is_ok() {
+            retire_fence::<I>(device, fence);
+        }
+    }
+
+    OK
+}
+
+unsafe extern "C" fn vkCreateSemaphore<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkSemaphoreCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    semaphore: *mut sys::VkSemaphore,
+) -> sys::VkResult {
+    let icd_semaphore = vulkan_try!(I::Semaphore::new(
+        vulkan_dispatch_cast!(device => Device),
+        SemaphoreCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *semaphore = vulkan_handle_form!(icd_semaphore => Semaphore with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkSemaphore as Semaphore => vkDestroySemaphore with alloc);
+
+unsafe extern "C" fn vkCreateEvent<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkEventCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    event: *mut sys::VkEvent,
+) -> sys::VkResult {
+    let icd_event = vulkan_try!(I::Event::new(
+        vulkan_dispatch_cast!(device => Device),
+        EventCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *event = vulkan_handle_form!(icd_event => Event with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkEvent as Event => vkDestroyEvent with alloc);
+
+unsafe extern "C" fn vkGetEventStatus<I: Impl>(
+    device: sys::VkDevice,
+    event: sys::VkEvent,
+) -> sys::VkResult {
+    if vulkan_try!(vulkan_handle_cast!(event => Event).get_status(vulkan_dispatch_cast!(device => Device))) {
+        sys::VkResult::VK_EVENT_SET
+    } else {
+        sys::VkResult::VK_EVENT_RESET
+    }
+}
+
+unsafe extern "C" fn vkSetEvent<I: Impl>(
+    device: sys::VkDevice,
+    event: sys::VkEvent,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_handle_cast!(event => mut Event).set(vulkan_dispatch_cast!(device => Device)));
+
+    OK
+}
+
+unsafe extern "C" fn vkResetEvent<I: Impl>(
+    device: sys::VkDevice,
+    event: sys::VkEvent,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_handle_cast!(event => mut Event).reset(vulkan_dispatch_cast!(device => Device)));
+
+    OK
+}
+
+unsafe extern "C" fn vkCreateShaderModule<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkShaderModuleCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    module: *mut sys::VkShaderModule,
+) -> sys::VkResult {
+    let info = &*create_info;
+    if info.codeSize == 0 || info.codeSize % 4 != 0 {
+        error!("shader module code is {} bytes, which is not a whole number of SPIR-V words", info.codeSize);
+        return Error::InvalidShader.into();
+    }
+    let spirv = vulkan_try!(SpirvModule::new(raw_slice(info.pCode, (info.codeSize / 4) as u32)));
+
+    let icd_module = vulkan_try!(I::ShaderModule::new(
+        vulkan_dispatch_cast!(device => Device),
+        ShaderModuleCreateInfo(info, &spirv, PhantomData),
+    ));
+
+    *module = vulkan_handle_form!(icd_module => ShaderModule with alloc);
+    vulkan_dispatch_cast!(device => Device.pipelines)
+        .add_shader_module(handle::NonDispatchableHandle::as_raw(*module), spirv.code());
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkShaderModule as ShaderModule => vkDestroyShaderModule with alloc);
+
+unsafe extern "C" fn vkCreatePipelineCache<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkPipelineCacheCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    pipeline_cache: *mut sys::VkPipelineCache,
+) -> sys::VkResult {
+    let header = *vulkan_dispatch_cast!(device => Device.pipeline_cache_header);
+    // Data made for another device, or by another version of the backend, is as good as none.
+    let mut raw_info = *create_info;
+    if !header.accepts(PipelineCacheCreateInfo::<I>(&raw_info, header, PhantomData).initial_data()) {
+        warn!("ignoring pipeline cache initial data that was not made for this device");
+        raw_info.initialDataSize = 0;
+        raw_info.pInitialData = ptr::null();
+    }
+
+    let icd_cache = vulkan_try!(I::PipelineCache::new(
+        vulkan_dispatch_cast!(device => Device),
+        PipelineCacheCreateInfo(&raw_info, header, PhantomData),
+    ));
+
+    *pipeline_cache = vulkan_handle_form!(icd_cache => PipelineCache with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkPipelineCache as PipelineCache => vkDestroyPipelineCache with alloc);
+
+unsafe extern "C" fn vkGetPipelineCacheData<I: Impl>(
+    device: sys::VkDevice,
+    pipeline_cache: sys::VkPipelineCache,
+    data_size: *mut usize,
+    data: *mut c_void,
+) -> sys::VkResult {
+    let device = vulkan_dispatch_cast!(device => Device);
+    let cache = vulkan_handle_cast!(pipeline_cache => PipelineCache);
+
+    let actual_size = vulkan_try!(cache.get_data_size(device));
+
+    if data.is_null() {
+        *data_size = actual_size;
+        return OK;
+    }
+
+    let buffer = slice::from_raw_parts_mut(data as *mut u8, *data_size);
+    *data_size = vulkan_try!(cache.get_data(device, buffer));
+
+    if *data_size >= actual_size {
+        OK
+    } else {
+        sys::VkResult::VK_INCOMPLETE
+    }
+}
+
+unsafe extern "C" fn vkMergePipelineCaches<I: Impl>(
+    device: sys::VkDevice,
+    dst_cache: sys::VkPipelineCache,
+    src_cache_count: u32,
+    src_caches: *const sys::VkPipelineCache,
+) -> sys::VkResult {
+    let icd_src_caches: Vec<&I::PipelineCache> = raw_slice(src_caches, src_cache_count).iter()
+        .map(|&cache| vulkan_handle_cast!(cache => PipelineCache))
+        .collect();
+
+    vulkan_try!(vulkan_handle_cast!(dst_cache => mut PipelineCache)
+        .merge(vulkan_dispatch_cast!(device => Device), &icd_src_caches));
+
+    OK
+}
+
+/// What becomes of one create info of a batch pipeline creation.
+enum PipelineCreation {
+    /// Given the live pipeline with this raw handle.
+    Shared(u64),
+    /// Given the same pipeline as an earlier create info of the batch.
+    Duplicate(usize),
+    /// Passed on to the backend, at this index of what it is asked to create.
+    New(usize),
+}
+
+/// Creates a batch of pipelines, passing on to the backend only the create infos that do not
+/// match a live pipeline or an earlier create info of the batch. Pipelines that failed are
+/// reported as VK_NULL_HANDLE, and the first failure becomes the result of the whole call.
+unsafe fn create_pipelines<I: Impl, T: RawPipelineCreateInfo, F>(
+    device: sys::VkDevice,
+    raw_infos: &[T],
+    mut keys: Vec<Option<PipelineKey>>,
+    alloc: *const sys::VkAllocationCallbacks,
+    pipelines: *mut sys::VkPipeline,
+    create: F,
+) -> sys::VkResult
+    where F: FnOnce(&[T]) -> Vec<Result<I::Pipeline>>
+{
+    let sharing = vulkan_dispatch_cast!(device => Device.pipelines);
+
+    let mut creations = Vec::with_capacity(raw_infos.len());
+    let mut batch = Vec::new();
+    for (i, raw_info) in raw_infos.iter().enumerate() {
+        let shared = keys[i].as_ref().and_then(|key| sharing.share(key));
+        let duplicate = (0..i).find(|&j| keys[i].is_some() && keys[j] == keys[i] && match creations[j] {
+            PipelineCreation::New(_) => true,
+            _ => false,
+        });
+        let creation = match (shared, duplicate) {
+            (Some(pipeline), _) => PipelineCreation::Shared(pipeline),
+            (None, Some(j)) => PipelineCreation::Duplicate(j),
+            (None, None) => {
+                // A base named by index may no longer be at that index, or passed on at all.
+                let mut raw_info = *raw_info;
+                if let Some(base) = raw_info.base_index() {
+                    match creations.get(base) {
+                        Some(&PipelineCreation::Shared(pipeline)) => raw_info.set_base(handle::NonDispatchableHandle::from_raw(pipeline), -1),
+                        Some(&PipelineCreation::Duplicate(j)) => match creations[j] {
+                            PipelineCreation::New(index) => raw_info.set_base(handle::NonDispatchableHandle::null(), index as i32),
+                            _ => unreachable!(),
+                        },
+                        Some(&PipelineCreation::New(index)) => raw_info.set_base(handle::NonDispatchableHandle::null(), index as i32),
+                        None => {
+                            warn!("derivative pipeline names base pipeline {}, which is not earlier in the batch", base);
+                            raw_info.set_base(handle::NonDispatchableHandle::null(), -1);
+                        },
+                    }
+                }
+                batch.push(raw_info);
+                PipelineCreation::New(batch.len() - 1)
+            },
+        };
+        creations.push(creation);
+    }
+
+    let mut icd_pipelines = create(&batch).into_iter();
+    let mut created: Vec<Result<sys::VkPipeline>> = Vec::with_capacity(creations.len());
+    let mut result = OK;
+
+    for (i, creation) in creations.into_iter().enumerate() {
+        let pipeline = match creation {
+            PipelineCreation::Shared(pipeline) => {
+                debug!("pipeline {:016x} shared", keys[i].as_ref().map(|key| key.digest()).unwrap_or(0));
+                Ok(handle::NonDispatchableHandle::from_raw(pipeline))
+            },
+            PipelineCreation::Duplicate(j) => {
+                if let Ok(pipeline) = created[j] {
+                    sharing.retain(handle::NonDispatchableHandle::as_raw(pipeline));
+                }
+                created[j]
+            },
+            PipelineCreation::New(_) => match icd_pipelines.next() {
+                Some(Ok(pipeline)) => {
+                    let pipeline: sys::VkPipeline = vulkan_handle_form!(pipeline => Pipeline with alloc);
+                    sharing.insert(handle::NonDispatchableHandle::as_raw(pipeline), keys[i].take());
+                    Ok(pipeline)
+                },
+                Some(Err(err)) => Err(err),
+                None => {
+                    error!("backend created fewer pipelines than requested");
+                    Err(Error::InitializationFailed)
+                },
+            },
+        };
+        created.push(pipeline);
+
+        *pipelines.offset(i as isize) = match pipeline {
+            Ok(pipeline) => pipeline,
+            Err(err) => {
+                if result == OK {
+                    result = err.into();
+                }
+                handle::NonDispatchableHandle::null()
+            },
+        };
+    }
+
+    result
+}
+
+unsafe extern "C" fn vkCreateGraphicsPipelines<I: Impl>(
+    device: sys::VkDevice,
+    pipeline_cache: sys::VkPipelineCache,
+    create_info_count: u32,
+    create_infos: *const sys::VkGraphicsPipelineCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    pipelines: *mut sys::VkPipeline,
+) -> sys::VkResult {
+    let raw_infos = raw_slice(create_infos, create_info_count);
+    let keys = raw_infos.iter()
+        .map(|x| vulkan_dispatch_cast!(device => Device.pipelines).graphics_key(&GraphicsPipelineCreateInfo::<I>(x, PhantomData)))
+        .collect();
+
+    create_pipelines::<I, _, _>(device, raw_infos, keys, alloc, pipelines, |raw_infos| {
+        let infos: Vec<_> = raw_infos.iter()
+            .map(|x| GraphicsPipelineCreateInfo(x, PhantomData))
+            .collect();
+
+        I::Pipeline::create_graphics(
+            vulkan_dispatch_cast!(device => Device),
+            vulkan_handle_cast!(pipeline_cache => opt PipelineCache),
+            &infos,
+        )
+    })
+}
+
+unsafe extern "C" fn vkCreateComputePipelines<I: Impl>(
+    device: sys::VkDevice,
+    pipeline_cache: sys::VkPipelineCache,
+    create_info_count: u32,
+    create_infos: *const sys::VkComputePipelineCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    pipelines: *mut sys::VkPipeline,
+) -> sys::VkResult {
+    let raw_infos = raw_slice(create_infos, create_info_count);
+    let keys = raw_infos.iter()
+        .map(|x| vulkan_dispatch_cast!(device => Device.pipelines).compute_key(&ComputePipelineCreateInfo::<I>(x, PhantomData)))
+        .collect();
+
+    create_pipelines::<I, _, _>(device, raw_infos, keys, alloc, pipelines, |raw_infos| {
+        let infos: Vec<_> = raw_infos.iter()
+            .map(|x| ComputePipelineCreateInfo(x, PhantomData))
+            .collect();
+
+        I::Pipeline::create_compute(
+            vulkan_dispatch_cast!(device => Device),
+            vulkan_handle_cast!(pipeline_cache => opt PipelineCache),
+            &infos,
+        )
+    })
+}
+
+unsafe extern "C" fn vkDestroyPipeline<I: Impl>(
+    device: sys::VkDevice,
+    pipeline: sys::VkPipeline,
+    alloc: *const sys::VkAllocationCallbacks,
+) {
+    // TODO: alloc
+    mem::drop(alloc);
+
+    // A handle given out for several creations stays valid until each of them is destroyed.
+    let raw = handle::NonDispatchableHandle::as_raw(pipeline);
+    if raw == 0 || !vulkan_dispatch_cast!(device => Device.pipelines).release(raw) {
+        return;
+    }
+
+    invalidate_references::<I>(device, raw);
+
+    if let Some(object) = handle::take::<I::Pipeline, _>(pipeline) {
+        object.destroy(vulkan_dispatch_cast!(device => Device));
+    }
+}
+
+unsafe extern "C" fn vkCreateQueryPool<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkQueryPoolCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    query_pool: *mut sys::VkQueryPool,
+) -> sys::VkResult {
+    let info = QueryPoolCreateInfo(&*create_info, PhantomData);
+    let value_count = query::value_count(info.query_type(), info.pipeline_statistics());
+
+    let icd_pool = vulkan_try!(I::QueryPool::new(
+        vulkan_dispatch_cast!(device => Device),
+        info,
+    ));
+
+    // TODO: alloc
+    mem::drop(alloc);
+
+    *query_pool = handle::form(query::QueryPoolObject::<I> {
+        imp: icd_pool,
+        value_count: value_count,
+    });
+
+    OK
+}
+
+unsafe extern "C" fn vkDestroyQueryPool<I: Impl>(
+    device: sys::VkDevice,
+    query_pool: sys::VkQueryPool,
+    alloc: *const sys::VkAllocationCallbacks,
+) {
+    // TODO: alloc
+    mem::drop(alloc);
+
+    invalidate_references::<I>(device, handle::NonDispatchableHandle::as_raw(query_pool));
+
+    if let Some(pool) = handle::take::<query::QueryPoolObject<I>, _>(query_pool) {
+        pool.imp.destroy(vulkan_dispatch_cast!(device => Device));
+    }
+}
+
+unsafe extern "C" fn vkGetQueryPoolResults<I: Impl>(
+    device: sys::VkDevice,
+    query_pool: sys::VkQueryPool,
+    first_query: u32,
+    query_count: u32,
+    data_size: usize,
+    data: *mut c_void,
+    stride: sys::VkDeviceSize,
+    flags: sys::VkQueryResultFlags,
+) -> sys::VkResult {
+    let pool = handle::cast::<query::QueryPoolObject<I>, _>(query_pool);
+    let flags = QueryResultFlags::from_bits_truncate(flags);
+
+    let end = match first_query.checked_add(query_count) {
+        Some(end) => end,
+        None => return INVALID_USAGE,
+    };
+
+    let mut results = vulkan_try!(pool.imp.get_results(
+        vulkan_dispatch_cast!(device => Device),
+        first_query..end,
+        flags.contains(VK_QUERY_RESULT_WAIT_BIT),
+    ));
+    results.resize(query_count as usize, None);
+
+    let data = slice::from_raw_parts_mut(data as *mut u8, data_size);
+    if vulkan_try!(query::write_query_results(&results, pool.value_count, data, stride as usize, flags)) {
+        OK
+    } else {
+        sys::VkResult::VK_NOT_READY
+    }
+}
+
+unsafe extern "C" fn vkCreateSampler<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkSamplerCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    sampler: *mut sys::VkSampler,
+) -> sys::VkResult {
+    let info = SamplerCreateInfo(&*create_info, PhantomData);
+    vulkan_try!(info.validate(
+        vulkan_dispatch_cast!(device => Device.enabled_features),
+        vulkan_dispatch_cast!(device => Device.limits),
+    ));
+
+    let icd_sampler = vulkan_try!(I::Sampler::new(
+        vulkan_dispatch_cast!(device => Device),
+        info,
+    ));
+
+    *sampler = vulkan_handle_form!(icd_sampler => Sampler with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkSampler as Sampler => vkDestroySampler with alloc);
+
+unsafe extern "C" fn vkCreateDescriptorSetLayout<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkDescriptorSetLayoutCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    set_layout: *mut sys::VkDescriptorSetLayout,
+) -> sys::VkResult {
+    let icd_layout = vulkan_try!(I::DescriptorSetLayout::new(
+        vulkan_dispatch_cast!(device => Device),
+        DescriptorSetLayoutCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *set_layout = vulkan_handle_form!(icd_layout => DescriptorSetLayout with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkDescriptorSetLayout as DescriptorSetLayout => vkDestroyDescriptorSetLayout with alloc);
+
+unsafe extern "C" fn vkCreatePipelineLayout<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkPipelineLayoutCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    pipeline_layout: *mut sys::VkPipelineLayout,
+) -> sys::VkResult {
+    let icd_layout = vulkan_try!(I::PipelineLayout::new(
+        vulkan_dispatch_cast!(device => Device),
+        PipelineLayoutCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *pipeline_layout = vulkan_handle_form!(icd_layout => PipelineLayout with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkPipelineLayout as PipelineLayout => vkDestroyPipelineLayout with alloc);
+
+unsafe extern "C" fn vkCreateDescriptorPool<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkDescriptorPoolCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    descriptor_pool: *mut sys::VkDescriptorPool,
+) -> sys::VkResult {
+    let info = DescriptorPoolCreateInfo(&*create_info, PhantomData);
+    let flags = info.flags();
+
+    let icd_pool = vulkan_try!(I::DescriptorPool::new(
+        vulkan_dispatch_cast!(device => Device),
+        info,
+    ));
+
+    // TODO: alloc
+    mem::drop(alloc);
+
+    *descriptor_pool = handle::form(descriptor::DescriptorPoolObject::<I> {
+        imp: icd_pool,
+        flags: flags,
+        sets: HashSet::new(),
+    });
+
+    OK
+}
+
+unsafe extern "C" fn vkDestroyDescriptorPool<I: Impl>(
+    device: sys::VkDevice,
+    descriptor_pool: sys::VkDescriptorPool,
+    alloc: *const sys::VkAllocationCallbacks,
+) {
+    // TODO: alloc
+    mem::drop(alloc);
+
+    if let Some(mut pool) = handle::take::<descriptor::DescriptorPoolObject<I>, _>(descriptor_pool) {
+        for &set in &pool.sets {
+            invalidate_references::<I>(device, handle::NonDispatchableHandle::as_raw(set));
+        }
+
+        let device = vulkan_dispatch_cast!(device => Device);
+        let sets = pool.take_sets();
+        if !sets.is_empty() {
+            pool.imp.free(device, sets);
+        }
+        pool.imp.destroy(device);
+    }
+}
+
+unsafe extern "C" fn vkResetDescriptorPool<I: Impl>(
+    device: sys::VkDevice,
+    descriptor_pool: sys::VkDescriptorPool,
+    _flags: sys::VkDescriptorPoolResetFlags,
+) -> sys::VkResult {
+    let pool = handle::cast_mut::<descriptor::DescriptorPoolObject<I>, _>(descriptor_pool);
+
+    for &set in &pool.sets {
+        invalidate_references::<I>(device, handle::NonDispatchableHandle::as_raw(set));
+    }
+    let sets = pool.take_sets();
+    pool.imp.reset(vulkan_dispatch_cast!(device => Device), sets);
+
+    OK
+}
+
+unsafe extern "C" fn vkAllocateDescriptorSets<I: Impl>(
+    device: sys::VkDevice,
+    allocate_info: *const sys::VkDescriptorSetAllocateInfo,
+    descriptor_sets: *mut sys::VkDescriptorSet,
+) -> sys::VkResult {
+    let allocate_info = &*allocate_info;
+    let count = allocate_info.descriptorSetCount;
+    let pool = handle::cast_mut::<descriptor::DescriptorPoolObject<I>, _>(allocate_info.descriptorPool);
+
+    let layouts: Vec<&I::DescriptorSetLayout> = raw_slice(allocate_info.pSetLayouts, count).iter()
+        .map(|&layout| vulkan_handle_cast!(layout => DescriptorSetLayout))
+        .collect();
+
+    let result = pool.imp.allocate(vulkan_dispatch_cast!(device => Device), &layouts);
+    let icd_sets = match result {
+        Ok(ref icd_sets) if icd_sets.len() != layouts.len() => {
+            error!("backend allocated {} descriptor sets instead of {}", icd_sets.len(), layouts.len());
+            Err(Error::InitializationFailed)
+        },
+        other => other,
+    };
+
+    match icd_sets {
+        Ok(icd_sets) => {
+            for (i, icd_set) in icd_sets.into_iter().enumerate() {
+                let set: sys::VkDescriptorSet = handle::form::<I::DescriptorSet, _>(icd_set);
+                pool.sets.insert(set);
+                *descriptor_sets.offset(i as isize) = set;
+            }
+            OK
+        },
+        Err(err) => {
+            for i in 0..count {
+                *descriptor_sets.offset(i as isize) = handle::NonDispatchableHandle::null();
+            }
+            err.into()
+        },
+    }
+}
+
+unsafe extern "C" fn vkFreeDescriptorSets<I: Impl>(
+    device: sys::VkDevice,
+    descriptor_pool: sys::VkDescriptorPool,
+    descriptor_set_count: u32,
+    descriptor_sets: *const sys::VkDescriptorSet,
+) -> sys::VkResult {
+    let pool = handle::cast_mut::<descriptor::DescriptorPoolObject<I>, _>(descriptor_pool);
+
+    if !pool.flags.contains(VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT) {
+        error!("vkFreeDescriptorSets called on a pool created without VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT");
+        return INVALID_USAGE;
+    }
+
+    let sets: Vec<sys::VkDescriptorSet> = raw_slice(descriptor_sets, descriptor_set_count).iter()
+        .cloned()
+        .filter(|&set| !handle::NonDispatchableHandle::is_null(set))
+        .collect();
+    if sets.iter().any(|set| !pool.sets.contains(set)) {
+        error!("vkFreeDescriptorSets called with a descriptor set not allocated from the given pool");
+        return INVALID_USAGE;
+    }
+
+    let mut icd_sets = Vec::new();
+    for set in sets {
+        // The same set may be listed twice
+        if !pool.sets.remove(&set) {
+            continue;
+        }
+        invalidate_references::<I>(device, handle::NonDispatchableHandle::as_raw(set));
+        icd_sets.extend(handle::take::<I::DescriptorSet, _>(set));
+    }
+
+    pool.imp.free(vulkan_dispatch_cast!(device => Device), icd_sets);
+
+    OK
+}
+
+unsafe extern "C" fn vkUpdateDescriptorSets<I: Impl>(
+    device: sys::VkDevice,
+    descriptor_write_count: u32,
+    descriptor_writes: *const sys::VkWriteDescriptorSet,
+    descriptor_copy_count: u32,
+    descriptor_copies: *const sys::VkCopyDescriptorSet,
+) {
+    let writes: Vec<DescriptorWrite<I>> = raw_slice(descriptor_writes, descriptor_write_count).iter()
+        .map(|x| DescriptorWrite::from_raw(x))
+        .collect();
+    let copies: Vec<DescriptorCopy<I>> = raw_slice(descriptor_copies, descriptor_copy_count).iter()
+        .map(|x| DescriptorCopy::from_raw(x))
+        .collect();
+
+    vulkan_dispatch_cast!(device => Device).update_descriptor_sets(&writes, &copies);
+
+    // Command buffers that bound an updated set no longer see what they were recorded with.
+    let written = raw_slice(descriptor_writes, descriptor_write_count).iter().map(|x| x.dstSet);
+    let copied = raw_slice(descriptor_copies, descriptor_copy_count).iter().map(|x| x.dstSet);
+    for set in written.chain(copied) {
+        invalidate_references::<I>(device, handle::NonDispatchableHandle::as_raw(set));
+    }
+}
+
+unsafe extern "C" fn vkCreateFramebuffer<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkFramebufferCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    framebuffer: *mut sys::VkFramebuffer,
+) -> sys::VkResult {
+    let icd_framebuffer = vulkan_try!(I::Framebuffer::new(
+        vulkan_dispatch_cast!(device => Device),
+        FramebufferCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *framebuffer = vulkan_handle_form!(icd_framebuffer => Framebuffer with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkFramebuffer as Framebuffer => vkDestroyFramebuffer with alloc);
+
+unsafe extern "C" fn vkCreateRenderPass<I: Impl>(
+    device: sys::VkDevice,
+    create_info: *const sys::VkRenderPassCreateInfo,
+    alloc: *const sys::VkAllocationCallbacks,
+    render_pass: *mut sys::VkRenderPass,
+) -> sys::VkResult {
+    let icd_render_pass = vulkan_try!(I::RenderPass::new(
+        vulkan_dispatch_cast!(device => Device),
+        RenderPassCreateInfo(&*create_info, PhantomData),
+    ));
+
+    *render_pass = vulkan_handle_form!(icd_render_pass => RenderPass with alloc);
+
+    OK
+}
+
+vulkan_handle_destroy_impl!(VkRenderPass as RenderPass => vkDestroyRenderPass with alloc);
+
+unsafe extern "C" fn vkGetRenderAreaGranularity<I: Impl>(
+    device: sys::VkDevice,
+    render_pass: sys::VkRenderPass,
+    granularity: *mut sys::VkExtent2D,
+) {
+    *granularity = vulkan_handle_cast!(render_pass => RenderPass)
+        .get_render_area_granularity(vulkan_dispatch_cast!(device => Device))
+        .into();
+}
+
+macro_rules! vulkan_dispatch_impls {
+    ($($n:ident { $($field:ident: $field_ty:ty,)* })*) => {
+        #[allow(unused_imports)]
+        mod dispatch {
+            use super::{Impl, PhysicalDeviceFeatures, PhysicalDeviceLimits, CacheHeader, PipelineSharing, CommandPoolCreateFlags, CommandBufferLevel, Lifecycle, Submission, sys};
+            use std::collections::HashSet;
+            use std::sync::Mutex;
+
+            $(
+                #[repr(C)]
+                pub struct $n<I: Impl> {
+                    pub loader: usize,
+                    pub imp: I::$n,
+                    $(pub $field: $field_ty,)*
+                }
+            )*
+        }
+    }
+}
+
+vulkan_dispatch_impls! {
+    Instance {}
+    PhysicalDevice {}
+    Device {
+        enabled_features: PhysicalDeviceFeatures,
+        limits: PhysicalDeviceLimits,
+        pipeline_cache_header: CacheHeader,
+        pipelines: PipelineSharing,
+        queues: Vec<(u32, u32, sys::VkQueue)>,
+        command_buffers: Mutex<HashSet<sys::VkCommandBuffer>>,
+    }
+    Queue {
+        device: sys::VkDevice,
+        submissions: Mutex<Vec<Submission>>,
+    }
+    CommandBuffer {
+        device: sys::VkDevice,
+        pool_flags: CommandPoolCreateFlags,
+        level: CommandBufferLevel,
+        lifecycle: Mutex<Lifecycle>,
+    }
+}
+
+#[macro_export]
+macro_rules! vulkan_icd_dispatch {
+    ($target:ty) => {
+        #[allow(non_snake_case)]
+        #[no_mangle]
+        pub unsafe extern "C" fn vk_icdGetInstanceProcAddr(instance: *mut ::std::os::raw::c_void, name: *const ::std::os::raw::c_char) 
+            -> Option<unsafe extern "C" fn()>
+        {
+            $crate::icd::get_instance_proc_addr::<$target>(instance, name)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+
+    #[test]
+    fn every_command_is_found_by_name() {
+        for &command in Command::all() {
+            let name = CString::new(command.name()).unwrap();
+            assert_eq!(Command::from_name(&name), Some(command));
+        }
+    }
+
+    #[test]
+    fn commands_are_declared_in_name_order() {
+        for pair in COMMANDS_BY_NAME.windows(2) {
+            assert!(pair[0].0.as_bytes() < pair[1].0.as_bytes(), "{} is declared before {}", pair[0].0, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_not_found() {
+        for name in &["", "vk", "vkCreateInstanceKHX", "vkTrimCommandPool", "vkCreateSwapchainKHR"] {
+            assert_eq!(Command::from_name(&CString::new(*name).unwrap()), None);
+        }
+    }
+}
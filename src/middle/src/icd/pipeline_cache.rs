@@ -0,0 +1,347 @@
+//! The data of pipeline caches, in a format vk-middle owns so that backends get header validation,
+//! merging and persistence without implementing any of them.
+//!
+//! Data starts with the `VkPipelineCacheHeaderVersionOne` header the spec requires, which names the
+//! device it was made for by vendor ID, device ID and pipeline cache UUID. `vkCreatePipelineCache`
+//! ignores initial data with any other header, so a backend that changes what it stores only has
+//! to change its UUID. Entries follow the header, each a key and a value of whatever bytes the
+//! backend chooses. Every integer is little-endian, as the header's are.
+//!
+//! When `VK_MIDDLE_PIPELINE_CACHE_DIR` names a directory, caches also start out with the entries
+//! saved there for their device, and save their own there when they are destroyed, so applications
+//! that never call `vkGetPipelineCacheData` still keep their pipelines from one run to the next.
+
+use super::*;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+
+/// The size of `VkPipelineCacheHeaderVersionOne`.
+pub const HEADER_SIZE: usize = 32;
+
+/// `VK_PIPELINE_CACHE_HEADER_VERSION_ONE`.
+const HEADER_VERSION_ONE: u32 = 1;
+
+const DIRECTORY_VARIABLE: &'static str = "VK_MIDDLE_PIPELINE_CACHE_DIR";
+
+/// How many caches this process has started saving, which tells their temporary files apart.
+static SAVES: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// What identifies the device pipeline cache data was made for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CacheHeader {
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub uuid: [u8; 16],
+}
+
+impl CacheHeader {
+    pub fn new(properties: &PhysicalDeviceProperties) -> CacheHeader {
+        CacheHeader {
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            uuid: properties.pipeline_cache_uuid,
+        }
+    }
+
+    /// Splits data into its header and what follows it, if it starts with a version one header.
+    pub fn read(data: &[u8]) -> Option<(CacheHeader, &[u8])> {
+        if data.len() < HEADER_SIZE {
+            return None;
+        }
+        // Later versions of the header may be longer, but are still to start with its size.
+        let size = read_u32(&data[0..4]) as usize;
+        if size < HEADER_SIZE || size > data.len() || read_u32(&data[4..8]) != HEADER_VERSION_ONE {
+            return None;
+        }
+        let mut uuid = [0; 16];
+        uuid.copy_from_slice(&data[16..32]);
+        let header = CacheHeader {
+            vendor_id: read_u32(&data[8..12]),
+            device_id: read_u32(&data[12..16]),
+            uuid: uuid,
+        };
+        Some((header, &data[size..]))
+    }
+
+    /// Whether `data` can initialize a cache for this device: it is empty or was made for it.
+    pub fn accepts(&self, data: &[u8]) -> bool {
+        data.is_empty() || CacheHeader::read(data).map(|(header, _)| header) == Some(*self)
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        write_u32(data, HEADER_SIZE as u32);
+        write_u32(data, HEADER_VERSION_ONE);
+        write_u32(data, self.vendor_id);
+        write_u32(data, self.device_id);
+        data.extend_from_slice(&self.uuid);
+    }
+
+    /// The name of the file the caches of this device are saved in.
+    fn file_name(&self) -> String {
+        let uuid: Vec<String> = self.uuid.iter().map(|x| format!("{:02x}", x)).collect();
+        format!("{:08x}-{:08x}-{}.bin", self.vendor_id, self.device_id, uuid.concat())
+    }
+}
+
+/// The entries of a pipeline cache, for backends to fill with what they compile pipelines into,
+/// keyed by whatever they can derive from a pipeline's create info.
+///
+/// Entries are only ever added, so backends can hold on to values after looking them up. The
+/// default `get_data_size`, `get_data` and `merge` of `PipelineCache` work with it once a backend
+/// returns it from `PipelineCache::data`.
+pub struct PipelineCacheData {
+    header: CacheHeader,
+    state: Mutex<State>,
+    /// Where the cache is saved, if it is.
+    file: Option<PathBuf>,
+}
+
+struct State {
+    entries: BTreeMap<Vec<u8>, Arc<Vec<u8>>>,
+    /// Whether there are entries the file may not have.
+    unsaved: bool,
+}
+
+impl PipelineCacheData {
+    /// Creates a cache with the entries of the initial data, and of the saved cache of the device
+    /// when caches are persisted.
+    pub fn new<I: Impl>(info: &PipelineCacheCreateInfo<I>) -> PipelineCacheData {
+        let header = info.header();
+        let file = env::var_os(DIRECTORY_VARIABLE).map(|directory| Path::new(&directory).join(header.file_name()));
+        let mut entries = BTreeMap::new();
+        if let Some(ref file) = file {
+            load(header, file, &mut entries);
+        }
+        let saved = entries.len();
+        if let Some((_, data)) = CacheHeader::read(info.initial_data()) {
+            parse(data, &mut entries);
+        }
+
+        PipelineCacheData {
+            header: header,
+            state: Mutex::new(State {
+                unsaved: entries.len() > saved,
+                entries: entries,
+            }),
+            file: file,
+        }
+    }
+
+    pub fn header(&self) -> CacheHeader { self.header }
+
+    pub fn get(&self, key: &[u8]) -> Option<Arc<Vec<u8>>> {
+        self.state.lock().unwrap().entries.get(key).cloned()
+    }
+
+    /// Adds an entry, unless there already is one with the same key.
+    pub fn insert(&self, key: Vec<u8>, value: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.contains_key(&key) {
+            state.entries.insert(key, Arc::new(value));
+            state.unsaved = true;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// The size of all of the cache's data.
+    pub fn size(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        HEADER_SIZE + state.entries.iter().map(|(key, value)| entry_size(key, value)).sum::<usize>()
+    }
+
+    /// Writes as much of the cache's data as fits: the header and whole entries, so that what is
+    /// written is valid initial data even when it is not all of it. Returns the number of bytes
+    /// written, which is zero if not even the header fits.
+    pub fn write(&self, data: &mut [u8]) -> usize {
+        if data.len() < HEADER_SIZE {
+            return 0;
+        }
+        let state = self.state.lock().unwrap();
+        let mut written = Vec::new();
+        self.header.write(&mut written);
+        for (key, value) in &state.entries {
+            if written.len() + entry_size(key, value) > data.len() {
+                break;
+            }
+            write_entry(&mut written, key, value);
+        }
+        data[..written.len()].copy_from_slice(&written);
+        written.len()
+    }
+
+    /// Adds the entries of `source` that this cache does not have.
+    pub fn merge(&self, source: &PipelineCacheData) {
+        if source as *const PipelineCacheData == self as *const PipelineCacheData {
+            return;
+        }
+        if source.header != self.header {
+            warn!("not merging pipeline caches made for different devices");
+            return;
+        }
+        let entries = source.state.lock().unwrap().entries.clone();
+        let mut state = self.state.lock().unwrap();
+        for (key, value) in entries {
+            if !state.entries.contains_key(&key) {
+                state.entries.insert(key, value);
+                state.unsaved = true;
+            }
+        }
+    }
+}
+
+impl Drop for PipelineCacheData {
+    fn drop(&mut self) {
+        let file = match self.file {
+            Some(ref file) => file,
+            None => return,
+        };
+        let state = match self.state.get_mut() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        if !state.unsaved {
+            return;
+        }
+
+        // Other caches may have saved entries since this one was created.
+        let mut entries = BTreeMap::new();
+        load(self.header, file, &mut entries);
+        for (key, value) in &state.entries {
+            entries.insert(key.clone(), value.clone());
+        }
+        let mut data = Vec::new();
+        self.header.write(&mut data);
+        for (key, value) in &entries {
+            write_entry(&mut data, key, value);
+        }
+
+        // Writing to another file first keeps other processes from seeing half a cache. Every save
+        // has its own, so caches saved at the same time cannot write into each other's.
+        let temporary = file.with_file_name(format!(
+            "{}.{}.{}.tmp",
+            file.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default(),
+            process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed),
+        ));
+        let saved = file.parent().map(fs::create_dir_all).unwrap_or(Ok(()))
+            .and_then(|_| File::create(&temporary))
+            .and_then(|mut out| out.write_all(&data))
+            .and_then(|_| fs::rename(&temporary, file));
+        if let Err(err) = saved {
+            let _ = fs::remove_file(&temporary);
+            warn!("failed to save pipeline cache to {}: {}", file.display(), err);
+        }
+    }
+}
+
+/// Adds the entries saved in `file` to `entries`, if it has any for the device `header` names.
+fn load(header: CacheHeader, file: &Path, entries: &mut BTreeMap<Vec<u8>, Arc<Vec<u8>>>) {
+    let mut data = Vec::new();
+    if File::open(file).and_then(|mut file| file.read_to_end(&mut data)).is_err() {
+        return;
+    }
+    match CacheHeader::read(&data) {
+        Some((saved, rest)) if saved == header => parse(rest, entries),
+        _ => warn!("ignoring pipeline cache {}, which was not saved for this device", file.display()),
+    }
+}
+
+/// Adds the entries that follow a header to `entries`, keeping any it already has with the same
+/// keys. Stops at the first entry that runs past the end of the data.
+fn parse(mut data: &[u8], entries: &mut BTreeMap<Vec<u8>, Arc<Vec<u8>>>) {
+    while !data.is_empty() {
+        if data.len() < 8 {
+            warn!("pipeline cache data ends in the middle of an entry");
+            return;
+        }
+        let key_size = read_u32(&data[0..4]) as usize;
+        let value_size = read_u32(&data[4..8]) as usize;
+        data = &data[8..];
+        if key_size > data.len() || value_size > data.len() - key_size {
+            warn!("pipeline cache data ends in the middle of an entry");
+            return;
+        }
+        entries.entry(data[..key_size].to_vec()).or_insert_with(|| Arc::new(data[key_size..(key_size + value_size)].to_vec()));
+        data = &data[(key_size + value_size)..];
+    }
+}
+
+fn entry_size(key: &[u8], value: &[u8]) -> usize {
+    8 + key.len() + value.len()
+}
+
+fn write_entry(data: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    write_u32(data, key.len() as u32);
+    write_u32(data, value.len() as u32);
+    data.extend_from_slice(key);
+    data.extend_from_slice(value);
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+fn write_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    const HEADER: CacheHeader = CacheHeader { vendor_id: 1, device_id: 2, uuid: [3; 16] };
+
+    fn cache(file: &Path, key: u8) -> PipelineCacheData {
+        let cache = PipelineCacheData {
+            header: HEADER,
+            state: Mutex::new(State { entries: BTreeMap::new(), unsaved: false }),
+            file: Some(file.to_path_buf()),
+        };
+        cache.insert(vec![key], vec![key; 4]);
+        cache
+    }
+
+    #[test]
+    fn caches_saved_at_once_do_not_share_temporary_files() {
+        let directory = env::temp_dir().join(format!("vk-middle-pipeline-cache-{}", process::id()));
+        let file = directory.join(HEADER.file_name());
+        let threads: Vec<_> = (0..8u8).map(|key| {
+            let file = file.clone();
+            thread::spawn(move || drop(cache(&file, key)))
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Which entries the last save found depends on timing, but it wrote a whole cache.
+        let mut entries = BTreeMap::new();
+        load(HEADER, &file, &mut entries);
+        assert!(!entries.is_empty());
+        for (key, value) in &entries {
+            assert_eq!(**value, vec![key[0]; 4]);
+        }
+        let left: Vec<_> = fs::read_dir(&directory).unwrap().map(|x| x.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        assert_eq!(left, [HEADER.file_name()]);
+
+        // Saving again keeps what is already there.
+        let saved = entries.len();
+        drop(cache(&file, 8));
+        entries.clear();
+        load(HEADER, &file, &mut entries);
+        assert_eq!(entries.len(), saved + 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,547 @@
+//! Sharing of backend pipelines between identical creations, so that backends that pay a lot for
+//! each compile only pay it once per distinct pipeline.
+//!
+//! Each create info is reduced to a canonical key: the state that decides what the pipeline does,
+//! leaving out dynamic state, state that is disabled and fields the pipeline never reads, with
+//! shader modules in it by their code, so that modules with the same code match. A creation whose key matches a live pipeline is
+//! given that pipeline's handle again, as the spec allows for non-dispatchable handles, and the
+//! backend pipeline is only destroyed once the handle has been destroyed as many times as it was
+//! returned.
+//!
+//! Keys name pipeline layouts and render passes by handle, and stop matching once either is
+//! destroyed, since a new object may be given the same handle.
+
+use super::*;
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The pipelines of a device, and what they were created from.
+pub struct PipelineSharing {
+    state: Mutex<State>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+struct State {
+    /// The code of live shader modules, by raw handle.
+    shader_modules: HashMap<u64, Vec<u32>>,
+    /// Live pipelines that may be shared, by key.
+    keys: HashMap<Vec<u8>, u64>,
+    /// Live pipelines, by raw handle.
+    pipelines: HashMap<u64, SharedPipeline>,
+    /// The pipelines whose keys name an object, by the object's raw handle.
+    dependents: HashMap<u64, Vec<u64>>,
+}
+
+struct SharedPipeline {
+    /// `None` once the pipeline may no longer be shared.
+    key: Option<Vec<u8>>,
+    references: Vec<u64>,
+    /// The number of times the handle was returned and not yet destroyed.
+    count: usize,
+}
+
+/// The canonical form of a pipeline create info.
+#[derive(PartialEq, Eq)]
+pub struct PipelineKey {
+    bytes: Vec<u8>,
+    /// The raw handles of the objects the key names.
+    references: Vec<u64>,
+}
+
+impl PipelineKey {
+    /// A hash of the key, for telling pipelines apart in logs.
+    pub fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&self.bytes);
+        hasher.finish()
+    }
+}
+
+impl PipelineSharing {
+    pub fn new() -> PipelineSharing {
+        PipelineSharing {
+            state: Mutex::new(State {
+                shader_modules: HashMap::new(),
+                keys: HashMap::new(),
+                pipelines: HashMap::new(),
+                dependents: HashMap::new(),
+            }),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of pipeline creations that were given a pipeline that already existed.
+    pub fn hits(&self) -> usize { self.hits.load(Ordering::Relaxed) }
+
+    /// The number of pipeline creations the backend was asked for.
+    pub fn misses(&self) -> usize { self.misses.load(Ordering::Relaxed) }
+
+    pub fn add_shader_module(&self, module: u64, code: &[u32]) {
+        self.state.lock().unwrap().shader_modules.insert(module, code.to_vec());
+    }
+
+    /// Stops sharing the pipelines whose keys name an object that is being destroyed.
+    pub fn forget(&self, object: u64) {
+        if object == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.shader_modules.remove(&object);
+        for pipeline in state.dependents.remove(&object).unwrap_or_default() {
+            let key = state.pipelines.get_mut(&pipeline).and_then(|shared| shared.key.take());
+            if let Some(key) = key {
+                state.keys.remove(&key);
+            }
+        }
+    }
+
+    pub fn graphics_key<I: Impl>(&self, info: &GraphicsPipelineCreateInfo<I>) -> Option<PipelineKey> {
+        let dynamic: Vec<DynamicState> = info.dynamic_states().collect();
+        let is_static = |state: DynamicState| !dynamic.contains(&state);
+        let mut key = KeyWriter::new(b'g');
+        key.write_u32((info.flags() & !VK_PIPELINE_CREATE_DERIVATIVE_BIT).bits());
+
+        let mut stages: Vec<_> = info.stages().collect();
+        stages.sort_by_key(|stage| stage.stage().bits());
+        key.write_usize(stages.len());
+        for stage in &stages {
+            if !self.write_stage(&mut key, stage) {
+                return None;
+            }
+        }
+
+        let vertex_input = info.vertex_input_state();
+        let mut bindings: Vec<_> = vertex_input.bindings().collect();
+        bindings.sort_by_key(|binding| binding.binding);
+        key.write_usize(bindings.len());
+        for binding in bindings {
+            key.write_u32(binding.binding);
+            key.write_u32(binding.stride);
+            binding.input_rate.hash(&mut key);
+        }
+        let mut attributes: Vec<_> = vertex_input.attributes().collect();
+        attributes.sort_by_key(|attribute| attribute.location);
+        key.write_usize(attributes.len());
+        for attribute in attributes {
+            key.write_u32(attribute.location);
+            key.write_u32(attribute.binding);
+            attribute.format.hash(&mut key);
+            key.write_u32(attribute.offset);
+        }
+
+        let input_assembly = info.input_assembly_state();
+        input_assembly.topology().hash(&mut key);
+        key.write_bool(input_assembly.primitive_restart_enable());
+
+        if stages.iter().any(|stage| stage.stage().intersects(VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT | VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT)) {
+            key.write_u32(unsafe { info.0.pTessellationState.as_ref() }.map(|x| x.patchControlPoints).unwrap_or(0));
+        }
+
+        let rasterization = info.rasterization_state();
+        key.write_bool(rasterization.depth_clamp_enable());
+        key.write_bool(rasterization.rasterizer_discard_enable());
+        rasterization.polygon_mode().hash(&mut key);
+        key.write_u32(rasterization.cull_mode().bits());
+        rasterization.front_face().hash(&mut key);
+        key.write_bool(rasterization.depth_bias_enable());
+        if rasterization.depth_bias_enable() && is_static(DynamicState::DepthBias) {
+            key.write_f32(rasterization.depth_bias_constant_factor());
+            key.write_f32(rasterization.depth_bias_clamp());
+            key.write_f32(rasterization.depth_bias_slope_factor());
+        }
+        if is_static(DynamicState::LineWidth) {
+            key.write_f32(rasterization.line_width());
+        }
+
+        key.write_bool(info.viewport_state().is_some());
+        if let Some(viewport) = info.viewport_state() {
+            key.write_u32(viewport.viewport_count());
+            if is_static(DynamicState::Viewport) {
+                for x in viewport.viewports() {
+                    for &value in &[x.x, x.y, x.width, x.height, x.min_depth, x.max_depth] {
+                        key.write_f32(value);
+                    }
+                }
+            }
+            if is_static(DynamicState::Scissor) {
+                for x in viewport.scissors() {
+                    key.write_i32(x.offset.x);
+                    key.write_i32(x.offset.y);
+                    key.write_u32(x.extent.width);
+                    key.write_u32(x.extent.height);
+                }
+            }
+        }
+
+        key.write_bool(info.multisample_state().is_some());
+        if let Some(multisample) = info.multisample_state() {
+            key.write_u32(multisample.rasterization_samples().bits());
+            key.write_bool(multisample.min_sample_shading().is_some());
+            if let Some(min_sample_shading) = multisample.min_sample_shading() {
+                key.write_f32(min_sample_shading);
+            }
+            key.write_bool(multisample.sample_mask().is_some());
+            if let Some(sample_mask) = multisample.sample_mask() {
+                for &word in sample_mask {
+                    key.write_u32(word);
+                }
+            }
+            key.write_bool(multisample.alpha_to_coverage_enable());
+            key.write_bool(multisample.alpha_to_one_enable());
+        }
+
+        key.write_bool(info.depth_stencil_state().is_some());
+        if let Some(depth_stencil) = info.depth_stencil_state() {
+            key.write_bool(depth_stencil.depth_compare_op().is_some());
+            // Depth writes only happen when the depth test is enabled.
+            if let Some(compare_op) = depth_stencil.depth_compare_op() {
+                compare_op.hash(&mut key);
+                key.write_bool(depth_stencil.depth_write_enable());
+            }
+            key.write_bool(depth_stencil.depth_bounds().is_some());
+            if let Some((min, max)) = depth_stencil.depth_bounds() {
+                if is_static(DynamicState::DepthBounds) {
+                    key.write_f32(min);
+                    key.write_f32(max);
+                }
+            }
+            key.write_bool(depth_stencil.stencil_test_enable());
+            if depth_stencil.stencil_test_enable() {
+                for face in &[depth_stencil.front(), depth_stencil.back()] {
+                    face.fail_op.hash(&mut key);
+                    face.pass_op.hash(&mut key);
+                    face.depth_fail_op.hash(&mut key);
+                    face.compare_op.hash(&mut key);
+                    if is_static(DynamicState::StencilCompareMask) {
+                        key.write_u32(face.compare_mask);
+                    }
+                    if is_static(DynamicState::StencilWriteMask) {
+                        key.write_u32(face.write_mask);
+                    }
+                    if is_static(DynamicState::StencilReference) {
+                        key.write_u32(face.reference);
+                    }
+                }
+            }
+        }
+
+        key.write_bool(info.color_blend_state().is_some());
+        if let Some(color_blend) = info.color_blend_state() {
+            key.write_bool(color_blend.logic_op().is_some());
+            if let Some(logic_op) = color_blend.logic_op() {
+                logic_op.hash(&mut key);
+            }
+            key.write_usize(color_blend.attachments().len());
+            let mut blending = false;
+            for attachment in color_blend.attachments() {
+                key.write_u32(attachment.color_write_mask.bits());
+                key.write_bool(attachment.blend_enable);
+                if attachment.blend_enable {
+                    blending = true;
+                    attachment.src_color_blend_factor.hash(&mut key);
+                    attachment.dst_color_blend_factor.hash(&mut key);
+                    attachment.color_blend_op.hash(&mut key);
+                    attachment.src_alpha_blend_factor.hash(&mut key);
+                    attachment.dst_alpha_blend_factor.hash(&mut key);
+                    attachment.alpha_blend_op.hash(&mut key);
+                }
+            }
+            if blending && is_static(DynamicState::BlendConstants) {
+                for &constant in &color_blend.blend_constants() {
+                    key.write_f32(constant);
+                }
+            }
+        }
+
+        let layout = handle::NonDispatchableHandle::as_raw(info.0.layout);
+        let render_pass = handle::NonDispatchableHandle::as_raw(info.0.renderPass);
+        key.write_u64(layout);
+        key.write_u64(render_pass);
+        key.write_u32(info.subpass());
+        Some(key.finish_key(vec![layout, render_pass]))
+    }
+
+    pub fn compute_key<I: Impl>(&self, info: &ComputePipelineCreateInfo<I>) -> Option<PipelineKey> {
+        let mut key = KeyWriter::new(b'c');
+        key.write_u32((info.flags() & !VK_PIPELINE_CREATE_DERIVATIVE_BIT).bits());
+        if !self.write_stage(&mut key, &info.stage()) {
+            return None;
+        }
+        let layout = handle::NonDispatchableHandle::as_raw(info.0.layout);
+        key.write_u64(layout);
+        Some(key.finish_key(vec![layout]))
+    }
+
+    /// Writes what decides the code a stage runs. Returns false if its module is unknown.
+    fn write_stage<I: Impl>(&self, key: &mut KeyWriter, stage: &PipelineShaderStageCreateInfo<I>) -> bool {
+        key.write_u32(stage.stage().bits());
+        if !self.write_module(key, handle::NonDispatchableHandle::as_raw(stage.0.module)) {
+            return false;
+        }
+        key.write_bytes(stage.entry_point().to_bytes());
+
+        // Constants are written by ID with their values, however the application lays them out.
+        let (mut entries, data) = match stage.specialization_info() {
+            Some(info) => (info.map_entries().collect(), info.data()),
+            None => (Vec::new(), &[][..]),
+        };
+        entries.sort_by_key(|entry: &SpecializationMapEntry| entry.constant_id);
+        key.write_usize(entries.len());
+        for entry in entries {
+            let start = entry.offset as usize;
+            if start > data.len() || entry.size > data.len() - start {
+                return false;
+            }
+            key.write_u32(entry.constant_id);
+            key.write_bytes(&data[start..(start + entry.size)]);
+        }
+        true
+    }
+
+    /// Writes the code of a shader module. Returns false if the module is unknown.
+    fn write_module(&self, key: &mut KeyWriter, module: u64) -> bool {
+        match self.state.lock().unwrap().shader_modules.get(&module) {
+            Some(code) => {
+                key.write_usize(code.len());
+                for &word in code {
+                    key.write_u32(word);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Gives out the live pipeline with a key once more, if there is one.
+    pub fn share(&self, key: &PipelineKey) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        let pipeline = match state.keys.get(&key.bytes) {
+            Some(&pipeline) => pipeline,
+            None => return None,
+        };
+        if self.retain_locked(&mut state, pipeline) { Some(pipeline) } else { None }
+    }
+
+    /// Gives out a pipeline created earlier in the same batch once more.
+    pub fn retain(&self, pipeline: u64) {
+        self.retain_locked(&mut self.state.lock().unwrap(), pipeline);
+    }
+
+    /// Returns whether the pipeline is live, which only then counts as a hit.
+    fn retain_locked(&self, state: &mut State, pipeline: u64) -> bool {
+        match state.pipelines.get_mut(&pipeline) {
+            Some(shared) => {
+                shared.count += 1;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Adds a pipeline the backend created, to be shared if it has a key.
+    pub fn insert(&self, pipeline: u64, key: Option<PipelineKey>) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+        // Another thread may have created the same pipeline in the meantime.
+        let key = key.and_then(|key| if state.keys.contains_key(&key.bytes) { None } else { Some(key) });
+        let (key, references) = match key {
+            Some(key) => (Some(key.bytes), key.references),
+            None => (None, Vec::new()),
+        };
+        if let Some(ref key) = key {
+            state.keys.insert(key.clone(), pipeline);
+        }
+        for &object in &references {
+            state.dependents.entry(object).or_insert_with(Vec::new).push(pipeline);
+        }
+        state.pipelines.insert(pipeline, SharedPipeline {
+            key: key,
+            references: references,
+            count: 1,
+        });
+    }
+
+    /// Records that a pipeline handle was destroyed. Returns whether that was the last time it
+    /// was given out, so that the backend pipeline is to be destroyed.
+    pub fn release(&self, pipeline: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(shared) = state.pipelines.get_mut(&pipeline) {
+            if shared.count > 1 {
+                shared.count -= 1;
+                return false;
+            }
+        }
+        let shared = match state.pipelines.remove(&pipeline) {
+            Some(shared) => shared,
+            None => return true,
+        };
+        if let Some(key) = shared.key {
+            state.keys.remove(&key);
+        }
+        for object in shared.references {
+            if let Some(dependents) = state.dependents.get_mut(&object) {
+                dependents.retain(|&x| x != pipeline);
+            }
+            if state.dependents.get(&object).map(|x| x.is_empty()).unwrap_or(false) {
+                state.dependents.remove(&object);
+            }
+        }
+        true
+    }
+}
+
+/// The raw create infos of the kinds of pipelines, whose base pipelines are redirected when only
+/// some of a batch is passed on to the backend.
+pub(crate) trait RawPipelineCreateInfo: Copy {
+    /// The index of the base pipeline in the batch, if it is a derivative named by index.
+    fn base_index(&self) -> Option<usize>;
+    fn set_base(&mut self, handle: sys::VkPipeline, index: i32);
+}
+
+macro_rules! raw_pipeline_create_info_impl {
+    ($($name:ident)*) => {
+        $(
+            impl RawPipelineCreateInfo for sys::$name {
+                fn base_index(&self) -> Option<usize> {
+                    let derivative = PipelineCreateFlags::from_bits_truncate(self.flags).contains(VK_PIPELINE_CREATE_DERIVATIVE_BIT);
+                    if derivative && handle::NonDispatchableHandle::is_null(self.basePipelineHandle) && self.basePipelineIndex >= 0 {
+                        Some(self.basePipelineIndex as usize)
+                    } else {
+                        None
+                    }
+                }
+                fn set_base(&mut self, handle: sys::VkPipeline, index: i32) {
+                    self.basePipelineHandle = handle;
+                    self.basePipelineIndex = index;
+                }
+            }
+        )*
+    }
+}
+
+raw_pipeline_create_info_impl! {
+    VkGraphicsPipelineCreateInfo
+    VkComputePipelineCreateInfo
+}
+
+/// Collects the canonical encoding of a create info. The whole encoding is the key, so that
+/// different pipelines can never be taken for one another.
+struct KeyWriter(Vec<u8>);
+
+impl KeyWriter {
+    fn new(kind: u8) -> KeyWriter { KeyWriter(vec![kind]) }
+
+    fn write_bool(&mut self, value: bool) { self.write_u8(value as u8) }
+
+    fn write_f32(&mut self, value: f32) { self.write_u32(value.to_bits()) }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_usize(bytes.len());
+        self.write(bytes);
+    }
+
+    fn finish_key(self, references: Vec<u64>) -> PipelineKey {
+        PipelineKey {
+            bytes: self.0,
+            references: references,
+        }
+    }
+}
+
+impl Hasher for KeyWriter {
+    fn write(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes) }
+
+    fn finish(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&self.0);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(kind: u8, references: &[u64]) -> PipelineKey {
+        let mut key = KeyWriter::new(kind);
+        for &object in references {
+            key.write_u64(object);
+        }
+        key.finish_key(references.to_vec())
+    }
+
+    #[test]
+    fn modules_with_the_same_code_match() {
+        let sharing = PipelineSharing::new();
+        sharing.add_shader_module(1, &[0x07230203, 1, 2, 3]);
+        sharing.add_shader_module(2, &[0x07230203, 1, 2, 3]);
+        sharing.add_shader_module(3, &[0x07230203, 1, 2, 4]);
+        sharing.add_shader_module(4, &[0x07230203, 1, 2]);
+        let written = |module| {
+            let mut key = KeyWriter::new(b'c');
+            if sharing.write_module(&mut key, module) { Some(key.0) } else { None }
+        };
+
+        assert_eq!(written(1), written(2));
+        assert!(written(1) != written(3));
+        assert!(written(1) != written(4));
+        assert_eq!(written(5), None);
+        sharing.forget(2);
+        assert_eq!(written(2), None);
+    }
+
+    #[test]
+    fn pipelines_are_destroyed_when_every_handle_is() {
+        let sharing = PipelineSharing::new();
+        sharing.insert(10, Some(key(b'c', &[])));
+        assert_eq!(sharing.misses(), 1);
+
+        assert_eq!(sharing.share(&key(b'c', &[])), Some(10));
+        assert_eq!(sharing.share(&key(b'g', &[])), None);
+        sharing.retain(10);
+        assert_eq!(sharing.hits(), 2);
+
+        assert!(!sharing.release(10));
+        assert!(!sharing.release(10));
+        assert!(sharing.release(10));
+        assert_eq!(sharing.share(&key(b'c', &[])), None);
+        assert_eq!(sharing.hits(), 2);
+    }
+
+    #[test]
+    fn only_live_pipelines_are_hits() {
+        let sharing = PipelineSharing::new();
+        sharing.retain(10);
+        assert_eq!(sharing.hits(), 0);
+
+        sharing.insert(10, None);
+        sharing.retain(10);
+        assert_eq!(sharing.hits(), 1);
+        assert!(!sharing.release(10));
+        assert!(sharing.release(10));
+        sharing.retain(10);
+        assert_eq!(sharing.hits(), 1);
+    }
+
+    #[test]
+    fn destroying_a_named_object_stops_sharing() {
+        let sharing = PipelineSharing::new();
+        sharing.insert(10, Some(key(b'c', &[5])));
+        sharing.insert(11, Some(key(b'c', &[6])));
+        sharing.forget(5);
+        assert_eq!(sharing.share(&key(b'c', &[5])), None);
+        assert_eq!(sharing.share(&key(b'c', &[6])), Some(11));
+
+        // The pipeline still lives until its handle is destroyed.
+        assert!(sharing.release(10));
+        assert!(!sharing.release(11));
+        assert!(sharing.release(11));
+    }
+}
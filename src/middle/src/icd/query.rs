@@ -0,0 +1,166 @@
+//! Packing of query results into the layout `vkGetQueryPoolResults` and
+//! `vkCmdCopyQueryPoolResults` produce.
+
+use super::*;
+
+use std::ptr;
+
+/// The most values a single query can produce: one per pipeline statistic.
+pub const MAX_QUERY_VALUES: usize = 11;
+
+/// The values of one query. Only the first `value_count` of them are meaningful.
+pub type QueryValues = [u64; MAX_QUERY_VALUES];
+
+/// The number of values each query of a pool produces.
+pub fn value_count(query_type: QueryType, pipeline_statistics: QueryPipelineStatisticFlags) -> usize {
+    match query_type {
+        QueryType::PipelineStatistics => pipeline_statistics.bits().count_ones() as usize,
+        _ => 1,
+    }
+}
+
+/// Writes `results` to `data`, one query every `stride` bytes, following `flags`.
+///
+/// Unavailable queries leave their values untouched unless `VK_QUERY_RESULT_PARTIAL_BIT` is set, in
+/// which case zero is written as the intermediate result. Returns whether every query was
+/// available, or `Error::ValidationFailed` without writing anything if the results do not fit in
+/// `data`.
+pub fn write_query_results(
+    results: &[Option<QueryValues>],
+    value_count: usize,
+    data: &mut [u8],
+    stride: usize,
+    flags: QueryResultFlags,
+) -> Result<bool> {
+    let wide = flags.contains(VK_QUERY_RESULT_64_BIT);
+    let partial = flags.contains(VK_QUERY_RESULT_PARTIAL_BIT);
+    let with_availability = flags.contains(VK_QUERY_RESULT_WITH_AVAILABILITY_BIT);
+
+    let width = if wide { 8 } else { 4 };
+    let query_size = (value_count + with_availability as usize) * width;
+    if let Some(last) = results.len().checked_sub(1) {
+        let end = last.checked_mul(stride).and_then(|x| x.checked_add(query_size));
+        if end.map_or(true, |end| end > data.len()) {
+            return Err(Error::ValidationFailed);
+        }
+    }
+
+    let mut all_available = true;
+
+    for (i, result) in results.iter().enumerate() {
+        let mut write = |index: usize, value: u64| {
+            let offset = i * stride + index * width;
+            let word = &mut data[offset..(offset + width)];
+            if wide {
+                unsafe { ptr::write_unaligned(word.as_mut_ptr() as *mut u64, value) };
+            } else {
+                unsafe { ptr::write_unaligned(word.as_mut_ptr() as *mut u32, value as u32) };
+            }
+        };
+
+        match *result {
+            Some(ref values) => {
+                for (index, &value) in values[..value_count].iter().enumerate() {
+                    write(index, value);
+                }
+            },
+            None => {
+                all_available = false;
+                if partial {
+                    for index in 0..value_count {
+                        write(index, 0);
+                    }
+                }
+            },
+        }
+
+        if with_availability {
+            write(value_count, result.is_some() as u64);
+        }
+    }
+
+    Ok(all_available)
+}
+
+/// What a query pool handle refers to: the backend's pool plus what vk-middle needs to lay out its
+/// results.
+pub(crate) struct QueryPoolObject<I: Impl> {
+    pub imp: I::QueryPool,
+    pub value_count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[u64]) -> Option<QueryValues> {
+        let mut result = [0; MAX_QUERY_VALUES];
+        result[..values.len()].copy_from_slice(values);
+        Some(result)
+    }
+
+    fn words32(data: &[u8]) -> Vec<u32> {
+        data.chunks(4).map(|x| unsafe { ptr::read_unaligned(x.as_ptr() as *const u32) }).collect()
+    }
+
+    fn words64(data: &[u8]) -> Vec<u64> {
+        data.chunks(8).map(|x| unsafe { ptr::read_unaligned(x.as_ptr() as *const u64) }).collect()
+    }
+
+    #[test]
+    fn results_are_truncated_to_32_bits_unless_64_bits_are_asked_for() {
+        let results = [values(&[0x1_0000_0002, 3])];
+
+        let mut data = [0; 8];
+        assert_eq!(write_query_results(&results, 2, &mut data, 8, QueryResultFlags::empty()), Ok(true));
+        assert_eq!(words32(&data), [2, 3]);
+
+        let mut data = [0; 16];
+        assert_eq!(write_query_results(&results, 2, &mut data, 16, VK_QUERY_RESULT_64_BIT), Ok(true));
+        assert_eq!(words64(&data), [0x1_0000_0002, 3]);
+    }
+
+    #[test]
+    fn availability_follows_the_values() {
+        let results = [values(&[5, 6]), None];
+        let mut data = [0xff; 24];
+        let flags = VK_QUERY_RESULT_WITH_AVAILABILITY_BIT;
+        assert_eq!(write_query_results(&results, 2, &mut data, 12, flags), Ok(false));
+        assert_eq!(words32(&data), [5, 6, 1, !0, !0, 0]);
+    }
+
+    #[test]
+    fn unavailable_results_are_left_alone_unless_partial() {
+        let results = [None];
+
+        let mut data = [0xff; 8];
+        assert_eq!(write_query_results(&results, 1, &mut data, 8, VK_QUERY_RESULT_64_BIT), Ok(false));
+        assert_eq!(words64(&data), [!0]);
+
+        let flags = VK_QUERY_RESULT_64_BIT | VK_QUERY_RESULT_PARTIAL_BIT;
+        assert_eq!(write_query_results(&results, 1, &mut data, 8, flags), Ok(false));
+        assert_eq!(words64(&data), [0]);
+    }
+
+    #[test]
+    fn queries_are_a_stride_apart() {
+        let results = [values(&[1]), values(&[2]), values(&[3])];
+        let mut data = [0; 36];
+        assert_eq!(write_query_results(&results, 1, &mut data, 16, QueryResultFlags::empty()), Ok(true));
+        assert_eq!(words32(&data), [1, 0, 0, 0, 2, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn results_past_the_end_are_refused() {
+        let results = [values(&[1]), values(&[2])];
+
+        let mut data = [0; 12];
+        assert_eq!(write_query_results(&results, 1, &mut data, 8, VK_QUERY_RESULT_64_BIT), Err(Error::ValidationFailed));
+        assert_eq!(data, [0; 12]);
+
+        let flags = VK_QUERY_RESULT_WITH_AVAILABILITY_BIT;
+        assert_eq!(write_query_results(&results, 1, &mut data, 8, flags), Err(Error::ValidationFailed));
+        assert_eq!(write_query_results(&results, 1, &mut data, !0, flags), Err(Error::ValidationFailed));
+        assert_eq!(write_query_results(&[], 1, &mut [], 8, flags), Ok(true));
+    }
+}
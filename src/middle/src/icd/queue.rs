@@ -0,0 +1,118 @@
+//! Trampolines for queues, and tracking of in-flight submissions so command buffers leave the
+//! pending state once their work is known to be complete.
+
+use super::*;
+use super::handle::NonDispatchableHandle;
+
+use std::ptr;
+
+/// A batch of command buffers passed to one `vkQueueSubmit` call. Batches are retired when their
+/// fence or a later one is seen signaled, when the queue is waited on, or as soon as the backend
+/// reports the queue idle.
+pub(crate) struct Submission {
+    /// Raw handle of the fence signalled with the batch, or zero if there is none.
+    pub fence: u64,
+    /// The submitted command buffers, and the secondaries they execute.
+    pub command_buffers: Vec<sys::VkCommandBuffer>,
+}
+
+/// Retires every submission made to `queue`.
+unsafe fn retire_all<I: Impl>(queue: sys::VkQueue) {
+    let queue = vulkan_dispatch_cast!(queue => raw Queue);
+    let submissions: Vec<Submission> = queue.submissions.lock().unwrap().drain(..).collect();
+    for submission in submissions {
+        end_submission::<I>(queue.device, &submission.command_buffers);
+    }
+}
+
+/// Retires the submission that signals `fence`, along with every submission made before it to the
+/// same queue.
+pub(crate) unsafe fn retire_fence<I: Impl>(device: sys::VkDevice, fence: sys::VkFence) {
+    for &(_, _, queue) in &vulkan_dispatch_cast!(device => raw Device).queues {
+        let completed: Vec<Submission> = {
+            let mut submissions = vulkan_dispatch_cast!(queue => raw Queue).submissions.lock().unwrap();
+            match submissions.iter().position(|x| x.fence == fence.as_raw()) {
+                Some(i) => submissions.drain(..(i + 1)).collect(),
+                None => continue,
+            }
+        };
+        for submission in completed {
+            end_submission::<I>(device, &submission.command_buffers);
+        }
+        return;
+    }
+}
+
+pub unsafe extern "C" fn vkGetDeviceQueue<I: Impl>(
+    device: sys::VkDevice,
+    queue_family_index: u32,
+    queue_index: u32,
+    queue: *mut sys::VkQueue,
+) {
+    let queues = &vulkan_dispatch_cast!(device => raw Device).queues;
+
+    *queue = match queues.iter().find(|x| x.0 == queue_family_index && x.1 == queue_index) {
+        Some(&(_, _, found)) => found,
+        None => {
+            error!("vkGetDeviceQueue called for queue {} of family {}, which was not requested at device creation", queue_index, queue_family_index);
+            ptr::null_mut()
+        },
+    };
+}
+
+pub unsafe extern "C" fn vkQueueSubmit<I: Impl>(
+    queue: sys::VkQueue,
+    submit_count: u32,
+    submits: *const sys::VkSubmitInfo,
+    fence: sys::VkFence,
+) -> sys::VkResult {
+    let submits = raw_slice(submits, submit_count);
+
+    let mut command_buffers = Vec::new();
+    for submit in submits {
+        command_buffers.extend(raw_slice(submit.pCommandBuffers, submit.commandBufferCount).iter().cloned());
+    }
+    let command_buffers = match begin_submission::<I>(&command_buffers) {
+        Some(command_buffers) => command_buffers,
+        None => return INVALID_USAGE,
+    };
+
+    let raw_queue = queue;
+    let queue = vulkan_dispatch_cast!(queue => raw mut Queue);
+    queue.submissions.lock().unwrap().push(Submission {
+        fence: fence.as_raw(),
+        command_buffers: command_buffers,
+    });
+    if let Err(err) = queue.imp.submit(SubmitInfos(submits, PhantomData), vulkan_handle_cast!(fence => opt mut Fence)) {
+        // Queues are externally synchronized, so the submission is still the last one.
+        if let Some(submission) = queue.submissions.lock().unwrap().pop() {
+            cancel_submission::<I>(&submission.command_buffers);
+        }
+        return err.into();
+    }
+    if queue.imp.is_idle() {
+        retire_all::<I>(raw_queue);
+    }
+
+    OK
+}
+
+pub unsafe extern "C" fn vkQueueWaitIdle<I: Impl>(
+    queue: sys::VkQueue,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_dispatch_cast!(queue => Queue).wait_idle());
+    retire_all::<I>(queue);
+
+    OK
+}
+
+pub unsafe extern "C" fn vkDeviceWaitIdle<I: Impl>(
+    device: sys::VkDevice,
+) -> sys::VkResult {
+    vulkan_try!(vulkan_dispatch_cast!(device => Device).wait_idle());
+    for &(_, _, queue) in &vulkan_dispatch_cast!(device => raw Device).queues {
+        retire_all::<I>(queue);
+    }
+
+    OK
+}
@@ -0,0 +1,1333 @@
+//! An optional command buffer implementation that records `vkCmd*` calls into a typed command
+//! stream instead of encoding them.
+//!
+//! Backends that need render pass or encoder context before they can encode anything (Metal, GL, a
+//! CPU rasterizer) can use `RecordedCommandPool` and `RecordedCommandBuffer` as their
+//! `Impl::CommandPool` and `Impl::CommandBuffer`, and walk `RecordedCommandBuffer::stream` when the
+//! command buffer is submitted.
+//!
+//! Handles in the stream are already resolved to backend objects. Slices of parameters (regions,
+//! barriers, push constant data and so on) live in side tables of the stream and are referred to
+//! by `Slice`s, so the commands themselves stay small and `Copy`. Each command buffer owns its
+//! stream: beginning or resetting the command buffer clears the stream but keeps its allocations,
+//! and the pool hands the streams of freed command buffers to the next ones it allocates.
+//!
+//! Secondary command buffers show up as `Command::ExecuteCommands`, unless the backend asks for them
+//! to be inlined with `Impl::inline_secondary_command_buffers`.
+
+use super::*;
+
+use std::hash::{Hash, Hasher};
+use std::fmt;
+
+/// A resolved reference to a backend object.
+///
+/// The spec makes a command buffer invalid once an object it references is destroyed, so the
+/// referenced object outlives any valid use of the stream. Comparison and hashing use the object's
+/// address, which makes `ObjectRef`s usable as keys when tracking resources.
+#[repr(C)]
+pub struct ObjectRef<T>(*const T);
+
+impl<T> ObjectRef<T> {
+    pub fn new(object: &T) -> ObjectRef<T> { ObjectRef(object) }
+
+    /// The object must not have been destroyed, which holds while the command buffer that recorded
+    /// the reference is valid.
+    pub unsafe fn get<'a>(self) -> &'a T { &*self.0 }
+
+    pub fn as_ptr(self) -> *const T { self.0 }
+}
+
+impl<T> Clone for ObjectRef<T> {
+    fn clone(&self) -> ObjectRef<T> { *self }
+}
+
+impl<T> Copy for ObjectRef<T> {}
+
+impl<T> PartialEq for ObjectRef<T> {
+    fn eq(&self, other: &ObjectRef<T>) -> bool { self.0 == other.0 }
+}
+
+impl<T> Eq for ObjectRef<T> {}
+
+impl<T> Hash for ObjectRef<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) { (self.0 as usize).hash(state) }
+}
+
+impl<T> fmt::Debug for ObjectRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "ObjectRef({:p})", self.0) }
+}
+
+unsafe impl<T: Sync> Send for ObjectRef<T> {}
+unsafe impl<T: Sync> Sync for ObjectRef<T> {}
+
+/// A run of values in one of the side tables of a `CommandStream`.
+pub struct Slice<T> {
+    start: u32,
+    len: u32,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> Slice<T> {
+    pub fn len(&self) -> usize { self.len as usize }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+impl<T> Clone for Slice<T> {
+    fn clone(&self) -> Slice<T> { *self }
+}
+
+impl<T> Copy for Slice<T> {}
+
+impl<T> fmt::Debug for Slice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Slice({}..{})", self.start, self.start + self.len) }
+}
+
+unsafe impl<T> Send for Slice<T> {}
+unsafe impl<T> Sync for Slice<T> {}
+
+/// A memory barrier as recorded by `vkCmdPipelineBarrier` or `vkCmdWaitEvents`.
+#[derive(Clone, Copy, Debug)]
+pub struct RecordedMemoryBarrier {
+    pub src_access_mask: AccessFlags,
+    pub dst_access_mask: AccessFlags,
+}
+
+pub struct RecordedBufferBarrier<I: Impl> {
+    pub src_access_mask: AccessFlags,
+    pub dst_access_mask: AccessFlags,
+    pub src_queue_family_index: u32,
+    pub dst_queue_family_index: u32,
+    pub buffer: ObjectRef<I::Buffer>,
+    pub offset: u64,
+    /// May be `WHOLE_SIZE`, meaning the rest of the buffer after `offset`.
+    pub size: u64,
+}
+
+impl<I: Impl> Clone for RecordedBufferBarrier<I> {
+    fn clone(&self) -> RecordedBufferBarrier<I> { *self }
+}
+
+impl<I: Impl> Copy for RecordedBufferBarrier<I> {}
+
+pub struct RecordedImageBarrier<I: Impl> {
+    pub src_access_mask: AccessFlags,
+    pub dst_access_mask: AccessFlags,
+    pub old_layout: ImageLayout,
+    pub new_layout: ImageLayout,
+    pub src_queue_family_index: u32,
+    pub dst_queue_family_index: u32,
+    pub image: ObjectRef<I::Image>,
+    pub subresource_range: ImageSubresourceRange,
+}
+
+impl<I: Impl> Clone for RecordedImageBarrier<I> {
+    fn clone(&self) -> RecordedImageBarrier<I> { *self }
+}
+
+impl<I: Impl> Copy for RecordedImageBarrier<I> {}
+
+/// The state a secondary command buffer inherits from the primary that executes it, as passed to
+/// `vkBeginCommandBuffer`.
+pub struct RecordedInheritance<I: Impl> {
+    /// Only set for command buffers begun with `VK_COMMAND_BUFFER_USAGE_RENDER_PASS_CONTINUE_BIT`.
+    pub render_pass: Option<ObjectRef<I::RenderPass>>,
+    pub subpass: u32,
+    pub framebuffer: Option<ObjectRef<I::Framebuffer>>,
+    pub occlusion_query_enable: bool,
+    pub query_flags: QueryControlFlags,
+    pub pipeline_statistics: QueryPipelineStatisticFlags,
+}
+
+impl<I: Impl> Clone for RecordedInheritance<I> {
+    fn clone(&self) -> RecordedInheritance<I> { *self }
+}
+
+impl<I: Impl> Copy for RecordedInheritance<I> {}
+
+/// One recorded `vkCmd*` call.
+pub enum Command<I: Impl> {
+    BindPipeline {
+        bind_point: PipelineBindPoint,
+        pipeline: ObjectRef<I::Pipeline>,
+    },
+    BindDescriptorSets {
+        bind_point: PipelineBindPoint,
+        layout: ObjectRef<I::PipelineLayout>,
+        first_set: u32,
+        sets: Slice<ObjectRef<I::DescriptorSet>>,
+        dynamic_offsets: Slice<u32>,
+    },
+    BindIndexBuffer {
+        buffer: ObjectRef<I::Buffer>,
+        offset: u64,
+        index_type: IndexType,
+    },
+    BindVertexBuffers {
+        first_binding: u32,
+        buffers: Slice<ObjectRef<I::Buffer>>,
+        offsets: Slice<u64>,
+    },
+    PushConstants {
+        layout: ObjectRef<I::PipelineLayout>,
+        stages: ShaderStageFlags,
+        offset: u32,
+        data: Slice<u8>,
+    },
+
+    SetViewport {
+        first_viewport: u32,
+        viewports: Slice<Viewport>,
+    },
+    SetScissor {
+        first_scissor: u32,
+        scissors: Slice<Rect2D>,
+    },
+    SetLineWidth(f32),
+    SetDepthBias {
+        constant_factor: f32,
+        clamp: f32,
+        slope_factor: f32,
+    },
+    SetBlendConstants([f32; 4]),
+    SetDepthBounds {
+        min_depth_bounds: f32,
+        max_depth_bounds: f32,
+    },
+    SetStencilCompareMask {
+        faces: StencilFaceFlags,
+        compare_mask: u32,
+    },
+    SetStencilWriteMask {
+        faces: StencilFaceFlags,
+        write_mask: u32,
+    },
+    SetStencilReference {
+        faces: StencilFaceFlags,
+        reference: u32,
+    },
+
+    Draw {
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    },
+    DrawIndexed {
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    },
+    DrawIndirect {
+        buffer: ObjectRef<I::Buffer>,
+        offset: u64,
+        draw_count: u32,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        buffer: ObjectRef<I::Buffer>,
+        offset: u64,
+        draw_count: u32,
+        stride: u32,
+    },
+    Dispatch {
+        group_count_x: u32,
+        group_count_y: u32,
+        group_count_z: u32,
+    },
+    DispatchIndirect {
+        buffer: ObjectRef<I::Buffer>,
+        offset: u64,
+    },
+
+    CopyBuffer {
+        src: ObjectRef<I::Buffer>,
+        dst: ObjectRef<I::Buffer>,
+        regions: Slice<BufferCopy>,
+    },
+    CopyImage {
+        src: ObjectRef<I::Image>,
+        src_layout: ImageLayout,
+        dst: ObjectRef<I::Image>,
+        dst_layout: ImageLayout,
+        regions: Slice<ImageCopy>,
+    },
+    BlitImage {
+        src: ObjectRef<I::Image>,
+        src_layout: ImageLayout,
+        dst: ObjectRef<I::Image>,
+        dst_layout: ImageLayout,
+        regions: Slice<ImageBlit>,
+        filter: Filter,
+    },
+    CopyBufferToImage {
+        src: ObjectRef<I::Buffer>,
+        dst: ObjectRef<I::Image>,
+        dst_layout: ImageLayout,
+        regions: Slice<BufferImageCopy>,
+    },
+    CopyImageToBuffer {
+        src: ObjectRef<I::Image>,
+        src_layout: ImageLayout,
+        dst: ObjectRef<I::Buffer>,
+        regions: Slice<BufferImageCopy>,
+    },
+    UpdateBuffer {
+        dst: ObjectRef<I::Buffer>,
+        offset: u64,
+        data: Slice<u8>,
+    },
+    FillBuffer {
+        dst: ObjectRef<I::Buffer>,
+        offset: u64,
+        size: u64,
+        data: u32,
+    },
+    ClearColorImage {
+        image: ObjectRef<I::Image>,
+        layout: ImageLayout,
+        color: ClearColorValue,
+        ranges: Slice<ImageSubresourceRange>,
+    },
+    ClearDepthStencilImage {
+        image: ObjectRef<I::Image>,
+        layout: ImageLayout,
+        depth_stencil: ClearDepthStencilValue,
+        ranges: Slice<ImageSubresourceRange>,
+    },
+    ClearAttachments {
+        attachments: Slice<ClearAttachment>,
+        rects: Slice<ClearRect>,
+    },
+    ResolveImage {
+        src: ObjectRef<I::Image>,
+        src_layout: ImageLayout,
+        dst: ObjectRef<I::Image>,
+        dst_layout: ImageLayout,
+        regions: Slice<ImageResolve>,
+    },
+
+    SetEvent {
+        event: ObjectRef<I::Event>,
+        stages: PipelineStageFlags,
+    },
+    ResetEvent {
+        event: ObjectRef<I::Event>,
+        stages: PipelineStageFlags,
+    },
+    WaitEvents {
+        events: Slice<ObjectRef<I::Event>>,
+        src_stages: PipelineStageFlags,
+        dst_stages: PipelineStageFlags,
+        memory_barriers: Slice<RecordedMemoryBarrier>,
+        buffer_barriers: Slice<RecordedBufferBarrier<I>>,
+        image_barriers: Slice<RecordedImageBarrier<I>>,
+    },
+    PipelineBarrier {
+        src_stages: PipelineStageFlags,
+        dst_stages: PipelineStageFlags,
+        dependency_flags: DependencyFlags,
+        memory_barriers: Slice<RecordedMemoryBarrier>,
+        buffer_barriers: Slice<RecordedBufferBarrier<I>>,
+        image_barriers: Slice<RecordedImageBarrier<I>>,
+    },
+
+    BeginQuery {
+        pool: ObjectRef<I::QueryPool>,
+        query: u32,
+        flags: QueryControlFlags,
+    },
+    EndQuery {
+        pool: ObjectRef<I::QueryPool>,
+        query: u32,
+    },
+    ResetQueryPool {
+        pool: ObjectRef<I::QueryPool>,
+        first_query: u32,
+        query_count: u32,
+    },
+    WriteTimestamp {
+        stage: PipelineStageFlags,
+        pool: ObjectRef<I::QueryPool>,
+        query: u32,
+    },
+    CopyQueryPoolResults {
+        pool: ObjectRef<I::QueryPool>,
+        first_query: u32,
+        query_count: u32,
+        dst: ObjectRef<I::Buffer>,
+        offset: u64,
+        stride: u64,
+        flags: QueryResultFlags,
+    },
+
+    BeginRenderPass {
+        render_pass: ObjectRef<I::RenderPass>,
+        framebuffer: ObjectRef<I::Framebuffer>,
+        render_area: Rect2D,
+        clear_values: Slice<ClearValue>,
+        contents: SubpassContents,
+    },
+    NextSubpass(SubpassContents),
+    EndRenderPass,
+
+    ExecuteCommands(Slice<ObjectRef<I::CommandBuffer>>),
+}
+
+impl<I: Impl> Clone for Command<I> {
+    fn clone(&self) -> Command<I> { *self }
+}
+
+impl<I: Impl> Copy for Command<I> {}
+
+impl<I: Impl> Command<I> {
+    /// The name of the Vulkan command this was recorded from, for diagnostics.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Command::BindPipeline { .. } => "vkCmdBindPipeline",
+            Command::BindDescriptorSets { .. } => "vkCmdBindDescriptorSets",
+            Command::BindIndexBuffer { .. } => "vkCmdBindIndexBuffer",
+            Command::BindVertexBuffers { .. } => "vkCmdBindVertexBuffers",
+            Command::PushConstants { .. } => "vkCmdPushConstants",
+            Command::SetViewport { .. } => "vkCmdSetViewport",
+            Command::SetScissor { .. } => "vkCmdSetScissor",
+            Command::SetLineWidth(..) => "vkCmdSetLineWidth",
+            Command::SetDepthBias { .. } => "vkCmdSetDepthBias",
+            Command::SetBlendConstants(..) => "vkCmdSetBlendConstants",
+            Command::SetDepthBounds { .. } => "vkCmdSetDepthBounds",
+            Command::SetStencilCompareMask { .. } => "vkCmdSetStencilCompareMask",
+            Command::SetStencilWriteMask { .. } => "vkCmdSetStencilWriteMask",
+            Command::SetStencilReference { .. } => "vkCmdSetStencilReference",
+            Command::Draw { .. } => "vkCmdDraw",
+            Command::DrawIndexed { .. } => "vkCmdDrawIndexed",
+            Command::DrawIndirect { .. } => "vkCmdDrawIndirect",
+            Command::DrawIndexedIndirect { .. } => "vkCmdDrawIndexedIndirect",
+            Command::Dispatch { .. } => "vkCmdDispatch",
+            Command::DispatchIndirect { .. } => "vkCmdDispatchIndirect",
+            Command::CopyBuffer { .. } => "vkCmdCopyBuffer",
+            Command::CopyImage { .. } => "vkCmdCopyImage",
+            Command::BlitImage { .. } => "vkCmdBlitImage",
+            Command::CopyBufferToImage { .. } => "vkCmdCopyBufferToImage",
+            Command::CopyImageToBuffer { .. } => "vkCmdCopyImageToBuffer",
+            Command::UpdateBuffer { .. } => "vkCmdUpdateBuffer",
+            Command::FillBuffer { .. } => "vkCmdFillBuffer",
+            Command::ClearColorImage { .. } => "vkCmdClearColorImage",
+            Command::ClearDepthStencilImage { .. } => "vkCmdClearDepthStencilImage",
+            Command::ClearAttachments { .. } => "vkCmdClearAttachments",
+            Command::ResolveImage { .. } => "vkCmdResolveImage",
+            Command::SetEvent { .. } => "vkCmdSetEvent",
+            Command::ResetEvent { .. } => "vkCmdResetEvent",
+            Command::WaitEvents { .. } => "vkCmdWaitEvents",
+            Command::PipelineBarrier { .. } => "vkCmdPipelineBarrier",
+            Command::BeginQuery { .. } => "vkCmdBeginQuery",
+            Command::EndQuery { .. } => "vkCmdEndQuery",
+            Command::ResetQueryPool { .. } => "vkCmdResetQueryPool",
+            Command::WriteTimestamp { .. } => "vkCmdWriteTimestamp",
+            Command::CopyQueryPoolResults { .. } => "vkCmdCopyQueryPoolResults",
+            Command::BeginRenderPass { .. } => "vkCmdBeginRenderPass",
+            Command::NextSubpass(..) => "vkCmdNextSubpass",
+            Command::EndRenderPass => "vkCmdEndRenderPass",
+            Command::ExecuteCommands(..) => "vkCmdExecuteCommands",
+        }
+    }
+}
+
+/// The commands recorded into a command buffer, together with the side tables their `Slice`s
+/// point into.
+pub struct CommandStream<I: Impl> {
+    commands: Vec<Command<I>>,
+
+    objects: Vec<*const ()>,
+    bytes: Vec<u8>,
+    words: Vec<u32>,
+    sizes: Vec<u64>,
+    viewports: Vec<Viewport>,
+    rects: Vec<Rect2D>,
+    buffer_copies: Vec<BufferCopy>,
+    image_copies: Vec<ImageCopy>,
+    image_blits: Vec<ImageBlit>,
+    buffer_image_copies: Vec<BufferImageCopy>,
+    image_resolves: Vec<ImageResolve>,
+    subresource_ranges: Vec<ImageSubresourceRange>,
+    clear_attachments: Vec<ClearAttachment>,
+    clear_rects: Vec<ClearRect>,
+    clear_values: Vec<ClearValue>,
+    memory_barriers: Vec<RecordedMemoryBarrier>,
+    buffer_barriers: Vec<RecordedBufferBarrier<I>>,
+    image_barriers: Vec<RecordedImageBarrier<I>>,
+}
+
+// The raw pointers in the stream only ever point at backend objects, which are `Sync`.
+unsafe impl<I: Impl> Send for CommandStream<I> {}
+unsafe impl<I: Impl> Sync for CommandStream<I> {}
+
+/// Types that have a side table in `CommandStream`.
+pub trait Stored<I: Impl>: Copy {
+    #[doc(hidden)]
+    fn table(stream: &CommandStream<I>) -> &[Self];
+    #[doc(hidden)]
+    fn push(stream: &mut CommandStream<I>, value: Self);
+}
+
+impl<I: Impl, T> Stored<I> for ObjectRef<T> {
+    fn table(stream: &CommandStream<I>) -> &[ObjectRef<T>] {
+        // ObjectRef<T> is a #[repr(C)] wrapper around a single pointer.
+        unsafe { slice::from_raw_parts(stream.objects.as_ptr() as *const ObjectRef<T>, stream.objects.len()) }
+    }
+    fn push(stream: &mut CommandStream<I>, value: ObjectRef<T>) {
+        stream.objects.push(value.0 as *const ());
+    }
+}
+
+macro_rules! vulkan_stored_impls {
+    ($($field:ident: $ty:ty,)*) => {
+        $(
+            impl<I: Impl> Stored<I> for $ty {
+                fn table(stream: &CommandStream<I>) -> &[$ty] { &stream.$field }
+                fn push(stream: &mut CommandStream<I>, value: $ty) { stream.$field.push(value) }
+            }
+        )*
+
+        impl<I: Impl> CommandStream<I> {
+            pub fn new() -> CommandStream<I> {
+                CommandStream {
+                    commands: Vec::new(),
+                    objects: Vec::new(),
+                    $($field: Vec::new(),)*
+                }
+            }
+
+            /// Removes every command, keeping the allocations around for the next recording.
+            pub fn clear(&mut self) {
+                self.commands.clear();
+                self.objects.clear();
+                $(self.$field.clear();)*
+            }
+
+            pub fn shrink_to_fit(&mut self) {
+                self.commands.shrink_to_fit();
+                self.objects.shrink_to_fit();
+                $(self.$field.shrink_to_fit();)*
+            }
+        }
+    }
+}
+
+vulkan_stored_impls! {
+    bytes: u8,
+    words: u32,
+    sizes: u64,
+    viewports: Viewport,
+    rects: Rect2D,
+    buffer_copies: BufferCopy,
+    image_copies: ImageCopy,
+    image_blits: ImageBlit,
+    buffer_image_copies: BufferImageCopy,
+    image_resolves: ImageResolve,
+    subresource_ranges: ImageSubresourceRange,
+    clear_attachments: ClearAttachment,
+    clear_rects: ClearRect,
+    clear_values: ClearValue,
+    memory_barriers: RecordedMemoryBarrier,
+    buffer_barriers: RecordedBufferBarrier<I>,
+    image_barriers: RecordedImageBarrier<I>,
+}
+
+impl<I: Impl> CommandStream<I> {
+    pub fn commands(&self) -> &[Command<I>] { &self.commands }
+
+    pub fn get<T: Stored<I>>(&self, slice: Slice<T>) -> &[T] {
+        let start = slice.start as usize;
+        &T::table(self)[start..(start + slice.len as usize)]
+    }
+
+    pub fn push(&mut self, command: Command<I>) {
+        self.commands.push(command);
+    }
+
+    /// Copies `values` into the stream's side table for `T`.
+    pub fn store<T, It>(&mut self, values: It) -> Slice<T> where
+        T: Stored<I>,
+        It: IntoIterator<Item = T>,
+    {
+        let start = T::table(self).len() as u32;
+        for value in values {
+            T::push(self, value);
+        }
+        Slice {
+            start: start,
+            len: T::table(self).len() as u32 - start,
+            _marker: PhantomData,
+        }
+    }
+
+    fn store_refs<'a, T: 'a>(&mut self, objects: &[&'a T]) -> Slice<ObjectRef<T>> {
+        self.store(objects.iter().map(|&x| ObjectRef::new(x)))
+    }
+
+    fn store_copies<T: Stored<I>>(&mut self, values: &[T]) -> Slice<T> {
+        self.store(values.iter().cloned())
+    }
+
+    /// Appends the commands of `other`, copying the side table entries they refer to.
+    pub fn append(&mut self, other: &CommandStream<I>) {
+        self.commands.reserve(other.commands.len());
+        for &command in &other.commands {
+            let command = self.copy_command(other, command);
+            self.commands.push(command);
+        }
+    }
+
+    fn copy_slice<T: Stored<I>>(&mut self, from: &CommandStream<I>, slice: Slice<T>) -> Slice<T> {
+        self.store_copies(from.get(slice))
+    }
+
+    /// Rebases the slices of a command from `from` onto copies in this stream.
+    fn copy_command(&mut self, from: &CommandStream<I>, mut command: Command<I>) -> Command<I> {
+        match command {
+            Command::BindDescriptorSets { ref mut sets, ref mut dynamic_offsets, .. } => {
+                *sets = self.copy_slice(from, *sets);
+                *dynamic_offsets = self.copy_slice(from, *dynamic_offsets);
+            },
+            Command::BindVertexBuffers { ref mut buffers, ref mut offsets, .. } => {
+                *buffers = self.copy_slice(from, *buffers);
+                *offsets = self.copy_slice(from, *offsets);
+            },
+            Command::PushConstants { ref mut data, .. } => *data = self.copy_slice(from, *data),
+            Command::SetViewport { ref mut viewports, .. } => *viewports = self.copy_slice(from, *viewports),
+            Command::SetScissor { ref mut scissors, .. } => *scissors = self.copy_slice(from, *scissors),
+            Command::CopyBuffer { ref mut regions, .. } => *regions = self.copy_slice(from, *regions),
+            Command::CopyImage { ref mut regions, .. } => *regions = self.copy_slice(from, *regions),
+            Command::BlitImage { ref mut regions, .. } => *regions = self.copy_slice(from, *regions),
+            Command::CopyBufferToImage { ref mut regions, .. } => *regions = self.copy_slice(from, *regions),
+            Command::CopyImageToBuffer { ref mut regions, .. } => *regions = self.copy_slice(from, *regions),
+            Command::UpdateBuffer { ref mut data, .. } => *data = self.copy_slice(from, *data),
+            Command::ClearColorImage { ref mut ranges, .. } => *ranges = self.copy_slice(from, *ranges),
+            Command::ClearDepthStencilImage { ref mut ranges, .. } => *ranges = self.copy_slice(from, *ranges),
+            Command::ClearAttachments { ref mut attachments, ref mut rects } => {
+                *attachments = self.copy_slice(from, *attachments);
+                *rects = self.copy_slice(from, *rects);
+            },
+            Command::ResolveImage { ref mut regions, .. } => *regions = self.copy_slice(from, *regions),
+            Command::WaitEvents { ref mut events, ref mut memory_barriers, ref mut buffer_barriers, ref mut image_barriers, .. } => {
+                *events = self.copy_slice(from, *events);
+                *memory_barriers = self.copy_slice(from, *memory_barriers);
+                *buffer_barriers = self.copy_slice(from, *buffer_barriers);
+                *image_barriers = self.copy_slice(from, *image_barriers);
+            },
+            Command::PipelineBarrier { ref mut memory_barriers, ref mut buffer_barriers, ref mut image_barriers, .. } => {
+                *memory_barriers = self.copy_slice(from, *memory_barriers);
+                *buffer_barriers = self.copy_slice(from, *buffer_barriers);
+                *image_barriers = self.copy_slice(from, *image_barriers);
+            },
+            Command::BeginRenderPass { ref mut clear_values, .. } => *clear_values = self.copy_slice(from, *clear_values),
+            Command::ExecuteCommands(ref mut command_buffers) => *command_buffers = self.copy_slice(from, *command_buffers),
+            _ => {},
+        }
+        command
+    }
+
+    fn store_barriers(
+        &mut self,
+        memory_barriers: &[MemoryBarrier<I>],
+        buffer_barriers: &[BufferMemoryBarrier<I>],
+        image_barriers: &[ImageMemoryBarrier<I>],
+    ) -> (Slice<RecordedMemoryBarrier>, Slice<RecordedBufferBarrier<I>>, Slice<RecordedImageBarrier<I>>) {
+        let memory_barriers = self.store(memory_barriers.iter().map(|x| RecordedMemoryBarrier {
+            src_access_mask: x.src_access_mask(),
+            dst_access_mask: x.dst_access_mask(),
+        }));
+        let buffer_barriers = self.store(buffer_barriers.iter().map(|x| RecordedBufferBarrier {
+            src_access_mask: x.src_access_mask(),
+            dst_access_mask: x.dst_access_mask(),
+            src_queue_family_index: x.src_queue_family_index(),
+            dst_queue_family_index: x.dst_queue_family_index(),
+            buffer: ObjectRef::new(x.buffer()),
+            offset: x.offset(),
+            size: x.size(),
+        }));
+        let image_barriers = self.store(image_barriers.iter().map(|x| RecordedImageBarrier {
+            src_access_mask: x.src_access_mask(),
+            dst_access_mask: x.dst_access_mask(),
+            old_layout: x.old_layout(),
+            new_layout: x.new_layout(),
+            src_queue_family_index: x.src_queue_family_index(),
+            dst_queue_family_index: x.dst_queue_family_index(),
+            image: ObjectRef::new(x.image()),
+            subresource_range: x.subresource_range(),
+        }));
+        (memory_barriers, buffer_barriers, image_barriers)
+    }
+}
+
+/// A command pool for `RecordedCommandBuffer`s.
+///
+/// The pool does not own the streams of the command buffers it allocates. It only keeps the
+/// cleared streams of freed command buffers and hands them to newly allocated ones, so their
+/// allocations are reused until the pool is trimmed or destroyed.
+pub struct RecordedCommandPool<I: Impl> {
+    flags: CommandPoolCreateFlags,
+    free_streams: Vec<CommandStream<I>>,
+}
+
+impl<I: Impl> RecordedCommandPool<I> {
+    pub fn flags(&self) -> CommandPoolCreateFlags { self.flags }
+}
+
+impl<I: Impl<CommandBuffer = RecordedCommandBuffer<I>>> CommandPool<I> for RecordedCommandPool<I> {
+    fn new(_device: &I::Device, info: CommandPoolCreateInfo<I>) -> Result<Self> {
+        Ok(RecordedCommandPool {
+            flags: info.flags(),
+            free_streams: Vec::new(),
+        })
+    }
+
+    fn allocate(&mut self, _device: &I::Device, level: CommandBufferLevel, count: u32) -> Result<Vec<RecordedCommandBuffer<I>>> {
+        Ok((0..count).map(|_| RecordedCommandBuffer {
+            level: level,
+            usage: CommandBufferUsageFlags::empty(),
+            inheritance: None,
+            stream: self.free_streams.pop().unwrap_or_else(CommandStream::new),
+        }).collect())
+    }
+
+    fn free(&mut self, _device: &I::Device, command_buffers: Vec<RecordedCommandBuffer<I>>) {
+        for command_buffer in command_buffers {
+            let mut stream = command_buffer.stream;
+            stream.clear();
+            self.free_streams.push(stream);
+        }
+    }
+
+    fn trim(&mut self, _device: &I::Device) {
+        self.free_streams.clear();
+        self.free_streams.shrink_to_fit();
+    }
+}
+
+/// A command buffer that records into a `CommandStream` for the backend to execute at submit time.
+pub struct RecordedCommandBuffer<I: Impl> {
+    level: CommandBufferLevel,
+    usage: CommandBufferUsageFlags,
+    inheritance: Option<RecordedInheritance<I>>,
+    stream: CommandStream<I>,
+}
+
+impl<I: Impl> RecordedCommandBuffer<I> {
+    pub fn level(&self) -> CommandBufferLevel { self.level }
+    /// The usage flags passed to the last `vkBeginCommandBuffer`.
+    pub fn usage(&self) -> CommandBufferUsageFlags { self.usage }
+    /// What a secondary command buffer inherits. Always `None` for primaries.
+    pub fn inheritance(&self) -> Option<RecordedInheritance<I>> { self.inheritance }
+    pub fn stream(&self) -> &CommandStream<I> { &self.stream }
+}
+
+impl<I: Impl<CommandBuffer = RecordedCommandBuffer<I>>> CommandBuffer<I> for RecordedCommandBuffer<I> {
+    fn begin(&mut self, info: CommandBufferBeginInfo<I>) -> Result<()> {
+        self.usage = info.flags();
+        // The render pass, subpass and framebuffer are ignored unless the buffer continues a render pass,
+        // so they may be garbage otherwise.
+        let continues = self.usage.contains(VK_COMMAND_BUFFER_USAGE_RENDER_PASS_CONTINUE_BIT);
+        self.inheritance = info.inheritance_info().map(|x| RecordedInheritance {
+            render_pass: if continues { x.render_pass().map(ObjectRef::new) } else { None },
+            subpass: if continues { x.subpass() } else { 0 },
+            framebuffer: if continues { x.framebuffer().map(ObjectRef::new) } else { None },
+            occlusion_query_enable: x.occlusion_query_enable(),
+            query_flags: x.query_flags(),
+            pipeline_statistics: x.pipeline_statistics(),
+        });
+        self.stream.clear();
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> { Ok(()) }
+
+    fn reset(&mut self, flags: CommandBufferResetFlags) -> Result<()> {
+        self.stream.clear();
+        if flags.contains(VK_COMMAND_BUFFER_RESET_RELEASE_RESOURCES_BIT) {
+            self.stream.shrink_to_fit();
+        }
+        Ok(())
+    }
+
+    fn bind_pipeline(&mut self, bind_point: PipelineBindPoint, pipeline: &I::Pipeline) {
+        self.stream.push(Command::BindPipeline {
+            bind_point: bind_point,
+            pipeline: ObjectRef::new(pipeline),
+        });
+    }
+
+    fn bind_descriptor_sets(
+        &mut self,
+        bind_point: PipelineBindPoint,
+        layout: &I::PipelineLayout,
+        first_set: u32,
+        sets: &[&I::DescriptorSet],
+        dynamic_offsets: &[u32],
+    ) {
+        let sets = self.stream.store_refs(sets);
+        let dynamic_offsets = self.stream.store_copies(dynamic_offsets);
+        self.stream.push(Command::BindDescriptorSets {
+            bind_point: bind_point,
+            layout: ObjectRef::new(layout),
+            first_set: first_set,
+            sets: sets,
+            dynamic_offsets: dynamic_offsets,
+        });
+    }
+
+    fn bind_index_buffer(&mut self, buffer: &I::Buffer, offset: u64, index_type: IndexType) {
+        self.stream.push(Command::BindIndexBuffer {
+            buffer: ObjectRef::new(buffer),
+            offset: offset,
+            index_type: index_type,
+        });
+    }
+
+    fn bind_vertex_buffers(&mut self, first_binding: u32, buffers: &[&I::Buffer], offsets: &[u64]) {
+        let buffers = self.stream.store_refs(buffers);
+        let offsets = self.stream.store_copies(offsets);
+        self.stream.push(Command::BindVertexBuffers {
+            first_binding: first_binding,
+            buffers: buffers,
+            offsets: offsets,
+        });
+    }
+
+    fn push_constants(&mut self, layout: &I::PipelineLayout, stages: ShaderStageFlags, offset: u32, data: &[u8]) {
+        let data = self.stream.store_copies(data);
+        self.stream.push(Command::PushConstants {
+            layout: ObjectRef::new(layout),
+            stages: stages,
+            offset: offset,
+            data: data,
+        });
+    }
+
+    fn set_viewport(&mut self, first_viewport: u32, viewports: &[Viewport]) {
+        let viewports = self.stream.store_copies(viewports);
+        self.stream.push(Command::SetViewport { first_viewport: first_viewport, viewports: viewports });
+    }
+
+    fn set_scissor(&mut self, first_scissor: u32, scissors: &[Rect2D]) {
+        let scissors = self.stream.store_copies(scissors);
+        self.stream.push(Command::SetScissor { first_scissor: first_scissor, scissors: scissors });
+    }
+
+    fn set_line_width(&mut self, line_width: f32) {
+        self.stream.push(Command::SetLineWidth(line_width));
+    }
+
+    fn set_depth_bias(&mut self, constant_factor: f32, clamp: f32, slope_factor: f32) {
+        self.stream.push(Command::SetDepthBias {
+            constant_factor: constant_factor,
+            clamp: clamp,
+            slope_factor: slope_factor,
+        });
+    }
+
+    fn set_blend_constants(&mut self, blend_constants: [f32; 4]) {
+        self.stream.push(Command::SetBlendConstants(blend_constants));
+    }
+
+    fn set_depth_bounds(&mut self, min_depth_bounds: f32, max_depth_bounds: f32) {
+        self.stream.push(Command::SetDepthBounds {
+            min_depth_bounds: min_depth_bounds,
+            max_depth_bounds: max_depth_bounds,
+        });
+    }
+
+    fn set_stencil_compare_mask(&mut self, faces: StencilFaceFlags, compare_mask: u32) {
+        self.stream.push(Command::SetStencilCompareMask { faces: faces, compare_mask: compare_mask });
+    }
+
+    fn set_stencil_write_mask(&mut self, faces: StencilFaceFlags, write_mask: u32) {
+        self.stream.push(Command::SetStencilWriteMask { faces: faces, write_mask: write_mask });
+    }
+
+    fn set_stencil_reference(&mut self, faces: StencilFaceFlags, reference: u32) {
+        self.stream.push(Command::SetStencilReference { faces: faces, reference: reference });
+    }
+
+    fn draw(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) {
+        self.stream.push(Command::Draw {
+            vertex_count: vertex_count,
+            instance_count: instance_count,
+            first_vertex: first_vertex,
+            first_instance: first_instance,
+        });
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32) {
+        self.stream.push(Command::DrawIndexed {
+            index_count: index_count,
+            instance_count: instance_count,
+            first_index: first_index,
+            vertex_offset: vertex_offset,
+            first_instance: first_instance,
+        });
+    }
+
+    fn draw_indirect(&mut self, buffer: &I::Buffer, offset: u64, draw_count: u32, stride: u32) {
+        self.stream.push(Command::DrawIndirect {
+            buffer: ObjectRef::new(buffer),
+            offset: offset,
+            draw_count: draw_count,
+            stride: stride,
+        });
+    }
+
+    fn draw_indexed_indirect(&mut self, buffer: &I::Buffer, offset: u64, draw_count: u32, stride: u32) {
+        self.stream.push(Command::DrawIndexedIndirect {
+            buffer: ObjectRef::new(buffer),
+            offset: offset,
+            draw_count: draw_count,
+            stride: stride,
+        });
+    }
+
+    fn dispatch(&mut self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        self.stream.push(Command::Dispatch {
+            group_count_x: group_count_x,
+            group_count_y: group_count_y,
+            group_count_z: group_count_z,
+        });
+    }
+
+    fn dispatch_indirect(&mut self, buffer: &I::Buffer, offset: u64) {
+        self.stream.push(Command::DispatchIndirect { buffer: ObjectRef::new(buffer), offset: offset });
+    }
+
+    fn copy_buffer(&mut self, src: &I::Buffer, dst: &I::Buffer, regions: &[BufferCopy]) {
+        let regions = self.stream.store_copies(regions);
+        self.stream.push(Command::CopyBuffer {
+            src: ObjectRef::new(src),
+            dst: ObjectRef::new(dst),
+            regions: regions,
+        });
+    }
+
+    fn copy_image(&mut self, src: &I::Image, src_layout: ImageLayout, dst: &I::Image, dst_layout: ImageLayout, regions: &[ImageCopy]) {
+        let regions = self.stream.store_copies(regions);
+        self.stream.push(Command::CopyImage {
+            src: ObjectRef::new(src),
+            src_layout: src_layout,
+            dst: ObjectRef::new(dst),
+            dst_layout: dst_layout,
+            regions: regions,
+        });
+    }
+
+    fn blit_image(
+        &mut self,
+        src: &I::Image,
+        src_layout: ImageLayout,
+        dst: &I::Image,
+        dst_layout: ImageLayout,
+        regions: &[ImageBlit],
+        filter: Filter,
+    ) {
+        let regions = self.stream.store_copies(regions);
+        self.stream.push(Command::BlitImage {
+            src: ObjectRef::new(src),
+            src_layout: src_layout,
+            dst: ObjectRef::new(dst),
+            dst_layout: dst_layout,
+            regions: regions,
+            filter: filter,
+        });
+    }
+
+    fn copy_buffer_to_image(&mut self, src: &I::Buffer, dst: &I::Image, dst_layout: ImageLayout, regions: &[BufferImageCopy]) {
+        let regions = self.stream.store_copies(regions);
+        self.stream.push(Command::CopyBufferToImage {
+            src: ObjectRef::new(src),
+            dst: ObjectRef::new(dst),
+            dst_layout: dst_layout,
+            regions: regions,
+        });
+    }
+
+    fn copy_image_to_buffer(&mut self, src: &I::Image, src_layout: ImageLayout, dst: &I::Buffer, regions: &[BufferImageCopy]) {
+        let regions = self.stream.store_copies(regions);
+        self.stream.push(Command::CopyImageToBuffer {
+            src: ObjectRef::new(src),
+            src_layout: src_layout,
+            dst: ObjectRef::new(dst),
+            regions: regions,
+        });
+    }
+
+    fn update_buffer(&mut self, dst: &I::Buffer, offset: u64, data: &[u8]) {
+        let data = self.stream.store_copies(data);
+        self.stream.push(Command::UpdateBuffer { dst: ObjectRef::new(dst), offset: offset, data: data });
+    }
+
+    fn fill_buffer(&mut self, dst: &I::Buffer, offset: u64, size: u64, data: u32) {
+        self.stream.push(Command::FillBuffer {
+            dst: ObjectRef::new(dst),
+            offset: offset,
+            size: size,
+            data: data,
+        });
+    }
+
+    fn clear_color_image(&mut self, image: &I::Image, layout: ImageLayout, color: ClearColorValue, ranges: &[ImageSubresourceRange]) {
+        let ranges = self.stream.store_copies(ranges);
+        self.stream.push(Command::ClearColorImage {
+            image: ObjectRef::new(image),
+            layout: layout,
+            color: color,
+            ranges: ranges,
+        });
+    }
+
+    fn clear_depth_stencil_image(
+        &mut self,
+        image: &I::Image,
+        layout: ImageLayout,
+        depth_stencil: ClearDepthStencilValue,
+        ranges: &[ImageSubresourceRange],
+    ) {
+        let ranges = self.stream.store_copies(ranges);
+        self.stream.push(Command::ClearDepthStencilImage {
+            image: ObjectRef::new(image),
+            layout: layout,
+            depth_stencil: depth_stencil,
+            ranges: ranges,
+        });
+    }
+
+    fn clear_attachments(&mut self, attachments: &[ClearAttachment], rects: &[ClearRect]) {
+        let attachments = self.stream.store_copies(attachments);
+        let rects = self.stream.store_copies(rects);
+        self.stream.push(Command::ClearAttachments { attachments: attachments, rects: rects });
+    }
+
+    fn resolve_image(&mut self, src: &I::Image, src_layout: ImageLayout, dst: &I::Image, dst_layout: ImageLayout, regions: &[ImageResolve]) {
+        let regions = self.stream.store_copies(regions);
+        self.stream.push(Command::ResolveImage {
+            src: ObjectRef::new(src),
+            src_layout: src_layout,
+            dst: ObjectRef::new(dst),
+            dst_layout: dst_layout,
+            regions: regions,
+        });
+    }
+
+    fn set_event(&mut self, event: &I::Event, stages: PipelineStageFlags) {
+        self.stream.push(Command::SetEvent { event: ObjectRef::new(event), stages: stages });
+    }
+
+    fn reset_event(&mut self, event: &I::Event, stages: PipelineStageFlags) {
+        self.stream.push(Command::ResetEvent { event: ObjectRef::new(event), stages: stages });
+    }
+
+    fn wait_events(
+        &mut self,
+        events: &[&I::Event],
+        src_stages: PipelineStageFlags,
+        dst_stages: PipelineStageFlags,
+        memory_barriers: &[MemoryBarrier<I>],
+        buffer_barriers: &[BufferMemoryBarrier<I>],
+        image_barriers: &[ImageMemoryBarrier<I>],
+    ) {
+        let events = self.stream.store_refs(events);
+        let (memory_barriers, buffer_barriers, image_barriers) =
+            self.stream.store_barriers(memory_barriers, buffer_barriers, image_barriers);
+        self.stream.push(Command::WaitEvents {
+            events: events,
+            src_stages: src_stages,
+            dst_stages: dst_stages,
+            memory_barriers: memory_barriers,
+            buffer_barriers: buffer_barriers,
+            image_barriers: image_barriers,
+        });
+    }
+
+    fn pipeline_barrier(
+        &mut self,
+        src_stages: PipelineStageFlags,
+        dst_stages: PipelineStageFlags,
+        dependency_flags: DependencyFlags,
+        memory_barriers: &[MemoryBarrier<I>],
+        buffer_barriers: &[BufferMemoryBarrier<I>],
+        image_barriers: &[ImageMemoryBarrier<I>],
+    ) {
+        let (memory_barriers, buffer_barriers, image_barriers) =
+            self.stream.store_barriers(memory_barriers, buffer_barriers, image_barriers);
+        self.stream.push(Command::PipelineBarrier {
+            src_stages: src_stages,
+            dst_stages: dst_stages,
+            dependency_flags: dependency_flags,
+            memory_barriers: memory_barriers,
+            buffer_barriers: buffer_barriers,
+            image_barriers: image_barriers,
+        });
+    }
+
+    fn begin_query(&mut self, pool: &I::QueryPool, query: u32, flags: QueryControlFlags) {
+        self.stream.push(Command::BeginQuery { pool: ObjectRef::new(pool), query: query, flags: flags });
+    }
+
+    fn end_query(&mut self, pool: &I::QueryPool, query: u32) {
+        self.stream.push(Command::EndQuery { pool: ObjectRef::new(pool), query: query });
+    }
+
+    fn reset_query_pool(&mut self, pool: &I::QueryPool, queries: Range<u32>) {
+        self.stream.push(Command::ResetQueryPool {
+            pool: ObjectRef::new(pool),
+            first_query: queries.start,
+            query_count: queries.end - queries.start,
+        });
+    }
+
+    fn write_timestamp(&mut self, stage: PipelineStageFlags, pool: &I::QueryPool, query: u32) {
+        self.stream.push(Command::WriteTimestamp { stage: stage, pool: ObjectRef::new(pool), query: query });
+    }
+
+    fn copy_query_pool_results(
+        &mut self,
+        pool: &I::QueryPool,
+        queries: Range<u32>,
+        dst: &I::Buffer,
+        offset: u64,
+        stride: u64,
+        flags: QueryResultFlags,
+    ) {
+        self.stream.push(Command::CopyQueryPoolResults {
+            pool: ObjectRef::new(pool),
+            first_query: queries.start,
+            query_count: queries.end - queries.start,
+            dst: ObjectRef::new(dst),
+            offset: offset,
+            stride: stride,
+            flags: flags,
+        });
+    }
+
+    fn begin_render_pass(&mut self, info: RenderPassBeginInfo<I>, contents: SubpassContents) {
+        let clear_values = self.stream.store(info.clear_values());
+        self.stream.push(Command::BeginRenderPass {
+            render_pass: ObjectRef::new(info.render_pass()),
+            framebuffer: ObjectRef::new(info.framebuffer()),
+            render_area: info.render_area(),
+            clear_values: clear_values,
+            contents: contents,
+        });
+    }
+
+    fn next_subpass(&mut self, contents: SubpassContents) {
+        self.stream.push(Command::NextSubpass(contents));
+    }
+
+    fn end_render_pass(&mut self) {
+        self.stream.push(Command::EndRenderPass);
+    }
+
+    fn execute_commands(&mut self, command_buffers: &[&RecordedCommandBuffer<I>]) {
+        // Inlining copies the secondaries as they are now. That is enough: resetting, beginning or
+        // freeing a secondary moves every primary that executes it to the invalid state, and
+        // invalid primaries are never submitted.
+        if I::inline_secondary_command_buffers() {
+            for command_buffer in command_buffers {
+                self.stream.append(&command_buffer.stream);
+            }
+            return;
+        }
+
+        let command_buffers = self.stream.store_refs(command_buffers);
+        self.stream.push(Command::ExecuteCommands(command_buffers));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_impl::*;
+
+    fn allocate(pool: &mut RecordedCommandPool<TestImpl>, level: CommandBufferLevel) -> RecordedCommandBuffer<TestImpl> {
+        pool.allocate(&TestDevice(0), level, 1).unwrap().pop().unwrap()
+    }
+
+    fn pool() -> RecordedCommandPool<TestImpl> {
+        RecordedCommandPool {
+            flags: CommandPoolCreateFlags::empty(),
+            free_streams: Vec::new(),
+        }
+    }
+
+    fn region(src_offset: u64, dst_offset: u64, size: u64) -> BufferCopy {
+        BufferCopy { src_offset: src_offset, dst_offset: dst_offset, size: size }
+    }
+
+    fn regions(stream: &CommandStream<TestImpl>, command: Command<TestImpl>) -> Vec<(u64, u64, u64)> {
+        match command {
+            Command::CopyBuffer { regions, .. } => {
+                stream.get(regions).iter().map(|x| (x.src_offset, x.dst_offset, x.size)).collect()
+            },
+            _ => panic!("{} is not vkCmdCopyBuffer", command.name()),
+        }
+    }
+
+    fn data(stream: &CommandStream<TestImpl>, command: Command<TestImpl>) -> Vec<u8> {
+        match command {
+            Command::UpdateBuffer { data, .. } => stream.get(data).to_vec(),
+            _ => panic!("{} is not vkCmdUpdateBuffer", command.name()),
+        }
+    }
+
+    fn names(stream: &CommandStream<TestImpl>) -> Vec<&'static str> {
+        stream.commands().iter().map(|x| x.name()).collect()
+    }
+
+    #[test]
+    fn commands_are_recorded_with_their_parameters() {
+        let (src, dst) = (TestBuffer(0), TestBuffer(1));
+        let mut pool = pool();
+        let mut command_buffer = allocate(&mut pool, CommandBufferLevel::Primary);
+
+        command_buffer.copy_buffer(&src, &dst, &[region(0, 16, 4), region(4, 20, 4)]);
+        command_buffer.dispatch(1, 2, 3);
+        command_buffer.update_buffer(&dst, 8, &[1, 2, 3, 4]);
+
+        let stream = command_buffer.stream();
+        assert_eq!(names(stream), ["vkCmdCopyBuffer", "vkCmdDispatch", "vkCmdUpdateBuffer"]);
+
+        match stream.commands()[0] {
+            Command::CopyBuffer { src: recorded_src, dst: recorded_dst, regions } => {
+                assert_eq!(recorded_src, ObjectRef::new(&src));
+                assert_eq!(recorded_dst, ObjectRef::new(&dst));
+                assert_eq!(regions.len(), 2);
+            },
+            _ => unreachable!(),
+        }
+        assert_eq!(regions(stream, stream.commands()[0]), [(0, 16, 4), (4, 20, 4)]);
+
+        match stream.commands()[1] {
+            Command::Dispatch { group_count_x: 1, group_count_y: 2, group_count_z: 3 } => {},
+            _ => panic!("dispatch recorded with the wrong group counts"),
+        }
+        assert_eq!(data(stream, stream.commands()[2]), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn slices_of_one_table_do_not_overlap() {
+        let buffers = [TestBuffer(0), TestBuffer(1), TestBuffer(2)];
+        let mut pool = pool();
+        let mut command_buffer = allocate(&mut pool, CommandBufferLevel::Primary);
+
+        command_buffer.bind_vertex_buffers(0, &[&buffers[0], &buffers[1]], &[16, 32]);
+        command_buffer.bind_vertex_buffers(2, &[&buffers[2]], &[48]);
+        command_buffer.bind_vertex_buffers(3, &[], &[]);
+
+        let stream = command_buffer.stream();
+        let bindings: Vec<_> = stream.commands().iter()
+            .map(|&command| match command {
+                Command::BindVertexBuffers { first_binding, buffers, offsets } => {
+                    (first_binding, stream.get(buffers).to_vec(), stream.get(offsets).to_vec())
+                },
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(bindings, [
+            (0, vec![ObjectRef::new(&buffers[0]), ObjectRef::new(&buffers[1])], vec![16, 32]),
+            (2, vec![ObjectRef::new(&buffers[2])], vec![48]),
+            (3, vec![], vec![]),
+        ]);
+    }
+
+    #[test]
+    fn appended_commands_refer_to_copies_of_their_parameters() {
+        let (src, dst) = (TestBuffer(0), TestBuffer(1));
+        let mut pool = pool();
+        let mut secondary = allocate(&mut pool, CommandBufferLevel::Secondary);
+        let mut primary = allocate(&mut pool, CommandBufferLevel::Primary);
+
+        secondary.copy_buffer(&src, &dst, &[region(0, 4, 4)]);
+        secondary.update_buffer(&dst, 0, &[5, 6]);
+        // The primary's side tables already hold entries, so the appended slices must be rebased.
+        primary.update_buffer(&dst, 0, &[1, 2, 3]);
+        primary.copy_buffer(&dst, &src, &[region(8, 8, 8)]);
+
+        primary.stream.append(secondary.stream());
+        secondary.reset(CommandBufferResetFlags::empty()).unwrap();
+
+        let stream = primary.stream();
+        assert_eq!(names(stream), ["vkCmdUpdateBuffer", "vkCmdCopyBuffer", "vkCmdCopyBuffer", "vkCmdUpdateBuffer"]);
+        assert_eq!(data(stream, stream.commands()[0]), [1, 2, 3]);
+        assert_eq!(regions(stream, stream.commands()[1]), [(8, 8, 8)]);
+        assert_eq!(regions(stream, stream.commands()[2]), [(0, 4, 4)]);
+        assert_eq!(data(stream, stream.commands()[3]), [5, 6]);
+    }
+
+    #[test]
+    fn every_slice_of_a_copied_command_is_rebased() {
+        let events = [TestEvent(0), TestEvent(1), TestEvent(2)];
+        let mut from = CommandStream::<TestImpl>::new();
+        let events_slice = from.store(events[..2].iter().map(ObjectRef::new));
+        let memory_barriers = from.store(Some(RecordedMemoryBarrier {
+            src_access_mask: VK_ACCESS_TRANSFER_WRITE_BIT,
+            dst_access_mask: VK_ACCESS_SHADER_READ_BIT,
+        }));
+        let command = Command::WaitEvents {
+            events: events_slice,
+            src_stages: VK_PIPELINE_STAGE_TRANSFER_BIT,
+            dst_stages: VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+            memory_barriers: memory_barriers,
+            buffer_barriers: from.store(None),
+            image_barriers: from.store(None),
+        };
+
+        let mut to = CommandStream::<TestImpl>::new();
+        to.store(Some(ObjectRef::new(&events[2])));
+        to.store(Some(RecordedMemoryBarrier {
+            src_access_mask: AccessFlags::empty(),
+            dst_access_mask: AccessFlags::empty(),
+        }));
+
+        match to.copy_command(&from, command) {
+            Command::WaitEvents { events: copied_events, memory_barriers, buffer_barriers, image_barriers, .. } => {
+                assert_eq!(to.get(copied_events), [ObjectRef::new(&events[0]), ObjectRef::new(&events[1])]);
+                let barriers: Vec<_> = to.get(memory_barriers).iter().map(|x| (x.src_access_mask, x.dst_access_mask)).collect();
+                assert_eq!(barriers, [(VK_ACCESS_TRANSFER_WRITE_BIT, VK_ACCESS_SHADER_READ_BIT)]);
+                assert!(buffer_barriers.is_empty() && image_barriers.is_empty());
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn freed_streams_are_reused_empty() {
+        let buffer = TestBuffer(0);
+        let mut pool = pool();
+        let mut command_buffer = allocate(&mut pool, CommandBufferLevel::Primary);
+        command_buffer.update_buffer(&buffer, 0, &[0; 64]);
+        pool.free(&TestDevice(0), vec![command_buffer]);
+
+        let command_buffer = allocate(&mut pool, CommandBufferLevel::Secondary);
+        assert_eq!(command_buffer.level(), CommandBufferLevel::Secondary);
+        assert!(command_buffer.stream().commands().is_empty());
+        assert!(command_buffer.stream().bytes.is_empty());
+        assert!(command_buffer.stream().bytes.capacity() >= 64);
+    }
+
+    #[test]
+    fn resetting_clears_and_can_release_the_stream() {
+        let buffer = TestBuffer(0);
+        let mut pool = pool();
+        let mut command_buffer = allocate(&mut pool, CommandBufferLevel::Primary);
+
+        command_buffer.update_buffer(&buffer, 0, &[0; 64]);
+        command_buffer.reset(CommandBufferResetFlags::empty()).unwrap();
+        assert!(command_buffer.stream().commands().is_empty());
+        assert!(command_buffer.stream().bytes.capacity() >= 64);
+
+        command_buffer.update_buffer(&buffer, 0, &[0; 64]);
+        command_buffer.reset(VK_COMMAND_BUFFER_RESET_RELEASE_RESOURCES_BIT).unwrap();
+        assert!(command_buffer.stream().commands().is_empty());
+        assert_eq!(command_buffer.stream().bytes.capacity(), 0);
+    }
+}
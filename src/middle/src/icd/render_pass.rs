@@ -0,0 +1,386 @@
+//! Per-attachment analysis of render passes, for backends that (like tiled GPUs) must decide up
+//! front where each attachment is loaded, stored and moved between layouts.
+
+use super::*;
+
+/// Where within a render pass a layout transition takes place.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TransitionPoint {
+    /// Before the given subpass starts.
+    BeginSubpass(u32),
+    /// After the last subpass, into the attachment's final layout.
+    EndRenderPass,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LayoutTransition {
+    pub point: TransitionPoint,
+    pub old_layout: ImageLayout,
+    pub new_layout: ImageLayout,
+}
+
+/// How a single attachment is used over the course of a render pass.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AttachmentUsage {
+    /// The first subpass referencing the attachment, which is where its load op applies.
+    pub first_subpass: u32,
+    /// The last subpass referencing the attachment, after which its store op applies.
+    ///
+    /// Preserve references do not count as uses.
+    pub last_subpass: u32,
+    /// Every layout change the attachment goes through, in order. Transitions into the layout the
+    /// attachment is already in are omitted.
+    pub transitions: Vec<LayoutTransition>,
+    /// What becomes of the color or depth contents, and of the stencil contents, as
+    /// `first_subpass` begins.
+    pub load_ops: (AttachmentLoadOp, AttachmentLoadOp),
+    /// What becomes of the color or depth contents, and of the stencil contents, once
+    /// `last_subpass` ends.
+    pub store_ops: (AttachmentStoreOp, AttachmentStoreOp),
+}
+
+impl<'a, I: Impl> RenderPassCreateInfo<'a, I> {
+    /// Computes the usage of each attachment, indexed like `attachments()`. Attachments no subpass
+    /// references are `None`.
+    pub fn attachment_usage(&self) -> Vec<Option<AttachmentUsage>> {
+        let attachments: Vec<_> = self.attachments().collect();
+        let mut usage: Vec<Option<AttachmentUsage>> = attachments.iter().map(|_| None).collect();
+        let mut layouts: Vec<ImageLayout> = attachments.iter().map(|x| x.initial_layout()).collect();
+
+        for (subpass_index, subpass) in self.subpasses().enumerate() {
+            let subpass_index = subpass_index as u32;
+
+            // An attachment may be referenced more than once by a subpass (e.g. as both an input
+            // and a color attachment), in which case all references share a layout.
+            let mut subpass_layouts: Vec<Option<ImageLayout>> = attachments.iter().map(|_| None).collect();
+            for reference in subpass.references() {
+                match subpass_layouts.get_mut(reference.attachment as usize) {
+                    Some(slot) => if slot.is_none() { *slot = Some(reference.layout) },
+                    None => if reference.attachment != ATTACHMENT_UNUSED {
+                        warn!("subpass {} references nonexistent attachment {}", subpass_index, reference.attachment);
+                    },
+                }
+            }
+
+            for (attachment, layout) in subpass_layouts.into_iter().enumerate() {
+                let layout = match layout {
+                    Some(layout) => layout,
+                    None => continue,
+                };
+
+                if usage[attachment].is_none() {
+                    let description = &attachments[attachment];
+                    usage[attachment] = Some(AttachmentUsage {
+                        first_subpass: subpass_index,
+                        last_subpass: subpass_index,
+                        transitions: Vec::new(),
+                        load_ops: (description.load_op(), description.stencil_load_op()),
+                        store_ops: (description.store_op(), description.stencil_store_op()),
+                    });
+                }
+                let entry = usage[attachment].as_mut().unwrap();
+                entry.last_subpass = subpass_index;
+
+                if layouts[attachment] != layout {
+                    entry.transitions.push(LayoutTransition {
+                        point: TransitionPoint::BeginSubpass(subpass_index),
+                        old_layout: layouts[attachment],
+                        new_layout: layout,
+                    });
+                    layouts[attachment] = layout;
+                }
+            }
+        }
+
+        for (attachment, entry) in usage.iter_mut().enumerate() {
+            let final_layout = attachments[attachment].final_layout();
+            if let Some(ref mut entry) = *entry {
+                if layouts[attachment] != final_layout {
+                    entry.transitions.push(LayoutTransition {
+                        point: TransitionPoint::EndRenderPass,
+                        old_layout: layouts[attachment],
+                        new_layout: final_layout,
+                    });
+                }
+            }
+        }
+
+        usage
+    }
+
+    /// The dependencies of the render pass, followed by those the spec implies.
+    ///
+    /// Without a dependency from `SUBPASS_EXTERNAL` into the first subpass that uses an attachment,
+    /// one exists that makes the attachment accesses of that subpass wait for everything before the
+    /// render pass. Likewise, without one from the last subpass that uses an attachment to
+    /// `SUBPASS_EXTERNAL`, one exists that makes its attachment accesses available afterwards.
+    pub fn dependencies_with_implicit(&self) -> Vec<SubpassDependency> {
+        let mut dependencies: Vec<SubpassDependency> = self.dependencies().collect();
+        let access = VK_ACCESS_INPUT_ATTACHMENT_READ_BIT | VK_ACCESS_COLOR_ATTACHMENT_READ_BIT |
+            VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT | VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_READ_BIT |
+            VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT;
+
+        let mut firsts = Vec::new();
+        let mut lasts = Vec::new();
+        for usage in self.attachment_usage().into_iter().filter_map(|x| x) {
+            if !firsts.contains(&usage.first_subpass) {
+                firsts.push(usage.first_subpass);
+            }
+            if !lasts.contains(&usage.last_subpass) {
+                lasts.push(usage.last_subpass);
+            }
+        }
+        firsts.sort();
+        lasts.sort();
+
+        let mut implicit = Vec::new();
+        for first in firsts {
+            if !dependencies.iter().any(|x| x.src_subpass == SUBPASS_EXTERNAL && x.dst_subpass == first) {
+                implicit.push(SubpassDependency {
+                    src_subpass: SUBPASS_EXTERNAL,
+                    dst_subpass: first,
+                    src_stage_mask: VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT,
+                    dst_stage_mask: VK_PIPELINE_STAGE_ALL_COMMANDS_BIT,
+                    src_access_mask: AccessFlags::empty(),
+                    dst_access_mask: access,
+                    dependency_flags: DependencyFlags::empty(),
+                });
+            }
+        }
+        for last in lasts {
+            if !dependencies.iter().any(|x| x.src_subpass == last && x.dst_subpass == SUBPASS_EXTERNAL) {
+                implicit.push(SubpassDependency {
+                    src_subpass: last,
+                    dst_subpass: SUBPASS_EXTERNAL,
+                    src_stage_mask: VK_PIPELINE_STAGE_ALL_COMMANDS_BIT,
+                    dst_stage_mask: VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT,
+                    src_access_mask: access,
+                    dst_access_mask: AccessFlags::empty(),
+                    dependency_flags: DependencyFlags::empty(),
+                });
+            }
+        }
+
+        dependencies.extend(implicit);
+        dependencies
+    }
+}
+
+impl<'a, I: Impl> SubpassDescription<'a, I> {
+    /// Every attachment reference of the subpass, except preserve references which carry no layout.
+    fn references(&self) -> Vec<AttachmentReference> {
+        let mut references: Vec<AttachmentReference> = self.input_attachments()
+            .chain(self.color_attachments())
+            .chain(self.resolve_attachments())
+            .collect();
+        if let Some(reference) = self.depth_stencil_attachment() {
+            references.push(reference);
+        }
+        references
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_impl::TestImpl;
+
+    use std::ptr;
+
+    use self::ImageLayout::*;
+
+    fn attachment(loads: (AttachmentLoadOp, AttachmentLoadOp), stores: (AttachmentStoreOp, AttachmentStoreOp),
+        initial_layout: ImageLayout, final_layout: ImageLayout) -> sys::VkAttachmentDescription
+    {
+        sys::VkAttachmentDescription {
+            flags: 0,
+            format: sys::VkFormat::VK_FORMAT_D24_UNORM_S8_UINT,
+            samples: sys::VkSampleCountFlagBits::VK_SAMPLE_COUNT_1_BIT,
+            loadOp: loads.0.into(),
+            storeOp: stores.0.into(),
+            stencilLoadOp: loads.1.into(),
+            stencilStoreOp: stores.1.into(),
+            initialLayout: initial_layout.into(),
+            finalLayout: final_layout.into(),
+        }
+    }
+
+    fn color(initial_layout: ImageLayout, final_layout: ImageLayout) -> sys::VkAttachmentDescription {
+        attachment(
+            (AttachmentLoadOp::Load, AttachmentLoadOp::DontCare),
+            (AttachmentStoreOp::Store, AttachmentStoreOp::DontCare),
+            initial_layout,
+            final_layout,
+        )
+    }
+
+    fn reference(attachment: u32, layout: ImageLayout) -> sys::VkAttachmentReference {
+        sys::VkAttachmentReference { attachment: attachment, layout: layout.into() }
+    }
+
+    fn subpass(inputs: &[sys::VkAttachmentReference], colors: &[sys::VkAttachmentReference],
+        depth_stencil: Option<&sys::VkAttachmentReference>, preserves: &[u32]) -> sys::VkSubpassDescription
+    {
+        sys::VkSubpassDescription {
+            flags: 0,
+            pipelineBindPoint: sys::VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_GRAPHICS,
+            inputAttachmentCount: inputs.len() as u32,
+            pInputAttachments: inputs.as_ptr(),
+            colorAttachmentCount: colors.len() as u32,
+            pColorAttachments: colors.as_ptr(),
+            pResolveAttachments: ptr::null(),
+            pDepthStencilAttachment: depth_stencil.map_or(ptr::null(), |x| x),
+            preserveAttachmentCount: preserves.len() as u32,
+            pPreserveAttachments: preserves.as_ptr(),
+        }
+    }
+
+    fn external(src_subpass: u32, dst_subpass: u32) -> sys::VkSubpassDependency {
+        sys::VkSubpassDependency {
+            srcSubpass: src_subpass,
+            dstSubpass: dst_subpass,
+            srcStageMask: VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT.bits(),
+            dstStageMask: VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT.bits(),
+            srcAccessMask: VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT.bits(),
+            dstAccessMask: VK_ACCESS_SHADER_READ_BIT.bits(),
+            dependencyFlags: 0,
+        }
+    }
+
+    fn create_info(attachments: &[sys::VkAttachmentDescription], subpasses: &[sys::VkSubpassDescription],
+        dependencies: &[sys::VkSubpassDependency]) -> sys::VkRenderPassCreateInfo
+    {
+        sys::VkRenderPassCreateInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_RENDER_PASS_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            attachmentCount: attachments.len() as u32,
+            pAttachments: attachments.as_ptr(),
+            subpassCount: subpasses.len() as u32,
+            pSubpasses: subpasses.as_ptr(),
+            dependencyCount: dependencies.len() as u32,
+            pDependencies: dependencies.as_ptr(),
+        }
+    }
+
+    fn usage(info: &sys::VkRenderPassCreateInfo) -> Vec<Option<AttachmentUsage>> {
+        RenderPassCreateInfo::<TestImpl>(info, PhantomData).attachment_usage()
+    }
+
+    fn dependencies(info: &sys::VkRenderPassCreateInfo) -> Vec<(u32, u32, AccessFlags, AccessFlags)> {
+        RenderPassCreateInfo::<TestImpl>(info, PhantomData).dependencies_with_implicit().iter()
+            .map(|x| (x.src_subpass, x.dst_subpass, x.src_access_mask, x.dst_access_mask))
+            .collect()
+    }
+
+    #[test]
+    fn attachments_are_used_from_their_first_to_their_last_reference() {
+        let attachments = [color(Undefined, General), color(Undefined, General), color(Undefined, General)];
+        let colors = [reference(0, General)];
+        let inputs = [reference(0, General)];
+        let both = [reference(1, General)];
+        let subpasses = [
+            subpass(&[], &colors, None, &[]),
+            subpass(&[], &both, None, &[0]),
+            subpass(&inputs, &both, None, &[]),
+        ];
+        let info = create_info(&attachments, &subpasses, &[]);
+
+        let usage = usage(&info);
+        let spans: Vec<_> = usage.iter().map(|x| x.as_ref().map(|x| (x.first_subpass, x.last_subpass))).collect();
+        assert_eq!(spans, [Some((0, 2)), Some((1, 2)), None]);
+    }
+
+    #[test]
+    fn preserving_an_attachment_does_not_use_it() {
+        let attachments = [color(Undefined, General)];
+        let colors = [reference(0, General)];
+        let subpasses = [subpass(&[], &colors, None, &[]), subpass(&[], &[], None, &[0])];
+        let info = create_info(&attachments, &subpasses, &[]);
+
+        let usage = usage(&info);
+        assert_eq!(usage[0].as_ref().map(|x| x.last_subpass), Some(0));
+    }
+
+    #[test]
+    fn load_and_store_ops_are_kept_per_aspect() {
+        let attachments = [attachment(
+            (AttachmentLoadOp::Clear, AttachmentLoadOp::Load),
+            (AttachmentStoreOp::DontCare, AttachmentStoreOp::Store),
+            Undefined,
+            DepthStencilReadOnlyOptimal,
+        )];
+        let depth_stencil = reference(0, DepthStencilAttachmentOptimal);
+        let subpasses = [subpass(&[], &[], Some(&depth_stencil), &[])];
+        let info = create_info(&attachments, &subpasses, &[]);
+
+        let usage = usage(&info);
+        let usage = usage[0].as_ref().unwrap();
+        assert_eq!(usage.load_ops, (AttachmentLoadOp::Clear, AttachmentLoadOp::Load));
+        assert_eq!(usage.store_ops, (AttachmentStoreOp::DontCare, AttachmentStoreOp::Store));
+    }
+
+    #[test]
+    fn layouts_move_from_initial_through_subpasses_to_final() {
+        let attachments = [color(Undefined, ShaderReadOnlyOptimal)];
+        let colors = [reference(0, ColorAttachmentOptimal)];
+        let inputs = [reference(0, ShaderReadOnlyOptimal)];
+        let subpasses = [
+            subpass(&[], &colors, None, &[]),
+            subpass(&[], &colors, None, &[]),
+            subpass(&inputs, &[], None, &[]),
+        ];
+        let info = create_info(&attachments, &subpasses, &[]);
+
+        let usage = usage(&info);
+        assert_eq!(usage[0].as_ref().unwrap().transitions, [
+            LayoutTransition { point: TransitionPoint::BeginSubpass(0), old_layout: Undefined, new_layout: ColorAttachmentOptimal },
+            LayoutTransition { point: TransitionPoint::BeginSubpass(2), old_layout: ColorAttachmentOptimal, new_layout: ShaderReadOnlyOptimal },
+        ]);
+    }
+
+    #[test]
+    fn final_layouts_are_reached_after_the_last_subpass() {
+        let attachments = [color(ColorAttachmentOptimal, PresentSrcKhr)];
+        let colors = [reference(0, ColorAttachmentOptimal)];
+        let subpasses = [subpass(&[], &colors, None, &[])];
+        let info = create_info(&attachments, &subpasses, &[]);
+
+        let usage = usage(&info);
+        assert_eq!(usage[0].as_ref().unwrap().transitions, [
+            LayoutTransition { point: TransitionPoint::EndRenderPass, old_layout: ColorAttachmentOptimal, new_layout: PresentSrcKhr },
+        ]);
+    }
+
+    #[test]
+    fn external_dependencies_are_implied_around_attachment_use() {
+        let attachments = [color(Undefined, General), color(Undefined, General)];
+        let first = [reference(0, General)];
+        let second = [reference(1, General)];
+        let subpasses = [subpass(&[], &first, None, &[]), subpass(&[], &second, None, &[])];
+        let info = create_info(&attachments, &subpasses, &[]);
+
+        let access = VK_ACCESS_INPUT_ATTACHMENT_READ_BIT | VK_ACCESS_COLOR_ATTACHMENT_READ_BIT |
+            VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT | VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_READ_BIT |
+            VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT;
+        assert_eq!(dependencies(&info), [
+            (SUBPASS_EXTERNAL, 0, AccessFlags::empty(), access),
+            (SUBPASS_EXTERNAL, 1, AccessFlags::empty(), access),
+            (0, SUBPASS_EXTERNAL, access, AccessFlags::empty()),
+            (1, SUBPASS_EXTERNAL, access, AccessFlags::empty()),
+        ]);
+    }
+
+    #[test]
+    fn explicit_external_dependencies_replace_implied_ones() {
+        let attachments = [color(Undefined, General)];
+        let colors = [reference(0, General)];
+        let subpasses = [subpass(&[], &colors, None, &[]), subpass(&[], &colors, None, &[])];
+        let explicit = [external(SUBPASS_EXTERNAL, 0), external(1, SUBPASS_EXTERNAL), external(0, 1)];
+        let info = create_info(&attachments, &subpasses, &explicit);
+
+        let dependencies = dependencies(&info);
+        assert_eq!(dependencies.len(), 3);
+        assert!(dependencies.iter().all(|x| x.3 == VK_ACCESS_SHADER_READ_BIT));
+    }
+}
@@ -0,0 +1,147 @@
+//! Validation of sampler parameters that backends would otherwise have to repeat.
+
+use super::*;
+
+impl<'a, I: Impl> SamplerCreateInfo<'a, I> {
+    /// Checks the create info against the features enabled on and the limits of the device, and
+    /// the rules the spec places on LOD, comparison and unnormalized coordinate parameters.
+    /// Problems are logged.
+    pub(crate) fn validate(&self, enabled_features: &PhysicalDeviceFeatures, limits: &PhysicalDeviceLimits) -> Result<()> {
+        if let Some(max_anisotropy) = self.max_anisotropy() {
+            if !enabled_features.sampler_anisotropy {
+                error!("sampler anisotropy enabled, but the samplerAnisotropy feature was not enabled on the device");
+                return Err(Error::ValidationFailed);
+            }
+            if !(max_anisotropy >= 1.0 && max_anisotropy <= limits.max_sampler_anisotropy) {
+                error!("sampler maxAnisotropy is {}, but must be between 1.0 and the device's maxSamplerAnisotropy ({})",
+                    max_anisotropy, limits.max_sampler_anisotropy);
+                return Err(Error::ValidationFailed);
+            }
+        }
+
+        if !(self.min_lod() <= self.max_lod()) {
+            error!("sampler minLod ({}) is greater than maxLod ({})", self.min_lod(), self.max_lod());
+            return Err(Error::ValidationFailed);
+        }
+
+        if let Some(CompareOp::Other(op)) = self.compare_op() {
+            error!("sampler compareOp {} is not a valid VkCompareOp", op);
+            return Err(Error::ValidationFailed);
+        }
+
+        let address_modes = [self.address_mode_u(), self.address_mode_v(), self.address_mode_w()];
+        if address_modes.iter().any(|&mode| mode == SamplerAddressMode::MirrorClampToEdge) {
+            warn!("sampler uses VK_SAMPLER_ADDRESS_MODE_MIRROR_CLAMP_TO_EDGE, which requires VK_KHR_sampler_mirror_clamp_to_edge");
+        }
+
+        if self.unnormalized_coordinates() {
+            let valid = self.min_filter() == self.mag_filter() &&
+                self.mipmap_mode() == SamplerMipmapMode::Nearest &&
+                self.min_lod() == 0.0 && self.max_lod() == 0.0 &&
+                address_modes[..2].iter().all(|&mode| {
+                    mode == SamplerAddressMode::ClampToEdge || mode == SamplerAddressMode::ClampToBorder
+                }) &&
+                self.max_anisotropy().is_none() &&
+                self.compare_op().is_none();
+
+            if !valid {
+                error!("sampler uses unnormalized coordinates with incompatible filtering, LOD, addressing, anisotropy or comparison parameters");
+                return Err(Error::ValidationFailed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_impl::TestImpl;
+
+    use std::ptr;
+
+    use sys::VkFilter::*;
+    use sys::VkSamplerAddressMode::*;
+    use sys::VkSamplerMipmapMode::*;
+
+    fn linear_repeat() -> sys::VkSamplerCreateInfo {
+        sys::VkSamplerCreateInfo {
+            sType: sys::VkStructureType::VK_STRUCTURE_TYPE_SAMPLER_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            magFilter: VK_FILTER_LINEAR,
+            minFilter: VK_FILTER_LINEAR,
+            mipmapMode: VK_SAMPLER_MIPMAP_MODE_LINEAR,
+            addressModeU: VK_SAMPLER_ADDRESS_MODE_REPEAT,
+            addressModeV: VK_SAMPLER_ADDRESS_MODE_REPEAT,
+            addressModeW: VK_SAMPLER_ADDRESS_MODE_REPEAT,
+            mipLodBias: 0.0,
+            anisotropyEnable: sys::VK_FALSE,
+            maxAnisotropy: 0.0,
+            compareEnable: sys::VK_FALSE,
+            compareOp: sys::VkCompareOp::VK_COMPARE_OP_NEVER,
+            minLod: 0.0,
+            maxLod: 1000.0,
+            borderColor: sys::VkBorderColor::VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK,
+            unnormalizedCoordinates: sys::VK_FALSE,
+        }
+    }
+
+    fn unnormalized(info: &mut sys::VkSamplerCreateInfo) {
+        info.unnormalizedCoordinates = sys::VK_TRUE;
+        info.mipmapMode = VK_SAMPLER_MIPMAP_MODE_NEAREST;
+        info.addressModeU = VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE;
+        info.addressModeV = VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER;
+        info.maxLod = 0.0;
+    }
+
+    fn anisotropic(info: &mut sys::VkSamplerCreateInfo, max_anisotropy: f32) {
+        info.anisotropyEnable = sys::VK_TRUE;
+        info.maxAnisotropy = max_anisotropy;
+    }
+
+    #[test]
+    fn create_infos_are_checked_against_the_spec() {
+        let cases: &[(&str, bool, fn(&mut sys::VkSamplerCreateInfo), bool)] = &[
+            ("plain", false, |_| {}, true),
+            ("equal LODs", false, |x| x.minLod = 1000.0, true),
+            ("inverted LODs", false, |x| x.minLod = 1001.0, false),
+            ("NaN LOD", false, |x| x.maxLod = ::std::f32::NAN, false),
+
+            ("anisotropy without the feature", false, |x| anisotropic(x, 4.0), false),
+            ("anisotropy", true, |x| anisotropic(x, 4.0), true),
+            ("anisotropy at the limit", true, |x| anisotropic(x, 16.0), true),
+            ("anisotropy past the limit", true, |x| anisotropic(x, 16.5), false),
+            ("anisotropy below one", true, |x| anisotropic(x, 0.5), false),
+            ("disabled anisotropy out of range", false, |x| x.maxAnisotropy = 100.0, true),
+
+            ("comparison", false, |x| { x.compareEnable = sys::VK_TRUE; x.compareOp = sys::VkCompareOp::VK_COMPARE_OP_LESS }, true),
+            ("clamp to edge mirrored", false, |x| x.addressModeW = VK_SAMPLER_ADDRESS_MODE_MIRROR_CLAMP_TO_EDGE, true),
+
+            ("unnormalized", false, unnormalized, true),
+            ("unnormalized with any W address mode", false, |x| { unnormalized(x); x.addressModeW = VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT }, true),
+            ("unnormalized with nearest filtering", false, |x| { unnormalized(x); x.minFilter = VK_FILTER_NEAREST; x.magFilter = VK_FILTER_NEAREST }, true),
+            ("unnormalized with mixed filters", false, |x| { unnormalized(x); x.minFilter = VK_FILTER_NEAREST }, false),
+            ("unnormalized with linear mipmaps", false, |x| { unnormalized(x); x.mipmapMode = VK_SAMPLER_MIPMAP_MODE_LINEAR }, false),
+            ("unnormalized with an LOD range", false, |x| { unnormalized(x); x.maxLod = 1.0 }, false),
+            ("unnormalized with repeat", false, |x| { unnormalized(x); x.addressModeV = VK_SAMPLER_ADDRESS_MODE_REPEAT }, false),
+            ("unnormalized with anisotropy", true, |x| { unnormalized(x); anisotropic(x, 2.0) }, false),
+            ("unnormalized with comparison", false, |x| { unnormalized(x); x.compareEnable = sys::VK_TRUE }, false),
+        ];
+
+        let mut limits = PhysicalDeviceLimits::default();
+        limits.max_sampler_anisotropy = 16.0;
+
+        for &(name, sampler_anisotropy, modify, valid) in cases {
+            let mut features = PhysicalDeviceFeatures::default();
+            features.sampler_anisotropy = sampler_anisotropy;
+
+            let mut raw = linear_repeat();
+            modify(&mut raw);
+            let info = SamplerCreateInfo::<TestImpl>(&raw, PhantomData);
+            let expected = if valid { Ok(()) } else { Err(Error::ValidationFailed) };
+            assert_eq!(info.validate(&features, &limits), expected, "{}", name);
+        }
+    }
+}
@@ -5,5 +5,7 @@ extern crate vk_middle_sys as sys;
 
 #[macro_use] extern crate log;
 #[macro_use] extern crate bitflags;
+#[macro_use] extern crate lazy_static;
 
 pub mod icd;
+pub mod spirv;
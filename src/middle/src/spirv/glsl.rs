@@ -0,0 +1,300 @@
+//! The `GLSL.std.450` extended instruction set, for the interpreter.
+//!
+//! Vectors are handled component by component, and matrices as lists of columns. The
+//! `InterpolateAt*` instructions take pointers, so the interpreter runs them itself.
+
+use std::f32;
+
+use super::Glsl450;
+use super::interpret::{Value, dot, from_floats, map, zip, zip3};
+
+fn unary(x: &Value, f: &Fn(f32) -> f32) -> Value {
+    map(x, &|x| f(f32::from_bits(x)).to_bits())
+}
+
+fn binary(a: &Value, b: &Value, f: &Fn(f32, f32) -> f32) -> Value {
+    zip(a, b, &|a, b| f(f32::from_bits(a), f32::from_bits(b)).to_bits())
+}
+
+fn ternary(a: &Value, b: &Value, c: &Value, f: &Fn(f32, f32, f32) -> f32) -> Value {
+    zip3(a, b, c, &|a, b, c| f(f32::from_bits(a), f32::from_bits(b), f32::from_bits(c)).to_bits())
+}
+
+fn signed_binary(a: &Value, b: &Value, f: &Fn(i32, i32) -> i32) -> Value {
+    zip(a, b, &|a, b| f(a as i32, b as i32) as u32)
+}
+
+fn round_even(x: f32) -> f32 {
+    let rounded = x.round();
+    if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - x.signum()
+    } else {
+        rounded
+    }
+}
+
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        x
+    }
+}
+
+/// Splits a float into a mantissa with magnitude in [0.5, 1) and a power of two.
+fn frexp(x: f32) -> (f32, i32) {
+    if x == 0.0 || !x.is_finite() {
+        return (x, 0);
+    }
+    let (x, bias) = if x.abs() < f32::MIN_POSITIVE { (x * 4294967296.0, -32) } else { (x, 0) };
+    let bits = x.to_bits();
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 126;
+    let mantissa = f32::from_bits((bits & 0x807FFFFF) | (126 << 23));
+    (mantissa, exponent + bias)
+}
+
+fn columns(matrix: &Value) -> Vec<Vec<f32>> {
+    match *matrix {
+        Value::Composite(ref columns) => columns.iter().map(|x| x.floats()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The matrix without one column and one row.
+fn minor(matrix: &[Vec<f32>], column: usize, row: usize) -> Vec<Vec<f32>> {
+    matrix.iter()
+        .enumerate()
+        .filter(|&(i, _)| i != column)
+        .map(|(_, x)| x.iter().enumerate().filter(|&(j, _)| j != row).map(|(_, &x)| x).collect())
+        .collect()
+}
+
+fn determinant(matrix: &[Vec<f32>]) -> f32 {
+    match matrix.len() {
+        0 => 1.0,
+        1 => matrix[0][0],
+        2 => matrix[0][0] * matrix[1][1] - matrix[1][0] * matrix[0][1],
+        n => (0..n)
+            .map(|i| {
+                let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+                sign * matrix[i][0] * determinant(&minor(matrix, i, 0))
+            })
+            .sum(),
+    }
+}
+
+fn inverse(matrix: &[Vec<f32>]) -> Value {
+    let n = matrix.len();
+    let determinant = determinant(matrix);
+    Value::Composite((0..n)
+        .map(|column| {
+            let inverse: Vec<f32> = (0..n)
+                .map(|row| {
+                    // The adjugate is the transposed matrix of cofactors.
+                    let sign = if (row + column) % 2 == 0 { 1.0 } else { -1.0 };
+                    sign * self::determinant(&minor(matrix, row, column)) / determinant
+                })
+                .collect();
+            from_floats(&inverse)
+        })
+        .collect())
+}
+
+fn pack(components: &[f32], bits: u32, f: &Fn(f32) -> i32) -> u32 {
+    let mask = (1u32 << bits) - 1;
+    components.iter()
+        .enumerate()
+        .fold(0, |packed, (i, &x)| packed | ((f(x) as u32 & mask) << (i as u32 * bits)))
+}
+
+fn unpack(packed: u32, count: u32, f: &Fn(u32) -> f32) -> Value {
+    let bits = 32 / count;
+    let mask = (1u32 << bits) - 1;
+    let components: Vec<f32> = (0..count).map(|i| f((packed >> (i * bits)) & mask)).collect();
+    from_floats(&components)
+}
+
+fn clamp(x: f32, low: f32, high: f32) -> f32 {
+    x.max(low).min(high)
+}
+
+/// Runs an instruction on its operands, or returns `None` if it is one the interpreter has to run
+/// itself or does not support.
+pub(crate) fn evaluate(instruction: Glsl450, args: &[Value]) -> Option<Value> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Scalar(0));
+    let x = arg(0);
+
+    Some(match instruction {
+        Glsl450::Round => unary(&x, &|x| x.round()),
+        Glsl450::RoundEven => unary(&x, &round_even),
+        Glsl450::Trunc => unary(&x, &|x| x.trunc()),
+        Glsl450::FAbs => unary(&x, &|x| x.abs()),
+        Glsl450::SAbs => map(&x, &|x| (x as i32).wrapping_abs() as u32),
+        Glsl450::FSign => unary(&x, &sign),
+        Glsl450::SSign => map(&x, &|x| (x as i32).signum() as u32),
+        Glsl450::Floor => unary(&x, &|x| x.floor()),
+        Glsl450::Ceil => unary(&x, &|x| x.ceil()),
+        Glsl450::Fract => unary(&x, &|x| x - x.floor()),
+        Glsl450::Radians => unary(&x, &|x| x.to_radians()),
+        Glsl450::Degrees => unary(&x, &|x| x.to_degrees()),
+        Glsl450::Sin => unary(&x, &|x| x.sin()),
+        Glsl450::Cos => unary(&x, &|x| x.cos()),
+        Glsl450::Tan => unary(&x, &|x| x.tan()),
+        Glsl450::Asin => unary(&x, &|x| x.asin()),
+        Glsl450::Acos => unary(&x, &|x| x.acos()),
+        Glsl450::Atan => unary(&x, &|x| x.atan()),
+        Glsl450::Sinh => unary(&x, &|x| x.sinh()),
+        Glsl450::Cosh => unary(&x, &|x| x.cosh()),
+        Glsl450::Tanh => unary(&x, &|x| x.tanh()),
+        Glsl450::Asinh => unary(&x, &|x| x.asinh()),
+        Glsl450::Acosh => unary(&x, &|x| x.acosh()),
+        Glsl450::Atanh => unary(&x, &|x| x.atanh()),
+        Glsl450::Atan2 => binary(&x, &arg(1), &|y, x| y.atan2(x)),
+        Glsl450::Pow => binary(&x, &arg(1), &|x, y| x.powf(y)),
+        Glsl450::Exp => unary(&x, &|x| x.exp()),
+        Glsl450::Log => unary(&x, &|x| x.ln()),
+        Glsl450::Exp2 => unary(&x, &|x| x.exp2()),
+        Glsl450::Log2 => unary(&x, &|x| x.log2()),
+        Glsl450::Sqrt => unary(&x, &|x| x.sqrt()),
+        Glsl450::InverseSqrt => unary(&x, &|x| 1.0 / x.sqrt()),
+        Glsl450::Determinant => Value::Scalar(determinant(&columns(&x)).to_bits()),
+        Glsl450::MatrixInverse => inverse(&columns(&x)),
+        Glsl450::ModfStruct => Value::Composite(vec![unary(&x, &|x| x - x.trunc()), unary(&x, &|x| x.trunc())]),
+        Glsl450::FrexpStruct => Value::Composite(vec![
+            unary(&x, &|x| frexp(x).0),
+            map(&x, &|x| frexp(f32::from_bits(x)).1 as u32),
+        ]),
+        Glsl450::Ldexp => zip(&x, &arg(1), &|x, exponent| {
+            let scaled = f32::from_bits(x) as f64 * 2f64.powi(exponent as i32);
+            (scaled as f32).to_bits()
+        }),
+
+        Glsl450::FMin | Glsl450::NMin => binary(&x, &arg(1), &|a, b| a.min(b)),
+        Glsl450::FMax | Glsl450::NMax => binary(&x, &arg(1), &|a, b| a.max(b)),
+        Glsl450::FClamp | Glsl450::NClamp => ternary(&x, &arg(1), &arg(2), &clamp),
+        Glsl450::UMin => zip(&x, &arg(1), &|a, b| ::std::cmp::min(a, b)),
+        Glsl450::UMax => zip(&x, &arg(1), &|a, b| ::std::cmp::max(a, b)),
+        Glsl450::UClamp => zip3(&x, &arg(1), &arg(2), &|x, low, high| ::std::cmp::min(::std::cmp::max(x, low), high)),
+        Glsl450::SMin => signed_binary(&x, &arg(1), &|a, b| ::std::cmp::min(a, b)),
+        Glsl450::SMax => signed_binary(&x, &arg(1), &|a, b| ::std::cmp::max(a, b)),
+        Glsl450::SClamp => zip3(&x, &arg(1), &arg(2), &|x, low, high| {
+            ::std::cmp::min(::std::cmp::max(x as i32, low as i32), high as i32) as u32
+        }),
+        Glsl450::FMix => ternary(&x, &arg(1), &arg(2), &|x, y, a| x * (1.0 - a) + y * a),
+        Glsl450::IMix => zip3(&x, &arg(1), &arg(2), &|x, y, a| if a != 0 { y } else { x }),
+        Glsl450::Step => binary(&x, &arg(1), &|edge, x| if x < edge { 0.0 } else { 1.0 }),
+        Glsl450::SmoothStep => ternary(&x, &arg(1), &arg(2), &|edge0, edge1, x| {
+            let t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        }),
+        Glsl450::Fma => ternary(&x, &arg(1), &arg(2), &|a, b, c| a.mul_add(b, c)),
+
+        Glsl450::PackSnorm4x8 => Value::Scalar(pack(&x.floats(), 8, &|x| (clamp(x, -1.0, 1.0) * 127.0).round() as i32)),
+        Glsl450::PackUnorm4x8 => Value::Scalar(pack(&x.floats(), 8, &|x| (clamp(x, 0.0, 1.0) * 255.0).round() as i32)),
+        Glsl450::PackSnorm2x16 => Value::Scalar(pack(&x.floats(), 16, &|x| (clamp(x, -1.0, 1.0) * 32767.0).round() as i32)),
+        Glsl450::PackUnorm2x16 => Value::Scalar(pack(&x.floats(), 16, &|x| (clamp(x, 0.0, 1.0) * 65535.0).round() as i32)),
+        Glsl450::PackHalf2x16 => Value::Scalar(pack(&x.floats(), 16, &|x| f32_to_f16(x) as i32)),
+        Glsl450::UnpackSnorm4x8 => unpack(x.scalar(), 4, &|x| clamp(x as u8 as i8 as f32 / 127.0, -1.0, 1.0)),
+        Glsl450::UnpackUnorm4x8 => unpack(x.scalar(), 4, &|x| x as f32 / 255.0),
+        Glsl450::UnpackSnorm2x16 => unpack(x.scalar(), 2, &|x| clamp(x as u16 as i16 as f32 / 32767.0, -1.0, 1.0)),
+        Glsl450::UnpackUnorm2x16 => unpack(x.scalar(), 2, &|x| x as f32 / 65535.0),
+        Glsl450::UnpackHalf2x16 => unpack(x.scalar(), 2, &|x| f16_to_f32(x as u16)),
+
+        Glsl450::Length => Value::Scalar(dot(&x.floats(), &x.floats()).sqrt().to_bits()),
+        Glsl450::Distance => {
+            let difference = binary(&x, &arg(1), &|a, b| a - b).floats();
+            Value::Scalar(dot(&difference, &difference).sqrt().to_bits())
+        },
+        Glsl450::Cross => {
+            let (a, b) = (x.floats(), arg(1).floats());
+            if a.len() < 3 || b.len() < 3 {
+                return None;
+            }
+            from_floats(&[a[1] * b[2] - b[1] * a[2], a[2] * b[0] - b[2] * a[0], a[0] * b[1] - b[0] * a[1]])
+        },
+        Glsl450::Normalize => {
+            let length = dot(&x.floats(), &x.floats()).sqrt();
+            unary(&x, &|x| x / length)
+        },
+        Glsl450::FaceForward => {
+            let facing = dot(&arg(2).floats(), &arg(1).floats()) < 0.0;
+            if facing { x } else { unary(&x, &|x| -x) }
+        },
+        Glsl450::Reflect => {
+            let normal = arg(1);
+            let d = dot(&normal.floats(), &x.floats());
+            binary(&x, &normal, &|i, n| i - 2.0 * d * n)
+        },
+        Glsl450::Refract => {
+            let normal = arg(1);
+            let eta = f32::from_bits(arg(2).scalar());
+            let d = dot(&normal.floats(), &x.floats());
+            let k = 1.0 - eta * eta * (1.0 - d * d);
+            if k < 0.0 {
+                map(&x, &|_| 0)
+            } else {
+                binary(&x, &normal, &|i, n| eta * i - (eta * d + k.sqrt()) * n)
+            }
+        },
+
+        Glsl450::FindILsb => map(&x, &|x| if x == 0 { !0 } else { x.trailing_zeros() }),
+        Glsl450::FindSMsb => map(&x, &|x| {
+            let x = if (x as i32) < 0 { !x } else { x };
+            if x == 0 { !0 } else { 31 - x.leading_zeros() }
+        }),
+        Glsl450::FindUMsb => map(&x, &|x| if x == 0 { !0 } else { 31 - x.leading_zeros() }),
+
+        // Doubles are not supported, and interpolation takes a pointer.
+        Glsl450::PackDouble2x32 | Glsl450::UnpackDouble2x32 |
+        Glsl450::InterpolateAtCentroid | Glsl450::InterpolateAtSample | Glsl450::InterpolateAtOffset |
+        Glsl450::Modf | Glsl450::Frexp | Glsl450::Other(_) => return None,
+    })
+}
+
+/// Converts to a half float, rounding to nearest even.
+pub(crate) fn f32_to_f16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7FFFFF;
+
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    // Rounding can carry into the exponent, which is still right, up to infinity.
+    let round = |value: u32, shift: u32| -> u32 {
+        let truncated = value >> shift;
+        let remainder = value & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if remainder > halfway || (remainder == halfway && truncated & 1 != 0) { truncated + 1 } else { truncated }
+    };
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        return sign | round(mantissa | 0x800000, (14 - exponent) as u32) as u16;
+    }
+    sign | round(((exponent as u32) << 23) | mantissa, 13) as u16
+}
+
+pub(crate) fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1F) as u32;
+    let mantissa = (half & 0x3FF) as u32;
+
+    match exponent {
+        0 => {
+            let value = mantissa as f32 / 16777216.0;
+            if sign != 0 { -value } else { value }
+        },
+        0x1F => f32::from_bits(sign | 0x7F800000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    }
+}
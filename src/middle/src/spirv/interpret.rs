@@ -0,0 +1,2630 @@
+//! Executes SPIR-V shaders on the host.
+//!
+//! A `Program` decodes a module once: types, constants, decorations and function bodies are pulled
+//! out into tables, and every instruction is checked to be one this interpreter knows. Any number of
+//! `Invocation`s can then run an entry point of it, each holding its own state so that `run` can
+//! return in the middle of a function and be called again to pick up where it left off. That is how
+//! invocations that have to see each other are run in lockstep: `run_workgroup` steps a compute
+//! workgroup from one `OpControlBarrier` to the next, and `run_quad` steps a 2×2 quad of fragments
+//! from one derivative to the next, including the implicit derivatives of image sampling.
+//!
+//! Only 32-bit integers and floats are supported. Descriptors, push constants and image access go
+//! through `Bindings`, which is the only thing a backend has to implement.
+
+use std::collections::HashMap;
+use std::{f32, ptr};
+
+use icd::{Error, Result};
+use super::*;
+use super::glsl;
+
+/// A type declared by an `OpType*` instruction. Component, element and member types are IDs.
+#[derive(Clone, Debug)]
+pub enum Type {
+    Void,
+    Bool,
+    Int { signed: bool },
+    Float,
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Image(ImageType),
+    Sampler,
+    SampledImage { image: u32 },
+    Pointer { storage: StorageClass, pointee: u32 },
+    Function,
+}
+
+/// The operands of `OpTypeImage`.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageType {
+    pub sampled_type: u32,
+    pub dim: Dim,
+    /// 0 if not a depth image, 1 if one, and 2 if not known.
+    pub depth: u32,
+    pub arrayed: bool,
+    pub multisampled: bool,
+    /// 1 if used with a sampler, 2 if used for storage, and 0 if not known.
+    pub sampled: u32,
+    pub format: ImageFormat,
+}
+
+impl ImageType {
+    /// The number of components in a texel coordinate for this type, including the array layer.
+    pub fn coordinate_count(&self) -> usize {
+        let count = match self.dim {
+            Dim::D1 | Dim::Buffer => 1,
+            Dim::D3 | Dim::Cube => 3,
+            _ => 2,
+        };
+        if self.arrayed { count + 1 } else { count }
+    }
+}
+
+/// Identifies one descriptor: an element of a binding in a descriptor set.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DescriptorRef {
+    pub set: u32,
+    pub binding: u32,
+    pub element: u32,
+}
+
+/// How a matrix in memory is laid out, from the decorations of the member holding it.
+#[derive(Clone, Copy, Debug)]
+pub struct MatrixLayout {
+    pub stride: u32,
+    pub row_major: bool,
+}
+
+/// A pointer into buffer memory: push constants, or a uniform or storage buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryPointer {
+    base: *mut u8,
+    size: u64,
+    offset: u64,
+    /// The type pointed to.
+    ty: u32,
+    /// The layout of the matrix pointed to, or of matrices in the array pointed to.
+    matrix: Option<MatrixLayout>,
+    /// The distance between the components of the vector pointed to, which is the matrix stride
+    /// for the columns of row-major matrices.
+    component_stride: u32,
+}
+
+/// A pointer, as the result of `OpVariable`, `OpAccessChain` and friends.
+#[derive(Clone, Debug)]
+pub enum Pointer {
+    /// Into the invocation's own memory: `Private`, `Function`, `Input` and `Output` variables.
+    Local { slot: usize, path: Vec<u32> },
+    /// Into the workgroup's memory.
+    Shared { slot: usize, path: Vec<u32> },
+    Memory(MemoryPointer),
+    /// To an image or sampler variable, or an array of them.
+    Descriptor { descriptor: DescriptorRef, ty: u32 },
+    /// To a texel of a storage image, for atomics.
+    Texel { image: DescriptorRef, coordinates: [i32; 4] },
+}
+
+/// A value, as held by an ID or in memory. Booleans are 0 or 1, and vectors, matrices, arrays and
+/// structures are all composites.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Scalar(u32),
+    Composite(Vec<Value>),
+    Pointer(Pointer),
+    Image(DescriptorRef),
+    Sampler(DescriptorRef),
+    SampledImage(DescriptorRef, DescriptorRef),
+}
+
+impl Value {
+    pub fn scalar(&self) -> u32 {
+        match *self {
+            Value::Scalar(x) => x,
+            Value::Composite(ref values) => values.get(0).map(|x| x.scalar()).unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// The scalars of a scalar or vector.
+    pub fn components(&self) -> Vec<u32> {
+        match *self {
+            Value::Composite(ref values) => values.iter().map(|x| x.scalar()).collect(),
+            ref value => vec![value.scalar()],
+        }
+    }
+
+    pub fn floats(&self) -> Vec<f32> {
+        self.components().into_iter().map(f32::from_bits).collect()
+    }
+
+    fn from_components(components: &[u32], count: usize) -> Value {
+        if count == 0 {
+            Value::Scalar(components.get(0).cloned().unwrap_or(0))
+        } else {
+            Value::Composite((0..count).map(|i| Value::Scalar(components.get(i).cloned().unwrap_or(0))).collect())
+        }
+    }
+}
+
+/// What a backend provides to the invocations it runs.
+pub trait Bindings {
+    /// The memory of a uniform or storage buffer descriptor and its size.
+    fn buffer(&self, descriptor: DescriptorRef) -> Option<(*mut u8, u64)>;
+
+    fn push_constants(&self) -> &[u8];
+
+    /// Samples an image through a sampler. `coordinates` are as the instruction gave them, after
+    /// projection, with unused components zero: `(u, v, w)` up to the image's dimensionality with
+    /// the array layer after them, or `(x, y, z, layer)` for cubes. The sampler's own LOD bias and
+    /// clamps are still to be applied to `lod`.
+    fn sample(
+        &self,
+        image: DescriptorRef,
+        sampler: DescriptorRef,
+        coordinates: [f32; 4],
+        lod: f32,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> [u32; 4];
+
+    /// Gathers `component` of the four texels bilinear filtering at level 0 would use, in the
+    /// order `OpImageGather` returns them.
+    fn gather(
+        &self,
+        image: DescriptorRef,
+        sampler: DescriptorRef,
+        coordinates: [f32; 4],
+        component: u32,
+        reference: Option<f32>,
+        offset: [i32; 3],
+    ) -> [u32; 4];
+
+    /// Reads a texel of an image or texel buffer. `coordinates` are `(x, y, z, layer)`, with the
+    /// face as the layer for cubes.
+    fn read(&self, image: DescriptorRef, coordinates: [i32; 4], lod: u32) -> [u32; 4];
+
+    fn write(&self, image: DescriptorRef, coordinates: [i32; 4], texel: [u32; 4]);
+
+    /// The texel of a 32-bit storage image or texel buffer, for atomics.
+    fn texel_pointer(&self, image: DescriptorRef, coordinates: [i32; 4]) -> Option<*mut u32>;
+
+    /// The width, height, depth and number of layers of a level of an image.
+    fn size(&self, image: DescriptorRef, lod: u32) -> [u32; 4];
+
+    fn levels(&self, image: DescriptorRef) -> u32;
+}
+
+/// Where an entry point's input or output goes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Slot {
+    BuiltIn(BuiltIn),
+    Location(u32),
+}
+
+/// One input or output of an entry point: a variable, or a part of one taking up one location.
+#[derive(Clone, Debug)]
+pub struct Interface {
+    pub slot: Slot,
+    /// The number of scalars it holds.
+    pub components: u32,
+    pub flat: bool,
+    pub no_perspective: bool,
+    memory: usize,
+    path: Vec<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntryPoint {
+    pub name: String,
+    pub model: ExecutionModel,
+    pub local_size: [u32; 3],
+    pub origin_upper_left: bool,
+    pub early_fragment_tests: bool,
+    pub depth_replacing: bool,
+    function: u32,
+    interface: Vec<u32>,
+    inputs: Vec<Interface>,
+    outputs: Vec<Interface>,
+}
+
+impl EntryPoint {
+    pub fn inputs(&self) -> &[Interface] { &self.inputs }
+    pub fn outputs(&self) -> &[Interface] { &self.outputs }
+}
+
+struct Global {
+    id: u32,
+    storage: StorageClass,
+    pointee: u32,
+    initializer: Option<u32>,
+    /// Where it lives in the invocation's or workgroup's memory, for storage classes that live there.
+    slot: usize,
+}
+
+#[derive(Debug)]
+struct Inst {
+    op: Op,
+    operands: Vec<u32>,
+}
+
+struct Block {
+    label: u32,
+    instructions: Vec<Inst>,
+}
+
+struct Function {
+    parameters: Vec<u32>,
+    blocks: Vec<Block>,
+    labels: HashMap<u32, usize>,
+}
+
+/// A decoded module, ready to run.
+pub struct Program {
+    types: Vec<Option<Type>>,
+    result_types: Vec<u32>,
+    constants: Vec<Option<Value>>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    globals: Vec<Global>,
+    functions: HashMap<u32, Function>,
+    entry_points: Vec<EntryPoint>,
+    glsl: Option<u32>,
+    private_count: usize,
+    workgroup_count: usize,
+    void: Type,
+    undecorated: Decorations,
+}
+
+// Constants never hold pointers, which are all that keeps this from being sent between threads.
+unsafe impl Send for Program {}
+unsafe impl Sync for Program {}
+
+const SUPPORTED_CAPABILITIES: &'static [Capability] = &[
+    Capability::Matrix,
+    Capability::Shader,
+    Capability::InputAttachment,
+    Capability::Sampled1D,
+    Capability::Image1D,
+    Capability::SampledBuffer,
+    Capability::ImageBuffer,
+    Capability::ImageQuery,
+    Capability::DerivativeControl,
+    Capability::SampledCubeArray,
+    Capability::ImageCubeArray,
+    Capability::ClipDistance,
+    Capability::CullDistance,
+    Capability::ImageGatherExtended,
+    Capability::StorageImageExtendedFormats,
+    Capability::StorageImageReadWithoutFormat,
+    Capability::StorageImageWriteWithoutFormat,
+    Capability::MinLod,
+    Capability::SampledRect,
+    Capability::ImageRect,
+    Capability::UniformBufferArrayDynamicIndexing,
+    Capability::SampledImageArrayDynamicIndexing,
+    Capability::StorageBufferArrayDynamicIndexing,
+    Capability::StorageImageArrayDynamicIndexing,
+    Capability::InterpolationFunction,
+];
+
+/// Whether the interpreter can run an instruction.
+fn supported(op: Op) -> bool {
+    match op {
+        Op::Other(_) |
+        Op::PtrAccessChain |
+        Op::InBoundsPtrAccessChain |
+        Op::ImageQueryFormat |
+        Op::ImageQueryOrder |
+        Op::EmitVertex |
+        Op::EndPrimitive |
+        Op::EmitStreamVertex |
+        Op::EndStreamPrimitive => false,
+        _ => true,
+    }
+}
+
+impl Program {
+    pub fn new(code: &[u32]) -> Result<Program> {
+        let instructions = try!(instructions(code));
+        let bound = id_bound(code) as usize;
+
+        let mut program = Program {
+            types: vec![None; bound],
+            result_types: vec![0; bound],
+            constants: vec![None; bound],
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            globals: Vec::new(),
+            functions: HashMap::new(),
+            entry_points: Vec::new(),
+            glsl: None,
+            private_count: 0,
+            workgroup_count: 0,
+            void: Type::Void,
+            undecorated: Decorations::default(),
+        };
+
+        let (decorations, member_decorations) = decorations(&instructions);
+        program.decorations = decorations;
+        program.member_decorations = member_decorations;
+
+        let mut execution_modes = Vec::new();
+        let mut function: Option<(u32, Function)> = None;
+        for instruction in &instructions {
+            let op = instruction.op;
+            let o = instruction.operands;
+            if !supported(op) {
+                error!("SPIR-V instruction {:?} is not supported", op);
+                return Err(Error::FeatureNotPresent);
+            }
+            if let Some(index) = result_index(op) {
+                let id = o.get(index).cloned().unwrap_or(u32::max_value());
+                if id as usize >= bound {
+                    error!("SPIR-V instruction {:?} has a result ID out of the module's bound of {}", op, bound);
+                    return Err(Error::InvalidShader);
+                }
+                if index == 1 {
+                    program.result_types[id as usize] = o[0];
+                }
+            }
+
+            if let Some((_, ref mut function)) = function {
+                match op {
+                    Op::FunctionEnd => {},
+                    Op::FunctionParameter => function.parameters.push(o[1]),
+                    Op::Label => {
+                        function.labels.insert(o[0], function.blocks.len());
+                        function.blocks.push(Block { label: o[0], instructions: Vec::new() });
+                    },
+                    Op::Line | Op::NoLine | Op::Nop => {},
+                    _ => match function.blocks.last_mut() {
+                        Some(block) => block.instructions.push(Inst { op: op, operands: o.to_vec() }),
+                        None => {
+                            error!("SPIR-V instruction {:?} is outside of any block", op);
+                            return Err(Error::InvalidShader);
+                        },
+                    },
+                }
+            }
+            if op == Op::FunctionEnd {
+                if let Some((id, function)) = function.take() {
+                    program.functions.insert(id, function);
+                }
+                continue;
+            }
+            if function.is_some() {
+                continue;
+            }
+
+            match op {
+                Op::Capability => {
+                    let capability = Capability::from(o[0]);
+                    if !SUPPORTED_CAPABILITIES.contains(&capability) {
+                        error!("SPIR-V capability {:?} is not supported", capability);
+                        return Err(Error::FeatureNotPresent);
+                    }
+                },
+                Op::ExtInstImport => {
+                    let (name, _) = literal_string(&o[1..]);
+                    if name != "GLSL.std.450" {
+                        error!("SPIR-V extended instruction set {:?} is not supported", name);
+                        return Err(Error::FeatureNotPresent);
+                    }
+                    program.glsl = Some(o[0]);
+                },
+                Op::MemoryModel => {
+                    // Logical addressing; any memory model is fine, since invocations do not run
+                    // concurrently.
+                    if o[0] != 0 {
+                        error!("SPIR-V addressing model {} is not supported", o[0]);
+                        return Err(Error::FeatureNotPresent);
+                    }
+                },
+                Op::EntryPoint => {
+                    let (name, words) = literal_string(&o[2..]);
+                    program.entry_points.push(EntryPoint {
+                        name: name,
+                        model: ExecutionModel::from(o[0]),
+                        local_size: [1, 1, 1],
+                        origin_upper_left: false,
+                        early_fragment_tests: false,
+                        depth_replacing: false,
+                        function: o[1],
+                        interface: o[(2 + words)..].to_vec(),
+                        inputs: Vec::new(),
+                        outputs: Vec::new(),
+                    });
+                },
+                Op::ExecutionMode => execution_modes.push(o.to_vec()),
+                Op::TypeVoid => program.types[o[0] as usize] = Some(Type::Void),
+                Op::TypeBool => program.types[o[0] as usize] = Some(Type::Bool),
+                Op::TypeInt | Op::TypeFloat => {
+                    if o[1] != 32 {
+                        error!("{}-bit SPIR-V types are not supported", o[1]);
+                        return Err(Error::FeatureNotPresent);
+                    }
+                    program.types[o[0] as usize] = Some(if op == Op::TypeInt {
+                        Type::Int { signed: o[2] != 0 }
+                    } else {
+                        Type::Float
+                    });
+                },
+                Op::TypeVector => program.types[o[0] as usize] = Some(Type::Vector { component: o[1], count: o[2] }),
+                Op::TypeMatrix => program.types[o[0] as usize] = Some(Type::Matrix { column: o[1], count: o[2] }),
+                Op::TypeImage => {
+                    program.types[o[0] as usize] = Some(Type::Image(ImageType {
+                        sampled_type: o[1],
+                        dim: Dim::from(o[2]),
+                        depth: o[3],
+                        arrayed: o[4] != 0,
+                        multisampled: o[5] != 0,
+                        sampled: o[6],
+                        format: ImageFormat::from(o[7]),
+                    }));
+                },
+                Op::TypeSampler => program.types[o[0] as usize] = Some(Type::Sampler),
+                Op::TypeSampledImage => program.types[o[0] as usize] = Some(Type::SampledImage { image: o[1] }),
+                Op::TypeArray => {
+                    let length = program.constant(o[2]).map(|x| x.scalar()).unwrap_or(0);
+                    program.types[o[0] as usize] = Some(Type::Array { element: o[1], length: length });
+                },
+                Op::TypeRuntimeArray => program.types[o[0] as usize] = Some(Type::RuntimeArray { element: o[1] }),
+                Op::TypeStruct => program.types[o[0] as usize] = Some(Type::Struct { members: o[1..].to_vec() }),
+                Op::TypePointer => {
+                    program.types[o[0] as usize] = Some(Type::Pointer { storage: StorageClass::from(o[1]), pointee: o[2] });
+                },
+                Op::TypeFunction => program.types[o[0] as usize] = Some(Type::Function),
+                Op::ConstantTrue | Op::SpecConstantTrue => program.constants[o[1] as usize] = Some(Value::Scalar(1)),
+                Op::ConstantFalse | Op::SpecConstantFalse => program.constants[o[1] as usize] = Some(Value::Scalar(0)),
+                Op::Constant | Op::SpecConstant => program.constants[o[1] as usize] = Some(Value::Scalar(o[2])),
+                Op::ConstantComposite | Op::SpecConstantComposite => {
+                    let members = o[2..].iter().map(|&id| program.constant(id).cloned().unwrap_or(Value::Scalar(0))).collect();
+                    program.constants[o[1] as usize] = Some(Value::Composite(members));
+                },
+                Op::ConstantNull | Op::Undef => {
+                    let value = program.zero(o[0]);
+                    program.constants[o[1] as usize] = Some(value);
+                },
+                Op::SpecConstantOp => {
+                    let value = {
+                        let lookup = |id: u32| program.constant(id).cloned().unwrap_or(Value::Scalar(0));
+                        evaluate(&program, Op::from(o[2]), o[0], &o[3..], &lookup)
+                    };
+                    match value {
+                        Some(value) => program.constants[o[1] as usize] = Some(value),
+                        None => {
+                            error!("SPIR-V specialization constant operation {:?} is not supported", Op::from(o[2]));
+                            return Err(Error::FeatureNotPresent);
+                        },
+                    }
+                },
+                Op::Variable => {
+                    let storage = StorageClass::from(o[2]);
+                    let slot = match storage {
+                        StorageClass::Workgroup => {
+                            program.workgroup_count += 1;
+                            program.workgroup_count - 1
+                        },
+                        StorageClass::Private | StorageClass::Input | StorageClass::Output => {
+                            program.private_count += 1;
+                            program.private_count - 1
+                        },
+                        _ => 0,
+                    };
+                    let pointee = match program.ty(o[0]) {
+                        &Type::Pointer { pointee, .. } => pointee,
+                        _ => 0,
+                    };
+                    program.globals.push(Global {
+                        id: o[1],
+                        storage: storage,
+                        pointee: pointee,
+                        initializer: o.get(3).cloned(),
+                        slot: slot,
+                    });
+                },
+                Op::Function => {
+                    function = Some((o[1], Function {
+                        parameters: Vec::new(),
+                        blocks: Vec::new(),
+                        labels: HashMap::new(),
+                    }));
+                },
+                _ => {},
+            }
+        }
+
+        for mode in execution_modes {
+            let entry_point = match program.entry_points.iter_mut().find(|x| x.function == mode[0]) {
+                Some(entry_point) => entry_point,
+                None => continue,
+            };
+            match ExecutionMode::from(mode[1]) {
+                ExecutionMode::LocalSize => entry_point.local_size = [mode[2], mode[3], mode[4]],
+                ExecutionMode::OriginUpperLeft => entry_point.origin_upper_left = true,
+                ExecutionMode::EarlyFragmentTests => entry_point.early_fragment_tests = true,
+                ExecutionMode::DepthReplacing => entry_point.depth_replacing = true,
+                _ => {},
+            }
+        }
+
+        // A constant decorated as the workgroup size overrides every LocalSize.
+        let workgroup_size = program.decorations.iter()
+            .find(|&(_, decorations)| decorations.builtin == Some(BuiltIn::WorkgroupSize))
+            .and_then(|(&id, _)| program.constant(id))
+            .map(|value| value.components());
+        if let Some(size) = workgroup_size {
+            for entry_point in &mut program.entry_points {
+                for i in 0..3 {
+                    entry_point.local_size[i] = size.get(i).cloned().unwrap_or(1);
+                }
+            }
+        }
+
+        for i in 0..program.entry_points.len() {
+            if !program.functions.contains_key(&program.entry_points[i].function) {
+                error!("SPIR-V entry point {:?} has no function", program.entry_points[i].name);
+                return Err(Error::InvalidShader);
+            }
+            let (inputs, outputs) = program.interfaces(&program.entry_points[i].interface);
+            program.entry_points[i].inputs = inputs;
+            program.entry_points[i].outputs = outputs;
+        }
+
+        Ok(program)
+    }
+
+    pub fn entry_point(&self, name: &str, model: ExecutionModel) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|x| x.name == name && x.model == model)
+    }
+
+    pub fn entry_points(&self) -> &[EntryPoint] { &self.entry_points }
+
+    /// Fresh workgroup memory for a workgroup to share, for `run_workgroup`.
+    pub fn workgroup_memory(&self) -> Vec<Value> {
+        let mut memory = vec![Value::Scalar(0); self.workgroup_count];
+        for global in &self.globals {
+            if global.storage == StorageClass::Workgroup {
+                memory[global.slot] = self.zero(global.pointee);
+            }
+        }
+        memory
+    }
+
+    pub fn ty(&self, id: u32) -> &Type {
+        self.types.get(id as usize).and_then(|x| x.as_ref()).unwrap_or(&self.void)
+    }
+
+    /// The type of the value an ID holds.
+    pub fn type_of(&self, id: u32) -> &Type {
+        self.ty(self.result_types.get(id as usize).cloned().unwrap_or(0))
+    }
+
+    pub fn constant(&self, id: u32) -> Option<&Value> {
+        self.constants.get(id as usize).and_then(|x| x.as_ref())
+    }
+
+    pub fn decorations(&self, id: u32) -> &Decorations {
+        self.decorations.get(&id).unwrap_or(&self.undecorated)
+    }
+
+    pub fn member_decorations(&self, id: u32, member: u32) -> &Decorations {
+        self.member_decorations.get(&(id, member)).unwrap_or(&self.undecorated)
+    }
+
+    /// The all-zero value of a type.
+    pub fn zero(&self, ty: u32) -> Value {
+        match *self.ty(ty) {
+            Type::Vector { count, .. } => Value::Composite(vec![Value::Scalar(0); count as usize]),
+            Type::Matrix { column, count } => Value::Composite(vec![self.zero(column); count as usize]),
+            Type::Array { element, length } => Value::Composite(vec![self.zero(element); length as usize]),
+            Type::Struct { ref members } => Value::Composite(members.iter().map(|&x| self.zero(x)).collect()),
+            _ => Value::Scalar(0),
+        }
+    }
+
+    /// The image type of an image, sampled image, or pointer to either, by the ID holding it.
+    pub fn image_type(&self, id: u32) -> Option<ImageType> {
+        let mut ty = self.type_of(id);
+        loop {
+            ty = match *ty {
+                Type::Image(image) => return Some(image),
+                Type::SampledImage { image } => self.ty(image),
+                Type::Pointer { pointee, .. } => self.ty(pointee),
+                Type::Array { element, .. } | Type::RuntimeArray { element } => self.ty(element),
+                _ => return None,
+            };
+        }
+    }
+
+    /// The number of descriptors a variable of a type takes up.
+    pub fn descriptor_count(&self, ty: u32) -> u32 {
+        match *self.ty(ty) {
+            Type::Array { element, length } => length * self.descriptor_count(element),
+            _ => 1,
+        }
+    }
+
+    /// The number of scalars in a value of a type.
+    fn scalar_count(&self, ty: u32) -> u32 {
+        match *self.ty(ty) {
+            Type::Vector { count, .. } => count,
+            Type::Matrix { column, count } => count * self.scalar_count(column),
+            Type::Array { element, length } => length * self.scalar_count(element),
+            Type::Struct { ref members } => members.iter().map(|&x| self.scalar_count(x)).sum(),
+            _ => 1,
+        }
+    }
+
+    fn interfaces(&self, ids: &[u32]) -> (Vec<Interface>, Vec<Interface>) {
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for global in &self.globals {
+            if !ids.contains(&global.id) {
+                continue;
+            }
+            let interfaces = match global.storage {
+                StorageClass::Input => &mut inputs,
+                StorageClass::Output => &mut outputs,
+                _ => continue,
+            };
+
+            let decorations = self.decorations(global.id);
+            let flat = decorations.flat;
+            let no_perspective = decorations.no_perspective;
+            if let Some(builtin) = decorations.builtin {
+                interfaces.push(Interface {
+                    slot: Slot::BuiltIn(builtin),
+                    components: self.scalar_count(global.pointee),
+                    flat: flat,
+                    no_perspective: no_perspective,
+                    memory: global.slot,
+                    path: Vec::new(),
+                });
+            } else if let Some(location) = decorations.location {
+                self.expand_interface(interfaces, global.pointee, location, flat, no_perspective, global.slot, &mut Vec::new());
+            } else if let Type::Struct { ref members } = *self.ty(global.pointee) {
+                // A block whose members carry their own decorations, like `gl_PerVertex`.
+                for member in 0..(members.len() as u32) {
+                    let decorations = self.member_decorations(global.pointee, member);
+                    let flat = flat || decorations.flat;
+                    let no_perspective = no_perspective || decorations.no_perspective;
+                    if let Some(builtin) = decorations.builtin {
+                        interfaces.push(Interface {
+                            slot: Slot::BuiltIn(builtin),
+                            components: self.scalar_count(members[member as usize]),
+                            flat: flat,
+                            no_perspective: no_perspective,
+                            memory: global.slot,
+                            path: vec![member],
+                        });
+                    } else if let Some(location) = decorations.location {
+                        self.expand_interface(interfaces, members[member as usize], location, flat, no_perspective, global.slot, &mut vec![member]);
+                    }
+                }
+            }
+        }
+        (inputs, outputs)
+    }
+
+    /// Splits a value with a location into one interface per location, returning the next
+    /// location after it.
+    fn expand_interface(
+        &self,
+        interfaces: &mut Vec<Interface>,
+        ty: u32,
+        location: u32,
+        flat: bool,
+        no_perspective: bool,
+        memory: usize,
+        path: &mut Vec<u32>,
+    ) -> u32 {
+        let (count, element) = match *self.ty(ty) {
+            Type::Matrix { column, count } => (count, Some(column)),
+            Type::Array { element, length } => (length, Some(element)),
+            Type::Struct { ref members } => {
+                let mut location = location;
+                for (i, &member) in members.iter().enumerate() {
+                    path.push(i as u32);
+                    location = self.expand_interface(interfaces, member, location, flat, no_perspective, memory, path);
+                    path.pop();
+                }
+                return location;
+            },
+            _ => (1, None),
+        };
+
+        match element {
+            Some(element) => {
+                let mut location = location;
+                for i in 0..count {
+                    path.push(i);
+                    location = self.expand_interface(interfaces, element, location, flat, no_perspective, memory, path);
+                    path.pop();
+                }
+                location
+            },
+            None => {
+                interfaces.push(Interface {
+                    slot: Slot::Location(location),
+                    components: self.scalar_count(ty),
+                    flat: flat,
+                    no_perspective: no_perspective,
+                    memory: memory,
+                    path: path.clone(),
+                });
+                location + 1
+            },
+        }
+    }
+
+    /// The size of a type with no explicit layout, for arrays in memory without an `ArrayStride`.
+    fn packed_size(&self, ty: u32) -> u64 {
+        self.scalar_count(ty) as u64 * 4
+    }
+
+    /// A pointer to a member, element, column or component of what `pointer` points to.
+    fn memory_element(&self, pointer: &MemoryPointer, index: u32) -> MemoryPointer {
+        let mut element = *pointer;
+        element.component_stride = 4;
+        match *self.ty(pointer.ty) {
+            Type::Struct { ref members } => {
+                let decorations = self.member_decorations(pointer.ty, index);
+                element.ty = members.get(index as usize).cloned().unwrap_or(0);
+                element.offset += decorations.offset.unwrap_or(0) as u64;
+                element.matrix = decorations.matrix_stride.map(|stride| MatrixLayout {
+                    stride: stride,
+                    row_major: decorations.row_major,
+                });
+            },
+            Type::Array { element: ty, .. } | Type::RuntimeArray { element: ty } => {
+                let stride = self.decorations(pointer.ty).array_stride
+                    .map(|x| x as u64)
+                    .unwrap_or_else(|| self.packed_size(ty));
+                element.ty = ty;
+                element.offset += index as u64 * stride;
+            },
+            Type::Matrix { column, .. } => {
+                let layout = pointer.matrix.unwrap_or(MatrixLayout { stride: 16, row_major: false });
+                element.ty = column;
+                element.matrix = None;
+                if layout.row_major {
+                    element.offset += index as u64 * 4;
+                    element.component_stride = layout.stride;
+                } else {
+                    element.offset += index as u64 * layout.stride as u64;
+                }
+            },
+            Type::Vector { component, .. } => {
+                element.ty = component;
+                element.offset += index as u64 * pointer.component_stride as u64;
+            },
+            _ => {},
+        }
+        element
+    }
+
+    /// The number of members, elements, columns or components of what `pointer` points to.
+    fn memory_count(&self, pointer: &MemoryPointer) -> u32 {
+        match *self.ty(pointer.ty) {
+            Type::Struct { ref members } => members.len() as u32,
+            Type::Array { length, .. } => length,
+            Type::Vector { count, .. } | Type::Matrix { count, .. } => count,
+            Type::RuntimeArray { element } => {
+                let stride = self.decorations(pointer.ty).array_stride
+                    .map(|x| x as u64)
+                    .unwrap_or_else(|| self.packed_size(element));
+                if stride == 0 { 0 } else { (pointer.size.saturating_sub(pointer.offset) / stride) as u32 }
+            },
+            _ => 0,
+        }
+    }
+
+    fn load_memory(&self, pointer: &MemoryPointer) -> Value {
+        match *self.ty(pointer.ty) {
+            Type::Bool | Type::Int { .. } | Type::Float => {
+                let word = if !pointer.base.is_null() && pointer.offset + 4 <= pointer.size {
+                    unsafe { ptr::read_unaligned(pointer.base.offset(pointer.offset as isize) as *const u32) }
+                } else {
+                    0
+                };
+                match *self.ty(pointer.ty) {
+                    Type::Bool => Value::Scalar((word != 0) as u32),
+                    _ => Value::Scalar(word),
+                }
+            },
+            _ => {
+                let count = self.memory_count(pointer);
+                Value::Composite((0..count).map(|i| self.load_memory(&self.memory_element(pointer, i))).collect())
+            },
+        }
+    }
+
+    fn store_memory(&self, pointer: &MemoryPointer, value: &Value) {
+        match *value {
+            Value::Composite(ref values) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.store_memory(&self.memory_element(pointer, i as u32), value);
+                }
+            },
+            Value::Scalar(word) => {
+                if !pointer.base.is_null() && pointer.offset + 4 <= pointer.size {
+                    unsafe { ptr::write_unaligned(pointer.base.offset(pointer.offset as isize) as *mut u32, word) };
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// The number of components of a vector type, or 0 for anything else.
+    fn vector_count(&self, ty: u32) -> usize {
+        match *self.ty(ty) {
+            Type::Vector { count, .. } => count as usize,
+            _ => 0,
+        }
+    }
+}
+
+/// Applies `f` to every scalar of a value.
+pub(crate) fn map(value: &Value, f: &Fn(u32) -> u32) -> Value {
+    match *value {
+        Value::Scalar(x) => Value::Scalar(f(x)),
+        Value::Composite(ref values) => Value::Composite(values.iter().map(|x| map(x, f)).collect()),
+        ref other => other.clone(),
+    }
+}
+
+/// Applies `f` to the corresponding scalars of two values, repeating a scalar against a composite.
+pub(crate) fn zip(a: &Value, b: &Value, f: &Fn(u32, u32) -> u32) -> Value {
+    match (a, b) {
+        (&Value::Composite(ref a), &Value::Composite(ref b)) => {
+            Value::Composite(a.iter().zip(b).map(|(a, b)| zip(a, b, f)).collect())
+        },
+        (&Value::Composite(ref a), b) => Value::Composite(a.iter().map(|a| zip(a, b, f)).collect()),
+        (a, &Value::Composite(ref b)) => Value::Composite(b.iter().map(|b| zip(a, b, f)).collect()),
+        (a, b) => Value::Scalar(f(a.scalar(), b.scalar())),
+    }
+}
+
+/// Applies `f` to the corresponding scalars of three values, repeating scalars against composites.
+pub(crate) fn zip3(a: &Value, b: &Value, c: &Value, f: &Fn(u32, u32, u32) -> u32) -> Value {
+    let at = |value: &Value, i: usize| match *value {
+        Value::Composite(ref values) => values.get(i).cloned().unwrap_or(Value::Scalar(0)),
+        ref other => other.clone(),
+    };
+    let count = [a, b, c].iter()
+        .filter_map(|x| match **x { Value::Composite(ref values) => Some(values.len()), _ => None })
+        .max();
+    match count {
+        Some(count) => Value::Composite((0..count).map(|i| zip3(&at(a, i), &at(b, i), &at(c, i), f)).collect()),
+        None => Value::Scalar(f(a.scalar(), b.scalar(), c.scalar())),
+    }
+}
+
+pub(crate) fn navigate<'v>(value: &'v Value, path: &[u32]) -> Option<&'v Value> {
+    match path.split_first() {
+        None => Some(value),
+        Some((&index, rest)) => match *value {
+            Value::Composite(ref values) => values.get(index as usize).and_then(|x| navigate(x, rest)),
+            _ => None,
+        },
+    }
+}
+
+pub(crate) fn navigate_mut<'v>(value: &'v mut Value, path: &[u32]) -> Option<&'v mut Value> {
+    match path.split_first() {
+        None => Some(value),
+        Some((&index, rest)) => match *value {
+            Value::Composite(ref mut values) => values.get_mut(index as usize).and_then(|x| navigate_mut(x, rest)),
+            _ => None,
+        },
+    }
+}
+
+/// Lists the scalars of a value in order.
+fn flatten(value: &Value, scalars: &mut Vec<u32>) {
+    match *value {
+        Value::Composite(ref values) => for value in values {
+            flatten(value, scalars);
+        },
+        ref value => scalars.push(value.scalar()),
+    }
+}
+
+/// Overwrites the scalars of a value in order, for as many as there are.
+fn fill(value: &mut Value, scalars: &mut Iterator<Item = u32>) {
+    match *value {
+        Value::Composite(ref mut values) => for value in values {
+            fill(value, scalars);
+        },
+        Value::Scalar(ref mut x) => if let Some(scalar) = scalars.next() {
+            *x = scalar;
+        },
+        _ => {},
+    }
+}
+
+pub(crate) fn from_floats(floats: &[f32]) -> Value {
+    Value::Composite(floats.iter().map(|x| Value::Scalar(x.to_bits())).collect())
+}
+
+fn columns(matrix: &Value) -> Vec<Vec<f32>> {
+    match *matrix {
+        Value::Composite(ref columns) => columns.iter().map(|x| x.floats()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn from_columns(columns: &[Vec<f32>]) -> Value {
+    Value::Composite(columns.iter().map(|x| from_floats(x)).collect())
+}
+
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Converts a float to a signed integer, saturating where it is out of range.
+fn f32_to_i32(x: f32) -> i32 {
+    if x.is_nan() {
+        0
+    } else if x >= 2147483648.0 {
+        i32::max_value()
+    } else if x <= -2147483648.0 {
+        i32::min_value()
+    } else {
+        x as i32
+    }
+}
+
+/// Converts a float to an unsigned integer, saturating where it is out of range.
+fn f32_to_u32(x: f32) -> u32 {
+    if x.is_nan() || x <= 0.0 {
+        0
+    } else if x >= 4294967296.0 {
+        u32::max_value()
+    } else {
+        x as u32
+    }
+}
+
+/// A mask of the low `count` bits.
+fn low_bits(count: u32) -> u32 {
+    if count >= 32 { !0 } else { (1 << count) - 1 }
+}
+
+fn offset_of(value: &Value) -> [i32; 3] {
+    let components = value.components();
+    let at = |i: usize| components.get(i).cloned().unwrap_or(0) as i32;
+    [at(0), at(1), at(2)]
+}
+
+/// Evaluates an instruction that only computes a value from its operands, which are given after
+/// the result type and ID; `value` looks up the value of an ID. Returns `None` for anything else.
+///
+/// This is shared between running code and folding `OpSpecConstantOp`.
+pub(crate) fn evaluate(program: &Program, op: Op, result_type: u32, o: &[u32], value: &Fn(u32) -> Value) -> Option<Value> {
+    let f = f32::from_bits;
+    let unary = |g: &Fn(u32) -> u32| map(&value(o[0]), g);
+    let binary = |g: &Fn(u32, u32) -> u32| zip(&value(o[0]), &value(o[1]), g);
+    let float_binary = |g: &Fn(f32, f32) -> f32| zip(&value(o[0]), &value(o[1]), &|a, b| g(f(a), f(b)).to_bits());
+    let compare = |g: &Fn(u32, u32) -> bool| zip(&value(o[0]), &value(o[1]), &|a, b| g(a, b) as u32);
+    let signed_compare = |g: &Fn(i32, i32) -> bool| zip(&value(o[0]), &value(o[1]), &|a, b| g(a as i32, b as i32) as u32);
+    let float_compare = |g: &Fn(f32, f32) -> bool| zip(&value(o[0]), &value(o[1]), &|a, b| g(f(a), f(b)) as u32);
+
+    Some(match op {
+        Op::Undef => program.zero(result_type),
+        Op::CopyObject | Op::UConvert | Op::SConvert | Op::FConvert | Op::Bitcast => value(o[0]),
+        Op::SNegate => unary(&|x| (x as i32).wrapping_neg() as u32),
+        Op::FNegate => unary(&|x| x ^ 0x8000_0000),
+        Op::Not => unary(&|x| !x),
+        Op::LogicalNot => unary(&|x| (x == 0) as u32),
+        Op::ConvertFToU => unary(&|x| f32_to_u32(f(x))),
+        Op::ConvertFToS => unary(&|x| f32_to_i32(f(x)) as u32),
+        Op::ConvertSToF => unary(&|x| (x as i32 as f32).to_bits()),
+        Op::ConvertUToF => unary(&|x| (x as f32).to_bits()),
+        Op::QuantizeToF16 => unary(&|x| glsl::f16_to_f32(glsl::f32_to_f16(f(x))).to_bits()),
+        Op::BitReverse => unary(&|mut x| {
+            let mut reversed = 0;
+            for _ in 0..32 {
+                reversed = (reversed << 1) | (x & 1);
+                x >>= 1;
+            }
+            reversed
+        }),
+        Op::BitCount => unary(&|x| x.count_ones()),
+        Op::IsNan => unary(&|x| f(x).is_nan() as u32),
+        Op::IsInf => unary(&|x| f(x).is_infinite() as u32),
+        Op::IsFinite => unary(&|x| f(x).is_finite() as u32),
+        Op::IsNormal => unary(&|x| f(x).is_normal() as u32),
+        Op::SignBitSet => unary(&|x| x >> 31),
+
+        Op::IAdd => binary(&|a, b| a.wrapping_add(b)),
+        Op::ISub => binary(&|a, b| a.wrapping_sub(b)),
+        Op::IMul => binary(&|a, b| a.wrapping_mul(b)),
+        Op::UDiv => binary(&|a, b| a.checked_div(b).unwrap_or(0)),
+        Op::SDiv => binary(&|a, b| (a as i32).checked_div(b as i32).unwrap_or(0) as u32),
+        Op::UMod => binary(&|a, b| a.checked_rem(b).unwrap_or(0)),
+        Op::SRem => binary(&|a, b| (a as i32).checked_rem(b as i32).unwrap_or(0) as u32),
+        Op::SMod => binary(&|a, b| {
+            let (a, b) = (a as i32, b as i32);
+            let remainder = a.checked_rem(b).unwrap_or(0);
+            (if remainder != 0 && (remainder < 0) != (b < 0) { remainder + b } else { remainder }) as u32
+        }),
+        Op::FAdd => float_binary(&|a, b| a + b),
+        Op::FSub => float_binary(&|a, b| a - b),
+        Op::FMul => float_binary(&|a, b| a * b),
+        Op::FDiv => float_binary(&|a, b| a / b),
+        Op::FRem => float_binary(&|a, b| a % b),
+        Op::FMod => float_binary(&|a, b| a - b * (a / b).floor()),
+        Op::ShiftRightLogical => binary(&|a, b| a.checked_shr(b).unwrap_or(0)),
+        Op::ShiftRightArithmetic => binary(&|a, b| (a as i32).checked_shr(b).unwrap_or((a as i32) >> 31) as u32),
+        Op::ShiftLeftLogical => binary(&|a, b| a.checked_shl(b).unwrap_or(0)),
+        Op::BitwiseOr | Op::LogicalOr => binary(&|a, b| a | b),
+        Op::BitwiseXor => binary(&|a, b| a ^ b),
+        Op::BitwiseAnd | Op::LogicalAnd => binary(&|a, b| a & b),
+
+        Op::IEqual | Op::LogicalEqual => compare(&|a, b| a == b),
+        Op::INotEqual | Op::LogicalNotEqual => compare(&|a, b| a != b),
+        Op::UGreaterThan => compare(&|a, b| a > b),
+        Op::UGreaterThanEqual => compare(&|a, b| a >= b),
+        Op::ULessThan => compare(&|a, b| a < b),
+        Op::ULessThanEqual => compare(&|a, b| a <= b),
+        Op::SGreaterThan => signed_compare(&|a, b| a > b),
+        Op::SGreaterThanEqual => signed_compare(&|a, b| a >= b),
+        Op::SLessThan => signed_compare(&|a, b| a < b),
+        Op::SLessThanEqual => signed_compare(&|a, b| a <= b),
+        Op::FOrdEqual => float_compare(&|a, b| a == b),
+        Op::FUnordEqual => float_compare(&|a, b| !(a < b || a > b)),
+        Op::FOrdNotEqual | Op::LessOrGreater => float_compare(&|a, b| a < b || a > b),
+        Op::FUnordNotEqual => float_compare(&|a, b| a != b),
+        Op::FOrdLessThan => float_compare(&|a, b| a < b),
+        Op::FUnordLessThan => float_compare(&|a, b| !(a >= b)),
+        Op::FOrdGreaterThan => float_compare(&|a, b| a > b),
+        Op::FUnordGreaterThan => float_compare(&|a, b| !(a <= b)),
+        Op::FOrdLessThanEqual => float_compare(&|a, b| a <= b),
+        Op::FUnordLessThanEqual => float_compare(&|a, b| !(a > b)),
+        Op::FOrdGreaterThanEqual => float_compare(&|a, b| a >= b),
+        Op::FUnordGreaterThanEqual => float_compare(&|a, b| !(a < b)),
+        Op::Ordered => float_compare(&|a, b| !a.is_nan() && !b.is_nan()),
+        Op::Unordered => float_compare(&|a, b| a.is_nan() || b.is_nan()),
+        Op::Any => Value::Scalar(value(o[0]).components().iter().any(|&x| x != 0) as u32),
+        Op::All => Value::Scalar(value(o[0]).components().iter().all(|&x| x != 0) as u32),
+        Op::Select => match value(o[0]) {
+            Value::Scalar(condition) => if condition != 0 { value(o[1]) } else { value(o[2]) },
+            condition => zip3(&condition, &value(o[1]), &value(o[2]), &|c, a, b| if c != 0 { a } else { b }),
+        },
+
+        Op::BitFieldInsert => {
+            let offset = value(o[2]).scalar();
+            let mask = low_bits(value(o[3]).scalar()).checked_shl(offset).unwrap_or(0);
+            binary(&|base, insert| (base & !mask) | (insert.checked_shl(offset).unwrap_or(0) & mask))
+        },
+        Op::BitFieldUExtract | Op::BitFieldSExtract => {
+            let offset = value(o[1]).scalar();
+            let count = value(o[2]).scalar();
+            let signed = op == Op::BitFieldSExtract;
+            unary(&|base| {
+                let bits = base.checked_shr(offset).unwrap_or(0) & low_bits(count);
+                if signed && count > 0 && count < 32 && bits >> (count - 1) != 0 { bits | !low_bits(count) } else { bits }
+            })
+        },
+        Op::IAddCarry | Op::ISubBorrow | Op::UMulExtended | Op::SMulExtended => {
+            let wide = |a: u32, b: u32| -> (u32, u32) {
+                match op {
+                    Op::IAddCarry => (a.wrapping_add(b), a.checked_add(b).is_none() as u32),
+                    Op::ISubBorrow => (a.wrapping_sub(b), (b > a) as u32),
+                    Op::UMulExtended => {
+                        let product = a as u64 * b as u64;
+                        (product as u32, (product >> 32) as u32)
+                    },
+                    _ => {
+                        let product = a as i32 as i64 * b as i32 as i64;
+                        (product as u32, (product >> 32) as u32)
+                    },
+                }
+            };
+            Value::Composite(vec![binary(&|a, b| wide(a, b).0), binary(&|a, b| wide(a, b).1)])
+        },
+
+        Op::VectorTimesScalar | Op::MatrixTimesScalar => {
+            let scalar = f(value(o[1]).scalar());
+            unary(&|x| (f(x) * scalar).to_bits())
+        },
+        Op::Dot => Value::Scalar(dot(&value(o[0]).floats(), &value(o[1]).floats()).to_bits()),
+        Op::VectorTimesMatrix => {
+            let vector = value(o[0]).floats();
+            let result: Vec<f32> = columns(&value(o[1])).iter().map(|column| dot(&vector, column)).collect();
+            from_floats(&result)
+        },
+        Op::MatrixTimesVector => {
+            let vector = value(o[1]).floats();
+            let columns = columns(&value(o[0]));
+            let rows = columns.get(0).map(|x| x.len()).unwrap_or(0);
+            let result: Vec<f32> = (0..rows).map(|row| columns.iter().zip(&vector).map(|(column, v)| column[row] * v).sum()).collect();
+            from_floats(&result)
+        },
+        Op::MatrixTimesMatrix => {
+            let left = columns(&value(o[0]));
+            let rows = left.get(0).map(|x| x.len()).unwrap_or(0);
+            let result: Vec<Vec<f32>> = columns(&value(o[1])).iter()
+                .map(|right| (0..rows).map(|row| left.iter().zip(right).map(|(column, v)| column[row] * v).sum()).collect())
+                .collect();
+            from_columns(&result)
+        },
+        Op::OuterProduct => {
+            let left = value(o[0]).floats();
+            let result: Vec<Vec<f32>> = value(o[1]).floats().iter().map(|&v| left.iter().map(|x| x * v).collect()).collect();
+            from_columns(&result)
+        },
+        Op::Transpose => {
+            let columns = columns(&value(o[0]));
+            let rows = columns.get(0).map(|x| x.len()).unwrap_or(0);
+            let result: Vec<Vec<f32>> = (0..rows).map(|row| columns.iter().map(|column| column[row]).collect()).collect();
+            from_columns(&result)
+        },
+
+        Op::CompositeConstruct => {
+            if program.vector_count(result_type) > 0 {
+                let mut components = Vec::new();
+                for &id in o {
+                    for component in value(id).components() {
+                        components.push(Value::Scalar(component));
+                    }
+                }
+                Value::Composite(components)
+            } else {
+                Value::Composite(o.iter().map(|&id| value(id)).collect())
+            }
+        },
+        Op::CompositeExtract => {
+            let composite = value(o[0]);
+            return navigate(&composite, &o[1..]).cloned().or_else(|| Some(program.zero(result_type)));
+        },
+        Op::CompositeInsert => {
+            let mut composite = value(o[1]);
+            if let Some(target) = navigate_mut(&mut composite, &o[2..]) {
+                *target = value(o[0]);
+            }
+            composite
+        },
+        Op::VectorExtractDynamic => {
+            let index = value(o[1]).scalar();
+            Value::Scalar(value(o[0]).components().get(index as usize).cloned().unwrap_or(0))
+        },
+        Op::VectorInsertDynamic => {
+            let mut vector = value(o[0]);
+            let index = value(o[2]).scalar();
+            if let Some(target) = navigate_mut(&mut vector, &[index]) {
+                *target = value(o[1]);
+            }
+            vector
+        },
+        Op::VectorShuffle => {
+            let mut components = value(o[0]).components();
+            components.extend(value(o[1]).components());
+            Value::Composite(o[2..].iter()
+                .map(|&i| Value::Scalar(if i == 0xFFFFFFFF { 0 } else { components.get(i as usize).cloned().unwrap_or(0) }))
+                .collect())
+        },
+
+        Op::SampledImage => match (value(o[0]), value(o[1])) {
+            (Value::Image(image), Value::Sampler(sampler)) => Value::SampledImage(image, sampler),
+            (Value::SampledImage(image, _), Value::Sampler(sampler)) => Value::SampledImage(image, sampler),
+            (image, _) => image,
+        },
+        Op::Image => match value(o[0]) {
+            Value::SampledImage(image, _) => Value::Image(image),
+            image => image,
+        },
+
+        Op::ExtInst => {
+            if program.glsl != Some(o[0]) {
+                return None;
+            }
+            let arguments: Vec<Value> = o[2..].iter().map(|&id| value(id)).collect();
+            return glsl::evaluate(Glsl450::from(o[1]), &arguments);
+        },
+
+        _ => return None,
+    })
+}
+
+/// Why `Invocation::run` returned.
+#[derive(Clone, Debug)]
+pub enum Status {
+    Finished,
+    /// The fragment was discarded by `OpKill`.
+    Killed,
+    /// At an `OpControlBarrier` for the workgroup.
+    Barrier,
+    /// The invocation needs the derivatives of this value across its quad, which should be passed to
+    /// `set_derivatives` before running it again.
+    Derivatives(Value),
+}
+
+enum Flow<'a> {
+    Next,
+    Branch(u32),
+    Call(&'a Function, u32, Vec<Value>),
+    Return(Option<Value>),
+    Suspend(Status),
+    Kill,
+}
+
+struct Frame<'a> {
+    function: &'a Function,
+    block: usize,
+    instruction: usize,
+    /// The ID to give the return value to.
+    result: Option<u32>,
+    /// Where the function's variables start in the invocation's memory.
+    memory: usize,
+}
+
+/// The optional operands of an image instruction.
+struct ImageOperands {
+    bias: f32,
+    /// A float for sampling, and an integer for fetches.
+    lod: Option<u32>,
+    grad: Option<(Value, Value)>,
+    offset: [i32; 3],
+    offsets: Option<Value>,
+    min_lod: Option<f32>,
+}
+
+/// One run of an entry point.
+pub struct Invocation<'a> {
+    program: &'a Program,
+    entry_point: &'a EntryPoint,
+    bindings: &'a Bindings,
+    ids: Vec<Option<Value>>,
+    /// Global variables in the invocation's own storage classes, then the variables of each
+    /// function being run.
+    memory: Vec<Value>,
+    frames: Vec<Frame<'a>>,
+    killed: bool,
+    derivatives: Option<(Value, Value)>,
+    quad: bool,
+    helper: bool,
+    fragment_position: [i32; 2],
+}
+
+impl<'a> Invocation<'a> {
+    pub fn new(program: &'a Program, entry_point: &'a EntryPoint, bindings: &'a Bindings) -> Invocation<'a> {
+        let mut invocation = Invocation {
+            program: program,
+            entry_point: entry_point,
+            bindings: bindings,
+            ids: program.constants.clone(),
+            memory: vec![Value::Scalar(0); program.private_count],
+            frames: Vec::new(),
+            killed: false,
+            derivatives: None,
+            quad: false,
+            helper: false,
+            fragment_position: [0, 0],
+        };
+
+        for global in &program.globals {
+            let pointer = match global.storage {
+                StorageClass::Private | StorageClass::Input | StorageClass::Output => {
+                    invocation.memory[global.slot] = global.initializer
+                        .and_then(|id| program.constant(id).cloned())
+                        .unwrap_or_else(|| program.zero(global.pointee));
+                    Pointer::Local { slot: global.slot, path: Vec::new() }
+                },
+                StorageClass::Workgroup => Pointer::Shared { slot: global.slot, path: Vec::new() },
+                StorageClass::PushConstant => {
+                    let data = bindings.push_constants();
+                    Pointer::Memory(MemoryPointer {
+                        base: data.as_ptr() as *mut u8,
+                        size: data.len() as u64,
+                        offset: 0,
+                        ty: global.pointee,
+                        matrix: None,
+                        component_stride: 4,
+                    })
+                },
+                _ => {
+                    let decorations = program.decorations(global.id);
+                    let descriptor = DescriptorRef {
+                        set: decorations.set.unwrap_or(0),
+                        binding: decorations.binding.unwrap_or(0),
+                        element: 0,
+                    };
+                    invocation.descriptor_pointer(descriptor, global.pointee)
+                },
+            };
+            invocation.ids[global.id as usize] = Some(Value::Pointer(pointer));
+        }
+
+        if let Some(function) = program.functions.get(&entry_point.function) {
+            let memory = invocation.memory.len();
+            invocation.frames.push(Frame {
+                function: function,
+                block: 0,
+                instruction: 0,
+                result: None,
+                memory: memory,
+            });
+        }
+        invocation
+    }
+
+    /// Sets the scalars of an input, in order.
+    pub fn set_input(&mut self, slot: Slot, scalars: &[u32]) {
+        let interface = match self.entry_point.inputs.iter().find(|x| x.slot == slot) {
+            Some(interface) => interface,
+            None => return,
+        };
+        if let Some(target) = self.memory.get_mut(interface.memory).and_then(|x| navigate_mut(x, &interface.path)) {
+            fill(target, &mut scalars.iter().cloned());
+        }
+    }
+
+    /// The scalars of an output, in order, if the entry point has it.
+    pub fn output(&self, slot: Slot) -> Option<Vec<u32>> {
+        let interface = match self.entry_point.outputs.iter().find(|x| x.slot == slot) {
+            Some(interface) => interface,
+            None => return None,
+        };
+        self.memory.get(interface.memory).and_then(|x| navigate(x, &interface.path)).map(|value| {
+            let mut scalars = Vec::new();
+            flatten(value, &mut scalars);
+            scalars
+        })
+    }
+
+    /// Makes this a fragment in a quad, to be run by `run_quad`. Subpass inputs are read at `x` and
+    /// `y`, and helper invocations run without writing to memory.
+    pub fn set_fragment(&mut self, x: i32, y: i32, helper: bool) {
+        self.fragment_position = [x, y];
+        self.helper = helper;
+        self.quad = true;
+    }
+
+    /// Gives the derivatives asked for by `Status::Derivatives`.
+    pub fn set_derivatives(&mut self, dx: Value, dy: Value) {
+        self.derivatives = Some((dx, dy));
+    }
+
+    /// Runs until the entry point returns, the fragment is killed, or the invocation has to wait
+    /// for others.
+    pub fn run(&mut self, shared: &mut [Value]) -> Status {
+        loop {
+            let (function, block, instruction) = match self.frames.last() {
+                Some(frame) => (frame.function, frame.block, frame.instruction),
+                None => return if self.killed { Status::Killed } else { Status::Finished },
+            };
+
+            let flow = match function.blocks.get(block).and_then(|x| x.instructions.get(instruction)) {
+                Some(inst) => {
+                    self.frames.last_mut().unwrap().instruction += 1;
+                    self.execute(inst, shared)
+                },
+                // A block without a terminator.
+                None => Flow::Return(None),
+            };
+
+            match flow {
+                Flow::Next => {},
+                Flow::Branch(label) => self.branch(label),
+                Flow::Call(function, result, arguments) => {
+                    for (&parameter, argument) in function.parameters.iter().zip(arguments) {
+                        self.ids[parameter as usize] = Some(argument);
+                    }
+                    let memory = self.memory.len();
+                    self.frames.push(Frame {
+                        function: function,
+                        block: 0,
+                        instruction: 0,
+                        result: Some(result),
+                        memory: memory,
+                    });
+                },
+                Flow::Return(value) => {
+                    let frame = self.frames.pop().unwrap();
+                    self.memory.truncate(frame.memory);
+                    if let (Some(result), Some(value)) = (frame.result, value) {
+                        self.ids[result as usize] = Some(value);
+                    }
+                },
+                Flow::Suspend(status) => {
+                    // The instruction runs again once it has its derivatives.
+                    if let Status::Derivatives(_) = status {
+                        self.frames.last_mut().unwrap().instruction -= 1;
+                    }
+                    return status;
+                },
+                Flow::Kill => {
+                    self.killed = true;
+                    self.frames.clear();
+                },
+            }
+        }
+    }
+
+    fn value(&self, id: u32) -> Value {
+        self.ids.get(id as usize).and_then(|x| x.as_ref()).cloned().unwrap_or(Value::Scalar(0))
+    }
+
+    fn set(&mut self, id: u32, value: Value) {
+        if let Some(slot) = self.ids.get_mut(id as usize) {
+            *slot = Some(value);
+        }
+    }
+
+    fn pointer(&self, id: u32) -> Option<Pointer> {
+        match self.value(id) {
+            Value::Pointer(pointer) => Some(pointer),
+            _ => None,
+        }
+    }
+
+    /// Moves to the start of a block of the current function, running its `OpPhi`s.
+    fn branch(&mut self, label: u32) {
+        let (function, previous) = {
+            let frame = self.frames.last().unwrap();
+            (frame.function, frame.function.blocks[frame.block].label)
+        };
+        let block = function.labels.get(&label).cloned().unwrap_or(function.blocks.len());
+
+        let mut phis = Vec::new();
+        if let Some(block) = function.blocks.get(block) {
+            for inst in block.instructions.iter().take_while(|x| x.op == Op::Phi) {
+                let o = &inst.operands;
+                let value = o[2..].chunks(2)
+                    .find(|pair| pair.len() == 2 && pair[1] == previous)
+                    .map(|pair| self.value(pair[0]))
+                    .unwrap_or_else(|| self.program.zero(o[0]));
+                phis.push((o[1], value));
+            }
+        }
+        let count = phis.len();
+        for (id, value) in phis {
+            self.set(id, value);
+        }
+
+        let frame = self.frames.last_mut().unwrap();
+        frame.block = block;
+        frame.instruction = count;
+    }
+
+    /// A pointer to a descriptor binding of a type: buffers point into their memory, and images
+    /// and samplers point to the descriptor.
+    fn descriptor_pointer(&self, descriptor: DescriptorRef, ty: u32) -> Pointer {
+        match *self.program.ty(ty) {
+            Type::Struct { .. } => {
+                let (base, size) = self.bindings.buffer(descriptor).unwrap_or((ptr::null_mut(), 0));
+                Pointer::Memory(MemoryPointer {
+                    base: base,
+                    size: size,
+                    offset: 0,
+                    ty: ty,
+                    matrix: None,
+                    component_stride: 4,
+                })
+            },
+            _ => Pointer::Descriptor { descriptor: descriptor, ty: ty },
+        }
+    }
+
+    fn element(&self, pointer: Pointer, index: u32) -> Pointer {
+        match pointer {
+            Pointer::Local { slot, mut path } => {
+                path.push(index);
+                Pointer::Local { slot: slot, path: path }
+            },
+            Pointer::Shared { slot, mut path } => {
+                path.push(index);
+                Pointer::Shared { slot: slot, path: path }
+            },
+            Pointer::Memory(memory) => Pointer::Memory(self.program.memory_element(&memory, index)),
+            Pointer::Descriptor { descriptor, ty } => match *self.program.ty(ty) {
+                Type::Array { element, .. } | Type::RuntimeArray { element } => {
+                    let descriptor = DescriptorRef {
+                        element: descriptor.element + index * self.program.descriptor_count(element),
+                        ..descriptor
+                    };
+                    self.descriptor_pointer(descriptor, element)
+                },
+                _ => Pointer::Descriptor { descriptor: descriptor, ty: ty },
+            },
+            other => other,
+        }
+    }
+
+    fn load(&self, pointer: &Pointer, shared: &[Value]) -> Value {
+        match *pointer {
+            Pointer::Local { slot, ref path } => self.memory.get(slot).and_then(|x| navigate(x, path)).cloned(),
+            Pointer::Shared { slot, ref path } => shared.get(slot).and_then(|x| navigate(x, path)).cloned(),
+            Pointer::Memory(ref memory) => Some(self.program.load_memory(memory)),
+            Pointer::Descriptor { descriptor, ty } => Some(match *self.program.ty(ty) {
+                Type::Image(_) => Value::Image(descriptor),
+                Type::Sampler => Value::Sampler(descriptor),
+                Type::SampledImage { .. } => Value::SampledImage(descriptor, descriptor),
+                Type::Array { length, .. } => Value::Composite((0..length)
+                    .map(|i| {
+                        let element = self.element(pointer.clone(), i);
+                        self.load(&element, shared)
+                    })
+                    .collect()),
+                _ => Value::Scalar(0),
+            }),
+            Pointer::Texel { image, coordinates } => self.bindings.texel_pointer(image, coordinates)
+                .map(|texel| Value::Scalar(unsafe { ptr::read_unaligned(texel) })),
+        }.unwrap_or(Value::Scalar(0))
+    }
+
+    fn store(&mut self, pointer: &Pointer, value: Value, shared: &mut [Value]) {
+        match *pointer {
+            Pointer::Local { slot, ref path } => {
+                if let Some(target) = self.memory.get_mut(slot).and_then(|x| navigate_mut(x, path)) {
+                    *target = value;
+                }
+            },
+            Pointer::Shared { slot, ref path } => {
+                if let Some(target) = shared.get_mut(slot).and_then(|x| navigate_mut(x, path)) {
+                    *target = value;
+                }
+            },
+            Pointer::Memory(ref memory) => if !self.helper {
+                self.program.store_memory(memory, &value);
+            },
+            Pointer::Texel { image, coordinates } => if !self.helper {
+                if let Some(texel) = self.bindings.texel_pointer(image, coordinates) {
+                    unsafe { ptr::write_unaligned(texel, value.scalar()) };
+                }
+            },
+            Pointer::Descriptor { .. } => {},
+        }
+    }
+
+    /// The derivatives of `value` across the quad, or zero outside of one. Returns the flow that
+    /// suspends the invocation if they have to be asked for.
+    fn derivatives(&mut self, value: Value) -> ::std::result::Result<(Value, Value), Flow<'a>> {
+        if !self.quad {
+            let zero = map(&value, &|_| 0);
+            return Ok((zero.clone(), zero));
+        }
+        match self.derivatives.take() {
+            Some(derivatives) => Ok(derivatives),
+            None => Err(Flow::Suspend(Status::Derivatives(value))),
+        }
+    }
+
+    fn execute(&mut self, inst: &'a Inst, shared: &mut [Value]) -> Flow<'a> {
+        let o = &inst.operands[..];
+        match inst.op {
+            Op::Phi | Op::LoopMerge | Op::SelectionMerge | Op::MemoryBarrier => {},
+            Op::Branch => return Flow::Branch(o[0]),
+            Op::BranchConditional => {
+                let condition = self.value(o[0]).scalar();
+                return Flow::Branch(if condition != 0 { o[1] } else { o[2] });
+            },
+            Op::Switch => {
+                let selector = self.value(o[0]).scalar();
+                let target = o[2..].chunks(2)
+                    .find(|pair| pair.len() == 2 && pair[0] == selector)
+                    .map(|pair| pair[1])
+                    .unwrap_or(o[1]);
+                return Flow::Branch(target);
+            },
+            Op::Return | Op::Unreachable => return Flow::Return(None),
+            Op::ReturnValue => return Flow::Return(Some(self.value(o[0]))),
+            Op::Kill => return Flow::Kill,
+            Op::FunctionCall => {
+                if let Some(function) = self.program.functions.get(&o[2]) {
+                    let arguments = o[3..].iter().map(|&id| self.value(id)).collect();
+                    return Flow::Call(function, o[1], arguments);
+                }
+            },
+            Op::ControlBarrier => {
+                let scope = self.value(o[0]).scalar();
+                if self.entry_point.model == ExecutionModel::GLCompute && scope == SCOPE_WORKGROUP {
+                    return Flow::Suspend(Status::Barrier);
+                }
+            },
+
+            Op::Variable => {
+                let value = match o.get(3) {
+                    Some(&initializer) => self.value(initializer),
+                    None => match *self.program.ty(o[0]) {
+                        Type::Pointer { pointee, .. } => self.program.zero(pointee),
+                        _ => Value::Scalar(0),
+                    },
+                };
+                self.memory.push(value);
+                let slot = self.memory.len() - 1;
+                self.set(o[1], Value::Pointer(Pointer::Local { slot: slot, path: Vec::new() }));
+            },
+            Op::Load => {
+                let value = match self.pointer(o[2]) {
+                    Some(pointer) => self.load(&pointer, shared),
+                    None => self.program.zero(o[0]),
+                };
+                self.set(o[1], value);
+            },
+            Op::Store => {
+                if let Some(pointer) = self.pointer(o[0]) {
+                    let value = self.value(o[1]);
+                    self.store(&pointer, value, shared);
+                }
+            },
+            Op::CopyMemory => {
+                if let (Some(target), Some(source)) = (self.pointer(o[0]), self.pointer(o[1])) {
+                    let value = self.load(&source, shared);
+                    self.store(&target, value, shared);
+                }
+            },
+            Op::AccessChain | Op::InBoundsAccessChain => {
+                if let Some(mut pointer) = self.pointer(o[2]) {
+                    for &index in &o[3..] {
+                        let index = self.value(index).scalar();
+                        pointer = self.element(pointer, index);
+                    }
+                    self.set(o[1], Value::Pointer(pointer));
+                }
+            },
+            Op::ArrayLength => {
+                let length = match self.pointer(o[2]) {
+                    Some(Pointer::Memory(memory)) => {
+                        let member = self.program.memory_element(&memory, o[3]);
+                        self.program.memory_count(&member)
+                    },
+                    _ => 0,
+                };
+                self.set(o[1], Value::Scalar(length));
+            },
+            Op::ImageTexelPointer => {
+                let image = self.pointer(o[2]).map(|pointer| self.load(&pointer, shared));
+                if let Some(Value::Image(image)) = image {
+                    let coordinates = texel_coordinates(self.program.image_type(o[2]), &self.value(o[3]));
+                    self.set(o[1], Value::Pointer(Pointer::Texel { image: image, coordinates: coordinates }));
+                }
+            },
+
+            Op::DPdx | Op::DPdy | Op::Fwidth |
+            Op::DPdxFine | Op::DPdyFine | Op::FwidthFine |
+            Op::DPdxCoarse | Op::DPdyCoarse | Op::FwidthCoarse => {
+                let value = self.value(o[2]);
+                let (dx, dy) = match self.derivatives(value) {
+                    Ok(derivatives) => derivatives,
+                    Err(flow) => return flow,
+                };
+                let result = match inst.op {
+                    Op::DPdx | Op::DPdxFine | Op::DPdxCoarse => dx,
+                    Op::DPdy | Op::DPdyFine | Op::DPdyCoarse => dy,
+                    _ => zip(&dx, &dy, &|a, b| (f32::from_bits(a).abs() + f32::from_bits(b).abs()).to_bits()),
+                };
+                self.set(o[1], result);
+            },
+
+            Op::ImageSampleImplicitLod | Op::ImageSampleExplicitLod |
+            Op::ImageSampleDrefImplicitLod | Op::ImageSampleDrefExplicitLod |
+            Op::ImageSampleProjImplicitLod | Op::ImageSampleProjExplicitLod |
+            Op::ImageSampleProjDrefImplicitLod | Op::ImageSampleProjDrefExplicitLod |
+            Op::ImageQueryLod => return self.sample(inst.op, o),
+            Op::ImageGather | Op::ImageDrefGather => self.gather(inst.op, o),
+            Op::ImageFetch | Op::ImageRead => {
+                if let Some(image) = self.image(o[2]) {
+                    let ty = self.program.image_type(o[2]);
+                    let operands = self.image_operands(operands_from(o, 4));
+                    let mut coordinates = texel_coordinates(ty, &self.value(o[3]));
+                    for i in 0..3 {
+                        coordinates[i] += operands.offset[i];
+                    }
+                    if ty.map(|x| x.dim) == Some(Dim::SubpassData) {
+                        coordinates[0] += self.fragment_position[0];
+                        coordinates[1] += self.fragment_position[1];
+                    }
+                    let texel = self.bindings.read(image, coordinates, operands.lod.unwrap_or(0));
+                    let value = Value::from_components(&texel, self.program.vector_count(o[0]));
+                    self.set(o[1], value);
+                }
+            },
+            Op::ImageWrite => {
+                if let (Some(image), false) = (self.image(o[0]), self.helper) {
+                    let coordinates = texel_coordinates(self.program.image_type(o[0]), &self.value(o[1]));
+                    let mut texel = [0; 4];
+                    for (i, component) in self.value(o[2]).components().into_iter().take(4).enumerate() {
+                        texel[i] = component;
+                    }
+                    self.bindings.write(image, coordinates, texel);
+                }
+            },
+            Op::ImageQuerySize | Op::ImageQuerySizeLod => {
+                if let (Some(image), Some(ty)) = (self.image(o[2]), self.program.image_type(o[2])) {
+                    let lod = if inst.op == Op::ImageQuerySizeLod { self.value(o[3]).scalar() } else { 0 };
+                    let size = self.bindings.size(image, lod);
+                    let mut components = match ty.dim {
+                        Dim::D1 | Dim::Buffer => vec![size[0]],
+                        Dim::D3 => vec![size[0], size[1], size[2]],
+                        _ => vec![size[0], size[1]],
+                    };
+                    if ty.arrayed {
+                        components.push(if ty.dim == Dim::Cube { size[3] / 6 } else { size[3] });
+                    }
+                    let value = Value::from_components(&components, self.program.vector_count(o[0]));
+                    self.set(o[1], value);
+                }
+            },
+            Op::ImageQueryLevels => {
+                let levels = self.image(o[2]).map(|image| self.bindings.levels(image)).unwrap_or(0);
+                self.set(o[1], Value::Scalar(levels));
+            },
+            Op::ImageQuerySamples => self.set(o[1], Value::Scalar(1)),
+
+            Op::AtomicLoad | Op::AtomicStore | Op::AtomicExchange |
+            Op::AtomicCompareExchange | Op::AtomicCompareExchangeWeak |
+            Op::AtomicIIncrement | Op::AtomicIDecrement | Op::AtomicIAdd | Op::AtomicISub |
+            Op::AtomicSMin | Op::AtomicUMin | Op::AtomicSMax | Op::AtomicUMax |
+            Op::AtomicAnd | Op::AtomicOr | Op::AtomicXor => self.atomic(inst.op, o, shared),
+
+            Op::ExtInst if self.program.glsl == Some(o[2]) && o.len() > 4 => match Glsl450::from(o[3]) {
+                Glsl450::Modf | Glsl450::Frexp => {
+                    // The same as the struct-returning forms, with the second member stored.
+                    let structure = if Glsl450::from(o[3]) == Glsl450::Modf { Glsl450::ModfStruct } else { Glsl450::FrexpStruct };
+                    let mut members = match glsl::evaluate(structure, &[self.value(o[4])]) {
+                        Some(Value::Composite(members)) => members,
+                        _ => Vec::new(),
+                    };
+                    let second = members.pop().unwrap_or(Value::Scalar(0));
+                    let first = members.pop().unwrap_or(Value::Scalar(0));
+                    if let Some(pointer) = o.get(5).and_then(|&id| self.pointer(id)) {
+                        self.store(&pointer, second, shared);
+                    }
+                    self.set(o[1], first);
+                },
+                // Every fragment is sampled at its center, wherever the interpolant is asked for.
+                Glsl450::InterpolateAtCentroid | Glsl450::InterpolateAtSample | Glsl450::InterpolateAtOffset => {
+                    let value = match self.pointer(o[4]) {
+                        Some(pointer) => self.load(&pointer, shared),
+                        None => self.program.zero(o[0]),
+                    };
+                    self.set(o[1], value);
+                },
+                _ => self.compute(inst),
+            },
+            _ => self.compute(inst),
+        }
+        Flow::Next
+    }
+
+    /// Runs an instruction `evaluate` handles.
+    fn compute(&mut self, inst: &Inst) {
+        let o = &inst.operands;
+        if o.len() < 2 {
+            return;
+        }
+        let value = {
+            let this = &*self;
+            evaluate(this.program, inst.op, o[0], &o[2..], &|id| this.value(id))
+        };
+        let value = match value {
+            Some(value) => value,
+            None => {
+                warn!("SPIR-V instruction {:?} could not be evaluated", inst.op);
+                self.program.zero(o[0])
+            },
+        };
+        self.set(o[1], value);
+    }
+
+    /// The image descriptor in an image or sampled image ID.
+    fn image(&self, id: u32) -> Option<DescriptorRef> {
+        match self.value(id) {
+            Value::Image(image) | Value::SampledImage(image, _) => Some(image),
+            _ => None,
+        }
+    }
+
+    fn image_operands(&self, operands: &[u32]) -> ImageOperands {
+        let mut result = ImageOperands {
+            bias: 0.0,
+            lod: None,
+            grad: None,
+            offset: [0; 3],
+            offsets: None,
+            min_lod: None,
+        };
+        let mask = match operands.first() {
+            Some(&mask) => mask,
+            None => return result,
+        };
+
+        let mut ids = operands[1..].iter();
+        let mut next = || ids.next().map(|&id| self.value(id)).unwrap_or(Value::Scalar(0));
+        if mask & image_operands::BIAS != 0 {
+            result.bias = f32::from_bits(next().scalar());
+        }
+        if mask & image_operands::LOD != 0 {
+            result.lod = Some(next().scalar());
+        }
+        if mask & image_operands::GRAD != 0 {
+            let dx = next();
+            result.grad = Some((dx, next()));
+        }
+        if mask & image_operands::CONST_OFFSET != 0 {
+            result.offset = offset_of(&next());
+        }
+        if mask & image_operands::OFFSET != 0 {
+            result.offset = offset_of(&next());
+        }
+        if mask & image_operands::CONST_OFFSETS != 0 {
+            result.offsets = Some(next());
+        }
+        if mask & image_operands::SAMPLE != 0 {
+            // Every image has one sample.
+            next();
+        }
+        if mask & image_operands::MIN_LOD != 0 {
+            result.min_lod = Some(f32::from_bits(next().scalar()));
+        }
+        result
+    }
+
+    /// The level of detail for sampling an image with the given coordinate derivatives.
+    fn lod(&self, image: DescriptorRef, ty: Option<ImageType>, coordinates: &[f32], dx: &[f32], dy: &[f32]) -> f32 {
+        let size = self.bindings.size(image, 0);
+        let dim = ty.map(|x| x.dim).unwrap_or(Dim::D2);
+        let (count, sizes, scale) = match dim {
+            Dim::D1 => (1, [size[0] as f32, 0.0, 0.0], 1.0),
+            Dim::D3 => (3, [size[0] as f32, size[1] as f32, size[2] as f32], 1.0),
+            Dim::Cube => {
+                // Cube coordinates are directions; projected onto the face they hit, the face
+                // spans -1 to 1 along the other two axes.
+                let major = coordinates.iter().take(3).fold(0f32, |major, &x| major.max(x.abs()));
+                let scale = if major > 0.0 { 0.5 / major } else { 0.0 };
+                (3, [size[0] as f32, size[0] as f32, size[0] as f32], scale)
+            },
+            Dim::Rect => return 0.0,
+            _ => (2, [size[0] as f32, size[1] as f32, 0.0], 1.0),
+        };
+        let length = |derivative: &[f32]| -> f32 {
+            (0..count)
+                .map(|i| derivative.get(i).cloned().unwrap_or(0.0) * sizes[i] * scale)
+                .map(|x| x * x)
+                .sum::<f32>()
+                .sqrt()
+        };
+        length(dx).max(length(dy)).log2()
+    }
+
+    /// Runs an `OpImageSample*` instruction or `OpImageQueryLod`.
+    fn sample(&mut self, op: Op, o: &[u32]) -> Flow<'a> {
+        let (image, sampler) = match self.value(o[2]) {
+            Value::SampledImage(image, sampler) => (image, sampler),
+            _ => {
+                let zero = self.program.zero(o[0]);
+                self.set(o[1], zero);
+                return Flow::Next;
+            },
+        };
+        let ty = self.program.image_type(o[2]);
+        let projective = match op {
+            Op::ImageSampleProjImplicitLod | Op::ImageSampleProjExplicitLod |
+            Op::ImageSampleProjDrefImplicitLod | Op::ImageSampleProjDrefExplicitLod => true,
+            _ => false,
+        };
+        let dref = match op {
+            Op::ImageSampleDrefImplicitLod | Op::ImageSampleDrefExplicitLod |
+            Op::ImageSampleProjDrefImplicitLod | Op::ImageSampleProjDrefExplicitLod => true,
+            _ => false,
+        };
+        let implicit = match op {
+            Op::ImageSampleImplicitLod | Op::ImageSampleDrefImplicitLod |
+            Op::ImageSampleProjImplicitLod | Op::ImageSampleProjDrefImplicitLod | Op::ImageQueryLod => true,
+            _ => false,
+        };
+
+        let mut coordinates = self.value(o[3]).floats();
+        let mut reference = if dref { Some(f32::from_bits(self.value(o[4]).scalar())) } else { None };
+        if projective {
+            // q follows the coordinates the image's dimensionality needs.
+            let count = ty.map(|x| x.coordinate_count()).unwrap_or(2);
+            let q = coordinates.get(count).cloned().unwrap_or(1.0);
+            coordinates.truncate(count);
+            for x in &mut coordinates {
+                *x /= q;
+            }
+            reference = reference.map(|x| x / q);
+        }
+        let operands = if op == Op::ImageQueryLod {
+            self.image_operands(&[])
+        } else {
+            self.image_operands(operands_from(o, if dref { 5 } else { 4 }))
+        };
+
+        let mut lod = match (operands.lod, operands.grad) {
+            (Some(lod), _) => f32::from_bits(lod),
+            (None, Some((dx, dy))) => self.lod(image, ty, &coordinates, &dx.floats(), &dy.floats()),
+            (None, None) => if implicit {
+                let (dx, dy) = match self.derivatives(from_floats(&coordinates)) {
+                    Ok(derivatives) => derivatives,
+                    Err(flow) => return flow,
+                };
+                self.lod(image, ty, &coordinates, &dx.floats(), &dy.floats()) + operands.bias
+            } else {
+                0.0
+            },
+        };
+        if let Some(min_lod) = operands.min_lod {
+            lod = lod.max(min_lod);
+        }
+
+        if op == Op::ImageQueryLod {
+            let last_level = self.bindings.levels(image).saturating_sub(1) as f32;
+            let value = from_floats(&[lod.max(0.0).min(last_level), lod]);
+            self.set(o[1], value);
+            return Flow::Next;
+        }
+
+        let mut padded = [0.0; 4];
+        for (i, &x) in coordinates.iter().take(4).enumerate() {
+            padded[i] = x;
+        }
+        let texel = self.bindings.sample(image, sampler, padded, lod, reference, operands.offset);
+        let value = Value::from_components(&texel, self.program.vector_count(o[0]));
+        self.set(o[1], value);
+        Flow::Next
+    }
+
+    fn gather(&mut self, op: Op, o: &[u32]) {
+        let (image, sampler) = match self.value(o[2]) {
+            Value::SampledImage(image, sampler) => (image, sampler),
+            _ => return,
+        };
+        let mut coordinates = [0.0; 4];
+        for (i, x) in self.value(o[3]).floats().into_iter().take(4).enumerate() {
+            coordinates[i] = x;
+        }
+        let (component, reference) = if op == Op::ImageDrefGather {
+            (0, Some(f32::from_bits(self.value(o[4]).scalar())))
+        } else {
+            (self.value(o[4]).scalar(), None)
+        };
+        let operands = self.image_operands(operands_from(o, 5));
+
+        let texel = match operands.offsets {
+            // Each of the four texels comes from a gather at its own offset.
+            Some(Value::Composite(ref offsets)) => {
+                let mut texel = [0; 4];
+                for i in 0..4 {
+                    let offset = offsets.get(i).map(offset_of).unwrap_or([0; 3]);
+                    texel[i] = self.bindings.gather(image, sampler, coordinates, component, reference, offset)[i];
+                }
+                texel
+            },
+            _ => self.bindings.gather(image, sampler, coordinates, component, reference, operands.offset),
+        };
+        let value = Value::from_components(&texel, self.program.vector_count(o[0]));
+        self.set(o[1], value);
+    }
+
+    fn atomic(&mut self, op: Op, o: &[u32], shared: &mut [Value]) {
+        let pointer = match self.pointer(if op == Op::AtomicStore { o[0] } else { o[2] }) {
+            Some(pointer) => pointer,
+            None => return,
+        };
+        let original = self.load(&pointer, shared).scalar();
+        let operand = |i: usize| self.value(o[i]).scalar();
+        let new = match op {
+            Op::AtomicLoad => None,
+            Op::AtomicStore => Some(operand(3)),
+            Op::AtomicExchange => Some(operand(5)),
+            Op::AtomicCompareExchange | Op::AtomicCompareExchangeWeak => {
+                if original == operand(7) { Some(operand(6)) } else { None }
+            },
+            Op::AtomicIIncrement => Some(original.wrapping_add(1)),
+            Op::AtomicIDecrement => Some(original.wrapping_sub(1)),
+            Op::AtomicIAdd => Some(original.wrapping_add(operand(5))),
+            Op::AtomicISub => Some(original.wrapping_sub(operand(5))),
+            Op::AtomicSMin => Some(::std::cmp::min(original as i32, operand(5) as i32) as u32),
+            Op::AtomicUMin => Some(::std::cmp::min(original, operand(5))),
+            Op::AtomicSMax => Some(::std::cmp::max(original as i32, operand(5) as i32) as u32),
+            Op::AtomicUMax => Some(::std::cmp::max(original, operand(5))),
+            Op::AtomicAnd => Some(original & operand(5)),
+            Op::AtomicOr => Some(original | operand(5)),
+            Op::AtomicXor => Some(original ^ operand(5)),
+            _ => None,
+        };
+
+        if let Some(new) = new {
+            self.store(&pointer, Value::Scalar(new), shared);
+        }
+        if op != Op::AtomicStore {
+            self.set(o[1], Value::Scalar(original));
+        }
+    }
+}
+
+/// The operands from `index` on, or none if there are not that many.
+fn operands_from(operands: &[u32], index: usize) -> &[u32] {
+    if operands.len() > index { &operands[index..] } else { &[] }
+}
+
+/// Integer texel coordinates as `(x, y, z, layer)`, with the face as the layer for cubes.
+fn texel_coordinates(ty: Option<ImageType>, coordinate: &Value) -> [i32; 4] {
+    let components = coordinate.components();
+    let at = |i: usize| components.get(i).cloned().unwrap_or(0) as i32;
+    let (dim, arrayed) = match ty {
+        Some(ty) => (ty.dim, ty.arrayed),
+        None => (Dim::D3, false),
+    };
+    match dim {
+        Dim::D1 | Dim::Buffer => [at(0), 0, 0, if arrayed { at(1) } else { 0 }],
+        Dim::D3 => [at(0), at(1), at(2), 0],
+        Dim::Cube => [at(0), at(1), 0, at(2)],
+        _ => [at(0), at(1), 0, if arrayed { at(2) } else { 0 }],
+    }
+}
+
+/// Runs the invocations of a compute workgroup together, each up to the next barrier in turn,
+/// until they have all finished.
+pub fn run_workgroup(invocations: &mut [Invocation], shared: &mut [Value]) {
+    loop {
+        let mut barrier = false;
+        for invocation in invocations.iter_mut() {
+            if let Status::Barrier = invocation.run(shared) {
+                barrier = true;
+            }
+        }
+        if !barrier {
+            break;
+        }
+    }
+}
+
+/// Runs the fragments of a 2×2 quad together, in the order (0, 0), (1, 0), (0, 1), (1, 1), giving
+/// them their derivatives whenever they ask. Returns how each one finished.
+///
+/// Derivatives are always the fine ones: differences with the horizontal and vertical neighbours.
+/// Fragments without a neighbour in the quad, or whose neighbour is not asking, take their own
+/// value in its place.
+pub fn run_quad(invocations: &mut [Invocation]) -> Vec<Status> {
+    let mut statuses: Vec<Option<Status>> = vec![None; invocations.len()];
+    loop {
+        let mut values: Vec<Option<Value>> = Vec::with_capacity(invocations.len());
+        for (invocation, status) in invocations.iter_mut().zip(&mut statuses) {
+            if status.is_some() {
+                values.push(None);
+                continue;
+            }
+            match invocation.run(&mut []) {
+                Status::Derivatives(value) => values.push(Some(value)),
+                finished => {
+                    *status = Some(finished);
+                    values.push(None);
+                },
+            }
+        }
+        if values.iter().all(|x| x.is_none()) {
+            break;
+        }
+
+        for (i, invocation) in invocations.iter_mut().enumerate() {
+            if let Some(ref value) = values[i] {
+                let neighbour = |j: usize| values.get(j).and_then(|x| x.as_ref()).unwrap_or(value);
+                let difference = |a: &Value, b: &Value| zip(a, b, &|a, b| (f32::from_bits(a) - f32::from_bits(b)).to_bits());
+                let dx = difference(neighbour(i | 1), neighbour(i & !1));
+                let dy = difference(neighbour(i | 2), neighbour(i & !2));
+                invocation.set_derivatives(dx, dy);
+            }
+        }
+    }
+    statuses.into_iter().map(|x| x.unwrap_or(Status::Finished)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spirv::Assembler;
+
+    use std::cell::UnsafeCell;
+
+    // IDs every test module declares. The IDs of each test's own start at 40.
+    const VOID: u32 = 1;
+    const VOID_FN: u32 = 2;
+    const BOOL: u32 = 3;
+    const UINT: u32 = 4;
+    const INT: u32 = 5;
+    const FLOAT: u32 = 6;
+    const UVEC3: u32 = 7;
+    const VEC4: u32 = 8;
+    const UINT_ARRAY: u32 = 9;
+    const OUT_BLOCK: u32 = 10;
+    const OUT_BLOCK_POINTER: u32 = 11;
+    const OUT_POINTER: u32 = 12;
+    const OUT: u32 = 13;
+    const GLSL: u32 = 14;
+    const MAIN: u32 = 15;
+    const ENTRY: u32 = 16;
+    /// `uint` constants from 0 to 7.
+    const UINTS: u32 = 20;
+
+    /// Starts a module whose only entry point is `main`: `annotations` (its execution modes and
+    /// decorations) come first, then the types and constants every test uses, and the buffer at
+    /// binding 0 that tests write their results to.
+    fn start(model: ExecutionModel, interface: &[u32], annotations: &Fn(&mut Assembler)) -> Assembler {
+        let mut a = Assembler::new(100);
+        a.op(Op::Capability, &[u32::from(Capability::Shader)]);
+        a.op_string(Op::ExtInstImport, &[GLSL], "GLSL.std.450", &[]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[u32::from(model), MAIN], "main", interface);
+        annotations(&mut a);
+        a.op(Op::Decorate, &[UINT_ARRAY, u32::from(Decoration::ArrayStride), 4]);
+        a.op(Op::MemberDecorate, &[OUT_BLOCK, 0, u32::from(Decoration::Offset), 0]);
+        a.op(Op::Decorate, &[OUT_BLOCK, u32::from(Decoration::BufferBlock)]);
+        a.op(Op::Decorate, &[OUT, u32::from(Decoration::DescriptorSet), 0]);
+        a.op(Op::Decorate, &[OUT, u32::from(Decoration::Binding), 0]);
+        a.op(Op::TypeVoid, &[VOID]);
+        a.op(Op::TypeFunction, &[VOID_FN, VOID]);
+        a.op(Op::TypeBool, &[BOOL]);
+        a.op(Op::TypeInt, &[UINT, 32, 0]);
+        a.op(Op::TypeInt, &[INT, 32, 1]);
+        a.op(Op::TypeFloat, &[FLOAT, 32]);
+        a.op(Op::TypeVector, &[UVEC3, UINT, 3]);
+        a.op(Op::TypeVector, &[VEC4, FLOAT, 4]);
+        a.op(Op::TypeRuntimeArray, &[UINT_ARRAY, UINT]);
+        a.op(Op::TypeStruct, &[OUT_BLOCK, UINT_ARRAY]);
+        a.op(Op::TypePointer, &[OUT_BLOCK_POINTER, u32::from(StorageClass::Uniform), OUT_BLOCK]);
+        a.op(Op::TypePointer, &[OUT_POINTER, u32::from(StorageClass::Uniform), UINT]);
+        a.op(Op::Variable, &[OUT_BLOCK_POINTER, OUT, u32::from(StorageClass::Uniform)]);
+        for i in 0..8 {
+            a.op(Op::Constant, &[UINT, UINTS + i, i]);
+        }
+        a
+    }
+
+    fn local_size(a: &mut Assembler, x: u32) {
+        a.op(Op::ExecutionMode, &[MAIN, u32::from(ExecutionMode::LocalSize), x, 1, 1]);
+    }
+
+    fn begin_main(a: &mut Assembler) {
+        a.op(Op::Function, &[VOID, MAIN, 0, VOID_FN]);
+        a.op(Op::Label, &[ENTRY]);
+    }
+
+    fn end_main(a: &mut Assembler) {
+        a.op(Op::Return, &[]);
+        a.op(Op::FunctionEnd, &[]);
+    }
+
+    /// Stores a `uint` to element `index` of the output buffer, through a new pointer `pointer`.
+    fn store(a: &mut Assembler, pointer: u32, index: u32, value: u32) {
+        a.op(Op::AccessChain, &[OUT_POINTER, pointer, OUT, UINTS, UINTS + index]);
+        a.op(Op::Store, &[pointer, value]);
+    }
+
+    /// The output buffer at binding 0, and a 4×4 single-channel storage image at binding 3.
+    /// Sampling and reading other images return what they were asked for, so tests can check how
+    /// instructions called them.
+    struct TestBindings {
+        out: UnsafeCell<Vec<u32>>,
+        texels: UnsafeCell<Vec<u32>>,
+    }
+
+    impl TestBindings {
+        fn new() -> TestBindings {
+            TestBindings {
+                out: UnsafeCell::new(vec![0; 8]),
+                texels: UnsafeCell::new(vec![0; 16]),
+            }
+        }
+
+        fn out(&self) -> Vec<u32> { unsafe { (*self.out.get()).clone() } }
+        fn texels(&self) -> Vec<u32> { unsafe { (*self.texels.get()).clone() } }
+    }
+
+    impl Bindings for TestBindings {
+        fn buffer(&self, descriptor: DescriptorRef) -> Option<(*mut u8, u64)> {
+            if descriptor.binding != 0 {
+                return None;
+            }
+            let out = unsafe { &mut *self.out.get() };
+            Some((out.as_mut_ptr() as *mut u8, out.len() as u64 * 4))
+        }
+
+        fn push_constants(&self) -> &[u8] { &[] }
+
+        fn sample(
+            &self,
+            image: DescriptorRef,
+            sampler: DescriptorRef,
+            coordinates: [f32; 4],
+            lod: f32,
+            _reference: Option<f32>,
+            _offset: [i32; 3],
+        ) -> [u32; 4] {
+            let descriptors = (image.binding * 10 + sampler.binding) as f32;
+            [coordinates[0].to_bits(), coordinates[1].to_bits(), lod.to_bits(), descriptors.to_bits()]
+        }
+
+        fn gather(
+            &self,
+            _image: DescriptorRef,
+            _sampler: DescriptorRef,
+            _coordinates: [f32; 4],
+            _component: u32,
+            _reference: Option<f32>,
+            _offset: [i32; 3],
+        ) -> [u32; 4] {
+            [0; 4]
+        }
+
+        fn read(&self, image: DescriptorRef, coordinates: [i32; 4], lod: u32) -> [u32; 4] {
+            [coordinates[0] as u32, coordinates[1] as u32, lod, image.binding]
+        }
+
+        fn write(&self, image: DescriptorRef, coordinates: [i32; 4], texel: [u32; 4]) {
+            if let Some(pointer) = self.texel_pointer(image, coordinates) {
+                unsafe { *pointer = texel[0] };
+            }
+        }
+
+        fn texel_pointer(&self, _image: DescriptorRef, coordinates: [i32; 4]) -> Option<*mut u32> {
+            let (x, y) = (coordinates[0], coordinates[1]);
+            if x < 0 || x >= 4 || y < 0 || y >= 4 {
+                return None;
+            }
+            let texels = unsafe { &mut *self.texels.get() };
+            Some(&mut texels[(y * 4 + x) as usize] as *mut u32)
+        }
+
+        fn size(&self, _image: DescriptorRef, _lod: u32) -> [u32; 4] { [4, 4, 1, 1] }
+
+        fn levels(&self, _image: DescriptorRef) -> u32 { 3 }
+    }
+
+    /// Runs `main` once, to the end.
+    fn run(code: &[u32], model: ExecutionModel) -> TestBindings {
+        let program = Program::new(code).unwrap();
+        let bindings = TestBindings::new();
+        {
+            let entry_point = program.entry_point("main", model).unwrap();
+            let mut invocation = Invocation::new(&program, entry_point, &bindings);
+            match invocation.run(&mut program.workgroup_memory()) {
+                Status::Finished => {},
+                status => panic!("main stopped with {:?}", status),
+            }
+        }
+        bindings
+    }
+
+    fn floats(x: &[f32]) -> Vec<u32> {
+        x.iter().map(|x| x.to_bits()).collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| local_size(a, 1));
+        a.op(Op::Constant, &[INT, 40, -7i32 as u32]);
+        a.op(Op::Constant, &[INT, 41, 2]);
+        a.op(Op::Constant, &[FLOAT, 42, 1.5f32.to_bits()]);
+        a.op(Op::Constant, &[FLOAT, 43, 16.0f32.to_bits()]);
+        a.op(Op::Constant, &[UINT, 44, 0xF0]);
+        begin_main(&mut a);
+        a.op(Op::IAdd, &[UINT, 50, UINTS + 5, UINTS + 7]);
+        a.op(Op::SDiv, &[INT, 51, 40, 41]);
+        a.op(Op::SMod, &[INT, 52, 40, 41]);
+        a.op(Op::SRem, &[INT, 53, 40, 41]);
+        a.op(Op::FMul, &[FLOAT, 54, 42, 42]);
+        a.op(Op::ExtInst, &[FLOAT, 55, GLSL, u32::from(Glsl450::Sqrt), 43]);
+        a.op(Op::ConvertFToS, &[INT, 56, 55]);
+        a.op(Op::ShiftRightLogical, &[UINT, 57, 44, UINTS + 4]);
+        a.op(Op::BitwiseXor, &[UINT, 58, 57, UINTS + 5]);
+        for (i, &value) in [51, 52, 53, 54, 56].iter().enumerate() {
+            a.op(Op::Bitcast, &[UINT, 60 + i as u32, value]);
+        }
+        store(&mut a, 70, 0, 50);
+        for i in 0..5 {
+            store(&mut a, 71 + i, 1 + i, 60 + i);
+        }
+        store(&mut a, 76, 6, 58);
+        end_main(&mut a);
+
+        let out = run(&a.words(), ExecutionModel::GLCompute).out();
+        assert_eq!(&out[..7], &[12, -3i32 as u32, 1, -1i32 as u32, 2.25f32.to_bits(), 4, 0xA]);
+    }
+
+    #[test]
+    fn branches_and_phis() {
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| local_size(a, 1));
+        a.op(Op::TypeFunction, &[61, UINT, UINT]);
+        begin_main(&mut a);
+        a.op(Op::Branch, &[41]);
+        // Sums the integers below 5 in a loop.
+        a.op(Op::Label, &[41]);
+        a.op(Op::Phi, &[UINT, 50, UINTS, ENTRY, 53, 42]);
+        a.op(Op::Phi, &[UINT, 51, UINTS, ENTRY, 54, 42]);
+        a.op(Op::LoopMerge, &[43, 42, 0]);
+        a.op(Op::ULessThan, &[BOOL, 52, 50, UINTS + 5]);
+        a.op(Op::BranchConditional, &[52, 42, 43]);
+        a.op(Op::Label, &[42]);
+        a.op(Op::IAdd, &[UINT, 53, 50, UINTS + 1]);
+        a.op(Op::IAdd, &[UINT, 54, 51, 50]);
+        a.op(Op::Branch, &[41]);
+        a.op(Op::Label, &[43]);
+        store(&mut a, 90, 0, 51);
+        a.op(Op::FunctionCall, &[UINT, 55, 60, 51]);
+        store(&mut a, 91, 1, 55);
+        a.op(Op::SelectionMerge, &[47, 0]);
+        a.op(Op::Switch, &[51, 46, 3, 46, 10, 45]);
+        a.op(Op::Label, &[45]);
+        store(&mut a, 92, 2, UINTS + 7);
+        a.op(Op::Branch, &[47]);
+        a.op(Op::Label, &[46]);
+        store(&mut a, 93, 2, UINTS + 6);
+        a.op(Op::Branch, &[47]);
+        a.op(Op::Label, &[47]);
+        end_main(&mut a);
+        // Doubles its parameter.
+        a.op(Op::Function, &[UINT, 60, 0, 61]);
+        a.op(Op::FunctionParameter, &[UINT, 62]);
+        a.op(Op::Label, &[63]);
+        a.op(Op::IAdd, &[UINT, 64, 62, 62]);
+        a.op(Op::ReturnValue, &[64]);
+        a.op(Op::FunctionEnd, &[]);
+
+        let out = run(&a.words(), ExecutionModel::GLCompute).out();
+        assert_eq!(&out[..3], &[10, 20, 7]);
+    }
+
+    #[test]
+    fn composites() {
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| local_size(a, 1));
+        a.op(Op::TypeArray, &[40, UINT, UINTS + 4]);
+        a.op(Op::TypePointer, &[41, u32::from(StorageClass::Function), 40]);
+        a.op(Op::TypePointer, &[42, u32::from(StorageClass::Function), UINT]);
+        begin_main(&mut a);
+        a.op(Op::Variable, &[41, 50, u32::from(StorageClass::Function)]);
+        a.op(Op::CompositeConstruct, &[UVEC3, 51, UINTS + 1, UINTS + 2, UINTS + 3]);
+        a.op(Op::CompositeInsert, &[UVEC3, 52, UINTS + 7, 51, 1]);
+        a.op(Op::VectorShuffle, &[UVEC3, 53, 52, 51, 2, 4, 0]);
+        a.op(Op::CompositeExtract, &[UINT, 54, 53, 0]);
+        a.op(Op::CompositeExtract, &[UINT, 55, 52, 1]);
+        a.op(Op::VectorExtractDynamic, &[UINT, 56, 53, UINTS + 1]);
+        a.op(Op::AccessChain, &[42, 57, 50, UINTS + 2]);
+        a.op(Op::Store, &[57, 55]);
+        a.op(Op::Load, &[40, 58, 50]);
+        a.op(Op::CompositeExtract, &[UINT, 59, 58, 2]);
+        a.op(Op::CompositeExtract, &[UINT, 60, 58, 0]);
+        for (i, &value) in [54, 55, 56, 59, 60].iter().enumerate() {
+            store(&mut a, 70 + i as u32, i as u32, value);
+        }
+        end_main(&mut a);
+
+        let out = run(&a.words(), ExecutionModel::GLCompute).out();
+        assert_eq!(&out[..5], &[3, 7, 2, 7, 0]);
+    }
+
+    #[test]
+    fn image_and_sampler_reads() {
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| {
+            local_size(a, 1);
+            a.op(Op::Decorate, &[45, u32::from(Decoration::DescriptorSet), 0]);
+            a.op(Op::Decorate, &[45, u32::from(Decoration::Binding), 1]);
+            a.op(Op::Decorate, &[46, u32::from(Decoration::DescriptorSet), 0]);
+            a.op(Op::Decorate, &[46, u32::from(Decoration::Binding), 2]);
+        });
+        a.op(Op::TypeImage, &[40, FLOAT, u32::from(Dim::D2), 0, 0, 0, 1, u32::from(ImageFormat::Unknown)]);
+        a.op(Op::TypeSampler, &[41]);
+        a.op(Op::TypeSampledImage, &[42, 40]);
+        a.op(Op::TypePointer, &[43, u32::from(StorageClass::UniformConstant), 40]);
+        a.op(Op::TypePointer, &[44, u32::from(StorageClass::UniformConstant), 41]);
+        a.op(Op::Variable, &[43, 45, u32::from(StorageClass::UniformConstant)]);
+        a.op(Op::Variable, &[44, 46, u32::from(StorageClass::UniformConstant)]);
+        a.op(Op::TypeVector, &[47, FLOAT, 2]);
+        a.op(Op::Constant, &[FLOAT, 48, 0.25f32.to_bits()]);
+        a.op(Op::Constant, &[FLOAT, 49, 0.75f32.to_bits()]);
+        a.op(Op::ConstantComposite, &[47, 50, 48, 49]);
+        a.op(Op::Constant, &[FLOAT, 51, 2.0f32.to_bits()]);
+        a.op(Op::TypeVector, &[52, INT, 2]);
+        a.op(Op::Constant, &[INT, 53, 3]);
+        a.op(Op::Constant, &[INT, 54, 2]);
+        a.op(Op::ConstantComposite, &[52, 55, 53, 54]);
+        a.op(Op::Constant, &[INT, 56, 1]);
+        a.op(Op::TypeVector, &[57, UINT, 4]);
+        begin_main(&mut a);
+        a.op(Op::Load, &[40, 60, 45]);
+        a.op(Op::Load, &[41, 61, 46]);
+        a.op(Op::SampledImage, &[42, 62, 60, 61]);
+        a.op(Op::ImageSampleExplicitLod, &[VEC4, 63, 62, 50, image_operands::LOD, 51]);
+        a.op(Op::ImageFetch, &[VEC4, 64, 60, 55, image_operands::LOD, 56]);
+        a.op(Op::Bitcast, &[57, 65, 63]);
+        a.op(Op::Bitcast, &[57, 66, 64]);
+        for i in 0..4 {
+            a.op(Op::CompositeExtract, &[UINT, 70 + i, 65, i]);
+            a.op(Op::CompositeExtract, &[UINT, 74 + i, 66, i]);
+        }
+        for i in 0..8 {
+            store(&mut a, 80 + i, i, 70 + i);
+        }
+        end_main(&mut a);
+
+        let out = run(&a.words(), ExecutionModel::GLCompute).out();
+        // Sampled at the coordinates and LOD given, through image 1 and sampler 2.
+        assert_eq!(&out[..4], &floats(&[0.25, 0.75, 2.0, 12.0])[..]);
+        // Fetched from texel (3, 2) of level 1 of image 1.
+        assert_eq!(&out[4..], &[3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn atomics() {
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| {
+            local_size(a, 1);
+            a.op(Op::Decorate, &[42, u32::from(Decoration::DescriptorSet), 0]);
+            a.op(Op::Decorate, &[42, u32::from(Decoration::Binding), 3]);
+        });
+        a.op(Op::TypeImage, &[40, UINT, u32::from(Dim::D2), 0, 0, 0, 2, u32::from(ImageFormat::R32ui)]);
+        a.op(Op::TypePointer, &[41, u32::from(StorageClass::UniformConstant), 40]);
+        a.op(Op::Variable, &[41, 42, u32::from(StorageClass::UniformConstant)]);
+        a.op(Op::TypePointer, &[43, u32::from(StorageClass::Image), UINT]);
+        a.op(Op::TypeVector, &[44, INT, 2]);
+        a.op(Op::Constant, &[INT, 45, 1]);
+        a.op(Op::Constant, &[INT, 46, 2]);
+        a.op(Op::ConstantComposite, &[44, 47, 45, 46]);
+        let (device, relaxed) = (UINTS + 1, UINTS);
+        begin_main(&mut a);
+        a.op(Op::ImageTexelPointer, &[43, 50, 42, 47, UINTS]);
+        a.op(Op::AtomicIAdd, &[UINT, 51, 50, device, relaxed, UINTS + 5]);
+        a.op(Op::AtomicCompareExchange, &[UINT, 52, 50, device, relaxed, relaxed, UINTS + 7, UINTS + 5]);
+        a.op(Op::AtomicCompareExchange, &[UINT, 53, 50, device, relaxed, relaxed, UINTS + 3, UINTS + 5]);
+        a.op(Op::AccessChain, &[OUT_POINTER, 54, OUT, UINTS, UINTS + 7]);
+        a.op(Op::AtomicIIncrement, &[UINT, 55, 54, device, relaxed]);
+        a.op(Op::AtomicUMax, &[UINT, 56, 54, device, relaxed, UINTS + 6]);
+        for (i, &value) in [51, 52, 53, 55, 56].iter().enumerate() {
+            store(&mut a, 70 + i as u32, i as u32, value);
+        }
+        end_main(&mut a);
+
+        let bindings = run(&a.words(), ExecutionModel::GLCompute);
+        // Each returns what was there before it.
+        assert_eq!(bindings.out(), [0, 5, 7, 0, 1, 0, 0, 6]);
+        assert_eq!(bindings.texels()[2 * 4 + 1], 7);
+    }
+
+    #[test]
+    fn compute_workgroup() {
+        let mut a = start(ExecutionModel::GLCompute, &[40], &|a| {
+            local_size(a, 4);
+            a.op(Op::Decorate, &[40, u32::from(Decoration::BuiltIn), u32::from(BuiltIn::LocalInvocationId)]);
+        });
+        a.op(Op::TypePointer, &[41, u32::from(StorageClass::Input), UVEC3]);
+        a.op(Op::Variable, &[41, 40, u32::from(StorageClass::Input)]);
+        a.op(Op::TypeArray, &[42, UINT, UINTS + 4]);
+        a.op(Op::TypePointer, &[43, u32::from(StorageClass::Workgroup), 42]);
+        a.op(Op::Variable, &[43, 44, u32::from(StorageClass::Workgroup)]);
+        a.op(Op::TypePointer, &[45, u32::from(StorageClass::Workgroup), UINT]);
+        a.op(Op::Variable, &[45, 46, u32::from(StorageClass::Workgroup)]);
+        let workgroup = UINTS + SCOPE_WORKGROUP;
+        begin_main(&mut a);
+        a.op(Op::Load, &[UVEC3, 50, 40]);
+        a.op(Op::CompositeExtract, &[UINT, 51, 50, 0]);
+        // Each invocation writes the square of its index to shared memory and adds its index to
+        // a shared counter, then reads its neighbour's square and the total after the barrier.
+        a.op(Op::IMul, &[UINT, 52, 51, 51]);
+        a.op(Op::AccessChain, &[45, 53, 44, 51]);
+        a.op(Op::Store, &[53, 52]);
+        a.op(Op::AtomicIAdd, &[UINT, 54, 46, workgroup, UINTS, 51]);
+        a.op(Op::ControlBarrier, &[workgroup, workgroup, UINTS]);
+        a.op(Op::IAdd, &[UINT, 55, 51, UINTS + 1]);
+        a.op(Op::UMod, &[UINT, 56, 55, UINTS + 4]);
+        a.op(Op::AccessChain, &[45, 57, 44, 56]);
+        a.op(Op::Load, &[UINT, 58, 57]);
+        a.op(Op::Load, &[UINT, 59, 46]);
+        a.op(Op::AccessChain, &[OUT_POINTER, 60, OUT, UINTS, 51]);
+        a.op(Op::Store, &[60, 58]);
+        a.op(Op::IAdd, &[UINT, 61, 51, UINTS + 4]);
+        a.op(Op::AccessChain, &[OUT_POINTER, 62, OUT, UINTS, 61]);
+        a.op(Op::Store, &[62, 59]);
+        end_main(&mut a);
+
+        let code = a.words();
+        let program = Program::new(&code).unwrap();
+        let entry_point = program.entry_point("main", ExecutionModel::GLCompute).unwrap();
+        assert_eq!(entry_point.local_size, [4, 1, 1]);
+        let bindings = TestBindings::new();
+        {
+            let mut invocations: Vec<Invocation> = (0..4).map(|i| {
+                let mut invocation = Invocation::new(&program, entry_point, &bindings);
+                invocation.set_input(Slot::BuiltIn(BuiltIn::LocalInvocationId), &[i, 0, 0]);
+                invocation
+            }).collect();
+            run_workgroup(&mut invocations, &mut program.workgroup_memory());
+        }
+        assert_eq!(bindings.out(), [1, 4, 9, 0, 6, 6, 6, 6]);
+    }
+
+    #[test]
+    fn vertex_invocation() {
+        let mut a = start(ExecutionModel::Vertex, &[40, 41, 42], &|a| {
+            a.op(Op::Decorate, &[40, u32::from(Decoration::Location), 0]);
+            a.op(Op::Decorate, &[41, u32::from(Decoration::BuiltIn), u32::from(BuiltIn::Position)]);
+            a.op(Op::Decorate, &[42, u32::from(Decoration::Location), 1]);
+        });
+        a.op(Op::TypePointer, &[43, u32::from(StorageClass::Input), VEC4]);
+        a.op(Op::TypePointer, &[44, u32::from(StorageClass::Output), VEC4]);
+        a.op(Op::TypePointer, &[45, u32::from(StorageClass::Output), FLOAT]);
+        a.op(Op::Variable, &[43, 40, u32::from(StorageClass::Input)]);
+        a.op(Op::Variable, &[44, 41, u32::from(StorageClass::Output)]);
+        a.op(Op::Variable, &[45, 42, u32::from(StorageClass::Output)]);
+        a.op(Op::Constant, &[FLOAT, 46, 2.0f32.to_bits()]);
+        begin_main(&mut a);
+        a.op(Op::Load, &[VEC4, 50, 40]);
+        a.op(Op::VectorTimesScalar, &[VEC4, 51, 50, 46]);
+        a.op(Op::Store, &[41, 51]);
+        a.op(Op::CompositeExtract, &[FLOAT, 52, 50, 3]);
+        a.op(Op::FNegate, &[FLOAT, 53, 52]);
+        a.op(Op::Store, &[42, 53]);
+        end_main(&mut a);
+
+        let code = a.words();
+        let program = Program::new(&code).unwrap();
+        let entry_point = program.entry_point("main", ExecutionModel::Vertex).unwrap();
+        assert_eq!(entry_point.inputs().len(), 1);
+        assert_eq!(entry_point.outputs().len(), 2);
+        let bindings = TestBindings::new();
+        let mut invocation = Invocation::new(&program, entry_point, &bindings);
+        invocation.set_input(Slot::Location(0), &floats(&[1.0, 2.0, 3.0, 4.0]));
+        match invocation.run(&mut []) {
+            Status::Finished => {},
+            status => panic!("main stopped with {:?}", status),
+        }
+        assert_eq!(invocation.output(Slot::BuiltIn(BuiltIn::Position)), Some(floats(&[2.0, 4.0, 6.0, 8.0])));
+        assert_eq!(invocation.output(Slot::Location(1)), Some(floats(&[-4.0])));
+    }
+
+    #[test]
+    fn fragment_quad() {
+        let mut a = start(ExecutionModel::Fragment, &[40, 41], &|a| {
+            a.op(Op::ExecutionMode, &[MAIN, u32::from(ExecutionMode::OriginUpperLeft)]);
+            a.op(Op::Decorate, &[40, u32::from(Decoration::Location), 0]);
+            a.op(Op::Decorate, &[41, u32::from(Decoration::Location), 0]);
+        });
+        a.op(Op::TypePointer, &[42, u32::from(StorageClass::Input), FLOAT]);
+        a.op(Op::TypePointer, &[43, u32::from(StorageClass::Output), VEC4]);
+        a.op(Op::Variable, &[42, 40, u32::from(StorageClass::Input)]);
+        a.op(Op::Variable, &[43, 41, u32::from(StorageClass::Output)]);
+        a.op(Op::Constant, &[FLOAT, 44, 1.0f32.to_bits()]);
+        a.op(Op::Constant, &[FLOAT, 45, 10.0f32.to_bits()]);
+        begin_main(&mut a);
+        // Outputs the input with its derivatives, and discards fragments whose input is over 10.
+        a.op(Op::Load, &[FLOAT, 50, 40]);
+        a.op(Op::DPdx, &[FLOAT, 51, 50]);
+        a.op(Op::DPdy, &[FLOAT, 52, 50]);
+        a.op(Op::FOrdGreaterThan, &[BOOL, 53, 50, 45]);
+        a.op(Op::SelectionMerge, &[47, 0]);
+        a.op(Op::BranchConditional, &[53, 46, 47]);
+        a.op(Op::Label, &[46]);
+        a.op(Op::Kill, &[]);
+        a.op(Op::Label, &[47]);
+        a.op(Op::CompositeConstruct, &[VEC4, 54, 50, 51, 52, 44]);
+        a.op(Op::Store, &[41, 54]);
+        end_main(&mut a);
+
+        let code = a.words();
+        let program = Program::new(&code).unwrap();
+        let entry_point = program.entry_point("main", ExecutionModel::Fragment).unwrap();
+        assert!(entry_point.origin_upper_left);
+        let bindings = TestBindings::new();
+        let inputs = [0.0, 1.0, 4.0, 20.0];
+        let mut invocations: Vec<Invocation> = (0..4).map(|i| {
+            let mut invocation = Invocation::new(&program, entry_point, &bindings);
+            invocation.set_fragment(i & 1, i >> 1, false);
+            invocation.set_input(Slot::Location(0), &floats(&inputs[i as usize..i as usize + 1]));
+            invocation
+        }).collect();
+        let statuses = run_quad(&mut invocations);
+
+        let killed: Vec<bool> = statuses.iter().map(|x| match *x { Status::Killed => true, _ => false }).collect();
+        assert_eq!(killed, [false, false, false, true]);
+        assert_eq!(invocations[0].output(Slot::Location(0)), Some(floats(&[0.0, 1.0, 4.0, 1.0])));
+        assert_eq!(invocations[1].output(Slot::Location(0)), Some(floats(&[1.0, 1.0, 19.0, 1.0])));
+        assert_eq!(invocations[2].output(Slot::Location(0)), Some(floats(&[4.0, 16.0, 4.0, 1.0])));
+    }
+}
@@ -0,0 +1,232 @@
+//! SPIR-V modules, as far as backends need to look inside them.
+//!
+//! `vkCreateShaderModule` parses and validates every module into a `SpirvModule` before a backend
+//! sees it, so backends can rely on the checks it makes and ask it about entry points instead of
+//! walking the module themselves. `instructions` splits a module into instructions without
+//! interpreting any operands, for consumers that do need to walk one; `interpret` goes further and
+//! executes them on the host, and `msl` translates them for backends on Metal. `transform` rewrites
+//! them, to apply specialization and to leave backends less to translate.
+
+use icd::{Error, Result};
+
+use std::collections::HashMap;
+
+pub mod spec;
+pub use self::spec::*;
+
+mod module;
+pub use self::module::*;
+
+pub mod interpret;
+mod glsl;
+pub mod msl;
+pub mod transform;
+
+/// The first word of every module.
+pub const MAGIC: u32 = 0x07230203;
+
+/// The number of words before the first instruction: the magic number, version, generator, ID
+/// bound and a reserved word.
+pub const HEADER_WORDS: usize = 5;
+
+/// One instruction of a module.
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction<'a> {
+    pub op: Op,
+    /// The words after the one holding the opcode and word count.
+    pub operands: &'a [u32],
+}
+
+/// Splits a module into its instructions, checking only that the header is there and that every
+/// instruction's word count fits in the module.
+pub fn instructions(code: &[u32]) -> Result<Vec<Instruction>> {
+    if code.len() < HEADER_WORDS || code[0] != MAGIC {
+        error!("shader module is not SPIR-V: it does not start with the magic number");
+        return Err(Error::InvalidShader);
+    }
+
+    let mut instructions = Vec::new();
+    let mut offset = HEADER_WORDS;
+    while offset < code.len() {
+        let word_count = (code[offset] >> 16) as usize;
+        if word_count == 0 || offset + word_count > code.len() {
+            error!("SPIR-V instruction at word {} has a word count of {}, which does not fit in the module", offset, word_count);
+            return Err(Error::InvalidShader);
+        }
+        instructions.push(Instruction {
+            op: Op::from(code[offset] & 0xFFFF),
+            operands: &code[(offset + 1)..(offset + word_count)],
+        });
+        offset += word_count;
+    }
+    Ok(instructions)
+}
+
+/// The ID bound from a module's header: every ID in the module is less than it.
+pub fn id_bound(code: &[u32]) -> u32 {
+    code.get(3).cloned().unwrap_or(0)
+}
+
+/// Decodes a nul-terminated literal string operand, returning it with the number of words it
+/// takes up.
+pub fn literal_string(operands: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, &word) in operands.iter().enumerate() {
+        for shift in 0..4 {
+            let byte = (word >> (shift * 8)) as u8;
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), operands.len())
+}
+
+/// Where an instruction's result ID is among its operands, if it has one: after the result type
+/// for most instructions, and first for those without a result type.
+pub fn result_index(op: Op) -> Option<usize> {
+    match op {
+        Op::Nop | Op::SourceContinued | Op::Source | Op::SourceExtension | Op::Name | Op::MemberName |
+        Op::Line | Op::NoLine | Op::Extension | Op::MemoryModel | Op::EntryPoint | Op::ExecutionMode |
+        Op::ExecutionModeId | Op::Capability | Op::Store | Op::CopyMemory | Op::Decorate |
+        Op::MemberDecorate | Op::DecorateId | Op::GroupDecorate | Op::GroupMemberDecorate |
+        Op::ImageWrite | Op::EmitVertex | Op::EndPrimitive | Op::EmitStreamVertex | Op::EndStreamPrimitive |
+        Op::ControlBarrier | Op::MemoryBarrier | Op::AtomicStore | Op::LoopMerge | Op::SelectionMerge |
+        Op::Branch | Op::BranchConditional | Op::Switch | Op::Kill | Op::Return | Op::ReturnValue |
+        Op::Unreachable | Op::FunctionEnd | Op::ModuleProcessed | Op::Other(_) => None,
+        Op::String | Op::ExtInstImport | Op::DecorationGroup | Op::Label | Op::TypeVoid | Op::TypeBool |
+        Op::TypeInt | Op::TypeFloat | Op::TypeVector | Op::TypeMatrix | Op::TypeImage | Op::TypeSampler |
+        Op::TypeSampledImage | Op::TypeArray | Op::TypeRuntimeArray | Op::TypeStruct | Op::TypePointer |
+        Op::TypeFunction => Some(0),
+        _ => Some(1),
+    }
+}
+
+/// The decorations of an ID or structure member that backends act on.
+#[derive(Clone, Default, Debug)]
+pub struct Decorations {
+    pub builtin: Option<BuiltIn>,
+    pub location: Option<u32>,
+    pub component: Option<u32>,
+    pub set: Option<u32>,
+    pub binding: Option<u32>,
+    pub offset: Option<u32>,
+    pub array_stride: Option<u32>,
+    pub matrix_stride: Option<u32>,
+    pub row_major: bool,
+    pub flat: bool,
+    pub no_perspective: bool,
+    pub block: bool,
+    pub buffer_block: bool,
+    pub spec_id: Option<u32>,
+}
+
+impl Decorations {
+    fn new(raw: &[(Decoration, Vec<u32>)]) -> Decorations {
+        let mut decorations = Decorations::default();
+        for &(decoration, ref operands) in raw {
+            let operand = operands.get(0).cloned();
+            match decoration {
+                Decoration::BuiltIn => decorations.builtin = operand.map(BuiltIn::from),
+                Decoration::Location => decorations.location = operand,
+                Decoration::Component => decorations.component = operand,
+                Decoration::DescriptorSet => decorations.set = operand,
+                Decoration::Binding => decorations.binding = operand,
+                Decoration::Offset => decorations.offset = operand,
+                Decoration::ArrayStride => decorations.array_stride = operand,
+                Decoration::MatrixStride => decorations.matrix_stride = operand,
+                Decoration::RowMajor => decorations.row_major = true,
+                Decoration::ColMajor => decorations.row_major = false,
+                Decoration::Flat => decorations.flat = true,
+                Decoration::NoPerspective => decorations.no_perspective = true,
+                Decoration::Block => decorations.block = true,
+                Decoration::BufferBlock => decorations.buffer_block = true,
+                Decoration::SpecId => decorations.spec_id = operand,
+                _ => {},
+            }
+        }
+        decorations
+    }
+}
+
+/// Collects the decorations of every ID and of every structure member, by structure and member
+/// index, with decoration groups applied.
+pub fn decorations(instructions: &[Instruction]) -> (HashMap<u32, Decorations>, HashMap<(u32, u32), Decorations>) {
+    // Decorations come before everything they decorate, but decoration groups can be applied
+    // before all of a group's decorations are seen, so they are gathered first.
+    let mut raw: HashMap<u32, Vec<(Decoration, Vec<u32>)>> = HashMap::new();
+    let mut raw_members: HashMap<(u32, u32), Vec<(Decoration, Vec<u32>)>> = HashMap::new();
+    for instruction in instructions {
+        let o = instruction.operands;
+        match instruction.op {
+            Op::Decorate | Op::DecorateId if o.len() >= 2 => {
+                raw.entry(o[0]).or_insert_with(Vec::new).push((Decoration::from(o[1]), o[2..].to_vec()));
+            },
+            Op::MemberDecorate if o.len() >= 3 => {
+                raw_members.entry((o[0], o[1])).or_insert_with(Vec::new).push((Decoration::from(o[2]), o[3..].to_vec()));
+            },
+            _ => {},
+        }
+    }
+    for instruction in instructions {
+        let o = instruction.operands;
+        match instruction.op {
+            Op::GroupDecorate if o.len() >= 1 => {
+                let group = raw.get(&o[0]).cloned().unwrap_or_else(Vec::new);
+                for &target in &o[1..] {
+                    raw.entry(target).or_insert_with(Vec::new).extend(group.iter().cloned());
+                }
+            },
+            Op::GroupMemberDecorate if o.len() >= 1 => {
+                let group = raw.get(&o[0]).cloned().unwrap_or_else(Vec::new);
+                for pair in o[1..].chunks(2) {
+                    if pair.len() == 2 {
+                        raw_members.entry((pair[0], pair[1])).or_insert_with(Vec::new).extend(group.iter().cloned());
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    (
+        raw.iter().map(|(&id, raw)| (id, Decorations::new(raw))).collect(),
+        raw_members.iter().map(|(&id, raw)| (id, Decorations::new(raw))).collect(),
+    )
+}
+
+/// Builds modules for tests one instruction at a time, so they need no tools to produce.
+#[cfg(test)]
+pub(crate) struct Assembler {
+    words: Vec<u32>,
+}
+
+#[cfg(test)]
+impl Assembler {
+    /// Starts a SPIR-V 1.0 module whose IDs are all below `bound`.
+    pub fn new(bound: u32) -> Assembler {
+        Assembler { words: vec![MAGIC, 0x00010000, 0, bound, 0] }
+    }
+
+    pub fn op(&mut self, op: Op, operands: &[u32]) -> &mut Assembler {
+        self.words.push((operands.len() as u32 + 1) << 16 | u32::from(op));
+        self.words.extend_from_slice(operands);
+        self
+    }
+
+    /// Appends an instruction with a literal string between its other operands.
+    pub fn op_string(&mut self, op: Op, before: &[u32], string: &str, after: &[u32]) -> &mut Assembler {
+        let mut operands = before.to_vec();
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        for chunk in bytes.chunks(4) {
+            operands.push(chunk.iter().enumerate().fold(0, |word, (i, &byte)| word | (byte as u32) << (i * 8)));
+        }
+        operands.extend_from_slice(after);
+        self.op(op, &operands)
+    }
+
+    pub fn words(&self) -> Vec<u32> {
+        self.words.clone()
+    }
+}
@@ -0,0 +1,1068 @@
+//! Validation of whole modules, and what can be learned about their entry points without running
+//! them.
+//!
+//! `SpirvModule::new` checks what every consumer of a module relies on: the header, that every
+//! instruction fits in the module, that result IDs are unique and in bound, and that entry points
+//! name functions. It does not apply the rest of the SPIR-V validation rules; modules are assumed
+//! to come from a compiler that does.
+//!
+//! Reflection is per entry point. The descriptors and push constants an entry point uses are the
+//! ones its call tree statically refers to through instructions that take pointers, so variables
+//! declared but never touched are left out. Variable pointers are not followed.
+
+use icd::{Error, Result, DescriptorType, PushConstantRange, ShaderStageFlags};
+use icd::{VK_SHADER_STAGE_VERTEX_BIT, VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT, VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT};
+use icd::{VK_SHADER_STAGE_GEOMETRY_BIT, VK_SHADER_STAGE_FRAGMENT_BIT, VK_SHADER_STAGE_COMPUTE_BIT};
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+
+/// A module that has passed validation, with its entry points and specialization constants
+/// reflected.
+#[derive(Clone, Debug)]
+pub struct SpirvModule {
+    code: Vec<u32>,
+    capabilities: Vec<Capability>,
+    entry_points: Vec<EntryPointInfo>,
+    specialization_constants: Vec<SpecializationConstant>,
+}
+
+/// What an entry point needs from the pipeline it is used in.
+#[derive(Clone, Debug)]
+pub struct EntryPointInfo {
+    pub name: String,
+    pub model: ExecutionModel,
+    /// The execution modes declared for the entry point, with their operands. `LocalSizeId` is
+    /// reported as `LocalSize`, with the values of the constants it names.
+    pub execution_modes: Vec<(ExecutionMode, Vec<u32>)>,
+    /// The local size of a compute entry point, with a `WorkgroupSize` built-in taking precedence
+    /// over the execution mode. Sizes given by specialization constants are their defaults.
+    pub workgroup_size: Option<[u32; 3]>,
+    /// Sorted by set and binding.
+    pub descriptors: Vec<DescriptorBinding>,
+    pub push_constants: Option<PushConstantBlock>,
+    /// Input variables with locations, sorted by location. Built-ins are left out.
+    pub inputs: Vec<InterfaceVariable>,
+    /// Output variables with locations, sorted by location. Built-ins are left out.
+    pub outputs: Vec<InterfaceVariable>,
+}
+
+/// A descriptor binding an entry point uses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    /// Uniform and storage buffers are reported without `Dynamic`, which the module cannot tell.
+    pub descriptor_type: DescriptorType,
+    /// The array size of the variable, or 0 for a runtime array.
+    pub count: u32,
+}
+
+/// The bytes of push constants an entry point's block declares.
+#[derive(Clone, Debug)]
+pub struct PushConstantBlock {
+    pub offset: u32,
+    pub size: u32,
+    /// The offset and size of each member.
+    pub members: Vec<(u32, u32)>,
+}
+
+/// The kind of scalar an interface variable or specialization constant is made of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScalarKind {
+    Bool,
+    Int,
+    Uint,
+    Float,
+}
+
+/// An input or output variable of an entry point, as the locations it takes up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InterfaceVariable {
+    pub location: u32,
+    pub component: u32,
+    pub kind: ScalarKind,
+    /// The bit width of each component.
+    pub width: u32,
+    /// The number of components in each location it takes up.
+    pub components: u32,
+    /// The number of consecutive locations it takes up, for arrays, matrices and 64-bit vectors.
+    pub locations: u32,
+}
+
+/// A specialization constant the module declares with a `SpecId`.
+#[derive(Clone, Debug)]
+pub struct SpecializationConstant {
+    /// The constant ID specialization map entries refer to.
+    pub id: u32,
+    /// The ID of the constant within the module.
+    pub result: u32,
+    pub kind: ScalarKind,
+    /// The size in bytes a map entry for it has. Booleans are `VkBool32`s.
+    pub size: u32,
+    /// The default value, low-order word first.
+    pub default: Vec<u32>,
+}
+
+/// A binding of a pipeline layout, as far as `EntryPointInfo::check_layout` is concerned.
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    pub count: u32,
+    pub stages: ShaderStageFlags,
+}
+
+/// The execution model of entry points that can run as `stage`, if there is one.
+pub fn execution_model(stage: ShaderStageFlags) -> Option<ExecutionModel> {
+    if stage == VK_SHADER_STAGE_VERTEX_BIT {
+        Some(ExecutionModel::Vertex)
+    } else if stage == VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT {
+        Some(ExecutionModel::TessellationControl)
+    } else if stage == VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT {
+        Some(ExecutionModel::TessellationEvaluation)
+    } else if stage == VK_SHADER_STAGE_GEOMETRY_BIT {
+        Some(ExecutionModel::Geometry)
+    } else if stage == VK_SHADER_STAGE_FRAGMENT_BIT {
+        Some(ExecutionModel::Fragment)
+    } else if stage == VK_SHADER_STAGE_COMPUTE_BIT {
+        Some(ExecutionModel::GLCompute)
+    } else {
+        None
+    }
+}
+
+impl SpirvModule {
+    pub fn new(code: &[u32]) -> Result<SpirvModule> {
+        if code.len() < HEADER_WORDS {
+            error!("shader module is {} words long, which is too short for a SPIR-V header", code.len());
+            return Err(Error::InvalidShader);
+        }
+        if code[0] == MAGIC.swap_bytes() {
+            error!("shader module is SPIR-V in the wrong byte order");
+            return Err(Error::InvalidShader);
+        }
+        if code[1] & 0xFF0000FF != 0 || code[1] >> 16 != 1 {
+            error!("shader module has SPIR-V version word {:#010x}, which is not a version 1.x", code[1]);
+            return Err(Error::InvalidShader);
+        }
+        let bound = id_bound(code);
+        if bound == 0 {
+            error!("shader module has an ID bound of 0");
+            return Err(Error::InvalidShader);
+        }
+        if code[4] != 0 {
+            error!("shader module has {:#x} in the reserved header word, which must be 0", code[4]);
+            return Err(Error::InvalidShader);
+        }
+
+        let instructions = try!(instructions(code));
+        let (decorations, member_decorations) = decorations(&instructions);
+        let mut module = Reflection {
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            composites: HashMap::new(),
+            decorations: decorations,
+            member_decorations: member_decorations,
+        };
+
+        let mut defined = HashSet::new();
+        let mut capabilities = Vec::new();
+        let mut memory_models = 0;
+        let mut entry_points = Vec::new();
+        let mut execution_modes: Vec<(u32, ExecutionMode, Vec<u32>)> = Vec::new();
+        let mut spec_constants = Vec::new();
+        let mut variables: Vec<(u32, StorageClass, u32)> = Vec::new();
+        let mut functions: HashMap<u32, Uses> = HashMap::new();
+        let mut function = None;
+        for instruction in &instructions {
+            let (op, o) = (instruction.op, instruction.operands);
+            if let Some(index) = result_index(op) {
+                let id = o.get(index).cloned().unwrap_or(0);
+                if id == 0 || id >= bound {
+                    error!("SPIR-V instruction {:?} has a result ID that is missing or out of the module's bound of {}", op, bound);
+                    return Err(Error::InvalidShader);
+                }
+                if !defined.insert(id) {
+                    error!("SPIR-V ID {} is the result of more than one instruction", id);
+                    return Err(Error::InvalidShader);
+                }
+            }
+
+            match op {
+                Op::Capability if o.len() >= 1 => capabilities.push(Capability::from(o[0])),
+                Op::MemoryModel => memory_models += 1,
+                Op::EntryPoint if o.len() >= 3 => {
+                    let (name, words) = literal_string(&o[2..]);
+                    entry_points.push((ExecutionModel::from(o[0]), o[1], name, o[(2 + words)..].to_vec()));
+                },
+                Op::ExecutionMode | Op::ExecutionModeId if o.len() >= 2 => {
+                    execution_modes.push((o[0], ExecutionMode::from(o[1]), o[2..].to_vec()));
+                },
+                Op::TypeBool => { module.types.insert(o[0], Ty::Bool); },
+                Op::TypeInt if o.len() >= 3 => { module.types.insert(o[0], Ty::Int { width: o[1], signed: o[2] != 0 }); },
+                Op::TypeFloat if o.len() >= 2 => { module.types.insert(o[0], Ty::Float { width: o[1] }); },
+                Op::TypeVector if o.len() >= 3 => { module.types.insert(o[0], Ty::Vector { component: o[1], count: o[2] }); },
+                Op::TypeMatrix if o.len() >= 3 => { module.types.insert(o[0], Ty::Matrix { column: o[1], count: o[2] }); },
+                Op::TypeImage if o.len() >= 7 => { module.types.insert(o[0], Ty::Image { dim: Dim::from(o[2]), sampled: o[6] }); },
+                Op::TypeSampler => { module.types.insert(o[0], Ty::Sampler); },
+                Op::TypeSampledImage => { module.types.insert(o[0], Ty::SampledImage); },
+                Op::TypeArray if o.len() >= 3 => { module.types.insert(o[0], Ty::Array { element: o[1], length: o[2] }); },
+                Op::TypeRuntimeArray if o.len() >= 2 => { module.types.insert(o[0], Ty::RuntimeArray { element: o[1] }); },
+                Op::TypeStruct if o.len() >= 1 => { module.types.insert(o[0], Ty::Struct { members: o[1..].to_vec() }); },
+                Op::TypePointer if o.len() >= 3 => {
+                    module.types.insert(o[0], Ty::Pointer { storage: StorageClass::from(o[1]), pointee: o[2] });
+                },
+                Op::Constant | Op::SpecConstant if o.len() >= 3 => {
+                    module.constants.insert(o[1], o[2..].to_vec());
+                    if op == Op::SpecConstant {
+                        spec_constants.push((o[1], o[0]));
+                    }
+                },
+                Op::ConstantTrue | Op::ConstantFalse | Op::SpecConstantTrue | Op::SpecConstantFalse if o.len() >= 2 => {
+                    let value = (op == Op::ConstantTrue || op == Op::SpecConstantTrue) as u32;
+                    module.constants.insert(o[1], vec![value]);
+                    if op == Op::SpecConstantTrue || op == Op::SpecConstantFalse {
+                        spec_constants.push((o[1], o[0]));
+                    }
+                },
+                Op::ConstantNull if o.len() >= 2 => { module.constants.insert(o[1], vec![0, 0]); },
+                Op::ConstantComposite | Op::SpecConstantComposite if o.len() >= 2 => {
+                    module.composites.insert(o[1], o[2..].to_vec());
+                },
+                Op::Variable if o.len() >= 3 && function.is_none() => variables.push((o[1], StorageClass::from(o[2]), o[0])),
+                Op::Function if o.len() >= 2 => {
+                    function = Some(o[1]);
+                    functions.insert(o[1], Uses::default());
+                },
+                Op::FunctionEnd => function = None,
+                _ => if let Some(function) = function {
+                    let uses = functions.get_mut(&function).unwrap();
+                    if op == Op::FunctionCall && o.len() >= 3 {
+                        uses.calls.push(o[2]);
+                    }
+                    uses.pointers.extend(pointer_operands(op, o).iter().cloned());
+                },
+            }
+        }
+
+        if memory_models != 1 {
+            error!("SPIR-V module has {} OpMemoryModel instructions instead of one", memory_models);
+            return Err(Error::InvalidShader);
+        }
+        if entry_points.is_empty() && !capabilities.contains(&Capability::Linkage) {
+            error!("SPIR-V module has no entry points");
+            return Err(Error::InvalidShader);
+        }
+        for (i, &(model, function, ref name, _)) in entry_points.iter().enumerate() {
+            if !functions.contains_key(&function) {
+                error!("SPIR-V entry point {:?} names {}, which is not a function", name, function);
+                return Err(Error::InvalidShader);
+            }
+            if entry_points[..i].iter().any(|x| x.0 == model && x.2 == *name) {
+                error!("SPIR-V module has more than one {:?} entry point named {:?}", model, name);
+                return Err(Error::InvalidShader);
+            }
+        }
+
+        let specialization_constants = spec_constants.iter()
+            .filter_map(|&(result, ty)| {
+                let id = match module.decorations.get(&result).and_then(|x| x.spec_id) {
+                    Some(id) => id,
+                    None => return None,
+                };
+                let (kind, size) = match module.types.get(&ty) {
+                    Some(&Ty::Bool) => (ScalarKind::Bool, 4),
+                    Some(&Ty::Int { width, signed }) => (if signed { ScalarKind::Int } else { ScalarKind::Uint }, width / 8),
+                    Some(&Ty::Float { width }) => (ScalarKind::Float, width / 8),
+                    _ => return None,
+                };
+                Some(SpecializationConstant {
+                    id: id,
+                    result: result,
+                    kind: kind,
+                    size: size,
+                    default: module.constants.get(&result).cloned().unwrap_or_else(Vec::new),
+                })
+            })
+            .collect();
+
+        let workgroup_size = module.decorations.iter()
+            .find(|&(_, decorations)| decorations.builtin == Some(BuiltIn::WorkgroupSize))
+            .and_then(|(id, _)| module.composites.get(id))
+            .map(|constituents| {
+                let mut size = [1; 3];
+                for (size, &constituent) in size.iter_mut().zip(constituents) {
+                    *size = module.constant(constituent);
+                }
+                size
+            });
+
+        let entry_points = entry_points.iter()
+            .map(|&(model, function, ref name, ref interface)| {
+                // Everything the call tree rooted at the entry point's function refers to
+                let mut used = HashSet::new();
+                let mut visited = HashSet::new();
+                let mut pending = vec![function];
+                while let Some(function) = pending.pop() {
+                    if !visited.insert(function) {
+                        continue;
+                    }
+                    if let Some(uses) = functions.get(&function) {
+                        used.extend(uses.pointers.iter().cloned());
+                        pending.extend(uses.calls.iter().cloned());
+                    }
+                }
+
+                let mut info = EntryPointInfo {
+                    name: name.clone(),
+                    model: model,
+                    execution_modes: execution_modes.iter()
+                        .filter(|x| x.0 == function)
+                        .map(|&(_, mode, ref operands)| match mode {
+                            ExecutionMode::LocalSizeId => {
+                                (ExecutionMode::LocalSize, operands.iter().map(|&id| module.constant(id)).collect())
+                            },
+                            _ => (mode, operands.clone()),
+                        })
+                        .collect(),
+                    workgroup_size: None,
+                    descriptors: Vec::new(),
+                    push_constants: None,
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                };
+                if model == ExecutionModel::GLCompute {
+                    let local_size = info.execution_modes.iter()
+                        .find(|x| x.0 == ExecutionMode::LocalSize && x.1.len() >= 3)
+                        .map(|x| [x.1[0], x.1[1], x.1[2]]);
+                    info.workgroup_size = workgroup_size.or(local_size);
+                }
+
+                for &(id, storage, pointer) in &variables {
+                    let pointee = match module.types.get(&pointer) {
+                        Some(&Ty::Pointer { pointee, .. }) => pointee,
+                        _ => continue,
+                    };
+                    match storage {
+                        StorageClass::Input | StorageClass::Output if interface.contains(&id) => {
+                            let variables = module.interface(id, pointee);
+                            if storage == StorageClass::Input {
+                                info.inputs.extend(variables);
+                            } else {
+                                info.outputs.extend(variables);
+                            }
+                        },
+                        StorageClass::PushConstant if used.contains(&id) => info.push_constants = module.push_constants(pointee),
+                        _ if used.contains(&id) => {
+                            let decorations = module.decorations.get(&id);
+                            let set = decorations.and_then(|x| x.set);
+                            let binding = decorations.and_then(|x| x.binding);
+                            if let (Some(set), Some(binding), Some((descriptor_type, count))) = (set, binding, module.descriptor(storage, pointee)) {
+                                info.descriptors.push(DescriptorBinding {
+                                    set: set,
+                                    binding: binding,
+                                    descriptor_type: descriptor_type,
+                                    count: count,
+                                });
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+                info.descriptors.sort_by_key(|x| (x.set, x.binding));
+                info.inputs.sort_by_key(|x| (x.location, x.component));
+                info.outputs.sort_by_key(|x| (x.location, x.component));
+                info
+            })
+            .collect();
+
+        Ok(SpirvModule {
+            code: code.to_vec(),
+            capabilities: capabilities,
+            entry_points: entry_points,
+            specialization_constants: specialization_constants,
+        })
+    }
+
+    pub fn code(&self) -> &[u32] { &self.code }
+
+    /// The SPIR-V version the module declares, as major and minor.
+    pub fn version(&self) -> (u32, u32) { (self.code[1] >> 16, (self.code[1] >> 8) & 0xFF) }
+
+    /// The generator's magic number: which tool made the module.
+    pub fn generator(&self) -> u32 { self.code[2] }
+
+    pub fn id_bound(&self) -> u32 { id_bound(&self.code) }
+
+    pub fn capabilities(&self) -> &[Capability] { &self.capabilities }
+
+    pub fn entry_points(&self) -> &[EntryPointInfo] { &self.entry_points }
+
+    pub fn entry_point(&self, name: &str, model: ExecutionModel) -> Option<&EntryPointInfo> {
+        self.entry_points.iter().find(|x| x.name == name && x.model == model)
+    }
+
+    /// In the order the module declares them.
+    pub fn specialization_constants(&self) -> &[SpecializationConstant] { &self.specialization_constants }
+}
+
+impl EntryPointInfo {
+    /// The stage the entry point runs as.
+    pub fn stage(&self) -> ShaderStageFlags {
+        match self.model {
+            ExecutionModel::Vertex => VK_SHADER_STAGE_VERTEX_BIT,
+            ExecutionModel::TessellationControl => VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT,
+            ExecutionModel::TessellationEvaluation => VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT,
+            ExecutionModel::Geometry => VK_SHADER_STAGE_GEOMETRY_BIT,
+            ExecutionModel::Fragment => VK_SHADER_STAGE_FRAGMENT_BIT,
+            _ => VK_SHADER_STAGE_COMPUTE_BIT,
+        }
+    }
+
+    /// Checks that a pipeline layout has a binding of a matching type, with enough descriptors and
+    /// visible to the entry point's stage, for every descriptor the entry point uses, and that its
+    /// push constant ranges for the stage cover every member of the entry point's push constant
+    /// block.
+    pub fn check_layout(&self, bindings: &[LayoutBinding], push_constant_ranges: &[PushConstantRange]) -> Result<()> {
+        let stage = self.stage();
+        for used in &self.descriptors {
+            let binding = match bindings.iter().find(|x| x.set == used.set && x.binding == used.binding) {
+                Some(binding) => binding,
+                None => {
+                    error!(
+                        "entry point {:?} uses binding {} of set {}, which is not in the pipeline layout",
+                        self.name, used.binding, used.set,
+                    );
+                    return Err(Error::InitializationFailed);
+                },
+            };
+            if !compatible(used.descriptor_type, binding.descriptor_type) {
+                error!(
+                    "entry point {:?} uses binding {} of set {} as {:?}, but the pipeline layout has {:?}",
+                    self.name, used.binding, used.set, used.descriptor_type, binding.descriptor_type,
+                );
+                return Err(Error::InitializationFailed);
+            }
+            if binding.count < used.count.max(1) {
+                error!(
+                    "entry point {:?} uses {} descriptors of binding {} of set {}, but the pipeline layout has {}",
+                    self.name, used.count.max(1), used.binding, used.set, binding.count,
+                );
+                return Err(Error::InitializationFailed);
+            }
+            if !binding.stages.contains(stage) {
+                error!(
+                    "entry point {:?} uses binding {} of set {}, which the pipeline layout does not make visible to {:?}",
+                    self.name, used.binding, used.set, stage,
+                );
+                return Err(Error::InitializationFailed);
+            }
+        }
+
+        if let Some(ref block) = self.push_constants {
+            for &(offset, size) in &block.members {
+                let covered = push_constant_ranges.iter().any(|range| {
+                    range.stage_flags.contains(stage) && range.offset <= offset && offset + size <= range.offset + range.size
+                });
+                if !covered {
+                    error!(
+                        "entry point {:?} uses push constant bytes {} to {}, which no range of the pipeline layout for {:?} covers",
+                        self.name, offset, offset + size, stage,
+                    );
+                    return Err(Error::InitializationFailed);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether a descriptor a module uses as `used` can be bound through a layout binding of type
+/// `layout`.
+fn compatible(used: DescriptorType, layout: DescriptorType) -> bool {
+    match (used, layout) {
+        (DescriptorType::UniformBuffer, DescriptorType::UniformBufferDynamic) |
+        (DescriptorType::StorageBuffer, DescriptorType::StorageBufferDynamic) |
+        (DescriptorType::Sampler, DescriptorType::CombinedImageSampler) |
+        (DescriptorType::SampledImage, DescriptorType::CombinedImageSampler) => true,
+        (used, layout) => used == layout,
+    }
+}
+
+/// What reflection needs of a type. Component, element and member types are IDs, and so is an
+/// array's length.
+#[derive(Clone, Debug)]
+enum Ty {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Image { dim: Dim, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Pointer { storage: StorageClass, pointee: u32 },
+}
+
+/// What a function body refers to.
+#[derive(Default)]
+struct Uses {
+    calls: Vec<u32>,
+    pointers: Vec<u32>,
+}
+
+/// The tables reflection looks types and constants up in.
+struct Reflection {
+    types: HashMap<u32, Ty>,
+    /// The words of scalar constants, with booleans as 0 or 1 and specialization constants at
+    /// their defaults.
+    constants: HashMap<u32, Vec<u32>>,
+    /// The constituents of composite constants.
+    composites: HashMap<u32, Vec<u32>>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+}
+
+impl Reflection {
+    /// The low word of a scalar constant, or 0 if `id` is not one.
+    fn constant(&self, id: u32) -> u32 {
+        self.constants.get(&id).and_then(|x| x.get(0).cloned()).unwrap_or(0)
+    }
+
+    /// The descriptor type and count of a resource variable.
+    fn descriptor(&self, storage: StorageClass, pointee: u32) -> Option<(DescriptorType, u32)> {
+        let mut ty = pointee;
+        let mut count = 1;
+        loop {
+            match self.types.get(&ty) {
+                Some(&Ty::Array { element, length }) => {
+                    count *= self.constant(length);
+                    ty = element;
+                },
+                Some(&Ty::RuntimeArray { element }) => {
+                    count = 0;
+                    ty = element;
+                },
+                _ => break,
+            }
+        }
+
+        let buffer_block = self.decorations.get(&ty).map(|x| x.buffer_block).unwrap_or(false);
+        let descriptor_type = match (storage, self.types.get(&ty)) {
+            (StorageClass::UniformConstant, Some(&Ty::Sampler)) => DescriptorType::Sampler,
+            (StorageClass::UniformConstant, Some(&Ty::SampledImage)) => DescriptorType::CombinedImageSampler,
+            (StorageClass::UniformConstant, Some(&Ty::Image { dim: Dim::Buffer, sampled: 2 })) => DescriptorType::StorageTexelBuffer,
+            (StorageClass::UniformConstant, Some(&Ty::Image { dim: Dim::Buffer, .. })) => DescriptorType::UniformTexelBuffer,
+            (StorageClass::UniformConstant, Some(&Ty::Image { dim: Dim::SubpassData, .. })) => DescriptorType::InputAttachment,
+            (StorageClass::UniformConstant, Some(&Ty::Image { sampled: 2, .. })) => DescriptorType::StorageImage,
+            (StorageClass::UniformConstant, Some(&Ty::Image { .. })) => DescriptorType::SampledImage,
+            (StorageClass::Uniform, Some(&Ty::Struct { .. })) if buffer_block => DescriptorType::StorageBuffer,
+            (StorageClass::Uniform, Some(&Ty::Struct { .. })) => DescriptorType::UniformBuffer,
+            (StorageClass::StorageBuffer, Some(&Ty::Struct { .. })) => DescriptorType::StorageBuffer,
+            _ => return None,
+        };
+        Some((descriptor_type, count))
+    }
+
+    /// The members of a push constant block, by offset and size.
+    fn push_constants(&self, pointee: u32) -> Option<PushConstantBlock> {
+        let members = match self.types.get(&pointee) {
+            Some(&Ty::Struct { ref members }) => members,
+            _ => return None,
+        };
+
+        let mut block = PushConstantBlock { offset: 0, size: 0, members: Vec::new() };
+        let mut end = 0;
+        for (i, &member) in members.iter().enumerate() {
+            let decorations = self.member_decorations.get(&(pointee, i as u32));
+            let offset = decorations.and_then(|x| x.offset).unwrap_or(end);
+            let size = self.size(member, decorations);
+            block.members.push((offset, size));
+            end = end.max(offset + size);
+        }
+        if let Some(offset) = block.members.iter().map(|x| x.0).min() {
+            block.offset = offset;
+            block.size = end - offset;
+        }
+        Some(block)
+    }
+
+    /// The number of bytes a value of a type takes up in memory, up to the end of its last
+    /// component. `member` holds the decorations of the structure member the value is in, for the
+    /// layout of matrices.
+    fn size(&self, ty: u32, member: Option<&Decorations>) -> u32 {
+        match self.types.get(&ty) {
+            Some(&Ty::Bool) => 4,
+            Some(&Ty::Int { width, .. }) | Some(&Ty::Float { width }) => width / 8,
+            Some(&Ty::Vector { component, count }) => count * self.size(component, None),
+            Some(&Ty::Matrix { column, count }) => {
+                let (rows, component) = match self.types.get(&column) {
+                    Some(&Ty::Vector { component, count }) => (count, self.size(component, None)),
+                    _ => (1, self.size(column, None)),
+                };
+                match member.and_then(|x| x.matrix_stride.map(|stride| (stride, x.row_major))) {
+                    Some((stride, true)) => (rows - 1) * stride + count * component,
+                    Some((stride, false)) => (count - 1) * stride + rows * component,
+                    None => count * rows * component,
+                }
+            },
+            Some(&Ty::Array { element, length }) => {
+                let length = self.constant(length);
+                if length == 0 {
+                    return 0;
+                }
+                let element_size = self.size(element, member);
+                let stride = self.decorations.get(&ty).and_then(|x| x.array_stride).unwrap_or(element_size);
+                (length - 1) * stride + element_size
+            },
+            Some(&Ty::Struct { ref members }) => {
+                let mut end = 0;
+                for (i, &member) in members.iter().enumerate() {
+                    let decorations = self.member_decorations.get(&(ty, i as u32));
+                    let offset = decorations.and_then(|x| x.offset).unwrap_or(end);
+                    end = end.max(offset + self.size(member, decorations));
+                }
+                end
+            },
+            _ => 0,
+        }
+    }
+
+    /// The locations an input or output variable takes up. Blocks are split into their members;
+    /// built-ins have no locations.
+    fn interface(&self, id: u32, pointee: u32) -> Vec<InterfaceVariable> {
+        let decorations = self.decorations.get(&id);
+        if decorations.and_then(|x| x.builtin).is_some() {
+            return Vec::new();
+        }
+        let mut location = decorations.and_then(|x| x.location);
+        let component = decorations.and_then(|x| x.component).unwrap_or(0);
+
+        let members = match self.types.get(&pointee) {
+            Some(&Ty::Struct { ref members }) => members,
+            _ => {
+                return match (location, self.shape(pointee)) {
+                    (Some(location), Some(shape)) => vec![shape.at(location, component)],
+                    _ => Vec::new(),
+                };
+            },
+        };
+
+        let mut variables = Vec::new();
+        for (i, &member) in members.iter().enumerate() {
+            let decorations = self.member_decorations.get(&(pointee, i as u32));
+            if decorations.and_then(|x| x.builtin).is_some() {
+                continue;
+            }
+            if let Some(member_location) = decorations.and_then(|x| x.location) {
+                location = Some(member_location);
+            }
+            let component = decorations.and_then(|x| x.component).unwrap_or(0);
+            if let (Some(member_location), Some(shape)) = (location, self.shape(member)) {
+                variables.push(shape.at(member_location, component));
+                location = Some(member_location + shape.locations);
+            }
+        }
+        variables
+    }
+
+    /// The shape of an interface variable of a type, not yet given a location.
+    fn shape(&self, ty: u32) -> Option<InterfaceVariable> {
+        let scalar = |kind, width| InterfaceVariable {
+            location: 0,
+            component: 0,
+            kind: kind,
+            width: width,
+            components: 1,
+            locations: 1,
+        };
+        match self.types.get(&ty) {
+            Some(&Ty::Bool) => Some(scalar(ScalarKind::Bool, 32)),
+            Some(&Ty::Int { width, signed }) => Some(scalar(if signed { ScalarKind::Int } else { ScalarKind::Uint }, width)),
+            Some(&Ty::Float { width }) => Some(scalar(ScalarKind::Float, width)),
+            Some(&Ty::Vector { component, count }) => self.shape(component).map(|mut shape| {
+                shape.components = count;
+                shape.locations = if shape.width == 64 && count > 2 { 2 } else { 1 };
+                shape
+            }),
+            Some(&Ty::Matrix { column, count }) => self.shape(column).map(|mut shape| {
+                shape.locations *= count;
+                shape
+            }),
+            Some(&Ty::Array { element, length }) => self.shape(element).map(|mut shape| {
+                shape.locations *= self.constant(length);
+                shape
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl InterfaceVariable {
+    fn at(mut self, location: u32, component: u32) -> InterfaceVariable {
+        self.location = location;
+        self.component = component;
+        self
+    }
+}
+
+/// The operands of an instruction in a function body that can be pointers to module-scope
+/// variables.
+fn pointer_operands(op: Op, o: &[u32]) -> &[u32] {
+    let (start, end) = match op {
+        Op::Load | Op::AccessChain | Op::InBoundsAccessChain | Op::PtrAccessChain | Op::InBoundsPtrAccessChain |
+        Op::ArrayLength | Op::ImageTexelPointer | Op::CopyObject | Op::AtomicLoad | Op::AtomicExchange |
+        Op::AtomicCompareExchange | Op::AtomicCompareExchangeWeak | Op::AtomicIIncrement | Op::AtomicIDecrement |
+        Op::AtomicIAdd | Op::AtomicISub | Op::AtomicSMin | Op::AtomicUMin | Op::AtomicSMax | Op::AtomicUMax |
+        Op::AtomicAnd | Op::AtomicOr | Op::AtomicXor => (2, 3),
+        Op::Store | Op::AtomicStore => (0, 1),
+        Op::CopyMemory => (0, 2),
+        Op::FunctionCall => (3, o.len()),
+        // Extended instructions, like `InterpolateAt*`, take nothing but IDs
+        Op::ExtInst => (4, o.len()),
+        _ => return &[],
+    };
+    &o[start.min(o.len())..end.min(o.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use icd::ShaderStageFlags;
+    use spirv::Assembler;
+
+    // IDs every test module declares. The IDs of each test's own start at 40.
+    const VOID: u32 = 1;
+    const VOID_FN: u32 = 2;
+    const UINT: u32 = 3;
+    const FLOAT: u32 = 4;
+    const VEC4: u32 = 5;
+    const MAIN: u32 = 6;
+    /// `uint` constants from 0 to 3.
+    const UINTS: u32 = 10;
+
+    /// Starts a module whose only entry point is `main`, with `annotations` (its execution modes
+    /// and decorations) before the types and constants every test uses.
+    fn start(model: ExecutionModel, interface: &[u32], annotations: &Fn(&mut Assembler)) -> Assembler {
+        let mut a = Assembler::new(100);
+        a.op(Op::Capability, &[u32::from(Capability::Shader)]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[u32::from(model), MAIN], "main", interface);
+        annotations(&mut a);
+        a.op(Op::TypeVoid, &[VOID]);
+        a.op(Op::TypeFunction, &[VOID_FN, VOID]);
+        a.op(Op::TypeInt, &[UINT, 32, 0]);
+        a.op(Op::TypeFloat, &[FLOAT, 32]);
+        a.op(Op::TypeVector, &[VEC4, FLOAT, 4]);
+        for i in 0..4 {
+            a.op(Op::Constant, &[UINT, UINTS + i, i]);
+        }
+        a
+    }
+
+    /// Starts a function whose ID is below 50. Its entry block's label is the ID 50 above.
+    fn begin_function(a: &mut Assembler, function: u32) {
+        a.op(Op::Function, &[VOID, function, 0, VOID_FN]);
+        a.op(Op::Label, &[function + 50]);
+    }
+
+    fn end_function(a: &mut Assembler) {
+        a.op(Op::Return, &[]);
+        a.op(Op::FunctionEnd, &[]);
+    }
+
+    /// A compute shader with an empty `main`.
+    fn minimal() -> Vec<u32> {
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| {
+            a.op(Op::ExecutionMode, &[MAIN, u32::from(ExecutionMode::LocalSize), 1, 1, 1]);
+        });
+        begin_function(&mut a, MAIN);
+        end_function(&mut a);
+        a.words()
+    }
+
+    fn binding(set: u32, binding: u32, descriptor_type: DescriptorType, count: u32, stages: ShaderStageFlags) -> LayoutBinding {
+        LayoutBinding {
+            set: set,
+            binding: binding,
+            descriptor_type: descriptor_type,
+            count: count,
+            stages: stages,
+        }
+    }
+
+    fn range(stage_flags: ShaderStageFlags, offset: u32, size: u32) -> PushConstantRange {
+        PushConstantRange { stage_flags: stage_flags, offset: offset, size: size }
+    }
+
+    #[test]
+    fn entry_points_are_reflected_with_their_interfaces() {
+        const INPUT_POINTER: u32 = 40;
+        const OUTPUT_POINTER: u32 = 41;
+        const ATTRIBUTE: u32 = 42;
+        const POSITION: u32 = 43;
+        const COLOR: u32 = 44;
+        const FRAGMENT: u32 = 45;
+        const COMPUTE: u32 = 46;
+
+        let mut a = start(ExecutionModel::Vertex, &[ATTRIBUTE, POSITION], &|a| {
+            a.op_string(Op::EntryPoint, &[u32::from(ExecutionModel::Fragment), FRAGMENT], "main", &[COLOR]);
+            a.op_string(Op::EntryPoint, &[u32::from(ExecutionModel::GLCompute), COMPUTE], "fill", &[]);
+            a.op(Op::ExecutionMode, &[FRAGMENT, u32::from(ExecutionMode::OriginUpperLeft)]);
+            a.op(Op::ExecutionMode, &[COMPUTE, u32::from(ExecutionMode::LocalSize), 8, 4, 1]);
+            a.op(Op::Decorate, &[ATTRIBUTE, u32::from(Decoration::Location), 1]);
+            a.op(Op::Decorate, &[POSITION, u32::from(Decoration::BuiltIn), u32::from(BuiltIn::Position)]);
+            a.op(Op::Decorate, &[COLOR, u32::from(Decoration::Location), 0]);
+        });
+        a.op(Op::TypePointer, &[INPUT_POINTER, u32::from(StorageClass::Input), VEC4]);
+        a.op(Op::TypePointer, &[OUTPUT_POINTER, u32::from(StorageClass::Output), VEC4]);
+        a.op(Op::Variable, &[INPUT_POINTER, ATTRIBUTE, u32::from(StorageClass::Input)]);
+        a.op(Op::Variable, &[OUTPUT_POINTER, POSITION, u32::from(StorageClass::Output)]);
+        a.op(Op::Variable, &[OUTPUT_POINTER, COLOR, u32::from(StorageClass::Output)]);
+        for &function in &[MAIN, FRAGMENT, COMPUTE] {
+            begin_function(&mut a, function);
+            end_function(&mut a);
+        }
+        let module = SpirvModule::new(&a.words()).unwrap();
+
+        assert_eq!(module.version(), (1, 0));
+        assert_eq!(module.capabilities(), [Capability::Shader]);
+        let names: Vec<_> = module.entry_points().iter().map(|x| (x.name.as_str(), x.model)).collect();
+        assert_eq!(names, [
+            ("main", ExecutionModel::Vertex),
+            ("main", ExecutionModel::Fragment),
+            ("fill", ExecutionModel::GLCompute),
+        ]);
+        assert!(module.entry_point("fill", ExecutionModel::Vertex).is_none());
+
+        let vertex = module.entry_point("main", ExecutionModel::Vertex).unwrap();
+        assert_eq!(vertex.stage(), VK_SHADER_STAGE_VERTEX_BIT);
+        assert_eq!(vertex.inputs, [InterfaceVariable {
+            location: 1,
+            component: 0,
+            kind: ScalarKind::Float,
+            width: 32,
+            components: 4,
+            locations: 1,
+        }]);
+        // Built-ins have no locations
+        assert!(vertex.outputs.is_empty());
+        assert_eq!(vertex.workgroup_size, None);
+
+        let fragment = module.entry_point("main", ExecutionModel::Fragment).unwrap();
+        assert_eq!(fragment.stage(), VK_SHADER_STAGE_FRAGMENT_BIT);
+        assert!(fragment.inputs.is_empty());
+        assert_eq!(fragment.outputs.iter().map(|x| x.location).collect::<Vec<_>>(), [0]);
+        assert_eq!(fragment.execution_modes, [(ExecutionMode::OriginUpperLeft, Vec::new())]);
+
+        let compute = module.entry_point("fill", ExecutionModel::GLCompute).unwrap();
+        assert_eq!(compute.stage(), VK_SHADER_STAGE_COMPUTE_BIT);
+        assert_eq!(compute.workgroup_size, Some([8, 4, 1]));
+    }
+
+    #[test]
+    fn descriptors_are_the_ones_the_call_tree_uses() {
+        const UNIFORM_BLOCK: u32 = 40;
+        const UNIFORM_POINTER: u32 = 41;
+        const STORAGE_BLOCK: u32 = 42;
+        const STORAGE_ARRAY: u32 = 43;
+        const STORAGE_POINTER: u32 = 44;
+        const UINT_POINTER: u32 = 45;
+        const UNIFORMS: u32 = 46;
+        const STORAGE: u32 = 47;
+        const UNUSED: u32 = 48;
+        const HELPER: u32 = 49;
+
+        let mut a = start(ExecutionModel::GLCompute, &[], &|a| {
+            a.op(Op::ExecutionMode, &[MAIN, u32::from(ExecutionMode::LocalSize), 1, 1, 1]);
+            a.op(Op::Decorate, &[UNIFORM_BLOCK, u32::from(Decoration::Block)]);
+            a.op(Op::MemberDecorate, &[UNIFORM_BLOCK, 0, u32::from(Decoration::Offset), 0]);
+            a.op(Op::Decorate, &[STORAGE_BLOCK, u32::from(Decoration::BufferBlock)]);
+            a.op(Op::MemberDecorate, &[STORAGE_BLOCK, 0, u32::from(Decoration::Offset), 0]);
+            for &(variable, set, binding) in &[(UNIFORMS, 0, 1), (STORAGE, 1, 0), (UNUSED, 2, 0)] {
+                a.op(Op::Decorate, &[variable, u32::from(Decoration::DescriptorSet), set]);
+                a.op(Op::Decorate, &[variable, u32::from(Decoration::Binding), binding]);
+            }
+        });
+        a.op(Op::TypeStruct, &[UNIFORM_BLOCK, UINT]);
+        a.op(Op::TypePointer, &[UNIFORM_POINTER, u32::from(StorageClass::Uniform), UNIFORM_BLOCK]);
+        a.op(Op::TypeStruct, &[STORAGE_BLOCK, UINT]);
+        a.op(Op::TypeArray, &[STORAGE_ARRAY, STORAGE_BLOCK, UINTS + 3]);
+        a.op(Op::TypePointer, &[STORAGE_POINTER, u32::from(StorageClass::Uniform), STORAGE_ARRAY]);
+        a.op(Op::TypePointer, &[UINT_POINTER, u32::from(StorageClass::Uniform), UINT]);
+        a.op(Op::Variable, &[UNIFORM_POINTER, UNIFORMS, u32::from(StorageClass::Uniform)]);
+        a.op(Op::Variable, &[STORAGE_POINTER, STORAGE, u32::from(StorageClass::Uniform)]);
+        a.op(Op::Variable, &[UNIFORM_POINTER, UNUSED, u32::from(StorageClass::Uniform)]);
+        // `main` reads the uniform buffer and calls a function that writes to the storage buffers
+        begin_function(&mut a, MAIN);
+        a.op(Op::AccessChain, &[UINT_POINTER, 60, UNIFORMS, UINTS]);
+        a.op(Op::Load, &[UINT, 61, 60]);
+        a.op(Op::FunctionCall, &[VOID, 62, HELPER]);
+        end_function(&mut a);
+        begin_function(&mut a, HELPER);
+        a.op(Op::AccessChain, &[UINT_POINTER, 63, STORAGE, UINTS + 2, UINTS]);
+        a.op(Op::Store, &[63, UINTS + 1]);
+        end_function(&mut a);
+        let module = SpirvModule::new(&a.words()).unwrap();
+
+        let compute = module.entry_point("main", ExecutionModel::GLCompute).unwrap();
+        assert_eq!(compute.descriptors, [
+            DescriptorBinding { set: 0, binding: 1, descriptor_type: DescriptorType::UniformBuffer, count: 1 },
+            DescriptorBinding { set: 1, binding: 0, descriptor_type: DescriptorType::StorageBuffer, count: 3 },
+        ]);
+
+        let stages = VK_SHADER_STAGE_COMPUTE_BIT;
+        let layout = [
+            binding(0, 1, DescriptorType::UniformBufferDynamic, 1, stages),
+            binding(1, 0, DescriptorType::StorageBuffer, 4, stages),
+        ];
+        assert!(compute.check_layout(&layout, &[]).is_ok());
+        assert_eq!(compute.check_layout(&layout[..1], &[]), Err(Error::InitializationFailed));
+
+        let wrong_type = [layout[0], binding(1, 0, DescriptorType::UniformBuffer, 3, stages)];
+        assert_eq!(compute.check_layout(&wrong_type, &[]), Err(Error::InitializationFailed));
+        let too_few = [layout[0], binding(1, 0, DescriptorType::StorageBuffer, 2, stages)];
+        assert_eq!(compute.check_layout(&too_few, &[]), Err(Error::InitializationFailed));
+        let wrong_stage = [layout[0], binding(1, 0, DescriptorType::StorageBuffer, 3, VK_SHADER_STAGE_VERTEX_BIT)];
+        assert_eq!(compute.check_layout(&wrong_stage, &[]), Err(Error::InitializationFailed));
+    }
+
+    #[test]
+    fn push_constant_blocks_are_reflected_by_member() {
+        const BLOCK: u32 = 40;
+        const BLOCK_POINTER: u32 = 41;
+        const UINT_POINTER: u32 = 42;
+        const CONSTANTS: u32 = 43;
+
+        let mut a = start(ExecutionModel::Vertex, &[], &|a| {
+            a.op(Op::Decorate, &[BLOCK, u32::from(Decoration::Block)]);
+            a.op(Op::MemberDecorate, &[BLOCK, 0, u32::from(Decoration::Offset), 16]);
+            a.op(Op::MemberDecorate, &[BLOCK, 1, u32::from(Decoration::Offset), 32]);
+        });
+        a.op(Op::TypeStruct, &[BLOCK, VEC4, UINT]);
+        a.op(Op::TypePointer, &[BLOCK_POINTER, u32::from(StorageClass::PushConstant), BLOCK]);
+        a.op(Op::TypePointer, &[UINT_POINTER, u32::from(StorageClass::PushConstant), UINT]);
+        a.op(Op::Variable, &[BLOCK_POINTER, CONSTANTS, u32::from(StorageClass::PushConstant)]);
+        begin_function(&mut a, MAIN);
+        a.op(Op::AccessChain, &[UINT_POINTER, 60, CONSTANTS, UINTS + 1]);
+        a.op(Op::Load, &[UINT, 61, 60]);
+        end_function(&mut a);
+        let module = SpirvModule::new(&a.words()).unwrap();
+
+        let vertex = module.entry_point("main", ExecutionModel::Vertex).unwrap();
+        let block = vertex.push_constants.as_ref().unwrap();
+        assert_eq!((block.offset, block.size), (16, 20));
+        assert_eq!(block.members, [(16, 16), (32, 4)]);
+
+        let stage = VK_SHADER_STAGE_VERTEX_BIT;
+        assert!(vertex.check_layout(&[], &[range(stage, 0, 36)]).is_ok());
+        assert!(vertex.check_layout(&[], &[range(stage, 16, 16), range(stage, 32, 4)]).is_ok());
+        assert_eq!(vertex.check_layout(&[], &[range(stage, 16, 16)]), Err(Error::InitializationFailed));
+        assert_eq!(vertex.check_layout(&[], &[range(VK_SHADER_STAGE_FRAGMENT_BIT, 0, 36)]), Err(Error::InitializationFailed));
+        assert_eq!(vertex.check_layout(&[], &[]), Err(Error::InitializationFailed));
+    }
+
+    #[test]
+    fn malformed_modules_are_rejected() {
+        let valid = minimal();
+        assert!(SpirvModule::new(&valid).is_ok());
+
+        let with = |index: usize, word: u32| {
+            let mut code = valid.clone();
+            code[index] = word;
+            code
+        };
+        let appended = |words: &[u32]| {
+            let mut code = valid.clone();
+            code.extend_from_slice(words);
+            code
+        };
+        let body = |build: &Fn(&mut Assembler)| {
+            let mut a = Assembler::new(100);
+            build(&mut a);
+            a.words()
+        };
+        let entry_point = |a: &mut Assembler, function: u32| {
+            a.op_string(Op::EntryPoint, &[u32::from(ExecutionModel::GLCompute), function], "main", &[]);
+        };
+        let function = |a: &mut Assembler, function: u32| {
+            a.op(Op::TypeVoid, &[VOID]);
+            a.op(Op::TypeFunction, &[VOID_FN, VOID]);
+            begin_function(a, function);
+            end_function(a);
+        };
+
+        let malformed = vec![
+            ("empty", Vec::new()),
+            ("a truncated header", valid[..4].to_vec()),
+            ("the wrong magic number", with(0, 0x12345678)),
+            ("the wrong byte order", with(0, MAGIC.swap_bytes())),
+            ("version 2.0", with(1, 0x00020000)),
+            ("an ID bound of 0", with(3, 0)),
+            ("a reserved header word", with(4, 1)),
+            // Cuts the `OpLabel` before the `OpReturn` and `OpFunctionEnd` in half
+            ("a truncated instruction", valid[..(valid.len() - 3)].to_vec()),
+            ("a word count of 0", appended(&[u32::from(Op::Nop)])),
+            ("an instruction past the end", appended(&[3 << 16 | u32::from(Op::Nop), 0])),
+            ("a result ID of 0", appended(&[2 << 16 | u32::from(Op::TypeBool), 0])),
+            ("a result ID out of bound", appended(&[2 << 16 | u32::from(Op::TypeBool), 100])),
+            ("no result ID", appended(&[1 << 16 | u32::from(Op::TypeBool)])),
+            ("a result ID defined twice", appended(&[2 << 16 | u32::from(Op::TypeBool), UINT])),
+            ("no memory model", body(&|a| {
+                entry_point(a, MAIN);
+                function(a, MAIN);
+            })),
+            ("two memory models", body(&|a| {
+                a.op(Op::MemoryModel, &[0, 1]);
+                a.op(Op::MemoryModel, &[0, 1]);
+                entry_point(a, MAIN);
+                function(a, MAIN);
+            })),
+            ("no entry points", body(&|a| {
+                a.op(Op::MemoryModel, &[0, 1]);
+                function(a, MAIN);
+            })),
+            ("an entry point that is not a function", body(&|a| {
+                a.op(Op::MemoryModel, &[0, 1]);
+                entry_point(a, VOID);
+                function(a, MAIN);
+            })),
+            ("two entry points with the same name and model", body(&|a| {
+                a.op(Op::MemoryModel, &[0, 1]);
+                entry_point(a, MAIN);
+                entry_point(a, MAIN);
+                function(a, MAIN);
+            })),
+        ];
+        for (what, code) in malformed {
+            assert_eq!(SpirvModule::new(&code).err(), Some(Error::InvalidShader), "module with {}", what);
+        }
+
+        // Modules without an entry point are fine for linking
+        let library = body(&|a| {
+            a.op(Op::Capability, &[u32::from(Capability::Linkage)]);
+            a.op(Op::MemoryModel, &[0, 1]);
+            function(a, MAIN);
+        });
+        assert!(SpirvModule::new(&library).is_ok());
+    }
+
+    #[test]
+    fn corrupt_modules_do_not_panic() {
+        // Operands that are missing or refer to the wrong kind of ID must not be trusted
+        let valid = minimal();
+        for index in HEADER_WORDS..valid.len() {
+            for &word in &[0, 1, 2, 4, 0xFFFF, 0x10000, 0xFFFFFFFF, valid[index] & 0xFFFF | 1 << 16] {
+                let mut code = valid.clone();
+                code[index] = word;
+                let _ = SpirvModule::new(&code);
+            }
+            let _ = SpirvModule::new(&valid[..index]);
+        }
+    }
+}
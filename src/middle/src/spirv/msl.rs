@@ -0,0 +1,2425 @@
+//! Translation of entry points to the Metal Shading Language, for backends on Metal, which cannot
+//! consume SPIR-V.
+//!
+//! `translate` writes one entry point, and the functions it calls, as MSL 2.0 source. Descriptors
+//! become arguments of the Metal function at the buffer, texture and sampler indices a
+//! `BindingMap` assigns; the map is plain data, so a backend binds descriptor sets with the same
+//! map it translated with. Push constants take one more buffer index, and vertex buffers go after
+//! everything else in the buffer table. Specialization constants become function constants whose
+//! indices are their constant IDs, so specialization is left to the Metal compiler.
+//!
+//! The output is plain text and depends on nothing but the module, so it can be checked without
+//! Metal. It is not optimized: every value gets a variable, declared at the top of its function,
+//! and structured control flow becomes `if`, `switch` and `for (;;)` with `break` and `continue`.
+//!
+//! Not everything has a Metal equivalent. Translation fails with `FeatureNotPresent` for
+//! tessellation and geometry shaders, types other than 32-bit ones and booleans, arrays of
+//! descriptors, buffer layouts Metal cannot reproduce (such as the 16-byte scalar array stride of
+//! std140), row-major matrices, `OpArrayLength`, image atomics, breaks out of more than one
+//! construct, and recursion. Texel buffers are bound as 2D textures `TEXEL_BUFFER_WIDTH` texels
+//! wide.
+
+use icd::{DescriptorType, Error, Result};
+use super::*;
+
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+/// The width of the 2D textures texel buffers are bound as; texel `i` is at `(i % width, i / width)`.
+pub const TEXEL_BUFFER_WIDTH: u32 = 4096;
+
+/// Where a descriptor set binding goes in Metal's argument tables.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResourceBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    pub count: u32,
+    /// The first of `count` buffer indices, for uniform and storage buffers.
+    pub buffer: Option<u32>,
+    /// The first of `count` texture indices, for images, texel buffers and combined image samplers.
+    pub texture: Option<u32>,
+    /// The first of `count` sampler indices, for samplers and combined image samplers.
+    pub sampler: Option<u32>,
+}
+
+/// The Metal argument table indices of every binding of a pipeline layout.
+///
+/// Every stage uses the same indices, so a descriptor set can be bound to all of a pipeline's
+/// stages alike.
+#[derive(Clone, Debug)]
+pub struct BindingMap {
+    resources: Vec<ResourceBinding>,
+    push_constant_buffer: Option<u32>,
+    buffers: u32,
+}
+
+impl BindingMap {
+    /// Assigns indices to `bindings` in order of set and binding, each table counting up from 0,
+    /// with the push constant buffer after the descriptors if there are push constants.
+    pub fn new(bindings: &[LayoutBinding], push_constants: bool) -> BindingMap {
+        fn take(counter: &mut u32, count: u32) -> Option<u32> {
+            let index = *counter;
+            *counter += count;
+            Some(index)
+        }
+
+        let mut sorted = bindings.to_vec();
+        sorted.sort_by_key(|x| (x.set, x.binding));
+
+        let (mut buffers, mut textures, mut samplers) = (0, 0, 0);
+        let mut resources = Vec::with_capacity(sorted.len());
+        for binding in sorted {
+            let count = binding.count;
+            let (buffer, texture, sampler) = match binding.descriptor_type {
+                DescriptorType::UniformBuffer | DescriptorType::StorageBuffer |
+                DescriptorType::UniformBufferDynamic | DescriptorType::StorageBufferDynamic => (take(&mut buffers, count), None, None),
+                DescriptorType::SampledImage | DescriptorType::StorageImage | DescriptorType::UniformTexelBuffer |
+                DescriptorType::StorageTexelBuffer | DescriptorType::InputAttachment => (None, take(&mut textures, count), None),
+                DescriptorType::CombinedImageSampler => (None, take(&mut textures, count), take(&mut samplers, count)),
+                DescriptorType::Sampler => (None, None, take(&mut samplers, count)),
+                DescriptorType::Other(_) => (None, None, None),
+            };
+            resources.push(ResourceBinding {
+                set: binding.set,
+                binding: binding.binding,
+                descriptor_type: binding.descriptor_type,
+                count: count,
+                buffer: buffer,
+                texture: texture,
+                sampler: sampler,
+            });
+        }
+        let push_constant_buffer = if push_constants { take(&mut buffers, 1) } else { None };
+
+        BindingMap {
+            resources: resources,
+            push_constant_buffer: push_constant_buffer,
+            buffers: buffers,
+        }
+    }
+
+    /// Sorted by set and binding.
+    pub fn resources(&self) -> &[ResourceBinding] { &self.resources }
+
+    pub fn resource(&self, set: u32, binding: u32) -> Option<&ResourceBinding> {
+        self.resources.iter().find(|x| x.set == set && x.binding == binding)
+    }
+
+    pub fn push_constant_buffer(&self) -> Option<u32> { self.push_constant_buffer }
+
+    /// The buffer index of vertex buffer binding 0, with the other vertex buffer bindings
+    /// following it.
+    pub fn vertex_buffer_base(&self) -> u32 { self.buffers }
+}
+
+/// Choices about the output that do not come from the module.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Negates the Y coordinate of vertex positions, since Metal's normalized device coordinates
+    /// have Y pointing up where Vulkan's have it pointing down.
+    pub flip_vertex_y: bool,
+    /// Writes `PointSize` from vertex shaders, which Metal only accepts when drawing points.
+    pub point_size: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            flip_vertex_y: true,
+            point_size: false,
+        }
+    }
+}
+
+/// An entry point as MSL source.
+#[derive(Clone, Debug)]
+pub struct Translation {
+    pub source: String,
+    /// The name of the Metal function, which differs from the entry point's where that is not a
+    /// usable identifier.
+    pub entry_point: String,
+}
+
+/// Translates the entry point `name` of `module` that has execution model `model`.
+pub fn translate(module: &SpirvModule, name: &str, model: ExecutionModel, bindings: &BindingMap, options: &Options) -> Result<Translation> {
+    let translator = try!(Translator::new(module.code(), bindings, options));
+    translator.translate(name, model)
+}
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    error!("cannot translate SPIR-V to MSL: {}", what);
+    Err(Error::FeatureNotPresent)
+}
+
+fn round_up(x: u32, alignment: u32) -> u32 {
+    (x + alignment - 1) / alignment * alignment
+}
+
+/// Helper functions the output may need, in the order they are written.
+const HELPERS: &'static [(&'static str, &'static str)] = &[
+    ("spvFMod", "template<typename T> T spvFMod(T x, T y)\n{\n    return x - y * floor(x / y);\n}\n"),
+    ("spvSMod", "template<typename T> T spvSMod(T x, T y)\n{\n    T r = x % y;\n    return select(r, r + y, (r != T(0)) && ((r < T(0)) != (y < T(0))));\n}\n"),
+    ("spvSSign", "template<typename T> T spvSSign(T x)\n{\n    return select(select(T(0), T(1), x > T(0)), T(-1), x < T(0));\n}\n"),
+    ("spvFindLSB", "template<typename T> T spvFindLSB(T x)\n{\n    return select(ctz(x), T(-1), x == T(0));\n}\n"),
+    ("spvFindUMSB", "template<typename T> T spvFindUMSB(T x)\n{\n    return select(T(31) - clz(x), T(-1), x == T(0));\n}\n"),
+    ("spvFindSMSB", "template<typename T> T spvFindSMSB(T x)\n{\n    T v = select(x, T(-1) - x, x < T(0));\n    return select(T(31) - clz(v), T(-1), v == T(0));\n}\n"),
+    ("spvTexelBufferCoord", "inline uint2 spvTexelBufferCoord(uint i)\n{\n    return uint2(i % 4096, i / 4096);\n}\n"),
+];
+
+#[derive(Clone, Debug)]
+enum Type {
+    Void,
+    Bool,
+    /// Whether it is signed.
+    Int(bool),
+    Float,
+    /// Component type and count.
+    Vector(u32, u32),
+    /// Column type and count.
+    Matrix(u32, u32),
+    /// Element type and length.
+    Array(u32, u32),
+    RuntimeArray(u32),
+    Struct(Vec<u32>),
+    Image(Image),
+    Sampler,
+    SampledImage(u32),
+    Pointer(StorageClass, u32),
+    Function,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Image {
+    sampled_type: u32,
+    dim: Dim,
+    depth: bool,
+    arrayed: bool,
+    multisampled: bool,
+    /// Whether it is a storage image rather than a sampled one.
+    storage: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Merge {
+    Selection(u32),
+    /// The merge block and continue target.
+    Loop(u32, u32),
+}
+
+struct Block {
+    label: u32,
+    body: Vec<(Op, Vec<u32>)>,
+    merge: Option<Merge>,
+    terminator: (Op, Vec<u32>),
+}
+
+struct Function {
+    result_type: u32,
+    parameters: Vec<(u32, u32)>,
+    blocks: Vec<Block>,
+    labels: HashMap<u32, usize>,
+    /// The functions it calls, in order of first call.
+    calls: Vec<u32>,
+    /// The global variables it refers to itself.
+    globals: BTreeSet<u32>,
+}
+
+#[derive(Clone, Copy)]
+enum Construct {
+    Loop { header: u32, merge: u32, continue_target: u32 },
+    Switch { merge: u32 },
+}
+
+/// The state of writing one function.
+struct Body {
+    text: String,
+    indent: usize,
+    /// What IDs are called where that is not their variable: pointers, images and samplers.
+    names: HashMap<u32, String>,
+    /// The sampler half of sampled images, and of pointers to combined image samplers.
+    samplers: HashMap<u32, String>,
+    storage: HashMap<u32, StorageClass>,
+    constructs: Vec<Construct>,
+    /// For entry points, the statements that fill in the output structure before returning it.
+    epilogue: Option<Vec<String>>,
+    returns_value: bool,
+    model: ExecutionModel,
+    frag_coord: bool,
+}
+
+impl Body {
+    fn line<S: AsRef<str>>(&mut self, line: S) {
+        for _ in 0..self.indent {
+            self.text.push_str("    ");
+        }
+        self.text.push_str(line.as_ref());
+        self.text.push('\n');
+    }
+
+    fn open(&mut self, line: String) {
+        if !line.is_empty() {
+            self.line(line);
+        }
+        self.line("{");
+        self.indent += 1;
+    }
+
+    fn close(&mut self) {
+        self.indent -= 1;
+        self.line("}");
+    }
+}
+
+struct Translator<'a> {
+    bindings: &'a BindingMap,
+    options: &'a Options,
+    glsl: Option<u32>,
+    entry_points: Vec<(ExecutionModel, u32, String)>,
+    execution_modes: Vec<(u32, ExecutionMode)>,
+    types: HashMap<u32, Type>,
+    type_names: HashMap<u32, String>,
+    /// The size and alignment of declared structures, as Metal lays them out.
+    struct_layouts: HashMap<u32, (u32, u32)>,
+    result_types: HashMap<u32, u32>,
+    /// How constants are written, inline or by the name of a program scope constant.
+    constants: HashMap<u32, String>,
+    /// The first word of scalar constants that are not specialization constants.
+    values: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    /// Structures and program scope constants, in module order.
+    declarations: Vec<String>,
+    globals: HashSet<u32>,
+    initializers: HashMap<u32, u32>,
+    functions: HashMap<u32, Function>,
+    subpass: bool,
+    helpers: RefCell<BTreeSet<&'static str>>,
+}
+
+impl<'a> Translator<'a> {
+    fn new(code: &[u32], bindings: &'a BindingMap, options: &'a Options) -> Result<Translator<'a>> {
+        let instructions = try!(instructions(code));
+        let (decorations, member_decorations) = decorations(&instructions);
+        let mut translator = Translator {
+            bindings: bindings,
+            options: options,
+            glsl: None,
+            entry_points: Vec::new(),
+            execution_modes: Vec::new(),
+            types: HashMap::new(),
+            type_names: HashMap::new(),
+            struct_layouts: HashMap::new(),
+            result_types: HashMap::new(),
+            constants: HashMap::new(),
+            values: HashMap::new(),
+            decorations: decorations,
+            member_decorations: member_decorations,
+            declarations: Vec::new(),
+            globals: HashSet::new(),
+            initializers: HashMap::new(),
+            functions: HashMap::new(),
+            subpass: false,
+            helpers: RefCell::new(BTreeSet::new()),
+        };
+
+        let mut function: Option<(u32, Function)> = None;
+        let mut block: Option<(u32, Vec<(Op, Vec<u32>)>, Option<Merge>)> = None;
+        for instruction in &instructions {
+            let op = instruction.op;
+            let o = instruction.operands;
+            if let Some(1) = result_index(op) {
+                if o.len() >= 2 {
+                    translator.result_types.insert(o[1], o[0]);
+                }
+            }
+
+            if let Some((_, ref mut f)) = function {
+                match op {
+                    Op::FunctionParameter if o.len() >= 2 => f.parameters.push((o[1], o[0])),
+                    Op::Label if o.len() >= 1 => block = Some((o[0], Vec::new(), None)),
+                    Op::SelectionMerge if o.len() >= 1 => {
+                        if let Some((_, _, ref mut merge)) = block {
+                            *merge = Some(Merge::Selection(o[0]));
+                        }
+                    },
+                    Op::LoopMerge if o.len() >= 2 => {
+                        if let Some((_, _, ref mut merge)) = block {
+                            *merge = Some(Merge::Loop(o[0], o[1]));
+                        }
+                    },
+                    Op::Branch | Op::BranchConditional | Op::Switch | Op::Kill | Op::Return |
+                    Op::ReturnValue | Op::Unreachable => {
+                        if let Some((label, body, merge)) = block.take() {
+                            f.labels.insert(label, f.blocks.len());
+                            f.blocks.push(Block {
+                                label: label,
+                                body: body,
+                                merge: merge,
+                                terminator: (op, o.to_vec()),
+                            });
+                        }
+                    },
+                    Op::FunctionEnd => {},
+                    _ => {
+                        if op == Op::FunctionCall && o.len() >= 3 && !f.calls.contains(&o[2]) {
+                            f.calls.push(o[2]);
+                        }
+                        for word in o {
+                            if translator.globals.contains(word) {
+                                f.globals.insert(*word);
+                            }
+                        }
+                        if let Some((_, ref mut body, _)) = block {
+                            body.push((op, o.to_vec()));
+                        }
+                    },
+                }
+            } else {
+                try!(translator.declare(op, o));
+            }
+
+            match op {
+                Op::Function if o.len() >= 2 => {
+                    function = Some((o[1], Function {
+                        result_type: o[0],
+                        parameters: Vec::new(),
+                        blocks: Vec::new(),
+                        labels: HashMap::new(),
+                        calls: Vec::new(),
+                        globals: BTreeSet::new(),
+                    }));
+                },
+                Op::FunctionEnd => {
+                    if let Some((id, f)) = function.take() {
+                        translator.functions.insert(id, f);
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(translator)
+    }
+
+    /// Takes in an instruction from outside any function.
+    fn declare(&mut self, op: Op, o: &[u32]) -> Result<()> {
+        match op {
+            Op::Capability if o.len() >= 1 => match Capability::from(o[0]) {
+                Capability::Matrix | Capability::Shader | Capability::ImageGatherExtended |
+                Capability::StorageImageMultisample | Capability::ImageCubeArray | Capability::ImageRect |
+                Capability::SampledRect | Capability::InputAttachment | Capability::Sampled1D |
+                Capability::Image1D | Capability::SampledCubeArray | Capability::SampledBuffer |
+                Capability::ImageBuffer | Capability::StorageImageExtendedFormats | Capability::ImageQuery |
+                Capability::DerivativeControl | Capability::StorageImageReadWithoutFormat |
+                Capability::StorageImageWriteWithoutFormat => {},
+                capability => return unsupported(&format!("capability {:?}", capability)),
+            },
+            Op::ExtInstImport if o.len() >= 2 => {
+                let (name, _) = literal_string(&o[1..]);
+                if name == "GLSL.std.450" {
+                    self.glsl = Some(o[0]);
+                } else {
+                    return unsupported(&format!("extended instruction set {}", name));
+                }
+            },
+            Op::EntryPoint if o.len() >= 3 => {
+                let (name, _) = literal_string(&o[2..]);
+                self.entry_points.push((ExecutionModel::from(o[0]), o[1], name));
+            },
+            Op::ExecutionMode if o.len() >= 2 => self.execution_modes.push((o[0], ExecutionMode::from(o[1]))),
+            Op::TypeVoid | Op::TypeBool | Op::TypeInt | Op::TypeFloat | Op::TypeVector | Op::TypeMatrix |
+            Op::TypeImage | Op::TypeSampler | Op::TypeSampledImage | Op::TypeArray | Op::TypeRuntimeArray |
+            Op::TypeStruct | Op::TypePointer | Op::TypeFunction if o.len() >= 1 => try!(self.declare_type(op, o)),
+            Op::ConstantTrue | Op::ConstantFalse if o.len() >= 2 => {
+                self.constants.insert(o[1], (op == Op::ConstantTrue).to_string());
+            },
+            Op::Constant if o.len() >= 3 => {
+                let literal = try!(self.literal(o[0], o[2]));
+                self.constants.insert(o[1], literal);
+                self.values.insert(o[1], o[2]);
+            },
+            Op::ConstantNull | Op::Undef if o.len() >= 2 => {
+                let name = try!(self.type_name(o[0]));
+                self.constants.insert(o[1], format!("{}{{}}", name));
+            },
+            Op::ConstantComposite | Op::SpecConstantComposite if o.len() >= 2 => {
+                let value = try!(self.construct(o[0], &o[2..], &|id| self.value(None, id)));
+                try!(self.declare_constant(o[0], o[1], value));
+            },
+            Op::SpecConstantTrue | Op::SpecConstantFalse | Op::SpecConstant if o.len() >= 2 => {
+                let default = if op == Op::SpecConstant {
+                    try!(self.literal(o[0], o.get(2).cloned().unwrap_or(0)))
+                } else {
+                    (op == Op::SpecConstantTrue).to_string()
+                };
+                let spec_id = self.decorations.get(&o[1]).and_then(|x| x.spec_id);
+                match spec_id {
+                    Some(spec_id) => {
+                        let name = try!(self.type_name(o[0]));
+                        self.declarations.push(format!(
+                            "constant {0} _{1}_tmp [[function_constant({2})]];\nconstant {0} _{1} = is_function_constant_defined(_{1}_tmp) ? _{1}_tmp : {3};\n",
+                            name, o[1], spec_id, default,
+                        ));
+                        self.constants.insert(o[1], format!("_{}", o[1]));
+                    },
+                    None => try!(self.declare_constant(o[0], o[1], default)),
+                }
+            },
+            Op::SpecConstantOp if o.len() >= 3 => {
+                let value = match try!(self.expression(Op::from(o[2]), o[0], &o[3..], &|id| self.value(None, id))) {
+                    Some(value) => value,
+                    None => return unsupported(&format!("OpSpecConstantOp with {:?}", Op::from(o[2]))),
+                };
+                try!(self.declare_constant(o[0], o[1], value));
+            },
+            Op::Variable if o.len() >= 3 => {
+                self.globals.insert(o[1]);
+                if let Some(&initializer) = o.get(3) {
+                    self.initializers.insert(o[1], initializer);
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn declare_constant(&mut self, ty: u32, id: u32, value: String) -> Result<()> {
+        let name = try!(self.type_name(ty));
+        self.declarations.push(format!("constant {} _{} = {};\n", name, id, value));
+        self.constants.insert(id, format!("_{}", id));
+        Ok(())
+    }
+
+    fn declare_type(&mut self, op: Op, o: &[u32]) -> Result<()> {
+        let at = |i: usize| o.get(i).cloned().unwrap_or(0);
+        let id = o[0];
+        let (ty, name) = match op {
+            Op::TypeVoid => (Type::Void, "void".to_owned()),
+            Op::TypeBool => (Type::Bool, "bool".to_owned()),
+            Op::TypeInt => {
+                if at(1) != 32 {
+                    return unsupported(&format!("{}-bit integers", at(1)));
+                }
+                (Type::Int(at(2) != 0), if at(2) != 0 { "int" } else { "uint" }.to_owned())
+            },
+            Op::TypeFloat => {
+                if at(1) != 32 {
+                    return unsupported(&format!("{}-bit floats", at(1)));
+                }
+                (Type::Float, "float".to_owned())
+            },
+            Op::TypeVector => (Type::Vector(at(1), at(2)), format!("{}{}", try!(self.type_name(at(1))), at(2))),
+            Op::TypeMatrix => {
+                let rows = match self.types.get(&at(1)) {
+                    Some(&Type::Vector(_, rows)) => rows,
+                    _ => return unsupported("matrices of anything but float vectors"),
+                };
+                (Type::Matrix(at(1), at(2)), format!("float{}x{}", at(2), rows))
+            },
+            Op::TypeImage => {
+                let image = Image {
+                    sampled_type: at(1),
+                    dim: Dim::from(at(2)),
+                    depth: at(3) == 1,
+                    arrayed: at(4) != 0,
+                    multisampled: at(5) != 0,
+                    storage: at(6) == 2,
+                };
+                if image.dim == Dim::SubpassData {
+                    self.subpass = true;
+                }
+                let name = try!(self.texture_type(&image));
+                (Type::Image(image), name)
+            },
+            Op::TypeSampler => (Type::Sampler, "sampler".to_owned()),
+            Op::TypeSampledImage => (Type::SampledImage(at(1)), try!(self.type_name(at(1)))),
+            Op::TypeArray => {
+                let length = match self.values.get(&at(2)) {
+                    Some(&length) => length,
+                    None => return unsupported("arrays sized by specialization constants"),
+                };
+                (Type::Array(at(1), length), format!("array<{}, {}>", try!(self.type_name(at(1))), length))
+            },
+            Op::TypeRuntimeArray => (Type::RuntimeArray(at(1)), try!(self.type_name(at(1)))),
+            Op::TypeStruct => {
+                try!(self.declare_struct(id, &o[1..]));
+                (Type::Struct(o[1..].to_vec()), format!("_{}", id))
+            },
+            Op::TypePointer => (Type::Pointer(StorageClass::from(at(1)), at(2)), try!(self.type_name(at(2)))),
+            _ => (Type::Function, String::new()),
+        };
+        self.types.insert(id, ty);
+        self.type_names.insert(id, name);
+        Ok(())
+    }
+
+    fn texture_type(&self, image: &Image) -> Result<String> {
+        let (array, ms) = (if image.arrayed { "_array" } else { "" }, if image.multisampled { "_ms" } else { "" });
+        if image.depth {
+            return match image.dim {
+                Dim::D2 => Ok(format!("depth2d{}{}<float>", ms, array)),
+                Dim::Cube => Ok(format!("depthcube{}<float>", array)),
+                dim => unsupported(&format!("{:?} depth images", dim)),
+            };
+        }
+        let base = match image.dim {
+            Dim::D1 => "texture1d",
+            Dim::D2 | Dim::Rect | Dim::SubpassData | Dim::Buffer => "texture2d",
+            Dim::D3 => "texture3d",
+            Dim::Cube => "texturecube",
+            dim => return unsupported(&format!("{:?} images", dim)),
+        };
+        let component = match self.types.get(&image.sampled_type) {
+            Some(&Type::Float) => "float",
+            Some(&Type::Int(true)) => "int",
+            Some(&Type::Int(false)) => "uint",
+            _ => return unsupported("images of anything but 32-bit scalars"),
+        };
+        let access = if image.storage { ", access::read_write" } else { "" };
+        Ok(format!("{}{}{}<{}{}>", base, ms, array, component, access))
+    }
+
+    /// Writes a structure, placing members where their `Offset`s say if they have them.
+    ///
+    /// Three-component vectors take 16 bytes in Metal, so those with less room are packed.
+    fn declare_struct(&mut self, id: u32, members: &[u32]) -> Result<()> {
+        let offsets: Vec<Option<u32>> = (0..(members.len() + 1))
+            .map(|i| self.member_decorations.get(&(id, i as u32)).and_then(|x| x.offset))
+            .collect();
+        let explicit = offsets.iter().any(|x| x.is_some());
+        let mut text = format!("struct _{}\n{{\n", id);
+        let (mut cursor, mut alignment) = (0, 1);
+        for (i, &member) in members.iter().enumerate() {
+            let decorations = self.member_decorations.get(&(id, i as u32)).cloned().unwrap_or_default();
+            let mut name = try!(self.type_name(member));
+            let (mut size, mut member_alignment) = try!(self.layout(member));
+            if explicit {
+                let offset = match offsets[i] {
+                    Some(offset) => offset,
+                    None => return unsupported(&format!("member {} of structure {} has no offset", i, id)),
+                };
+                if decorations.row_major {
+                    return unsupported("row-major matrices");
+                }
+                try!(self.check_strides(member, decorations.matrix_stride));
+                let room = offsets[i + 1].map_or(16, |next| next.saturating_sub(offset));
+                if let Some(&Type::Vector(_, 3)) = self.types.get(&member) {
+                    if offset % 16 != 0 || room < 16 {
+                        name = format!("packed_{}", name);
+                        size = 12;
+                        member_alignment = 4;
+                    }
+                }
+                if offset < cursor || offset % member_alignment != 0 {
+                    return unsupported(&format!("member {} of structure {} is at offset {}, where Metal cannot place a {}", i, id, offset, name));
+                }
+                if offset > cursor {
+                    let _ = writeln!(text, "    char _m{}_pad[{}];", i, offset - cursor);
+                }
+                cursor = offset + size;
+            } else {
+                cursor = round_up(cursor, member_alignment) + size;
+            }
+            alignment = cmp::max(alignment, member_alignment);
+            match self.types.get(&member) {
+                Some(&Type::RuntimeArray(element)) => {
+                    let _ = writeln!(text, "    {} m{}[1];", try!(self.type_name(element)), i);
+                },
+                _ => {
+                    let _ = writeln!(text, "    {} m{};", name, i);
+                },
+            }
+        }
+        text.push_str("};\n");
+        self.struct_layouts.insert(id, (round_up(cursor, alignment), alignment));
+        self.declarations.push(text);
+        Ok(())
+    }
+
+    /// The size and alignment of a type in Metal.
+    fn layout(&self, ty: u32) -> Result<(u32, u32)> {
+        match self.types.get(&ty) {
+            Some(&Type::Bool) => Ok((1, 1)),
+            Some(&Type::Int(_)) | Some(&Type::Float) => Ok((4, 4)),
+            Some(&Type::Vector(component, count)) => {
+                let (size, _) = try!(self.layout(component));
+                let size = size * if count == 3 { 4 } else { count };
+                Ok((size, size))
+            },
+            Some(&Type::Matrix(column, count)) => {
+                let (size, alignment) = try!(self.layout(column));
+                Ok((size * count, alignment))
+            },
+            Some(&Type::Array(element, length)) => {
+                let (size, alignment) = try!(self.layout(element));
+                Ok((size * length, alignment))
+            },
+            Some(&Type::RuntimeArray(element)) => {
+                let (_, alignment) = try!(self.layout(element));
+                Ok((0, alignment))
+            },
+            Some(&Type::Struct(_)) => Ok(self.struct_layouts.get(&ty).cloned().unwrap_or((0, 1))),
+            _ => unsupported(&format!("type {} in a structure", ty)),
+        }
+    }
+
+    /// Checks that the strides a buffer gives arrays and matrices are the ones Metal uses.
+    fn check_strides(&self, ty: u32, matrix_stride: Option<u32>) -> Result<()> {
+        match self.types.get(&ty) {
+            Some(&Type::Matrix(column, _)) => {
+                let (size, _) = try!(self.layout(column));
+                match matrix_stride {
+                    Some(stride) if stride != size => unsupported(&format!("a matrix stride of {} where Metal uses {}", stride, size)),
+                    _ => Ok(()),
+                }
+            },
+            Some(&Type::Array(element, _)) | Some(&Type::RuntimeArray(element)) => {
+                let (size, _) = try!(self.layout(element));
+                match self.decorations.get(&ty).and_then(|x| x.array_stride) {
+                    Some(stride) if stride != size => unsupported(&format!("an array stride of {} where Metal uses {}", stride, size)),
+                    _ => self.check_strides(element, matrix_stride),
+                }
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn type_name(&self, ty: u32) -> Result<String> {
+        match self.type_names.get(&ty) {
+            Some(name) => Ok(name.clone()),
+            None => unsupported(&format!("ID {} is used as a type before one is declared", ty)),
+        }
+    }
+
+    /// The integer type with the shape of `ty` and the given signedness.
+    fn integer_type(&self, ty: u32, signed: bool) -> String {
+        let scalar = if signed { "int" } else { "uint" };
+        match self.types.get(&ty) {
+            Some(&Type::Vector(_, count)) => format!("{}{}", scalar, count),
+            _ => scalar.to_owned(),
+        }
+    }
+
+    fn is_integer(&self, ty: u32) -> bool {
+        match self.types.get(&ty) {
+            Some(&Type::Int(_)) => true,
+            Some(&Type::Vector(component, _)) => self.is_integer(component),
+            _ => false,
+        }
+    }
+
+    fn components(&self, ty: u32) -> u32 {
+        match self.types.get(&ty) {
+            Some(&Type::Vector(_, count)) => count,
+            _ => 1,
+        }
+    }
+
+    fn type_of(&self, id: u32) -> u32 {
+        self.result_types.get(&id).cloned().unwrap_or(0)
+    }
+
+    /// Whether values of a type are kept in variables, rather than named by what they come from.
+    fn declared(&self, ty: u32) -> bool {
+        match self.types.get(&ty) {
+            Some(&Type::Void) | Some(&Type::Pointer(..)) | Some(&Type::Image(_)) | Some(&Type::Sampler) |
+            Some(&Type::SampledImage(_)) | None => false,
+            _ => true,
+        }
+    }
+
+    fn is_handle(&self, ty: u32) -> bool {
+        match self.types.get(&ty) {
+            Some(&Type::Image(_)) | Some(&Type::Sampler) | Some(&Type::SampledImage(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn literal(&self, ty: u32, word: u32) -> Result<String> {
+        match self.types.get(&ty) {
+            Some(&Type::Int(true)) => {
+                let value = word as i32;
+                Ok(if value == i32::min_value() {
+                    "(-2147483647 - 1)".to_owned()
+                } else if value < 0 {
+                    format!("({})", value)
+                } else {
+                    value.to_string()
+                })
+            },
+            Some(&Type::Int(false)) => Ok(format!("{}u", word)),
+            Some(&Type::Float) => {
+                let value = f32::from_bits(word);
+                Ok(if !value.is_finite() {
+                    format!("as_type<float>(0x{:08x}u)", word)
+                } else if value < 0.0 {
+                    format!("({:?}f)", value)
+                } else {
+                    format!("{:?}f", value)
+                })
+            },
+            Some(&Type::Bool) => Ok((word != 0).to_string()),
+            _ => unsupported(&format!("constants of type {}", ty)),
+        }
+    }
+
+    fn value(&self, body: Option<&Body>, id: u32) -> String {
+        if let Some(constant) = self.constants.get(&id) {
+            return constant.clone();
+        }
+        if let Some(name) = body.and_then(|x| x.names.get(&id)) {
+            return name.clone();
+        }
+        format!("_{}", id)
+    }
+
+    /// `id` as a value of type `ty`, converting integers whose signedness differs from it.
+    fn convert(&self, id: u32, ty: u32, value: &Fn(u32) -> String) -> String {
+        let from = self.type_of(id);
+        if from != ty && self.is_integer(from) && self.is_integer(ty) {
+            format!("{}({})", self.type_names.get(&ty).map_or("", |x| &x[..]), value(id))
+        } else {
+            value(id)
+        }
+    }
+
+    fn construct(&self, ty: u32, constituents: &[u32], value: &Fn(u32) -> String) -> Result<String> {
+        let name = try!(self.type_name(ty));
+        let values: Vec<String> = constituents.iter().map(|&x| value(x)).collect();
+        Ok(match self.types.get(&ty) {
+            Some(&Type::Vector(..)) | Some(&Type::Matrix(..)) => format!("{}({})", name, values.join(", ")),
+            _ => format!("{}{{ {} }}", name, values.join(", ")),
+        })
+    }
+
+    /// The accessor for member or element `index` of a value of type `ty`, and the type it has.
+    /// `literal` is the index when it is known, as it must be for structures.
+    fn member(&self, ty: u32, index: String, literal: Option<u32>) -> Result<(String, u32)> {
+        match (self.types.get(&ty), literal) {
+            (Some(&Type::Struct(ref members)), Some(literal)) if (literal as usize) < members.len() => {
+                Ok((format!(".m{}", literal), members[literal as usize]))
+            },
+            (Some(&Type::Vector(component, _)), Some(literal)) if literal < 4 => {
+                Ok((format!(".{}", &"xyzw"[(literal as usize)..(literal as usize + 1)]), component))
+            },
+            (Some(&Type::Vector(element, _)), _) | (Some(&Type::Matrix(element, _)), _) |
+            (Some(&Type::Array(element, _)), _) | (Some(&Type::RuntimeArray(element)), _) => {
+                Ok((format!("[{}]", index), element))
+            },
+            _ => unsupported(&format!("indexing into type {}", ty)),
+        }
+    }
+
+    fn mark(&self, helper: &'static str) -> &'static str {
+        self.helpers.borrow_mut().insert(helper);
+        helper
+    }
+
+    /// The value of an instruction that only computes a value from its operands, or `None` for
+    /// any other instruction. `a` holds the operands after the result ID.
+    fn expression(&self, op: Op, ty: u32, a: &[u32], value: &Fn(u32) -> String) -> Result<Option<String>> {
+        let at = |i: usize| a.get(i).cloned().unwrap_or(0);
+        let arg = |i: usize| value(at(i));
+        let int = |i: usize| self.convert(at(i), ty, value);
+        let name = try!(self.type_name(ty));
+        let unsigned = self.integer_type(ty, false);
+        let signed = self.integer_type(ty, true);
+        let operand_unsigned = self.integer_type(self.type_of(at(0)), false);
+        let operand_signed = self.integer_type(self.type_of(at(0)), true);
+
+        let infix = |operator: &str| format!("({} {} {})", arg(0), operator, arg(1));
+        let integer_infix = |operator: &str| format!("({} {} {})", int(0), operator, int(1));
+        let cast_infix = |cast: &str, operator: &str| format!("{}({}({}) {} {}({}))", name, cast, arg(0), operator, cast, arg(1));
+        let compare = |cast: &str, operator: &str| format!("({}({}) {} {}({}))", cast, arg(0), operator, cast, arg(1));
+        let call = |function: &str, count: usize| {
+            let args: Vec<String> = (0..count).map(|i| arg(i)).collect();
+            format!("{}({})", function, args.join(", "))
+        };
+
+        Ok(Some(match op {
+            Op::IAdd => integer_infix("+"),
+            Op::ISub => integer_infix("-"),
+            Op::IMul => integer_infix("*"),
+            Op::FAdd => infix("+"),
+            Op::FSub => infix("-"),
+            Op::FMul | Op::VectorTimesScalar | Op::MatrixTimesScalar | Op::VectorTimesMatrix |
+            Op::MatrixTimesVector | Op::MatrixTimesMatrix => infix("*"),
+            Op::FDiv => infix("/"),
+            Op::UDiv => cast_infix(&unsigned, "/"),
+            Op::SDiv => cast_infix(&signed, "/"),
+            Op::UMod => cast_infix(&unsigned, "%"),
+            Op::SRem => cast_infix(&signed, "%"),
+            Op::SMod => format!("{}({}({}({}), {}({})))", name, self.mark("spvSMod"), signed, arg(0), signed, arg(1)),
+            Op::FRem => call("fmod", 2),
+            Op::FMod => call(self.mark("spvFMod"), 2),
+            Op::OuterProduct => {
+                let columns: Vec<String> = (0..self.components(self.type_of(at(1))))
+                    .map(|i| format!("{} * {}.{}", arg(0), arg(1), &"xyzw"[(i as usize)..(i as usize + 1)]))
+                    .collect();
+                format!("{}({})", name, columns.join(", "))
+            },
+            Op::Dot => call("dot", 2),
+            Op::Transpose => call("transpose", 1),
+            Op::SNegate => format!("(-{})", int(0)),
+            Op::FNegate => format!("(-{})", arg(0)),
+            Op::Not => format!("(~{})", int(0)),
+            Op::LogicalNot => format!("(!{})", arg(0)),
+            Op::LogicalOr => infix("||"),
+            Op::LogicalAnd => infix("&&"),
+            Op::LogicalEqual => infix("=="),
+            Op::LogicalNotEqual => infix("!="),
+            Op::Select => {
+                if self.components(self.type_of(at(0))) > 1 {
+                    format!("select({}, {}, {})", arg(2), arg(1), arg(0))
+                } else {
+                    format!("({} ? {} : {})", arg(0), arg(1), arg(2))
+                }
+            },
+            Op::IEqual => compare(&operand_unsigned, "=="),
+            Op::INotEqual => compare(&operand_unsigned, "!="),
+            Op::UGreaterThan => compare(&operand_unsigned, ">"),
+            Op::SGreaterThan => compare(&operand_signed, ">"),
+            Op::UGreaterThanEqual => compare(&operand_unsigned, ">="),
+            Op::SGreaterThanEqual => compare(&operand_signed, ">="),
+            Op::ULessThan => compare(&operand_unsigned, "<"),
+            Op::SLessThan => compare(&operand_signed, "<"),
+            Op::ULessThanEqual => compare(&operand_unsigned, "<="),
+            Op::SLessThanEqual => compare(&operand_signed, "<="),
+            Op::FOrdEqual | Op::FUnordEqual => infix("=="),
+            Op::FOrdNotEqual | Op::FUnordNotEqual => infix("!="),
+            Op::FOrdLessThan | Op::FUnordLessThan => infix("<"),
+            Op::FOrdGreaterThan | Op::FUnordGreaterThan => infix(">"),
+            Op::FOrdLessThanEqual | Op::FUnordLessThanEqual => infix("<="),
+            Op::FOrdGreaterThanEqual | Op::FUnordGreaterThanEqual => infix(">="),
+            Op::ShiftLeftLogical => format!("({} << {})", int(0), int(1)),
+            Op::ShiftRightLogical => cast_infix(&unsigned, ">>"),
+            Op::ShiftRightArithmetic => cast_infix(&signed, ">>"),
+            Op::BitwiseOr => integer_infix("|"),
+            Op::BitwiseXor => integer_infix("^"),
+            Op::BitwiseAnd => integer_infix("&"),
+            Op::Any => call("any", 1),
+            Op::All => call("all", 1),
+            Op::IsNan => call("isnan", 1),
+            Op::IsInf => call("isinf", 1),
+            Op::IsFinite => call("isfinite", 1),
+            Op::IsNormal => call("isnormal", 1),
+            Op::SignBitSet => call("signbit", 1),
+            Op::ConvertFToU | Op::ConvertFToS | Op::UConvert | Op::SConvert | Op::FConvert => format!("{}({})", name, arg(0)),
+            Op::ConvertSToF => format!("{}({}({}))", name, operand_signed, arg(0)),
+            Op::ConvertUToF => format!("{}({}({}))", name, operand_unsigned, arg(0)),
+            Op::Bitcast => format!("as_type<{}>({})", name, arg(0)),
+            Op::QuantizeToF16 => {
+                let half = match self.components(ty) {
+                    1 => "half".to_owned(),
+                    count => format!("half{}", count),
+                };
+                format!("{}({}({}))", name, half, arg(0))
+            },
+            Op::CompositeConstruct => try!(self.construct(ty, a, value)),
+            Op::CompositeExtract => {
+                let mut text = arg(0);
+                let mut current = self.type_of(at(0));
+                for &index in &a[cmp::min(1, a.len())..] {
+                    let (accessor, next) = try!(self.member(current, index.to_string(), Some(index)));
+                    text.push_str(&accessor);
+                    current = next;
+                }
+                text
+            },
+            Op::VectorShuffle => {
+                let first = self.components(self.type_of(at(0)));
+                let selected: Vec<(usize, usize)> = a[cmp::min(2, a.len())..].iter()
+                    .map(|&x| if x == 0xFFFFFFFF { (0, 0) } else if x < first { (0, x as usize) } else { (1, (x - first) as usize) })
+                    .collect();
+                let swizzle = |selected: &[(usize, usize)]| selected.iter().map(|&(_, i)| &"xyzw"[i..(i + 1)]).collect::<String>();
+                if selected.iter().all(|&(source, i)| source == 0 && i < 4) && self.components(self.type_of(at(0))) > 1 {
+                    format!("{}.{}", arg(0), swizzle(&selected))
+                } else if selected.iter().all(|&(source, i)| source == 1 && i < 4) && self.components(self.type_of(at(1))) > 1 {
+                    format!("{}.{}", arg(1), swizzle(&selected))
+                } else {
+                    let components: Vec<String> = selected.iter().map(|&(source, i)| {
+                        if self.components(self.type_of(at(source))) > 1 {
+                            format!("{}.{}", arg(source), &"xyzw"[i..(i + 1)])
+                        } else {
+                            arg(source)
+                        }
+                    }).collect();
+                    format!("{}({})", name, components.join(", "))
+                }
+            },
+            Op::VectorExtractDynamic => format!("{}[{}]", arg(0), arg(1)),
+            Op::CopyObject => arg(0),
+            Op::DPdx | Op::DPdxFine | Op::DPdxCoarse => call("dfdx", 1),
+            Op::DPdy | Op::DPdyFine | Op::DPdyCoarse => call("dfdy", 1),
+            Op::Fwidth | Op::FwidthFine | Op::FwidthCoarse => call("fwidth", 1),
+            Op::BitFieldInsert => format!("insert_bits({}, {}, uint({}), uint({}))", int(0), int(1), arg(2), arg(3)),
+            Op::BitFieldSExtract | Op::BitFieldUExtract => format!("extract_bits({}, uint({}), uint({}))", int(0), arg(1), arg(2)),
+            Op::BitReverse => format!("reverse_bits({})", int(0)),
+            Op::BitCount => format!("{}(popcount({}))", name, arg(0)),
+            Op::ExtInst if Some(at(0)) == self.glsl => return self.glsl_instruction(Glsl450::from(at(1)), ty, &a[cmp::min(2, a.len())..], value).map(Some),
+            _ => return Ok(None),
+        }))
+    }
+
+    fn glsl_instruction(&self, instruction: Glsl450, ty: u32, a: &[u32], value: &Fn(u32) -> String) -> Result<String> {
+        let at = |i: usize| a.get(i).cloned().unwrap_or(0);
+        let arg = |i: usize| value(at(i));
+        let name = try!(self.type_name(ty));
+        let call = |function: &str| {
+            let args: Vec<String> = (0..a.len()).map(|i| arg(i)).collect();
+            format!("{}({})", function, args.join(", "))
+        };
+        // Integer instructions work on their operands as signed or unsigned whatever their
+        // types, and Metal needs the result converted back.
+        let integer = |function: &str, signed: bool| {
+            let cast = self.integer_type(self.type_of(at(0)), signed);
+            let args: Vec<String> = (0..a.len()).map(|i| format!("{}({})", cast, arg(i))).collect();
+            format!("{}({}({}))", name, function, args.join(", "))
+        };
+        let scalar = self.components(self.type_of(at(0))) == 1;
+
+        Ok(match instruction {
+            Glsl450::Round => call("round"),
+            Glsl450::RoundEven => call("rint"),
+            Glsl450::Trunc => call("trunc"),
+            Glsl450::FAbs => call("abs"),
+            Glsl450::SAbs => integer("abs", true),
+            Glsl450::FSign => call("sign"),
+            Glsl450::SSign => integer(self.mark("spvSSign"), true),
+            Glsl450::Floor => call("floor"),
+            Glsl450::Ceil => call("ceil"),
+            Glsl450::Fract => call("fract"),
+            Glsl450::Radians => format!("({} * 0.01745329251994329577f)", arg(0)),
+            Glsl450::Degrees => format!("({} * 57.2957795130823208768f)", arg(0)),
+            Glsl450::Sin => call("sin"),
+            Glsl450::Cos => call("cos"),
+            Glsl450::Tan => call("tan"),
+            Glsl450::Asin => call("asin"),
+            Glsl450::Acos => call("acos"),
+            Glsl450::Atan => call("atan"),
+            Glsl450::Sinh => call("sinh"),
+            Glsl450::Cosh => call("cosh"),
+            Glsl450::Tanh => call("tanh"),
+            Glsl450::Asinh => call("asinh"),
+            Glsl450::Acosh => call("acosh"),
+            Glsl450::Atanh => call("atanh"),
+            Glsl450::Atan2 => call("atan2"),
+            Glsl450::Pow => call("pow"),
+            Glsl450::Exp => call("exp"),
+            Glsl450::Log => call("log"),
+            Glsl450::Exp2 => call("exp2"),
+            Glsl450::Log2 => call("log2"),
+            Glsl450::Sqrt => call("sqrt"),
+            Glsl450::InverseSqrt => call("rsqrt"),
+            Glsl450::Determinant => call("determinant"),
+            Glsl450::Modf => call("modf"),
+            Glsl450::FMin | Glsl450::NMin => call("fmin"),
+            Glsl450::FMax | Glsl450::NMax => call("fmax"),
+            Glsl450::FClamp | Glsl450::NClamp => call("clamp"),
+            Glsl450::UMin => integer("min", false),
+            Glsl450::SMin => integer("min", true),
+            Glsl450::UMax => integer("max", false),
+            Glsl450::SMax => integer("max", true),
+            Glsl450::UClamp => integer("clamp", false),
+            Glsl450::SClamp => integer("clamp", true),
+            Glsl450::FMix => call("mix"),
+            Glsl450::Step => call("step"),
+            Glsl450::SmoothStep => call("smoothstep"),
+            Glsl450::Fma => call("fma"),
+            Glsl450::Frexp => call("frexp"),
+            Glsl450::Ldexp => call("ldexp"),
+            Glsl450::PackSnorm4x8 => call("pack_float_to_snorm4x8"),
+            Glsl450::PackUnorm4x8 => call("pack_float_to_unorm4x8"),
+            Glsl450::PackSnorm2x16 => call("pack_float_to_snorm2x16"),
+            Glsl450::PackUnorm2x16 => call("pack_float_to_unorm2x16"),
+            Glsl450::PackHalf2x16 => format!("as_type<uint>(half2({}))", arg(0)),
+            Glsl450::UnpackSnorm4x8 => call("unpack_snorm4x8_to_float"),
+            Glsl450::UnpackUnorm4x8 => call("unpack_unorm4x8_to_float"),
+            Glsl450::UnpackSnorm2x16 => call("unpack_snorm2x16_to_float"),
+            Glsl450::UnpackUnorm2x16 => call("unpack_unorm2x16_to_float"),
+            Glsl450::UnpackHalf2x16 => format!("float2(as_type<half2>({}))", arg(0)),
+            Glsl450::Length if scalar => call("abs"),
+            Glsl450::Length => call("length"),
+            Glsl450::Distance if scalar => format!("abs({} - {})", arg(0), arg(1)),
+            Glsl450::Distance => call("distance"),
+            Glsl450::Normalize if scalar => call("sign"),
+            Glsl450::Normalize => call("normalize"),
+            Glsl450::Cross => call("cross"),
+            Glsl450::FaceForward => call("faceforward"),
+            Glsl450::Reflect => call("reflect"),
+            Glsl450::Refract => call("refract"),
+            Glsl450::FindILsb => integer(self.mark("spvFindLSB"), false),
+            Glsl450::FindSMsb => integer(self.mark("spvFindSMSB"), true),
+            Glsl450::FindUMsb => integer(self.mark("spvFindUMSB"), false),
+            instruction => return unsupported(&format!("GLSL.std.450 {:?}", instruction)),
+        })
+    }
+
+    fn translate(&self, name: &str, model: ExecutionModel) -> Result<Translation> {
+        let function = match self.entry_points.iter().find(|x| x.0 == model && x.2 == name) {
+            Some(&(_, function, _)) => function,
+            None => {
+                error!("SPIR-V module has no {:?} entry point named {:?}", model, name);
+                return Err(Error::InitializationFailed);
+            },
+        };
+        match model {
+            ExecutionModel::Vertex | ExecutionModel::Fragment | ExecutionModel::GLCompute => {},
+            model => return unsupported(&format!("{:?} shaders", model)),
+        }
+
+        // Callees come before their callers, as Metal needs them declared first.
+        let mut order = Vec::new();
+        try!(self.call_order(function, &mut Vec::new(), &mut order));
+        let mut globals: HashMap<u32, BTreeSet<u32>> = HashMap::new();
+        for &id in &order {
+            let f = &self.functions[&id];
+            let mut used = f.globals.clone();
+            for callee in &f.calls {
+                used.extend(globals[callee].iter().cloned());
+            }
+            globals.insert(id, used);
+        }
+
+        let entry_point = identifier(name);
+        let mut text = "#include <metal_stdlib>\n#include <simd/simd.h>\n\nusing namespace metal;\n\n".to_owned();
+        let mut functions = Vec::new();
+        for &id in &order {
+            if id != function {
+                let globals: Vec<u32> = globals[&id].iter().cloned().collect();
+                functions.push(try!(self.function(id, &globals, model)));
+            }
+        }
+        let globals: Vec<u32> = globals[&function].iter().cloned().collect();
+        let (interface, entry) = try!(self.entry(function, &entry_point, model, &globals));
+
+        let helpers = self.helpers.borrow();
+        for &(helper, definition) in HELPERS {
+            if helpers.contains(helper) {
+                text.push_str(definition);
+                text.push('\n');
+            }
+        }
+        for declaration in self.declarations.iter().chain(interface.iter()).chain(functions.iter()) {
+            text.push_str(declaration);
+            text.push('\n');
+        }
+        text.push_str(&entry);
+
+        Ok(Translation {
+            source: text,
+            entry_point: entry_point,
+        })
+    }
+
+    fn call_order(&self, function: u32, stack: &mut Vec<u32>, order: &mut Vec<u32>) -> Result<()> {
+        if order.contains(&function) {
+            return Ok(());
+        }
+        if stack.contains(&function) {
+            return unsupported("recursion");
+        }
+        let f = match self.functions.get(&function) {
+            Some(f) => f,
+            None => return unsupported(&format!("call to function {}, which is not defined", function)),
+        };
+        stack.push(function);
+        for &callee in &f.calls {
+            try!(self.call_order(callee, stack, order));
+        }
+        stack.pop();
+        order.push(function);
+        Ok(())
+    }
+
+    fn storage(&self, variable: u32) -> Result<(StorageClass, u32)> {
+        match self.types.get(&self.type_of(variable)) {
+            Some(&Type::Pointer(storage, pointee)) => Ok((storage, pointee)),
+            _ => unsupported(&format!("variable {} does not have a pointer type", variable)),
+        }
+    }
+
+    /// The address space of pointers to a storage class in a function's parameters.
+    fn address_space(&self, storage: StorageClass, pointee: u32) -> Result<&'static str> {
+        match storage {
+            StorageClass::Function | StorageClass::Private | StorageClass::Input | StorageClass::Output => Ok("thread"),
+            StorageClass::Workgroup => Ok("threadgroup"),
+            StorageClass::StorageBuffer => Ok("device"),
+            StorageClass::Uniform if self.decorations.get(&pointee).map_or(false, |x| x.buffer_block) => Ok("device"),
+            StorageClass::Uniform | StorageClass::PushConstant => Ok("constant"),
+            storage => unsupported(&format!("pointers into {:?}", storage)),
+        }
+    }
+
+    /// The parameter declarations for a pointer to or value of type `ty`, which are two for
+    /// sampled images.
+    fn parameters(&self, id: u32, ty: u32) -> Result<Vec<String>> {
+        let (storage, pointee) = match self.types.get(&ty) {
+            Some(&Type::Pointer(storage, pointee)) => (Some(storage), pointee),
+            _ => (None, ty),
+        };
+        let name = try!(self.type_name(pointee));
+        let mut parameters = Vec::new();
+        match (storage, self.types.get(&pointee)) {
+            (_, Some(&Type::Image(_))) | (_, Some(&Type::SampledImage(_))) => parameters.push(format!("{} _{}", name, id)),
+            (_, Some(&Type::Sampler)) => parameters.push(format!("sampler _{}", id)),
+            (Some(storage), Some(&Type::Array(element, _))) if self.is_handle(element) && storage == StorageClass::UniformConstant => {
+                return unsupported("arrays of descriptors");
+            },
+            (Some(storage), _) => parameters.push(format!("{} {}& _{}", try!(self.address_space(storage, pointee)), name, id)),
+            (None, _) => parameters.push(format!("{} _{}", name, id)),
+        }
+        if let Some(&Type::SampledImage(_)) = self.types.get(&pointee) {
+            parameters.push(format!("sampler _{}Smplr", id));
+        }
+        Ok(parameters)
+    }
+
+    /// Sets up how a function refers to a global variable, which is the same in every function.
+    fn name_global(&self, body: &mut Body, global: u32) -> Result<()> {
+        let (storage, pointee) = try!(self.storage(global));
+        body.storage.insert(global, storage);
+        if let Some(&Type::SampledImage(_)) = self.types.get(&pointee) {
+            body.samplers.insert(global, format!("_{}Smplr", global));
+        }
+        Ok(())
+    }
+
+    fn function(&self, id: u32, globals: &[u32], model: ExecutionModel) -> Result<String> {
+        let f = &self.functions[&id];
+        let mut body = Body {
+            text: String::new(),
+            indent: 0,
+            names: HashMap::new(),
+            samplers: HashMap::new(),
+            storage: HashMap::new(),
+            constructs: Vec::new(),
+            epilogue: None,
+            returns_value: self.declared(f.result_type),
+            model: model,
+            frag_coord: self.subpass && model == ExecutionModel::Fragment,
+        };
+
+        let mut parameters = Vec::new();
+        if body.frag_coord {
+            parameters.push("float4 spvFragCoord".to_owned());
+        }
+        for &(parameter, ty) in &f.parameters {
+            parameters.extend(try!(self.parameters(parameter, ty)));
+            if let Some(&Type::Pointer(storage, pointee)) = self.types.get(&ty) {
+                body.storage.insert(parameter, storage);
+                if let Some(&Type::SampledImage(_)) = self.types.get(&pointee) {
+                    body.samplers.insert(parameter, format!("_{}Smplr", parameter));
+                }
+            } else if let Some(&Type::SampledImage(_)) = self.types.get(&ty) {
+                body.samplers.insert(parameter, format!("_{}Smplr", parameter));
+            }
+        }
+        for &global in globals {
+            parameters.extend(try!(self.parameters(global, self.type_of(global))));
+            try!(self.name_global(&mut body, global));
+        }
+
+        let result = try!(self.type_name(f.result_type));
+        body.open(format!("{} _{}({})", result, id, parameters.join(", ")));
+        try!(self.body(f, &mut body));
+        body.close();
+        Ok(body.text)
+    }
+
+    /// Writes an entry point's function, returning it after the structures for its inputs and
+    /// outputs.
+    fn entry(&self, id: u32, name: &str, model: ExecutionModel, globals: &[u32]) -> Result<(Vec<String>, String)> {
+        let f = &self.functions[&id];
+        let modes: Vec<ExecutionMode> = self.execution_modes.iter().filter(|x| x.0 == id).map(|x| x.1).collect();
+        let mut body = Body {
+            text: String::new(),
+            indent: 0,
+            names: HashMap::new(),
+            samplers: HashMap::new(),
+            storage: HashMap::new(),
+            constructs: Vec::new(),
+            epilogue: None,
+            returns_value: false,
+            model: model,
+            frag_coord: model == ExecutionModel::Fragment && self.subpass,
+        };
+
+        let mut parameters = Vec::new();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut prologue = Vec::new();
+        let mut epilogue = Vec::new();
+        for &global in globals {
+            let (storage, pointee) = try!(self.storage(global));
+            try!(self.name_global(&mut body, global));
+            let decorations = self.decorations.get(&global).cloned().unwrap_or_default();
+            let ty = try!(self.type_name(pointee));
+            let initializer = match self.initializers.get(&global) {
+                Some(&initializer) => format!(" = {}", self.value(None, initializer)),
+                None => String::new(),
+            };
+            match storage {
+                StorageClass::Input => {
+                    if let Some(builtin) = decorations.builtin {
+                        let (parameter, parameter_type, attribute) = match (model, builtin) {
+                            (ExecutionModel::Vertex, BuiltIn::VertexIndex) | (ExecutionModel::Vertex, BuiltIn::VertexId) => ("spvVertexIndex", "uint", "vertex_id"),
+                            (ExecutionModel::Vertex, BuiltIn::InstanceIndex) | (ExecutionModel::Vertex, BuiltIn::InstanceId) => ("spvInstanceIndex", "uint", "instance_id"),
+                            (ExecutionModel::Fragment, BuiltIn::FragCoord) => {
+                                body.frag_coord = true;
+                                ("spvFragCoord", "", "")
+                            },
+                            (ExecutionModel::Fragment, BuiltIn::FrontFacing) => ("spvFrontFacing", "bool", "front_facing"),
+                            (ExecutionModel::Fragment, BuiltIn::PointCoord) => ("spvPointCoord", "float2", "point_coord"),
+                            (ExecutionModel::Fragment, BuiltIn::SampleId) => ("spvSampleId", "uint", "sample_id"),
+                            (ExecutionModel::Fragment, BuiltIn::SampleMask) => ("spvSampleMaskIn", "uint", "sample_mask"),
+                            (ExecutionModel::GLCompute, BuiltIn::GlobalInvocationId) => ("spvGlobalInvocationId", "uint3", "thread_position_in_grid"),
+                            (ExecutionModel::GLCompute, BuiltIn::LocalInvocationId) => ("spvLocalInvocationId", "uint3", "thread_position_in_threadgroup"),
+                            (ExecutionModel::GLCompute, BuiltIn::WorkgroupId) => ("spvWorkgroupId", "uint3", "threadgroup_position_in_grid"),
+                            (ExecutionModel::GLCompute, BuiltIn::NumWorkgroups) => ("spvNumWorkgroups", "uint3", "threadgroups_per_grid"),
+                            (ExecutionModel::GLCompute, BuiltIn::LocalInvocationIndex) => ("spvLocalInvocationIndex", "uint", "thread_index_in_threadgroup"),
+                            (model, builtin) => return unsupported(&format!("built-in input {:?} in {:?} shaders", builtin, model)),
+                        };
+                        if !attribute.is_empty() {
+                            parameters.push(format!("{} {} [[{}]]", parameter_type, parameter, attribute));
+                        }
+                        match self.types.get(&pointee) {
+                            Some(&Type::Array(element, _)) => {
+                                prologue.push(format!("{} _{} = {{ {}({}) }};", ty, global, try!(self.type_name(element)), parameter));
+                            },
+                            _ => prologue.push(format!("{} _{} = {}({});", ty, global, ty, parameter)),
+                        }
+                    } else {
+                        let location = match decorations.location {
+                            Some(location) => location,
+                            None => return unsupported(&format!("input {} without a location", global)),
+                        };
+                        match self.types.get(&pointee) {
+                            Some(&Type::Bool) | Some(&Type::Int(_)) | Some(&Type::Float) | Some(&Type::Vector(..)) => {},
+                            _ => return unsupported(&format!("input {}, which takes up more than one location", global)),
+                        }
+                        let attributes = match model {
+                            ExecutionModel::Vertex => format!("[[attribute({})]]", location),
+                            _ => {
+                                let interpolation = if decorations.flat {
+                                    " [[flat]]"
+                                } else if decorations.no_perspective {
+                                    " [[center_no_perspective]]"
+                                } else {
+                                    ""
+                                };
+                                format!("[[user(locn{})]]{}", location, interpolation)
+                            },
+                        };
+                        inputs.push(format!("    {} m_{} {};", ty, location, attributes));
+                        prologue.push(format!("{} _{} = in.m_{};", ty, global, location));
+                    }
+                },
+                StorageClass::Output => {
+                    prologue.push(format!("{} _{}{};", ty, global, initializer));
+                    let members = match self.types.get(&pointee) {
+                        Some(&Type::Struct(ref members)) => (0..members.len())
+                            .map(|i| (format!("_{}.m{}", global, i), self.member_decorations.get(&(pointee, i as u32)).cloned().unwrap_or_default()))
+                            .collect(),
+                        _ => vec![(format!("_{}", global), decorations.clone())],
+                    };
+                    for (source, decorations) in members {
+                        let member = match (decorations.builtin, decorations.location) {
+                            (Some(BuiltIn::Position), _) => Some(("gl_Position".to_owned(), "float4", "[[position]]".to_owned())),
+                            (Some(BuiltIn::PointSize), _) if self.options.point_size => Some(("gl_PointSize".to_owned(), "float", "[[point_size]]".to_owned())),
+                            (Some(BuiltIn::FragDepth), _) => {
+                                let direction = if modes.contains(&ExecutionMode::DepthGreater) {
+                                    "greater"
+                                } else if modes.contains(&ExecutionMode::DepthLess) {
+                                    "less"
+                                } else {
+                                    "any"
+                                };
+                                Some(("gl_FragDepth".to_owned(), "float", format!("[[depth({})]]", direction)))
+                            },
+                            (Some(BuiltIn::SampleMask), _) => {
+                                outputs.push("    uint gl_SampleMask [[sample_mask]];".to_owned());
+                                epilogue.push(format!("out.gl_SampleMask = uint({}[0]);", source));
+                                None
+                            },
+                            // Clip and cull distances are left out, as Metal has no
+                            // equivalent.
+                            (Some(_), _) => None,
+                            (None, Some(location)) if model == ExecutionModel::Fragment => Some((format!("m_{}", location), "", format!("[[color({})]]", location))),
+                            (None, Some(location)) => Some((format!("m_{}", location), "", format!("[[user(locn{})]]", location))),
+                            (None, None) => return unsupported(&format!("output {} without a location", global)),
+                        };
+                        if let Some((member, member_type, attribute)) = member {
+                            let member_type = if member_type.is_empty() { ty.clone() } else { member_type.to_owned() };
+                            outputs.push(format!("    {} {} {};", member_type, member, attribute));
+                            epilogue.push(format!("out.{} = {};", member, source));
+                        }
+                    }
+                },
+                StorageClass::UniformConstant => {
+                    let resource = try!(self.resource(global, &decorations));
+                    match self.types.get(&pointee) {
+                        Some(&Type::Image(_)) | Some(&Type::SampledImage(_)) => {
+                            let texture = match resource.texture {
+                                Some(texture) => texture,
+                                None => return unsupported(&format!("image {} bound to a {:?} descriptor", global, resource.descriptor_type)),
+                            };
+                            parameters.push(format!("{} _{} [[texture({})]]", ty, global, texture));
+                        },
+                        Some(&Type::Sampler) => {},
+                        _ => return unsupported("arrays of descriptors"),
+                    }
+                    match self.types.get(&pointee) {
+                        Some(&Type::Sampler) | Some(&Type::SampledImage(_)) => {
+                            let sampler = match resource.sampler {
+                                Some(sampler) => sampler,
+                                None => return unsupported(&format!("sampler {} bound to a {:?} descriptor", global, resource.descriptor_type)),
+                            };
+                            let suffix = if let Some(&Type::Sampler) = self.types.get(&pointee) { "" } else { "Smplr" };
+                            parameters.push(format!("sampler _{}{} [[sampler({})]]", global, suffix, sampler));
+                        },
+                        _ => {},
+                    }
+                },
+                StorageClass::Uniform | StorageClass::StorageBuffer => {
+                    let resource = try!(self.resource(global, &decorations));
+                    let buffer = match resource.buffer {
+                        Some(buffer) => buffer,
+                        None => return unsupported(&format!("buffer {} bound to a {:?} descriptor", global, resource.descriptor_type)),
+                    };
+                    parameters.push(format!("{} {}& _{} [[buffer({})]]", try!(self.address_space(storage, pointee)), ty, global, buffer));
+                },
+                StorageClass::PushConstant => {
+                    let buffer = match self.bindings.push_constant_buffer() {
+                        Some(buffer) => buffer,
+                        None => return unsupported("push constants with a binding map that has no room for them"),
+                    };
+                    parameters.push(format!("constant {}& _{} [[buffer({})]]", ty, global, buffer));
+                },
+                StorageClass::Private => prologue.push(format!("{} _{}{};", ty, global, initializer)),
+                StorageClass::Workgroup if model == ExecutionModel::GLCompute => prologue.push(format!("threadgroup {} _{};", ty, global)),
+                storage => return unsupported(&format!("{:?} variables in {:?} shaders", storage, model)),
+            }
+        }
+        if body.frag_coord {
+            parameters.insert(0, "float4 spvFragCoord [[position]]".to_owned());
+        }
+
+        let mut interface = Vec::new();
+        if !inputs.is_empty() {
+            parameters.insert(0, format!("{}_in in [[stage_in]]", name));
+            interface.push(format!("struct {}_in\n{{\n{}\n}};\n", name, inputs.join("\n")));
+        }
+        let result = if outputs.is_empty() {
+            body.epilogue = Some(Vec::new());
+            "void".to_owned()
+        } else {
+            interface.push(format!("struct {}_out\n{{\n{}\n}};\n", name, outputs.join("\n")));
+            epilogue.insert(0, format!("{}_out out = {{}};", name));
+            if model == ExecutionModel::Vertex && self.options.flip_vertex_y && outputs.iter().any(|x| x.contains("gl_Position")) {
+                epilogue.push("out.gl_Position.y = -out.gl_Position.y;".to_owned());
+            }
+            body.epilogue = Some(epilogue);
+            format!("{}_out", name)
+        };
+
+        let qualifier = match model {
+            ExecutionModel::Vertex => "vertex",
+            ExecutionModel::Fragment if modes.contains(&ExecutionMode::EarlyFragmentTests) => "[[early_fragment_tests]] fragment",
+            ExecutionModel::Fragment => "fragment",
+            _ => "kernel",
+        };
+        body.open(format!("{} {} {}({})", qualifier, result, name, parameters.join(", ")));
+        for line in prologue {
+            body.line(line);
+        }
+        try!(self.body(f, &mut body));
+        body.close();
+        Ok((interface, body.text))
+    }
+
+    fn resource(&self, global: u32, decorations: &Decorations) -> Result<&ResourceBinding> {
+        let (set, binding) = (decorations.set.unwrap_or(0), decorations.binding.unwrap_or(0));
+        match self.bindings.resource(set, binding) {
+            Some(resource) if resource.count <= 1 => Ok(resource),
+            Some(_) => unsupported("arrays of descriptors"),
+            None => unsupported(&format!("variable {} uses set {} binding {}, which is not in the binding map", global, set, binding)),
+        }
+    }
+
+    /// Declares a function's values and writes its blocks.
+    fn body(&self, f: &Function, body: &mut Body) -> Result<()> {
+        for block in &f.blocks {
+            for &(op, ref o) in &block.body {
+                if o.len() < 2 || result_index(op) != Some(1) {
+                    continue;
+                }
+                if op == Op::Variable {
+                    let (_, pointee) = try!(self.storage(o[1]));
+                    let initializer = match o.get(3) {
+                        Some(&initializer) => format!(" = {}", self.value(Some(&*body), initializer)),
+                        None => String::new(),
+                    };
+                    let line = format!("{} _{}{};", try!(self.type_name(pointee)), o[1], initializer);
+                    body.line(line);
+                    body.storage.insert(o[1], StorageClass::Function);
+                } else if self.declared(o[0]) {
+                    let line = format!("{} _{};", try!(self.type_name(o[0])), o[1]);
+                    body.line(line);
+                }
+            }
+        }
+        match f.blocks.first() {
+            Some(first) => self.goto(f, body, first.label, None),
+            None => Ok(()),
+        }
+    }
+
+    /// Continues control flow at `to`, which ends where it reaches `stop`.
+    fn goto(&self, f: &Function, body: &mut Body, to: u32, stop: Option<u32>) -> Result<()> {
+        if Some(to) == stop {
+            return Ok(());
+        }
+        let innermost_loop = body.constructs.iter().rev().filter_map(|x| match *x {
+            Construct::Loop { header, merge, continue_target } => Some((header, merge, continue_target)),
+            _ => None,
+        }).next();
+        let innermost_merge = body.constructs.last().map(|x| match *x {
+            Construct::Loop { merge, .. } | Construct::Switch { merge } => merge,
+        });
+        if let Some((header, _, continue_target)) = innermost_loop {
+            if to == header {
+                body.line("continue;");
+                return Ok(());
+            }
+            if to == continue_target {
+                return self.block(f, body, to, None);
+            }
+        }
+        if Some(to) == innermost_merge {
+            body.line("break;");
+            return Ok(());
+        }
+        let escapes = body.constructs.iter().any(|x| match *x {
+            Construct::Loop { merge, continue_target, .. } => to == merge || to == continue_target,
+            Construct::Switch { merge } => to == merge,
+        });
+        if escapes {
+            return unsupported("a branch out of more than one construct");
+        }
+        self.block(f, body, to, stop)
+    }
+
+    /// Assigns the values `to`'s phis take when coming from `from`, then goes there.
+    fn branch(&self, f: &Function, body: &mut Body, from: u32, to: u32, stop: Option<u32>) -> Result<()> {
+        let phis = self.phis(f, from, to);
+        if phis.len() == 1 {
+            let line = format!("_{} = {};", phis[0].0, self.value(Some(&*body), phis[0].1));
+            body.line(line);
+        } else if phis.len() > 1 {
+            // Phis take their values all at once, so some may read others' old values.
+            body.open(String::new());
+            for &(phi, value) in &phis {
+                let line = format!("auto _{}_tmp = {};", phi, self.value(Some(&*body), value));
+                body.line(line);
+            }
+            for &(phi, _) in &phis {
+                body.line(format!("_{0} = _{0}_tmp;", phi));
+            }
+            body.close();
+        }
+        self.goto(f, body, to, stop)
+    }
+
+    fn phis(&self, f: &Function, from: u32, to: u32) -> Vec<(u32, u32)> {
+        let block = match f.labels.get(&to) {
+            Some(&index) => &f.blocks[index],
+            None => return Vec::new(),
+        };
+        block.body.iter()
+            .filter(|x| x.0 == Op::Phi && x.1.len() >= 2)
+            .filter_map(|&(_, ref o)| o[2..].chunks(2).find(|x| x.len() == 2 && x[1] == from).map(|x| (o[1], x[0])))
+            .collect()
+    }
+
+    fn block(&self, f: &Function, body: &mut Body, label: u32, stop: Option<u32>) -> Result<()> {
+        let block = match f.labels.get(&label) {
+            Some(&index) => &f.blocks[index],
+            None => return unsupported(&format!("branch to {}, which is not a block of the function", label)),
+        };
+        if let Some(Merge::Loop(merge, continue_target)) = block.merge {
+            body.open("for (;;)".to_owned());
+            body.constructs.push(Construct::Loop { header: label, merge: merge, continue_target: continue_target });
+            for &(op, ref o) in &block.body {
+                try!(self.instruction(body, op, o));
+            }
+            try!(self.terminator(f, body, block, None));
+            body.constructs.pop();
+            body.close();
+            return self.goto(f, body, merge, stop);
+        }
+        for &(op, ref o) in &block.body {
+            try!(self.instruction(body, op, o));
+        }
+        self.terminator(f, body, block, stop)
+    }
+
+    fn terminator(&self, f: &Function, body: &mut Body, block: &Block, stop: Option<u32>) -> Result<()> {
+        let (op, ref o) = block.terminator;
+        let at = |i: usize| o.get(i).cloned().unwrap_or(0);
+        match op {
+            Op::Branch => self.branch(f, body, block.label, at(0), stop),
+            Op::BranchConditional => {
+                let condition = self.value(Some(&*body), at(0));
+                match block.merge {
+                    Some(Merge::Selection(merge)) => {
+                        body.open(format!("if ({})", condition));
+                        try!(self.branch(f, body, block.label, at(1), Some(merge)));
+                        body.close();
+                        if at(2) != merge || !self.phis(f, block.label, merge).is_empty() {
+                            body.open("else".to_owned());
+                            try!(self.branch(f, body, block.label, at(2), Some(merge)));
+                            body.close();
+                        }
+                        self.goto(f, body, merge, stop)
+                    },
+                    _ => {
+                        body.open(format!("if ({})", condition));
+                        try!(self.branch(f, body, block.label, at(1), stop));
+                        body.close();
+                        body.open("else".to_owned());
+                        try!(self.branch(f, body, block.label, at(2), stop));
+                        body.close();
+                        Ok(())
+                    },
+                }
+            },
+            Op::Switch => {
+                let merge = match block.merge {
+                    Some(Merge::Selection(merge)) => merge,
+                    _ => return unsupported("OpSwitch without a merge block"),
+                };
+                let signed = match self.types.get(&self.type_of(at(0))) {
+                    Some(&Type::Int(signed)) => signed,
+                    _ => false,
+                };
+                let mut cases: Vec<(u32, Vec<u32>)> = Vec::new();
+                for pair in o[cmp::min(2, o.len())..].chunks(2) {
+                    if pair.len() < 2 {
+                        continue;
+                    }
+                    match cases.iter().position(|x| x.0 == pair[1]) {
+                        Some(i) => cases[i].1.push(pair[0]),
+                        None => cases.push((pair[1], vec![pair[0]])),
+                    }
+                }
+                let selector = self.value(Some(&*body), at(0));
+                body.open(format!("switch ({})", selector));
+                body.constructs.push(Construct::Switch { merge: merge });
+                for (target, literals) in cases {
+                    for literal in literals {
+                        body.line(if signed { format!("case {}:", literal as i32) } else { format!("case {}u:", literal) });
+                    }
+                    body.open(String::new());
+                    try!(self.branch(f, body, block.label, target, Some(merge)));
+                    body.line("break;");
+                    body.close();
+                }
+                body.line("default:");
+                body.open(String::new());
+                try!(self.branch(f, body, block.label, at(1), Some(merge)));
+                body.line("break;");
+                body.close();
+                body.constructs.pop();
+                body.close();
+                self.goto(f, body, merge, stop)
+            },
+            Op::Return => {
+                self.ret(body, None);
+                Ok(())
+            },
+            Op::ReturnValue => {
+                self.ret(body, Some(at(0)));
+                Ok(())
+            },
+            Op::Kill => {
+                body.line("discard_fragment();");
+                self.ret(body, None);
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn ret(&self, body: &mut Body, value: Option<u32>) {
+        if let Some(epilogue) = body.epilogue.clone() {
+            let returns_out = !epilogue.is_empty();
+            for line in epilogue {
+                body.line(line);
+            }
+            body.line(if returns_out { "return out;" } else { "return;" });
+        } else if let Some(value) = value {
+            let line = format!("return {};", self.value(Some(&*body), value));
+            body.line(line);
+        } else if body.returns_value {
+            body.line("return {};");
+        } else {
+            body.line("return;");
+        }
+    }
+
+    fn instruction(&self, body: &mut Body, op: Op, o: &[u32]) -> Result<()> {
+        let at = |i: usize| o.get(i).cloned().unwrap_or(0);
+        let (ty, result) = (at(0), at(1));
+        let line = {
+            let body = &*body;
+            let value = |id: u32| self.value(Some(body), id);
+            match op {
+                Op::Nop | Op::Line | Op::NoLine | Op::Phi | Op::Undef | Op::Variable | Op::SelectionMerge | Op::LoopMerge => None,
+                Op::Load if self.is_handle(ty) => None,
+                Op::Load => Some(format!("_{} = {};", result, value(at(2)))),
+                Op::Store => Some(format!("{} = {};", value(at(0)), value(at(1)))),
+                Op::CopyMemory => Some(format!("{} = {};", value(at(0)), value(at(1)))),
+                Op::AccessChain | Op::InBoundsAccessChain | Op::SampledImage | Op::Image => None,
+                Op::CopyObject if !self.declared(ty) => None,
+                Op::FunctionCall => {
+                    let f = match self.functions.get(&at(2)) {
+                        Some(f) => f,
+                        None => return unsupported(&format!("call to function {}, which is not defined", at(2))),
+                    };
+                    let mut arguments = Vec::new();
+                    if body.frag_coord {
+                        arguments.push("spvFragCoord".to_owned());
+                    }
+                    for &argument in &o[cmp::min(3, o.len())..] {
+                        arguments.push(value(argument));
+                        if let Some(sampler) = body.samplers.get(&argument) {
+                            arguments.push(sampler.clone());
+                        }
+                    }
+                    let mut globals = BTreeSet::new();
+                    try!(self.collect_globals(at(2), &mut globals));
+                    for global in globals {
+                        arguments.push(value(global));
+                        if let Some(sampler) = body.samplers.get(&global) {
+                            arguments.push(sampler.clone());
+                        }
+                    }
+                    let call = format!("_{}({})", at(2), arguments.join(", "));
+                    Some(if self.declared(f.result_type) { format!("_{} = {};", result, call) } else { format!("{};", call) })
+                },
+                Op::CompositeInsert => {
+                    let mut target = format!("_{}", result);
+                    let mut current = ty;
+                    for &index in &o[cmp::min(4, o.len())..] {
+                        let (accessor, next) = try!(self.member(current, index.to_string(), Some(index)));
+                        target.push_str(&accessor);
+                        current = next;
+                    }
+                    Some(format!("_{} = {};\n{} = {};", result, value(at(3)), target, value(at(2))))
+                },
+                Op::VectorInsertDynamic => Some(format!("_{0} = {1};\n_{0}[{2}] = {3};", result, value(at(2)), value(at(4)), value(at(3)))),
+                Op::ExtInst if Some(at(2)) == self.glsl && (Glsl450::from(at(3)) == Glsl450::ModfStruct || Glsl450::from(at(3)) == Glsl450::FrexpStruct) => {
+                    let function = if Glsl450::from(at(3)) == Glsl450::ModfStruct { "modf" } else { "frexp" };
+                    Some(format!("_{0}.m0 = {1}({2}, _{0}.m1);", result, function, value(at(4))))
+                },
+                Op::ImageSampleImplicitLod | Op::ImageSampleExplicitLod | Op::ImageSampleDrefImplicitLod |
+                Op::ImageSampleDrefExplicitLod | Op::ImageSampleProjImplicitLod | Op::ImageSampleProjExplicitLod |
+                Op::ImageSampleProjDrefImplicitLod | Op::ImageSampleProjDrefExplicitLod | Op::ImageFetch |
+                Op::ImageGather | Op::ImageDrefGather | Op::ImageRead | Op::ImageQuerySizeLod | Op::ImageQuerySize |
+                Op::ImageQueryLevels | Op::ImageQuerySamples => {
+                    Some(format!("_{} = {};", result, try!(self.image(body, op, ty, &o[cmp::min(2, o.len())..]))))
+                },
+                Op::ImageWrite => Some(try!(self.image_write(body, o))),
+                Op::AtomicLoad | Op::AtomicStore | Op::AtomicExchange | Op::AtomicCompareExchange |
+                Op::AtomicCompareExchangeWeak | Op::AtomicIIncrement | Op::AtomicIDecrement | Op::AtomicIAdd |
+                Op::AtomicISub | Op::AtomicSMin | Op::AtomicUMin | Op::AtomicSMax | Op::AtomicUMax | Op::AtomicAnd |
+                Op::AtomicOr | Op::AtomicXor => Some(try!(self.atomic(body, op, o))),
+                Op::ControlBarrier | Op::MemoryBarrier => {
+                    // Metal has no barriers outside compute functions; the ones SPIR-V allows
+                    // there have nothing to order against.
+                    let semantics = self.values.get(&at(if op == Op::ControlBarrier { 2 } else { 1 })).cloned().unwrap_or(0);
+                    let mut flags = Vec::new();
+                    if semantics & 0x40 != 0 {
+                        flags.push("mem_flags::mem_device");
+                    }
+                    if semantics & 0x100 != 0 {
+                        flags.push("mem_flags::mem_threadgroup");
+                    }
+                    if semantics & 0x800 != 0 {
+                        flags.push("mem_flags::mem_texture");
+                    }
+                    if flags.is_empty() {
+                        flags.push("mem_flags::mem_none");
+                    }
+                    if body.model != ExecutionModel::GLCompute {
+                        None
+                    } else {
+                        Some(format!("threadgroup_barrier({});", flags.join(" | ")))
+                    }
+                },
+                Op::ArrayLength => return unsupported("OpArrayLength, as Metal does not know buffer sizes"),
+                Op::ImageTexelPointer => return unsupported("image atomics"),
+                _ => match try!(self.expression(op, ty, &o[cmp::min(2, o.len())..], &value)) {
+                    Some(expression) => Some(format!("_{} = {};", result, expression)),
+                    None => return unsupported(&format!("instruction {:?}", op)),
+                },
+            }
+        };
+        if let Some(line) = line {
+            for line in line.split('\n') {
+                body.line(line);
+            }
+        }
+
+        // Pointers and images are referred to by the expressions they come from.
+        match op {
+            Op::AccessChain | Op::InBoundsAccessChain => {
+                let (mut name, mut sampler) = (self.value(Some(&*body), at(2)), body.samplers.get(&at(2)).cloned());
+                let mut current = match self.types.get(&self.type_of(at(2))) {
+                    Some(&Type::Pointer(_, pointee)) => pointee,
+                    _ => 0,
+                };
+                for &index in &o[cmp::min(3, o.len())..] {
+                    let (accessor, next) = try!(self.member(current, self.value(Some(&*body), index), self.values.get(&index).cloned()));
+                    name.push_str(&accessor);
+                    if let Some(ref mut sampler) = sampler {
+                        sampler.push_str(&accessor);
+                    }
+                    current = next;
+                }
+                let storage = body.storage.get(&at(2)).cloned().unwrap_or(StorageClass::Function);
+                body.names.insert(result, name);
+                body.storage.insert(result, storage);
+                if let Some(sampler) = sampler {
+                    body.samplers.insert(result, sampler);
+                }
+            },
+            Op::Load | Op::CopyObject if !self.declared(ty) => {
+                let name = self.value(Some(&*body), at(2));
+                body.names.insert(result, name);
+                if let Some(sampler) = body.samplers.get(&at(2)).cloned() {
+                    body.samplers.insert(result, sampler);
+                }
+                if let Some(storage) = body.storage.get(&at(2)).cloned() {
+                    body.storage.insert(result, storage);
+                }
+            },
+            Op::SampledImage => {
+                let (image, sampler) = (self.value(Some(&*body), at(2)), self.value(Some(&*body), at(3)));
+                body.names.insert(result, image);
+                body.samplers.insert(result, sampler);
+            },
+            Op::Image => {
+                let image = self.value(Some(&*body), at(2));
+                body.names.insert(result, image);
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    /// The global variables `function` and everything it calls refer to.
+    fn collect_globals(&self, function: u32, globals: &mut BTreeSet<u32>) -> Result<()> {
+        let mut stack = vec![function];
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            match self.functions.get(&id) {
+                Some(f) => {
+                    globals.extend(f.globals.iter().cloned());
+                    stack.extend(f.calls.iter().cloned());
+                },
+                None => return unsupported(&format!("call to function {}, which is not defined", id)),
+            }
+        }
+        Ok(())
+    }
+
+    fn image_type(&self, id: u32) -> Result<Image> {
+        match self.types.get(&self.type_of(id)) {
+            Some(&Type::Image(image)) => Ok(image),
+            Some(&Type::SampledImage(image)) => match self.types.get(&image) {
+                Some(&Type::Image(image)) => Ok(image),
+                _ => unsupported(&format!("sampled image {} of something other than an image", id)),
+            },
+            _ => unsupported(&format!("{} is used as an image", id)),
+        }
+    }
+
+    /// Integer texel coordinates for reading or writing an image, with the array layer.
+    fn texel_coordinates(&self, body: &Body, image: &Image, coordinate: u32) -> (String, Option<String>) {
+        let c = self.value(Some(body), coordinate);
+        let count = self.components(self.type_of(coordinate));
+        let component = |i: usize| if count == 1 { c.clone() } else { format!("{}.{}", c, &"xyzw"[i..(i + 1)]) };
+        let (coordinates, dimensions) = match image.dim {
+            Dim::D1 => (format!("uint({})", component(0)), 1),
+            Dim::Buffer => (format!("{}(uint({}))", self.mark("spvTexelBufferCoord"), component(0)), 1),
+            Dim::D3 | Dim::Cube => (format!("uint3({}.xyz)", c), 3),
+            Dim::SubpassData => (format!("uint2(int2(spvFragCoord.xy) + {}.xy)", c), 2),
+            _ => (format!("uint2({}.xy)", c), 2),
+        };
+        (coordinates, if image.arrayed { Some(format!("uint({})", component(dimensions))) } else { None })
+    }
+
+    fn image(&self, body: &Body, op: Op, ty: u32, a: &[u32]) -> Result<String> {
+        let at = |i: usize| a.get(i).cloned().unwrap_or(0);
+        let value = |id: u32| self.value(Some(body), id);
+        let image = try!(self.image_type(at(0)));
+        let texture = value(at(0));
+        let name = try!(self.type_name(ty));
+        let (dref, proj) = match op {
+            Op::ImageSampleDrefImplicitLod | Op::ImageSampleDrefExplicitLod | Op::ImageDrefGather => (true, false),
+            Op::ImageSampleProjImplicitLod | Op::ImageSampleProjExplicitLod => (false, true),
+            Op::ImageSampleProjDrefImplicitLod | Op::ImageSampleProjDrefExplicitLod => (true, true),
+            _ => (false, false),
+        };
+        // The operands after the coordinate: a depth reference or gather component, then the
+        // image operands.
+        let operands_at = match op {
+            Op::ImageGather => 3,
+            _ if dref => 3,
+            _ => 2,
+        };
+        let operands = try!(ImageOperands::new(&a[cmp::min(operands_at, a.len())..]));
+        // Depth images read and sample as scalars, where SPIR-V has vectors.
+        let widen = |expression: String| if image.depth && !dref { format!("float4({})", expression) } else { expression };
+
+        match op {
+            Op::ImageFetch | Op::ImageRead => {
+                let (coordinates, layer) = self.texel_coordinates(body, &image, at(1));
+                let mut args = vec![coordinates];
+                args.extend(layer);
+                if let Some(sample) = operands.sample {
+                    args.push(format!("uint({})", value(sample)));
+                } else if let Some(lod) = operands.lod {
+                    if image.dim != Dim::Buffer && image.dim != Dim::D1 {
+                        args.push(format!("uint({})", value(lod)));
+                    }
+                }
+                Ok(widen(format!("{}.read({})", texture, args.join(", "))))
+            },
+            Op::ImageQuerySizeLod | Op::ImageQuerySize => {
+                let lod = if op == Op::ImageQuerySizeLod { format!("uint({})", value(at(1))) } else { String::new() };
+                let mut sizes = vec![format!("{}.get_width({})", texture, lod)];
+                match image.dim {
+                    Dim::D2 | Dim::Rect | Dim::Cube | Dim::SubpassData => sizes.push(format!("{}.get_height({})", texture, lod)),
+                    Dim::D3 => {
+                        sizes.push(format!("{}.get_height({})", texture, lod));
+                        sizes.push(format!("{}.get_depth({})", texture, lod));
+                    },
+                    Dim::Buffer => sizes = vec![format!("{0}.get_width() * {0}.get_height()", texture)],
+                    _ => {},
+                }
+                if image.arrayed {
+                    sizes.push(format!("{}.get_array_size()", texture));
+                }
+                Ok(format!("{}({})", name, sizes.join(", ")))
+            },
+            Op::ImageQueryLevels => Ok(format!("{}({}.get_num_mip_levels())", name, texture)),
+            Op::ImageQuerySamples => Ok(format!("{}({}.get_num_samples())", name, texture)),
+            _ => {
+                let sampler = match body.samplers.get(&at(0)) {
+                    Some(sampler) => sampler.clone(),
+                    None => return unsupported(&format!("sampling {}, which has no sampler", at(0))),
+                };
+                let c = value(at(1));
+                let count = self.components(self.type_of(at(1)));
+                let component = |i: usize| if count == 1 { c.clone() } else { format!("{}.{}", c, &"xyzw"[i..(i + 1)]) };
+                let dimensions = match image.dim {
+                    Dim::D1 => 1,
+                    Dim::D3 | Dim::Cube => 3,
+                    _ => 2,
+                };
+                let mut coordinates = if dimensions == 1 { component(0) } else { format!("{}.{}", c, &"xyzw"[..dimensions]) };
+                if proj {
+                    coordinates = format!("{} / {}", coordinates, component(dimensions));
+                }
+                let mut args = vec![sampler, coordinates];
+                if image.arrayed {
+                    args.push(format!("uint(rint({}))", component(dimensions)));
+                }
+                if dref {
+                    args.push(value(at(2)));
+                }
+                if image.dim != Dim::D1 {
+                    if let Some(bias) = operands.bias {
+                        args.push(format!("bias({})", value(bias)));
+                    }
+                    if let Some(lod) = operands.lod {
+                        args.push(format!("level({})", value(lod)));
+                    }
+                    if let Some((dx, dy)) = operands.grad {
+                        let gradient = match image.dim {
+                            Dim::D3 => "gradient3d",
+                            Dim::Cube => "gradientcube",
+                            _ => "gradient2d",
+                        };
+                        args.push(format!("{}({}, {})", gradient, value(dx), value(dy)));
+                    }
+                    if let Some(offset) = operands.offset {
+                        if image.dim != Dim::Cube {
+                            args.push(value(offset));
+                        }
+                    }
+                }
+                if op == Op::ImageGather {
+                    if image.dim != Dim::Cube && operands.offset.is_none() && !image.depth {
+                        args.push("int2(0)".to_owned());
+                    }
+                    if !image.depth {
+                        let index = self.values.get(&at(2)).cloned().unwrap_or(0) as usize;
+                        args.push(format!("component::{}", &"xyzw"[cmp::min(index, 3)..(cmp::min(index, 3) + 1)]));
+                    }
+                }
+                let function = match op {
+                    Op::ImageGather => "gather",
+                    Op::ImageDrefGather => "gather_compare",
+                    _ if dref => "sample_compare",
+                    _ => "sample",
+                };
+                let sampled = format!("{}.{}({})", texture, function, args.join(", "));
+                Ok(if op == Op::ImageGather || op == Op::ImageDrefGather { sampled } else { widen(sampled) })
+            },
+        }
+    }
+
+    fn image_write(&self, body: &Body, o: &[u32]) -> Result<String> {
+        let at = |i: usize| o.get(i).cloned().unwrap_or(0);
+        let image = try!(self.image_type(at(0)));
+        let (coordinates, layer) = self.texel_coordinates(body, &image, at(1));
+        let texel = self.value(Some(body), at(2));
+        let texel_type = self.type_of(at(2));
+        let component = match self.types.get(&texel_type) {
+            Some(&Type::Vector(component, _)) => component,
+            _ => texel_type,
+        };
+        let vector = format!("{}4", try!(self.type_name(component)));
+        let texel = match self.components(texel_type) {
+            4 => texel,
+            1 => format!("{}({})", vector, texel),
+            2 => format!("{}({}, 0, 0)", vector, texel),
+            _ => format!("{}({}, 0)", vector, texel),
+        };
+        let mut args = vec![texel, coordinates];
+        args.extend(layer);
+        Ok(format!("{}.write({});", self.value(Some(body), at(0)), args.join(", ")))
+    }
+
+    fn atomic(&self, body: &Body, op: Op, o: &[u32]) -> Result<String> {
+        let at = |i: usize| o.get(i).cloned().unwrap_or(0);
+        let value = |id: u32| self.value(Some(body), id);
+        // AtomicStore has no result type or ID; the rest start with them.
+        let (pointer, operands) = if op == Op::AtomicStore { (at(0), 3) } else { (at(2), 5) };
+        let space = match body.storage.get(&pointer) {
+            Some(&StorageClass::Workgroup) => "threadgroup",
+            Some(&StorageClass::StorageBuffer) | Some(&StorageClass::Uniform) => "device",
+            storage => return unsupported(&format!("atomics on {:?} memory", storage)),
+        };
+        let pointee = match self.types.get(&self.type_of(pointer)) {
+            Some(&Type::Pointer(_, pointee)) => pointee,
+            _ => 0,
+        };
+        let kind = match self.types.get(&pointee) {
+            Some(&Type::Int(true)) => "atomic_int",
+            Some(&Type::Int(false)) => "atomic_uint",
+            _ => return unsupported("atomics on anything but 32-bit integers"),
+        };
+        let target = format!("({} {}*)&{}", space, kind, value(pointer));
+        let operand = value(at(operands));
+        let result = at(1);
+        let fetch = |function: &str, operand: &str| format!("_{} = atomic_fetch_{}_explicit({}, {}, memory_order_relaxed);", result, function, target, operand);
+        Ok(match op {
+            Op::AtomicLoad => format!("_{} = atomic_load_explicit({}, memory_order_relaxed);", result, target),
+            Op::AtomicStore => format!("atomic_store_explicit({}, {}, memory_order_relaxed);", target, value(at(3))),
+            Op::AtomicExchange => format!("_{} = atomic_exchange_explicit({}, {}, memory_order_relaxed);", result, target, operand),
+            Op::AtomicCompareExchange | Op::AtomicCompareExchangeWeak => {
+                let (new, comparator) = (value(at(6)), value(at(7)));
+                format!(
+                    "_{0} = {1};\nwhile (!atomic_compare_exchange_weak_explicit({2}, &_{0}, {3}, memory_order_relaxed, memory_order_relaxed) && _{0} == {1}) {{}}",
+                    result, comparator, target, new,
+                )
+            },
+            Op::AtomicIIncrement => fetch("add", "1"),
+            Op::AtomicIDecrement => fetch("sub", "1"),
+            Op::AtomicIAdd => fetch("add", &operand),
+            Op::AtomicISub => fetch("sub", &operand),
+            Op::AtomicSMin | Op::AtomicUMin => fetch("min", &operand),
+            Op::AtomicSMax | Op::AtomicUMax => fetch("max", &operand),
+            Op::AtomicAnd => fetch("and", &operand),
+            Op::AtomicOr => fetch("or", &operand),
+            _ => fetch("xor", &operand),
+        })
+    }
+}
+
+/// The optional operands of an image instruction that Metal has a use for.
+#[derive(Default)]
+struct ImageOperands {
+    bias: Option<u32>,
+    lod: Option<u32>,
+    grad: Option<(u32, u32)>,
+    offset: Option<u32>,
+    sample: Option<u32>,
+}
+
+impl ImageOperands {
+    fn new(operands: &[u32]) -> Result<ImageOperands> {
+        let mut result = ImageOperands::default();
+        let mask = match operands.first() {
+            Some(&mask) => mask,
+            None => return Ok(result),
+        };
+        let mut next = 1;
+        let mut take = || {
+            let operand = operands.get(next).cloned().unwrap_or(0);
+            next += 1;
+            operand
+        };
+        if mask & image_operands::BIAS != 0 {
+            result.bias = Some(take());
+        }
+        if mask & image_operands::LOD != 0 {
+            result.lod = Some(take());
+        }
+        if mask & image_operands::GRAD != 0 {
+            let dx = take();
+            result.grad = Some((dx, take()));
+        }
+        if mask & image_operands::CONST_OFFSET != 0 {
+            result.offset = Some(take());
+        }
+        if mask & image_operands::OFFSET != 0 {
+            return unsupported("image offsets that are not constant");
+        }
+        if mask & image_operands::CONST_OFFSETS != 0 {
+            return unsupported("gathers with four offsets");
+        }
+        if mask & image_operands::SAMPLE != 0 {
+            result.sample = Some(take());
+        }
+        if mask & image_operands::MIN_LOD != 0 {
+            return unsupported("minimum level of detail clamps");
+        }
+        Ok(result)
+    }
+}
+
+/// An identifier for an entry point named `name` that is valid in MSL and cannot clash with the
+/// names the translation makes up.
+fn identifier(name: &str) -> String {
+    let valid = |x: char| x == '_' || x.is_digit(10) || (x >= 'a' && x <= 'z') || (x >= 'A' && x <= 'Z');
+    let mut identifier: String = name.chars().map(|x| if valid(x) { x } else { '_' }).collect();
+    if identifier.is_empty() || identifier.starts_with(|x: char| x.is_digit(10)) || identifier.starts_with('_') {
+        identifier.insert_str(0, "ep");
+    }
+    if identifier.starts_with("spv") || ["main", "vertex", "fragment", "kernel"].contains(&&identifier[..]) {
+        identifier.push('0');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use icd::ShaderStageFlags;
+    use spirv::Assembler;
+
+    use std::env;
+    use std::fs::File;
+    use std::io::{Read, Write as IoWrite};
+    use std::path::Path;
+
+    fn binding(set: u32, binding: u32, descriptor_type: DescriptorType, count: u32) -> LayoutBinding {
+        LayoutBinding {
+            set: set,
+            binding: binding,
+            descriptor_type: descriptor_type,
+            count: count,
+            stages: ShaderStageFlags::all(),
+        }
+    }
+
+    /// Compares the translation of `code` with `tests/msl/<name>.metal`, or rewrites that file
+    /// when `VK_MIDDLE_BLESS` is set.
+    fn check(name: &str, code: &[u32], model: ExecutionModel, bindings: &BindingMap) {
+        let module = SpirvModule::new(code).unwrap();
+        let translation = translate(&module, "main", model, bindings, &Options::default()).unwrap();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/msl").join(name).with_extension("metal");
+        if env::var_os("VK_MIDDLE_BLESS").is_some() {
+            File::create(&path).unwrap().write_all(translation.source.as_bytes()).unwrap();
+            return;
+        }
+        let mut expected = String::new();
+        File::open(&path).unwrap().read_to_string(&mut expected).unwrap();
+        assert!(translation.source == expected, "{} differs from {}:\n{}", name, path.display(), translation.source);
+    }
+
+    #[test]
+    fn binding_indices() {
+        let map = BindingMap::new(&[
+            binding(1, 0, DescriptorType::UniformBuffer, 1),
+            binding(0, 3, DescriptorType::Sampler, 2),
+            binding(0, 1, DescriptorType::CombinedImageSampler, 3),
+            binding(0, 0, DescriptorType::StorageBuffer, 2),
+            binding(0, 2, DescriptorType::StorageImage, 1),
+            binding(1, 1, DescriptorType::UniformTexelBuffer, 1),
+        ], true);
+
+        let order: Vec<_> = map.resources().iter().map(|x| (x.set, x.binding)).collect();
+        assert_eq!(order, [(0, 0), (0, 1), (0, 2), (0, 3), (1, 0), (1, 1)]);
+        let indices = |set, binding| {
+            let x = map.resource(set, binding).unwrap();
+            (x.buffer, x.texture, x.sampler)
+        };
+        assert_eq!(indices(0, 0), (Some(0), None, None));
+        assert_eq!(indices(0, 1), (None, Some(0), Some(0)));
+        assert_eq!(indices(0, 2), (None, Some(3), None));
+        assert_eq!(indices(0, 3), (None, None, Some(3)));
+        assert_eq!(indices(1, 0), (Some(2), None, None));
+        assert_eq!(indices(1, 1), (None, Some(4), None));
+        assert!(map.resource(2, 0).is_none());
+        assert_eq!(map.push_constant_buffer(), Some(3));
+        assert_eq!(map.vertex_buffer_base(), 4);
+    }
+
+    #[test]
+    fn binding_indices_without_push_constants() {
+        let map = BindingMap::new(&[binding(0, 0, DescriptorType::UniformBufferDynamic, 1)], false);
+        assert_eq!(map.push_constant_buffer(), None);
+        assert_eq!(map.vertex_buffer_base(), 1);
+        assert_eq!(BindingMap::new(&[], false).vertex_buffer_base(), 0);
+    }
+
+    /// A storage buffer with a runtime array, workgroup memory, a barrier, a function call, an
+    /// atomic and a switch.
+    #[test]
+    fn compute() {
+        let mut a = Assembler::new(44);
+        a.op(Op::Capability, &[1]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[5, 1], "main", &[2]);
+        a.op(Op::ExecutionMode, &[1, 17, 64, 1, 1]);
+        a.op(Op::Decorate, &[2, 11, 28]);
+        a.op(Op::Decorate, &[3, 34, 0]);
+        a.op(Op::Decorate, &[3, 33, 0]);
+        a.op(Op::Decorate, &[4, 3]);
+        a.op(Op::MemberDecorate, &[4, 0, 35, 0]);
+        a.op(Op::MemberDecorate, &[4, 1, 35, 16]);
+        a.op(Op::Decorate, &[5, 6, 4]);
+        a.op(Op::TypeVoid, &[6]);
+        a.op(Op::TypeFunction, &[7, 6]);
+        a.op(Op::TypeInt, &[8, 32, 0]);
+        a.op(Op::TypeInt, &[9, 32, 1]);
+        a.op(Op::TypeVector, &[10, 8, 3]);
+        a.op(Op::TypeRuntimeArray, &[5, 8]);
+        a.op(Op::TypeStruct, &[4, 8, 5]);
+        a.op(Op::TypePointer, &[11, 2, 4]);
+        a.op(Op::Variable, &[11, 3, 2]);
+        a.op(Op::TypePointer, &[12, 1, 10]);
+        a.op(Op::Variable, &[12, 2, 1]);
+        a.op(Op::Constant, &[8, 13, 0]);
+        a.op(Op::Constant, &[8, 14, 1]);
+        a.op(Op::Constant, &[8, 15, 2]);
+        a.op(Op::Constant, &[8, 16, 64]);
+        a.op(Op::Constant, &[9, 17, 1]);
+        a.op(Op::Constant, &[8, 18, 264]);
+        a.op(Op::TypeArray, &[19, 8, 16]);
+        a.op(Op::TypePointer, &[20, 4, 19]);
+        a.op(Op::Variable, &[20, 21, 4]);
+        a.op(Op::TypePointer, &[22, 4, 8]);
+        a.op(Op::TypePointer, &[23, 2, 8]);
+        a.op(Op::TypeFunction, &[24, 8, 8]);
+        a.op(Op::Function, &[8, 25, 0, 24]);
+        a.op(Op::FunctionParameter, &[8, 26]);
+        a.op(Op::Label, &[27]);
+        a.op(Op::AccessChain, &[23, 28, 3, 13]);
+        a.op(Op::AtomicIAdd, &[8, 29, 28, 14, 13, 26]);
+        a.op(Op::ReturnValue, &[29]);
+        a.op(Op::FunctionEnd, &[]);
+        a.op(Op::Function, &[6, 1, 0, 7]);
+        a.op(Op::Label, &[30]);
+        a.op(Op::Load, &[10, 31, 2]);
+        a.op(Op::CompositeExtract, &[8, 32, 31, 0]);
+        a.op(Op::UMod, &[8, 33, 32, 16]);
+        a.op(Op::AccessChain, &[22, 34, 21, 33]);
+        a.op(Op::Store, &[34, 32]);
+        a.op(Op::ControlBarrier, &[15, 15, 18]);
+        a.op(Op::BitwiseAnd, &[8, 35, 32, 14]);
+        a.op(Op::SelectionMerge, &[36, 0]);
+        a.op(Op::Switch, &[35, 37, 0, 38, 1, 39]);
+        a.op(Op::Label, &[38]);
+        a.op(Op::FunctionCall, &[8, 40, 25, 32]);
+        a.op(Op::AccessChain, &[23, 41, 3, 17, 32]);
+        a.op(Op::Store, &[41, 40]);
+        a.op(Op::Branch, &[36]);
+        a.op(Op::Label, &[39]);
+        a.op(Op::ShiftRightArithmetic, &[8, 42, 32, 14]);
+        a.op(Op::AccessChain, &[23, 43, 3, 17, 32]);
+        a.op(Op::Store, &[43, 42]);
+        a.op(Op::Branch, &[36]);
+        a.op(Op::Label, &[37]);
+        a.op(Op::Branch, &[36]);
+        a.op(Op::Label, &[36]);
+        a.op(Op::Return, &[]);
+        a.op(Op::FunctionEnd, &[]);
+        let bindings = BindingMap::new(&[binding(0, 0, DescriptorType::StorageBuffer, 1)], false);
+        check("compute", &a.words(), ExecutionModel::GLCompute, &bindings);
+    }
+
+    /// The `gl_PerVertex` block, a push constant matrix, a built-in input and a flat output.
+    #[test]
+    fn vertex() {
+        let mut a = Assembler::new(30);
+        a.op(Op::Capability, &[1]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[0, 1], "main", &[2, 3, 4, 5]);
+        a.op(Op::Decorate, &[2, 30, 0]);
+        a.op(Op::Decorate, &[5, 30, 1]);
+        a.op(Op::Decorate, &[5, 14]);
+        a.op(Op::Decorate, &[3, 11, 42]);
+        a.op(Op::Decorate, &[6, 2]);
+        a.op(Op::MemberDecorate, &[6, 0, 11, 0]);
+        a.op(Op::MemberDecorate, &[6, 1, 11, 1]);
+        a.op(Op::Decorate, &[7, 2]);
+        a.op(Op::MemberDecorate, &[7, 0, 35, 0]);
+        a.op(Op::MemberDecorate, &[7, 0, 5]);
+        a.op(Op::MemberDecorate, &[7, 0, 7, 16]);
+        a.op(Op::TypeVoid, &[8]);
+        a.op(Op::TypeFunction, &[9, 8]);
+        a.op(Op::TypeFloat, &[10, 32]);
+        a.op(Op::TypeInt, &[11, 32, 1]);
+        a.op(Op::TypeVector, &[12, 10, 4]);
+        a.op(Op::TypeMatrix, &[13, 12, 4]);
+        a.op(Op::TypeStruct, &[6, 12, 10]);
+        a.op(Op::TypePointer, &[14, 3, 6]);
+        a.op(Op::Variable, &[14, 4, 3]);
+        a.op(Op::TypeStruct, &[7, 13]);
+        a.op(Op::TypePointer, &[15, 9, 7]);
+        a.op(Op::Variable, &[15, 16, 9]);
+        a.op(Op::TypePointer, &[17, 1, 12]);
+        a.op(Op::Variable, &[17, 2, 1]);
+        a.op(Op::TypePointer, &[18, 1, 11]);
+        a.op(Op::Variable, &[18, 3, 1]);
+        a.op(Op::TypePointer, &[19, 3, 11]);
+        a.op(Op::Variable, &[19, 5, 3]);
+        a.op(Op::TypePointer, &[20, 9, 13]);
+        a.op(Op::TypePointer, &[21, 3, 12]);
+        a.op(Op::Constant, &[11, 22, 0]);
+        a.op(Op::Function, &[8, 1, 0, 9]);
+        a.op(Op::Label, &[23]);
+        a.op(Op::AccessChain, &[20, 24, 16, 22]);
+        a.op(Op::Load, &[13, 25, 24]);
+        a.op(Op::Load, &[12, 26, 2]);
+        a.op(Op::MatrixTimesVector, &[12, 27, 25, 26]);
+        a.op(Op::AccessChain, &[21, 28, 4, 22]);
+        a.op(Op::Store, &[28, 27]);
+        a.op(Op::Load, &[11, 29, 3]);
+        a.op(Op::Store, &[5, 29]);
+        a.op(Op::Return, &[]);
+        a.op(Op::FunctionEnd, &[]);
+        let bindings = BindingMap::new(&[], true);
+        check("vertex", &a.words(), ExecutionModel::Vertex, &bindings);
+    }
+
+    /// A combined image sampler, a uniform buffer, a loop with phis, a selection nested in it and
+    /// an extended instruction.
+    #[test]
+    fn fragment() {
+        let mut a = Assembler::new(59);
+        a.op(Op::Capability, &[1]);
+        a.op_string(Op::ExtInstImport, &[1], "GLSL.std.450", &[]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[4, 2], "main", &[3, 4, 5]);
+        a.op(Op::ExecutionMode, &[2, 7]);
+        a.op(Op::Decorate, &[3, 30, 0]);
+        a.op(Op::Decorate, &[4, 30, 0]);
+        a.op(Op::Decorate, &[5, 11, 15]);
+        a.op(Op::Decorate, &[6, 34, 0]);
+        a.op(Op::Decorate, &[6, 33, 1]);
+        a.op(Op::Decorate, &[7, 34, 0]);
+        a.op(Op::Decorate, &[7, 33, 0]);
+        a.op(Op::Decorate, &[8, 2]);
+        a.op(Op::MemberDecorate, &[8, 0, 35, 0]);
+        a.op(Op::MemberDecorate, &[8, 1, 35, 12]);
+        a.op(Op::MemberDecorate, &[8, 2, 35, 16]);
+        a.op(Op::TypeVoid, &[9]);
+        a.op(Op::TypeFunction, &[10, 9]);
+        a.op(Op::TypeFloat, &[11, 32]);
+        a.op(Op::TypeInt, &[12, 32, 1]);
+        a.op(Op::TypeBool, &[13]);
+        a.op(Op::TypeVector, &[14, 11, 2]);
+        a.op(Op::TypeVector, &[15, 11, 3]);
+        a.op(Op::TypeVector, &[16, 11, 4]);
+        a.op(Op::TypeStruct, &[8, 15, 11, 12]);
+        a.op(Op::TypePointer, &[17, 2, 8]);
+        a.op(Op::Variable, &[17, 7, 2]);
+        a.op(Op::TypeImage, &[18, 11, 1, 0, 0, 0, 1, 0]);
+        a.op(Op::TypeSampledImage, &[19, 18]);
+        a.op(Op::TypePointer, &[20, 0, 19]);
+        a.op(Op::Variable, &[20, 6, 0]);
+        a.op(Op::TypePointer, &[21, 1, 14]);
+        a.op(Op::Variable, &[21, 3, 1]);
+        a.op(Op::TypePointer, &[22, 1, 16]);
+        a.op(Op::Variable, &[22, 5, 1]);
+        a.op(Op::TypePointer, &[23, 3, 16]);
+        a.op(Op::Variable, &[23, 4, 3]);
+        a.op(Op::TypePointer, &[24, 2, 11]);
+        a.op(Op::TypePointer, &[25, 2, 12]);
+        a.op(Op::Constant, &[12, 26, 0]);
+        a.op(Op::Constant, &[12, 27, 1]);
+        a.op(Op::Constant, &[12, 28, 2]);
+        a.op(Op::Constant, &[11, 29, 0.0f32.to_bits()]);
+        a.op(Op::Constant, &[11, 30, 0.5f32.to_bits()]);
+        a.op(Op::ConstantComposite, &[16, 31, 30, 30, 30, 29]);
+        a.op(Op::Function, &[9, 2, 0, 10]);
+        a.op(Op::Label, &[32]);
+        a.op(Op::Load, &[14, 33, 3]);
+        a.op(Op::Load, &[19, 34, 6]);
+        a.op(Op::ImageSampleImplicitLod, &[16, 35, 34, 33]);
+        a.op(Op::AccessChain, &[25, 36, 7, 28]);
+        a.op(Op::Load, &[12, 37, 36]);
+        a.op(Op::Branch, &[38]);
+        a.op(Op::Label, &[38]);
+        a.op(Op::Phi, &[12, 39, 26, 32, 40, 41]);
+        a.op(Op::Phi, &[16, 42, 35, 32, 43, 41]);
+        a.op(Op::LoopMerge, &[44, 41, 0]);
+        a.op(Op::Branch, &[45]);
+        a.op(Op::Label, &[45]);
+        a.op(Op::SLessThan, &[13, 46, 39, 37]);
+        a.op(Op::BranchConditional, &[46, 47, 44]);
+        a.op(Op::Label, &[47]);
+        a.op(Op::AccessChain, &[24, 48, 7, 27]);
+        a.op(Op::Load, &[11, 49, 48]);
+        a.op(Op::VectorTimesScalar, &[16, 50, 42, 49]);
+        a.op(Op::FOrdGreaterThan, &[13, 51, 49, 30]);
+        a.op(Op::SelectionMerge, &[52, 0]);
+        a.op(Op::BranchConditional, &[51, 53, 52]);
+        a.op(Op::Label, &[53]);
+        a.op(Op::FAdd, &[16, 54, 50, 31]);
+        a.op(Op::Branch, &[52]);
+        a.op(Op::Label, &[52]);
+        a.op(Op::Phi, &[16, 43, 54, 53, 50, 47]);
+        a.op(Op::Branch, &[41]);
+        a.op(Op::Label, &[41]);
+        a.op(Op::IAdd, &[12, 40, 39, 27]);
+        a.op(Op::Branch, &[38]);
+        a.op(Op::Label, &[44]);
+        a.op(Op::Load, &[16, 55, 5]);
+        a.op(Op::VectorShuffle, &[14, 56, 55, 55, 0, 1]);
+        a.op(Op::ExtInst, &[14, 57, 1, 69, 56]);
+        a.op(Op::VectorShuffle, &[16, 58, 42, 57, 4, 5, 2, 3]);
+        a.op(Op::Store, &[4, 58]);
+        a.op(Op::Return, &[]);
+        a.op(Op::FunctionEnd, &[]);
+        let bindings = BindingMap::new(&[
+            binding(0, 0, DescriptorType::UniformBuffer, 1),
+            binding(0, 1, DescriptorType::CombinedImageSampler, 1),
+        ], false);
+        check("fragment", &a.words(), ExecutionModel::Fragment, &bindings);
+    }
+}
@@ -0,0 +1,675 @@
+//! Enumerants from the SPIR-V specification and the `GLSL.std.450` extended instruction set.
+//!
+//! Every enum has an `Other` case for values it does not name, so decoding a word never fails;
+//! whether a value is supported is up to whoever reads it.
+
+macro_rules! spirv_enum {
+    ($(#[$attr:meta])* enum $name:ident {
+        $($case:ident = $value:tt,)*
+    }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum $name {
+            $($case,)*
+            Other(u32),
+        }
+
+        impl From<u32> for $name {
+            fn from(raw: u32) -> $name {
+                match raw {
+                    $($value => $name::$case,)*
+                    other => $name::Other(other),
+                }
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(value: $name) -> u32 {
+                match value {
+                    $($name::$case => $value,)*
+                    $name::Other(other) => other,
+                }
+            }
+        }
+    }
+}
+
+spirv_enum! {
+    /// An instruction opcode. Opcodes only OpenCL kernels use are left to `Other`.
+    enum Op {
+        Nop = 0,
+        Undef = 1,
+        SourceContinued = 2,
+        Source = 3,
+        SourceExtension = 4,
+        Name = 5,
+        MemberName = 6,
+        String = 7,
+        Line = 8,
+        Extension = 10,
+        ExtInstImport = 11,
+        ExtInst = 12,
+        MemoryModel = 14,
+        EntryPoint = 15,
+        ExecutionMode = 16,
+        Capability = 17,
+        TypeVoid = 19,
+        TypeBool = 20,
+        TypeInt = 21,
+        TypeFloat = 22,
+        TypeVector = 23,
+        TypeMatrix = 24,
+        TypeImage = 25,
+        TypeSampler = 26,
+        TypeSampledImage = 27,
+        TypeArray = 28,
+        TypeRuntimeArray = 29,
+        TypeStruct = 30,
+        TypePointer = 32,
+        TypeFunction = 33,
+        ConstantTrue = 41,
+        ConstantFalse = 42,
+        Constant = 43,
+        ConstantComposite = 44,
+        ConstantNull = 46,
+        SpecConstantTrue = 48,
+        SpecConstantFalse = 49,
+        SpecConstant = 50,
+        SpecConstantComposite = 51,
+        SpecConstantOp = 52,
+        Function = 54,
+        FunctionParameter = 55,
+        FunctionEnd = 56,
+        FunctionCall = 57,
+        Variable = 59,
+        ImageTexelPointer = 60,
+        Load = 61,
+        Store = 62,
+        CopyMemory = 63,
+        AccessChain = 65,
+        InBoundsAccessChain = 66,
+        PtrAccessChain = 67,
+        ArrayLength = 68,
+        InBoundsPtrAccessChain = 70,
+        Decorate = 71,
+        MemberDecorate = 72,
+        DecorationGroup = 73,
+        GroupDecorate = 74,
+        GroupMemberDecorate = 75,
+        VectorExtractDynamic = 77,
+        VectorInsertDynamic = 78,
+        VectorShuffle = 79,
+        CompositeConstruct = 80,
+        CompositeExtract = 81,
+        CompositeInsert = 82,
+        CopyObject = 83,
+        Transpose = 84,
+        SampledImage = 86,
+        ImageSampleImplicitLod = 87,
+        ImageSampleExplicitLod = 88,
+        ImageSampleDrefImplicitLod = 89,
+        ImageSampleDrefExplicitLod = 90,
+        ImageSampleProjImplicitLod = 91,
+        ImageSampleProjExplicitLod = 92,
+        ImageSampleProjDrefImplicitLod = 93,
+        ImageSampleProjDrefExplicitLod = 94,
+        ImageFetch = 95,
+        ImageGather = 96,
+        ImageDrefGather = 97,
+        ImageRead = 98,
+        ImageWrite = 99,
+        Image = 100,
+        ImageQueryFormat = 101,
+        ImageQueryOrder = 102,
+        ImageQuerySizeLod = 103,
+        ImageQuerySize = 104,
+        ImageQueryLod = 105,
+        ImageQueryLevels = 106,
+        ImageQuerySamples = 107,
+        ConvertFToU = 109,
+        ConvertFToS = 110,
+        ConvertSToF = 111,
+        ConvertUToF = 112,
+        UConvert = 113,
+        SConvert = 114,
+        FConvert = 115,
+        QuantizeToF16 = 116,
+        Bitcast = 124,
+        SNegate = 126,
+        FNegate = 127,
+        IAdd = 128,
+        FAdd = 129,
+        ISub = 130,
+        FSub = 131,
+        IMul = 132,
+        FMul = 133,
+        UDiv = 134,
+        SDiv = 135,
+        FDiv = 136,
+        UMod = 137,
+        SRem = 138,
+        SMod = 139,
+        FRem = 140,
+        FMod = 141,
+        VectorTimesScalar = 142,
+        MatrixTimesScalar = 143,
+        VectorTimesMatrix = 144,
+        MatrixTimesVector = 145,
+        MatrixTimesMatrix = 146,
+        OuterProduct = 147,
+        Dot = 148,
+        IAddCarry = 149,
+        ISubBorrow = 150,
+        UMulExtended = 151,
+        SMulExtended = 152,
+        Any = 154,
+        All = 155,
+        IsNan = 156,
+        IsInf = 157,
+        IsFinite = 158,
+        IsNormal = 159,
+        SignBitSet = 160,
+        LessOrGreater = 161,
+        Ordered = 162,
+        Unordered = 163,
+        LogicalEqual = 164,
+        LogicalNotEqual = 165,
+        LogicalOr = 166,
+        LogicalAnd = 167,
+        LogicalNot = 168,
+        Select = 169,
+        IEqual = 170,
+        INotEqual = 171,
+        UGreaterThan = 172,
+        SGreaterThan = 173,
+        UGreaterThanEqual = 174,
+        SGreaterThanEqual = 175,
+        ULessThan = 176,
+        SLessThan = 177,
+        ULessThanEqual = 178,
+        SLessThanEqual = 179,
+        FOrdEqual = 180,
+        FUnordEqual = 181,
+        FOrdNotEqual = 182,
+        FUnordNotEqual = 183,
+        FOrdLessThan = 184,
+        FUnordLessThan = 185,
+        FOrdGreaterThan = 186,
+        FUnordGreaterThan = 187,
+        FOrdLessThanEqual = 188,
+        FUnordLessThanEqual = 189,
+        FOrdGreaterThanEqual = 190,
+        FUnordGreaterThanEqual = 191,
+        ShiftRightLogical = 194,
+        ShiftRightArithmetic = 195,
+        ShiftLeftLogical = 196,
+        BitwiseOr = 197,
+        BitwiseXor = 198,
+        BitwiseAnd = 199,
+        Not = 200,
+        BitFieldInsert = 201,
+        BitFieldSExtract = 202,
+        BitFieldUExtract = 203,
+        BitReverse = 204,
+        BitCount = 205,
+        DPdx = 207,
+        DPdy = 208,
+        Fwidth = 209,
+        DPdxFine = 210,
+        DPdyFine = 211,
+        FwidthFine = 212,
+        DPdxCoarse = 213,
+        DPdyCoarse = 214,
+        FwidthCoarse = 215,
+        EmitVertex = 218,
+        EndPrimitive = 219,
+        EmitStreamVertex = 220,
+        EndStreamPrimitive = 221,
+        ControlBarrier = 224,
+        MemoryBarrier = 225,
+        AtomicLoad = 227,
+        AtomicStore = 228,
+        AtomicExchange = 229,
+        AtomicCompareExchange = 230,
+        AtomicCompareExchangeWeak = 231,
+        AtomicIIncrement = 232,
+        AtomicIDecrement = 233,
+        AtomicIAdd = 234,
+        AtomicISub = 235,
+        AtomicSMin = 236,
+        AtomicUMin = 237,
+        AtomicSMax = 238,
+        AtomicUMax = 239,
+        AtomicAnd = 240,
+        AtomicOr = 241,
+        AtomicXor = 242,
+        Phi = 245,
+        LoopMerge = 246,
+        SelectionMerge = 247,
+        Label = 248,
+        Branch = 249,
+        BranchConditional = 250,
+        Switch = 251,
+        Kill = 252,
+        Return = 253,
+        ReturnValue = 254,
+        Unreachable = 255,
+        NoLine = 317,
+        ModuleProcessed = 330,
+        ExecutionModeId = 331,
+        DecorateId = 332,
+    }
+}
+
+spirv_enum! {
+    enum Capability {
+        Matrix = 0,
+        Shader = 1,
+        Geometry = 2,
+        Tessellation = 3,
+        Addresses = 4,
+        Linkage = 5,
+        Kernel = 6,
+        Vector16 = 7,
+        Float16Buffer = 8,
+        Float16 = 9,
+        Float64 = 10,
+        Int64 = 11,
+        Int64Atomics = 12,
+        ImageBasic = 13,
+        ImageReadWrite = 14,
+        ImageMipmap = 15,
+        Pipes = 17,
+        Groups = 18,
+        DeviceEnqueue = 19,
+        LiteralSampler = 20,
+        AtomicStorage = 21,
+        Int16 = 22,
+        TessellationPointSize = 23,
+        GeometryPointSize = 24,
+        ImageGatherExtended = 25,
+        StorageImageMultisample = 27,
+        UniformBufferArrayDynamicIndexing = 28,
+        SampledImageArrayDynamicIndexing = 29,
+        StorageBufferArrayDynamicIndexing = 30,
+        StorageImageArrayDynamicIndexing = 31,
+        ClipDistance = 32,
+        CullDistance = 33,
+        ImageCubeArray = 34,
+        SampleRateShading = 35,
+        ImageRect = 36,
+        SampledRect = 37,
+        GenericPointer = 38,
+        Int8 = 39,
+        InputAttachment = 40,
+        SparseResidency = 41,
+        MinLod = 42,
+        Sampled1D = 43,
+        Image1D = 44,
+        SampledCubeArray = 45,
+        SampledBuffer = 46,
+        ImageBuffer = 47,
+        ImageMSArray = 48,
+        StorageImageExtendedFormats = 49,
+        ImageQuery = 50,
+        DerivativeControl = 51,
+        InterpolationFunction = 52,
+        TransformFeedback = 53,
+        GeometryStreams = 54,
+        StorageImageReadWithoutFormat = 55,
+        StorageImageWriteWithoutFormat = 56,
+        MultiViewport = 57,
+        SubgroupDispatch = 58,
+        NamedBarrier = 59,
+        PipeStorage = 60,
+        GroupNonUniform = 61,
+        GroupNonUniformVote = 62,
+        GroupNonUniformArithmetic = 63,
+        GroupNonUniformBallot = 64,
+        GroupNonUniformShuffle = 65,
+        GroupNonUniformShuffleRelative = 66,
+        GroupNonUniformClustered = 67,
+        GroupNonUniformQuad = 68,
+        ShaderLayer = 69,
+        ShaderViewportIndex = 70,
+    }
+}
+
+spirv_enum! {
+    enum ExecutionModel {
+        Vertex = 0,
+        TessellationControl = 1,
+        TessellationEvaluation = 2,
+        Geometry = 3,
+        Fragment = 4,
+        GLCompute = 5,
+        Kernel = 6,
+    }
+}
+
+spirv_enum! {
+    enum ExecutionMode {
+        Invocations = 0,
+        SpacingEqual = 1,
+        SpacingFractionalEven = 2,
+        SpacingFractionalOdd = 3,
+        VertexOrderCw = 4,
+        VertexOrderCcw = 5,
+        PixelCenterInteger = 6,
+        OriginUpperLeft = 7,
+        OriginLowerLeft = 8,
+        EarlyFragmentTests = 9,
+        PointMode = 10,
+        Xfb = 11,
+        DepthReplacing = 12,
+        DepthGreater = 14,
+        DepthLess = 15,
+        DepthUnchanged = 16,
+        LocalSize = 17,
+        LocalSizeHint = 18,
+        InputPoints = 19,
+        InputLines = 20,
+        InputLinesAdjacency = 21,
+        Triangles = 22,
+        InputTrianglesAdjacency = 23,
+        Quads = 24,
+        Isolines = 25,
+        OutputVertices = 26,
+        OutputPoints = 27,
+        OutputLineStrip = 28,
+        OutputTriangleStrip = 29,
+        VecTypeHint = 30,
+        ContractionOff = 31,
+        Initializer = 33,
+        Finalizer = 34,
+        SubgroupSize = 35,
+        SubgroupsPerWorkgroup = 36,
+        SubgroupsPerWorkgroupId = 37,
+        LocalSizeId = 38,
+        LocalSizeHintId = 39,
+    }
+}
+
+spirv_enum! {
+    enum StorageClass {
+        UniformConstant = 0,
+        Input = 1,
+        Uniform = 2,
+        Output = 3,
+        Workgroup = 4,
+        CrossWorkgroup = 5,
+        Private = 6,
+        Function = 7,
+        Generic = 8,
+        PushConstant = 9,
+        AtomicCounter = 10,
+        Image = 11,
+        StorageBuffer = 12,
+    }
+}
+
+spirv_enum! {
+    enum Dim {
+        D1 = 0,
+        D2 = 1,
+        D3 = 2,
+        Cube = 3,
+        Rect = 4,
+        Buffer = 5,
+        SubpassData = 6,
+    }
+}
+
+spirv_enum! {
+    enum ImageFormat {
+        Unknown = 0,
+        Rgba32f = 1,
+        Rgba16f = 2,
+        R32f = 3,
+        Rgba8 = 4,
+        Rgba8Snorm = 5,
+        Rg32f = 6,
+        Rg16f = 7,
+        R11fG11fB10f = 8,
+        R16f = 9,
+        Rgba16 = 10,
+        Rgb10A2 = 11,
+        Rg16 = 12,
+        Rg8 = 13,
+        R16 = 14,
+        R8 = 15,
+        Rgba16Snorm = 16,
+        Rg16Snorm = 17,
+        Rg8Snorm = 18,
+        R16Snorm = 19,
+        R8Snorm = 20,
+        Rgba32i = 21,
+        Rgba16i = 22,
+        Rgba8i = 23,
+        R32i = 24,
+        Rg32i = 25,
+        Rg16i = 26,
+        Rg8i = 27,
+        R16i = 28,
+        R8i = 29,
+        Rgba32ui = 30,
+        Rgba16ui = 31,
+        Rgba8ui = 32,
+        R32ui = 33,
+        Rgb10a2ui = 34,
+        Rg32ui = 35,
+        Rg16ui = 36,
+        Rg8ui = 37,
+        R16ui = 38,
+        R8ui = 39,
+    }
+}
+
+spirv_enum! {
+    enum Decoration {
+        RelaxedPrecision = 0,
+        SpecId = 1,
+        Block = 2,
+        BufferBlock = 3,
+        RowMajor = 4,
+        ColMajor = 5,
+        ArrayStride = 6,
+        MatrixStride = 7,
+        GLSLShared = 8,
+        GLSLPacked = 9,
+        CPacked = 10,
+        BuiltIn = 11,
+        NoPerspective = 13,
+        Flat = 14,
+        Patch = 15,
+        Centroid = 16,
+        Sample = 17,
+        Invariant = 18,
+        Restrict = 19,
+        Aliased = 20,
+        Volatile = 21,
+        Constant = 22,
+        Coherent = 23,
+        NonWritable = 24,
+        NonReadable = 25,
+        Uniform = 26,
+        UniformId = 27,
+        SaturatedConversion = 28,
+        Stream = 29,
+        Location = 30,
+        Component = 31,
+        Index = 32,
+        Binding = 33,
+        DescriptorSet = 34,
+        Offset = 35,
+        XfbBuffer = 36,
+        XfbStride = 37,
+        FuncParamAttr = 38,
+        FPRoundingMode = 39,
+        FPFastMathMode = 40,
+        LinkageAttributes = 41,
+        NoContraction = 42,
+        InputAttachmentIndex = 43,
+        Alignment = 44,
+        MaxByteOffset = 45,
+        AlignmentId = 46,
+        MaxByteOffsetId = 47,
+    }
+}
+
+spirv_enum! {
+    enum BuiltIn {
+        Position = 0,
+        PointSize = 1,
+        ClipDistance = 3,
+        CullDistance = 4,
+        VertexId = 5,
+        InstanceId = 6,
+        PrimitiveId = 7,
+        InvocationId = 8,
+        Layer = 9,
+        ViewportIndex = 10,
+        TessLevelOuter = 11,
+        TessLevelInner = 12,
+        TessCoord = 13,
+        PatchVertices = 14,
+        FragCoord = 15,
+        PointCoord = 16,
+        FrontFacing = 17,
+        SampleId = 18,
+        SamplePosition = 19,
+        SampleMask = 20,
+        FragDepth = 22,
+        HelperInvocation = 23,
+        NumWorkgroups = 24,
+        WorkgroupSize = 25,
+        WorkgroupId = 26,
+        LocalInvocationId = 27,
+        GlobalInvocationId = 28,
+        LocalInvocationIndex = 29,
+        WorkDim = 30,
+        GlobalSize = 31,
+        EnqueuedWorkgroupSize = 32,
+        GlobalOffset = 33,
+        GlobalLinearId = 34,
+        SubgroupSize = 36,
+        SubgroupMaxSize = 37,
+        NumSubgroups = 38,
+        NumEnqueuedSubgroups = 39,
+        SubgroupId = 40,
+        SubgroupLocalInvocationId = 41,
+        VertexIndex = 42,
+        InstanceIndex = 43,
+        SubgroupEqMask = 4416,
+        SubgroupGeMask = 4417,
+        SubgroupGtMask = 4418,
+        SubgroupLeMask = 4419,
+        SubgroupLtMask = 4420,
+        BaseVertex = 4424,
+        BaseInstance = 4425,
+        DrawIndex = 4426,
+    }
+}
+
+spirv_enum! {
+    /// An instruction of the `GLSL.std.450` extended instruction set.
+    enum Glsl450 {
+        Round = 1,
+        RoundEven = 2,
+        Trunc = 3,
+        FAbs = 4,
+        SAbs = 5,
+        FSign = 6,
+        SSign = 7,
+        Floor = 8,
+        Ceil = 9,
+        Fract = 10,
+        Radians = 11,
+        Degrees = 12,
+        Sin = 13,
+        Cos = 14,
+        Tan = 15,
+        Asin = 16,
+        Acos = 17,
+        Atan = 18,
+        Sinh = 19,
+        Cosh = 20,
+        Tanh = 21,
+        Asinh = 22,
+        Acosh = 23,
+        Atanh = 24,
+        Atan2 = 25,
+        Pow = 26,
+        Exp = 27,
+        Log = 28,
+        Exp2 = 29,
+        Log2 = 30,
+        Sqrt = 31,
+        InverseSqrt = 32,
+        Determinant = 33,
+        MatrixInverse = 34,
+        Modf = 35,
+        ModfStruct = 36,
+        FMin = 37,
+        UMin = 38,
+        SMin = 39,
+        FMax = 40,
+        UMax = 41,
+        SMax = 42,
+        FClamp = 43,
+        UClamp = 44,
+        SClamp = 45,
+        FMix = 46,
+        IMix = 47,
+        Step = 48,
+        SmoothStep = 49,
+        Fma = 50,
+        Frexp = 51,
+        FrexpStruct = 52,
+        Ldexp = 53,
+        PackSnorm4x8 = 54,
+        PackUnorm4x8 = 55,
+        PackSnorm2x16 = 56,
+        PackUnorm2x16 = 57,
+        PackHalf2x16 = 58,
+        PackDouble2x32 = 59,
+        UnpackSnorm2x16 = 60,
+        UnpackUnorm2x16 = 61,
+        UnpackHalf2x16 = 62,
+        UnpackSnorm4x8 = 63,
+        UnpackUnorm4x8 = 64,
+        UnpackDouble2x32 = 65,
+        Length = 66,
+        Distance = 67,
+        Cross = 68,
+        Normalize = 69,
+        FaceForward = 70,
+        Reflect = 71,
+        Refract = 72,
+        FindILsb = 73,
+        FindSMsb = 74,
+        FindUMsb = 75,
+        InterpolateAtCentroid = 76,
+        InterpolateAtSample = 77,
+        InterpolateAtOffset = 78,
+        NMin = 79,
+        NMax = 80,
+        NClamp = 81,
+    }
+}
+
+/// Bits of the mask that starts the optional operands of image instructions, in the order the
+/// operands they enable follow it.
+pub mod image_operands {
+    pub const BIAS: u32 = 0x01;
+    pub const LOD: u32 = 0x02;
+    pub const GRAD: u32 = 0x04;
+    pub const CONST_OFFSET: u32 = 0x08;
+    pub const OFFSET: u32 = 0x10;
+    pub const CONST_OFFSETS: u32 = 0x20;
+    pub const SAMPLE: u32 = 0x40;
+    pub const MIN_LOD: u32 = 0x80;
+}
+
+/// The execution scope of `OpControlBarrier` that synchronizes a workgroup.
+pub const SCOPE_WORKGROUP: u32 = 2;
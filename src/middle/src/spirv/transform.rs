@@ -0,0 +1,1136 @@
+//! Transformations of SPIR-V modules, for backends that would rather translate a module made
+//! smaller and specific to one pipeline stage than the one the application created.
+//!
+//! Every pass takes a module's words and returns new ones, so passes can run on their own or one
+//! after another; `prepare` runs all of them for one stage. `specialize` gives specialization
+//! constants the values a pipeline provides, `fold_constants` evaluates whatever only depends on
+//! constants, `strip` removes every entry point but one along with the functions only the others
+//! call and all debug instructions, and `eliminate_dead_code` removes unreachable blocks and
+//! anything whose result nothing uses.
+//!
+//! None of the passes restructure control flow beyond what constants decide. A conditional branch
+//! on a constant becomes an `OpBranch`, dropping the `OpSelectionMerge` of the selection it headed,
+//! a switch on a constant keeps its merge instruction and only its default target, and merge and
+//! continue blocks of constructs still there that can no longer be reached are emptied rather than
+//! removed, so the output is structured wherever the input was.
+
+use icd::{Error, Result, SpecializationMapEntry};
+use super::*;
+use super::interpret::{self, Program, Type, Value};
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::{mem, ptr};
+
+/// The most operands an instruction can have, since its word count, which includes the opcode,
+/// is 16 bits.
+const MAX_OPERANDS: usize = 0xFFFE;
+
+/// Runs every pass for the entry point `name` of a pipeline stage, specialized with the map
+/// entries and data of the stage's `VkSpecializationInfo`.
+pub fn prepare(
+    code: &[u32],
+    name: &str,
+    model: ExecutionModel,
+    map_entries: &[SpecializationMapEntry],
+    data: &[u8],
+) -> Result<Vec<u32>> {
+    // Stripping comes before folding, which needs the interpreter to accept the module, so that
+    // entry points the stage does not use cannot get in the way.
+    let code = try!(specialize(code, map_entries, data));
+    let code = try!(strip(&code, name, model));
+    let code = try!(fold_constants(&code));
+    eliminate_dead_code(&code)
+}
+
+/// Turns every specialization constant into an ordinary constant: the value a map entry gives it
+/// if there is one for its `SpecId`, and its default otherwise. `OpSpecConstantOp`s are left for
+/// `fold_constants` to evaluate.
+pub fn specialize(code: &[u32], map_entries: &[SpecializationMapEntry], data: &[u8]) -> Result<Vec<u32>> {
+    let mut module = try!(Module::parse(code));
+    let (decorations, _) = decorations(&try!(instructions(code)));
+
+    for inst in &mut module.globals {
+        let size = match inst.op {
+            Op::SpecConstantTrue | Op::SpecConstantFalse => 4,
+            Op::SpecConstant => (inst.operands.len() - 2) * 4,
+            Op::SpecConstantComposite => {
+                inst.op = Op::ConstantComposite;
+                continue;
+            },
+            _ => continue,
+        };
+        let entry = decorations.get(&inst.operands[1])
+            .and_then(|x| x.spec_id)
+            .and_then(|id| map_entries.iter().find(|x| x.constant_id == id));
+        let bytes = match entry {
+            Some(entry) => Some(try!(entry_data(entry, data, size))),
+            None => None,
+        };
+        match inst.op {
+            Op::SpecConstant => {
+                inst.op = Op::Constant;
+                if let Some(bytes) = bytes {
+                    inst.operands.truncate(2);
+                    inst.operands.extend(bytes.chunks(4).map(word));
+                }
+            },
+            op => {
+                let value = match bytes {
+                    Some(bytes) => word(bytes) != 0,
+                    None => op == Op::SpecConstantTrue,
+                };
+                inst.op = if value { Op::ConstantTrue } else { Op::ConstantFalse };
+            },
+        }
+    }
+
+    // `SpecId` is only allowed on specialization constants.
+    let spec_id = u32::from(Decoration::SpecId);
+    module.globals.retain(|x| !(x.op == Op::Decorate && x.operands.get(1) == Some(&spec_id)));
+    Ok(module.assemble())
+}
+
+/// The bytes of `data` a map entry points at, which have to be as many as the constant takes.
+fn entry_data<'a>(entry: &SpecializationMapEntry, data: &'a [u8], size: usize) -> Result<&'a [u8]> {
+    let start = entry.offset as usize;
+    if entry.size != size || start + size > data.len() {
+        error!(
+            "specialization map entry for constant {} has {} bytes at offset {} of {}, but the constant takes {}",
+            entry.constant_id, entry.size, entry.offset, data.len(), size,
+        );
+        return Err(Error::InitializationFailed);
+    }
+    Ok(&data[start..(start + size)])
+}
+
+/// A word in host byte order, as specialization data holds them.
+fn word(bytes: &[u8]) -> u32 {
+    unsafe { ptr::read_unaligned(bytes.as_ptr() as *const u32) }
+}
+
+/// Replaces instructions whose operands are all constants with constants holding their results,
+/// `OpSpecConstantOp`s included, and makes conditional branches and switches on constants go to
+/// the only block they can.
+///
+/// Results are computed by the interpreter, so modules it cannot load are rejected with the same
+/// errors. Specialization constants are not treated as constants, since their values are not known
+/// until `specialize`.
+pub fn fold_constants(code: &[u32]) -> Result<Vec<u32>> {
+    let program = try!(Program::new(code));
+    let mut module = try!(Module::parse(code));
+    let mut folder = Folder {
+        program: &program,
+        values: HashMap::new(),
+        scalars: HashMap::new(),
+        composites: HashMap::new(),
+    };
+
+    // Specialization constant operations are replaced where they are, since later declarations
+    // can use them.
+    for inst in mem::replace(&mut module.globals, Vec::new()) {
+        match inst.op {
+            Op::ConstantTrue | Op::ConstantFalse | Op::Constant | Op::ConstantComposite | Op::ConstantNull | Op::Undef => {
+                folder.record(&inst);
+            },
+            Op::SpecConstantOp => {
+                let o = &inst.operands;
+                let mut definition = Vec::new();
+                let folded = match folder.fold(Op::from(o[2]), o[0], &o[3..]) {
+                    Some(value) => folder.define(&mut module, o[0], o[1], &value, &mut definition),
+                    None => false,
+                };
+                if folded {
+                    module.globals.extend(definition);
+                    continue;
+                }
+            },
+            _ => {},
+        }
+        module.globals.push(inst);
+    }
+
+    // Constants from function bodies go at the end of the global section, where everything they
+    // depend on has been declared.
+    let mut hoisted = Vec::new();
+    let mut folded = HashSet::new();
+    for f in 0..module.functions.len() {
+        let function = mem::replace(&mut module.functions[f], Vec::new());
+        let mut body = Vec::with_capacity(function.len());
+        for inst in function {
+            if result_index(inst.op) == Some(1) && inst.operands.len() >= 2 {
+                let (ty, id) = (inst.operands[0], inst.operands[1]);
+                if let Some(value) = folder.fold(inst.op, ty, &inst.operands[2..]) {
+                    if folder.define(&mut module, ty, id, &value, &mut hoisted) {
+                        folded.insert(id);
+                        continue;
+                    }
+                }
+            }
+            body.push(inst);
+        }
+        module.functions[f] = body;
+    }
+    module.globals.extend(hoisted);
+
+    let wide = &module.wide;
+    for function in &mut module.functions {
+        let mut resolved = false;
+        let mut merges = Vec::new();
+        for i in 0..function.len() {
+            let target = {
+                let inst = &function[i];
+                match inst.op {
+                    Op::BranchConditional => {
+                        folder.values.get(&inst.operands[0])
+                            .map(|x| if x.scalar() != 0 { inst.operands[1] } else { inst.operands[2] })
+                    },
+                    // The interpreter only has 32-bit values, so a 64-bit selector is never folded.
+                    Op::Switch if inst.operands.len() > 2 && !wide.contains(&inst.operands[0]) => {
+                        folder.values.get(&inst.operands[0]).map(|x| {
+                            let selector = [x.scalar()];
+                            switch_cases(inst, wide)
+                                .find(|&(literal, _)| literal == &selector[..])
+                                .map(|(_, target)| target)
+                                .unwrap_or(inst.operands[1])
+                        })
+                    },
+                    _ => None,
+                }
+            };
+            if let Some(target) = target {
+                if function[i].op == Op::Switch {
+                    let condition = function[i].operands[0];
+                    function[i].operands = vec![condition, target];
+                } else {
+                    // An `OpSelectionMerge` can only come before a conditional branch or a switch,
+                    // where an `OpLoopMerge` can come before an `OpBranch` as well.
+                    if i > 0 && function[i - 1].op == Op::SelectionMerge {
+                        merges.push(i - 1);
+                    }
+                    function[i] = Inst::new(Op::Branch, vec![target]);
+                }
+                resolved = true;
+            }
+        }
+        for &i in merges.iter().rev() {
+            function.remove(i);
+        }
+        if resolved {
+            prune_phis(function, wide);
+        }
+    }
+
+    // Decorations like `NoContraction` are not allowed on constants.
+    module.globals.retain(|x| match x.op {
+        Op::Decorate | Op::DecorateId => !folded.contains(&x.operands[0]),
+        _ => true,
+    });
+    Ok(module.assemble())
+}
+
+/// What `fold_constants` knows about constants.
+struct Folder<'a> {
+    program: &'a Program,
+    /// The values of constants and of instructions that have been folded.
+    values: HashMap<u32, Value>,
+    /// Scalar constants by type and value, so that the members of folded composites can reuse them.
+    scalars: HashMap<(u32, u32), u32>,
+    /// Composite constants by type and members.
+    composites: HashMap<(u32, Vec<u32>), u32>,
+}
+
+impl<'a> Folder<'a> {
+    fn record(&mut self, inst: &Inst) {
+        let (ty, id) = (inst.operands[0], inst.operands[1]);
+        if !self.constant_type(ty) {
+            return;
+        }
+        if let Some(value) = self.program.constant(id) {
+            self.values.insert(id, value.clone());
+        }
+        match inst.op {
+            Op::ConstantTrue => { self.scalars.insert((ty, 1), id); },
+            Op::ConstantFalse => { self.scalars.insert((ty, 0), id); },
+            Op::Constant => { self.scalars.insert((ty, inst.operands[2]), id); },
+            Op::ConstantComposite => { self.composites.insert((ty, inst.operands[2..].to_vec()), id); },
+            _ => {},
+        }
+    }
+
+    /// Whether constants can have the type `ty`.
+    fn constant_type(&self, ty: u32) -> bool {
+        match *self.program.ty(ty) {
+            Type::Bool | Type::Int { .. } | Type::Float | Type::Vector { .. } | Type::Matrix { .. } |
+            Type::Array { .. } | Type::Struct { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The result of an instruction, if it only computes a value and every ID it takes is a
+    /// constant.
+    fn fold(&self, op: Op, ty: u32, o: &[u32]) -> Option<Value> {
+        // An `OpUndef` is not the zero the interpreter makes of it.
+        if op == Op::Undef || !self.constant_type(ty) {
+            return None;
+        }
+        let missing = Cell::new(false);
+        let value = {
+            let lookup = |id: u32| match self.values.get(&id) {
+                Some(value) => value.clone(),
+                None => {
+                    missing.set(true);
+                    Value::Scalar(0)
+                },
+            };
+            interpret::evaluate(self.program, op, ty, o, &lookup)
+        };
+        if missing.get() { None } else { value }
+    }
+
+    /// Adds the instructions defining `value` as the constant `id` of type `ty` to `insts`, after
+    /// any constants its members need that do not exist yet. Returns false, adding nothing, if the
+    /// value cannot be a constant.
+    fn define(&mut self, module: &mut Module, ty: u32, id: u32, value: &Value, insts: &mut Vec<Inst>) -> bool {
+        let mut definition = Vec::new();
+        let inst = match *value {
+            Value::Scalar(x) => match *self.program.ty(ty) {
+                Type::Bool => Inst::new(if x != 0 { Op::ConstantTrue } else { Op::ConstantFalse }, vec![ty, id]),
+                Type::Int { .. } | Type::Float => Inst::new(Op::Constant, vec![ty, id, x]),
+                _ => return false,
+            },
+            Value::Composite(ref members) => match self.members(module, ty, members, &mut definition) {
+                Some(members) => {
+                    let mut operands = vec![ty, id];
+                    operands.extend(members);
+                    Inst::new(Op::ConstantComposite, operands)
+                },
+                None => return false,
+            },
+            _ => return false,
+        };
+        self.record_defined(&inst, value);
+        insts.extend(definition);
+        insts.push(inst);
+        true
+    }
+
+    /// The IDs of constants holding `members`, the members of a composite of type `ty`.
+    fn members(&mut self, module: &mut Module, ty: u32, members: &[Value], insts: &mut Vec<Inst>) -> Option<Vec<u32>> {
+        let types = match *self.program.ty(ty) {
+            Type::Vector { component, count } => vec![component; count as usize],
+            Type::Matrix { column, count } => vec![column; count as usize],
+            Type::Array { element, length } => vec![element; length as usize],
+            Type::Struct { ref members } => members.clone(),
+            _ => return None,
+        };
+        if types.len() != members.len() || members.len() + 2 > MAX_OPERANDS {
+            return None;
+        }
+        let mut ids = Vec::with_capacity(members.len());
+        for (&ty, member) in types.iter().zip(members) {
+            match self.constant(module, ty, member, insts) {
+                Some(id) => ids.push(id),
+                None => return None,
+            }
+        }
+        Some(ids)
+    }
+
+    /// The ID of a constant of type `ty` holding `value`, defining one in `insts` if there is none.
+    fn constant(&mut self, module: &mut Module, ty: u32, value: &Value, insts: &mut Vec<Inst>) -> Option<u32> {
+        match *value {
+            Value::Scalar(x) => {
+                let key = if let Type::Bool = *self.program.ty(ty) { (ty, (x != 0) as u32) } else { (ty, x) };
+                if let Some(&id) = self.scalars.get(&key) {
+                    return Some(id);
+                }
+                let id = module.allocate_id();
+                if self.define(module, ty, id, value, insts) { Some(id) } else { None }
+            },
+            Value::Composite(ref members) => {
+                let members = match self.members(module, ty, members, insts) {
+                    Some(members) => members,
+                    None => return None,
+                };
+                if let Some(&id) = self.composites.get(&(ty, members.clone())) {
+                    return Some(id);
+                }
+                let id = module.allocate_id();
+                let mut operands = vec![ty, id];
+                operands.extend(members);
+                let inst = Inst::new(Op::ConstantComposite, operands);
+                self.record_defined(&inst, value);
+                insts.push(inst);
+                Some(id)
+            },
+            _ => None,
+        }
+    }
+
+    fn record_defined(&mut self, inst: &Inst, value: &Value) {
+        let (ty, id) = (inst.operands[0], inst.operands[1]);
+        self.values.insert(id, value.clone());
+        match inst.op {
+            Op::ConstantTrue => { self.scalars.insert((ty, 1), id); },
+            Op::ConstantFalse => { self.scalars.insert((ty, 0), id); },
+            Op::Constant => { self.scalars.insert((ty, inst.operands[2]), id); },
+            Op::ConstantComposite => { self.composites.insert((ty, inst.operands[2..].to_vec()), id); },
+            _ => {},
+        }
+    }
+}
+
+/// Removes every entry point but the `model` one named `name`, with its execution modes, every
+/// function it does not call, and all debug instructions.
+pub fn strip(code: &[u32], name: &str, model: ExecutionModel) -> Result<Vec<u32>> {
+    let mut module = try!(Module::parse(code));
+    let index = module.globals.iter().position(|x| {
+        x.op == Op::EntryPoint && x.operands.len() >= 3 && ExecutionModel::from(x.operands[0]) == model &&
+            literal_string(&x.operands[2..]).0 == name
+    });
+    let (index, entry) = match index {
+        Some(index) => (index, module.globals[index].operands[1]),
+        None => {
+            error!("shader module has no {:?} entry point named {:?}", model, name);
+            return Err(Error::InitializationFailed);
+        },
+    };
+
+    let globals = mem::replace(&mut module.globals, Vec::new());
+    for (i, inst) in globals.into_iter().enumerate() {
+        let keep = match inst.op {
+            Op::EntryPoint => i == index,
+            Op::ExecutionMode | Op::ExecutionModeId => inst.operands[0] == entry,
+            op => !debug(op),
+        };
+        if keep {
+            module.globals.push(inst);
+        }
+    }
+
+    let functions: HashMap<u32, usize> = module.functions.iter().enumerate().map(|(i, x)| (x[0].operands[1], i)).collect();
+    let mut called = HashSet::new();
+    let mut stack = vec![entry];
+    while let Some(function) = stack.pop() {
+        if !called.insert(function) {
+            continue;
+        }
+        if let Some(&i) = functions.get(&function) {
+            stack.extend(module.functions[i].iter().filter(|x| x.op == Op::FunctionCall).map(|x| x.operands[2]));
+        }
+    }
+    module.functions.retain(|x| called.contains(&x[0].operands[1]));
+    for function in &mut module.functions {
+        function.retain(|x| !debug(x.op));
+    }
+
+    module.drop_annotations();
+    Ok(module.assemble())
+}
+
+/// Whether an instruction only carries debug information.
+fn debug(op: Op) -> bool {
+    match op {
+        Op::SourceContinued | Op::Source | Op::SourceExtension | Op::String | Op::Name | Op::MemberName |
+        Op::Line | Op::NoLine | Op::ModuleProcessed => true,
+        _ => false,
+    }
+}
+
+/// Removes blocks that cannot be reached, then every function, instruction, variable, type and
+/// constant nothing that is kept uses, along with their decorations and names.
+///
+/// Everything an entry point's interface lists is kept, and so are constants decorated as
+/// built-ins, which stand in for execution modes.
+pub fn eliminate_dead_code(code: &[u32]) -> Result<Vec<u32>> {
+    let mut module = try!(Module::parse(code));
+    for f in 0..module.functions.len() {
+        module.remove_unreachable(f);
+    }
+
+    let glsl: HashSet<u32> = module.globals.iter()
+        .filter(|x| x.op == Op::ExtInstImport && literal_string(&x.operands[1..]).0 == "GLSL.std.450")
+        .map(|x| x.operands[0])
+        .collect();
+    let builtin = u32::from(Decoration::BuiltIn);
+
+    // Instructions are found by their section, which is 0 for the global section and one more
+    // than the index of the function otherwise, and their index in it.
+    let keep = {
+        let sections: Vec<&Vec<Inst>> = Some(&module.globals).into_iter().chain(module.functions.iter()).collect();
+        let mut definitions = HashMap::new();
+        for (s, section) in sections.iter().enumerate() {
+            for (i, inst) in section.iter().enumerate() {
+                if let Some(id) = inst.result() {
+                    definitions.insert(id, (s, i));
+                }
+            }
+        }
+
+        let mut keep: Vec<Vec<bool>> = sections.iter().map(|x| vec![false; x.len()]).collect();
+        let mut work = Vec::new();
+        for (i, inst) in sections[0].iter().enumerate() {
+            let root = match inst.op {
+                Op::Decorate => inst.operands.get(1) == Some(&builtin),
+                Op::MemberDecorate | Op::GroupDecorate | Op::GroupMemberDecorate | Op::Name | Op::MemberName => false,
+                Op::ExtInstImport | Op::DecorationGroup | Op::String => true,
+                op => result_index(op).is_none(),
+            };
+            if root {
+                work.push((0, i));
+            }
+        }
+        while let Some((s, i)) = work.pop() {
+            if keep[s][i] {
+                continue;
+            }
+            keep[s][i] = true;
+            let inst = &sections[s][i];
+            if inst.op == Op::Function {
+                // A function that is called keeps everything in it that has an effect.
+                for (j, inst) in sections[s].iter().enumerate() {
+                    if !removable(inst, &glsl) {
+                        work.push((s, j));
+                    }
+                }
+            }
+            // Literal operands are looked up as if they were IDs too, which can only keep more.
+            // Decorations only use what they decorate.
+            let result = result_index(inst.op);
+            let operands = if annotation(inst.op) { &inst.operands[..1] } else { &inst.operands[..] };
+            for (k, operand) in operands.iter().enumerate() {
+                if Some(k) != result {
+                    if let Some(&location) = definitions.get(operand) {
+                        work.push(location);
+                    }
+                }
+            }
+        }
+        keep
+    };
+
+    let globals = mem::replace(&mut module.globals, Vec::new());
+    module.globals = globals.into_iter().zip(&keep[0])
+        .filter(|&(ref inst, &keep)| keep || annotation(inst.op))
+        .map(|(inst, _)| inst)
+        .collect();
+    let functions = mem::replace(&mut module.functions, Vec::new());
+    module.functions = functions.into_iter().zip(&keep[1..])
+        .filter(|&(_, keep)| keep[0])
+        .map(|(function, keep)| function.into_iter().zip(keep).filter(|&(_, &keep)| keep).map(|(inst, _)| inst).collect())
+        .collect();
+
+    module.drop_annotations();
+    Ok(module.assemble())
+}
+
+/// Whether an instruction in a function can be removed when nothing uses its result: it has one,
+/// and producing it does nothing else.
+fn removable(inst: &Inst, glsl: &HashSet<u32>) -> bool {
+    match inst.op {
+        Op::Function | Op::FunctionParameter | Op::Label | Op::FunctionCall | Op::Other(_) |
+        Op::AtomicLoad | Op::AtomicExchange | Op::AtomicCompareExchange | Op::AtomicCompareExchangeWeak |
+        Op::AtomicIIncrement | Op::AtomicIDecrement | Op::AtomicIAdd | Op::AtomicISub | Op::AtomicSMin |
+        Op::AtomicUMin | Op::AtomicSMax | Op::AtomicUMax | Op::AtomicAnd | Op::AtomicOr | Op::AtomicXor => false,
+        // Only the forms of these that return a structure leave memory alone.
+        Op::ExtInst => inst.operands.len() >= 4 && glsl.contains(&inst.operands[2]) && match Glsl450::from(inst.operands[3]) {
+            Glsl450::Modf | Glsl450::Frexp => false,
+            _ => true,
+        },
+        op => result_index(op).is_some(),
+    }
+}
+
+/// Whether an instruction decorates or names an ID without using it.
+fn annotation(op: Op) -> bool {
+    match op {
+        Op::Decorate | Op::DecorateId | Op::MemberDecorate | Op::GroupDecorate | Op::GroupMemberDecorate |
+        Op::Name | Op::MemberName => true,
+        _ => false,
+    }
+}
+
+/// The blocks a block's terminator can branch to. `wide` holds the IDs of 64-bit integers, whose
+/// switch literals take two words.
+fn successors(inst: &Inst, wide: &HashSet<u32>) -> Vec<u32> {
+    let o = &inst.operands;
+    match inst.op {
+        Op::Branch => vec![o[0]],
+        Op::BranchConditional => vec![o[1], o[2]],
+        Op::Switch => Some(o[1]).into_iter().chain(switch_cases(inst, wide).map(|(_, target)| target)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The literal words and target of each case of an `OpSwitch`, whose literals are as wide as its
+/// selector.
+fn switch_cases<'a>(inst: &'a Inst, wide: &HashSet<u32>) -> impl Iterator<Item = (&'a [u32], u32)> + 'a {
+    let case_words = if wide.contains(&inst.operands[0]) { 3 } else { 2 };
+    inst.operands[2..].chunks(case_words)
+        .filter(move |x| x.len() == case_words)
+        .map(move |x| (&x[..(case_words - 1)], x[case_words - 1]))
+}
+
+/// Drops the incoming values of a function's `OpPhi`s that come from blocks that no longer branch
+/// to theirs.
+fn prune_phis(function: &mut Vec<Inst>, wide: &HashSet<u32>) {
+    let mut predecessors: HashMap<u32, HashSet<u32>> = HashMap::new();
+    let mut label = 0;
+    for inst in function.iter() {
+        if inst.op == Op::Label {
+            label = inst.operands[0];
+        }
+        for target in successors(inst, wide) {
+            predecessors.entry(target).or_insert_with(HashSet::new).insert(label);
+        }
+    }
+
+    let none = HashSet::new();
+    let mut label = 0;
+    for inst in function.iter_mut() {
+        match inst.op {
+            Op::Label => label = inst.operands[0],
+            Op::Phi => {
+                let predecessors = predecessors.get(&label).unwrap_or(&none);
+                let mut operands = inst.operands[..2].to_vec();
+                for pair in inst.operands[2..].chunks(2) {
+                    if pair.len() == 2 && predecessors.contains(&pair[1]) {
+                        operands.extend_from_slice(pair);
+                    }
+                }
+                inst.operands = operands;
+            },
+            _ => {},
+        }
+    }
+}
+
+/// An instruction that can be edited.
+#[derive(Clone, Debug)]
+struct Inst {
+    op: Op,
+    operands: Vec<u32>,
+}
+
+impl Inst {
+    fn new(op: Op, operands: Vec<u32>) -> Inst {
+        Inst { op: op, operands: operands }
+    }
+
+    fn result(&self) -> Option<u32> {
+        result_index(self.op).and_then(|i| self.operands.get(i).cloned())
+    }
+}
+
+/// A module taken apart for editing.
+struct Module {
+    header: Vec<u32>,
+    /// Everything before the first function.
+    globals: Vec<Inst>,
+    /// Each function, from its `OpFunction` to its `OpFunctionEnd`.
+    functions: Vec<Vec<Inst>>,
+    /// The IDs of 64-bit integers, for finding the targets of switches.
+    wide: HashSet<u32>,
+    /// The global `OpUndef` of each type.
+    undefs: HashMap<u32, u32>,
+}
+
+impl Module {
+    fn parse(code: &[u32]) -> Result<Module> {
+        let mut module = Module {
+            header: code[..HEADER_WORDS].to_vec(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+            wide: HashSet::new(),
+            undefs: HashMap::new(),
+        };
+
+        let mut wide_types = HashSet::new();
+        let mut function: Option<Vec<Inst>> = None;
+        for instruction in try!(instructions(code)) {
+            let inst = Inst::new(instruction.op, instruction.operands.to_vec());
+            if inst.op == Op::TypeInt && inst.operands.get(1) == Some(&64) {
+                wide_types.insert(inst.operands[0]);
+            } else if result_index(inst.op) == Some(1) && wide_types.contains(&inst.operands[0]) {
+                module.wide.insert(inst.operands[1]);
+            }
+            if inst.op == Op::Function {
+                if function.is_some() {
+                    error!("SPIR-V function {} starts inside another function", inst.operands[1]);
+                    return Err(Error::InvalidShader);
+                }
+                function = Some(Vec::new());
+            }
+            match function {
+                Some(ref mut function) => function.push(inst.clone()),
+                None => {
+                    if inst.op == Op::Undef {
+                        module.undefs.insert(inst.operands[0], inst.operands[1]);
+                    }
+                    module.globals.push(inst.clone());
+                },
+            }
+            if inst.op == Op::FunctionEnd {
+                match function.take() {
+                    Some(function) => module.functions.push(function),
+                    None => {
+                        error!("SPIR-V module has an OpFunctionEnd outside of any function");
+                        return Err(Error::InvalidShader);
+                    },
+                }
+            }
+        }
+        if function.is_some() {
+            error!("SPIR-V module ends inside a function");
+            return Err(Error::InvalidShader);
+        }
+        Ok(module)
+    }
+
+    fn assemble(&self) -> Vec<u32> {
+        let mut code = self.header.clone();
+        for inst in self.globals.iter().chain(self.functions.iter().flat_map(|x| x.iter())) {
+            code.push(((inst.operands.len() as u32 + 1) << 16) | u32::from(inst.op));
+            code.extend_from_slice(&inst.operands);
+        }
+        code
+    }
+
+    /// Takes an ID no instruction defines yet, raising the bound.
+    fn allocate_id(&mut self) -> u32 {
+        let id = self.header[3];
+        self.header[3] += 1;
+        id
+    }
+
+    /// The ID of an `OpUndef` of type `ty`, declaring one if there is none.
+    fn undef(&mut self, ty: u32) -> u32 {
+        if let Some(&id) = self.undefs.get(&ty) {
+            return id;
+        }
+        let id = self.allocate_id();
+        self.globals.push(Inst::new(Op::Undef, vec![ty, id]));
+        self.undefs.insert(ty, id);
+        id
+    }
+
+    /// Removes the blocks of function `f` no branch can reach, except merge and continue blocks of
+    /// constructs that are still there: merge blocks are left holding `OpUnreachable`, and continue
+    /// blocks branch straight back to their loop header.
+    fn remove_unreachable(&mut self, f: usize) {
+        let function = mem::replace(&mut self.functions[f], Vec::new());
+        let first = match function.iter().position(|x| x.op == Op::Label) {
+            Some(first) => first,
+            None => {
+                self.functions[f] = function;
+                return;
+            },
+        };
+
+        let mut blocks: Vec<Vec<Inst>> = Vec::new();
+        for inst in &function[first..(function.len() - 1)] {
+            if inst.op == Op::Label {
+                blocks.push(Vec::new());
+            }
+            if let Some(block) = blocks.last_mut() {
+                block.push(inst.clone());
+            }
+        }
+        let index: HashMap<u32, usize> = blocks.iter().enumerate().map(|(i, x)| (x[0].operands[0], i)).collect();
+
+        let mut reachable = vec![false; blocks.len()];
+        let mut stack = vec![0];
+        while let Some(b) = stack.pop() {
+            if reachable[b] {
+                continue;
+            }
+            reachable[b] = true;
+            if let Some(terminator) = blocks[b].last() {
+                stack.extend(successors(terminator, &self.wide).iter().filter_map(|x| index.get(x)));
+            }
+        }
+
+        // Blocks that reachable merge instructions name, with the loop header for continue blocks.
+        let mut named: HashMap<usize, Option<u32>> = HashMap::new();
+        for (b, block) in blocks.iter().enumerate() {
+            if !reachable[b] || block.len() < 2 {
+                continue;
+            }
+            let merge = &block[block.len() - 2];
+            match merge.op {
+                Op::LoopMerge => {
+                    if let Some(&i) = index.get(&merge.operands[0]) {
+                        named.entry(i).or_insert(None);
+                    }
+                    if let Some(&i) = index.get(&merge.operands[1]) {
+                        named.insert(i, Some(block[0].operands[0]));
+                    }
+                },
+                Op::SelectionMerge => {
+                    if let Some(&i) = index.get(&merge.operands[0]) {
+                        named.entry(i).or_insert(None);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        let mut body = function[..first].to_vec();
+        let mut gone = HashSet::new();
+        let mut continues = Vec::new();
+        for (b, block) in blocks.into_iter().enumerate() {
+            if reachable[b] {
+                body.extend(block);
+                continue;
+            }
+            gone.extend(block[1..].iter().filter_map(|x| x.result()));
+            match named.get(&b) {
+                Some(&Some(header)) => {
+                    continues.push((block[0].operands[0], header));
+                    body.push(block[0].clone());
+                    body.push(Inst::new(Op::Branch, vec![header]));
+                },
+                Some(&None) => {
+                    body.push(block[0].clone());
+                    body.push(Inst::new(Op::Unreachable, Vec::new()));
+                },
+                None => {},
+            }
+        }
+        body.push(function[function.len() - 1].clone());
+
+        // Values from removed blocks can only still be used by `OpPhi`s, and only for branches
+        // from blocks that are now unreachable, which continue blocks branching back still are.
+        let mut label = 0;
+        for inst in &mut body {
+            match inst.op {
+                Op::Label => label = inst.operands[0],
+                Op::Phi => {
+                    let ty = inst.operands[0];
+                    let mut i = 2;
+                    while i < inst.operands.len() {
+                        if gone.contains(&inst.operands[i]) {
+                            inst.operands[i] = self.undef(ty);
+                        }
+                        i += 2;
+                    }
+                    for &(block, header) in &continues {
+                        if header == label && !inst.operands[2..].chunks(2).any(|x| x.len() == 2 && x[1] == block) {
+                            let undef = self.undef(ty);
+                            inst.operands.push(undef);
+                            inst.operands.push(block);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        prune_phis(&mut body, &self.wide);
+        self.functions[f] = body;
+    }
+
+    /// Removes decorations and names of IDs that are no longer defined.
+    fn drop_annotations(&mut self) {
+        let mut defined = HashSet::new();
+        for inst in self.globals.iter().chain(self.functions.iter().flat_map(|x| x.iter())) {
+            if let Some(id) = inst.result() {
+                defined.insert(id);
+            }
+        }
+
+        let globals = mem::replace(&mut self.globals, Vec::new());
+        for mut inst in globals {
+            match inst.op {
+                Op::Decorate | Op::DecorateId | Op::MemberDecorate | Op::Name | Op::MemberName => {
+                    if !defined.contains(&inst.operands[0]) {
+                        continue;
+                    }
+                },
+                Op::GroupDecorate => {
+                    let mut operands = vec![inst.operands[0]];
+                    operands.extend(inst.operands[1..].iter().filter(|x| defined.contains(x)));
+                    if operands.len() == 1 {
+                        continue;
+                    }
+                    inst.operands = operands;
+                },
+                Op::GroupMemberDecorate => {
+                    let mut operands = vec![inst.operands[0]];
+                    for pair in inst.operands[1..].chunks(2) {
+                        if pair.len() == 2 && defined.contains(&pair[0]) {
+                            operands.extend_from_slice(pair);
+                        }
+                    }
+                    if operands.len() == 1 {
+                        continue;
+                    }
+                    inst.operands = operands;
+                },
+                _ => {},
+            }
+            self.globals.push(inst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spirv::Assembler;
+
+    use std::slice;
+
+    // IDs every test module declares. The IDs of each test's own start at 20.
+    const VOID: u32 = 1;
+    const VOID_FN: u32 = 2;
+    const BOOL: u32 = 3;
+    const UINT: u32 = 4;
+    const UVEC2: u32 = 5;
+    const MAIN: u32 = 6;
+    const ENTRY: u32 = 7;
+    const TRUE: u32 = 8;
+    /// `uint` constants from 0 to 3.
+    const UINTS: u32 = 10;
+
+    /// Starts a module whose only entry point is the compute shader `main`: `annotations` come
+    /// before the types and constants every test uses and `globals` after them, and then `main`'s
+    /// first block is opened.
+    fn start(annotations: &Fn(&mut Assembler), globals: &Fn(&mut Assembler)) -> Assembler {
+        let mut a = Assembler::new(60);
+        a.op(Op::Capability, &[u32::from(Capability::Shader)]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[u32::from(ExecutionModel::GLCompute), MAIN], "main", &[]);
+        a.op(Op::ExecutionMode, &[MAIN, u32::from(ExecutionMode::LocalSize), 1, 1, 1]);
+        annotations(&mut a);
+        a.op(Op::TypeVoid, &[VOID]);
+        a.op(Op::TypeFunction, &[VOID_FN, VOID]);
+        a.op(Op::TypeBool, &[BOOL]);
+        a.op(Op::TypeInt, &[UINT, 32, 0]);
+        a.op(Op::TypeVector, &[UVEC2, UINT, 2]);
+        a.op(Op::ConstantTrue, &[BOOL, TRUE]);
+        for i in 0..4 {
+            a.op(Op::Constant, &[UINT, UINTS + i, i]);
+        }
+        globals(&mut a);
+        a.op(Op::Function, &[VOID, MAIN, 0, VOID_FN]);
+        a.op(Op::Label, &[ENTRY]);
+        a
+    }
+
+    fn end(a: &mut Assembler) -> Vec<u32> {
+        a.op(Op::Return, &[]);
+        a.op(Op::FunctionEnd, &[]);
+        a.words()
+    }
+
+    /// The instructions of a module, after checking that it is still valid.
+    fn disassemble(code: &[u32]) -> Vec<(Op, Vec<u32>)> {
+        SpirvModule::new(code).unwrap();
+        instructions(code).unwrap().iter().map(|x| (x.op, x.operands.to_vec())).collect()
+    }
+
+    /// The operands of every `op` in a module.
+    fn find(ops: &[(Op, Vec<u32>)], op: Op) -> Vec<Vec<u32>> {
+        ops.iter().filter(|x| x.0 == op).map(|x| x.1.clone()).collect()
+    }
+
+    fn defines(ops: &[(Op, Vec<u32>)], id: u32) -> bool {
+        ops.iter().any(|&(op, ref operands)| result_index(op).and_then(|i| operands.get(i)) == Some(&id))
+    }
+
+    #[test]
+    fn specialize_uses_map_entries_and_defaults() {
+        let mut a = start(&|a| {
+            a.op(Op::Decorate, &[20, u32::from(Decoration::SpecId), 0]);
+            a.op(Op::Decorate, &[21, u32::from(Decoration::SpecId), 1]);
+            a.op(Op::Decorate, &[22, u32::from(Decoration::SpecId), 2]);
+        }, &|a| {
+            a.op(Op::SpecConstant, &[UINT, 20, 7]);
+            a.op(Op::SpecConstant, &[UINT, 21, 8]);
+            a.op(Op::SpecConstantTrue, &[BOOL, 22]);
+            a.op(Op::SpecConstantComposite, &[UVEC2, 23, 20, 21]);
+        });
+        let code = end(&mut a);
+
+        let words = [42u32, 0];
+        let data = unsafe { slice::from_raw_parts(words.as_ptr() as *const u8, 8) };
+        let entries = [
+            SpecializationMapEntry { constant_id: 0, offset: 0, size: 4 },
+            SpecializationMapEntry { constant_id: 2, offset: 4, size: 4 },
+        ];
+        let ops = disassemble(&specialize(&code, &entries, data).unwrap());
+        let constants = find(&ops, Op::Constant);
+        assert!(constants.contains(&vec![UINT, 20, 42]));
+        assert!(constants.contains(&vec![UINT, 21, 8]));
+        assert_eq!(find(&ops, Op::ConstantFalse), [[BOOL, 22]]);
+        assert_eq!(find(&ops, Op::ConstantComposite), [[UVEC2, 23, 20, 21]]);
+        assert!(find(&ops, Op::Decorate).is_empty());
+
+        let wrong_size = [SpecializationMapEntry { constant_id: 0, offset: 0, size: 2 }];
+        assert!(specialize(&code, &wrong_size, data).is_err());
+        let past_end = [SpecializationMapEntry { constant_id: 1, offset: 6, size: 4 }];
+        assert!(specialize(&code, &past_end, data).is_err());
+    }
+
+    #[test]
+    fn fold_constants_evaluates_instructions() {
+        let mut a = start(&|_| {}, &|a| {
+            a.op(Op::SpecConstantOp, &[UINT, 20, u32::from(Op::IMul), UINTS + 2, UINTS + 3]);
+        });
+        a.op(Op::IAdd, &[UINT, 21, UINTS + 2, 20]);
+        a.op(Op::IEqual, &[BOOL, 22, 21, UINTS + 3]);
+        a.op(Op::CompositeConstruct, &[UVEC2, 23, 20, 21]);
+        let ops = disassemble(&fold_constants(&end(&mut a)).unwrap());
+
+        let constants = find(&ops, Op::Constant);
+        assert!(constants.contains(&vec![UINT, 20, 6]));
+        assert!(constants.contains(&vec![UINT, 21, 8]));
+        assert_eq!(find(&ops, Op::ConstantFalse), [[BOOL, 22]]);
+        assert_eq!(find(&ops, Op::ConstantComposite), [[UVEC2, 23, 20, 21]]);
+        assert!(find(&ops, Op::SpecConstantOp).is_empty());
+        assert!(find(&ops, Op::IAdd).is_empty());
+    }
+
+    #[test]
+    fn fold_constants_turns_conditional_branches_into_branches() {
+        let mut a = start(&|_| {}, &|_| {});
+        a.op(Op::SelectionMerge, &[22, 0]);
+        a.op(Op::BranchConditional, &[TRUE, 20, 21]);
+        a.op(Op::Label, &[20]);
+        a.op(Op::Branch, &[22]);
+        a.op(Op::Label, &[21]);
+        a.op(Op::Branch, &[22]);
+        a.op(Op::Label, &[22]);
+        let folded = fold_constants(&end(&mut a)).unwrap();
+        let ops = disassemble(&folded);
+
+        // The selection's merge instruction can only come before a conditional branch.
+        assert!(find(&ops, Op::SelectionMerge).is_empty());
+        assert!(find(&ops, Op::BranchConditional).is_empty());
+        assert_eq!(find(&ops, Op::Branch), [[20], [22], [22]]);
+
+        // Without a merge instruction naming it, nothing keeps the branch not taken.
+        let ops = disassemble(&eliminate_dead_code(&folded).unwrap());
+        assert!(!defines(&ops, 21));
+        assert_eq!(find(&ops, Op::Branch), [[20], [22]]);
+    }
+
+    #[test]
+    fn fold_constants_keeps_loop_merges() {
+        let mut a = start(&|_| {}, &|_| {});
+        a.op(Op::Branch, &[20]);
+        a.op(Op::Label, &[20]);
+        a.op(Op::LoopMerge, &[22, 21, 0]);
+        a.op(Op::BranchConditional, &[TRUE, 22, 21]);
+        a.op(Op::Label, &[21]);
+        a.op(Op::Branch, &[20]);
+        a.op(Op::Label, &[22]);
+        let ops = disassemble(&fold_constants(&end(&mut a)).unwrap());
+
+        assert_eq!(find(&ops, Op::LoopMerge), [[22, 21, 0]]);
+        assert_eq!(find(&ops, Op::Branch), [[20], [22], [20]]);
+    }
+
+    #[test]
+    fn fold_constants_picks_switch_cases() {
+        for &(selector, target) in &[(UINTS + 2, 22), (UINTS + 3, 20)] {
+            let mut a = start(&|_| {}, &|_| {});
+            a.op(Op::SelectionMerge, &[23, 0]);
+            a.op(Op::Switch, &[selector, 20, 1, 21, 2, 22]);
+            for &label in &[20, 21, 22] {
+                a.op(Op::Label, &[label]);
+                a.op(Op::Branch, &[23]);
+            }
+            a.op(Op::Label, &[23]);
+            let ops = disassemble(&fold_constants(&end(&mut a)).unwrap());
+
+            assert_eq!(find(&ops, Op::Switch), [[selector, target]]);
+            assert_eq!(find(&ops, Op::SelectionMerge), [[23, 0]]);
+        }
+    }
+
+    #[test]
+    fn switch_cases_of_64_bit_selectors() {
+        let mut wide = HashSet::new();
+        wide.insert(20);
+        let switch = Inst::new(Op::Switch, vec![20, 21, 1, 0, 22, 2, 0, 23]);
+        assert_eq!(switch_cases(&switch, &wide).collect::<Vec<_>>(), [(&[1, 0][..], 22), (&[2, 0][..], 23)]);
+        assert_eq!(successors(&switch, &wide), [21, 22, 23]);
+        let narrow = Inst::new(Op::Switch, vec![24, 21, 1, 22, 2, 23]);
+        assert_eq!(successors(&narrow, &wide), [21, 22, 23]);
+    }
+
+    #[test]
+    fn strip_keeps_one_entry_point() {
+        let mut a = Assembler::new(60);
+        a.op(Op::Capability, &[u32::from(Capability::Shader)]);
+        a.op(Op::MemoryModel, &[0, 1]);
+        a.op_string(Op::EntryPoint, &[u32::from(ExecutionModel::GLCompute), MAIN], "main", &[]);
+        a.op_string(Op::EntryPoint, &[u32::from(ExecutionModel::GLCompute), 20], "other", &[]);
+        a.op(Op::ExecutionMode, &[MAIN, u32::from(ExecutionMode::LocalSize), 1, 1, 1]);
+        a.op(Op::ExecutionMode, &[20, u32::from(ExecutionMode::LocalSize), 2, 1, 1]);
+        a.op(Op::Source, &[2, 450]);
+        a.op_string(Op::Name, &[MAIN], "main", &[]);
+        a.op_string(Op::Name, &[21], "helper", &[]);
+        a.op(Op::TypeVoid, &[VOID]);
+        a.op(Op::TypeFunction, &[VOID_FN, VOID]);
+        for &(function, call) in &[(21, None), (20, Some(21)), (MAIN, None)] {
+            a.op(Op::Function, &[VOID, function, 0, VOID_FN]);
+            a.op(Op::Label, &[function + 10]);
+            if let Some(call) = call {
+                a.op(Op::FunctionCall, &[VOID, 23, call]);
+            }
+            a.op(Op::Return, &[]);
+            a.op(Op::FunctionEnd, &[]);
+        }
+        let code = a.words();
+
+        let ops = disassemble(&strip(&code, "main", ExecutionModel::GLCompute).unwrap());
+        assert_eq!(find(&ops, Op::EntryPoint).len(), 1);
+        assert_eq!(find(&ops, Op::ExecutionMode), [[MAIN, u32::from(ExecutionMode::LocalSize), 1, 1, 1]]);
+        assert!(find(&ops, Op::Source).is_empty());
+        assert!(find(&ops, Op::Name).is_empty());
+        assert_eq!(find(&ops, Op::Function), [[VOID, MAIN, 0, VOID_FN]]);
+
+        let other = disassemble(&strip(&code, "other", ExecutionModel::GLCompute).unwrap());
+        assert_eq!(find(&other, Op::Function), [[VOID, 21, 0, VOID_FN], [VOID, 20, 0, VOID_FN]]);
+
+        assert!(strip(&code, "main", ExecutionModel::Vertex).is_err());
+        assert!(strip(&code, "missing", ExecutionModel::GLCompute).is_err());
+    }
+
+    #[test]
+    fn eliminate_dead_code_removes_unused_results_and_unreachable_blocks() {
+        let mut a = start(&|a| {
+            a.op(Op::Decorate, &[20, u32::from(Decoration::RelaxedPrecision)]);
+        }, &|a| {
+            a.op(Op::TypeFloat, &[20, 32]);
+            a.op(Op::ConstantComposite, &[UVEC2, 21, UINTS, UINTS + 1]);
+        });
+        a.op(Op::IAdd, &[UINT, 22, UINTS + 2, UINTS + 3]);
+        a.op(Op::SelectionMerge, &[25, 0]);
+        a.op(Op::BranchConditional, &[TRUE, 23, 24]);
+        a.op(Op::Label, &[23]);
+        a.op(Op::Return, &[]);
+        a.op(Op::Label, &[24]);
+        a.op(Op::Return, &[]);
+        a.op(Op::Label, &[25]);
+        a.op(Op::Branch, &[27]);
+        a.op(Op::Label, &[26]);
+        a.op(Op::Branch, &[27]);
+        a.op(Op::Label, &[27]);
+        let ops = disassemble(&eliminate_dead_code(&end(&mut a)).unwrap());
+
+        for &id in &[20, 21, 22, UINTS, UINTS + 2, 26, 27] {
+            assert!(!defines(&ops, id), "{} is still defined", id);
+        }
+        for &id in &[TRUE, 23, 24, 25] {
+            assert!(defines(&ops, id), "{} is gone", id);
+        }
+        assert!(find(&ops, Op::Decorate).is_empty());
+        // The merge block cannot be reached, but the selection still names it.
+        let merge = ops.iter().position(|x| x.0 == Op::Label && x.1 == [25]).unwrap();
+        assert_eq!(ops[merge + 1].0, Op::Unreachable);
+    }
+}
@@ -0,0 +1,56 @@
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using namespace metal;
+
+struct _4
+{
+    uint m0;
+    char _m1_pad[12];
+    uint m1[1];
+};
+
+uint _25(uint _26, device _4& _3)
+{
+    uint _29;
+    _29 = atomic_fetch_add_explicit((device atomic_uint*)&_3.m0, _26, memory_order_relaxed);
+    return _29;
+}
+
+kernel void main0(uint3 spvGlobalInvocationId [[thread_position_in_grid]], device _4& _3 [[buffer(0)]])
+{
+    uint3 _2 = uint3(spvGlobalInvocationId);
+    threadgroup array<uint, 64> _21;
+    uint3 _31;
+    uint _32;
+    uint _33;
+    uint _35;
+    uint _40;
+    uint _42;
+    _31 = _2;
+    _32 = _31.x;
+    _33 = uint(uint(_32) % uint(64u));
+    _21[_33] = _32;
+    threadgroup_barrier(mem_flags::mem_threadgroup);
+    _35 = (_32 & 1u);
+    switch (_35)
+    {
+        case 0u:
+        {
+            _40 = _25(_32, _3);
+            _3.m1[_32] = _40;
+            break;
+        }
+        case 1u:
+        {
+            _42 = uint(int(_32) >> int(1u));
+            _3.m1[_32] = _42;
+            break;
+        }
+        default:
+        {
+            break;
+        }
+    }
+    return;
+}
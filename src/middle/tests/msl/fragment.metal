@@ -0,0 +1,94 @@
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using namespace metal;
+
+struct _8
+{
+    packed_float3 m0;
+    float m1;
+    int m2;
+};
+
+constant float4 _31 = float4(0.5f, 0.5f, 0.5f, 0.0f);
+
+struct main0_in
+{
+    float2 m_0 [[user(locn0)]];
+};
+
+struct main0_out
+{
+    float4 m_0 [[color(0)]];
+};
+
+fragment main0_out main0(main0_in in [[stage_in]], float4 spvFragCoord [[position]], texture2d<float> _6 [[texture(0)]], sampler _6Smplr [[sampler(0)]], constant _8& _7 [[buffer(0)]])
+{
+    float2 _3 = in.m_0;
+    float4 _4;
+    float4 _5 = float4(spvFragCoord);
+    float2 _33;
+    float4 _35;
+    int _37;
+    int _39;
+    float4 _42;
+    bool _46;
+    float _49;
+    float4 _50;
+    bool _51;
+    float4 _54;
+    float4 _43;
+    int _40;
+    float4 _55;
+    float2 _56;
+    float2 _57;
+    float4 _58;
+    _33 = _3;
+    _35 = _6.sample(_6Smplr, _33.xy);
+    _37 = _7.m2;
+    {
+        auto _39_tmp = 0;
+        auto _42_tmp = _35;
+        _39 = _39_tmp;
+        _42 = _42_tmp;
+    }
+    for (;;)
+    {
+        _46 = (int(_39) < int(_37));
+        if (_46)
+        {
+            _49 = _7.m1;
+            _50 = (_42 * _49);
+            _51 = (_49 > 0.5f);
+            if (_51)
+            {
+                _54 = (_50 + _31);
+                _43 = _54;
+            }
+            else
+            {
+                _43 = _50;
+            }
+            _40 = (_39 + 1);
+            {
+                auto _39_tmp = _40;
+                auto _42_tmp = _43;
+                _39 = _39_tmp;
+                _42 = _42_tmp;
+            }
+            continue;
+        }
+        else
+        {
+            break;
+        }
+    }
+    _55 = _5;
+    _56 = _55.xy;
+    _57 = normalize(_56);
+    _58 = float4(_57.x, _57.y, _42.z, _42.w);
+    _4 = _58;
+    main0_out out = {};
+    out.m_0 = _4;
+    return out;
+}
@@ -0,0 +1,49 @@
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using namespace metal;
+
+struct _6
+{
+    float4 m0;
+    float m1;
+};
+
+struct _7
+{
+    float4x4 m0;
+};
+
+struct main0_in
+{
+    float4 m_0 [[attribute(0)]];
+};
+
+struct main0_out
+{
+    float4 gl_Position [[position]];
+    int m_1 [[user(locn1)]];
+};
+
+vertex main0_out main0(main0_in in [[stage_in]], uint spvVertexIndex [[vertex_id]], constant _7& _16 [[buffer(0)]])
+{
+    float4 _2 = in.m_0;
+    int _3 = int(spvVertexIndex);
+    _6 _4;
+    int _5;
+    float4x4 _25;
+    float4 _26;
+    float4 _27;
+    int _29;
+    _25 = _16.m0;
+    _26 = _2;
+    _27 = (_25 * _26);
+    _4.m0 = _27;
+    _29 = _3;
+    _5 = _29;
+    main0_out out = {};
+    out.gl_Position = _4.m0;
+    out.m_1 = _5;
+    out.gl_Position.y = -out.gl_Position.y;
+    return out;
+}
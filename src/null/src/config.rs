@@ -0,0 +1,250 @@
+//! What the null device reports to applications.
+//!
+//! Tests can narrow the features and limits to exercise an application's fallback paths, either
+//! with `configure` when linking the crate directly or through environment variables when the
+//! library is loaded as an ICD.
+
+use vk_middle::icd::*;
+
+use std::env;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub properties: PhysicalDeviceProperties,
+    pub features: PhysicalDeviceFeatures,
+    /// The number of queues in the device's only queue family.
+    pub queue_count: u32,
+    /// The size of the device's only memory heap. Allocations that would take the device past it
+    /// fail with `VK_ERROR_OUT_OF_DEVICE_MEMORY`.
+    pub heap_size: u64,
+    /// Whether queues follow image layouts through the command buffers submitted to them, and
+    /// refuse submissions that use an image in a layout other than the one it is in with
+    /// `VK_ERROR_VALIDATION_FAILED_EXT`.
+    pub validate: bool,
+    /// Whether queues follow the memory accesses of the command buffers submitted to them, and
+    /// refuse submissions with read-after-write, write-after-read or write-after-write hazards
+    /// with `VK_ERROR_VALIDATION_FAILED_EXT`. Only semaphores and barriers order accesses across
+    /// submissions; waiting for a fence in between does not.
+    pub validate_synchronization: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            properties: PhysicalDeviceProperties {
+                api_version: API_VERSION_1_0,
+                driver_version: 1,
+                vendor_id: 0,
+                device_id: 0,
+                device_type: PhysicalDeviceType::Cpu,
+                device_name: "vk-middle null device".to_owned(),
+                pipeline_cache_uuid: [0; 16],
+                limits: default_limits(),
+                sparse_properties: PhysicalDeviceSparseProperties::default(),
+            },
+            features: supported_features(),
+            queue_count: 4,
+            heap_size: 1 << 30,
+            validate: false,
+            validate_synchronization: false,
+        }
+    }
+}
+
+impl Config {
+    /// The default configuration, adjusted by environment variables:
+    ///
+    /// * `VK_MIDDLE_NULL_FEATURES`: `all` (the default, every feature but sparse resources) or
+    ///   `none`.
+    /// * `VK_MIDDLE_NULL_HEAP_SIZE`: the heap size in bytes.
+    /// * `VK_MIDDLE_NULL_QUEUE_COUNT`: the number of queues.
+    /// * `VK_MIDDLE_NULL_VALIDATE`: `1` to validate submissions, `0` (the default) not to.
+    /// * `VK_MIDDLE_NULL_VALIDATE_SYNC`: `1` to check submissions for hazards, `0` (the default)
+    ///   not to.
+    pub fn from_env() -> Config {
+        let mut config = Config::default();
+
+        if let Ok(features) = env::var("VK_MIDDLE_NULL_FEATURES") {
+            match &features[..] {
+                "all" => config.features = supported_features(),
+                "none" => config.features = PhysicalDeviceFeatures::default(),
+                other => warn!("ignoring unknown VK_MIDDLE_NULL_FEATURES value {:?}", other),
+            }
+        }
+        if let Ok(heap_size) = env::var("VK_MIDDLE_NULL_HEAP_SIZE") {
+            match heap_size.parse() {
+                Ok(heap_size) => config.heap_size = heap_size,
+                Err(_) => warn!("ignoring invalid VK_MIDDLE_NULL_HEAP_SIZE value {:?}", heap_size),
+            }
+        }
+        if let Ok(queue_count) = env::var("VK_MIDDLE_NULL_QUEUE_COUNT") {
+            match queue_count.parse() {
+                Ok(queue_count) if queue_count > 0 => config.queue_count = queue_count,
+                _ => warn!("ignoring invalid VK_MIDDLE_NULL_QUEUE_COUNT value {:?}", queue_count),
+            }
+        }
+
+        if let Ok(validate) = env::var("VK_MIDDLE_NULL_VALIDATE") {
+            match &validate[..] {
+                "1" => config.validate = true,
+                "0" => config.validate = false,
+                other => warn!("ignoring unknown VK_MIDDLE_NULL_VALIDATE value {:?}", other),
+            }
+        }
+        if let Ok(validate) = env::var("VK_MIDDLE_NULL_VALIDATE_SYNC") {
+            match &validate[..] {
+                "1" => config.validate_synchronization = true,
+                "0" => config.validate_synchronization = false,
+                other => warn!("ignoring unknown VK_MIDDLE_NULL_VALIDATE_SYNC value {:?}", other),
+            }
+        }
+
+        config
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+}
+
+/// Replaces what instances created from now on report. Until this is called, instances use
+/// `Config::from_env`.
+pub fn configure(config: Config) {
+    *CONFIG.lock().unwrap() = Some(config);
+}
+
+pub(crate) fn current() -> Config {
+    CONFIG.lock().unwrap().clone().unwrap_or_else(Config::from_env)
+}
+
+/// Every feature but sparse resources, which the device has no way to bind.
+fn supported_features() -> PhysicalDeviceFeatures {
+    PhysicalDeviceFeatures {
+        shader_resource_residency: false,
+        sparse_binding: false,
+        sparse_residency_buffer: false,
+        sparse_residency_image_2d: false,
+        sparse_residency_image_3d: false,
+        sparse_residency_2_samples: false,
+        sparse_residency_4_sample: false,
+        sparse_residency_8_sample: false,
+        sparse_residency_16_sample: false,
+        sparse_residency_aliased: false,
+        ..PhysicalDeviceFeatures::all()
+    }
+}
+
+/// Limits in line with desktop GPUs, so applications take their usual paths.
+fn default_limits() -> PhysicalDeviceLimits {
+    let sample_counts = VK_SAMPLE_COUNT_1_BIT | VK_SAMPLE_COUNT_4_BIT;
+
+    PhysicalDeviceLimits {
+        max_image_dimension_1d: 16384,
+        max_image_dimension_2d: 16384,
+        max_image_dimension_3d: 2048,
+        max_image_dimension_cube: 16384,
+        max_image_array_layers: 2048,
+        max_texel_buffer_elements: 1 << 27,
+        max_uniform_buffer_range: 65536,
+        max_storage_buffer_range: 1 << 30,
+        max_push_constants_size: 256,
+        max_memory_allocation_count: 4096,
+        max_sampler_allocation_count: 4000,
+        buffer_image_granularity: 1,
+        sparse_address_space_size: 0,
+        max_bound_descriptor_sets: 8,
+        max_per_stage_descriptor_samplers: 4096,
+        max_per_stage_descriptor_uniform_buffers: 64,
+        max_per_stage_descriptor_storage_buffers: 64,
+        max_per_stage_descriptor_sampled_images: 4096,
+        max_per_stage_descriptor_storage_images: 64,
+        max_per_stage_descriptor_input_attachments: 8,
+        max_per_stage_resources: 8192,
+        max_descriptor_set_samplers: 4096,
+        max_descriptor_set_uniform_buffers: 384,
+        max_descriptor_set_uniform_buffers_dynamic: 16,
+        max_descriptor_set_storage_buffers: 384,
+        max_descriptor_set_storage_buffers_dynamic: 16,
+        max_descriptor_set_sampled_images: 4096,
+        max_descriptor_set_storage_images: 384,
+        max_descriptor_set_input_attachments: 8,
+        max_vertex_input_attributes: 32,
+        max_vertex_input_bindings: 32,
+        max_vertex_input_attribute_offset: 2047,
+        max_vertex_input_binding_stride: 2048,
+        max_vertex_output_components: 128,
+        max_tessellation_generation_level: 64,
+        max_tessellation_patch_size: 32,
+        max_tessellation_control_per_vertex_input_components: 128,
+        max_tessellation_control_per_vertex_output_components: 128,
+        max_tessellation_control_per_patch_output_components: 120,
+        max_tessellation_control_total_output_components: 4096,
+        max_tessellation_evaluation_input_components: 128,
+        max_tessellation_evaluation_output_components: 128,
+        max_geometry_shader_invocations: 32,
+        max_geometry_input_components: 64,
+        max_geometry_output_components: 128,
+        max_geometry_output_vertices: 256,
+        max_geometry_total_output_components: 1024,
+        max_fragment_input_components: 128,
+        max_fragment_output_attachments: 8,
+        max_fragment_dual_src_attachments: 1,
+        max_fragment_combined_output_resources: 72,
+        max_compute_shared_memory_size: 32768,
+        max_compute_work_group_count: [65535, 65535, 65535],
+        max_compute_work_group_invocations: 1024,
+        max_compute_work_group_size: [1024, 1024, 64],
+        sub_pixel_precision_bits: 8,
+        sub_texel_precision_bits: 8,
+        mipmap_precision_bits: 8,
+        max_draw_indexed_index_value: !0,
+        max_draw_indirect_count: !0,
+        max_sampler_lod_bias: 16.0,
+        max_sampler_anisotropy: 16.0,
+        max_viewports: 16,
+        max_viewport_dimensions: [16384, 16384],
+        viewport_bounds_range: [-32768.0, 32767.0],
+        viewport_sub_pixel_bits: 8,
+        min_memory_map_alignment: 64,
+        min_texel_buffer_offset_alignment: 16,
+        min_uniform_buffer_offset_alignment: 256,
+        min_storage_buffer_offset_alignment: 16,
+        min_texel_offset: -8,
+        max_texel_offset: 7,
+        min_texel_gather_offset: -32,
+        max_texel_gather_offset: 31,
+        min_interpolation_offset: -0.5,
+        max_interpolation_offset: 0.4375,
+        sub_pixel_interpolation_offset_bits: 4,
+        max_framebuffer_width: 16384,
+        max_framebuffer_height: 16384,
+        max_framebuffer_layers: 2048,
+        framebuffer_color_sample_counts: sample_counts,
+        framebuffer_depth_sample_counts: sample_counts,
+        framebuffer_stencil_sample_counts: sample_counts,
+        framebuffer_no_attachments_sample_counts: sample_counts,
+        max_color_attachments: 8,
+        sampled_image_color_sample_counts: sample_counts,
+        sampled_image_integer_sample_counts: sample_counts,
+        sampled_image_depth_sample_counts: sample_counts,
+        sampled_image_stencil_sample_counts: sample_counts,
+        storage_image_sample_counts: sample_counts,
+        max_sample_mask_words: 1,
+        timestamp_compute_and_graphics: true,
+        timestamp_period: 1.0,
+        max_clip_distances: 8,
+        max_cull_distances: 8,
+        max_combined_clip_and_cull_distances: 8,
+        discrete_queue_priorities: 2,
+        point_size_range: [1.0, 64.0],
+        line_width_range: [1.0, 8.0],
+        point_size_granularity: 0.125,
+        line_width_granularity: 0.125,
+        strict_lines: false,
+        standard_sample_locations: true,
+        optimal_buffer_copy_offset_alignment: 1,
+        optimal_buffer_copy_row_pitch_alignment: 1,
+        non_coherent_atom_size: 64,
+    }
+}
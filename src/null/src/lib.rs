@@ -0,0 +1,459 @@
+//! A backend that needs no GPU, for testing applications and vk-middle itself.
+//!
+//! Memory lives on the host, so buffers and images can be mapped and transfer commands really
+//! copy, while draws and dispatches do nothing. Work finishes during `vkQueueSubmit`. The device's
+//! features, limits and memory are set by a `Config`, and the library can be loaded as an ICD.
+//!
+//! Pointing `VK_ICD_FILENAMES` at `vk_middle_null_icd.json` makes the loader use the debug build
+//! from the workspace's target directory.
+
+#[macro_use] extern crate vk_middle;
+
+#[macro_use] extern crate log;
+#[macro_use] extern crate lazy_static;
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+use vk_middle::icd::recorded::ObjectRef;
+
+use std::sync::{Arc, Mutex};
+
+mod config;
+pub use config::{Config, configure};
+mod memory;
+pub use memory::{DeviceMemory, Buffer, Image};
+mod queue;
+pub use queue::Queue;
+use queue::Validation;
+
+vulkan_icd_dispatch!(Null);
+
+pub enum Null {
+}
+
+impl icd::Impl for Null {
+    type Instance = Instance;
+    type PhysicalDevice = PhysicalDevice;
+    type Device = Device;
+    type Queue = Queue;
+    type CommandPool = RecordedCommandPool<Null>;
+    type CommandBuffer = RecordedCommandBuffer<Null>;
+
+    type DeviceMemory = DeviceMemory;
+    type Buffer = Buffer;
+    type BufferView = BufferView;
+    type Image = Image;
+    type ImageView = ImageView;
+
+    type Fence = CpuFence;
+    type Semaphore = Semaphore;
+    type Event = HostEvent;
+    type QueryPool = HostQueryPool;
+
+    type RenderPass = RenderPass;
+    type Framebuffer = Framebuffer;
+
+    type Sampler = Sampler;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorPool = DescriptorPool;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
+
+    type ShaderModule = ShaderModule;
+    type Pipeline = Pipeline;
+    type PipelineCache = PipelineCache;
+
+    /// Every command but those of sparse resources and extensions the backend does not expose.
+    fn supports(command: Command) -> bool {
+        match command {
+            Command::CreateInstance |
+            Command::DestroyInstance |
+            Command::EnumeratePhysicalDevices |
+            Command::GetPhysicalDeviceFeatures |
+            Command::GetPhysicalDeviceFormatProperties |
+            Command::GetPhysicalDeviceProperties |
+            Command::GetPhysicalDeviceQueueFamilyProperties |
+            Command::GetPhysicalDeviceMemoryProperties |
+            Command::GetDeviceProcAddr |
+            Command::CreateDevice |
+            Command::DestroyDevice |
+            Command::GetDeviceQueue |
+            Command::QueueSubmit |
+            Command::QueueWaitIdle |
+            Command::DeviceWaitIdle |
+            Command::AllocateMemory |
+            Command::FreeMemory |
+            Command::MapMemory |
+            Command::UnmapMemory |
+            Command::FlushMappedMemoryRanges |
+            Command::InvalidateMappedMemoryRanges |
+            Command::GetDeviceMemoryCommitment |
+            Command::BindBufferMemory |
+            Command::BindImageMemory |
+            Command::GetBufferMemoryRequirements |
+            Command::GetImageMemoryRequirements |
+            Command::CreateBuffer |
+            Command::DestroyBuffer |
+            Command::CreateBufferView |
+            Command::DestroyBufferView |
+            Command::CreateImage |
+            Command::DestroyImage |
+            Command::GetImageSubresourceLayout |
+            Command::CreateImageView |
+            Command::DestroyImageView |
+            Command::CreateFence |
+            Command::DestroyFence |
+            Command::ResetFences |
+            Command::GetFenceStatus |
+            Command::WaitForFences |
+            Command::CreateSemaphore |
+            Command::DestroySemaphore |
+            Command::CreateEvent |
+            Command::DestroyEvent |
+            Command::GetEventStatus |
+            Command::SetEvent |
+            Command::ResetEvent |
+            Command::CreateShaderModule |
+            Command::DestroyShaderModule |
+            Command::CreatePipelineCache |
+            Command::DestroyPipelineCache |
+            Command::GetPipelineCacheData |
+            Command::MergePipelineCaches |
+            Command::CreateGraphicsPipelines |
+            Command::CreateComputePipelines |
+            Command::DestroyPipeline |
+            Command::CreateQueryPool |
+            Command::DestroyQueryPool |
+            Command::GetQueryPoolResults |
+            Command::CreatePipelineLayout |
+            Command::DestroyPipelineLayout |
+            Command::CreateSampler |
+            Command::DestroySampler |
+            Command::CreateDescriptorSetLayout |
+            Command::DestroyDescriptorSetLayout |
+            Command::CreateDescriptorPool |
+            Command::DestroyDescriptorPool |
+            Command::ResetDescriptorPool |
+            Command::AllocateDescriptorSets |
+            Command::FreeDescriptorSets |
+            Command::UpdateDescriptorSets |
+            Command::CreateFramebuffer |
+            Command::DestroyFramebuffer |
+            Command::CreateRenderPass |
+            Command::DestroyRenderPass |
+            Command::GetRenderAreaGranularity |
+            Command::CreateCommandPool |
+            Command::DestroyCommandPool |
+            Command::ResetCommandPool |
+            Command::AllocateCommandBuffers |
+            Command::FreeCommandBuffers |
+            Command::BeginCommandBuffer |
+            Command::EndCommandBuffer |
+            Command::ResetCommandBuffer |
+            Command::CmdBindPipeline |
+            Command::CmdSetViewport |
+            Command::CmdSetScissor |
+            Command::CmdSetLineWidth |
+            Command::CmdSetDepthBias |
+            Command::CmdSetBlendConstants |
+            Command::CmdSetDepthBounds |
+            Command::CmdSetStencilCompareMask |
+            Command::CmdSetStencilWriteMask |
+            Command::CmdSetStencilReference |
+            Command::CmdBindDescriptorSets |
+            Command::CmdBindIndexBuffer |
+            Command::CmdBindVertexBuffers |
+            Command::CmdDraw |
+            Command::CmdDrawIndexed |
+            Command::CmdDrawIndirect |
+            Command::CmdDrawIndexedIndirect |
+            Command::CmdDispatch |
+            Command::CmdDispatchIndirect |
+            Command::CmdCopyBuffer |
+            Command::CmdCopyImage |
+            Command::CmdBlitImage |
+            Command::CmdCopyBufferToImage |
+            Command::CmdCopyImageToBuffer |
+            Command::CmdUpdateBuffer |
+            Command::CmdFillBuffer |
+            Command::CmdClearColorImage |
+            Command::CmdClearDepthStencilImage |
+            Command::CmdClearAttachments |
+            Command::CmdResolveImage |
+            Command::CmdSetEvent |
+            Command::CmdResetEvent |
+            Command::CmdWaitEvents |
+            Command::CmdPipelineBarrier |
+            Command::CmdBeginQuery |
+            Command::CmdEndQuery |
+            Command::CmdResetQueryPool |
+            Command::CmdWriteTimestamp |
+            Command::CmdCopyQueryPoolResults |
+            Command::CmdPushConstants |
+            Command::CmdBeginRenderPass |
+            Command::CmdNextSubpass |
+            Command::CmdEndRenderPass |
+            Command::CmdExecuteCommands |
+            Command::EnumerateInstanceExtensionProperties |
+            Command::EnumerateDeviceExtensionProperties => true,
+            _ => false,
+        }
+    }
+}
+
+pub struct Instance {
+    config: Config,
+}
+
+impl icd::Instance<Null> for Instance {
+    fn create(_info: InstanceCreateInfo<Null>) -> Result<Self> {
+        Ok(Instance {
+            config: config::current(),
+        })
+    }
+
+    fn physical_device_count(&self) -> Result<u32> { Ok(1) }
+
+    fn enumerate_physical_devices(&self, capacity: u32) -> Result<Vec<PhysicalDevice>> {
+        if capacity == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(vec![PhysicalDevice { config: self.config.clone() }])
+    }
+}
+
+pub struct PhysicalDevice {
+    config: Config,
+}
+
+impl icd::PhysicalDevice<Null> for PhysicalDevice {
+    fn get_features(&self) -> PhysicalDeviceFeatures { self.config.features }
+
+    fn get_format_properties(&self, format: Format) -> FormatProperties {
+        if format.block_size().is_none() {
+            return FormatProperties::default();
+        }
+
+        // Nothing is ever sampled or rendered, so every format can claim every use.
+        let features = VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT |
+            VK_FORMAT_FEATURE_STORAGE_IMAGE_BIT |
+            VK_FORMAT_FEATURE_STORAGE_IMAGE_ATOMIC_BIT |
+            VK_FORMAT_FEATURE_UNIFORM_TEXEL_BUFFER_BIT |
+            VK_FORMAT_FEATURE_STORAGE_TEXEL_BUFFER_BIT |
+            VK_FORMAT_FEATURE_STORAGE_TEXEL_BUFFER_ATOMIC_BIT |
+            VK_FORMAT_FEATURE_VERTEX_BUFFER_BIT |
+            VK_FORMAT_FEATURE_COLOR_ATTACHMENT_BIT |
+            VK_FORMAT_FEATURE_COLOR_ATTACHMENT_BLEND_BIT |
+            VK_FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT |
+            VK_FORMAT_FEATURE_BLIT_SRC_BIT |
+            VK_FORMAT_FEATURE_BLIT_DST_BIT |
+            VK_FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT;
+        FormatProperties {
+            linear_tiling_features: features,
+            optimal_tiling_features: features,
+            buffer_features: features,
+        }
+    }
+
+    fn get_properties(&self) -> PhysicalDeviceProperties { self.config.properties.clone() }
+
+    fn get_queue_family_properties(&self) -> Vec<QueueFamilyProperties> {
+        vec![QueueFamilyProperties {
+            queue_flags: VK_QUEUE_GRAPHICS_BIT | VK_QUEUE_COMPUTE_BIT | VK_QUEUE_TRANSFER_BIT,
+            queue_count: self.config.queue_count,
+            timestamp_valid_bits: 64,
+            min_image_transfer_granularity: Extent3D { width: 1, height: 1, depth: 1 },
+        }]
+    }
+
+    fn get_memory_properties(&self) -> PhysicalDeviceMemoryProperties {
+        PhysicalDeviceMemoryProperties {
+            memory_types: vec![MemoryType {
+                property_flags: VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT |
+                    VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT |
+                    VK_MEMORY_PROPERTY_HOST_COHERENT_BIT |
+                    VK_MEMORY_PROPERTY_HOST_CACHED_BIT,
+                heap_index: 0,
+            }],
+            memory_heaps: vec![MemoryHeap {
+                size: self.config.heap_size,
+                flags: VK_MEMORY_HEAP_DEVICE_LOCAL_BIT,
+            }],
+        }
+    }
+
+    fn create_device(&self, info: DeviceCreateInfo<Null>) -> Result<Device> {
+        for queue in info.queues() {
+            if queue.family_index() != 0 || queue.count() > self.config.queue_count {
+                error!("vkCreateDevice called for {} queues of family {}, which the device does not have", queue.count(), queue.family_index());
+                return Err(Error::InitializationFailed);
+            }
+        }
+
+        Ok(Device {
+            heap_size: self.config.heap_size,
+            allocated: Mutex::new(0),
+            validation: if self.config.validate || self.config.validate_synchronization {
+                Some(Arc::new(Mutex::new(Validation::new(self.config.validate, self.config.validate_synchronization))))
+            } else {
+                None
+            },
+        })
+    }
+}
+
+pub struct Device {
+    heap_size: u64,
+    allocated: Mutex<u64>,
+    /// Shared by every queue of the device, since each submission finishes before the next one
+    /// starts whatever queue it is made on.
+    validation: Option<Arc<Mutex<Validation>>>,
+}
+
+impl Device {
+    fn release_memory(&self, size: u64) {
+        *self.allocated.lock().unwrap() -= size;
+    }
+}
+
+impl icd::Device<Null> for Device {
+    fn get_queue(&self, _family_index: u32, _queue_index: u32) -> Result<Queue> {
+        Ok(Queue::new(self.validation.clone()))
+    }
+
+    fn wait_idle(&self) -> Result<()> { Ok(()) }
+
+    fn allocate_memory(&self, info: MemoryAllocateInfo<Null>) -> Result<DeviceMemory> {
+        if info.memory_type_index() != 0 {
+            error!("vkAllocateMemory called with memory type {}, which the device does not have", info.memory_type_index());
+            return Err(Error::OutOfDeviceMemory);
+        }
+
+        let size = info.allocation_size();
+        let mut allocated = self.allocated.lock().unwrap();
+        if self.heap_size - *allocated < size {
+            return Err(Error::OutOfDeviceMemory);
+        }
+        let memory = try!(DeviceMemory::new(size));
+        *allocated += size;
+        Ok(memory)
+    }
+
+    fn flush_mapped_memory_ranges(&self, _ranges: MappedMemoryRanges<Null>) -> Result<()> { Ok(()) }
+    fn invalidate_mapped_memory_ranges(&self, _ranges: MappedMemoryRanges<Null>) -> Result<()> { Ok(()) }
+}
+
+macro_rules! null_objects {
+    ($($name:ident : $create_info:ident,)*) => {
+        $(
+            pub struct $name {
+            }
+
+            impl icd::$name<Null> for $name {
+                fn new(_device: &Device, _info: $create_info<Null>) -> Result<Self> { Ok($name {}) }
+            }
+        )*
+    }
+}
+
+null_objects! {
+    BufferView: BufferViewCreateInfo,
+    Semaphore: SemaphoreCreateInfo,
+    Sampler: SamplerCreateInfo,
+    DescriptorSetLayout: DescriptorSetLayoutCreateInfo,
+    PipelineLayout: PipelineLayoutCreateInfo,
+    ShaderModule: ShaderModuleCreateInfo,
+}
+
+/// Keeps what validating submissions needs to know about the attachments of a framebuffer.
+pub struct ImageView {
+    image: ObjectRef<Image>,
+    range: ImageSubresourceRange,
+}
+
+impl icd::ImageView<Null> for ImageView {
+    fn new(_device: &Device, info: ImageViewCreateInfo<Null>) -> Result<Self> {
+        Ok(ImageView {
+            image: ObjectRef::new(info.image()),
+            range: info.subresource_range(),
+        })
+    }
+}
+
+pub struct RenderPass {
+    attachment_usage: Vec<Option<AttachmentUsage>>,
+    dependencies: Vec<SubpassDependency>,
+}
+
+impl icd::RenderPass<Null> for RenderPass {
+    fn new(_device: &Device, info: RenderPassCreateInfo<Null>) -> Result<Self> {
+        Ok(RenderPass {
+            attachment_usage: info.attachment_usage(),
+            dependencies: info.dependencies_with_implicit(),
+        })
+    }
+}
+
+pub struct Framebuffer {
+    attachments: Vec<(ObjectRef<Image>, ImageSubresourceRange)>,
+}
+
+impl icd::Framebuffer<Null> for Framebuffer {
+    fn new(_device: &Device, info: FramebufferCreateInfo<Null>) -> Result<Self> {
+        Ok(Framebuffer {
+            attachments: info.attachments().map(|x| (x.image, x.range)).collect(),
+        })
+    }
+}
+
+/// Holds no entries of its own, but keeps those of initial data and merges so that applications
+/// get back what they put in.
+pub struct PipelineCache {
+    data: PipelineCacheData,
+}
+
+impl icd::PipelineCache<Null> for PipelineCache {
+    fn new(_device: &Device, info: PipelineCacheCreateInfo<Null>) -> Result<Self> {
+        Ok(PipelineCache { data: PipelineCacheData::new(&info) })
+    }
+
+    fn data(&self) -> Option<&PipelineCacheData> { Some(&self.data) }
+}
+
+pub struct DescriptorPool {
+}
+
+impl icd::DescriptorPool<Null> for DescriptorPool {
+    fn new(_device: &Device, _info: DescriptorPoolCreateInfo<Null>) -> Result<Self> { Ok(DescriptorPool {}) }
+
+    fn allocate(&mut self, _device: &Device, layouts: &[&DescriptorSetLayout]) -> Result<Vec<DescriptorSet>> {
+        Ok(layouts.iter().map(|_| DescriptorSet {}).collect())
+    }
+}
+
+pub struct DescriptorSet {
+}
+
+impl icd::DescriptorSet<Null> for DescriptorSet {
+}
+
+pub struct Pipeline {
+}
+
+impl icd::Pipeline<Null> for Pipeline {
+    fn create_graphics(
+        _device: &Device,
+        _pipeline_cache: Option<&PipelineCache>,
+        create_infos: &[GraphicsPipelineCreateInfo<Null>],
+    ) -> Vec<Result<Self>> {
+        create_infos.iter().map(|_| Ok(Pipeline {})).collect()
+    }
+
+    fn create_compute(
+        _device: &Device,
+        _pipeline_cache: Option<&PipelineCache>,
+        create_infos: &[ComputePipelineCreateInfo<Null>],
+    ) -> Vec<Result<Self>> {
+        create_infos.iter().map(|_| Ok(Pipeline {})).collect()
+    }
+}
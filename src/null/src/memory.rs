@@ -0,0 +1,146 @@
+//! Device memory backed by host allocations, and the buffers and images bound to it.
+//!
+//! Images use `HostImageLayout` whatever their tiling, which is also what
+//! `vkGetImageSubresourceLayout` reports, so linear images can be written through a mapping.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use super::{Null, Device};
+
+/// The alignment required of buffers and images within an allocation. Covers every minimum
+/// offset alignment in the default limits.
+const RESOURCE_ALIGNMENT: u64 = 256;
+
+pub struct DeviceMemory {
+    memory: HostMemory,
+}
+
+impl DeviceMemory {
+    pub(crate) fn new(size: u64) -> Result<DeviceMemory> {
+        Ok(DeviceMemory { memory: try!(HostMemory::new(size)) })
+    }
+}
+
+impl icd::DeviceMemory<Null> for DeviceMemory {
+    fn map(&mut self, _device: &Device, offset: u64, _size: u64, _flags: MemoryMapFlags) -> Result<*mut u8> {
+        Ok(self.memory.ptr(offset))
+    }
+
+    fn get_memory_commitment(&self, _device: &Device) -> u64 { self.memory.size() }
+
+    fn destroy(self, device: &Device) {
+        device.release_memory(self.memory.size());
+    }
+}
+
+fn bind(memory: &DeviceMemory, offset: u64, size: u64) -> Result<*mut u8> {
+    match memory.memory.bind(offset, size, RESOURCE_ALIGNMENT) {
+        Some(data) => Ok(data),
+        None => {
+            error!("resource of {} bytes bound at offset {} of an allocation of {} bytes", size, offset, memory.memory.size());
+            Err(Error::OutOfDeviceMemory)
+        },
+    }
+}
+
+/// Numbers buffers and images in the order they are created.
+static NEXT_SERIAL: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub struct Buffer {
+    size: u64,
+    data: *mut u8,
+    serial: u64,
+}
+
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    pub fn size(&self) -> u64 { self.size }
+
+    /// The address of the byte at `offset`. Memory must be bound.
+    pub fn ptr(&self, offset: u64) -> *mut u8 {
+        unsafe { self.data.offset(offset as isize) }
+    }
+
+    /// Distinguishes the buffer from every other buffer or image created by the process.
+    pub(crate) fn serial(&self) -> u64 { self.serial }
+}
+
+impl icd::Buffer<Null> for Buffer {
+    fn new(_device: &Device, info: BufferCreateInfo<Null>) -> Result<Self> {
+        Ok(Buffer {
+            size: info.size(),
+            data: ptr::null_mut(),
+            serial: NEXT_SERIAL.fetch_add(1, Ordering::Relaxed) as u64,
+        })
+    }
+
+    fn get_memory_requirements(&self, _device: &Device) -> MemoryRequirements {
+        MemoryRequirements {
+            size: self.size,
+            alignment: RESOURCE_ALIGNMENT,
+            memory_types: 1,
+        }
+    }
+
+    fn bind_memory(&mut self, _device: &Device, memory: &DeviceMemory, offset: u64) -> Result<()> {
+        self.data = try!(bind(memory, offset, self.size));
+        Ok(())
+    }
+}
+
+pub struct Image {
+    layout: HostImageLayout,
+    data: *mut u8,
+    serial: u64,
+}
+
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Image {
+    pub fn layout(&self) -> &HostImageLayout { &self.layout }
+
+    /// The address of the first byte of the image. Memory must be bound.
+    pub fn ptr(&self) -> *mut u8 { self.data }
+
+    /// Distinguishes the image from every other buffer or image created by the process.
+    pub(crate) fn serial(&self) -> u64 { self.serial }
+}
+
+impl icd::Image<Null> for Image {
+    fn new(_device: &Device, info: ImageCreateInfo<Null>) -> Result<Self> {
+        let layout = match HostImageLayout::new(info.format(), info.extent(), info.mip_levels(), info.array_layers()) {
+            Some(layout) => layout,
+            None => return Err(Error::FormatNotSupported),
+        };
+
+        Ok(Image {
+            layout: layout,
+            data: ptr::null_mut(),
+            serial: NEXT_SERIAL.fetch_add(1, Ordering::Relaxed) as u64,
+        })
+    }
+
+    fn get_memory_requirements(&self, _device: &Device) -> MemoryRequirements {
+        MemoryRequirements {
+            size: self.layout.size(),
+            alignment: RESOURCE_ALIGNMENT,
+            memory_types: 1,
+        }
+    }
+
+    fn bind_memory(&mut self, _device: &Device, memory: &DeviceMemory, offset: u64) -> Result<()> {
+        self.data = try!(bind(memory, offset, self.layout.size()));
+        Ok(())
+    }
+
+    fn get_subresource_layout(&self, _device: &Device, subresource: ImageSubresource<Null>) -> SubresourceLayout {
+        self.layout.subresource_layout(subresource.mip_level(), subresource.array_layer())
+    }
+}
@@ -0,0 +1,202 @@
+//! Executes recorded command buffers on the host as they are submitted.
+//!
+//! Copies, updates and fills of buffers and images, events and queries take effect. Everything
+//! else (draws, dispatches, clears, blits and resolves) is accepted and does nothing, and queries
+//! report zero for anything but timestamps. Submission finishes all the work before returning, so
+//! semaphores never have anything to wait for and fences are signaled right away.
+//!
+//! With `Config::validate` set, the image layouts of each submission are checked against those
+//! left by earlier submissions before anything runs. With `Config::validate_synchronization` set,
+//! so are its memory accesses, for hazards with each other and with earlier submissions.
+
+use vk_middle::icd;
+use vk_middle::icd::*;
+use vk_middle::icd::recorded::{Command, CommandStream, ObjectRef};
+use vk_middle::icd::layout::{LayoutSource, LayoutTracker};
+use vk_middle::icd::hazard::{HazardSource, HazardTracker};
+use vk_middle::icd::query::write_query_results;
+use vk_middle::icd::host::{self, copy_buffer_image, copy_image};
+
+use std::{ptr, slice};
+use std::sync::{Arc, Mutex};
+
+use super::{Null, Buffer, Image, RenderPass, Framebuffer};
+
+pub struct Queue {
+    validation: Option<Arc<Mutex<Validation>>>,
+}
+
+impl Queue {
+    pub(crate) fn new(validation: Option<Arc<Mutex<Validation>>>) -> Queue {
+        Queue { validation: validation }
+    }
+}
+
+impl icd::Queue<Null> for Queue {
+    fn submit(&mut self, submits: SubmitInfos<Null>, fence: Option<&mut CpuFence>) -> Result<()> {
+        if let Some(ref validation) = self.validation {
+            try!(validation.lock().unwrap().check(&submits));
+        }
+
+        for submit in submits.iter() {
+            for command_buffer in submit.command_buffers() {
+                unsafe { execute(command_buffer.stream()) };
+            }
+        }
+
+        if let Some(fence) = fence {
+            fence.signal();
+        }
+        Ok(())
+    }
+
+    fn wait_idle(&self) -> Result<()> { Ok(()) }
+    fn is_idle(&self) -> bool { true }
+}
+
+/// What submissions are checked against.
+pub(crate) struct Validation {
+    layouts: Option<LayoutTracker<Null>>,
+    hazards: Option<HazardTracker<Null>>,
+}
+
+impl Validation {
+    pub(crate) fn new(layouts: bool, hazards: bool) -> Validation {
+        Validation {
+            layouts: if layouts { Some(LayoutTracker::new(true)) } else { None },
+            hazards: if hazards { Some(HazardTracker::new()) } else { None },
+        }
+    }
+
+    /// Follows the submissions, which are refused if they use images in the wrong layouts or
+    /// access memory without the synchronization they need. A refused submission leaves the
+    /// tracked state as it was.
+    fn check(&mut self, submits: &SubmitInfos<Null>) -> Result<()> {
+        let mut layouts = self.layouts.clone();
+        let mut hazards = self.hazards.clone();
+        for submit in submits.iter() {
+            if let Some(ref mut layouts) = layouts {
+                for command_buffer in submit.command_buffers() {
+                    layouts.execute(&Objects, command_buffer.stream());
+                }
+            }
+            if let Some(ref mut hazards) = hazards {
+                hazards.submit(&Objects, &submit);
+            }
+        }
+
+        if layouts.as_mut().map_or(false, |x| !x.take_mismatches().is_empty()) {
+            error!("vkQueueSubmit refused, since it uses images in the wrong layouts");
+            return Err(Error::ValidationFailed);
+        }
+        if hazards.as_mut().map_or(false, |x| !x.take_hazards().is_empty()) {
+            error!("vkQueueSubmit refused, since it accesses memory without the synchronization it needs");
+            return Err(Error::ValidationFailed);
+        }
+        self.layouts = layouts;
+        self.hazards = hazards;
+        Ok(())
+    }
+}
+
+/// Answers for the objects themselves, which keep what the trackers need.
+struct Objects;
+
+impl LayoutSource<Null> for Objects {
+    fn image_subresource_counts(&self, image: &Image) -> (u32, u32) {
+        (image.layout().mip_levels, image.layout().array_layers)
+    }
+
+    fn render_pass_attachment_usage(&self, render_pass: &RenderPass) -> Vec<Option<AttachmentUsage>> {
+        render_pass.attachment_usage.clone()
+    }
+
+    fn framebuffer_attachments(&self, framebuffer: &Framebuffer) -> Vec<(ObjectRef<Image>, ImageSubresourceRange)> {
+        framebuffer.attachments.clone()
+    }
+
+    fn image_serial(&self, image: &Image) -> u64 { image.serial() }
+}
+
+impl HazardSource<Null> for Objects {
+    fn render_pass_dependencies(&self, render_pass: &RenderPass) -> Vec<SubpassDependency> {
+        render_pass.dependencies.clone()
+    }
+
+    fn buffer_serial(&self, buffer: &Buffer) -> u64 { buffer.serial() }
+}
+
+unsafe fn execute(stream: &CommandStream<Null>) {
+    for command in stream.commands() {
+        match *command {
+            Command::CopyBuffer { src, dst, regions } => {
+                for region in stream.get(regions) {
+                    ptr::copy(src.get().ptr(region.src_offset), dst.get().ptr(region.dst_offset), region.size as usize);
+                }
+            },
+            Command::CopyImage { src, dst, regions, .. } => {
+                let (src, dst) = (src.get(), dst.get());
+                for region in stream.get(regions) {
+                    copy_image(src.ptr(), src.layout(), dst.ptr(), dst.layout(), region);
+                }
+            },
+            Command::CopyBufferToImage { src, dst, regions, .. } => {
+                let (src, dst) = (src.get(), dst.get());
+                for region in stream.get(regions) {
+                    copy_buffer_image(src.ptr(0), dst.ptr(), dst.layout(), region, true);
+                }
+            },
+            Command::CopyImageToBuffer { src, dst, regions, .. } => {
+                let (src, dst) = (src.get(), dst.get());
+                for region in stream.get(regions) {
+                    copy_buffer_image(dst.ptr(0), src.ptr(), src.layout(), region, false);
+                }
+            },
+            Command::UpdateBuffer { dst, offset, data } => {
+                let data = stream.get(data);
+                ptr::copy_nonoverlapping(data.as_ptr(), dst.get().ptr(offset), data.len());
+            },
+            Command::FillBuffer { dst, offset, size, data } => {
+                let dst = dst.get();
+                let size = if size == WHOLE_SIZE { (dst.size() - offset) & !3 } else { size };
+                let words = dst.ptr(offset) as *mut u32;
+                for i in 0..(size / 4) {
+                    *words.offset(i as isize) = data;
+                }
+            },
+
+            Command::SetEvent { event, .. } => event.get().set_signaled(true),
+            Command::ResetEvent { event, .. } => event.get().set_signaled(false),
+
+            Command::EndQuery { pool, query } => {
+                pool.get().write(query, Some([0; MAX_QUERY_VALUES]));
+            },
+            Command::ResetQueryPool { pool, first_query, query_count } => {
+                for query in first_query..(first_query + query_count) {
+                    pool.get().write(query, None);
+                }
+            },
+            Command::WriteTimestamp { pool, query, .. } => {
+                let mut values = [0; MAX_QUERY_VALUES];
+                values[0] = host::timestamp();
+                pool.get().write(query, Some(values));
+            },
+            Command::CopyQueryPoolResults { pool, first_query, query_count, dst, offset, stride, flags } => {
+                let pool = pool.get();
+                let dst = dst.get();
+                let data = slice::from_raw_parts_mut(dst.ptr(offset), (dst.size() - offset) as usize);
+                if write_query_results(&pool.read(first_query..(first_query + query_count)), pool.value_count(), data, stride as usize, flags).is_err() {
+                    error!("vkCmdCopyQueryPoolResults writes past the end of its buffer");
+                }
+            },
+
+            Command::ExecuteCommands(command_buffers) => {
+                for command_buffer in stream.get(command_buffers) {
+                    execute(command_buffer.get().stream());
+                }
+            },
+
+            _ => {},
+        }
+    }
+}
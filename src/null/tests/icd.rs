@@ -0,0 +1,664 @@
+//! Drives the null backend the way the Vulkan loader does, through `vk_icdGetInstanceProcAddr`.
+
+extern crate vk_middle_null;
+extern crate vk_middle_sys as sys;
+
+use sys::*;
+
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+unsafe fn proc_addr<T>(instance: VkInstance, name: &str) -> T {
+    let name = CString::new(name).unwrap();
+    let addr = vk_middle_null::vk_icdGetInstanceProcAddr(instance as *mut _, name.as_ptr());
+    assert!(addr.is_some(), "{:?} is not advertised", name);
+    mem::transmute_copy(&addr)
+}
+
+unsafe fn create_instance(extensions: &[*const c_char]) -> (VkResult, VkInstance) {
+    let create: PFN_vkCreateInstance = proc_addr(ptr::null_mut(), "vkCreateInstance");
+    let info = VkInstanceCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_INSTANCE_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: 0,
+        pApplicationInfo: ptr::null(),
+        enabledLayerCount: 0,
+        ppEnabledLayerNames: ptr::null(),
+        enabledExtensionCount: extensions.len() as u32,
+        ppEnabledExtensionNames: extensions.as_ptr(),
+    };
+    let mut instance = ptr::null_mut();
+    let result = create.unwrap()(&info, ptr::null(), &mut instance);
+    (result, instance)
+}
+
+unsafe fn physical_device(instance: VkInstance) -> VkPhysicalDevice {
+    let enumerate: PFN_vkEnumeratePhysicalDevices = proc_addr(instance, "vkEnumeratePhysicalDevices");
+    let mut count = 0;
+    assert_eq!(enumerate.unwrap()(instance, &mut count, ptr::null_mut()), VkResult::VK_SUCCESS);
+    assert_eq!(count, 1);
+    let mut physical_device = ptr::null_mut();
+    assert_eq!(enumerate.unwrap()(instance, &mut count, &mut physical_device), VkResult::VK_SUCCESS);
+    physical_device
+}
+
+unsafe fn create_device(instance: VkInstance, physical_device: VkPhysicalDevice) -> VkDevice {
+    let priority = 1.0f32;
+    let queue_info = VkDeviceQueueCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_DEVICE_QUEUE_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: 0,
+        queueFamilyIndex: 0,
+        queueCount: 1,
+        pQueuePriorities: &priority,
+    };
+    let device_info = VkDeviceCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_DEVICE_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: 0,
+        queueCreateInfoCount: 1,
+        pQueueCreateInfos: &queue_info,
+        enabledLayerCount: 0,
+        ppEnabledLayerNames: ptr::null(),
+        enabledExtensionCount: 0,
+        ppEnabledExtensionNames: ptr::null(),
+        pEnabledFeatures: ptr::null(),
+    };
+    let create_device: PFN_vkCreateDevice = proc_addr(instance, "vkCreateDevice");
+    let mut device = ptr::null_mut();
+    assert_eq!(create_device.unwrap()(physical_device, &device_info, ptr::null(), &mut device), VkResult::VK_SUCCESS);
+    device
+}
+
+#[test]
+fn unknown_commands_are_not_advertised() {
+    // The last two have trampolines, but the backend does not implement them.
+    for name in &["vkNotACommand", "vkGetImageSparseMemoryRequirements", "vkTrimCommandPoolKHR"] {
+        let name = CString::new(*name).unwrap();
+        unsafe {
+            assert!(vk_middle_null::vk_icdGetInstanceProcAddr(ptr::null_mut(), name.as_ptr()).is_none(), "{:?} is advertised", name);
+        }
+    }
+}
+
+#[test]
+fn no_extensions_are_reported() {
+    unsafe {
+        let enumerate: PFN_vkEnumerateInstanceExtensionProperties = proc_addr(ptr::null_mut(), "vkEnumerateInstanceExtensionProperties");
+        let mut count = 1;
+        assert_eq!(enumerate.unwrap()(ptr::null(), &mut count, ptr::null_mut()), VkResult::VK_SUCCESS);
+        assert_eq!(count, 0);
+
+        let layer = CString::new("VK_LAYER_LUNARG_standard_validation").unwrap();
+        assert_eq!(enumerate.unwrap()(layer.as_ptr(), &mut count, ptr::null_mut()), VkResult::VK_ERROR_LAYER_NOT_PRESENT);
+
+        let extension = CString::new("VK_KHR_surface").unwrap();
+        let (result, _) = create_instance(&[extension.as_ptr()]);
+        assert_eq!(result, VkResult::VK_ERROR_EXTENSION_NOT_PRESENT);
+
+        let (result, instance) = create_instance(&[]);
+        assert_eq!(result, VkResult::VK_SUCCESS);
+        let enumerate: PFN_vkEnumerateDeviceExtensionProperties = proc_addr(instance, "vkEnumerateDeviceExtensionProperties");
+        let mut count = 1;
+        assert_eq!(enumerate.unwrap()(physical_device(instance), ptr::null(), &mut count, ptr::null_mut()), VkResult::VK_SUCCESS);
+        assert_eq!(count, 0);
+
+        let destroy: PFN_vkDestroyInstance = proc_addr(instance, "vkDestroyInstance");
+        destroy.unwrap()(instance, ptr::null());
+    }
+}
+
+#[test]
+fn copies_between_mapped_buffers() {
+    const SIZE: usize = 256;
+
+    unsafe {
+        let (result, instance) = create_instance(&[]);
+        assert_eq!(result, VkResult::VK_SUCCESS);
+        let physical_device = physical_device(instance);
+
+        let get_properties: PFN_vkGetPhysicalDeviceProperties = proc_addr(instance, "vkGetPhysicalDeviceProperties");
+        let mut properties: VkPhysicalDeviceProperties = mem::zeroed();
+        get_properties.unwrap()(physical_device, &mut properties);
+        assert_eq!(CStr::from_ptr(properties.deviceName.as_ptr()).to_str(), Ok("vk-middle null device"));
+
+        let device = create_device(instance, physical_device);
+
+        let get_queue: PFN_vkGetDeviceQueue = proc_addr(instance, "vkGetDeviceQueue");
+        let mut queue = ptr::null_mut();
+        get_queue.unwrap()(device, 0, 0, &mut queue);
+        assert!(!queue.is_null());
+
+        // One allocation backs both buffers, the source in the first half and the destination in
+        // the second.
+        let allocate_memory: PFN_vkAllocateMemory = proc_addr(instance, "vkAllocateMemory");
+        let allocate_info = VkMemoryAllocateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+            pNext: ptr::null(),
+            allocationSize: 2 * SIZE as u64,
+            memoryTypeIndex: 0,
+        };
+        let mut memory = mem::zeroed();
+        assert_eq!(allocate_memory.unwrap()(device, &allocate_info, ptr::null(), &mut memory), VkResult::VK_SUCCESS);
+
+        let create_buffer: PFN_vkCreateBuffer = proc_addr(instance, "vkCreateBuffer");
+        let bind_buffer_memory: PFN_vkBindBufferMemory = proc_addr(instance, "vkBindBufferMemory");
+        let buffer_info = VkBufferCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            size: SIZE as u64,
+            usage: VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_SRC_BIT as u32 |
+                VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_DST_BIT as u32,
+            sharingMode: VkSharingMode::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: ptr::null(),
+        };
+        let (mut src, mut dst) = (mem::zeroed(), mem::zeroed());
+        assert_eq!(create_buffer.unwrap()(device, &buffer_info, ptr::null(), &mut src), VkResult::VK_SUCCESS);
+        assert_eq!(create_buffer.unwrap()(device, &buffer_info, ptr::null(), &mut dst), VkResult::VK_SUCCESS);
+        assert_eq!(bind_buffer_memory.unwrap()(device, src, memory, 0), VkResult::VK_SUCCESS);
+        assert_eq!(bind_buffer_memory.unwrap()(device, dst, memory, SIZE as u64), VkResult::VK_SUCCESS);
+
+        let map_memory: PFN_vkMapMemory = proc_addr(instance, "vkMapMemory");
+        let mut mapped = ptr::null_mut();
+        assert_eq!(map_memory.unwrap()(device, memory, 0, 2 * SIZE as u64, 0, &mut mapped), VkResult::VK_SUCCESS);
+        let mapped = slice::from_raw_parts_mut(mapped as *mut u8, 2 * SIZE);
+        for (i, byte) in mapped[..SIZE].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let create_command_pool: PFN_vkCreateCommandPool = proc_addr(instance, "vkCreateCommandPool");
+        let pool_info = VkCommandPoolCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            queueFamilyIndex: 0,
+        };
+        let mut pool = mem::zeroed();
+        assert_eq!(create_command_pool.unwrap()(device, &pool_info, ptr::null(), &mut pool), VkResult::VK_SUCCESS);
+
+        let allocate_command_buffers: PFN_vkAllocateCommandBuffers = proc_addr(instance, "vkAllocateCommandBuffers");
+        let command_buffer_info = VkCommandBufferAllocateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO,
+            pNext: ptr::null(),
+            commandPool: pool,
+            level: VkCommandBufferLevel::VK_COMMAND_BUFFER_LEVEL_PRIMARY,
+            commandBufferCount: 1,
+        };
+        let mut command_buffer = ptr::null_mut();
+        assert_eq!(allocate_command_buffers.unwrap()(device, &command_buffer_info, &mut command_buffer), VkResult::VK_SUCCESS);
+
+        let begin: PFN_vkBeginCommandBuffer = proc_addr(instance, "vkBeginCommandBuffer");
+        let copy: PFN_vkCmdCopyBuffer = proc_addr(instance, "vkCmdCopyBuffer");
+        let end: PFN_vkEndCommandBuffer = proc_addr(instance, "vkEndCommandBuffer");
+        let begin_info = VkCommandBufferBeginInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+            pNext: ptr::null(),
+            flags: VkCommandBufferUsageFlagBits::VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT as u32,
+            pInheritanceInfo: ptr::null(),
+        };
+        let region = VkBufferCopy { srcOffset: 16, dstOffset: 0, size: (SIZE - 16) as u64 };
+        assert_eq!(begin.unwrap()(command_buffer, &begin_info), VkResult::VK_SUCCESS);
+        copy.unwrap()(command_buffer, src, dst, 1, &region);
+        assert_eq!(end.unwrap()(command_buffer), VkResult::VK_SUCCESS);
+
+        let create_fence: PFN_vkCreateFence = proc_addr(instance, "vkCreateFence");
+        let fence_info = VkFenceCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_FENCE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+        };
+        let mut fence = mem::zeroed();
+        assert_eq!(create_fence.unwrap()(device, &fence_info, ptr::null(), &mut fence), VkResult::VK_SUCCESS);
+
+        let submit: PFN_vkQueueSubmit = proc_addr(instance, "vkQueueSubmit");
+        let submit_info = VkSubmitInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_SUBMIT_INFO,
+            pNext: ptr::null(),
+            waitSemaphoreCount: 0,
+            pWaitSemaphores: ptr::null(),
+            pWaitDstStageMask: ptr::null(),
+            commandBufferCount: 1,
+            pCommandBuffers: &command_buffer,
+            signalSemaphoreCount: 0,
+            pSignalSemaphores: ptr::null(),
+        };
+        assert_eq!(submit.unwrap()(queue, 1, &submit_info, fence), VkResult::VK_SUCCESS);
+
+        let wait_for_fences: PFN_vkWaitForFences = proc_addr(instance, "vkWaitForFences");
+        assert_eq!(wait_for_fences.unwrap()(device, 1, &fence, VK_TRUE, 0), VkResult::VK_SUCCESS);
+
+        let expected: Vec<u8> = (16..SIZE).map(|i| i as u8).collect();
+        assert_eq!(&mapped[SIZE..2 * SIZE - 16], &expected[..]);
+        assert!(mapped[2 * SIZE - 16..].iter().all(|&x| x == 0));
+
+        let device_wait_idle: PFN_vkDeviceWaitIdle = proc_addr(instance, "vkDeviceWaitIdle");
+        assert_eq!(device_wait_idle.unwrap()(device), VkResult::VK_SUCCESS);
+
+        let destroy_fence: PFN_vkDestroyFence = proc_addr(instance, "vkDestroyFence");
+        let destroy_command_pool: PFN_vkDestroyCommandPool = proc_addr(instance, "vkDestroyCommandPool");
+        let destroy_buffer: PFN_vkDestroyBuffer = proc_addr(instance, "vkDestroyBuffer");
+        let unmap_memory: PFN_vkUnmapMemory = proc_addr(instance, "vkUnmapMemory");
+        let free_memory: PFN_vkFreeMemory = proc_addr(instance, "vkFreeMemory");
+        let destroy_device: PFN_vkDestroyDevice = proc_addr(instance, "vkDestroyDevice");
+        let destroy_instance: PFN_vkDestroyInstance = proc_addr(instance, "vkDestroyInstance");
+        destroy_fence.unwrap()(device, fence, ptr::null());
+        destroy_command_pool.unwrap()(device, pool, ptr::null());
+        destroy_buffer.unwrap()(device, src, ptr::null());
+        destroy_buffer.unwrap()(device, dst, ptr::null());
+        unmap_memory.unwrap()(device, memory);
+        free_memory.unwrap()(device, memory, ptr::null());
+        destroy_device.unwrap()(device, ptr::null());
+        destroy_instance.unwrap()(instance, ptr::null());
+    }
+}
+
+unsafe fn create_descriptor_pool(instance: VkInstance, device: VkDevice, flags: VkDescriptorPoolCreateFlags) -> VkDescriptorPool {
+    let create_descriptor_pool: PFN_vkCreateDescriptorPool = proc_addr(instance, "vkCreateDescriptorPool");
+    let size = VkDescriptorPoolSize {
+        type_: VkDescriptorType::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+        descriptorCount: 4,
+    };
+    let info = VkDescriptorPoolCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: flags,
+        maxSets: 4,
+        poolSizeCount: 1,
+        pPoolSizes: &size,
+    };
+    let mut pool = mem::zeroed();
+    assert_eq!(create_descriptor_pool.unwrap()(device, &info, ptr::null(), &mut pool), VkResult::VK_SUCCESS);
+    pool
+}
+
+unsafe fn allocate_descriptor_sets(instance: VkInstance, device: VkDevice, pool: VkDescriptorPool, layout: VkDescriptorSetLayout) -> [VkDescriptorSet; 2] {
+    let allocate_descriptor_sets: PFN_vkAllocateDescriptorSets = proc_addr(instance, "vkAllocateDescriptorSets");
+    let layouts = [layout, layout];
+    let info = VkDescriptorSetAllocateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_ALLOCATE_INFO,
+        pNext: ptr::null(),
+        descriptorPool: pool,
+        descriptorSetCount: 2,
+        pSetLayouts: layouts.as_ptr(),
+    };
+    let mut sets: [VkDescriptorSet; 2] = mem::zeroed();
+    assert_eq!(allocate_descriptor_sets.unwrap()(device, &info, sets.as_mut_ptr()), VkResult::VK_SUCCESS);
+    sets
+}
+
+#[test]
+fn descriptor_sets_are_allocated_freed_and_reset() {
+    unsafe {
+        let (result, instance) = create_instance(&[]);
+        assert_eq!(result, VkResult::VK_SUCCESS);
+        let device = create_device(instance, physical_device(instance));
+
+        let create_set_layout: PFN_vkCreateDescriptorSetLayout = proc_addr(instance, "vkCreateDescriptorSetLayout");
+        let binding = VkDescriptorSetLayoutBinding {
+            binding: 0,
+            descriptorType: VkDescriptorType::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+            descriptorCount: 1,
+            stageFlags: VkShaderStageFlagBits::VK_SHADER_STAGE_COMPUTE_BIT as u32,
+            pImmutableSamplers: ptr::null(),
+        };
+        let layout_info = VkDescriptorSetLayoutCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            bindingCount: 1,
+            pBindings: &binding,
+        };
+        let mut layout = mem::zeroed();
+        assert_eq!(create_set_layout.unwrap()(device, &layout_info, ptr::null(), &mut layout), VkResult::VK_SUCCESS);
+
+        let free_descriptor_sets: PFN_vkFreeDescriptorSets = proc_addr(instance, "vkFreeDescriptorSets");
+        let reset_descriptor_pool: PFN_vkResetDescriptorPool = proc_addr(instance, "vkResetDescriptorPool");
+        let destroy_descriptor_pool: PFN_vkDestroyDescriptorPool = proc_addr(instance, "vkDestroyDescriptorPool");
+
+        // Sets of a pool created without VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT can only
+        // be released all at once.
+        let fixed = create_descriptor_pool(instance, device, 0);
+        let fixed_sets = allocate_descriptor_sets(instance, device, fixed, layout);
+        assert!(!fixed_sets[0].is_null() && fixed_sets[0] != fixed_sets[1]);
+        assert_eq!(free_descriptor_sets.unwrap()(device, fixed, 1, fixed_sets.as_ptr()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+        assert_eq!(reset_descriptor_pool.unwrap()(device, fixed, 0), VkResult::VK_SUCCESS);
+
+        let flags = VkDescriptorPoolCreateFlagBits::VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT as u32;
+        let pool = create_descriptor_pool(instance, device, flags);
+        let sets = allocate_descriptor_sets(instance, device, pool, layout);
+        let fixed_sets = allocate_descriptor_sets(instance, device, fixed, layout);
+
+        // Sets of another pool are refused without freeing anything
+        let mixed = [sets[0], fixed_sets[0]];
+        assert_eq!(free_descriptor_sets.unwrap()(device, pool, 2, mixed.as_ptr()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+
+        let with_null = [sets[0], mem::zeroed(), sets[0]];
+        assert_eq!(free_descriptor_sets.unwrap()(device, pool, 3, with_null.as_ptr()), VkResult::VK_SUCCESS);
+        // Once freed, a set no longer belongs to the pool
+        assert_eq!(free_descriptor_sets.unwrap()(device, pool, 1, sets.as_ptr()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+        assert_eq!(free_descriptor_sets.unwrap()(device, pool, 1, &sets[1]), VkResult::VK_SUCCESS);
+
+        let sets = allocate_descriptor_sets(instance, device, pool, layout);
+        assert_eq!(reset_descriptor_pool.unwrap()(device, pool, 0), VkResult::VK_SUCCESS);
+        assert_eq!(free_descriptor_sets.unwrap()(device, pool, 2, sets.as_ptr()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+
+        let destroy_set_layout: PFN_vkDestroyDescriptorSetLayout = proc_addr(instance, "vkDestroyDescriptorSetLayout");
+        let destroy_device: PFN_vkDestroyDevice = proc_addr(instance, "vkDestroyDevice");
+        let destroy_instance: PFN_vkDestroyInstance = proc_addr(instance, "vkDestroyInstance");
+        destroy_descriptor_pool.unwrap()(device, pool, ptr::null());
+        destroy_descriptor_pool.unwrap()(device, fixed, ptr::null());
+        destroy_set_layout.unwrap()(device, layout, ptr::null());
+        destroy_device.unwrap()(device, ptr::null());
+        destroy_instance.unwrap()(instance, ptr::null());
+    }
+}
+
+unsafe fn create_image(instance: VkInstance, device: VkDevice) -> (VkImage, VkDeviceMemory) {
+    let create_image: PFN_vkCreateImage = proc_addr(instance, "vkCreateImage");
+    let info = VkImageCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: 0,
+        imageType: VkImageType::VK_IMAGE_TYPE_2D,
+        format: VkFormat::VK_FORMAT_R8G8B8A8_UNORM,
+        extent: VkExtent3D { width: 4, height: 4, depth: 1 },
+        mipLevels: 1,
+        arrayLayers: 1,
+        samples: VkSampleCountFlagBits::VK_SAMPLE_COUNT_1_BIT,
+        tiling: VkImageTiling::VK_IMAGE_TILING_OPTIMAL,
+        usage: VkImageUsageFlagBits::VK_IMAGE_USAGE_TRANSFER_DST_BIT as u32,
+        sharingMode: VkSharingMode::VK_SHARING_MODE_EXCLUSIVE,
+        queueFamilyIndexCount: 0,
+        pQueueFamilyIndices: ptr::null(),
+        initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
+    };
+    let mut image = mem::zeroed();
+    assert_eq!(create_image.unwrap()(device, &info, ptr::null(), &mut image), VkResult::VK_SUCCESS);
+
+    let get_requirements: PFN_vkGetImageMemoryRequirements = proc_addr(instance, "vkGetImageMemoryRequirements");
+    let mut requirements: VkMemoryRequirements = mem::zeroed();
+    get_requirements.unwrap()(device, image, &mut requirements);
+    let allocate_memory: PFN_vkAllocateMemory = proc_addr(instance, "vkAllocateMemory");
+    let allocate_info = VkMemoryAllocateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+        pNext: ptr::null(),
+        allocationSize: requirements.size,
+        memoryTypeIndex: 0,
+    };
+    let mut memory = mem::zeroed();
+    assert_eq!(allocate_memory.unwrap()(device, &allocate_info, ptr::null(), &mut memory), VkResult::VK_SUCCESS);
+    let bind_image_memory: PFN_vkBindImageMemory = proc_addr(instance, "vkBindImageMemory");
+    assert_eq!(bind_image_memory.unwrap()(device, image, memory, 0), VkResult::VK_SUCCESS);
+    (image, memory)
+}
+
+unsafe fn create_command_pool(instance: VkInstance, device: VkDevice, flags: VkCommandPoolCreateFlags) -> VkCommandPool {
+    let create_command_pool: PFN_vkCreateCommandPool = proc_addr(instance, "vkCreateCommandPool");
+    let info = VkCommandPoolCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: flags,
+        queueFamilyIndex: 0,
+    };
+    let mut pool = mem::zeroed();
+    assert_eq!(create_command_pool.unwrap()(device, &info, ptr::null(), &mut pool), VkResult::VK_SUCCESS);
+    pool
+}
+
+unsafe fn allocate_command_buffer(instance: VkInstance, device: VkDevice, pool: VkCommandPool, level: VkCommandBufferLevel) -> VkCommandBuffer {
+    let allocate_command_buffers: PFN_vkAllocateCommandBuffers = proc_addr(instance, "vkAllocateCommandBuffers");
+    let info = VkCommandBufferAllocateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO,
+        pNext: ptr::null(),
+        commandPool: pool,
+        level: level,
+        commandBufferCount: 1,
+    };
+    let mut command_buffer = ptr::null_mut();
+    assert_eq!(allocate_command_buffers.unwrap()(device, &info, &mut command_buffer), VkResult::VK_SUCCESS);
+    command_buffer
+}
+
+unsafe fn begin_command_buffer(instance: VkInstance, command_buffer: VkCommandBuffer, flags: VkCommandBufferUsageFlags) -> VkResult {
+    let begin: PFN_vkBeginCommandBuffer = proc_addr(instance, "vkBeginCommandBuffer");
+    let info = VkCommandBufferBeginInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+        pNext: ptr::null(),
+        flags: flags,
+        pInheritanceInfo: ptr::null(),
+    };
+    begin.unwrap()(command_buffer, &info)
+}
+
+unsafe fn submit(instance: VkInstance, queue: VkQueue, command_buffers: &[VkCommandBuffer], fence: VkFence) -> VkResult {
+    let submit: PFN_vkQueueSubmit = proc_addr(instance, "vkQueueSubmit");
+    let info = VkSubmitInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_SUBMIT_INFO,
+        pNext: ptr::null(),
+        waitSemaphoreCount: 0,
+        pWaitSemaphores: ptr::null(),
+        pWaitDstStageMask: ptr::null(),
+        commandBufferCount: command_buffers.len() as u32,
+        pCommandBuffers: command_buffers.as_ptr(),
+        signalSemaphoreCount: 0,
+        pSignalSemaphores: ptr::null(),
+    };
+    submit.unwrap()(queue, 1, &info, fence)
+}
+
+fn color_range() -> VkImageSubresourceRange {
+    VkImageSubresourceRange {
+        aspectMask: VkImageAspectFlagBits::VK_IMAGE_ASPECT_COLOR_BIT as u32,
+        baseMipLevel: 0,
+        levelCount: 1,
+        baseArrayLayer: 0,
+        layerCount: 1,
+    }
+}
+
+unsafe fn transition(instance: VkInstance, command_buffer: VkCommandBuffer, image: VkImage, old_layout: VkImageLayout, new_layout: VkImageLayout) {
+    let pipeline_barrier: PFN_vkCmdPipelineBarrier = proc_addr(instance, "vkCmdPipelineBarrier");
+    let barrier = VkImageMemoryBarrier {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+        pNext: ptr::null(),
+        srcAccessMask: VkAccessFlagBits::VK_ACCESS_TRANSFER_WRITE_BIT as u32,
+        dstAccessMask: VkAccessFlagBits::VK_ACCESS_TRANSFER_WRITE_BIT as u32,
+        oldLayout: old_layout,
+        newLayout: new_layout,
+        srcQueueFamilyIndex: !0,
+        dstQueueFamilyIndex: !0,
+        image: image,
+        subresourceRange: color_range(),
+    };
+    let transfer = VkPipelineStageFlagBits::VK_PIPELINE_STAGE_TRANSFER_BIT as u32;
+    pipeline_barrier.unwrap()(command_buffer, transfer, transfer, 0, 0, ptr::null(), 0, ptr::null(), 1, &barrier);
+}
+
+unsafe fn clear(instance: VkInstance, command_buffer: VkCommandBuffer, image: VkImage, layout: VkImageLayout) {
+    let clear_color_image: PFN_vkCmdClearColorImage = proc_addr(instance, "vkCmdClearColorImage");
+    let color: VkClearColorValue = mem::zeroed();
+    let range = color_range();
+    clear_color_image.unwrap()(command_buffer, image, layout, &color, 1, &range);
+}
+
+/// Validation only refuses invalid submissions, which no other test makes, but the configuration
+/// is shared by the tests running alongside, so every test that needs validation asks for all of
+/// it.
+fn configure_validation() {
+    vk_middle_null::configure(vk_middle_null::Config {
+        validate: true,
+        validate_synchronization: true,
+        ..vk_middle_null::Config::default()
+    });
+}
+
+#[test]
+fn submissions_using_images_in_the_wrong_layout_are_refused() {
+    use VkImageLayout::*;
+
+    configure_validation();
+
+    unsafe {
+        let (result, instance) = create_instance(&[]);
+        assert_eq!(result, VkResult::VK_SUCCESS);
+        let device = create_device(instance, physical_device(instance));
+        let get_queue: PFN_vkGetDeviceQueue = proc_addr(instance, "vkGetDeviceQueue");
+        let mut queue = ptr::null_mut();
+        get_queue.unwrap()(device, 0, 0, &mut queue);
+
+        let (image, memory) = create_image(instance, device);
+        let pool = create_command_pool(instance, device, 0);
+        let end: PFN_vkEndCommandBuffer = proc_addr(instance, "vkEndCommandBuffer");
+        let record = |commands: &Fn(VkCommandBuffer)| {
+            let command_buffer = allocate_command_buffer(instance, device, pool, VkCommandBufferLevel::VK_COMMAND_BUFFER_LEVEL_PRIMARY);
+            assert_eq!(begin_command_buffer(instance, command_buffer, 0), VkResult::VK_SUCCESS);
+            commands(command_buffer);
+            assert_eq!(end.unwrap()(command_buffer), VkResult::VK_SUCCESS);
+            command_buffer
+        };
+
+        let upload = record(&|x| {
+            transition(instance, x, image, VK_IMAGE_LAYOUT_UNDEFINED, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+            clear(instance, x, image, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+        });
+        let clear_general = record(&|x| clear(instance, x, image, VK_IMAGE_LAYOUT_GENERAL));
+        let to_general = record(&|x| transition(instance, x, image, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, VK_IMAGE_LAYOUT_GENERAL));
+
+        assert_eq!(submit(instance, queue, &[upload], mem::zeroed()), VkResult::VK_SUCCESS);
+        assert_eq!(submit(instance, queue, &[clear_general], mem::zeroed()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+        // The refused submission did not move the image into the layout it claimed.
+        assert_eq!(submit(instance, queue, &[to_general, clear_general], mem::zeroed()), VkResult::VK_SUCCESS);
+        assert_eq!(submit(instance, queue, &[to_general], mem::zeroed()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+
+        // A new image is tracked from scratch, even if it takes the place of a destroyed one.
+        let destroy_image: PFN_vkDestroyImage = proc_addr(instance, "vkDestroyImage");
+        let free_memory: PFN_vkFreeMemory = proc_addr(instance, "vkFreeMemory");
+        destroy_image.unwrap()(device, image, ptr::null());
+        free_memory.unwrap()(device, memory, ptr::null());
+        let (image, memory) = create_image(instance, device);
+        let upload = record(&|x| {
+            transition(instance, x, image, VK_IMAGE_LAYOUT_UNDEFINED, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+            clear(instance, x, image, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+        });
+        assert_eq!(submit(instance, queue, &[upload], mem::zeroed()), VkResult::VK_SUCCESS);
+
+        let destroy_command_pool: PFN_vkDestroyCommandPool = proc_addr(instance, "vkDestroyCommandPool");
+        let destroy_device: PFN_vkDestroyDevice = proc_addr(instance, "vkDestroyDevice");
+        let destroy_instance: PFN_vkDestroyInstance = proc_addr(instance, "vkDestroyInstance");
+        destroy_command_pool.unwrap()(device, pool, ptr::null());
+        destroy_image.unwrap()(device, image, ptr::null());
+        free_memory.unwrap()(device, memory, ptr::null());
+        destroy_device.unwrap()(device, ptr::null());
+        destroy_instance.unwrap()(instance, ptr::null());
+    }
+}
+
+unsafe fn create_buffer(instance: VkInstance, device: VkDevice) -> (VkBuffer, VkDeviceMemory) {
+    let create_buffer: PFN_vkCreateBuffer = proc_addr(instance, "vkCreateBuffer");
+    let info = VkBufferCreateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+        pNext: ptr::null(),
+        flags: 0,
+        size: 256,
+        usage: VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_SRC_BIT as u32 |
+            VkBufferUsageFlagBits::VK_BUFFER_USAGE_TRANSFER_DST_BIT as u32,
+        sharingMode: VkSharingMode::VK_SHARING_MODE_EXCLUSIVE,
+        queueFamilyIndexCount: 0,
+        pQueueFamilyIndices: ptr::null(),
+    };
+    let mut buffer = mem::zeroed();
+    assert_eq!(create_buffer.unwrap()(device, &info, ptr::null(), &mut buffer), VkResult::VK_SUCCESS);
+
+    let allocate_memory: PFN_vkAllocateMemory = proc_addr(instance, "vkAllocateMemory");
+    let allocate_info = VkMemoryAllocateInfo {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+        pNext: ptr::null(),
+        allocationSize: 256,
+        memoryTypeIndex: 0,
+    };
+    let mut memory = mem::zeroed();
+    assert_eq!(allocate_memory.unwrap()(device, &allocate_info, ptr::null(), &mut memory), VkResult::VK_SUCCESS);
+    let bind_buffer_memory: PFN_vkBindBufferMemory = proc_addr(instance, "vkBindBufferMemory");
+    assert_eq!(bind_buffer_memory.unwrap()(device, buffer, memory, 0), VkResult::VK_SUCCESS);
+    (buffer, memory)
+}
+
+/// Makes transfer writes before the barrier visible to transfer reads after it.
+unsafe fn transfer_barrier(instance: VkInstance, command_buffer: VkCommandBuffer) {
+    let pipeline_barrier: PFN_vkCmdPipelineBarrier = proc_addr(instance, "vkCmdPipelineBarrier");
+    let barrier = VkMemoryBarrier {
+        sType: VkStructureType::VK_STRUCTURE_TYPE_MEMORY_BARRIER,
+        pNext: ptr::null(),
+        srcAccessMask: VkAccessFlagBits::VK_ACCESS_TRANSFER_WRITE_BIT as u32,
+        dstAccessMask: VkAccessFlagBits::VK_ACCESS_TRANSFER_READ_BIT as u32,
+    };
+    let transfer = VkPipelineStageFlagBits::VK_PIPELINE_STAGE_TRANSFER_BIT as u32;
+    pipeline_barrier.unwrap()(command_buffer, transfer, transfer, 0, 1, &barrier, 0, ptr::null(), 0, ptr::null());
+}
+
+#[test]
+fn submissions_with_hazards_are_refused() {
+    configure_validation();
+
+    unsafe {
+        let (result, instance) = create_instance(&[]);
+        assert_eq!(result, VkResult::VK_SUCCESS);
+        let device = create_device(instance, physical_device(instance));
+        let get_queue: PFN_vkGetDeviceQueue = proc_addr(instance, "vkGetDeviceQueue");
+        let mut queue = ptr::null_mut();
+        get_queue.unwrap()(device, 0, 0, &mut queue);
+
+        let (src, src_memory) = create_buffer(instance, device);
+        let (dst, dst_memory) = create_buffer(instance, device);
+        let pool = create_command_pool(instance, device, 0);
+        let end: PFN_vkEndCommandBuffer = proc_addr(instance, "vkEndCommandBuffer");
+        let fill_buffer: PFN_vkCmdFillBuffer = proc_addr(instance, "vkCmdFillBuffer");
+        let copy_buffer: PFN_vkCmdCopyBuffer = proc_addr(instance, "vkCmdCopyBuffer");
+        let region = VkBufferCopy { srcOffset: 0, dstOffset: 0, size: 256 };
+        let record = |commands: &Fn(VkCommandBuffer)| {
+            let command_buffer = allocate_command_buffer(instance, device, pool, VkCommandBufferLevel::VK_COMMAND_BUFFER_LEVEL_PRIMARY);
+            assert_eq!(begin_command_buffer(instance, command_buffer, 0), VkResult::VK_SUCCESS);
+            commands(command_buffer);
+            assert_eq!(end.unwrap()(command_buffer), VkResult::VK_SUCCESS);
+            command_buffer
+        };
+
+        let fill = record(&|x| fill_buffer.unwrap()(x, src, 0, !0, 1));
+        let copy = record(&|x| copy_buffer.unwrap()(x, src, dst, 1, &region));
+        let ordered_copy = record(&|x| {
+            transfer_barrier(instance, x);
+            copy_buffer.unwrap()(x, src, dst, 1, &region);
+        });
+        let fill_and_copy = record(&|x| {
+            fill_buffer.unwrap()(x, src, 0, !0, 2);
+            copy_buffer.unwrap()(x, src, dst, 1, &region);
+        });
+
+        assert_eq!(submit(instance, queue, &[fill], mem::zeroed()), VkResult::VK_SUCCESS);
+        // Waiting for the queue orders nothing on the device.
+        let queue_wait_idle: PFN_vkQueueWaitIdle = proc_addr(instance, "vkQueueWaitIdle");
+        assert_eq!(queue_wait_idle.unwrap()(queue), VkResult::VK_SUCCESS);
+        assert_eq!(submit(instance, queue, &[copy], mem::zeroed()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+        assert_eq!(submit(instance, queue, &[ordered_copy], mem::zeroed()), VkResult::VK_SUCCESS);
+        // Hazards within a command buffer count as well.
+        assert_eq!(submit(instance, queue, &[fill_and_copy], mem::zeroed()), VkResult::VK_ERROR_VALIDATION_FAILED_EXT);
+
+        let destroy_command_pool: PFN_vkDestroyCommandPool = proc_addr(instance, "vkDestroyCommandPool");
+        let destroy_buffer: PFN_vkDestroyBuffer = proc_addr(instance, "vkDestroyBuffer");
+        let free_memory: PFN_vkFreeMemory = proc_addr(instance, "vkFreeMemory");
+        let destroy_device: PFN_vkDestroyDevice = proc_addr(instance, "vkDestroyDevice");
+        let destroy_instance: PFN_vkDestroyInstance = proc_addr(instance, "vkDestroyInstance");
+        destroy_command_pool.unwrap()(device, pool, ptr::null());
+        destroy_buffer.unwrap()(device, src, ptr::null());
+        destroy_buffer.unwrap()(device, dst, ptr::null());
+        free_memory.unwrap()(device, src_memory, ptr::null());
+        free_memory.unwrap()(device, dst_memory, ptr::null());
+        destroy_device.unwrap()(device, ptr::null());
+        destroy_instance.unwrap()(instance, ptr::null());
+    }
+}